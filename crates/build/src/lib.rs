@@ -5,10 +5,11 @@ use directories::ProjectDirs;
 use lazy_static::lazy_static;
 use std::{
     env,
-    fs::File,
+    fs::{self, File},
     io::Write,
     path::{Path, PathBuf},
 };
+use toml_edit::{DocumentMut, Entry, Item, Table, TableLike};
 
 pub const PROJECT_NAME: &str = "BPM_DETECTION";
 
@@ -101,6 +102,124 @@ pub fn create_build_info() {
     let now = chrono::Local::now();
     let formatted_time = format!("{}", now.format("%Y-%m-%d %H:%M:%S"));
 
+    let profile = env::var("PROFILE").unwrap_or_default();
+
     let mut f = File::create(dest_path).unwrap();
-    write!(f, "pub const BUILD_TIME: &str = \"{formatted_time}\";").unwrap();
+    writeln!(f, "pub const BUILD_TIME: &str = \"{formatted_time}\";").unwrap();
+    writeln!(f, "pub const BUILD_PROFILE: &str = \"{profile}\";").unwrap();
+}
+
+/// Writes `base_config.toml` into `OUT_DIR`, for the crate to embed with
+/// `include_str!(concat!(env!("OUT_DIR"), "/base_config.toml"))`. It is `config/base_config.toml` at the root of the
+/// workspace, the settings all frontends share, merged with `overlay`, the settings of the crate: a table of the
+/// overlay adds its keys to the shared table of the same name, recursively, replacing the values it has too
+pub fn create_base_config(overlay: impl AsRef<Path>) {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let shared_path = manifest_dir.join("../../config/base_config.toml");
+    let overlay_path = manifest_dir.join(overlay);
+    println!("cargo:rerun-if-changed={}", shared_path.display());
+    println!("cargo:rerun-if-changed={}", overlay_path.display());
+
+    let shared = fs::read_to_string(&shared_path).unwrap();
+    let overlay = fs::read_to_string(&overlay_path).unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("base_config.toml"), merge_config(&shared, &overlay)).unwrap();
+}
+
+// the tables of the overlay that the shared file doesn't have are written after all of the shared ones
+fn shift_positions(table: &mut Table, offset: usize) {
+    if let Some(position) = table.position() {
+        table.set_position(position + offset);
+    }
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(table) => shift_positions(table, offset),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(|table| shift_positions(table, offset)),
+            Item::None | Item::Value(_) => (),
+        }
+    }
+}
+
+fn last_position(table: &Table) -> usize {
+    let nested = table.iter().map(|(_, item)| match item {
+        Item::Table(table) => last_position(table),
+        Item::ArrayOfTables(array) => array.iter().map(last_position).max().unwrap_or_default(),
+        Item::None | Item::Value(_) => 0,
+    });
+    nested.chain(table.position()).max().unwrap_or_default()
+}
+
+// tables, inline or not, are merged key by key, any other value of the overlay replaces the shared one. Keys keep the
+// comments above them from the file that first has them
+fn merge_tables(shared: &mut dyn TableLike, overlay: &dyn TableLike) {
+    for (key, item) in overlay.iter() {
+        let overlay_key = overlay.key(key).unwrap();
+        match shared.entry_format(overlay_key) {
+            Entry::Vacant(entry) => {
+                entry.insert(item.clone());
+            }
+            Entry::Occupied(mut entry) => match (entry.get().is_table_like(), item.as_table_like()) {
+                (true, Some(overlay)) => merge_tables(entry.get_mut().as_table_like_mut().unwrap(), overlay),
+                _ => {
+                    entry.insert(item.clone());
+                }
+            },
+        }
+    }
+}
+
+fn merge_config(shared: &str, overlay: &str) -> String {
+    let mut merged = shared.parse::<DocumentMut>().unwrap();
+    let mut overlay = overlay.parse::<DocumentMut>().unwrap();
+    shift_positions(overlay.as_table_mut(), last_position(merged.as_table()) + 1);
+    merge_tables(merged.as_table_mut(), overlay.as_table());
+
+    format!(
+        "# generated by the build script from config/base_config.toml at the root of the workspace and the\n# \
+         config/overlay.toml of the crate, edit those instead\n\n{merged}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_config() {
+        let shared =
+            "# shared\n\n[GUI]\ncurve = 0.8\n\n# how long\n[GUI.duration]\nsecs = 1\n\n[detection]\nrange = 70\n";
+        let overlay =
+            "send_tempo = true\n\n[GUI]\nscale = 2.0\n\n[GUI.duration]\nsecs = 2\n\n# bindings\n[keys]\nq = \"Quit\"\n";
+        let merged = merge_config(shared, overlay);
+        let expected = "send_tempo = true\n# shared\n\n[GUI]\ncurve = 0.8\nscale = 2.0\n\n# how \
+                        long\n[GUI.duration]\nsecs = 2\n\n[detection]\nrange = 70\n\n# bindings\n[keys]\nq = \
+                        \"Quit\"\n";
+        assert_eq!(merged.split_once("\n\n").unwrap().1, expected);
+    }
+
+    #[test]
+    fn test_merge_multiline_values() {
+        let shared = "[detection]\nfold_ratios = [\n    2.0,\n    3.0,\n]\nnote = \
+                      \"\"\"\nkick\n[snare]\n\"\"\"\nrange = { low = 60, high = 180 }\n";
+        let overlay = "[detection]\nfold_ratios = [\n    1.5,\n]\nrange = { high = 200 }\n";
+        let merged: toml_edit::DocumentMut = merge_config(shared, overlay).parse().unwrap();
+        let detection = &merged["detection"];
+        let fold_ratios = detection["fold_ratios"].as_array().unwrap();
+        assert_eq!(fold_ratios.iter().filter_map(toml_edit::Value::as_float).collect::<Vec<_>>(), [1.5]);
+        assert_eq!(detection["note"].as_str(), Some("kick\n[snare]\n"));
+        assert_eq!(detection["range"]["low"].as_integer(), Some(60));
+        assert_eq!(detection["range"]["high"].as_integer(), Some(200));
+        assert!(merged.get("snare").is_none());
+    }
+
+    #[test]
+    fn test_merge_nested_tables() {
+        let shared = "[detection]\nrange = 70\n\n[detection.weight]\nenabled = false\nvalue = 0.5\n";
+        let overlay = "detection.weight.value = 0.7\n\n[detection.age]\nenabled = true\n";
+        let merged: toml_edit::DocumentMut = merge_config(shared, overlay).parse().unwrap();
+        assert_eq!(merged["detection"]["range"].as_integer(), Some(70));
+        assert_eq!(merged["detection"]["weight"]["enabled"].as_bool(), Some(false));
+        assert_eq!(merged["detection"]["weight"]["value"].as_float(), Some(0.7));
+        assert_eq!(merged["detection"]["age"]["enabled"].as_bool(), Some(true));
+    }
 }
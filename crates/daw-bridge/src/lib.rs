@@ -0,0 +1,210 @@
+#![allow(forbidden_lint_groups)]
+#![allow(clippy::missing_errors_doc)]
+
+//! Other end of the DAW link, see `midi::daw_link_protocol` and the README of this crate for the frames
+
+use errors::{MakeReportExt, Report, Result};
+use midi::{DawMessage, DawMessageReader};
+use std::{
+    io::{self, Read},
+    net::{TcpListener, ToSocketAddrs, UdpSocket},
+};
+
+/// Where the messages received are passed on to
+pub trait Output {
+    fn send(&mut self, message: DawMessage) -> Result<()>;
+}
+
+/// Prints the messages to the standard output
+pub struct Print;
+
+impl Output for Print {
+    fn send(&mut self, message: DawMessage) -> Result<()> {
+        println!("{message:?}");
+        Ok(())
+    }
+}
+
+/// Same SysEx as the standalone application sends through its virtual output, see `midi::SysExCommand`
+#[must_use]
+pub fn sysex(message: DawMessage) -> Vec<u8> {
+    let command = match message {
+        DawMessage::Tempo(bpm) => format!("TEMPO|{bpm}"),
+        DawMessage::Play => "PLAY".to_string(),
+        DawMessage::Stop => "STOP".to_string(),
+        DawMessage::Continue => "CONTINUE".to_string(),
+    };
+    [0xF0].into_iter().chain(command.into_bytes()).chain([0xF7]).collect()
+}
+
+pub struct SysExOutput {
+    connection: midir::MidiOutputConnection,
+}
+
+impl SysExOutput {
+    /// Connects to the first MIDI output whose name contains `port_name`
+    pub fn connect(port_name: &str) -> Result<Self> {
+        let midi_output = midir::MidiOutput::new(clap::crate_name!())?;
+        let port = midi_output
+            .ports()
+            .into_iter()
+            .find(|port| midi_output.port_name(port).is_ok_and(|name| name.contains(port_name)))
+            .ok_or_else(|| Report::msg(format!("no MIDI output named like {port_name}")))?;
+        let connection =
+            midi_output.connect(&port, clap::crate_name!()).report_msg("could not connect to MIDI output")?;
+        Ok(Self { connection })
+    }
+}
+
+impl Output for SysExOutput {
+    fn send(&mut self, message: DawMessage) -> Result<()> {
+        Ok(self.connection.send(&sysex(message))?)
+    }
+}
+
+// strings are terminated by at least one null, up to a multiple of 4 bytes
+fn push_osc_string(packet: &mut Vec<u8>, string: &str) {
+    packet.extend_from_slice(string.as_bytes());
+    packet.resize((packet.len() / 4 + 1) * 4, 0);
+}
+
+/// OSC message to `/tempo` with the tempo as an f32 argument, or to `/play`, `/stop` or `/continue` without argument
+#[must_use]
+pub fn osc(message: DawMessage) -> Vec<u8> {
+    let (address, bpm) = match message {
+        DawMessage::Tempo(bpm) => ("/tempo", Some(bpm)),
+        DawMessage::Play => ("/play", None),
+        DawMessage::Stop => ("/stop", None),
+        DawMessage::Continue => ("/continue", None),
+    };
+    let mut packet = Vec::new();
+    push_osc_string(&mut packet, address);
+    push_osc_string(&mut packet, if bpm.is_some() { ",f" } else { "," });
+    if let Some(bpm) = bpm {
+        packet.extend_from_slice(&bpm.to_be_bytes());
+    }
+    packet
+}
+
+/// Sends OSC over UDP
+pub struct OscOutput {
+    socket: UdpSocket,
+}
+
+impl OscOutput {
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+        Ok(Self { socket })
+    }
+}
+
+impl Output for OscOutput {
+    fn send(&mut self, message: DawMessage) -> Result<()> {
+        self.socket.send(&osc(message))?;
+        Ok(())
+    }
+}
+
+/// Passes on the messages of a connection until it is closed. An output failing doesn't hold up the other ones
+pub fn relay(connection: impl Read, outputs: &mut [Box<dyn Output>]) -> io::Result<()> {
+    for message in DawMessageReader::new(connection) {
+        let Some(message) = message? else {
+            eprintln!("skipped a message of a type this version doesn't know");
+            continue;
+        };
+        for output in &mut *outputs {
+            if let Err(err) = output.send(message) {
+                eprintln!("could not pass on {message:?}: {err:?}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Relays one connection after the other, the application connects again when its DAW port changes
+pub fn serve(listener: &TcpListener, outputs: &mut [Box<dyn Output>]) -> Result<()> {
+    for connection in listener.incoming() {
+        let connection = connection?;
+        let peer = connection.peer_addr()?;
+        eprintln!("connected to {peer}");
+        match relay(connection, outputs) {
+            Ok(()) => eprintln!("{peer} disconnected"),
+            Err(err) => eprintln!("connection to {peer} closed: {err}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi::{DawLink, MidiMessage, SysExCommand};
+    use std::{
+        net::{Ipv4Addr, TcpStream},
+        sync::mpsc::{channel, Sender},
+        thread,
+    };
+
+    impl Output for Sender<DawMessage> {
+        fn send(&mut self, message: DawMessage) -> Result<()> {
+            Ok(Sender::send(self, message)?)
+        }
+    }
+
+    #[test]
+    fn test_loopback() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let daw_port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = channel();
+        let bridge = thread::spawn(move || {
+            let (connection, _) = listener.accept().unwrap();
+            relay(connection, &mut [Box::new(sender) as Box<dyn Output>])
+        });
+
+        // the same link the plugin and the standalone application send through
+        let mut daw_link = DawLink::connected(TcpStream::connect((Ipv4Addr::LOCALHOST, daw_port)).unwrap());
+        let messages = [
+            DawMessage::Tempo(120.0),
+            DawMessage::Play,
+            DawMessage::Tempo(87.25),
+            DawMessage::Stop,
+            DawMessage::Continue,
+            DawMessage::Tempo(0.1),
+            DawMessage::Tempo(1000.0),
+        ];
+        for message in messages {
+            assert!(daw_link.send(message));
+        }
+        drop(daw_link);
+
+        bridge.join().unwrap().unwrap();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), messages);
+    }
+
+    #[test]
+    fn test_sysex() {
+        // read back as the standalone application reads its SysEx input
+        let command =
+            |message| SysExCommand::try_from(&MidiMessage::try_from(sysex(message).as_slice()).unwrap().to_owned());
+
+        assert_eq!(sysex(DawMessage::Tempo(93.5)), b"\xF0TEMPO|93.5\xF7");
+        assert!(
+            matches!(command(DawMessage::Tempo(93.5)), Ok(SysExCommand::Tempo(bpm)) if (bpm - 93.5).abs() < f32::EPSILON)
+        );
+        assert!(matches!(command(DawMessage::Play), Ok(SysExCommand::Play)));
+        assert!(matches!(command(DawMessage::Stop), Ok(SysExCommand::Stop)));
+        assert!(matches!(command(DawMessage::Continue), Ok(SysExCommand::Continue)));
+    }
+
+    #[test]
+    fn test_osc() {
+        assert_eq!(osc(DawMessage::Tempo(120.0)), b"/tempo\0\0,f\0\0\x42\xF0\0\0");
+        assert_eq!(osc(DawMessage::Play), b"/play\0\0\0,\0\0\0");
+        assert_eq!(osc(DawMessage::Stop), b"/stop\0\0\0,\0\0\0");
+        assert_eq!(osc(DawMessage::Continue), b"/continue\0\0\0,\0\0\0");
+        for message in [DawMessage::Tempo(1.0), DawMessage::Play, DawMessage::Continue] {
+            assert_eq!(osc(message).len() % 4, 0);
+        }
+    }
+}
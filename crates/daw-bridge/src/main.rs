@@ -0,0 +1,47 @@
+use clap::{value_parser, Arg, Command};
+use daw_bridge::{serve, OscOutput, Output, Print, SysExOutput};
+use errors::Result;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+fn main() -> Result<()> {
+    let matches = Command::new(clap::crate_name!())
+        .author(clap::crate_authors!())
+        .version(clap::crate_version!())
+        .about(clap::crate_description!())
+        .arg(
+            Arg::new("port")
+                .value_parser(value_parser!(u16))
+                .short('p')
+                .long("port")
+                .value_name("PORT")
+                .help("Port to listen to on localhost, the DAW port the detector connects to")
+                .default_value("9000"),
+        )
+        .arg(
+            Arg::new("sysex")
+                .long("sysex")
+                .value_name("MIDI OUTPUT")
+                .help("Also send the messages as SysEx to the first MIDI output whose name contains this"),
+        )
+        .arg(
+            Arg::new("osc")
+                .value_parser(value_parser!(SocketAddr))
+                .long("osc")
+                .value_name("HOST:PORT")
+                .help("Also send the messages as OSC over UDP to this address"),
+        )
+        .get_matches();
+
+    let mut outputs: Vec<Box<dyn Output>> = vec![Box::new(Print)];
+    if let Some(port_name) = matches.get_one::<String>("sysex") {
+        outputs.push(Box::new(SysExOutput::connect(port_name)?));
+    }
+    if let Some(address) = matches.get_one::<SocketAddr>("osc") {
+        outputs.push(Box::new(OscOutput::connect(address)?));
+    }
+
+    let port = *matches.get_one::<u16>("port").expect("has a default value");
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    serve(&listener, &mut outputs)
+}
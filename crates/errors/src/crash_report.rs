@@ -0,0 +1,244 @@
+use crate::Backtrace;
+use build::{get_data_dir, LOG_FILE, PROJECT_NAME};
+use log::error;
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, PoisonError, TryLockError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Log lines included in the crash report
+pub const CRASH_LOG_LINES: usize = 200;
+// read from the end of the log file when the logger of this process keeps no lines, enough for `CRASH_LOG_LINES`
+const LOG_TAIL_BYTES: u64 = 256 * 1024;
+
+type SectionSource = Box<dyn Fn() -> String + Send + Sync>;
+
+// std mutexes, as they can be created in a static on every target. They are only tried from the panic hook: the
+// panicking thread may be the one holding them
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static SECTIONS: Mutex<Vec<(u64, String, SectionSource)>> = Mutex::new(Vec::new());
+static NEXT_SECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps the line among the last `CRASH_LOG_LINES` of the log
+pub(crate) fn record_log_line(line: String) {
+    let mut log_lines = LOG_LINES.lock().unwrap_or_else(PoisonError::into_inner);
+    if log_lines.len() == CRASH_LOG_LINES {
+        log_lines.pop_front();
+    }
+    log_lines.push_back(line);
+}
+
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Section of the crash report, until it is dropped. See `register_crash_section`
+#[must_use]
+pub struct CrashSection(u64);
+
+impl Drop for CrashSection {
+    fn drop(&mut self) {
+        SECTIONS.lock().unwrap_or_else(PoisonError::into_inner).retain(|(id, _, _)| *id != self.0);
+    }
+}
+
+/// Adds a section titled `title` to the crash reports written until the returned guard is dropped. `source` is called
+/// from the panic hook, possibly on the thread that panicked while holding a lock: it must only try locks, and must
+/// not panic, as a panic in the hook aborts the process
+pub fn register_crash_section(
+    title: impl Into<String>,
+    source: impl Fn() -> String + Send + Sync + 'static,
+) -> CrashSection {
+    let id = NEXT_SECTION_ID.fetch_add(1, Ordering::Relaxed);
+    SECTIONS.lock().unwrap_or_else(PoisonError::into_inner).push((id, title.into(), Box::new(source)));
+    CrashSection(id)
+}
+
+/// Where the crash report takes its content from, see `ProcessCrashSources`
+pub trait CrashSources {
+    /// The last `count` lines of the log, oldest first
+    fn recent_log_lines(&self, count: usize) -> Vec<String>;
+    /// Titles and contents of the sections following the log
+    fn sections(&self) -> Vec<(String, String)>;
+}
+
+/// Lines kept by the logger of this process, or the end of the log file, and the registered sections
+pub struct ProcessCrashSources {
+    pub log_file: PathBuf,
+}
+
+impl Default for ProcessCrashSources {
+    fn default() -> Self {
+        Self { log_file: get_data_dir().join(LOG_FILE.as_str()) }
+    }
+}
+
+impl CrashSources for ProcessCrashSources {
+    fn recent_log_lines(&self, count: usize) -> Vec<String> {
+        match try_lock(&LOG_LINES) {
+            Some(log_lines) if !log_lines.is_empty() => {
+                log_lines.iter().skip(log_lines.len().saturating_sub(count)).cloned().collect()
+            }
+            _ => tail_lines(&self.log_file, count).unwrap_or_default(),
+        }
+    }
+
+    fn sections(&self) -> Vec<(String, String)> {
+        let Some(sections) = try_lock(&SECTIONS) else {
+            return vec![("sections".to_string(), "unavailable, they were being registered".to_string())];
+        };
+        sections.iter().map(|(_, title, source)| (title.clone(), source())).collect()
+    }
+}
+
+/// The last `count` lines of the file at `path`, read from its last `LOG_TAIL_BYTES`
+fn tail_lines(path: &Path, count: usize) -> io::Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let start = file.metadata()?.len().saturating_sub(LOG_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let content = String::from_utf8_lossy(&content);
+    // the first line is cut unless the file was read from its start
+    let lines = content.lines().skip(usize::from(start > 0)).collect::<Vec<_>>();
+    Ok(lines[lines.len().saturating_sub(count)..].iter().map(ToString::to_string).collect())
+}
+
+/// Text of the crash report: the panic, its backtrace, the recent log lines and the sections of `sources`
+#[must_use]
+pub fn assemble_crash_bundle(panic_message: &str, backtrace: &str, sources: &impl CrashSources) -> String {
+    let mut bundle = format!("{PROJECT_NAME} crash report\n\n== panic ==\n{}\n", panic_message.trim_end());
+    write!(bundle, "\n== backtrace ==\n{}\n", backtrace.trim_end()).ok();
+
+    let log_lines = sources.recent_log_lines(CRASH_LOG_LINES);
+    write!(bundle, "\n== last {} log lines ==\n", log_lines.len()).ok();
+    for line in log_lines {
+        writeln!(bundle, "{}", line.trim_end()).ok();
+    }
+
+    for (title, content) in sources.sections() {
+        write!(bundle, "\n== {title} ==\n{}\n", content.trim_end()).ok();
+    }
+    bundle
+}
+
+/// Writes `bundle` to a file named after the time and the process in `directory`, and returns its path
+pub fn write_crash_bundle(directory: &Path, bundle: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(directory)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let path = directory.join(format!("crash-{timestamp}-{}.txt", std::process::id()));
+    fs::write(&path, bundle)?;
+    Ok(path)
+}
+
+/// Writes the crash report of the current panic in the data directory, none when it could not be written
+#[must_use]
+pub fn write_crash_report(panic_message: &str) -> Option<PathBuf> {
+    let backtrace = format!("{:?}", Backtrace::new());
+    let bundle = assemble_crash_bundle(panic_message, &backtrace, &ProcessCrashSources::default());
+    write_crash_bundle(&get_data_dir(), &bundle).ok()
+}
+
+/// Writes the crash report of a panic caught by `catch_unwind`, for a library loaded by a host, such as the plugin,
+/// which can't leave a panic hook behind once unloaded. The backtrace is the one of the caller
+pub fn report_caught_panic(payload: &(dyn Any + Send)) -> Option<PathBuf> {
+    let path = write_crash_report(panic_message(payload))?;
+    error!("crash report written to {}", path.display());
+    Some(path)
+}
+
+// `panic!` payloads are a `&str` without arguments, a `String` with
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("panicked without a message")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeSources {
+        log_lines: Vec<String>,
+        sections: Vec<(String, String)>,
+    }
+
+    impl CrashSources for FakeSources {
+        fn recent_log_lines(&self, count: usize) -> Vec<String> {
+            self.log_lines.iter().skip(self.log_lines.len().saturating_sub(count)).cloned().collect()
+        }
+
+        fn sections(&self) -> Vec<(String, String)> {
+            self.sections.clone()
+        }
+    }
+
+    #[test]
+    fn test_assemble_crash_bundle() {
+        let sources = FakeSources {
+            log_lines: (0..300).map(|index| format!("line {index}\n")).collect(),
+            sections: vec![
+                ("configuration".to_string(), "bpm_center = 90.0\n".to_string()),
+                ("diagnostics".to_string(), "rate limited notes: 3".to_string()),
+            ],
+        };
+        let bundle = assemble_crash_bundle("panicked at 'oops'\n", "0: main", &sources);
+
+        assert!(bundle.starts_with(&format!("{PROJECT_NAME} crash report\n\n== panic ==\npanicked at 'oops'\n\n")));
+        assert!(bundle.contains("\n== backtrace ==\n0: main\n"));
+        assert!(bundle.contains("\n== last 200 log lines ==\nline 100\nline 101\n"));
+        assert!(!bundle.contains("line 99\n"));
+        assert!(bundle.contains("line 299\n\n== configuration ==\nbpm_center = 90.0\n\n== diagnostics ==\n"));
+        assert!(bundle.ends_with("rate limited notes: 3\n"));
+
+        let empty = FakeSources { log_lines: Vec::new(), sections: Vec::new() };
+        assert!(assemble_crash_bundle("oops", "", &empty).ends_with("== last 0 log lines ==\n"));
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("oops {}", 1)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "oops 1");
+        let payload = std::panic::catch_unwind(|| panic!("oops")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "oops");
+        assert_eq!(panic_message(&1), "panicked without a message");
+    }
+
+    #[test]
+    fn test_log_file_and_sections() {
+        let directory = std::env::temp_dir().join(format!("crash_report_test_{}", std::process::id()));
+        let log_file = directory.join("test.log");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&log_file, (0..10).map(|index| format!("line {index}")).collect::<Vec<_>>().join("\n")).unwrap();
+        assert_eq!(tail_lines(&log_file, 3).unwrap(), ["line 7", "line 8", "line 9"]);
+        assert_eq!(tail_lines(&log_file, 20).unwrap().len(), 10);
+
+        // the logger of the test process keeps no lines, the file is read instead
+        let sources = ProcessCrashSources { log_file };
+        let section = register_crash_section("test section", || "content".to_string());
+        assert!(sources.sections().contains(&("test section".to_string(), "content".to_string())));
+        drop(section);
+        assert!(!sources.sections().iter().any(|(title, _)| title == "test section"));
+
+        let bundle = assemble_crash_bundle("oops", "", &sources);
+        let path = write_crash_bundle(&directory, &bundle).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), bundle);
+        assert!(bundle.contains("line 0\n"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
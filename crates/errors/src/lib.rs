@@ -3,11 +3,18 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::module_name_repetitions)]
 
+mod crash_report;
+mod log_elevation;
 mod logging;
 mod panic_handler;
 pub use backtrace::Backtrace;
 pub use color_eyre::{eyre::WrapErr, Context, Report, Result};
+pub use crash_report::{
+    assemble_crash_bundle, register_crash_section, report_caught_panic, write_crash_bundle, write_crash_report,
+    CrashSection, CrashSources, ProcessCrashSources, CRASH_LOG_LINES,
+};
 pub use log::{debug, error, info, LevelFilter};
+pub use log_elevation::{elevate_logging, error_debug_window, ERROR_DEBUG_WINDOW_ENV};
 pub use logging::{
     initialize_logging, LogDerefWithExt, LogErrorExt, LogErrorWithExt, LogOptionWithExt, MakeReportExt,
     WORKSPACE_CRATES,
@@ -0,0 +1,232 @@
+use env_logger::Logger;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// Seconds the workspace crates log at debug level after an error, 0 disables it
+pub const ERROR_DEBUG_WINDOW_ENV: &str = "BPM_DETECTION_ERROR_DEBUG_WINDOW";
+const DEFAULT_ERROR_DEBUG_WINDOW: Duration = Duration::from_secs(30);
+
+const NOT_ELEVATED: u64 = 0;
+
+// none until the logging is initialized
+static LOG_ELEVATION: OnceLock<LogElevation> = OnceLock::new();
+
+/// Deadline until which the workspace crates log at debug level, whatever the configured filter
+pub(crate) struct LogElevation {
+    origin: Instant,
+    // `NOT_ELEVATED`, or microseconds from origin to the deadline plus one
+    deadline: AtomicU64,
+    // of the configured filter, restored once the deadline passed
+    configured_max_level: LevelFilter,
+    error_debug_window: Duration,
+}
+
+impl LogElevation {
+    pub(crate) fn new(configured_max_level: LevelFilter, error_debug_window: Duration) -> Self {
+        Self {
+            origin: Instant::now(),
+            deadline: AtomicU64::new(NOT_ELEVATED),
+            configured_max_level,
+            error_debug_window,
+        }
+    }
+
+    /// Debug level until `duration` after `now` at least, a window that ends later is kept
+    pub(crate) fn elevate(&self, now: Instant, duration: Duration) {
+        let deadline = now
+            .checked_add(duration)
+            .map_or(u64::MAX, |deadline| micros(deadline.saturating_duration_since(self.origin)) + 1);
+        self.deadline.fetch_max(deadline, Ordering::Relaxed);
+        log::set_max_level(self.configured_max_level.max(LevelFilter::Debug));
+    }
+
+    /// Single load while not elevated, the clock is only read during a window. The first check after the deadline
+    /// ends the window
+    pub(crate) fn is_elevated(&self, now: impl FnOnce() -> Instant) -> bool {
+        let deadline = self.deadline.load(Ordering::Relaxed);
+        if deadline == NOT_ELEVATED {
+            return false;
+        }
+        if micros(now().saturating_duration_since(self.origin)) < deadline - 1 {
+            return true;
+        }
+        // unless it was elevated again in between
+        if self.deadline.compare_exchange(deadline, NOT_ELEVATED, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            log::set_max_level(self.configured_max_level);
+        }
+        false
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX - 1)
+}
+
+// whether a record is written: when the configured filter lets it through, and while elevated when it is at debug level
+// or above and comes from a workspace crate
+fn is_written(
+    configured: bool,
+    elevated: impl FnOnce() -> bool,
+    level: Level,
+    target: &str,
+    workspace_crates: &[String],
+) -> bool {
+    configured || (level <= Level::Debug && elevated() && is_workspace_target(target, workspace_crates))
+}
+
+fn is_workspace_target(target: &str, workspace_crates: &[String]) -> bool {
+    workspace_crates.iter().any(|crate_name| {
+        target.strip_prefix(crate_name.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+fn parse_error_debug_window(seconds: Option<&str>) -> Duration {
+    seconds
+        .and_then(|seconds| seconds.trim().parse::<f64>().ok())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .unwrap_or(DEFAULT_ERROR_DEBUG_WINDOW)
+}
+
+/// Logger of the configured filter, raising the workspace crates to debug level for a while after an error. The
+/// records only the elevation lets through are written by `elevated`, which has the same output
+pub(crate) struct ElevatingLogger {
+    configured: Logger,
+    elevated: Logger,
+    // as they appear in the targets, with underscores
+    workspace_crates: Vec<String>,
+    log_elevation: &'static LogElevation,
+}
+
+impl ElevatingLogger {
+    /// Installs the logger, the window after an error is read from `ERROR_DEBUG_WINDOW_ENV`
+    pub(crate) fn init(
+        configured: Logger,
+        elevated: Logger,
+        workspace_crates: &str,
+    ) -> Result<(), log::SetLoggerError> {
+        let configured_max_level = configured.filter();
+        let error_debug_window = parse_error_debug_window(std::env::var(ERROR_DEBUG_WINDOW_ENV).ok().as_deref());
+        let log_elevation = LOG_ELEVATION.get_or_init(|| LogElevation::new(configured_max_level, error_debug_window));
+        log::set_boxed_logger(Box::new(Self {
+            configured,
+            elevated,
+            workspace_crates: workspace_crates.split(',').map(|crate_name| crate_name.replace('-', "_")).collect(),
+            log_elevation,
+        }))?;
+        log::set_max_level(configured_max_level);
+        Ok(())
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.log_elevation.is_elevated(Instant::now)
+    }
+}
+
+impl Log for ElevatingLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        is_written(
+            self.configured.enabled(metadata),
+            || self.is_elevated(),
+            metadata.level(),
+            metadata.target(),
+            &self.workspace_crates,
+        )
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.level() == Level::Error && !self.log_elevation.error_debug_window.is_zero() {
+            self.log_elevation.elevate(Instant::now(), self.log_elevation.error_debug_window);
+        }
+        if self.configured.matches(record) {
+            self.configured.log(record);
+        } else if is_written(false, || self.is_elevated(), record.level(), record.target(), &self.workspace_crates) {
+            self.elevated.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.configured.flush();
+    }
+}
+
+/// Workspace crates log at debug level for `duration`, as they do after an error. False when the logging isn't
+/// initialized, such as in a plugin
+pub fn elevate_logging(duration: Duration) -> bool {
+    let Some(log_elevation) = LOG_ELEVATION.get() else {
+        return false;
+    };
+    log_elevation.elevate(Instant::now(), duration);
+    true
+}
+
+/// How long the workspace crates log at debug level after an error, see `ERROR_DEBUG_WINDOW_ENV`
+#[must_use]
+pub fn error_debug_window() -> Duration {
+    LOG_ELEVATION.get().map_or_else(
+        || parse_error_debug_window(std::env::var(ERROR_DEBUG_WINDOW_ENV).ok().as_deref()),
+        |log_elevation| log_elevation.error_debug_window,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_expiry() {
+        let log_elevation = LogElevation::new(LevelFilter::Info, DEFAULT_ERROR_DEBUG_WINDOW);
+        let now = Instant::now();
+        assert!(!log_elevation.is_elevated(|| unreachable!("the clock is read while not elevated")));
+
+        log_elevation.elevate(now, Duration::from_secs(30));
+        assert!(log_elevation.is_elevated(|| now));
+        assert!(log_elevation.is_elevated(|| now + Duration::from_secs(29)));
+
+        // a shorter window doesn't cut the running one, a longer one extends it
+        log_elevation.elevate(now + Duration::from_secs(1), Duration::from_secs(5));
+        assert!(log_elevation.is_elevated(|| now + Duration::from_secs(29)));
+        log_elevation.elevate(now + Duration::from_secs(10), Duration::from_secs(30));
+        assert!(log_elevation.is_elevated(|| now + Duration::from_secs(35)));
+
+        assert!(!log_elevation.is_elevated(|| now + Duration::from_secs(40)));
+        // over for good, without reading the clock again
+        assert!(!log_elevation.is_elevated(|| unreachable!("the clock is read while not elevated")));
+    }
+
+    #[test]
+    fn test_is_written() {
+        let workspace_crates = ["midi".to_string(), "midi_bpm_detector_plugin".to_string()];
+        let written =
+            |configured, elevated, level, target| is_written(configured, || elevated, level, target, &workspace_crates);
+
+        // the configured filter decides alone while not elevated
+        assert!(written(true, false, Level::Info, "midi::worker"));
+        assert!(!written(false, false, Level::Debug, "midi::worker"));
+        assert!(written(true, false, Level::Debug, "eframe"));
+
+        // elevated, debug and above of the workspace crates
+        assert!(written(false, true, Level::Debug, "midi::worker"));
+        assert!(written(false, true, Level::Debug, "midi"));
+        assert!(written(false, true, Level::Info, "midi_bpm_detector_plugin::task_executor"));
+        assert!(!written(false, true, Level::Trace, "midi::worker"));
+        assert!(!written(false, true, Level::Debug, "eframe::native"));
+        // a crate whose name starts with the one of a workspace crate
+        assert!(!written(false, true, Level::Debug, "midir::backend"));
+    }
+
+    #[test]
+    fn test_error_debug_window() {
+        assert_eq!(parse_error_debug_window(None), DEFAULT_ERROR_DEBUG_WINDOW);
+        assert_eq!(parse_error_debug_window(Some("10")), Duration::from_secs(10));
+        assert_eq!(parse_error_debug_window(Some(" 2.5 ")), Duration::from_millis(2500));
+        assert_eq!(parse_error_debug_window(Some("0")), Duration::ZERO);
+        assert_eq!(parse_error_debug_window(Some("-1")), DEFAULT_ERROR_DEBUG_WINDOW);
+        assert_eq!(parse_error_debug_window(Some("soon")), DEFAULT_ERROR_DEBUG_WINDOW);
+    }
+}
@@ -1,20 +1,85 @@
-use crate::{Report, Result};
+use crate::{crash_report::record_log_line, log_elevation::ElevatingLogger, Report, Result};
 use build::{get_data_dir, LOG_ENV, LOG_FILE};
 
-use env_logger::Builder;
-use log::{debug, error, info, LevelFilter};
-use std::{fmt::Debug, fs::File, io::Write, ops::Deref, panic::Location};
+use env_logger::{fmt::Formatter, Builder};
+use log::{debug, error, info, LevelFilter, Record};
+use std::{
+    fmt::Debug,
+    fs::{rename, File},
+    io,
+    io::Write,
+    ops::Deref,
+    panic::Location,
+    path::{Path, PathBuf},
+};
 use sync::Mutex;
 
 pub static WORKSPACE_CRATES: &str = env!("_WORKSPACE_CRATES");
 
+const LOG_FILE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const LOG_FILE_BACKUPS: usize = 2;
+
+/// Log file that is renamed to `<name>.1` once it reaches `max_size`, older backups being shifted up to `backups`
+struct RotatingLogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    backups: usize,
+}
+
+impl RotatingLogFile {
+    fn create(path: PathBuf, max_size: u64, backups: usize) -> io::Result<Self> {
+        let file = File::create(&path)?;
+        Ok(Self { path, file, size: 0, max_size, backups })
+    }
+
+    fn backup_path(path: &Path, index: usize) -> PathBuf {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(format!(".{index}"));
+        backup_path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.backups > 0 {
+            for index in (1..self.backups).rev() {
+                let from = Self::backup_path(&self.path, index);
+                if from.exists() {
+                    rename(from, Self::backup_path(&self.path, index + 1))?;
+                }
+            }
+            rename(&self.path, Self::backup_path(&self.path, 1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            // nowhere to report a failure, keep appending to the current file
+            self.rotate().ok();
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
 pub fn initialize_logging() -> Result<()> {
     let directory = get_data_dir();
     std::fs::create_dir_all(directory.clone())?;
     let log_path = directory.join(LOG_FILE.clone());
 
-    let log_file = Box::new(Mutex::new(File::create(log_path)?));
-    let log_file = Box::leak(log_file);
+    let log_file = Box::new(Mutex::new(RotatingLogFile::create(log_path, LOG_FILE_MAX_SIZE, LOG_FILE_BACKUPS)?));
+    let log_file = &*Box::leak(log_file);
     std::env::set_var(
         "RUST_LOG",
         std::env::var("RUST_LOG").or_else(|_| std::env::var(LOG_ENV.clone())).unwrap_or_else(|_| {
@@ -26,25 +91,28 @@ pub fn initialize_logging() -> Result<()> {
         }),
     );
 
-    Builder::from_default_env()
-        .filter(None, LevelFilter::Info)
-        .format(|buf, record| {
-            let timestamp = buf.timestamp_micros();
-
-            minitrace::Event::add_to_local_parent(record.level().as_str(), || {
-                [("message".into(), record.args().to_string().into())]
-            });
-            writeln!(
-                log_file.lock(),
-                "{} {} {}:{}: {}",
-                timestamp,
-                record.level(),
-                record.file().unwrap_or("unknown"),
-                record.line().unwrap_or(0),
-                record.args()
-            )
-        })
-        .init();
+    let format = |buf: &mut Formatter, record: &Record<'_>| {
+        let timestamp = buf.timestamp_micros();
+
+        minitrace::Event::add_to_local_parent(record.level().as_str(), || {
+            [("message".into(), record.args().to_string().into())]
+        });
+        let line = format!(
+            "{} {} {}:{}: {}",
+            timestamp,
+            record.level(),
+            record.file().unwrap_or("unknown"),
+            record.line().unwrap_or(0),
+            record.args()
+        );
+        let written = writeln!(log_file.lock(), "{line}");
+        record_log_line(line);
+        written
+    };
+    let configured = Builder::from_default_env().filter(None, LevelFilter::Info).format(format).build();
+    // for the records of the workspace crates let through after an error, see `LogElevation`
+    let elevated = Builder::new().filter(None, LevelFilter::Debug).format(format).build();
+    ElevatingLogger::init(configured, elevated, WORKSPACE_CRATES)?;
     Ok(())
 }
 
@@ -234,3 +302,28 @@ impl<T> LogOptionWithExt<T> for Option<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, remove_dir_all};
+
+    #[test]
+    fn test_rotation() {
+        let directory = std::env::temp_dir().join(format!("rotating_log_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("test.log");
+        let mut log_file = RotatingLogFile::create(path.clone(), 10, 2).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log_file.write_all(line.as_bytes()).unwrap();
+        }
+        log_file.flush().unwrap();
+
+        assert_eq!(read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(read_to_string(RotatingLogFile::backup_path(&path, 1)).unwrap(), "third\n");
+        assert_eq!(read_to_string(RotatingLogFile::backup_path(&path, 2)).unwrap(), "second\n");
+        assert!(!RotatingLogFile::backup_path(&path, 3).exists());
+        remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Result;
+use crate::{write_crash_report, Result};
 use log::error;
 
 pub fn initialize_panic_handler<I: 'static + Send + Sync + Fn()>(reset: I) -> Result<()> {
@@ -11,6 +11,8 @@ pub fn initialize_panic_handler<I: 'static + Send + Sync + Fn()>(reset: I) -> Re
     eyre_hook.install()?;
     std::panic::set_hook(Box::new(move |panic_info| {
         reset();
+        let msg = format!("{}", panic_hook.panic_report(panic_info));
+        let crash_report = write_crash_report(&strip_ansi_escapes::strip_str(&msg));
 
         #[cfg(all(not(debug_assertions), not(target_arch = "wasm32")))]
         {
@@ -27,8 +29,11 @@ pub fn initialize_panic_handler<I: 'static + Send + Sync + Fn()>(reset: I) -> Re
             print_msg(file_path, &meta).expect("human-panic: printing error message to console failed");
             eprintln!("{}", panic_hook.panic_report(panic_info)); // prints color-eyre stack trace to stderr
         }
-        let msg = format!("{}", panic_hook.panic_report(panic_info));
         error!("Error: {}", strip_ansi_escapes::strip_str(msg));
+        // the terminal is restored by now
+        if let Some(crash_report) = crash_report {
+            eprintln!("crash report written to {}", crash_report.display());
+        }
 
         #[cfg(debug_assertions)]
         {
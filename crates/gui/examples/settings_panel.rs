@@ -0,0 +1,109 @@
+//! The settings panel of the GUI against a configuration of its own, with an extra slider next to the detection
+//! parameters.
+//!
+//! cargo run -p gui --example settings_panel
+
+#![allow(forbidden_lint_groups)]
+#![allow(clippy::missing_errors_doc)]
+
+use gui::{
+    eframe, eframe::egui, render_settings_panel, BPMDetectionParameters, GUIConfig, PanelOptions, Parameter,
+    ParameterSlider,
+};
+use midi::{validate_interaction, DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+
+#[derive(Default)]
+struct ToyConfig {
+    dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    gui_config: GUIConfig,
+    send_tempo: bool,
+    // not part of the detection, edited with a `ParameterSlider`
+    volume: f32,
+}
+
+impl ToyConfig {
+    const VOLUME: Parameter<Self, f32> =
+        Parameter::new("Volume", Some("dB"), -60.0..=6.0, 0.5, false, 0.0, |config| &mut config.volume);
+}
+
+impl BPMDetectionParameters for ToyConfig {
+    type Error = ();
+
+    fn get_dynamic_bpm_detection_parameters(&self) -> &DynamicBPMDetectionParameters {
+        &self.dynamic_bpm_detection_parameters
+    }
+
+    fn get_dynamic_bpm_detection_parameters_mut(&mut self) -> &mut DynamicBPMDetectionParameters {
+        &mut self.dynamic_bpm_detection_parameters
+    }
+
+    fn get_static_bpm_detection_parameters(&self) -> &StaticBPMDetectionParameters {
+        &self.static_bpm_detection_parameters
+    }
+
+    fn get_static_bpm_detection_parameters_mut(&mut self) -> &mut StaticBPMDetectionParameters {
+        &mut self.static_bpm_detection_parameters
+    }
+
+    fn get_gui_config(&self) -> &GUIConfig {
+        &self.gui_config
+    }
+
+    fn get_gui_config_mut(&mut self) -> &mut GUIConfig {
+        &mut self.gui_config
+    }
+
+    fn get_send_tempo(&self) -> bool {
+        self.send_tempo
+    }
+
+    fn set_send_tempo(&mut self, enabled: bool) {
+        self.send_tempo = enabled;
+    }
+
+    fn apply_static(&mut self) -> Result<(), Self::Error> {
+        println!("static parameters: {:?}", self.static_bpm_detection_parameters);
+        Ok(())
+    }
+
+    fn apply_dynamic(&mut self) -> Result<(), Self::Error> {
+        println!("dynamic parameters: {:?}", self.dynamic_bpm_detection_parameters);
+        Ok(())
+    }
+}
+
+struct ToyApp {
+    config: ToyConfig,
+}
+
+impl eframe::App for ToyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let config_warnings = validate_interaction(
+                &self.config.static_bpm_detection_parameters,
+                &self.config.dynamic_bpm_detection_parameters,
+            );
+            let options = PanelOptions::new().with_config_warnings(config_warnings).with_display_settings(false);
+            render_settings_panel(ui, &mut self.config, &options);
+
+            ui.separator();
+            egui::Grid::new("toy").num_columns(2).show(ui, |ui| {
+                let volume = self.config.volume;
+                ParameterSlider::new(&ToyConfig::VOLUME).show(ui, || volume, |value| self.config.volume = value);
+            });
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<(), eframe::Error> {
+    eframe::run_native(
+        "Settings panel",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Box::new(ToyApp { config: ToyConfig::default() })),
+    )
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
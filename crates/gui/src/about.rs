@@ -0,0 +1,102 @@
+use crate::{BUILD_PROFILE, BUILD_TIME, GIT_COMMIT_HASH};
+use build::{get_config_dir, get_data_dir};
+use eframe::{egui, egui::Context};
+use itertools::Itertools;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Locations on disk used by a standalone application. Not relevant for the plugin, which stores its state in the
+/// host's project.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigPaths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub config_files: Vec<PathBuf>,
+}
+
+impl ConfigPaths {
+    #[must_use]
+    pub fn new(config_files: Vec<PathBuf>) -> Self {
+        Self { config_dir: get_config_dir(), data_dir: get_data_dir(), config_files }
+    }
+}
+
+/// Everything worth including in a support request
+#[derive(Clone, Debug, Serialize)]
+pub struct AboutInfo {
+    pub version: &'static str,
+    pub git_commit_hash: &'static str,
+    pub build_time: &'static str,
+    pub build_profile: &'static str,
+    #[serde(flatten)]
+    pub paths: Option<ConfigPaths>,
+}
+
+#[must_use]
+pub fn about_info(paths: Option<ConfigPaths>) -> AboutInfo {
+    AboutInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit_hash: GIT_COMMIT_HASH,
+        build_time: BUILD_TIME,
+        build_profile: BUILD_PROFILE,
+        paths,
+    }
+}
+
+impl AboutInfo {
+    #[must_use]
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("Version", self.version.to_string()),
+            ("Commit", self.git_commit_hash.to_string()),
+            ("Build time", self.build_time.to_string()),
+            ("Build profile", self.build_profile.to_string()),
+        ];
+        if let Some(paths) = &self.paths {
+            fields.push(("Config directory", paths.config_dir.display().to_string()));
+            fields.push(("Data directory", paths.data_dir.display().to_string()));
+            fields.push(("Config files", paths.config_files.iter().map(|path| path.display()).join("\n")));
+        }
+        fields
+    }
+
+    fn to_text(&self) -> String {
+        self.fields().into_iter().map(|(label, value)| format!("{label}: {value}")).join("\n")
+    }
+}
+
+pub(crate) fn about_window(ctx: &Context, open: &mut bool, about_info: &AboutInfo) {
+    egui::Window::new("About").open(open).collapsible(false).resizable(false).show(ctx, |ui| {
+        egui::Grid::new("about").num_columns(3).show(ui, |ui| {
+            for (label, value) in about_info.fields() {
+                ui.label(label);
+                ui.monospace(&value);
+                if ui.small_button("📋").on_hover_text("Copy to clipboard").clicked() {
+                    ui.output_mut(|output| output.copied_text = value);
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        if ui.button("Copy all").clicked() {
+            ui.output_mut(|output| output.copied_text = about_info.to_text());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_about_info_fields_are_not_empty() {
+        let about = about_info(Some(ConfigPaths::new(vec![get_config_dir().join("config.toml")])));
+        let fields = about.fields();
+        assert_eq!(fields.len(), 7);
+        for (label, value) in fields {
+            assert!(!value.is_empty(), "{label} is empty");
+        }
+
+        assert_eq!(about_info(None).fields().len(), 4);
+    }
+}
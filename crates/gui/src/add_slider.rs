@@ -1,13 +1,20 @@
-use eframe::{egui, egui::Slider};
+use eframe::{
+    egui,
+    egui::{emath::remap_clamp, pos2, Rect, RichText, Slider, Stroke},
+};
 use errors::{error_backtrace, LogErrorWithExt};
+use midi::ConfigWarning;
 use parameter::{Asf64, OnOff, Parameter};
 use std::{cell::RefCell, fmt::Debug, sync::atomic::Ordering};
 use sync::ArcAtomicOptional;
 
+/// `modulated_value` is marked over the slider, when a host modulates the parameter away from its value
 pub fn add_slider<V: Asf64, S, G>(
     ui: &mut egui::Ui,
     enabled: bool,
     parameter: &Parameter<S, G>,
+    config_warnings: &[ConfigWarning],
+    modulated_value: Option<f64>,
     get_set_value: impl FnMut(Option<f64>) -> f64,
 ) {
     let mut slider = Slider::from_get_set(parameter.range.clone(), get_set_value)
@@ -17,13 +24,59 @@ pub fn add_slider<V: Asf64, S, G>(
     if let Some(unit) = parameter.unit.as_ref() {
         slider = slider.text(*unit);
     }
-    ui.add_enabled(enabled, slider);
+    let explanation = config_warnings
+        .iter()
+        .filter(|config_warning| config_warning.parameters().contains(&parameter.label))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let slider_rect = if explanation.is_empty() {
+        ui.add_enabled(enabled, slider).rect
+    } else {
+        ui.horizontal(|ui| {
+            let slider_rect = ui.add_enabled(enabled, slider).rect;
+            let warning_color = ui.visuals().warn_fg_color;
+            ui.label(RichText::new("⚠").color(warning_color)).on_hover_text(explanation);
+            slider_rect
+        })
+        .inner
+    };
+    if let Some(modulated_value) = modulated_value {
+        mark_value(ui, slider_rect, parameter, modulated_value);
+    }
     ui.end_row();
 }
 
+// thin line across the rail, which starts the slider and is as wide as set in the spacing, the value shown next to it
+// follows
+fn mark_value<S, G>(ui: &egui::Ui, slider_rect: Rect, parameter: &Parameter<S, G>, value: f64) {
+    let rail = Rect::from_min_size(slider_rect.min, egui::vec2(ui.spacing().slider_width, slider_rect.height()));
+    // as egui keeps the handle within the rail
+    let handle_radius = rail.height() / 2.5;
+    let x = remap_clamp(
+        position(parameter, value) as f32,
+        0.0..=1.0,
+        (rail.left() + handle_radius)..=(rail.right() - handle_radius),
+    );
+    let stroke = Stroke::new(2.0, ui.visuals().hyperlink_color);
+    ui.painter().line_segment([pos2(x, rail.top()), pos2(x, rail.bottom())], stroke);
+}
+
+// from 0 to 1 along the slider. The logarithmic ranges all start above 0, where egui maps them the same way
+fn position<S, G>(parameter: &Parameter<S, G>, value: f64) -> f64 {
+    let (start, end) = (*parameter.range.start(), *parameter.range.end());
+    if parameter.logarithmic && start > 0.0 {
+        (value / start).ln() / (end / start).ln()
+    } else {
+        (value - start) / (end - start)
+    }
+}
+
 pub fn add_slider_default<E, V, S, G>(
     ui: &mut egui::Ui,
     parameter: &Parameter<S, G>,
+    config_warnings: &[ConfigWarning],
+    modulated_value: Option<f64>,
     mut get_set_as_f64: impl FnMut(Option<V>) -> Result<V, E>,
 ) where
     E: Debug,
@@ -31,7 +84,7 @@ pub fn add_slider_default<E, V, S, G>(
 {
     ui.label(parameter.label);
 
-    add_slider::<V, S, G>(ui, true, parameter, move |value: Option<f64>| {
+    add_slider::<V, S, G>(ui, true, parameter, config_warnings, modulated_value, move |value: Option<f64>| {
         match (value, get_set_as_f64(value.map(|value| V::from(value)))) {
             (_, Ok(value)) => value.get(),
             (Some(value), Err(e)) => {
@@ -55,6 +108,10 @@ where
     ui: &'a mut egui::Ui,
     apply: F,
     applier: &'b mut A,
+    // shown next to the sliders of the parameters involved
+    config_warnings: &'b [ConfigWarning],
+    // marked over the sliders, by label
+    modulated_values: &'b [(String, f64)],
 }
 
 impl<'a, 'b, A, F, E> SlideAdder<'a, 'b, A, F, E>
@@ -62,11 +119,20 @@ where
     F: for<'i> Fn(&'i mut A) -> Result<(), E> + Copy,
     E: Debug,
 {
-    pub fn builder(ui: &'a mut egui::Ui, apply: F, applier: &'b mut A) -> SliderAdderRefCell<'a, 'b, A, F, E>
+    pub fn builder(
+        ui: &'a mut egui::Ui,
+        apply: F,
+        applier: &'b mut A,
+        config_warnings: &'b [ConfigWarning],
+    ) -> SliderAdderRefCell<'a, 'b, A, F, E>
     where
         F: for<'i> Fn(&mut A) -> Result<(), E> + Copy,
     {
-        SliderAdderRefCell(RefCell::new(Self { ui, apply, applier }))
+        SliderAdderRefCell(RefCell::new(Self { ui, apply, applier, config_warnings, modulated_values: &[] }))
+    }
+
+    fn modulated_value<S, V>(&self, parameter: &Parameter<S, V>) -> Option<f64> {
+        self.modulated_values.iter().find(|(label, _)| label == parameter.label).map(|(_, value)| *value)
     }
 }
 
@@ -75,6 +141,13 @@ where
     F: for<'i> Fn(&'i mut A) -> Result<(), E> + Copy,
     E: Debug,
 {
+    /// Values a host modulates the parameters to, by label, see `BPMDetectionParameters::get_modulated_values`
+    #[must_use]
+    pub fn with_modulated_values(mut self, modulated_values: &'b [(String, f64)]) -> Self {
+        self.0.get_mut().modulated_values = modulated_values;
+        self
+    }
+
     pub fn for_config<'s, GetConfig, C>(
         &'s self,
         get_config: GetConfig,
@@ -113,7 +186,8 @@ where
     {
         let slide_adder = &mut *self.slide_adder.0.borrow_mut();
 
-        add_slider_default(slide_adder.ui, parameter, {
+        let modulated_value = slide_adder.modulated_value(parameter);
+        add_slider_default(slide_adder.ui, parameter, slide_adder.config_warnings, modulated_value, {
             |value| {
                 let config = (self.get_config)(slide_adder.applier);
                 match value {
@@ -132,15 +206,22 @@ where
         let slide_adder = &mut *self.slide_adder.0.borrow_mut();
         let atomic_u8 = &*(parameter.get_mut)((self.get_config)(slide_adder.applier));
 
-        add_slider_default(slide_adder.ui, parameter, |value: Option<u8>| {
-            Ok::<_, E>(match value {
-                None => atomic_u8.load(Ordering::Relaxed).unwrap_or_default(),
-                Some(value) => {
-                    atomic_u8.store(Some(value), Ordering::Relaxed);
-                    value
-                }
-            })
-        });
+        let modulated_value = slide_adder.modulated_value(parameter);
+        add_slider_default(
+            slide_adder.ui,
+            parameter,
+            slide_adder.config_warnings,
+            modulated_value,
+            |value: Option<u8>| {
+                Ok::<_, E>(match value {
+                    None => atomic_u8.load(Ordering::Relaxed).unwrap_or_default(),
+                    Some(value) => {
+                        atomic_u8.store(Some(value), Ordering::Relaxed);
+                        value
+                    }
+                })
+            },
+        );
     }
 
     pub fn add_on_off<V>(&mut self, parameter: &Parameter<C, OnOff<V>>)
@@ -170,7 +251,9 @@ where
             }
         };
 
-        add_slider::<V, _, _>(slide_adder.ui, must_enable, parameter, |new_value| {
+        let modulated_value = slide_adder.modulated_value(parameter);
+        let config_warnings = slide_adder.config_warnings;
+        add_slider::<V, _, _>(slide_adder.ui, must_enable, parameter, config_warnings, modulated_value, |new_value| {
             let config = (self.get_config)(slide_adder.applier);
             let current_value_mut = (parameter.get_mut)(config).value_mut();
             if must_enable {
@@ -1,21 +1,64 @@
-use crate::{egui::Color32, gui_remote::HistogramDataPoints, BPMDetectionParameters, BUILD_TIME};
+#[cfg(target_arch = "wasm32")]
+use crate::snapshot::download_csv;
+use crate::{
+    about::about_window,
+    config::YScale,
+    drift::{Drift, DriftLevel, DriftTracker, Trend},
+    effective_config::effective_config_window,
+    egui::Color32,
+    gui_remote::{FrozenHistogram, HistogramDataPoints, MultiResolution, PhaseSplitHistogram},
+    interpolation::smoothing_factor,
+    metronome::{flash, flash_circle, BeatAnchor, Flash},
+    note_strip::{note_strip, NoteHistory},
+    onboarding::{onboarding_window, OnboardingProgress},
+    render_settings_panel,
+    resample::{bpm_axis, resample},
+    snapshot::snapshot_file_stem,
+    ui_state::{UiStateHandle, SAVE_DEBOUNCE},
+    AboutInfo, BPMDetectionParameters, PanelOptions, BUILD_TIME,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    config::WindowLevelState,
+    snapshot::{export_csv, snapshots_dir},
+};
 use atomic_float::AtomicF32;
 use atomic_refcell::AtomicRefCell;
 use eframe::{
     egui,
-    egui::{Context, Event, RichText, Ui},
+    egui::{Context, Event, Rect, RichText, Stroke, Ui, ViewportCommand, WindowLevel},
     epaint::Hsva,
 };
-use egui_plot::{Bar, BarChart, Legend, PlotResponse, PlotUi};
+use egui_plot::{Bar, BarChart, Legend, Line, PlotPoints, PlotResponse, PlotUi, Polygon, VLine};
 use errors::{minitrace, LogErrorWithExt, LogOptionWithExt};
 use log::error;
+use midi::{
+    memory::shrink_excess, BarPosition, ConfigWarning, Feel, FeelAmbiguity, ResolutionFloor, SessionSummary,
+    StaticBPMDetectionParameters, TapTempo, TempoBand,
+};
 use num_traits::identities::Zero;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Weak,
+use parameter::OnOff;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Weak,
+    },
+    time::Duration,
 };
 use sync::Mutex;
 
+// below this difference between the displayed and the target value, we stop requesting repaints
+const INTERPOLATION_EPSILON: f32 = 1e-3;
+// of the histogram around the estimate, under the main one in multi-resolution mode
+const FINE_PLOT_HEIGHT: f32 = 120.0;
+// in BPM, a shift-drag over less than that is taken for a click
+const MIN_EXCLUDED_RANGE: f32 = 0.1;
+// of the stacked bars in the phase split view
+const ON_BEAT_HUE: f32 = 0.1;
+const OFF_BEAT_HUE: f32 = 0.6;
+
 pub struct BPMDetectionGUI<P: BPMDetectionParameters + 'static> {
     // keys_sender, gui_exit_callback and buffer_redraw belong to the GUI Remote,
     // that ultimately is held by the main app, which can drop it to let know the GUI app that we are exiting
@@ -25,16 +68,71 @@ pub struct BPMDetectionGUI<P: BPMDetectionParameters + 'static> {
     pub live_parameters: P,
     pub(crate) histogram_data_points: Weak<AtomicRefCell<HistogramDataPoints>>,
     pub(crate) interpolated_data_points: Vec<f32>,
+    // scale and log factor the interpolated data points are expressed in
+    pub(crate) interpolated_y_scale: Option<(YScale, f32)>,
+    // axis the interpolated data points are laid on, the parameters of the histogram they were computed from
+    pub(crate) interpolated_parameters: Option<StaticBPMDetectionParameters>,
     pub(crate) estimated_bpm: Weak<AtomicF32>,
+    pub(crate) estimated_confidence: Weak<AtomicF32>,
     pub(crate) daw_bpm: Weak<AtomicF32>,
     pub(crate) should_save: Weak<AtomicBool>,
+    pub(crate) should_export_snapshot: Weak<AtomicBool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) tui_focused: Weak<AtomicBool>,
+    pub(crate) should_cycle_always_on_top: Weak<AtomicBool>,
+    pub(crate) note_history: Weak<Mutex<NoteHistory>>,
+    pub(crate) config_warnings: Weak<Mutex<Vec<ConfigWarning>>>,
+    pub(crate) bar_position: Weak<Mutex<Option<BarPosition>>>,
+    // where the beat count stood at the newest note, the metronome flash is predicted from it
+    pub(crate) beat_anchor: Weak<Mutex<Option<BeatAnchor>>>,
+    pub(crate) velocity_gate: Weak<Mutex<Option<u8>>>,
+    // narrowed parameters the detection runs with, see `AutoZoom`
+    pub(crate) auto_zoom: Weak<Mutex<Option<StaticBPMDetectionParameters>>>,
+    // tempo tapped by the user, until the detection takes over
+    pub(crate) tapped_bpm: Weak<Mutex<Option<f32>>>,
+    // taps of the tap button and of shift+T, the tempo they give is drawn over the histogram to compare it with
+    pub(crate) tap_tempo: TapTempo,
+    pub(crate) reference_bpm: Option<f32>,
+    // tempos where a shift-drag over the histogram started and where it is now, excluded once it is released
+    pub(crate) excluded_range_drag: Option<(f32, f32)>,
+    // tempo of the MIDI clock received, when it is tracked
+    pub(crate) clock_bpm: Weak<Mutex<Option<f32>>>,
+    // evaluation of the frozen notes, until they are cleared
+    pub(crate) frozen_histogram: Weak<Mutex<Option<FrozenHistogram>>>,
+    // steadiness of the tempo since the session started or the notes were cleared
+    pub(crate) session_summary: Weak<Mutex<SessionSummary>>,
+    pub(crate) note_freshness: Weak<Mutex<Option<f32>>>,
+    pub(crate) feel_ambiguity: Weak<Mutex<Option<FeelAmbiguity>>>,
+    // coarse axis and histogram around the estimate, in multi-resolution mode
+    pub(crate) multi_resolution: Weak<Mutex<Option<MultiResolution>>>,
+    pub(crate) resolution_floor: Weak<Mutex<Option<ResolutionFloor>>>,
+    // band of the configured tempo bands the tempo is in
+    pub(crate) tempo_band: Weak<Mutex<Option<TempoBand>>>,
+    // the window is given to the detection when the split view is on, the shares it splits the histogram into come back
+    pub(crate) phase_split_window: Weak<Mutex<Option<f32>>>,
+    pub(crate) phase_split_histogram: Weak<Mutex<Option<PhaseSplitHistogram>>>,
+    pub(crate) drift_tracker: DriftTracker,
+    // saved through the parameters once it settled after a change
+    pub(crate) ui_state: UiStateHandle,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) window_level_state: WindowLevelState,
+    pub(crate) about_info: AboutInfo,
+    pub(crate) show_about: bool,
+    // shown in a window until it is closed
+    pub(crate) effective_config: Option<String>,
+    // area of the last drawn plot, used to crop the screenshot of a snapshot
+    pub(crate) plot_rect: Option<Rect>,
+    // where to write the png once the screenshot requested during the export is received
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) pending_screenshot: Option<(PathBuf, Rect)>,
+    pub(crate) status_message: Option<String>,
 }
 
 #[allow(forbidden_lint_groups)]
 #[allow(clippy::too_many_arguments)]
 impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
     #[minitrace::trace]
-    fn attach_barchart(&mut self, plot_ui: &mut PlotUi) -> Option<bool> {
+    fn attach_barchart(&mut self, plot_ui: &mut PlotUi, dt: f32) -> Option<bool> {
         let histogram_data_points = self
             .histogram_data_points
             .upgrade()
@@ -50,81 +148,444 @@ impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
             return None;
         }
 
-        if self.interpolated_data_points.len() != histogram_data_points.inbound_histogram_data_points.len() {
+        let gui_config = self.live_parameters.get_gui_config();
+        let (y_scale, log_scale_factor) = (gui_config.y_scale, gui_config.log_scale_factor);
+        // interpolation happens in display space, so switching scales converts the current values instead of
+        // animating from unrelated heights
+        if let Some((previous_y_scale, previous_log_scale_factor)) =
+            self.interpolated_y_scale.replace((y_scale, log_scale_factor))
+        {
+            if previous_y_scale != y_scale || previous_log_scale_factor.to_bits() != log_scale_factor.to_bits() {
+                for interpolated_y in &mut self.interpolated_data_points {
+                    *interpolated_y = y_scale.to_display(
+                        previous_y_scale.from_display(*interpolated_y, previous_log_scale_factor),
+                        log_scale_factor,
+                    );
+                }
+            }
+        }
+
+        let len = histogram_data_points.inbound_histogram_data_points.len();
+        let histogram_parameters = self.histogram_parameters(len);
+        // the histogram may follow the parameters a few evaluations later, the axis changes with either
+        let morph_from = self.interpolated_parameters.replace(histogram_parameters.clone()).filter(|previous| {
+            gui_config.morph_on_reconfigure
+                && !self.interpolated_data_points.is_empty()
+                && (*previous != histogram_parameters || self.interpolated_data_points.len() != len)
+        });
+        if let Some(previous_parameters) = morph_from {
+            // the bars move to where their tempo is on the new axis, the interpolation goes on from there
+            self.interpolated_data_points = resample(
+                &bpm_axis(&previous_parameters, self.interpolated_data_points.len()),
+                &self.interpolated_data_points,
+                &bpm_axis(&histogram_parameters, len),
+            );
+            // nothing left in common with the new axis, it starts from the target as when there is no morphing
+            if !self.interpolated_data_points.iter().any(|y| *y > 0.0) {
+                self.interpolated_data_points.clear();
+            }
+        }
+        if self.interpolated_data_points.len() != len {
             self.interpolated_data_points.resize(0, 0.0);
             self.interpolated_data_points.resize(histogram_data_points.inbound_histogram_data_points.len(), 0.0);
+            // the size follows the static parameters, don't keep the largest one ever seen
+            shrink_excess(
+                &mut self.interpolated_data_points,
+                histogram_data_points.inbound_histogram_data_points.len(),
+            );
             for (x, y) in histogram_data_points.inbound_histogram_data_points.iter().enumerate() {
-                self.interpolated_data_points[x] = *y / max_y;
+                self.interpolated_data_points[x] = y_scale.to_display(*y / max_y, log_scale_factor);
             }
         }
 
-        let elapsed = histogram_data_points.inbound_histogram_data_update.elapsed();
-        let interpolation_duration = self.live_parameters.get_gui_config().interpolation_duration;
-        let interpolation_ratio = (elapsed.as_micros() as f32 / interpolation_duration.as_micros() as f32).min(1.0);
-        let interpolation_ratio =
-            interpolation_ratio.powf(1.0 / self.live_parameters.get_gui_config().interpolation_curve);
+        let factor = smoothing_factor(dt, gui_config.interpolation_duration, gui_config.interpolation_curve);
 
+        let mut still_moving = false;
         for (y, interpolated_y) in
             histogram_data_points.inbound_histogram_data_points.iter().zip(self.interpolated_data_points.iter_mut())
         {
-            *interpolated_y = y / max_y * interpolation_ratio + *interpolated_y * (1.0 - interpolation_ratio);
+            let target = y_scale.to_display(y / max_y, log_scale_factor);
+            *interpolated_y += (target - *interpolated_y) * factor;
+            still_moving |= (target - *interpolated_y).abs() > INTERPOLATION_EPSILON;
         }
 
         // so max is always 1 after interpolation, otherwise the y axis will be jumpy
         let max_interpolated_y = self.interpolated_data_points.iter().max_by(|x, y| x.total_cmp(y))?;
 
-        let live_parameters = self.live_parameters.get_static_bpm_detection_parameters();
-        let min_x = live_parameters.index_to_bpm(0);
-        let max_x = live_parameters.index_to_bpm(histogram_data_points.inbound_histogram_data_points.len());
+        let zoomed = self.zoomed();
+        // the plot keeps the configured range
+        let configured = self.live_parameters.get_static_bpm_detection_parameters();
+        let (lowest_bpm, highest_bpm) = (configured.lowest_bpm(), configured.highest_bpm());
+        let min_x = histogram_parameters.index_to_bpm(0);
+        let max_x = histogram_parameters.index_to_bpm(histogram_data_points.inbound_histogram_data_points.len());
 
         drop(histogram_data_points);
 
-        let mut prev = f64::from(self.live_parameters.get_static_bpm_detection_parameters().index_to_bpm(1));
+        if let Some(zoomed) = zoomed {
+            let (from, to) = (f64::from(zoomed.lowest_bpm()), f64::from(zoomed.highest_bpm()));
+            plot_ui.polygon(
+                Polygon::new(vec![[from, 0.0], [to, 0.0], [to, 1.0], [from, 1.0]])
+                    .fill_color(Color32::from_rgba_unmultiplied(120, 160, 255, 24))
+                    .stroke(Stroke::NONE)
+                    .name("Auto zoom"),
+            );
+        }
+        if let Some(fine_parameters) = self.fine_histogram().map(|(parameters, _)| parameters) {
+            let (from, to) = (f64::from(fine_parameters.lowest_bpm()), f64::from(fine_parameters.highest_bpm()));
+            plot_ui.polygon(
+                Polygon::new(vec![[from, 0.0], [to, 0.0], [to, 1.0], [from, 1.0]])
+                    .fill_color(Color32::from_rgba_unmultiplied(255, 200, 80, 32))
+                    .stroke(Stroke::NONE)
+                    .name("Fine window"),
+            );
+        }
 
-        plot_ui.bar_chart(BarChart::new(
-            (self.interpolated_data_points.iter().enumerate().map(|(x, y)| {
-                let y = f64::from(*y / max_interpolated_y);
-                let x = f64::from(self.live_parameters.get_static_bpm_detection_parameters().index_to_bpm(x));
+        let mut prev = f64::from(histogram_parameters.index_to_bpm(1));
+        let bars = self.interpolated_data_points.iter().enumerate().map(|(index, y)| {
+            let y = f64::from(*y / max_interpolated_y);
+            let x = f64::from(histogram_parameters.index_to_bpm(index));
 
-                let width = ((x - prev) * 1.5).abs();
-                prev = x;
+            let width = ((x - prev) * 1.5).abs();
+            prev = x;
+            (index, x, y, width)
+        });
+        // empty bars at both ends of the configured range, so the plot spans it
+        let range = [
+            Bar::new(parameter::Asf64::get(&lowest_bpm), 0.0).width(0.0).fill(Color32::TRANSPARENT),
+            Bar::new(parameter::Asf64::get(&highest_bpm), 0.0).width(0.0).fill(Color32::TRANSPARENT),
+        ];
 
-                Bar::new(x, y)
-                    .fill(Hsva { h: (x as f32 - min_x) / (max_x - min_x), s: 0.5 + y as f32 / 2.0, v: 0.5, a: 1.0 })
-                    .width(width)
-            }))
-            .chain(
-                [
-                    Bar::new(
-                        parameter::Asf64::get(&self.live_parameters.get_static_bpm_detection_parameters().lowest_bpm()),
-                        0.0,
-                    )
-                    .width(0.0)
-                    .fill(Color32::TRANSPARENT),
-                    Bar::new(
-                        parameter::Asf64::get(
-                            &self.live_parameters.get_static_bpm_detection_parameters().highest_bpm(),
-                        ),
-                        0.0,
+        let phase_split_histogram = self.phase_split_histogram.upgrade().filter(|_| gui_config.phase_split_view);
+        let phase_split_histogram =
+            phase_split_histogram.as_ref().map(|phase_split_histogram| phase_split_histogram.lock());
+        // the shares of an older histogram don't line up with the bars
+        if let Some(phase_split_histogram) = phase_split_histogram
+            .as_deref()
+            .and_then(Option::as_ref)
+            .filter(|phase_split_histogram| phase_split_histogram.on_beat.len() == self.interpolated_data_points.len())
+        {
+            // the displayed height is split, whatever the scale
+            let (on_beat, off_beat): (Vec<_>, Vec<_>) = bars
+                .map(|(index, x, y, width)| {
+                    let on_beat_y = y * f64::from(phase_split_histogram.on_beat_share(index).unwrap_or_default());
+                    let saturation = if configured.excludes(x as f32) { 0.0 } else { 0.5 + y as f32 / 2.0 };
+                    (
+                        Bar::new(x, on_beat_y)
+                            .fill(Hsva { h: ON_BEAT_HUE, s: saturation, v: 0.6, a: 1.0 })
+                            .width(width),
+                        Bar::new(x, y - on_beat_y)
+                            .base_offset(on_beat_y)
+                            .fill(Hsva { h: OFF_BEAT_HUE, s: saturation, v: 0.6, a: 1.0 })
+                            .width(width),
                     )
-                    .width(0.0)
-                    .fill(Color32::TRANSPARENT),
-                ]
-                .into_iter(),
+                })
+                .unzip();
+            plot_ui.bar_chart(
+                BarChart::new(on_beat.into_iter().chain(range).collect())
+                    .name("On-beat")
+                    .element_formatter(Box::new(|bar, _| format!("{:.2} BPM\non-beat", bar.argument))),
+            );
+            plot_ui.bar_chart(BarChart::new(off_beat).name("Off-beat").element_formatter(Box::new(move |bar, _| {
+                let total = bar.value + bar.base_offset.unwrap_or_default();
+                format!(
+                    "{:.2} BPM\n{:.3}\n{:.0}% off-beat",
+                    bar.argument,
+                    y_scale.from_display(total as f32, log_scale_factor),
+                    if total > 0.0 { 100.0 * bar.value / total } else { 0.0 }
+                )
+            })));
+            return Some(still_moving);
+        }
+
+        plot_ui.bar_chart(
+            BarChart::new(
+                bars.map(|(_, x, y, width)| {
+                    let saturation = if configured.excludes(x as f32) { 0.0 } else { 0.5 + y as f32 / 2.0 };
+                    Bar::new(x, y)
+                        .fill(Hsva { h: (x as f32 - min_x) / (max_x - min_x), s: saturation, v: 0.5, a: 1.0 })
+                        .width(width)
+                })
+                .chain(range)
+                .collect::<Vec<_>>(),
             )
-            .collect::<Vec<_>>(),
-        ));
-        Some(interpolation_ratio < 1.0)
+            .element_formatter(Box::new(move |bar, _| {
+                format!("{:.2} BPM\n{:.3}", bar.argument, y_scale.from_display(bar.value as f32, log_scale_factor))
+            })),
+        );
+        Some(still_moving)
+    }
+
+    // the histogram of the frozen notes, as a line over the bars. It is computed with the configured parameters, it is
+    // left out while it is evaluated again with new ones
+    fn attach_frozen_histogram(&self, plot_ui: &mut PlotUi) {
+        let Some(frozen_histogram) = self.frozen_histogram.upgrade() else {
+            return;
+        };
+        let frozen_histogram = frozen_histogram.lock();
+        let Some(frozen_histogram) = frozen_histogram.as_ref() else {
+            return;
+        };
+        let configured = self.live_parameters.get_static_bpm_detection_parameters();
+        let max_y = frozen_histogram.histogram_data_points.iter().copied().fold(0.0, f32::max);
+        if max_y <= 0.0 || configured.buffer_size() != frozen_histogram.histogram_data_points.len() {
+            return;
+        }
+
+        let gui_config = self.live_parameters.get_gui_config();
+        let points = frozen_histogram
+            .histogram_data_points
+            .iter()
+            .enumerate()
+            .map(|(index, y)| {
+                [
+                    f64::from(configured.index_to_bpm(index)),
+                    f64::from(gui_config.y_scale.to_display(y / max_y, gui_config.log_scale_factor)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        plot_ui.line(
+            Line::new(PlotPoints::from(points))
+                .color(Color32::LIGHT_GRAY)
+                .width(1.5)
+                .name(format!("Frozen input, {:.2} BPM", frozen_histogram.bpm)),
+        );
+    }
+
+    // the ranges left out of the estimate and the one being dragged, over the bars they cover
+    fn attach_excluded_ranges(&self, plot_ui: &mut PlotUi) {
+        let configured = self.live_parameters.get_static_bpm_detection_parameters();
+        for (index, &(from, to)) in configured.excluded_ranges.iter().chain(&self.excluded_range_drag).enumerate() {
+            let (from, to) = (f64::from(from), f64::from(to));
+            let dragged = index == configured.excluded_ranges.len();
+            plot_ui.polygon(
+                Polygon::new(vec![[from, 0.0], [to, 0.0], [to, 1.0], [from, 1.0]])
+                    .fill_color(Color32::from_rgba_unmultiplied(128, 128, 128, if dragged { 32 } else { 64 }))
+                    .stroke(Stroke::NONE)
+                    .name("Excluded"),
+            );
+        }
+    }
+
+    // shift-drag over the histogram excludes the tempos it spans, a right click removes the excluded ranges under the
+    // pointer
+    fn edit_excluded_ranges(&mut self, plot_response: &PlotResponse<bool>, shift: bool) {
+        let response = &plot_response.response;
+        let pointer_bpm = response
+            .interact_pointer_pos()
+            .map(|position| plot_response.transform.value_from_position(position).x as f32);
+        if response.dragged() {
+            if let Some(bpm) = pointer_bpm {
+                if let Some((_, to)) = &mut self.excluded_range_drag {
+                    *to = bpm;
+                } else if shift {
+                    self.excluded_range_drag = Some((bpm, bpm));
+                }
+            }
+        } else if let Some((from, to)) = self.excluded_range_drag.take() {
+            self.exclude_range(from.min(to), from.max(to));
+        }
+
+        if response.secondary_clicked() {
+            if let Some(bpm) = pointer_bpm {
+                let excluded_ranges =
+                    &mut self.live_parameters.get_static_bpm_detection_parameters_mut().excluded_ranges;
+                let count = excluded_ranges.len();
+                excluded_ranges.retain(|&(low, high)| !(low.min(high)..=low.max(high)).contains(&bpm));
+                if excluded_ranges.len() != count {
+                    if let Err(e) = self.live_parameters.apply_static() {
+                        error!("could not remove the excluded range: {e:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    fn exclude_range(&mut self, low: f32, high: f32) {
+        // a click while holding shift
+        if high - low < MIN_EXCLUDED_RANGE {
+            return;
+        }
+        let mut excluded = self.live_parameters.get_static_bpm_detection_parameters().clone();
+        excluded.excluded_ranges.push((low, high));
+        if excluded.excludes_every_bin() {
+            self.status_message = Some("The excluded ranges can't cover the whole BPM range".to_string());
+            return;
+        }
+        self.live_parameters.get_static_bpm_detection_parameters_mut().excluded_ranges = excluded.excluded_ranges;
+        if let Err(e) = self.live_parameters.apply_static() {
+            error!("could not exclude the range: {e:?}");
+        }
+    }
+
+    fn attach_tapped_bpm(&self, plot_ui: &mut PlotUi) {
+        if let Some(tapped_bpm) = self.tapped_bpm() {
+            plot_ui.vline(
+                VLine::new(f64::from(tapped_bpm))
+                    .color(Color32::from_rgb(255, 120, 200))
+                    .width(1.5)
+                    .name(format!("Tapped, {tapped_bpm:.2} BPM")),
+            );
+        }
+    }
+
+    // the last tempo tapped in the GUI, or in the application when it reports one
+    fn tapped_bpm(&self) -> Option<f32> {
+        self.reference_bpm.or_else(|| self.tapped_bpm.upgrade().and_then(|tapped_bpm| *tapped_bpm.lock()))
+    }
+
+    // a tap starting a new sequence keeps the previous tempo shown until the next one
+    fn tap(&mut self) {
+        if let Some(bpm) = self.tap_tempo.tap(instant::Instant::now()) {
+            self.reference_bpm = Some(bpm);
+        }
+        if self.live_parameters.can_tap_tempo() {
+            self.live_parameters.tap_tempo();
+        }
+    }
+
+    // none when the metronome is off, a flash without intensity while there is no beat to predict
+    fn metronome_flash(&self) -> Option<Flash> {
+        let metronome = &self.live_parameters.get_gui_config().metronome;
+        if !metronome.enabled {
+            return None;
+        }
+        let beat_anchor = self.beat_anchor.upgrade().and_then(|beat_anchor| *beat_anchor.lock());
+        Some(beat_anchor.map_or(Flash { intensity: 0.0, next_in: None }, |beat_anchor| {
+            flash(&beat_anchor.phase, beat_anchor.received.elapsed().as_secs_f64(), metronome.downbeat_only)
+        }))
+    }
+
+    fn zoomed(&self) -> Option<StaticBPMDetectionParameters> {
+        self.auto_zoom.upgrade().and_then(|auto_zoom| auto_zoom.lock().clone())
+    }
+
+    // parameters and data points of the histogram around the estimate, in multi-resolution mode
+    fn fine_histogram(&self) -> Option<(StaticBPMDetectionParameters, Vec<f32>)> {
+        let multi_resolution = self.multi_resolution.upgrade()?;
+        let multi_resolution = multi_resolution.lock();
+        multi_resolution.as_ref()?.fine.clone()
+    }
+
+    // parameters a histogram of `len` bins was computed with: the coarse ones in multi-resolution mode, the narrowed
+    // ones only once the detection used them, at the resolution it capped them to
+    fn histogram_parameters(&self, len: usize) -> StaticBPMDetectionParameters {
+        let coarse_parameters = self.multi_resolution.upgrade().and_then(|multi_resolution| {
+            multi_resolution.lock().as_ref().map(|multi_resolution| multi_resolution.coarse_parameters.clone())
+        });
+        if let Some(coarse_parameters) = coarse_parameters.filter(|coarse| coarse.buffer_size() == len) {
+            return coarse_parameters;
+        }
+        let configured = self.live_parameters.get_static_bpm_detection_parameters().clone();
+        let effective_resolution =
+            self.resolution_floor().map(|resolution_floor| resolution_floor.effective_resolution);
+        self.zoomed()
+            .into_iter()
+            .chain([configured.clone()])
+            .flat_map(|parameters| {
+                let capped = effective_resolution.map(|histogram_resolution| StaticBPMDetectionParameters {
+                    histogram_resolution,
+                    ..parameters.clone()
+                });
+                [Some(parameters), capped]
+            })
+            .flatten()
+            .find(|parameters| parameters.buffer_size() == len)
+            .unwrap_or(configured)
+    }
+
+    fn resolution_floor(&self) -> Option<ResolutionFloor> {
+        self.resolution_floor.upgrade().and_then(|resolution_floor| *resolution_floor.lock())
+    }
+
+    // the histogram around the estimate, at the configured resolution, as bars normalized like the main ones
+    fn draw_fine_histogram(&self, ui: &mut Ui, fine_parameters: &StaticBPMDetectionParameters, fine: &[f32]) {
+        let gui_config = self.live_parameters.get_gui_config();
+        let (y_scale, log_scale_factor) = (gui_config.y_scale, gui_config.log_scale_factor);
+        let max_y = fine.iter().copied().fold(0.0, f32::max);
+        let width = f64::from((fine_parameters.index_to_bpm(0) - fine_parameters.index_to_bpm(1)).abs());
+        egui_plot::Plot::new("Fine BPMs")
+            .height(FINE_PLOT_HEIGHT)
+            .allow_zoom(false)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .include_x(fine_parameters.lowest_bpm())
+            .include_x(fine_parameters.highest_bpm())
+            .include_y(0.0)
+            .include_y(1.0)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(
+                    BarChart::new(
+                        fine.iter()
+                            .enumerate()
+                            .map(|(index, y)| {
+                                let y = if max_y > 0.0 { y_scale.to_display(y / max_y, log_scale_factor) } else { 0.0 };
+                                Bar::new(f64::from(fine_parameters.index_to_bpm(index)), f64::from(y))
+                                    .fill(Color32::from_rgb(255, 200, 80))
+                                    .width(width)
+                            })
+                            .collect(),
+                    )
+                    .element_formatter(Box::new(move |bar, _| {
+                        format!(
+                            "{:.2} BPM\n{:.3}",
+                            bar.argument,
+                            y_scale.from_display(bar.value as f32, log_scale_factor)
+                        )
+                    })),
+                );
+            });
     }
 
     #[minitrace::trace]
     fn draw_histogram(&mut self, ui: &mut Ui) -> PlotResponse<bool> {
-        egui_plot::Plot::new("BPMs")
-            .allow_zoom(true)
-            .allow_drag(true)
-            .allow_scroll(true)
-            .legend(Legend::default())
-            .show(ui, |plot_ui| self.attach_barchart(plot_ui).unwrap_or_default())
+        let dt = ui.ctx().input(|input| input.stable_dt);
+        let fine_histogram = self.fine_histogram();
+        ui.vertical(|ui| {
+            let mut y_scale = self.live_parameters.get_gui_config().y_scale;
+            ui.horizontal(|ui| {
+                for option in YScale::ALL {
+                    ui.selectable_value(&mut y_scale, option, option.label());
+                }
+            });
+            if y_scale != self.live_parameters.get_gui_config().y_scale {
+                self.live_parameters.get_gui_config_mut().y_scale = y_scale;
+                if let Err(e) = self.live_parameters.apply_gui() {
+                    error!("could not apply the Y scale: {e:?}");
+                }
+                self.ui_state.update(|ui_state| ui_state.y_scale = Some(y_scale), instant::Instant::now());
+            }
+            let log_scale_factor = self.live_parameters.get_gui_config().log_scale_factor;
+            // dragging with shift held excludes a range instead of moving the plot
+            let shift = ui.input(|input| input.modifiers.shift);
+
+            let mut plot = egui_plot::Plot::new("BPMs")
+                .allow_zoom(true)
+                .allow_drag(!shift && self.excluded_range_drag.is_none())
+                .allow_scroll(true)
+                .include_y(0.0)
+                .include_y(gui_config.y_headroom)
+                .label_formatter(move |_, point| {
+                    format!("{:.2} BPM\n{:.3}", point.x, y_scale.from_display(point.y as f32, log_scale_factor))
+                })
+                .legend(Legend::default());
+            if fine_histogram.is_some() {
+                let fine_plot_height = FINE_PLOT_HEIGHT + ui.spacing().item_spacing.y;
+                plot = plot.height((ui.available_height() - fine_plot_height).max(FINE_PLOT_HEIGHT));
+            }
+            let plot_response = plot.show(ui, |plot_ui| {
+                self.attach_excluded_ranges(plot_ui);
+                let still_moving = self.attach_barchart(plot_ui, dt).unwrap_or_default();
+                self.attach_frozen_histogram(plot_ui);
+                self.attach_tapped_bpm(plot_ui);
+                still_moving
+            });
+            self.edit_excluded_ranges(&plot_response, shift);
+            if let Some((fine_parameters, fine)) = &fine_histogram {
+                self.draw_fine_histogram(ui, fine_parameters, fine);
+            }
+            plot_response
+        })
+        .inner
     }
 }
 
@@ -132,30 +593,76 @@ pub struct UpdateError;
 
 impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
     pub fn update(&mut self, ctx: &Context) -> Result<(), UpdateError> {
-        let (Some(estimated_bpm), Some(daw_bpm), Some(should_save)) =
-            (self.estimated_bpm.upgrade(), self.daw_bpm.upgrade(), self.should_save.upgrade())
-        else {
+        let (Some(estimated_bpm), Some(daw_bpm), Some(should_save), Some(should_export_snapshot)) = (
+            self.estimated_bpm.upgrade(),
+            self.daw_bpm.upgrade(),
+            self.should_save.upgrade(),
+            self.should_export_snapshot.upgrade(),
+        ) else {
             error!("shared data weak references are gone");
             return Err(UpdateError);
         };
 
+        self.live_parameters.pull_changes();
+
         if should_save.swap(false, Ordering::Relaxed) {
             self.live_parameters.save();
         }
 
+        self.apply_window_behavior(ctx);
+
+        if let Some(phase_split_window) = self.phase_split_window.upgrade() {
+            *phase_split_window.lock() = self.live_parameters.get_gui_config().phase_split();
+        }
+
         let Some(sender) = self.keys_sender.upgrade().log_info_msg("key sender weak ref is gone") else {
             return Err(UpdateError);
         };
 
-        if let Some(sender) = sender.lock().as_mut() {
+        let mut taps = 0;
+        {
+            let mut sender = sender.lock();
             ctx.input(|input| {
                 for events in &input.events {
-                    if let Event::Key { key, modifiers: _, pressed: true, .. } = events {
-                        sender(key.name());
+                    if let Event::Key { key, modifiers, pressed: true, repeat, .. } = events {
+                        // shift+T taps the tempo as in the TUI, the application gets the tap through `tap_tempo`, not the key
+                        if *key == egui::Key::T && modifiers.shift {
+                            taps += usize::from(!repeat);
+                        } else if let Some(sender) = sender.as_mut() {
+                            sender(key.name());
+                        }
                     };
                 }
             });
         }
+        for _ in 0..taps {
+            self.tap();
+        }
+
+        let mut export_snapshot = should_export_snapshot.swap(false, Ordering::Relaxed);
+        let mut tap_clicked = false;
+
+        if self.live_parameters.get_gui_config().show_note_strip {
+            if let Some(note_history) = self.note_history.upgrade() {
+                egui::TopBottomPanel::bottom("note_strip").show(ctx, |ui| {
+                    note_strip(ui, &note_history.lock(), estimated_bpm.load(Ordering::Relaxed));
+                });
+            }
+        }
+
+        let (drift_tolerance, show_drift_trend) = {
+            let gui_config = self.live_parameters.get_gui_config();
+            (gui_config.drift_tolerance, gui_config.show_drift_trend)
+        };
+        let drift = self.drift_tracker.update(
+            estimated_bpm.load(Ordering::Relaxed),
+            daw_bpm.load(Ordering::Relaxed),
+            drift_tolerance,
+            ctx.input(|input| input.time),
+        );
+
+        let metronome_flash = self.metronome_flash();
+        let metronome_color = self.live_parameters.get_gui_config().metronome.color;
 
         let refresh = egui::CentralPanel::default()
             .show(ctx, |ui| {
@@ -163,30 +670,362 @@ impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
                     .horizontal_top(|ui| {
                         ui.vertical(|ui| {
                             ui.add_space(10.0);
-                            Self::legend(&estimated_bpm, &daw_bpm, ui);
+                            let bar_position =
+                                self.bar_position.upgrade().and_then(|bar_position| *bar_position.lock());
+                            let frozen_bpm = self.frozen_histogram.upgrade().and_then(|frozen_histogram| {
+                                frozen_histogram.lock().as_ref().map(|frozen_histogram| frozen_histogram.bpm)
+                            });
+                            let estimated_confidence = self
+                                .estimated_confidence
+                                .upgrade()
+                                .map_or(f32::NAN, |estimated_confidence| estimated_confidence.load(Ordering::Relaxed));
+                            let clock_bpm = self.clock_bpm.upgrade().and_then(|clock_bpm| *clock_bpm.lock());
+                            Self::legend(
+                                &estimated_bpm,
+                                estimated_confidence,
+                                &daw_bpm,
+                                clock_bpm,
+                                self.tapped_bpm(),
+                                frozen_bpm,
+                                drift,
+                                show_drift_trend,
+                                bar_position,
+                                metronome_flash.map(|flash| (metronome_color, flash.intensity)),
+                                ui,
+                            );
+                            ui.horizontal(|ui| {
+                                let hover_text = if self.live_parameters.can_tap_tempo() {
+                                    "Tap the tempo, or press shift+T. It is shown over the histogram, and favoured \
+                                     until the detection agrees"
+                                } else {
+                                    "Tap the tempo, or press shift+T. It is shown over the histogram"
+                                };
+                                if ui.button("Tap").on_hover_text(hover_text).clicked() {
+                                    tap_clicked = true;
+                                }
+                                let stability = self
+                                    .session_summary
+                                    .upgrade()
+                                    .and_then(|session_summary| session_summary.lock().stability);
+                                if let Some(stability) = stability {
+                                    ui.label(format!("stability: {stability:.0}")).on_hover_text(
+                                        "Steadiness of the detected tempo since the session started, from 0 to 100",
+                                    );
+                                }
+                                if self.live_parameters.can_freeze_notes() {
+                                    let frozen = frozen_bpm.is_some();
+                                    if ui
+                                        .button(if frozen { "Clear frozen input" } else { "Freeze input" })
+                                        .on_hover_text(
+                                            "Keep the current notes as a reference, the parameter changes are also \
+                                             evaluated on them",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.live_parameters.set_notes_frozen(!frozen);
+                                    }
+                                }
+                            });
+                            let feel_ambiguity =
+                                self.feel_ambiguity.upgrade().and_then(|feel_ambiguity| *feel_ambiguity.lock());
+                            if let Some(feel_ambiguity) = feel_ambiguity {
+                                ui.horizontal(|ui| {
+                                    ui.label("Feel:").on_hover_text(format!(
+                                        "The notes support a straight and a triplet feel, the other tempo at {:.0}% \
+                                         of the estimate",
+                                        feel_ambiguity.ratio_strength * 100.0
+                                    ));
+                                    for feel in Feel::ALL {
+                                        let text =
+                                            format!("{:.1} {}", feel_ambiguity.bpm(feel), feel.name().to_lowercase());
+                                        if !self.live_parameters.can_choose_feel() {
+                                            ui.label(text);
+                                        } else if ui
+                                            .button(text)
+                                            .on_hover_text("Favour this tempo until the detection agrees")
+                                            .clicked()
+                                        {
+                                            self.live_parameters.choose_feel(feel);
+                                        }
+                                    }
+                                });
+                            }
+                            if let Some(tempo_band) =
+                                self.tempo_band.upgrade().and_then(|tempo_band| *tempo_band.lock())
+                            {
+                                ui.label(format!("Tempo band: {tempo_band}"))
+                                    .on_hover_text("Sent on the MIDI output when the tempo entered this band");
+                            }
+                            if self.live_parameters.is_detection_bypassed() {
+                                ui.colored_label(Color32::YELLOW, "Detection bypassed");
+                            }
+                            if let Some(safe_mode_notice) = self.live_parameters.get_safe_mode_notice() {
+                                ui.colored_label(Color32::YELLOW, safe_mode_notice);
+                            }
+                            let rate_limited_notes = self.live_parameters.get_rate_limited_notes();
+                            if rate_limited_notes > 0 {
+                                ui.label(format!("Rate limited notes: {rate_limited_notes}"));
+                            }
+                            let auto_velocity_gate =
+                                self.live_parameters.get_dynamic_bpm_detection_parameters().auto_velocity_gate;
+                            if matches!(auto_velocity_gate, OnOff::On(_)) {
+                                let velocity_gate =
+                                    self.velocity_gate.upgrade().and_then(|threshold| *threshold.lock());
+                                let velocity_gate = velocity_gate
+                                    .map_or_else(|| "none".to_string(), |threshold| format!("below {threshold}"));
+                                ui.label(format!("Learned velocity gate: {velocity_gate}"))
+                                    .on_hover_text("Notes softer than that are ignored, learned from the recent notes");
+                            }
+                            if let Some(tempo_latency) = self.live_parameters.get_tempo_latency() {
+                                ui.label(format!("Tempo latency: {tempo_latency}"))
+                                    .on_hover_text("From the newest note of an evaluation to the tempo being sent");
+                            }
+                            let note_freshness =
+                                self.note_freshness.upgrade().and_then(|note_freshness| *note_freshness.lock());
+                            if let Some(note_freshness) = note_freshness {
+                                ui.label(format!("History: {:.0}% recent", note_freshness * 100.0)).on_hover_text(
+                                    "Share of the notes within the lookback window played since the last tempo \
+                                     change, the estimate fully reflects the recent playing at 100%",
+                                );
+                            }
+                            if let Some(clock_lookahead) = self.live_parameters.get_clock_lookahead() {
+                                ui.label(format!("Clock lookahead: {:.1} ms", clock_lookahead.as_secs_f64() * 1000.0))
+                                    .on_hover_text(
+                                        "The MIDI clock runs ahead of the beat while the tempo is steady, and on the \
+                                         beat while it changes",
+                                    );
+                            }
+                            if let Some(resolution_floor) = self.resolution_floor() {
+                                ui.label(format!(
+                                    "Input precision: {:.2} ms",
+                                    resolution_floor.precision.as_secs_f64() * 1000.0
+                                ))
+                                .on_hover_text(
+                                    "Measured on the timestamps of the notes received, from their quantization and \
+                                     their jitter",
+                                );
+                                let configured = self
+                                    .zoomed()
+                                    .unwrap_or_else(|| {
+                                        self.live_parameters.get_static_bpm_detection_parameters().clone()
+                                    })
+                                    .histogram_resolution;
+                                if resolution_floor.effective_resolution < configured {
+                                    ui.label(format!(
+                                        "Histogram resolution capped at {} bins/s",
+                                        resolution_floor.effective_resolution
+                                    ))
+                                    .on_hover_text(
+                                        "Bins finer than the precision of the notes would only spread them over more \
+                                         bins, raising the resolution further has no effect. The floor is set by \
+                                         resolution_floor_factor",
+                                    );
+                                }
+                            }
                             ui.add_space(20.0);
-                            self.settings_panel(ui);
+                            let config_warnings = self
+                                .config_warnings
+                                .upgrade()
+                                .map(|config_warnings| config_warnings.lock().clone())
+                                .unwrap_or_default();
+                            render_settings_panel(
+                                ui,
+                                &mut self.live_parameters,
+                                &PanelOptions::new()
+                                    .with_config_warnings(config_warnings)
+                                    .with_ui_state(self.ui_state.clone()),
+                            );
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                if ui.button("Export snapshot").clicked() {
+                                    export_snapshot = true;
+                                }
+                                if ui.button("Show effective config").clicked() {
+                                    self.effective_config = Some(
+                                        self.live_parameters
+                                            .effective_config()
+                                            .unwrap_or_else(|| "Not available".to_string()),
+                                    );
+                                }
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if ui
+                                    .button("Debug logs")
+                                    .on_hover_text("Log at debug level for a while, as after an error")
+                                    .clicked()
+                                {
+                                    let window = errors::error_debug_window();
+                                    self.status_message = Some(if errors::elevate_logging(window) {
+                                        format!("Debug logs for the next {} s", window.as_secs())
+                                    } else {
+                                        "No log file to write debug logs to".to_string()
+                                    });
+                                }
+                            });
+                            if let Some(status_message) = &self.status_message {
+                                ui.label(status_message);
+                            }
 
                             let available_size = ui.available_size();
                             ui.add_space(available_size.y - ui.spacing().interact_size.y);
 
                             ui.horizontal(|ui| {
                                 ui.label(BUILD_TIME);
+                                if ui.small_button("ℹ").on_hover_text("About").clicked() {
+                                    self.show_about = true;
+                                }
                             });
                         });
-                        self.draw_histogram(ui).inner
+                        let plot_response = self.draw_histogram(ui);
+                        self.plot_rect = Some(plot_response.response.rect);
+                        plot_response.inner
                     })
                     .inner;
                 refresh
             })
             .inner;
+        about_window(ctx, &mut self.show_about, &self.about_info);
+        if !self.ui_state.onboarding_dismissed() {
+            let notes_received =
+                self.note_history.upgrade().is_some_and(|note_history| note_history.lock().latest().is_some());
+            let progress = OnboardingProgress::new(
+                self.live_parameters.get_midi_inputs().as_ref(),
+                notes_received,
+                estimated_bpm.load(Ordering::Relaxed),
+            );
+            onboarding_window(ctx, &self.ui_state, progress, self.live_parameters.midi_input_step());
+        }
+        effective_config_window(ctx, &mut self.effective_config);
+        if export_snapshot {
+            self.export_snapshot(ctx);
+        }
+        if tap_clicked {
+            self.tap();
+            ctx.request_repaint();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        self.save_screenshot(ctx);
         if refresh {
             ctx.request_repaint();
         }
+        // repaints follow the flash while it fades, then wait for the next beat
+        if let Some(metronome_flash) = metronome_flash {
+            if metronome_flash.is_visible() {
+                ctx.request_repaint();
+            } else if let Some(next_in) = metronome_flash.next_in {
+                ctx.request_repaint_after(Duration::from_secs_f64(next_in));
+            }
+        }
+        self.save_ui_state(ctx);
         Ok(())
     }
 }
 
+impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
+    // once the layout settled, a repaint is requested for then as nothing else may trigger one
+    fn save_ui_state(&mut self, ctx: &Context) {
+        let now = instant::Instant::now();
+        if let Some(ui_state) = self.ui_state.take_due(now) {
+            self.live_parameters.save_ui_state(&ui_state);
+        } else if let Some(save_delay) = self.ui_state.save_delay(now) {
+            ctx.request_repaint_after(save_delay);
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn apply_window_behavior(&mut self, ctx: &Context) {
+        if self.should_cycle_always_on_top.upgrade().is_some_and(|cycle| cycle.swap(false, Ordering::Relaxed)) {
+            let window_behavior = &mut self.live_parameters.get_gui_config_mut().window_behavior;
+            window_behavior.always_on_top = window_behavior.always_on_top.next();
+            self.status_message = Some(format!("Always on top: {}", window_behavior.always_on_top.label()));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let tui_focused = self.tui_focused.upgrade().is_some_and(|tui_focused| tui_focused.load(Ordering::Relaxed));
+            let always_on_top = self.live_parameters.get_gui_config().window_behavior.always_on_top;
+            if let Some(on_top) = self.window_level_state.update(always_on_top, tui_focused) {
+                ctx.send_viewport_cmd(ViewportCommand::WindowLevel(if on_top {
+                    WindowLevel::AlwaysOnTop
+                } else {
+                    WindowLevel::Normal
+                }));
+            }
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn export_snapshot(&mut self, ctx: &Context) {
+        let Some(histogram_data_points) =
+            self.histogram_data_points.upgrade().log_error_msg("histogram_data_points weak reference is gone")
+        else {
+            return;
+        };
+        let Ok(histogram_data_points) = histogram_data_points.try_borrow() else {
+            self.status_message = Some("Histogram is being updated, please try again".to_string());
+            return;
+        };
+        let static_bpm_detection_parameters =
+            &self.histogram_parameters(histogram_data_points.inbound_histogram_data_points.len());
+        let file_stem = snapshot_file_stem(&chrono::Local::now(), static_bpm_detection_parameters);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match export_csv(
+            &snapshots_dir(),
+            &file_stem,
+            static_bpm_detection_parameters,
+            &histogram_data_points.inbound_histogram_data_points,
+        ) {
+            Ok(path) => {
+                self.status_message = Some(format!("Snapshot saved to {}", path.display()));
+                if let Some(plot_rect) = self.plot_rect {
+                    self.pending_screenshot = Some((path.with_extension("png"), plot_rect));
+                    ctx.send_viewport_cmd(ViewportCommand::Screenshot);
+                }
+            }
+            Err(e) => {
+                error!("could not export snapshot: {e:?}");
+                self.status_message = Some(format!("Could not export snapshot: {e}"));
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        if let Err(e) = download_csv(
+            &file_stem,
+            static_bpm_detection_parameters,
+            &histogram_data_points.inbound_histogram_data_points,
+        ) {
+            error!("could not export snapshot: {e:?}");
+            self.status_message = Some(format!("Could not export snapshot: {e}"));
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_screenshot(&mut self, ctx: &Context) {
+        if self.pending_screenshot.is_none() {
+            return;
+        }
+        let Some(screenshot) = ctx.input(|input| {
+            input.events.iter().find_map(|event| match event {
+                Event::Screenshot { image, .. } => Some(image.clone()),
+                _ => None,
+            })
+        }) else {
+            return;
+        };
+        let Some((path, plot_rect)) = self.pending_screenshot.take() else {
+            return;
+        };
+
+        let plot = screenshot.region(&plot_rect, Some(ctx.pixels_per_point()));
+        if let Err(e) =
+            image::save_buffer(&path, plot.as_raw(), plot.width() as u32, plot.height() as u32, image::ColorType::Rgba8)
+        {
+            error!("could not save snapshot screenshot: {e:?}");
+            self.status_message = Some(format!("Could not save snapshot screenshot: {e}"));
+        }
+    }
+}
+
 impl<P: BPMDetectionParameters> eframe::App for BPMDetectionGUI<P> {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         self.update(ctx).ok();
@@ -194,6 +1033,11 @@ impl<P: BPMDetectionParameters> eframe::App for BPMDetectionGUI<P> {
 
     #[cfg(not(target_arch = "wasm32"))]
     fn on_exit(&mut self) {
+        // a change that had no time to settle
+        if let Some(ui_state) = self.ui_state.take_due(instant::Instant::now() + SAVE_DEBOUNCE) {
+            self.live_parameters.save_ui_state(&ui_state);
+        }
+
         let Some(on_gui_exit_callback) =
             self.on_gui_exit_callback.upgrade().log_error_msg("gui exit callback weakref is gone")
         else {
@@ -208,8 +1052,24 @@ impl<P: BPMDetectionParameters> eframe::App for BPMDetectionGUI<P> {
     }
 }
 
+#[allow(forbidden_lint_groups)]
+#[allow(clippy::too_many_arguments)]
 impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
-    fn legend(estimated_bpm: &AtomicF32, daw_bpm: &AtomicF32, ui: &mut Ui) {
+    fn legend(
+        estimated_bpm: &AtomicF32,
+        // not a number until a first estimate
+        estimated_confidence: f32,
+        daw_bpm: &AtomicF32,
+        clock_bpm: Option<f32>,
+        tapped_bpm: Option<f32>,
+        frozen_bpm: Option<f32>,
+        drift: Option<Drift>,
+        show_drift_trend: bool,
+        bar_position: Option<BarPosition>,
+        // color and intensity, when the metronome is on
+        metronome_flash: Option<([u8; 3], f32)>,
+        ui: &mut Ui,
+    ) {
         let to_text = |bpm: &AtomicF32| {
             let bpm = bpm.load(Ordering::Relaxed);
             if bpm.is_nan() {
@@ -226,11 +1086,72 @@ impl<P: BPMDetectionParameters> BPMDetectionGUI<P> {
                 let bpm_text = RichText::new(bpm_text).size(20.0).monospace();
                 ui.label(bpm_text);
             });
+            if let Some(clock_bpm) = clock_bpm {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Clock BPM    ").size(20.0).monospace());
+                    ui.label(RichText::new(format!("{clock_bpm:>6.2}")).size(20.0).monospace())
+                        .on_hover_text("Tempo of the MIDI clock received, it doesn't take part in the detection");
+                });
+            }
+            if let Some(tapped_bpm) = tapped_bpm {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Tapped BPM   ").size(20.0).monospace());
+                    ui.label(RichText::new(format!("{tapped_bpm:>6.2}")).size(20.0).monospace())
+                        .on_hover_text("Tempo tapped with the Tap button or shift+T, marked over the histogram");
+                });
+            }
             ui.horizontal(|ui| {
                 ui.label(RichText::new("Estimated BPM").size(20.0).monospace());
                 let bpm_text = to_text(estimated_bpm);
                 let bpm_text = RichText::new(bpm_text).size(20.0).monospace();
                 ui.label(bpm_text);
+                if !estimated_confidence.is_nan() {
+                    ui.label(RichText::new(format!("{:>3.0}%", estimated_confidence * 100.0)).size(14.0).monospace())
+                        .on_hover_text(
+                            "Confidence: how far the peak of the estimate stands above the next highest one of the \
+                             histogram",
+                        );
+                }
+            });
+            if let Some(frozen_bpm) = frozen_bpm {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Frozen input BPM").size(14.0).monospace());
+                    ui.label(RichText::new(format!("{frozen_bpm:>6.2}")).size(14.0).monospace())
+                        .on_hover_text("Estimated from the frozen notes, with the current parameters");
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Drift        ").size(20.0).monospace());
+                let Some(drift) = drift else {
+                    ui.label(RichText::new(format!("{:>6.2}", "-")).size(20.0).monospace());
+                    return;
+                };
+                let color = match drift.level {
+                    DriftLevel::Within => Color32::GREEN,
+                    DriftLevel::Beyond => Color32::YELLOW,
+                    DriftLevel::Far => Color32::RED,
+                };
+                let drift_text = format!("{:>+6.2} ({:+.1}%)", drift.bpm, drift.percent);
+                ui.label(RichText::new(drift_text).size(20.0).monospace().color(color))
+                    .on_hover_text("Estimated BPM minus DAW BPM");
+                if show_drift_trend {
+                    let (arrow, hover_text) = match drift.trend {
+                        Trend::Toward => ("↘", "Getting closer to the DAW tempo"),
+                        Trend::Steady => ("→", "Steady"),
+                        Trend::Away => ("↗", "Moving away from the DAW tempo"),
+                    };
+                    ui.label(RichText::new(arrow).size(20.0)).on_hover_text(hover_text);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Position     ").size(20.0).monospace());
+                let position_text =
+                    bar_position.map_or_else(|| "-".to_string(), |bar_position| bar_position.to_string());
+                ui.label(RichText::new(position_text).size(20.0).monospace())
+                    .on_hover_text("Counted from the detected tempo, starts over after a pause");
+                if let Some((color, intensity)) = metronome_flash {
+                    flash_circle(ui, color, intensity);
+                }
             });
         });
     }
@@ -1,6 +1,20 @@
-use crate::config::GUIConfig;
-use midi::{DynamicBPMDetectionParameters, NormalDistributionConfig, StaticBPMDetectionParameters};
-use std::fmt::Debug;
+use crate::{config::GUIConfig, ui_state::UiState, ConfigPaths};
+use midi::{
+    DynamicBPMDetectionParameters, Feel, LatencySummary, NormalDistributionConfig, StaticBPMDetectionParameters,
+    TempoSource,
+};
+use std::{fmt::Debug, time::Duration};
+
+/// MIDI inputs to pick the one the detection listens to from
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MidiInputs {
+    // id and name of each input
+    pub inputs: Vec<(String, String)>,
+    pub selected: Option<String>,
+    // why there is nothing to pick from, such as a denied permission or no device plugged in, or why the selected
+    // input can't be listened to
+    pub message: Option<String>,
+}
 
 pub trait BPMDetectionParameters {
     type Error: Debug;
@@ -19,7 +33,84 @@ pub trait BPMDetectionParameters {
     fn get_gui_config_mut(&mut self) -> &mut GUIConfig;
     fn get_send_tempo(&self) -> bool;
     fn set_send_tempo(&mut self, enabled: bool);
+    // tempo that is sent, for applications that can also follow the DAW tempo
+    fn get_tempo_source(&self) -> Option<TempoSource> {
+        None
+    }
+    fn set_tempo_source(&mut self, _tempo_source: TempoSource) {}
+    // notes dropped by the note-on flood protection
+    fn get_rate_limited_notes(&self) -> u64 {
+        0
+    }
+    // from the newest note of an evaluation to the tempo being sent, for applications that send it
+    fn get_tempo_latency(&self) -> Option<LatencySummary> {
+        None
+    }
+    // how far ahead of the beat the MIDI clock runs, for applications that send one with a lookahead, see
+    // `ClockLookaheadConfig`
+    fn get_clock_lookahead(&self) -> Option<Duration> {
+        None
+    }
+    // notes are ignored and no tempo is sent, for applications that can pause the detection
+    fn is_detection_bypassed(&self) -> bool {
+        false
+    }
+    // for applications that accept a tapped tempo, see `TapTempo`
+    fn can_tap_tempo(&self) -> bool {
+        false
+    }
+    fn tap_tempo(&mut self) {}
+    // for applications that can favour the candidate picked among the ones of a `FeelAmbiguity`, as a tapped tempo is
+    fn can_choose_feel(&self) -> bool {
+        false
+    }
+    fn choose_feel(&mut self, _feel: Feel) {}
+    // for applications that can keep the notes as a reference the detection is also evaluated on, see
+    // `FrozenReference`
+    fn can_freeze_notes(&self) -> bool {
+        false
+    }
+    fn set_notes_frozen(&mut self, _frozen: bool) {}
+    // for applications that let the GUI pick the MIDI input, such as the browser through Web MIDI or the desktop app
+    // without the TUI
+    fn get_midi_inputs(&self) -> Option<MidiInputs> {
+        None
+    }
+    fn select_midi_input(&mut self, _id: &str) {}
+    // first of the getting started steps, how notes reach the application
+    fn midi_input_step(&self) -> &'static str {
+        "Connect a MIDI input"
+    }
+    // explains that the saved configuration was left out, for applications that can start in safe mode
+    fn get_safe_mode_notice(&self) -> Option<String> {
+        None
+    }
+    // configuration in use along with the origin of its values, see `effective_config::Provenance::render`
+    fn effective_config(&self) -> Option<String> {
+        None
+    }
+    // values a host modulates the parameters to, by label, for the ones differing from the value set, marked over the
+    // sliders
+    fn get_modulated_values(&self) -> Vec<(String, f64)> {
+        Vec::new()
+    }
+    // called before each frame, for applications whose parameters can also be changed outside the GUI
+    fn pull_changes(&mut self) {}
     fn apply_static(&mut self) -> Result<(), Self::Error>;
     fn apply_dynamic(&mut self) -> Result<(), Self::Error>;
+    // the GUI config only changes how the estimates are shown, the detection must not be updated for it
+    fn apply_gui(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
     fn save(&mut self) {}
+    // layout of the GUI as last saved, the default layout is used if none
+    fn load_ui_state(&self) -> Option<UiState> {
+        None
+    }
+    // called once the layout settled after a change, not by `save`, it is not part of the configuration
+    fn save_ui_state(&mut self, _ui_state: &UiState) {}
+    // shown in the about dialog, if the application stores anything on disk
+    fn config_paths(&self) -> Option<ConfigPaths> {
+        None
+    }
 }
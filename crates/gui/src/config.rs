@@ -1,3 +1,4 @@
+use midi::{parameter_descriptors, ParameterDescriptor};
 use parameter::{MutGetters, Parameter};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, time::Duration};
@@ -8,10 +9,146 @@ use std::{fmt::Debug, time::Duration};
 pub struct GUIConfig {
     pub interpolation_duration: Duration,
 
-    // since we only keep interpolating value, the interpolation will seem to 'accelerate' towards the end
-    // of the interval a factor of 1 will preserve this behaviour. factor < 1 will make the movement 'slower',
-    // factor > 1 will accelerate it
+    // the histogram bars are exponentially smoothed towards their target on each frame, so they cover most of the
+    // distance within `interpolation_duration`. A factor of 1 keeps this duration, factor < 1 will make the movement
+    // 'slower', factor > 1 will accelerate it
     pub interpolation_curve: f32,
+
+    // when the static parameters change the axis of the histogram, the displayed bars are moved onto the new axis and
+    // morph into the new histogram, instead of starting over
+    pub morph_on_reconfigure: bool,
+
+    pub window_behavior: WindowBehavior,
+
+    // notes plotted against the grid of the detected tempo, below the histogram
+    pub show_note_strip: bool,
+
+    pub y_scale: YScale,
+    // the top of the plot is that many times higher than the tallest bar
+    pub y_headroom: f32,
+    // how much the log scale expands low values, relative to the tallest bar
+    pub log_scale_factor: f32,
+
+    // difference between the detected and the DAW tempo, in BPM, still shown as matching
+    pub drift_tolerance: f32,
+    // an arrow next to the drift tells whether the detected tempo gets closer to the DAW tempo
+    pub show_drift_trend: bool,
+
+    pub metronome: MetronomeConfig,
+
+    // the bars are stacked by whether the newer note of each interval fell on a beat of the estimated grid, on-beat
+    // at the bottom and off-beat on top. Only once a beat count is running
+    pub phase_split_view: bool,
+    // a note closer than that to a beat, in beats, is on-beat
+    pub phase_split_window: f32,
+
+    // set by the application, the histogram buffers are then allocated at the size received
+    #[serde(skip)]
+    pub low_memory: bool,
+}
+
+/// Scale of the histogram bars, which are normalized to the tallest one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum YScale {
+    #[default]
+    Linear,
+    // makes small secondary peaks visible
+    Log,
+}
+
+impl YScale {
+    pub const ALL: [Self; 2] = [Self::Linear, Self::Log];
+
+    /// Height of a bar whose normalized value is `value`. Log maps through `log10(1 + value * log_scale_factor)`,
+    /// divided so 1 stays 1
+    #[must_use]
+    pub fn to_display(self, value: f32, log_scale_factor: f32) -> f32 {
+        match self {
+            Self::Log if log_scale_factor > 0.0 => (value * log_scale_factor).ln_1p() / log_scale_factor.ln_1p(),
+            Self::Linear | Self::Log => value,
+        }
+    }
+
+    /// Inverse of `to_display`, so hovering a bar reports its value whatever the scale
+    #[must_use]
+    pub fn from_display(self, display: f32, log_scale_factor: f32) -> f32 {
+        match self {
+            Self::Log if log_scale_factor > 0.0 => (display * log_scale_factor.ln_1p()).exp_m1() / log_scale_factor,
+            Self::Linear | Self::Log => display,
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Log => "Log",
+        }
+    }
+}
+
+/// Visual pulse on the beats predicted from the detected tempo and the beat count
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetronomeConfig {
+    // a circle next to the position flashes on each predicted beat
+    pub enabled: bool,
+    pub color: [u8; 3],
+    // only the first beat of each bar flashes
+    pub downbeat_only: bool,
+}
+
+impl Default for MetronomeConfig {
+    fn default() -> Self {
+        Self { enabled: false, color: [255, 200, 80], downbeat_only: false }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowBehavior {
+    pub always_on_top: AlwaysOnTop,
+}
+
+/// When the GUI window is kept above the other windows. Only applies to the native window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlwaysOnTop {
+    Always,
+    // follows the focus of the terminal running the TUI, so the histogram stays visible while using it
+    #[default]
+    WhenTuiFocused,
+    Never,
+}
+
+impl AlwaysOnTop {
+    pub const ALL: [Self; 3] = [Self::Always, Self::WhenTuiFocused, Self::Never];
+
+    #[must_use]
+    pub fn is_on_top(self, tui_focused: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::WhenTuiFocused => tui_focused,
+            Self::Never => false,
+        }
+    }
+
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Always => Self::WhenTuiFocused,
+            Self::WhenTuiFocused => Self::Never,
+            Self::Never => Self::Always,
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Always => "Always",
+            Self::WhenTuiFocused => "When TUI is focused",
+            Self::Never => "Never",
+        }
+    }
 }
 
 impl Default for GUIConfig {
@@ -19,11 +156,25 @@ impl Default for GUIConfig {
         Self {
             interpolation_duration: Self::INTERPOLATION_DURATION.default,
             interpolation_curve: Self::INTERPOLATION_CURVE.default,
+            morph_on_reconfigure: true,
+            window_behavior: WindowBehavior::default(),
+            show_note_strip: false,
+            y_scale: YScale::default(),
+            y_headroom: 1.1,
+            log_scale_factor: 100.0,
+            drift_tolerance: Self::DRIFT_TOLERANCE.default,
+            show_drift_trend: true,
+            metronome: MetronomeConfig::default(),
+            phase_split_view: false,
+            phase_split_window: Self::PHASE_SPLIT_WINDOW.default,
+            low_memory: false,
         }
     }
 }
 
 impl GUIConfig {
+    pub const DRIFT_TOLERANCE: Parameter<Self, f32> =
+        Parameter::new("Drift tolerance", Some("BPM"), 0.1..=10.0, 0.0, false, 1.0, Self::drift_tolerance_mut);
     pub const INTERPOLATION_CURVE: Parameter<Self, f32> =
         Parameter::new("Interpolation curve", None, 0.1..=2.0, 0.0, false, 0.7, Self::interpolation_curve_mut);
     pub const INTERPOLATION_DURATION: Parameter<Self, Duration> = Parameter::new(
@@ -35,4 +186,127 @@ impl GUIConfig {
         Duration::from_millis(500),
         Self::interpolation_duration_mut,
     );
+    pub const PHASE_SPLIT_WINDOW: Parameter<Self, f32> =
+        Parameter::new("Phase split window", Some("beats"), 0.01..=0.5, 0.0, false, 0.1, Self::phase_split_window_mut);
+
+    /// Window the detection splits the histogram with, none when the split view is off
+    #[must_use]
+    pub fn phase_split(&self) -> Option<f32> {
+        self.phase_split_view.then_some(self.phase_split_window)
+    }
+}
+
+/// Every parameter, the ones of the detection and the ones of the GUI
+#[must_use]
+pub fn parameter_catalog() -> Vec<ParameterDescriptor> {
+    let mut catalog = midi::parameters::catalog();
+    catalog.extend(parameter_descriptors![
+        "GUI.interpolation_duration" ("interpolation_duration") => GUIConfig::INTERPOLATION_DURATION,
+        "GUI.interpolation_curve" ("interpolation_curve") => GUIConfig::INTERPOLATION_CURVE,
+        "GUI.drift_tolerance" => GUIConfig::DRIFT_TOLERANCE,
+        "GUI.phase_split_window" => GUIConfig::PHASE_SPLIT_WINDOW,
+    ]);
+    catalog
+}
+
+/// Level the window was last set to, so a viewport command is only sent when the policy asks for a change
+#[derive(Debug, Default)]
+pub(crate) struct WindowLevelState {
+    on_top: bool,
+}
+
+impl WindowLevelState {
+    /// Returns the new level to apply, if it differs from the current one
+    pub(crate) fn update(&mut self, always_on_top: AlwaysOnTop, tui_focused: bool) -> Option<bool> {
+        let on_top = always_on_top.is_on_top(tui_focused);
+        (on_top != self.on_top).then(|| {
+            self.on_top = on_top;
+            on_top
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // commands sent after each focus event
+    fn commands(always_on_top: AlwaysOnTop, tui_focus_events: &[bool]) -> Vec<Option<bool>> {
+        let mut window_level_state = WindowLevelState::default();
+        tui_focus_events.iter().map(|tui_focused| window_level_state.update(always_on_top, *tui_focused)).collect()
+    }
+
+    #[test]
+    fn test_always_on_top_policies() {
+        let focus_events = [true, false, false, true, true, false];
+
+        assert_eq!(commands(AlwaysOnTop::Always, &focus_events), vec![Some(true), None, None, None, None, None]);
+        assert_eq!(commands(AlwaysOnTop::Never, &focus_events), vec![None; 6]);
+        assert_eq!(
+            commands(AlwaysOnTop::WhenTuiFocused, &focus_events),
+            vec![Some(true), Some(false), None, Some(true), None, Some(false)]
+        );
+    }
+
+    #[test]
+    fn test_policy_change_while_focused() {
+        let mut window_level_state = WindowLevelState::default();
+        assert_eq!(window_level_state.update(AlwaysOnTop::WhenTuiFocused, true), Some(true));
+        assert_eq!(window_level_state.update(AlwaysOnTop::Always, true), None);
+        assert_eq!(window_level_state.update(AlwaysOnTop::Never, true), Some(false));
+        assert_eq!(window_level_state.update(AlwaysOnTop::Always, false), Some(true));
+    }
+
+    const LOG_SCALE_FACTORS: [f32; 4] = [0.5, 9.0, 100.0, 10_000.0];
+
+    fn values() -> impl Iterator<Item = f32> {
+        (0..=1000).map(|step| step as f32 / 1000.0)
+    }
+
+    #[test]
+    fn test_y_scale_monotonicity() {
+        for y_scale in YScale::ALL {
+            for log_scale_factor in LOG_SCALE_FACTORS {
+                let displayed = values().map(|value| y_scale.to_display(value, log_scale_factor)).collect::<Vec<_>>();
+                assert!(displayed.windows(2).all(|window| window[0] < window[1]), "{y_scale:?} {log_scale_factor}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_y_scale_bounds() {
+        for y_scale in YScale::ALL {
+            for log_scale_factor in LOG_SCALE_FACTORS {
+                assert!(y_scale.to_display(0.0, log_scale_factor).abs() < f32::EPSILON);
+                assert!((y_scale.to_display(1.0, log_scale_factor) - 1.0).abs() < 1e-6);
+            }
+        }
+        // small values are expanded
+        assert!(YScale::Log.to_display(0.01, 100.0) > 0.1);
+        // an invalid factor falls back to linear
+        assert!((YScale::Log.to_display(0.25, 0.0) - 0.25).abs() < f32::EPSILON);
+        assert!((YScale::Log.from_display(0.25, -1.0) - 0.25).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_y_scale_inverse() {
+        for y_scale in YScale::ALL {
+            for log_scale_factor in LOG_SCALE_FACTORS {
+                for value in values() {
+                    let displayed = y_scale.to_display(value, log_scale_factor);
+                    let inverse = y_scale.from_display(displayed, log_scale_factor);
+                    assert!((inverse - value).abs() < 1e-4, "{y_scale:?} {log_scale_factor} {value} {inverse}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_always_on_top_cycle() {
+        let mut always_on_top = AlwaysOnTop::default();
+        for expected in [AlwaysOnTop::Never, AlwaysOnTop::Always, AlwaysOnTop::WhenTuiFocused] {
+            always_on_top = always_on_top.next();
+            assert_eq!(always_on_top, expected);
+        }
+    }
 }
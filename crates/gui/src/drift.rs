@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+
+// once above a threshold, the drift has to come back that share of the tolerance below it to go down a level
+const HYSTERESIS: f32 = 0.2;
+// multiple of the tolerance above which the drift is far off
+const FAR: f32 = 3.0;
+// seconds of drift history the trend is computed on
+const TREND_WINDOW: f64 = 4.0;
+// slope, in BPM per second, under which the drift is considered steady
+const STEADY_SLOPE: f32 = 0.05;
+
+// ordered from the closest to the farthest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DriftLevel {
+    #[default]
+    Within,
+    Beyond,
+    Far,
+}
+
+/// Whether the detected tempo gets closer to the DAW tempo
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Trend {
+    Toward,
+    Steady,
+    Away,
+}
+
+/// Detected tempo minus the DAW tempo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Drift {
+    pub(crate) bpm: f32,
+    pub(crate) percent: f32,
+    pub(crate) level: DriftLevel,
+    pub(crate) trend: Trend,
+}
+
+/// Follows the drift from frame to frame, so its level doesn't flicker at a threshold and its trend can be told
+#[derive(Debug, Default)]
+pub(crate) struct DriftTracker {
+    level: DriftLevel,
+    // time in seconds and absolute drift, one sample per change of the drift
+    samples: VecDeque<(f64, f32)>,
+}
+
+impl DriftTracker {
+    /// None while either tempo is unknown
+    pub(crate) fn update(&mut self, estimated_bpm: f32, daw_bpm: f32, tolerance: f32, time: f64) -> Option<Drift> {
+        if !estimated_bpm.is_finite() || !daw_bpm.is_finite() || daw_bpm <= 0.0 {
+            *self = Self::default();
+            return None;
+        }
+        let bpm = estimated_bpm - daw_bpm;
+        let distance = bpm.abs();
+        self.level = next_level(self.level, distance, tolerance);
+
+        if self.samples.back().is_none_or(|(_, sample)| sample.to_bits() != distance.to_bits()) {
+            self.samples.push_back((time, distance));
+        }
+        while self.samples.front().is_some_and(|(sample_time, _)| *sample_time < time - TREND_WINDOW) {
+            self.samples.pop_front();
+        }
+
+        Some(Drift { bpm, percent: bpm / daw_bpm * 100.0, level: self.level, trend: trend(&self.samples, time) })
+    }
+}
+
+fn next_level(level: DriftLevel, distance: f32, tolerance: f32) -> DriftLevel {
+    let tolerance = tolerance.max(0.0);
+    let margin = tolerance * HYSTERESIS;
+    let mut next_level = DriftLevel::Within;
+    for (threshold, above) in [(tolerance, DriftLevel::Beyond), (tolerance * FAR, DriftLevel::Far)] {
+        // the levels already reached are kept until the drift is clearly below them
+        let threshold = if level >= above { threshold - margin } else { threshold };
+        if distance > threshold {
+            next_level = above;
+        }
+    }
+    next_level
+}
+
+// sign of the least squares slope of the drift, the last sample lasting until `time`
+fn trend(samples: &VecDeque<(f64, f32)>, time: f64) -> Trend {
+    let Some(&(_, current)) = samples.back() else {
+        return Trend::Steady;
+    };
+    let points = samples.iter().copied().chain([(time, current)]);
+    let count = samples.len() as f64 + 1.0;
+    let (sum_time, sum_distance) =
+        points.clone().fold((0.0, 0.0), |(sum_time, sum_distance), (t, d)| (sum_time + t, sum_distance + f64::from(d)));
+    let (mean_time, mean_distance) = (sum_time / count, sum_distance / count);
+    let (covariance, variance) = points.fold((0.0, 0.0), |(covariance, variance), (t, d)| {
+        (covariance + (t - mean_time) * (f64::from(d) - mean_distance), variance + (t - mean_time).powi(2))
+    });
+    if variance <= f64::EPSILON {
+        return Trend::Steady;
+    }
+    let slope = (covariance / variance) as f32;
+    if slope < -STEADY_SLOPE {
+        Trend::Toward
+    } else if slope > STEADY_SLOPE {
+        Trend::Away
+    } else {
+        Trend::Steady
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // levels after each drift, one frame per tenth of a second
+    fn levels(drifts: &[f32], tolerance: f32) -> Vec<DriftLevel> {
+        let mut drift_tracker = DriftTracker::default();
+        drifts
+            .iter()
+            .enumerate()
+            .map(|(frame, drift)| {
+                drift_tracker.update(120.0 + drift, 120.0, tolerance, frame as f64 / 10.0).unwrap().level
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_levels() {
+        use DriftLevel::{Beyond, Far, Within};
+        assert_eq!(
+            levels(&[0.0, 0.5, -1.0, 1.5, -2.9, 3.5, -10.0], 1.0),
+            [Within, Within, Within, Beyond, Beyond, Far, Far]
+        );
+        // a larger tolerance
+        assert_eq!(levels(&[1.5, -4.0, 6.5], 2.0), [Within, Beyond, Far]);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        use DriftLevel::{Beyond, Far, Within};
+        // hovering around the tolerance doesn't flicker
+        assert_eq!(
+            levels(&[0.9, 1.05, 0.95, 1.05, 0.85, 0.79, 0.95], 1.0),
+            [Within, Beyond, Beyond, Beyond, Beyond, Within, Within]
+        );
+        // going down from far off, one level at a time
+        assert_eq!(levels(&[3.5, 2.9, 2.7, 1.0, 0.5], 1.0), [Far, Far, Beyond, Beyond, Within]);
+    }
+
+    #[test]
+    fn test_unknown_tempo() {
+        let mut drift_tracker = DriftTracker::default();
+        assert_eq!(drift_tracker.update(f32::NAN, 120.0, 1.0, 0.0), None);
+        assert_eq!(drift_tracker.update(120.0, f32::NAN, 1.0, 0.0), None);
+
+        let drift = drift_tracker.update(126.0, 120.0, 1.0, 0.0).unwrap();
+        assert!((drift.bpm - 6.0).abs() < f32::EPSILON);
+        assert!((drift.percent - 5.0).abs() < 1e-4);
+        // the tracking starts over once the tempo is known again
+        assert_eq!(drift_tracker.update(f32::NAN, 120.0, 1.0, 0.1), None);
+        assert_eq!(drift_tracker.update(120.5, 120.0, 1.0, 0.2).unwrap().level, DriftLevel::Within);
+    }
+
+    // trend after the detected tempo went through `estimated_bpms`, one estimate every half second
+    fn trend_of(estimated_bpms: &[f32]) -> Trend {
+        let mut drift_tracker = DriftTracker::default();
+        let mut last_trend = Trend::Steady;
+        for (index, estimated_bpm) in estimated_bpms.iter().enumerate() {
+            last_trend = drift_tracker.update(*estimated_bpm, 120.0, 1.0, index as f64 / 2.0).unwrap().trend;
+        }
+        last_trend
+    }
+
+    #[test]
+    fn test_trend() {
+        assert_eq!(trend_of(&[126.0, 125.0, 124.0, 123.0]), Trend::Toward);
+        // from below
+        assert_eq!(trend_of(&[114.0, 115.0, 116.0, 117.0]), Trend::Toward);
+        assert_eq!(trend_of(&[121.0, 122.0, 123.0]), Trend::Away);
+        // crossing the DAW tempo moves away again
+        assert_eq!(trend_of(&[118.0, 119.0, 120.0, 121.0, 122.0]), Trend::Away);
+        assert_eq!(trend_of(&[123.0, 123.0, 123.0]), Trend::Steady);
+        assert_eq!(trend_of(&[123.0]), Trend::Steady);
+    }
+
+    #[test]
+    fn test_trend_window() {
+        let mut drift_tracker = DriftTracker::default();
+        drift_tracker.update(126.0, 120.0, 1.0, 0.0);
+        assert_eq!(drift_tracker.update(123.0, 120.0, 1.0, 1.0).unwrap().trend, Trend::Toward);
+        // the drift held still long enough, the old samples are forgotten
+        assert_eq!(drift_tracker.update(123.0, 120.0, 1.0, 10.0).unwrap().trend, Trend::Steady);
+    }
+}
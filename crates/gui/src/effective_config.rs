@@ -0,0 +1,312 @@
+//! The configuration actually in use, with where each of its values comes from: the built-in configuration, the
+//! configuration file, or the parameters restored by the host.
+
+use eframe::{egui, egui::Context};
+use errors::{register_crash_section, CrashSection};
+use log::error;
+use midi::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+use sync::Mutex;
+use toml::{Table, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Builtin,
+    File,
+    // set through the plugin parameters, as restored with the project or automated
+    Daw,
+    // adjusted while loading, the file holds another value
+    Clamped,
+    // changed while running, from the GUI or the TUI
+    Live,
+}
+
+impl Origin {
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Origin::Builtin => "built-in",
+            Origin::File => "file",
+            Origin::Daw => "daw",
+            Origin::Clamped => "clamped",
+            Origin::Live => "live",
+        }
+    }
+}
+
+/// Values of the configuration at each layer, by dotted path. The layers are compared with the effective value when
+/// rendering, so only the changes that no layer explains need to be recorded
+#[derive(Debug, Default)]
+pub struct Provenance {
+    builtin: BTreeMap<String, Value>,
+    file: Option<BTreeMap<String, Value>>,
+    // origin of a value set by a merge or apply step, holds as long as the value stays the same
+    markers: BTreeMap<String, (Origin, Value)>,
+}
+
+impl Provenance {
+    #[must_use]
+    pub fn new(builtin: &impl Serialize) -> Self {
+        Self { builtin: leaves(&to_table(builtin)), file: None, markers: BTreeMap::new() }
+    }
+
+    /// `file` is the configuration file as written, `loaded` the configuration deserialized from it. Values of the
+    /// file that did not make it as they are into the configuration were clamped
+    pub fn loaded_from_file(&mut self, file: &Table, loaded: &impl Serialize) {
+        let loaded = leaves(&to_table(loaded));
+        let file = leaves(file);
+        for (path, file_value) in &file {
+            if let Some(value) = loaded.get(path).filter(|value| !same_value(value, file_value)) {
+                self.markers.insert(path.clone(), (Origin::Clamped, value.clone()));
+            }
+        }
+        self.file = Some(file);
+    }
+
+    /// Values that differ between `before` and `after` are attributed to `origin`
+    pub fn record_change(&mut self, origin: Origin, before: &impl Serialize, after: &impl Serialize) {
+        let before = leaves(&to_table(before));
+        for (path, value) in leaves(&to_table(after)) {
+            if before.get(&path).is_none_or(|before| !same_value(before, &value)) {
+                self.markers.insert(path, (origin, value));
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn origin(&self, path: &str, value: &Value) -> Origin {
+        if let Some((origin, _)) = self.markers.get(path).filter(|(_, marked)| same_value(marked, value)) {
+            return *origin;
+        }
+        if self.file_value(path).is_some_and(|file_value| same_value(file_value, value)) {
+            return Origin::File;
+        }
+        if self.builtin.get(path).is_some_and(|builtin| same_value(builtin, value)) {
+            return Origin::Builtin;
+        }
+        Origin::Live
+    }
+
+    /// `effective` as TOML, followed by the values differing from the built-in configuration or from the file
+    #[must_use]
+    pub fn render(&self, effective: &impl Serialize) -> String {
+        let effective = to_table(effective);
+        let mut rendered = toml::to_string_pretty(&effective).unwrap_or_else(|err| format!("# {err}\n"));
+        rendered.push_str("\n# Values differing from the built-in configuration or from the configuration file\n");
+        for (path, value) in leaves(&effective) {
+            let builtin = self.builtin.get(&path);
+            let file_value = self.file_value(&path);
+            let differs = builtin.is_none_or(|builtin| !same_value(builtin, &value))
+                || file_value.is_some_and(|file_value| !same_value(file_value, &value));
+            if !differs {
+                continue;
+            }
+            let describe = |value: Option<&Value>| value.map_or_else(|| "none".to_string(), ToString::to_string);
+            let file_column =
+                if self.file.is_some() { format!(", file: {}", describe(file_value)) } else { String::new() };
+            rendered.push_str(&format!(
+                "# {path} = {value}  ({}, built-in: {}{file_column})\n",
+                self.origin(&path, &value).label(),
+                describe(builtin)
+            ));
+        }
+        rendered
+    }
+
+    fn file_value(&self, path: &str) -> Option<&Value> {
+        self.file.as_ref().and_then(|file| file.get(path))
+    }
+}
+
+/// Provenance shared by all the clones of a configuration
+#[derive(Clone, Default)]
+pub struct SharedProvenance(Arc<Mutex<Provenance>>);
+
+impl Debug for SharedProvenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedProvenance")
+    }
+}
+
+impl SharedProvenance {
+    #[must_use]
+    pub fn new(provenance: Provenance) -> Self {
+        Self(Arc::new(Mutex::new(provenance)))
+    }
+
+    pub fn record_change(&self, origin: Origin, before: &impl Serialize, after: &impl Serialize) {
+        self.0.lock().record_change(origin, before, after);
+    }
+
+    #[must_use]
+    pub fn render(&self, effective: &impl Serialize) -> String {
+        self.0.lock().render(effective)
+    }
+}
+
+/// Detection parameters in use, written in the crash report
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DetectionParameters {
+    pub static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+}
+
+/// Adds `parameters` as TOML to the crash reports written until the returned guard is dropped
+pub fn register_parameters_crash_section(
+    title: impl Into<String>,
+    parameters: Arc<Mutex<DetectionParameters>>,
+) -> CrashSection {
+    // nothing is logged from there, the panic may have happened while logging
+    register_crash_section(title, move || match parameters.try_lock() {
+        Some(parameters) => toml::to_string_pretty(&*parameters).unwrap_or_else(|err| format!("# {err}")),
+        None => "# unavailable, they were being updated".to_string(),
+    })
+}
+
+/// `before` for `record_change`, where a clone of the configuration would share its atomic values
+#[must_use]
+pub fn snapshot(config: &impl Serialize) -> Table {
+    to_table(config)
+}
+
+fn to_table(config: &impl Serialize) -> Table {
+    match Value::try_from(config) {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => Table::new(),
+        Err(err) => {
+            error!("could not serialize the configuration: {err}");
+            Table::new()
+        }
+    }
+}
+
+fn leaves(table: &Table) -> BTreeMap<String, Value> {
+    fn visit(prefix: &str, table: &Table, leaves: &mut BTreeMap<String, Value>) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+            match value {
+                Value::Table(table) => visit(&path, table, leaves),
+                value => {
+                    leaves.insert(path, value.clone());
+                }
+            }
+        }
+    }
+    let mut result = BTreeMap::new();
+    visit("", table, &mut result);
+    result
+}
+
+// f32 values don't serialize back to the decimals they were written with, and files may write floats as integers
+fn same_value(a: &Value, b: &Value) -> bool {
+    let as_f64 = |value: &Value| match value {
+        Value::Integer(integer) => Some(*integer as f64),
+        Value::Float(float) => Some(*float),
+        _ => None,
+    };
+    match (as_f64(a), as_f64(b)) {
+        (Some(a), Some(b)) => (a - b).abs() <= f64::from(f32::EPSILON) * a.abs().max(b.abs()).max(1.0),
+        _ => a == b,
+    }
+}
+
+/// Read-only view of the text given by `BPMDetectionParameters::effective_config`, open while `effective_config` is
+/// set
+pub(crate) fn effective_config_window(ctx: &Context, effective_config: &mut Option<String>) {
+    let mut open = effective_config.is_some();
+    egui::Window::new("Effective config").open(&mut open).default_height(400.0).show(ctx, |ui| {
+        let text = effective_config.as_deref().unwrap_or_default();
+        if ui.button("Copy all").clicked() {
+            ui.output_mut(|output| output.copied_text = text.to_string());
+        }
+        ui.separator();
+        egui::ScrollArea::both().show(ui, |ui| {
+            ui.monospace(text);
+        });
+    });
+    if !open {
+        *effective_config = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct Settings {
+        bpm_center: f32,
+        bpm_range: u16,
+        weights: Weights,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct Weights {
+        octave: f32,
+        pitch: f32,
+    }
+
+    fn builtin() -> Settings {
+        Settings { bpm_center: 120.0, bpm_range: 60, weights: Weights { octave: 0.5, pitch: 0.3 } }
+    }
+
+    fn origin(provenance: &Provenance, config: &Settings, path: &str) -> Origin {
+        let value = leaves(&to_table(config)).remove(path).unwrap();
+        provenance.origin(path, &value)
+    }
+
+    #[test]
+    fn test_file_override() {
+        let file = "bpm_center = 100\n[weights]\npitch = 0.7\n".parse::<Table>().unwrap();
+        let mut loaded = builtin();
+        loaded.bpm_center = 100.0;
+        loaded.weights.pitch = 0.7;
+
+        let mut provenance = Provenance::new(&builtin());
+        provenance.loaded_from_file(&file, &loaded);
+        assert_eq!(origin(&provenance, &loaded, "bpm_center"), Origin::File);
+        assert_eq!(origin(&provenance, &loaded, "weights.pitch"), Origin::File);
+        assert_eq!(origin(&provenance, &loaded, "weights.octave"), Origin::Builtin);
+
+        let rendered = provenance.render(&loaded);
+        assert!(rendered.contains("# bpm_center = 100.0  (file, built-in: 120.0, file: 100)"), "{rendered}");
+        assert!(!rendered.contains("# weights.octave"), "{rendered}");
+    }
+
+    #[test]
+    fn test_daw_restore() {
+        let before = builtin();
+        let mut provenance = Provenance::new(&before);
+        let mut restored = before.clone();
+        restored.bpm_range = 90;
+        restored.weights.octave = 0.9;
+        provenance.record_change(Origin::Daw, &before, &restored);
+        assert_eq!(origin(&provenance, &restored, "bpm_range"), Origin::Daw);
+        assert_eq!(origin(&provenance, &restored, "weights.octave"), Origin::Daw);
+        assert_eq!(origin(&provenance, &restored, "bpm_center"), Origin::Builtin);
+
+        // changed again afterwards, the host is no longer where the value comes from
+        let mut edited = restored.clone();
+        edited.bpm_range = 100;
+        assert_eq!(origin(&provenance, &edited, "bpm_range"), Origin::Live);
+        assert!(provenance.render(&edited).contains("# bpm_range = 100  (live, built-in: 60)"));
+    }
+
+    #[test]
+    fn test_clamped() {
+        let file = "bpm_range = 1000\n".parse::<Table>().unwrap();
+        let mut loaded = builtin();
+        loaded.bpm_range = 400;
+
+        let mut provenance = Provenance::new(&builtin());
+        provenance.loaded_from_file(&file, &loaded);
+        assert_eq!(origin(&provenance, &loaded, "bpm_range"), Origin::Clamped);
+        assert!(provenance.render(&loaded).contains("# bpm_range = 400  (clamped, built-in: 60, file: 1000)"));
+    }
+}
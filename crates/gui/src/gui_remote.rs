@@ -2,10 +2,14 @@ use atomic_float::AtomicF32;
 
 use atomic_refcell::AtomicRefCell;
 use derivative::Derivative;
-use eframe::egui::{Context, ViewportCommand, WindowLevel};
+use eframe::egui::{Context, ViewportCommand};
 use errors::{minitrace, LogErrorWithExt, LogOptionWithExt};
 use instant::Instant;
-use midi::{bpm::max_histogram_data_buffer_size, bpm_detection_receiver::BPMDetectionReceiver};
+use midi::{
+    bpm::max_histogram_data_buffer_size, bpm_detection_receiver::BPMDetectionReceiver, BarPosition, BeatPhase,
+    BpmEstimate, ConfigWarning, FeelAmbiguity, MultiResolutionHistogram, ResolutionFloor, SessionSummary,
+    StaticBPMDetectionParameters, TempoBand, TimedMidiNoteOn,
+};
 use std::{
     mem,
     sync::{
@@ -15,10 +19,17 @@ use std::{
 };
 use sync::Mutex;
 
+use crate::{
+    metronome::BeatAnchor,
+    note_strip::{NoteHistory, NoteHistoryEntry},
+};
+
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub struct GuiRemote {
     pub(crate) context: Arc<AtomicRefCell<Option<Context>>>,
+    // handed over by the GUI while `context` was borrowed, taken on the next access, see `set_context`
+    pub(crate) pending_context: Arc<Mutex<Option<Context>>>,
     #[derivative(Debug = "ignore")]
     pub(crate) keys_sender: Arc<Mutex<Option<Box<dyn FnMut(&'static str) + Send>>>>,
     #[derivative(Debug = "ignore")]
@@ -26,59 +37,238 @@ pub struct GuiRemote {
     pub(crate) swap_histogram_data_points: Arc<AtomicRefCell<Vec<f32>>>,
     pub(crate) histogram_data_points: Arc<AtomicRefCell<HistogramDataPoints>>,
     pub(crate) estimated_bpm: Arc<AtomicF32>,
+    // of the estimated tempo, see `BpmEstimate`
+    pub(crate) estimated_confidence: Arc<AtomicF32>,
     pub(crate) daw_bpm: Arc<AtomicF32>,
     pub(crate) should_save: Arc<AtomicBool>,
+    pub(crate) should_export_snapshot: Arc<AtomicBool>,
+    pub(crate) tui_focused: Arc<AtomicBool>,
+    pub(crate) should_cycle_always_on_top: Arc<AtomicBool>,
+    pub(crate) note_history: Arc<Mutex<NoteHistory>>,
+    pub(crate) config_warnings: Arc<Mutex<Vec<ConfigWarning>>>,
+    pub(crate) bar_position: Arc<Mutex<Option<BarPosition>>>,
+    pub(crate) beat_anchor: Arc<Mutex<Option<BeatAnchor>>>,
+    pub(crate) velocity_gate: Arc<Mutex<Option<u8>>>,
+    pub(crate) auto_zoom: Arc<Mutex<Option<StaticBPMDetectionParameters>>>,
+    pub(crate) tapped_bpm: Arc<Mutex<Option<f32>>>,
+    pub(crate) clock_bpm: Arc<Mutex<Option<f32>>>,
+    pub(crate) frozen_histogram: Arc<Mutex<Option<FrozenHistogram>>>,
+    pub(crate) session_summary: Arc<Mutex<SessionSummary>>,
+    pub(crate) note_freshness: Arc<Mutex<Option<f32>>>,
+    pub(crate) feel_ambiguity: Arc<Mutex<Option<FeelAmbiguity>>>,
+    pub(crate) multi_resolution: Arc<Mutex<Option<MultiResolution>>>,
+    pub(crate) resolution_floor: Arc<Mutex<Option<ResolutionFloor>>>,
+    pub(crate) tempo_band: Arc<Mutex<Option<TempoBand>>>,
+    // set by the GUI from `GUIConfig::phase_split`
+    pub(crate) phase_split_window: Arc<Mutex<Option<f32>>>,
+    pub(crate) phase_split_histogram: Arc<Mutex<Option<PhaseSplitHistogram>>>,
+    pub(crate) low_memory: bool,
+}
+
+/// Evaluation of the frozen notes, drawn over the live histogram
+#[derive(Debug, PartialEq)]
+pub(crate) struct FrozenHistogram {
+    pub(crate) histogram_data_points: Vec<f32>,
+    pub(crate) bpm: f32,
+}
+
+/// Axis of the coarse histogram and histogram around the estimate, when the detection runs in multi-resolution mode
+#[derive(Debug, PartialEq)]
+pub(crate) struct MultiResolution {
+    pub(crate) coarse_parameters: StaticBPMDetectionParameters,
+    // none until a first estimate
+    pub(crate) fine: Option<(StaticBPMDetectionParameters, Vec<f32>)>,
+}
+
+/// On-beat and off-beat shares of the histogram, see `GUIConfig::phase_split_view`
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PhaseSplitHistogram {
+    pub(crate) on_beat: Vec<f32>,
+    pub(crate) off_beat: Vec<f32>,
+}
+
+impl PhaseSplitHistogram {
+    /// Share of the bar at `index` that is on-beat, none past the end
+    pub(crate) fn on_beat_share(&self, index: usize) -> Option<f32> {
+        let (on_beat, off_beat) = (*self.on_beat.get(index)?, *self.off_beat.get(index)?);
+        let total = on_beat + off_beat;
+        Some(if total > 0.0 { (on_beat / total).clamp(0.0, 1.0) } else { 0.0 })
+    }
 }
 
 #[allow(forbidden_lint_groups)]
 #[allow(clippy::struct_field_names)]
-#[derive(Derivative)]
+#[derive(Derivative, Default)]
 #[derivative(Debug)]
 pub(crate) struct HistogramDataPoints {
     pub(crate) inbound_histogram_data_points: Vec<f32>,
-    pub(crate) inbound_histogram_data_update: Instant,
-}
-
-impl Default for HistogramDataPoints {
-    fn default() -> Self {
-        Self {
-            inbound_histogram_data_points: Vec::with_capacity(max_histogram_data_buffer_size()),
-            inbound_histogram_data_update: Instant::now(),
-        }
-    }
 }
 
 impl BPMDetectionReceiver for GuiRemote {
-    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], detected_bpm: f32) {
-        let mut swap_histogram_data_points = self.swap_histogram_data_points.borrow_mut();
+    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], estimate: BpmEstimate) {
+        // clones of the remote share the swap buffer, a receive may interleave with another one
+        let Ok(mut swap_histogram_data_points) = self
+            .swap_histogram_data_points
+            .try_borrow_mut()
+            .log_error_msg("race condition while taking swap_histogram_data_points, skipping update")
+        else {
+            return;
+        };
+        if self.low_memory {
+            // the size of the histogram received, not more
+            swap_histogram_data_points.clear();
+            swap_histogram_data_points.shrink_to(histogram_data_points.len());
+            swap_histogram_data_points.reserve_exact(histogram_data_points.len());
+        }
         swap_histogram_data_points.resize(histogram_data_points.len(), 0.0);
         swap_histogram_data_points.copy_from_slice(histogram_data_points);
 
         self.histogram_data_points
             .try_borrow_mut()
             .map(|mut histogram_data_points| {
-                let HistogramDataPoints { inbound_histogram_data_points, inbound_histogram_data_update } =
-                    &mut *histogram_data_points;
-                mem::swap(inbound_histogram_data_points, &mut *swap_histogram_data_points);
-                *inbound_histogram_data_update = Instant::now();
+                mem::swap(&mut histogram_data_points.inbound_histogram_data_points, &mut *swap_histogram_data_points);
             })
             .log_error_msg("race condition while taking histogram_data_points, skipping update")
             .ok();
 
-        self.estimated_bpm.store(detected_bpm, Ordering::Relaxed);
+        drop(swap_histogram_data_points);
+
+        self.store_estimate(estimate);
         self.request_repaint();
     }
 
     fn receive_daw_bpm(&self, bpm: f32) {
         self.daw_bpm.store(bpm, Ordering::Relaxed);
     }
+
+    fn receive_clock_bpm(&self, bpm: Option<f32>) {
+        *self.clock_bpm.lock() = bpm;
+        self.request_repaint();
+    }
+
+    fn receive_note(&self, timed_midi_note_on: &TimedMidiNoteOn) {
+        self.push_note(timed_midi_note_on);
+    }
+
+    fn receive_config_warnings(&self, config_warnings: &[ConfigWarning]) {
+        config_warnings.clone_into(&mut self.config_warnings.lock());
+        self.request_repaint();
+    }
+
+    fn receive_bar_position(&self, bar_position: Option<BarPosition>) {
+        *self.bar_position.lock() = bar_position;
+    }
+
+    fn receive_beat_phase(&self, beat_phase: Option<BeatPhase>) {
+        *self.beat_anchor.lock() = beat_phase.map(|phase| BeatAnchor { received: Instant::now(), phase });
+    }
+
+    fn receive_velocity_gate(&self, threshold: Option<u8>) {
+        *self.velocity_gate.lock() = threshold;
+    }
+
+    fn receive_auto_zoom(&self, zoomed: Option<&StaticBPMDetectionParameters>) {
+        let mut auto_zoom = self.auto_zoom.lock();
+        if auto_zoom.as_ref() != zoomed {
+            *auto_zoom = zoomed.cloned();
+        }
+    }
+
+    fn receive_tapped_tempo(&self, bpm: Option<f32>) {
+        *self.tapped_bpm.lock() = bpm;
+        self.request_repaint();
+    }
+
+    fn receive_frozen_bpm_histogram_data(&self, frozen: Option<(&[f32], f32)>) {
+        // only sent when the parameters change, the copy doesn't matter
+        *self.frozen_histogram.lock() = frozen.map(|(histogram_data_points, bpm)| FrozenHistogram {
+            histogram_data_points: histogram_data_points.to_vec(),
+            bpm,
+        });
+        self.request_repaint();
+    }
+
+    fn receive_session_summary(&self, session_summary: SessionSummary) {
+        // sent along with the histogram, which requests the repaint
+        *self.session_summary.lock() = session_summary;
+    }
+
+    fn receive_note_freshness(&self, freshness: Option<f32>) {
+        *self.note_freshness.lock() = freshness;
+    }
+
+    fn receive_feel_ambiguity(&self, feel_ambiguity: Option<FeelAmbiguity>) {
+        *self.feel_ambiguity.lock() = feel_ambiguity;
+    }
+
+    fn receive_tempo_band(&self, tempo_band: Option<TempoBand>) {
+        *self.tempo_band.lock() = tempo_band;
+    }
+
+    fn receive_multi_resolution_histogram(&self, multi_resolution_histogram: Option<MultiResolutionHistogram<'_>>) {
+        // a few bins around the estimate
+        *self.multi_resolution.lock() = multi_resolution_histogram.map(|multi_resolution_histogram| MultiResolution {
+            coarse_parameters: multi_resolution_histogram.coarse_parameters.clone(),
+            fine: multi_resolution_histogram
+                .fine
+                .map(|(parameters, histogram_data_points)| (parameters.clone(), histogram_data_points.to_vec())),
+        });
+    }
+
+    fn phase_split_window(&self) -> Option<f32> {
+        *self.phase_split_window.lock()
+    }
+
+    fn receive_phase_split_histogram(&self, phase_split: Option<(&[f32], &[f32])>) {
+        // the buffers are kept from one to the next
+        let mut phase_split_histogram = self.phase_split_histogram.lock();
+        match phase_split {
+            Some((on_beat, off_beat)) => {
+                let phase_split_histogram = phase_split_histogram.get_or_insert_with(PhaseSplitHistogram::default);
+                on_beat.clone_into(&mut phase_split_histogram.on_beat);
+                off_beat.clone_into(&mut phase_split_histogram.off_beat);
+            }
+            None => *phase_split_histogram = None,
+        }
+    }
+
+    fn receive_resolution_floor(&self, resolution_floor: Option<ResolutionFloor>) {
+        *self.resolution_floor.lock() = resolution_floor;
+    }
 }
 
 impl GuiRemote {
+    /// Allocates the histogram buffers once for the largest histogram, when the GUI starts. Until then, or in low-memory
+    /// mode, they take the size of the histograms received
+    pub(crate) fn preallocate_histograms(&self) {
+        if self.low_memory {
+            return;
+        }
+        let capacity = max_histogram_data_buffer_size();
+        if let Ok(mut histogram_data_points) =
+            self.histogram_data_points.try_borrow_mut().log_error_msg("could not preallocate histogram_data_points")
+        {
+            let inbound_histogram_data_points = &mut histogram_data_points.inbound_histogram_data_points;
+            inbound_histogram_data_points.reserve(capacity.saturating_sub(inbound_histogram_data_points.len()));
+        }
+        if let Ok(mut swap_histogram_data_points) = self
+            .swap_histogram_data_points
+            .try_borrow_mut()
+            .log_error_msg("could not preallocate swap_histogram_data_points")
+        {
+            swap_histogram_data_points.reserve(capacity.saturating_sub(swap_histogram_data_points.len()));
+        }
+    }
+
     pub fn save_config(&self) {
         self.should_save.store(true, Ordering::Relaxed);
     }
 
+    pub fn export_snapshot(&self) {
+        self.should_export_snapshot.store(true, Ordering::Relaxed);
+        self.request_repaint();
+    }
+
     pub fn set_on_gui_exit_callback<F: Fn() + Send + 'static>(&self, callback: F) {
         self.on_gui_exit_callback.lock().replace(Box::new(callback));
     }
@@ -87,8 +277,19 @@ impl GuiRemote {
         self.keys_sender.lock().replace(sender);
     }
 
+    // puts in place the context set while it was borrowed. Skipped while it still is, as the other updates of the remote
+    fn take_pending_context(&self) {
+        let mut pending_context = self.pending_context.lock();
+        if pending_context.is_some() {
+            if let Ok(mut context) = self.context.try_borrow_mut() {
+                *context = pending_context.take();
+            }
+        }
+    }
+
     #[minitrace::trace]
     pub fn close(&self) {
+        self.take_pending_context();
         if let Ok(context) = self.context.try_borrow().log_error_msg("could not get context to close window") {
             if let Some(context) = context.as_ref().log_error_msg("no context present") {
                 context.send_viewport_cmd(ViewportCommand::Close);
@@ -96,30 +297,65 @@ impl GuiRemote {
         }
     }
 
-    #[minitrace::trace]
-    pub fn always_on_top(&self) {
-        if let Ok(context) = self.context.try_borrow().log_error_msg("could not get context to put window on top") {
-            if let Some(context) = context.as_ref().log_error_msg("no context present") {
-                context.send_viewport_cmd(ViewportCommand::WindowLevel(WindowLevel::AlwaysOnTop));
-            }
+    /// The window level is then decided by the GUI, according to `GUIConfig::window_behavior`
+    pub fn set_tui_focused(&self, tui_focused: bool) {
+        self.tui_focused.store(tui_focused, Ordering::Relaxed);
+        self.request_repaint();
+    }
+
+    /// Shown in the note strip, the GUI repaints on its own when the histogram is updated
+    pub fn push_note(&self, timed_midi_note_on: &TimedMidiNoteOn) {
+        self.note_history.lock().push(NoteHistoryEntry {
+            timestamp: timed_midi_note_on.timestamp.num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0,
+            note: timed_midi_note_on.midi_message.note,
+            velocity: timed_midi_note_on.midi_message.velocity,
+        });
+    }
+
+    /// Same as `receive_bpm_histogram_data`, without copying: `histogram_data_points` becomes the data displayed by the
+    /// GUI and the previous buffer is returned so it can be reused. If the GUI is reading, the update is skipped and
+    /// the given buffer is returned
+    pub fn publish_histogram(&self, mut histogram_data_points: Vec<f32>, estimate: BpmEstimate) -> Vec<f32> {
+        if let Ok(mut current) = self
+            .histogram_data_points
+            .try_borrow_mut()
+            .log_error_msg("race condition while taking histogram_data_points, skipping update")
+        {
+            mem::swap(&mut current.inbound_histogram_data_points, &mut histogram_data_points);
+            self.store_estimate(estimate);
+            self.request_repaint();
         }
+        histogram_data_points
+    }
+
+    fn store_estimate(&self, estimate: BpmEstimate) {
+        self.estimated_bpm.store(estimate.bpm, Ordering::Relaxed);
+        self.estimated_confidence.store(estimate.confidence, Ordering::Relaxed);
+    }
+
+    pub fn cycle_always_on_top(&self) {
+        self.should_cycle_always_on_top.store(true, Ordering::Relaxed);
+        self.request_repaint();
     }
 
     #[minitrace::trace]
-    pub fn always_on_top_cancel(&self) {
-        if let Ok(context) = self.context.try_borrow().log_error_msg("could not get context to cancel window on top") {
+    pub fn focus_window(&self) {
+        self.take_pending_context();
+        if let Ok(context) = self.context.try_borrow().log_error_msg("could not get context to focus window") {
             if let Some(context) = context.as_ref().log_error_msg("no context present") {
-                context.send_viewport_cmd(ViewportCommand::WindowLevel(WindowLevel::Normal));
+                context.send_viewport_cmd(ViewportCommand::Focus);
             }
         }
     }
 
     #[must_use]
     pub fn get_context(&self) -> Option<Context> {
+        self.take_pending_context();
         self.context.try_borrow().ok()?.clone()
     }
 
     pub fn request_repaint(&self) {
+        self.take_pending_context();
         let Ok(context) = self.context.try_borrow() else {
             return;
         };
@@ -129,3 +365,166 @@ impl GuiRemote {
         }
     }
 }
+
+/// Hands the egui context over to the remote. The remote may be reading it at that moment, from another thread, or
+/// further up the stack on wasm where waiting for it would never end. The context is then kept in `pending_context`
+/// and the remote takes it on its next access
+pub(crate) fn set_context(
+    context_receiver: &AtomicRefCell<Option<Context>>,
+    pending_context: &Mutex<Option<Context>>,
+    context: Context,
+) {
+    let mut pending_context = pending_context.lock();
+    match context_receiver.try_borrow_mut() {
+        Ok(mut context_receiver) => {
+            pending_context.take();
+            context_receiver.replace(context);
+        }
+        Err(_) => {
+            pending_context.replace(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_gui, tests::TestParameters};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    fn estimate(bpm: f32) -> BpmEstimate {
+        BpmEstimate { bpm, confidence: 1.0 }
+    }
+
+    fn gui_remote() -> GuiRemote {
+        create_gui(TestParameters::default()).0
+    }
+
+    #[test]
+    fn test_low_memory_buffers() {
+        let mut test_parameters = TestParameters::default();
+        test_parameters.gui_config.low_memory = true;
+        let (mut gui_remote, _) = create_gui(test_parameters);
+        assert_eq!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points.capacity(), 0);
+        for len in [300, 700, 100] {
+            gui_remote.receive_bpm_histogram_data(&vec![1.0; len], estimate(120.0));
+            gui_remote.receive_bpm_histogram_data(&vec![1.0; len], estimate(120.0));
+            assert_eq!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points.capacity(), len);
+            assert_eq!(gui_remote.swap_histogram_data_points.borrow().capacity(), len);
+        }
+    }
+
+    #[test]
+    fn test_receive_while_gui_reads() {
+        let gui_remote = gui_remote();
+        let mut receiver = gui_remote.clone();
+        receiver.receive_bpm_histogram_data(&[1.0, 2.0], estimate(120.0));
+
+        {
+            let histogram_data_points = gui_remote.histogram_data_points.borrow();
+            receiver.receive_bpm_histogram_data(&[3.0, 4.0], estimate(130.0));
+            let rejected = receiver.publish_histogram(vec![5.0], estimate(140.0));
+            assert_eq!(rejected, vec![5.0]);
+            assert_eq!(histogram_data_points.inbound_histogram_data_points, vec![1.0, 2.0]);
+        }
+
+        receiver.receive_bpm_histogram_data(&[3.0, 4.0], BpmEstimate { bpm: 130.0, confidence: 0.5 });
+        assert_eq!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points, vec![3.0, 4.0]);
+        assert!(gui_remote.estimated_bpm.load(Ordering::Relaxed).total_cmp(&130.0).is_eq());
+        assert!(gui_remote.estimated_confidence.load(Ordering::Relaxed).total_cmp(&0.5).is_eq());
+    }
+
+    #[test]
+    fn test_reentrant_receive() {
+        let gui_remote = gui_remote();
+        let mut receiver = gui_remote.clone();
+        receiver.receive_bpm_histogram_data(&[1.0, 2.0], estimate(120.0));
+
+        // a receive interrupted while copying into the swap buffer
+        let swap_histogram_data_points = gui_remote.swap_histogram_data_points.borrow_mut();
+        receiver.receive_bpm_histogram_data(&[3.0, 4.0], estimate(130.0));
+        drop(swap_histogram_data_points);
+
+        assert_eq!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_frozen_histogram() {
+        let gui_remote = gui_remote();
+        gui_remote.receive_frozen_bpm_histogram_data(Some((&[1.0, 2.0], 120.0)));
+        // the live histogram is received on its own
+        assert!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points.is_empty());
+        assert_eq!(
+            *gui_remote.frozen_histogram.lock(),
+            Some(FrozenHistogram { histogram_data_points: vec![1.0, 2.0], bpm: 120.0 })
+        );
+
+        gui_remote.receive_frozen_bpm_histogram_data(None);
+        assert_eq!(*gui_remote.frozen_histogram.lock(), None);
+    }
+
+    #[test]
+    fn test_multi_resolution_histogram() {
+        let gui_remote = gui_remote();
+        let coarse_parameters = StaticBPMDetectionParameters { histogram_resolution: 100, ..Default::default() };
+        let fine_parameters = StaticBPMDetectionParameters { bpm_center: 120.0, bpm_range: 10, ..Default::default() };
+        gui_remote.receive_multi_resolution_histogram(Some(MultiResolutionHistogram {
+            coarse_parameters: &coarse_parameters,
+            fine: Some((&fine_parameters, &[1.0, 2.0])),
+        }));
+        assert_eq!(
+            *gui_remote.multi_resolution.lock(),
+            Some(MultiResolution {
+                coarse_parameters: coarse_parameters.clone(),
+                fine: Some((fine_parameters, vec![1.0, 2.0]))
+            })
+        );
+
+        gui_remote.receive_multi_resolution_histogram(None);
+        assert_eq!(*gui_remote.multi_resolution.lock(), None);
+    }
+
+    #[test]
+    fn test_phase_split_histogram() {
+        let gui_remote = gui_remote();
+        assert!(gui_remote.phase_split_window().is_none());
+        *gui_remote.phase_split_window.lock() = Some(0.1);
+        assert!(gui_remote.phase_split_window().is_some_and(|window| window.total_cmp(&0.1).is_eq()));
+
+        gui_remote.receive_phase_split_histogram(Some((&[1.0, 0.0, 0.0], &[3.0, 2.0, 0.0])));
+        {
+            let phase_split_histogram = gui_remote.phase_split_histogram.lock();
+            let phase_split_histogram = phase_split_histogram.as_ref().unwrap();
+            let on_beat_share = |index| phase_split_histogram.on_beat_share(index).map(f32::to_bits);
+            assert_eq!(on_beat_share(0), Some(0.25f32.to_bits()));
+            assert_eq!(on_beat_share(1), Some(0.0f32.to_bits()));
+            // an empty bar has no on-beat share to draw
+            assert_eq!(on_beat_share(2), Some(0.0f32.to_bits()));
+            assert_eq!(on_beat_share(3), None);
+        }
+
+        gui_remote.receive_phase_split_histogram(None);
+        assert_eq!(*gui_remote.phase_split_histogram.lock(), None);
+    }
+
+    #[test]
+    fn test_set_context_while_borrowed() {
+        let gui_remote = gui_remote();
+
+        // the remote reads the context further up the stack, as on wasm
+        let context = gui_remote.context.borrow();
+        set_context(&gui_remote.context, &gui_remote.pending_context, Context::default());
+        gui_remote.request_repaint();
+        assert!(context.is_none());
+        drop(context);
+
+        // taken on the next access
+        assert!(gui_remote.get_context().is_some());
+        assert!(gui_remote.pending_context.lock().is_none());
+
+        set_context(&gui_remote.context, &gui_remote.pending_context, Context::default());
+        assert!(gui_remote.pending_context.lock().is_none());
+        assert!(gui_remote.get_context().is_some());
+    }
+}
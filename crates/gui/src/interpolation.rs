@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+// number of time constants after which the displayed value is considered to have reached its target. After 3 time
+// constants, 95% of the distance is covered, which matches the perceived duration of the previous interpolation at
+// 60fps with the default settings
+const TIME_CONSTANTS_PER_INTERPOLATION: f32 = 3.0;
+
+/// Time constant, in seconds, of the exponential smoothing applied to the histogram bars.
+#[must_use]
+pub fn time_constant(interpolation_duration: Duration, interpolation_curve: f32) -> f32 {
+    interpolation_duration.as_secs_f32() / (TIME_CONSTANTS_PER_INTERPOLATION * interpolation_curve.max(f32::EPSILON))
+}
+
+/// Fraction of the remaining distance to the target value that is covered during a frame lasting `dt` seconds.
+///
+/// Applying this factor on each frame gives the same result whatever the frame rate : two frames of `dt / 2` cover the
+/// same distance as one frame of `dt`. A long pause ( hidden window, throttled editor ) converges to the target
+/// without overshooting, and a zero or invalid `dt` leaves the value untouched.
+#[must_use]
+pub fn smoothing_factor(dt: f32, interpolation_duration: Duration, interpolation_curve: f32) -> f32 {
+    if dt.is_nan() || dt <= 0.0 {
+        return 0.0;
+    }
+    let time_constant = time_constant(interpolation_duration, interpolation_curve);
+    if time_constant <= 0.0 {
+        return 1.0;
+    }
+    (1.0 - (-dt / time_constant).exp()).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DURATION: Duration = Duration::from_millis(730);
+    const CURVE: f32 = 0.8;
+
+    fn smooth(current: f32, target: f32, dt: f32, interpolation_duration: Duration, interpolation_curve: f32) -> f32 {
+        current + (target - current) * smoothing_factor(dt, interpolation_duration, interpolation_curve)
+    }
+
+    fn run(frame_rate: f32, seconds: f32, curve: f32) -> f32 {
+        let dt = 1.0 / frame_rate;
+        let frames = (seconds * frame_rate).round() as usize;
+        (0..frames).fold(0.0, |value, _| smooth(value, 1.0, dt, DURATION, curve))
+    }
+
+    #[test]
+    fn test_converges_within_interpolation_duration() {
+        let value = run(60.0, DURATION.as_secs_f32(), 1.0);
+        assert!(value > 0.94 && value < 0.96, "{value}");
+        assert!(run(60.0, DURATION.as_secs_f32() * 3.0, 1.0) > 0.999);
+        // a curve below 1 slows the movement down
+        assert!(run(60.0, DURATION.as_secs_f32(), CURVE) < value);
+    }
+
+    #[test]
+    fn test_frame_rate_independent() {
+        let at_60fps = run(60.0, 0.5, CURVE);
+        let at_30fps = run(30.0, 0.5, CURVE);
+        let at_144fps = run(144.0, 0.5, CURVE);
+        assert!((at_60fps - at_30fps).abs() < 1e-4, "{at_60fps} {at_30fps}");
+        assert!((at_60fps - at_144fps).abs() < 1e-4, "{at_60fps} {at_144fps}");
+    }
+
+    #[test]
+    fn test_tiny_dt() {
+        let factor = smoothing_factor(1e-9, DURATION, CURVE);
+        assert!((0.0..1e-6).contains(&factor), "{factor}");
+        assert!(smoothing_factor(0.0, DURATION, CURVE).abs() < f32::EPSILON);
+        assert!(smoothing_factor(-1.0, DURATION, CURVE).abs() < f32::EPSILON);
+        assert!(smoothing_factor(f32::NAN, DURATION, CURVE).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_huge_dt() {
+        for dt in [10.0, 1e6, f32::MAX, f32::INFINITY] {
+            let value = smooth(0.2, 1.0, dt, DURATION, CURVE);
+            assert!((value - 1.0).abs() < f32::EPSILON, "{dt} {value}");
+        }
+    }
+
+    #[test]
+    fn test_degenerate_configuration() {
+        assert!((smoothing_factor(0.016, Duration::ZERO, CURVE) - 1.0).abs() < f32::EPSILON);
+        let factor = smoothing_factor(0.016, DURATION, 0.0);
+        assert!(factor.is_finite() && (0.0..=1.0).contains(&factor));
+    }
+}
@@ -21,73 +21,180 @@ use eframe::{egui, egui::Context};
 use eframe::Theme;
 
 use log::info;
+use midi::{SessionSummary, TapTempo};
 use sync::Mutex;
 
 use errors::{MakeReportExt, Result};
-use midi::bpm::max_histogram_data_buffer_size;
 
-pub use crate::application_parameters::BPMDetectionParameters;
-use crate::gui_remote::HistogramDataPoints;
+pub use crate::application_parameters::{BPMDetectionParameters, MidiInputs};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::WindowLevelState;
+use crate::{drift::DriftTracker, gui_remote::HistogramDataPoints, note_strip::NoteHistory};
 
+mod about;
+// building blocks of the settings panel, `ParameterSlider` is the stable way to add sliders
+#[doc(hidden)]
 pub mod add_slider;
 mod app;
 mod application_parameters;
 mod config;
-mod config_ui;
+mod drift;
+pub mod effective_config;
 mod gui_remote;
+mod interpolation;
+mod metronome;
+mod note_strip;
+mod onboarding;
+mod resample;
+mod settings_panel;
+pub mod snapshot;
+mod ui_state;
+pub mod unknown_keys;
 
-pub use config::GUIConfig;
-
-pub fn create_gui<P: BPMDetectionParameters>(bpm_detection_parameters: P) -> (GuiRemote, GUIBuilder<P>) {
-    let estimated_bpm = Arc::new(AtomicF32::new(f32::NAN));
-    let daw_bpm = Arc::new(AtomicF32::new(f32::NAN));
-    let should_save = Arc::new(AtomicBool::default());
+pub use about::{about_info, AboutInfo, ConfigPaths};
+pub use config::{parameter_catalog, AlwaysOnTop, GUIConfig, MetronomeConfig, WindowBehavior, YScale};
+pub use parameter::{Asf64, Parameter};
+pub use settings_panel::{render_settings_panel, PanelOptions, ParameterSlider};
+pub use ui_state::{UiState, UiStateHandle};
 
+/// Remote of the GUI, and the builder it starts from. Only the state shared with the remote is created here: the GUI
+/// itself and its histogram buffers are left to `GUIBuilder::build`, for applications that may never show it. See
+/// `GUIBuilder::eager` otherwise
+pub fn create_gui<P: BPMDetectionParameters + 'static>(bpm_detection_parameters: P) -> (GuiRemote, GUIBuilder<P>) {
     let context_receiver = Arc::new(AtomicRefCell::new(None));
-    let keys_sender = Arc::new(Mutex::new(None));
-    let weak_keys_sender = Arc::downgrade(&keys_sender);
-    let gui_exit_callback = Arc::new(Mutex::new(None));
-    let weak_on_gui_exit_callback = Arc::downgrade(&gui_exit_callback);
+    let pending_context = Arc::new(Mutex::new(None));
+    let gui_remote = GuiRemote {
+        context: context_receiver.clone(),
+        pending_context: pending_context.clone(),
+        keys_sender: Arc::new(Mutex::new(None)),
+        on_gui_exit_callback: Arc::new(Mutex::new(None)),
+        // they take the size of the histograms received until the GUI starts, see `GuiRemote::preallocate_histograms`
+        swap_histogram_data_points: Arc::new(AtomicRefCell::new(Vec::new())),
+        histogram_data_points: Arc::new(AtomicRefCell::new(HistogramDataPoints::default())),
+        estimated_bpm: Arc::new(AtomicF32::new(f32::NAN)),
+        estimated_confidence: Arc::new(AtomicF32::new(f32::NAN)),
+        daw_bpm: Arc::new(AtomicF32::new(f32::NAN)),
+        should_save: Arc::new(AtomicBool::default()),
+        should_export_snapshot: Arc::new(AtomicBool::default()),
+        tui_focused: Arc::new(AtomicBool::default()),
+        should_cycle_always_on_top: Arc::new(AtomicBool::default()),
+        note_history: Arc::new(Mutex::new(NoteHistory::default())),
+        config_warnings: Arc::new(Mutex::new(Vec::new())),
+        bar_position: Arc::new(Mutex::new(None)),
+        beat_anchor: Arc::new(Mutex::new(None)),
+        velocity_gate: Arc::new(Mutex::new(None)),
+        auto_zoom: Arc::new(Mutex::new(None)),
+        tapped_bpm: Arc::new(Mutex::new(None)),
+        clock_bpm: Arc::new(Mutex::new(None)),
+        frozen_histogram: Arc::new(Mutex::new(None)),
+        session_summary: Arc::new(Mutex::new(SessionSummary::default())),
+        note_freshness: Arc::new(Mutex::new(None)),
+        feel_ambiguity: Arc::new(Mutex::new(None)),
+        multi_resolution: Arc::new(Mutex::new(None)),
+        resolution_floor: Arc::new(Mutex::new(None)),
+        tempo_band: Arc::new(Mutex::new(None)),
+        phase_split_window: Arc::new(Mutex::new(bpm_detection_parameters.get_gui_config().phase_split())),
+        phase_split_histogram: Arc::new(Mutex::new(None)),
+        low_memory: bpm_detection_parameters.get_gui_config().low_memory,
+    };
+
+    let bpm_detection_gui = Box::new({
+        let gui_remote = gui_remote.clone();
+        move || bpm_detection_gui(&gui_remote, bpm_detection_parameters)
+    });
+    (gui_remote, GUIBuilder { context_receiver, pending_context, bpm_detection_gui })
+}
+
+// the GUI only holds weak references to the state shared with the remote, so it notices once the remote is gone
+fn bpm_detection_gui<P: BPMDetectionParameters>(
+    gui_remote: &GuiRemote,
+    mut bpm_detection_parameters: P,
+) -> BPMDetectionGUI<P> {
+    gui_remote.preallocate_histograms();
 
-    let histogram_data_points = Arc::new(AtomicRefCell::new(HistogramDataPoints::default()));
+    let about_info = about_info(bpm_detection_parameters.config_paths());
 
-    let bpm_detection_gui = BPMDetectionGUI {
-        keys_sender: weak_keys_sender,
+    let ui_state = bpm_detection_parameters.load_ui_state().unwrap_or_default();
+    if let Some(y_scale) = ui_state.y_scale {
+        bpm_detection_parameters.get_gui_config_mut().y_scale = y_scale;
+    }
+
+    BPMDetectionGUI {
+        keys_sender: Arc::downgrade(&gui_remote.keys_sender),
+        #[cfg(not(target_arch = "wasm32"))]
+        on_gui_exit_callback: Arc::downgrade(&gui_remote.on_gui_exit_callback),
+        histogram_data_points: Arc::downgrade(&gui_remote.histogram_data_points),
+        interpolated_data_points: Vec::new(),
+        interpolated_y_scale: None,
+        interpolated_parameters: None,
+        estimated_bpm: Arc::downgrade(&gui_remote.estimated_bpm),
+        estimated_confidence: Arc::downgrade(&gui_remote.estimated_confidence),
+        daw_bpm: Arc::downgrade(&gui_remote.daw_bpm),
+        should_save: Arc::downgrade(&gui_remote.should_save),
+        should_export_snapshot: Arc::downgrade(&gui_remote.should_export_snapshot),
         #[cfg(not(target_arch = "wasm32"))]
-        on_gui_exit_callback: weak_on_gui_exit_callback,
-        histogram_data_points: Arc::downgrade(&histogram_data_points),
-        interpolated_data_points: Vec::with_capacity(max_histogram_data_buffer_size()),
-        estimated_bpm: Arc::downgrade(&estimated_bpm),
-        daw_bpm: Arc::downgrade(&daw_bpm),
-        should_save: Arc::downgrade(&should_save),
+        tui_focused: Arc::downgrade(&gui_remote.tui_focused),
+        should_cycle_always_on_top: Arc::downgrade(&gui_remote.should_cycle_always_on_top),
+        note_history: Arc::downgrade(&gui_remote.note_history),
+        config_warnings: Arc::downgrade(&gui_remote.config_warnings),
+        bar_position: Arc::downgrade(&gui_remote.bar_position),
+        beat_anchor: Arc::downgrade(&gui_remote.beat_anchor),
+        velocity_gate: Arc::downgrade(&gui_remote.velocity_gate),
+        auto_zoom: Arc::downgrade(&gui_remote.auto_zoom),
+        tapped_bpm: Arc::downgrade(&gui_remote.tapped_bpm),
+        tap_tempo: TapTempo::default(),
+        reference_bpm: None,
+        excluded_range_drag: None,
+        clock_bpm: Arc::downgrade(&gui_remote.clock_bpm),
+        frozen_histogram: Arc::downgrade(&gui_remote.frozen_histogram),
+        session_summary: Arc::downgrade(&gui_remote.session_summary),
+        note_freshness: Arc::downgrade(&gui_remote.note_freshness),
+        feel_ambiguity: Arc::downgrade(&gui_remote.feel_ambiguity),
+        multi_resolution: Arc::downgrade(&gui_remote.multi_resolution),
+        resolution_floor: Arc::downgrade(&gui_remote.resolution_floor),
+        tempo_band: Arc::downgrade(&gui_remote.tempo_band),
+        phase_split_window: Arc::downgrade(&gui_remote.phase_split_window),
+        phase_split_histogram: Arc::downgrade(&gui_remote.phase_split_histogram),
+        drift_tracker: DriftTracker::default(),
+        ui_state: UiStateHandle::new(ui_state),
+        #[cfg(not(target_arch = "wasm32"))]
+        window_level_state: WindowLevelState::default(),
         live_parameters: bpm_detection_parameters,
-    };
-
-    let gui_remote = GuiRemote {
-        context: context_receiver.clone(),
-        keys_sender,
-        on_gui_exit_callback: gui_exit_callback,
-        swap_histogram_data_points: Arc::new(AtomicRefCell::new(Vec::with_capacity(max_histogram_data_buffer_size()))),
-        histogram_data_points,
-        estimated_bpm,
-        daw_bpm,
-        should_save,
-    };
-    (gui_remote, GUIBuilder { context_receiver, bpm_detection_gui })
+        about_info,
+        show_about: false,
+        effective_config: None,
+        plot_rect: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        pending_screenshot: None,
+        status_message: None,
+    }
 }
 
 pub struct GUIBuilder<P: BPMDetectionParameters + 'static> {
     context_receiver: Arc<AtomicRefCell<Option<Context>>>,
-    bpm_detection_gui: BPMDetectionGUI<P>,
+    pending_context: Arc<Mutex<Option<Context>>>,
+    // holds a clone of the remote until the GUI is built
+    bpm_detection_gui: Box<dyn FnOnce() -> BPMDetectionGUI<P>>,
 }
 
 impl<P> GUIBuilder<P>
 where
     P: BPMDetectionParameters + 'static,
 {
+    /// Creates the GUI and allocates its buffers right away, for applications that start it as soon as they can
+    #[must_use]
+    pub fn eager(self) -> Self {
+        let bpm_detection_gui = (self.bpm_detection_gui)();
+        Self {
+            context_receiver: self.context_receiver,
+            pending_context: self.pending_context,
+            bpm_detection_gui: Box::new(move || bpm_detection_gui),
+        }
+    }
+
     pub fn build(self, context: Context) -> BPMDetectionGUI<P> {
-        self.context_receiver.borrow_mut().replace(context);
-        self.bpm_detection_gui
+        gui_remote::set_context(&self.context_receiver, &self.pending_context, context);
+        (self.bpm_detection_gui)()
     }
 }
 
@@ -110,8 +217,7 @@ where
             move |cc| {
                 // This gives us image support:
                 egui_extras::install_image_loaders(&cc.egui_ctx);
-                gui_builder.context_receiver.borrow_mut().replace(cc.egui_ctx.clone());
-                Box::new(gui_builder.bpm_detection_gui)
+                Box::new(gui_builder.build(cc.egui_ctx.clone()))
             }
         }),
     )
@@ -133,10 +239,7 @@ where
             .start(
                 "the_canvas_id", // hardcode it
                 web_options,
-                Box::new(move |cc| {
-                    gui_builder.context_receiver.borrow_mut().replace(cc.egui_ctx.clone());
-                    Box::new(gui_builder.bpm_detection_gui)
-                }),
+                Box::new(move |cc| Box::new(gui_builder.build(cc.egui_ctx.clone()))),
             )
             .await
             .expect("failed to start eframe");
@@ -146,3 +249,94 @@ where
 
 pub static GIT_COMMIT_HASH: &str = env!("_GIT_INFO");
 include!(concat!(env!("OUT_DIR"), "/build_time.rs"));
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use midi::{
+        bpm::max_histogram_data_buffer_size, bpm_detection_receiver::BPMDetectionReceiver, BpmEstimate,
+        DynamicBPMDetectionParameters, StaticBPMDetectionParameters,
+    };
+    use std::sync::atomic::Ordering;
+
+    #[derive(Default)]
+    pub(crate) struct TestParameters {
+        dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+        static_bpm_detection_parameters: StaticBPMDetectionParameters,
+        pub(crate) gui_config: GUIConfig,
+    }
+
+    impl BPMDetectionParameters for TestParameters {
+        type Error = ();
+
+        fn get_dynamic_bpm_detection_parameters(&self) -> &DynamicBPMDetectionParameters {
+            &self.dynamic_bpm_detection_parameters
+        }
+
+        fn get_dynamic_bpm_detection_parameters_mut(&mut self) -> &mut DynamicBPMDetectionParameters {
+            &mut self.dynamic_bpm_detection_parameters
+        }
+
+        fn get_static_bpm_detection_parameters(&self) -> &StaticBPMDetectionParameters {
+            &self.static_bpm_detection_parameters
+        }
+
+        fn get_static_bpm_detection_parameters_mut(&mut self) -> &mut StaticBPMDetectionParameters {
+            &mut self.static_bpm_detection_parameters
+        }
+
+        fn get_gui_config(&self) -> &GUIConfig {
+            &self.gui_config
+        }
+
+        fn get_gui_config_mut(&mut self) -> &mut GUIConfig {
+            &mut self.gui_config
+        }
+
+        fn get_send_tempo(&self) -> bool {
+            false
+        }
+
+        fn set_send_tempo(&mut self, _enabled: bool) {}
+
+        fn apply_static(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn apply_dynamic(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_receive_before_gui_starts() {
+        let (mut gui_remote, gui_builder) = create_gui(TestParameters::default());
+
+        // without a context nor a GUI, the histograms take the size received
+        gui_remote.receive_bpm_histogram_data(&[1.0, 2.0], BpmEstimate { bpm: 120.0, confidence: 1.0 });
+        gui_remote.receive_bpm_histogram_data(&[3.0, 4.0, 5.0], BpmEstimate { bpm: 130.0, confidence: 0.5 });
+        gui_remote.request_repaint();
+        assert!(gui_remote.histogram_data_points.borrow().inbound_histogram_data_points.capacity() < 100);
+
+        // the first frame draws the latest histogram
+        let gui = gui_builder.build(Context::default());
+        let histogram_data_points = gui.histogram_data_points.upgrade().unwrap();
+        let histogram_data_points = histogram_data_points.borrow();
+        assert_eq!(histogram_data_points.inbound_histogram_data_points, [3.0, 4.0, 5.0]);
+        assert!(histogram_data_points.inbound_histogram_data_points.capacity() >= max_histogram_data_buffer_size());
+        assert!(gui.estimated_bpm.upgrade().unwrap().load(Ordering::Relaxed).total_cmp(&130.0).is_eq());
+        assert!(gui_remote.get_context().is_some());
+    }
+
+    #[test]
+    fn test_eager_gui() {
+        let (gui_remote, gui_builder) = create_gui(TestParameters::default());
+        let gui_builder = gui_builder.eager();
+        assert!(gui_remote.swap_histogram_data_points.borrow().capacity() >= max_histogram_data_buffer_size());
+
+        let gui = gui_builder.build(Context::default());
+        drop(gui_remote);
+        // the GUI notices the remote is gone
+        assert!(gui.histogram_data_points.upgrade().is_none());
+    }
+}
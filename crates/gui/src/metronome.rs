@@ -0,0 +1,197 @@
+use crate::egui::{vec2, Color32, Sense, Stroke, Ui};
+use instant::Instant;
+use midi::BeatPhase;
+
+// seconds for a flash to fade to about a third of its brightness
+const FLASH_DECAY: f64 = 0.1;
+// below that, the flash is over and no repaint is needed until the next beat
+const FLASH_VISIBLE: f32 = 0.02;
+// the beats stop being predicted that many beats after the newest note, once nothing is played anymore
+const PREDICTED_BEATS: f64 = 16.0;
+// a beat landing on the boundary is counted as reached
+const EPSILON: f64 = 1e-9;
+const FLASH_RADIUS: f32 = 7.0;
+
+/// Phase of the beat count, along with when it was received
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BeatAnchor {
+    pub(crate) received: Instant,
+    pub(crate) phase: BeatPhase,
+}
+
+/// Beat of the count, at `seconds` from the phase anchor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Beat {
+    pub(crate) seconds: f64,
+    pub(crate) index: u64,
+}
+
+fn beat_duration(phase: &BeatPhase) -> Option<f64> {
+    (phase.bpm.is_finite() && phase.bpm > 0.0 && phase.elapsed_beats.is_finite()).then(|| 60.0 / f64::from(phase.bpm))
+}
+
+fn beat(phase: &BeatPhase, beat_duration: f64, index: f64) -> Option<Beat> {
+    (index >= 0.0 && index <= phase.elapsed_beats + PREDICTED_BEATS)
+        .then(|| Beat { seconds: (index - phase.elapsed_beats) * beat_duration, index: index as u64 })
+}
+
+/// First beat strictly after `seconds` from the anchor, at the tempo of the phase. A tempo change only reaches the
+/// GUI with the phase counted until then, the beats before the anchor are predicted with the new tempo as well. None
+/// once the predicted beats run out, or without a valid tempo
+pub(crate) fn next_beat(phase: &BeatPhase, seconds: f64) -> Option<Beat> {
+    let beat_duration = beat_duration(phase)?;
+    let index = (phase.elapsed_beats + seconds / beat_duration + EPSILON).floor() + 1.0;
+    beat(phase, beat_duration, index)
+}
+
+/// Last beat at or before `seconds` from the anchor, none before the count started, see `next_beat`
+pub(crate) fn previous_beat(phase: &BeatPhase, seconds: f64) -> Option<Beat> {
+    let beat_duration = beat_duration(phase)?;
+    let index = (phase.elapsed_beats + seconds / beat_duration + EPSILON).floor();
+    beat(phase, beat_duration, index)
+}
+
+fn is_downbeat(phase: &BeatPhase, index: u64) -> bool {
+    // a bar of 3.5 beats starts on a beat every other bar only
+    let offset = (index as f64).rem_euclid(phase.bar_length);
+    !phase.bar_length.is_normal() || offset < EPSILON || phase.bar_length - offset < EPSILON
+}
+
+/// Brightness of the flash at `seconds` from the anchor, and the seconds until the next flash starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Flash {
+    pub(crate) intensity: f32,
+    pub(crate) next_in: Option<f64>,
+}
+
+pub(crate) fn flash(phase: &BeatPhase, seconds: f64, downbeat_only: bool) -> Flash {
+    let flashes = |beat: &Beat| !downbeat_only || is_downbeat(phase, beat.index);
+    let beat_duration = beat_duration(phase).unwrap_or_default();
+
+    let intensity = previous_beat(phase, seconds)
+        .filter(flashes)
+        .map_or(0.0, |beat| (-(seconds - beat.seconds).max(0.0) / FLASH_DECAY).exp() as f32);
+    let next_in =
+        std::iter::successors(next_beat(phase, seconds), |beat| next_beat(phase, beat.seconds + beat_duration / 2.0))
+            .find(flashes)
+            .map(|beat| beat.seconds - seconds);
+    Flash { intensity, next_in }
+}
+
+impl Flash {
+    pub(crate) fn is_visible(self) -> bool {
+        self.intensity > FLASH_VISIBLE
+    }
+}
+
+/// Circle lit by the flash, outlined while it is off
+pub(crate) fn flash_circle(ui: &mut Ui, color: [u8; 3], intensity: f32) {
+    let (rect, _) = ui.allocate_exact_size(vec2(FLASH_RADIUS, FLASH_RADIUS) * 2.0, Sense::hover());
+    let painter = ui.painter_at(rect);
+    let [r, g, b] = color;
+    let alpha = (intensity.clamp(0.0, 1.0) * 255.0) as u8;
+    painter.circle_filled(rect.center(), FLASH_RADIUS - 1.0, Color32::from_rgba_unmultiplied(r, g, b, alpha));
+    painter.circle_stroke(rect.center(), FLASH_RADIUS - 1.0, Stroke::new(1.0, ui.visuals().weak_text_color()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase(elapsed_beats: f64, bpm: f32) -> BeatPhase {
+        BeatPhase { elapsed_beats, bpm, bar_length: 4.0 }
+    }
+
+    fn assert_beat(beat: Option<Beat>, seconds: f64, index: u64) {
+        let beat = beat.expect("no beat");
+        assert!((beat.seconds - seconds).abs() < 1e-9 && beat.index == index, "{beat:?}");
+    }
+
+    #[test]
+    fn test_next_beat() {
+        // the anchor is on beat 2 at 120 BPM
+        let phase = phase(2.0, 120.0);
+        assert_beat(next_beat(&phase, 0.0), 0.5, 3);
+        assert_beat(previous_beat(&phase, 0.0), 0.0, 2);
+        assert_beat(next_beat(&phase, 0.2), 0.5, 3);
+        assert_beat(previous_beat(&phase, 0.2), 0.0, 2);
+        // on a beat, the next one is a beat later
+        assert_beat(next_beat(&phase, 0.5), 1.0, 4);
+        assert_beat(previous_beat(&phase, 0.5), 0.5, 3);
+        assert_beat(next_beat(&phase, 3.1), 3.5, 9);
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        // 2.5 beats at 120 BPM, then the tempo changes to 60 BPM: the next beat is half a beat away at the new tempo
+        let phase = phase(2.5, 60.0);
+        assert_beat(next_beat(&phase, 0.0), 0.5, 3);
+        assert_beat(next_beat(&phase, 0.5), 1.5, 4);
+        assert_beat(previous_beat(&phase, 0.4), -0.5, 2);
+        // before the anchor, the beats follow the new tempo too
+        assert_beat(previous_beat(&phase, -0.6), -1.5, 1);
+        // nothing before the first note
+        assert_eq!(previous_beat(&phase, -2.6), None);
+        assert_beat(next_beat(&phase, -2.6), -2.5, 0);
+
+        // the same count going on at 180 BPM
+        let phase = BeatPhase { bpm: 180.0, ..phase };
+        assert_beat(next_beat(&phase, 0.0), 1.0 / 6.0, 3);
+        assert_beat(next_beat(&phase, 1.0 / 6.0), 0.5, 4);
+    }
+
+    #[test]
+    fn test_predictions_end() {
+        let phase = phase(2.0, 60.0);
+        assert_beat(next_beat(&phase, PREDICTED_BEATS - 0.5), PREDICTED_BEATS, 18);
+        assert_eq!(next_beat(&phase, PREDICTED_BEATS), None);
+        assert_beat(previous_beat(&phase, PREDICTED_BEATS + 0.5), PREDICTED_BEATS, 18);
+        assert_eq!(previous_beat(&phase, PREDICTED_BEATS + 1.0), None);
+        assert_eq!(flash(&phase, PREDICTED_BEATS + 1.0, false), Flash { intensity: 0.0, next_in: None });
+    }
+
+    #[test]
+    fn test_invalid_tempo() {
+        for bpm in [0.0, -120.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(next_beat(&phase(2.0, bpm), 0.0), None);
+            assert_eq!(previous_beat(&phase(2.0, bpm), 0.0), None);
+            assert_eq!(flash(&phase(2.0, bpm), 0.0, false), Flash { intensity: 0.0, next_in: None });
+        }
+        assert_eq!(next_beat(&phase(f64::NAN, 120.0), 0.0), None);
+    }
+
+    #[test]
+    fn test_flash() {
+        let phase = phase(3.0, 120.0);
+        let on_beat = flash(&phase, 0.0, false);
+        assert!((on_beat.intensity - 1.0).abs() < f32::EPSILON);
+        assert!(on_beat.is_visible());
+        assert!((on_beat.next_in.unwrap() - 0.5).abs() < 1e-9);
+
+        let fading = flash(&phase, FLASH_DECAY, false);
+        assert!((fading.intensity - (-1.0f32).exp()).abs() < 1e-6);
+        let over = flash(&phase, 0.45, false);
+        assert!(!over.is_visible());
+        assert!((over.next_in.unwrap() - 0.05).abs() < 1e-9);
+
+        // beat 3 is not a downbeat in 4/4, the next one is beat 4
+        let downbeats = flash(&phase, 0.0, true);
+        assert!(downbeats.intensity.abs() < f32::EPSILON);
+        assert!((downbeats.next_in.unwrap() - 0.5).abs() < 1e-9);
+        let downbeats = flash(&phase, 0.5, true);
+        assert!((downbeats.intensity - 1.0).abs() < f32::EPSILON);
+        assert!((downbeats.next_in.unwrap() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_downbeats() {
+        let seven_eighths = BeatPhase { elapsed_beats: 0.0, bpm: 60.0, bar_length: 3.5 };
+        let downbeats = (0..15).filter(|index| is_downbeat(&seven_eighths, *index)).collect::<Vec<_>>();
+        assert_eq!(downbeats, vec![0, 7, 14]);
+        assert!((flash(&seven_eighths, 0.5, true).next_in.unwrap() - 6.5).abs() < 1e-9);
+
+        let three_four = BeatPhase { elapsed_beats: 0.0, bpm: 60.0, bar_length: 3.0 };
+        let downbeats = (0..10).filter(|index| is_downbeat(&three_four, *index)).collect::<Vec<_>>();
+        assert_eq!(downbeats, vec![0, 3, 6, 9]);
+    }
+}
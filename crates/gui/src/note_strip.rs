@@ -0,0 +1,126 @@
+use crate::egui::{pos2, vec2, Color32, Rect, Sense, Stroke, Ui};
+use std::collections::VecDeque;
+
+pub(crate) const NOTE_HISTORY_CAPACITY: usize = 200;
+// seconds of notes shown in the strip
+const STRIP_DURATION: f64 = 4.0;
+const STRIP_HEIGHT: f32 = 60.0;
+// beyond that, the grid would be a solid block anyway
+const MAX_GRID_LINES: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct NoteHistoryEntry {
+    // seconds, in the time base of the detection
+    pub(crate) timestamp: f64,
+    pub(crate) note: u8,
+    pub(crate) velocity: u8,
+}
+
+/// Most recent notes received by the detection, oldest first
+#[derive(Debug, Default)]
+pub(crate) struct NoteHistory {
+    notes: VecDeque<NoteHistoryEntry>,
+}
+
+impl NoteHistory {
+    pub(crate) fn push(&mut self, note: NoteHistoryEntry) {
+        if self.notes.len() == NOTE_HISTORY_CAPACITY {
+            self.notes.pop_front();
+        }
+        self.notes.push_back(note);
+    }
+
+    pub(crate) fn latest(&self) -> Option<&NoteHistoryEntry> {
+        self.notes.back()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &NoteHistoryEntry> {
+        self.notes.iter()
+    }
+}
+
+/// Beats of a `bpm` grid going through `anchor`, between `from` and `to`, in seconds
+pub(crate) fn grid_lines(bpm: f32, anchor: f64, from: f64, to: f64) -> Vec<f64> {
+    if !bpm.is_finite() || bpm <= 0.0 || from > to {
+        return Vec::new();
+    }
+    let beat = 60.0 / f64::from(bpm);
+    let first = ((from - anchor) / beat).ceil();
+    (0..MAX_GRID_LINES).map(|index| anchor + (first + index as f64) * beat).take_while(|line| *line <= to).collect()
+}
+
+/// Paints the last seconds of notes against the grid implied by the detected tempo. There is no phase information, so
+/// the grid goes through the most recent note.
+pub(crate) fn note_strip(ui: &mut Ui, note_history: &NoteHistory, bpm: f32) {
+    let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), STRIP_HEIGHT), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let Some(latest) = note_history.latest() else {
+        return;
+    };
+    let to = latest.timestamp;
+    let from = to - STRIP_DURATION;
+    let x = |timestamp: f64| rect.left() + ((timestamp - from) / STRIP_DURATION) as f32 * rect.width();
+
+    let grid_stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+    for line in grid_lines(bpm, latest.timestamp, from, to) {
+        painter.vline(x(line), rect.y_range(), grid_stroke);
+    }
+
+    for note in note_history.iter().filter(|note| note.timestamp >= from) {
+        let y = rect.bottom() - f32::from(note.note) / 127.0 * rect.height();
+        let alpha = 80 + (u16::from(note.velocity) * 175 / 127) as u8;
+        painter.rect_filled(
+            Rect::from_center_size(pos2(x(note.timestamp), y), vec2(3.0, 4.0)),
+            0.0,
+            Color32::from_rgba_unmultiplied(255, 200, 80, alpha),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(timestamp: f64) -> NoteHistoryEntry {
+        NoteHistoryEntry { timestamp, note: 60, velocity: 100 }
+    }
+
+    fn assert_lines(lines: &[f64], expected: &[f64]) {
+        assert_eq!(lines.len(), expected.len(), "{lines:?}");
+        for (line, expected) in lines.iter().zip(expected) {
+            assert!((line - expected).abs() < 1e-9, "{lines:?}");
+        }
+    }
+
+    #[test]
+    fn test_grid_lines() {
+        // 120 BPM, a beat every half second, anchored on the latest note at the end of the window
+        assert_lines(&grid_lines(120.0, 10.0, 8.0, 10.0), &[8.0, 8.5, 9.0, 9.5, 10.0]);
+        // anchor outside the window, not aligned on its bounds
+        assert_lines(&grid_lines(120.0, 0.25, 8.0, 10.0), &[8.25, 8.75, 9.25, 9.75]);
+        assert_lines(&grid_lines(60.0, 20.1, 8.0, 10.0), &[8.1, 9.1]);
+    }
+
+    #[test]
+    fn test_grid_lines_invalid_tempo() {
+        assert!(grid_lines(f32::NAN, 10.0, 8.0, 10.0).is_empty());
+        assert!(grid_lines(0.0, 10.0, 8.0, 10.0).is_empty());
+        assert!(grid_lines(-120.0, 10.0, 8.0, 10.0).is_empty());
+        assert!(grid_lines(120.0, 10.0, 10.0, 8.0).is_empty());
+        assert_eq!(grid_lines(f32::MAX, 10.0, 8.0, 10.0).len(), MAX_GRID_LINES);
+    }
+
+    #[test]
+    fn test_bounded_history() {
+        let mut note_history = NoteHistory::default();
+        assert!(note_history.latest().is_none());
+        for index in 0..NOTE_HISTORY_CAPACITY + 50 {
+            note_history.push(note(index as f64));
+        }
+        assert_eq!(note_history.iter().count(), NOTE_HISTORY_CAPACITY);
+        assert_eq!(note_history.iter().next(), Some(&note(50.0)));
+        assert_eq!(note_history.latest(), Some(&note((NOTE_HISTORY_CAPACITY + 49) as f64)));
+    }
+}
@@ -0,0 +1,116 @@
+//! Getting started steps shown over the plot on the first run, until they are closed. A new user otherwise sees an
+//! empty plot without knowing that notes have to reach the detection. Each step is ticked off as soon as what it asks
+//! for happened, from the state the GUI already shares with the detection
+
+use crate::{application_parameters::MidiInputs, ui_state::UiStateHandle};
+use eframe::{egui, egui::Context};
+
+/// Which of the steps are done
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct OnboardingProgress {
+    pub(crate) input_connected: bool,
+    pub(crate) notes_received: bool,
+    pub(crate) estimated: bool,
+}
+
+impl OnboardingProgress {
+    /// `midi_inputs` are the ones the GUI picks from, if the application has any. `estimated_bpm` is NaN until there is
+    /// an estimate
+    pub(crate) fn new(midi_inputs: Option<&MidiInputs>, notes_received: bool, estimated_bpm: f32) -> Self {
+        // the notes are the only sign of a connection when the input is routed outside the GUI
+        let input_connected = midi_inputs
+            .map_or(notes_received, |midi_inputs| midi_inputs.selected.is_some() && midi_inputs.message.is_none());
+        Self { input_connected, notes_received, estimated: estimated_bpm.is_finite() }
+    }
+
+    fn steps(self, input_step: &str) -> [(&str, bool); 3] {
+        [
+            (input_step, self.input_connected),
+            ("Play something, the notes received show up as they come", self.notes_received),
+            ("Adjust the BPM range in the settings so it contains the tempo played", self.estimated),
+        ]
+    }
+}
+
+/// `input_step` tells how notes reach the application, see `BPMDetectionParameters::midi_input_step`
+pub(crate) fn onboarding_window(
+    ctx: &Context,
+    ui_state: &UiStateHandle,
+    progress: OnboardingProgress,
+    input_step: &str,
+) {
+    let mut open = true;
+    let mut dismissed = false;
+    egui::Window::new("Getting started")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            for (number, (step, done)) in progress.steps(input_step).into_iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if done {
+                        ui.colored_label(egui::Color32::GREEN, "✔");
+                    } else {
+                        ui.label("○");
+                    }
+                    ui.label(format!("{}. {step}", number + 1));
+                });
+            }
+            ui.separator();
+            dismissed = ui.button("Got it").clicked();
+        });
+    if dismissed || !open {
+        ui_state.update(|ui_state| ui_state.onboarding_dismissed = true, instant::Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn midi_inputs(selected: Option<&str>, message: Option<&str>) -> MidiInputs {
+        MidiInputs {
+            inputs: vec![("1".to_string(), "Keyboard".to_string())],
+            selected: selected.map(str::to_string),
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_nothing_done() {
+        assert_eq!(OnboardingProgress::new(None, false, f32::NAN), OnboardingProgress::default());
+        assert_eq!(
+            OnboardingProgress::new(Some(&midi_inputs(None, None)), false, f32::NAN),
+            OnboardingProgress::default()
+        );
+    }
+
+    #[test]
+    fn test_input_picked_in_the_gui() {
+        let progress = OnboardingProgress::new(Some(&midi_inputs(Some("1"), None)), false, f32::NAN);
+        assert_eq!(progress, OnboardingProgress { input_connected: true, ..OnboardingProgress::default() });
+        // picked but can't be listened to
+        let progress = OnboardingProgress::new(Some(&midi_inputs(Some("1"), Some("unplugged"))), false, f32::NAN);
+        assert!(!progress.input_connected);
+        // no input picked, the notes come from elsewhere
+        let progress = OnboardingProgress::new(Some(&midi_inputs(None, None)), true, f32::NAN);
+        assert!(!progress.input_connected && progress.notes_received);
+    }
+
+    #[test]
+    fn test_input_routed_outside_the_gui() {
+        let progress = OnboardingProgress::new(None, true, f32::NAN);
+        assert_eq!(progress, OnboardingProgress { input_connected: true, notes_received: true, estimated: false });
+        let progress = OnboardingProgress::new(None, true, 120.0);
+        assert_eq!(progress, OnboardingProgress { input_connected: true, notes_received: true, estimated: true });
+    }
+
+    #[test]
+    fn test_steps() {
+        let progress = OnboardingProgress { input_connected: true, notes_received: false, estimated: false };
+        let steps = progress.steps("Route a MIDI track to the plugin");
+        assert_eq!(steps[0], ("Route a MIDI track to the plugin", true));
+        assert_eq!(steps.map(|(_, done)| done), [true, false, false]);
+    }
+}
@@ -0,0 +1,95 @@
+use midi::StaticBPMDetectionParameters;
+
+/// Position, in BPM, of each of the `len` bins of a histogram computed with `parameters`. The tempo decreases along
+/// the axis
+#[must_use]
+pub fn bpm_axis(parameters: &StaticBPMDetectionParameters, len: usize) -> Vec<f32> {
+    (0..len).map(|index| parameters.index_to_bpm(index)).collect()
+}
+
+/// Values of a histogram shown over `old_axis`, read at the positions of `new_axis` by linear interpolation between
+/// the two closest old positions. An axis holds the position of each bin, ascending or descending. Positions outside of
+/// the old axis get 0, nothing was shown there
+#[must_use]
+pub fn resample(old_axis: &[f32], old_values: &[f32], new_axis: &[f32]) -> Vec<f32> {
+    let len = old_axis.len().min(old_values.len());
+    let (old_axis, old_values) = (&old_axis[..len], &old_values[..len]);
+    let descending = len > 1 && old_axis[0] > old_axis[len - 1];
+
+    new_axis
+        .iter()
+        .map(|position| {
+            // first old position that is not before the new one
+            let next = old_axis.partition_point(|old| if descending { old > position } else { old < position });
+            if next == len {
+                return 0.0;
+            }
+            if old_axis[next].total_cmp(position).is_eq() {
+                return old_values[next];
+            }
+            if next == 0 {
+                return 0.0;
+            }
+            let previous = next - 1;
+            let t = (position - old_axis[previous]) / (old_axis[next] - old_axis[previous]);
+            old_values[previous] * (1.0 - t) + old_values[next] * t
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argmax(values: &[f32]) -> usize {
+        values.iter().enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b)).map(|(index, _)| index).unwrap()
+    }
+
+    #[test]
+    fn test_identity() {
+        let parameters = StaticBPMDetectionParameters::default();
+        let axis = bpm_axis(&parameters, parameters.buffer_size());
+        let values = (0..axis.len()).map(|index| (index as f32 * 0.37).sin().abs()).collect::<Vec<_>>();
+        assert_eq!(resample(&axis, &values, &axis), values);
+
+        let ascending = [1.0, 2.0, 4.0];
+        assert_eq!(resample(&ascending, &[0.1, 0.5, 0.9], &ascending), [0.1, 0.5, 0.9]);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let resampled = resample(&[1.0, 2.0, 4.0], &[0.0, 1.0, 0.0], &[0.5, 1.5, 3.0, 4.5]);
+        assert_eq!(resampled, [0.0, 0.5, 0.5, 0.0]);
+        let resampled = resample(&[4.0, 2.0, 1.0], &[0.0, 1.0, 0.0], &[4.5, 3.0, 1.5, 0.5]);
+        assert_eq!(resampled, [0.0, 0.5, 0.5, 0.0]);
+        assert_eq!(resample(&[], &[], &[1.0]), [0.0]);
+    }
+
+    #[test]
+    fn test_peak_follows_range_shift() {
+        let old_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 40,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let new_parameters = StaticBPMDetectionParameters { bpm_center: 110.0, ..old_parameters.clone() };
+        let old_axis = bpm_axis(&old_parameters, old_parameters.buffer_size());
+        let new_axis = bpm_axis(&new_parameters, new_parameters.buffer_size());
+
+        let peak_bpm = 105.0;
+        let old_values = old_axis.iter().map(|bpm| (-(bpm - peak_bpm).powi(2)).exp()).collect::<Vec<_>>();
+        let resampled = resample(&old_axis, &old_values, &new_axis);
+        assert_eq!(resampled.len(), new_axis.len());
+
+        let old_peak = argmax(&old_values);
+        let new_peak = argmax(&resampled);
+        assert_ne!(old_peak, new_peak);
+        assert!((new_axis[new_peak] - peak_bpm).abs() < 0.5, "{}", new_axis[new_peak]);
+        // the tempos above the old range were not shown
+        assert!(new_axis
+            .iter()
+            .zip(&resampled)
+            .filter(|(bpm, _)| **bpm > 120.5)
+            .all(|(_, value)| value.abs() < f32::EPSILON));
+    }
+}
@@ -0,0 +1,421 @@
+//! The settings of the detection as shown by the GUI, reusable against any configuration implementing
+//! [`BPMDetectionParameters`]
+
+use crate::BPMDetectionParameters;
+use eframe::{egui, egui::Ui};
+use std::fmt::Debug;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::AlwaysOnTop;
+use crate::{
+    add_slider::{add_slider, SlideAdder},
+    config::GUIConfig,
+    ui_state::UiStateHandle,
+};
+use log::error;
+use midi::{
+    channel_filter::MIDI_CHANNELS, AgeAnchor, ConfigWarning, DynamicBPMDetectionParameters, NormalDistributionConfig,
+    StaticBPMDetectionParameters, TempoSource, WeightResponse,
+};
+use parameter::{Asf64, Parameter};
+
+/// What [`render_settings_panel`] shows besides the detection parameters. New options may be added in minor versions,
+/// start from `PanelOptions::default()`
+#[derive(Clone, Debug)]
+pub struct PanelOptions {
+    config_warnings: Vec<ConfigWarning>,
+    display_settings: bool,
+    ui_state: Option<UiStateHandle>,
+}
+
+impl Default for PanelOptions {
+    fn default() -> Self {
+        Self { config_warnings: Vec::new(), display_settings: true, ui_state: None }
+    }
+}
+
+impl PanelOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Warnings shown next to the sliders of the parameters involved, as given by `midi::validate_interaction`
+    #[must_use]
+    pub fn with_config_warnings(mut self, config_warnings: Vec<ConfigWarning>) -> Self {
+        self.config_warnings = config_warnings;
+        self
+    }
+
+    /// Interpolation of the plot, note strip and window level. Shown by default
+    #[must_use]
+    pub fn with_display_settings(mut self, display_settings: bool) -> Self {
+        self.display_settings = display_settings;
+        self
+    }
+
+    /// Where the sections that can be collapsed keep whether they are open, they are closed on each start without it
+    #[must_use]
+    pub fn with_ui_state(mut self, ui_state: UiStateHandle) -> Self {
+        self.ui_state = Some(ui_state);
+        self
+    }
+}
+
+/// Slider for a [`Parameter`] read and written through closures, for values that don't live where the parameter
+/// points to. Adds the label and the slider as a row of a two columns `egui::Grid`
+pub struct ParameterSlider<'p, S, V> {
+    parameter: &'p Parameter<S, V>,
+    enabled: bool,
+    config_warnings: &'p [ConfigWarning],
+}
+
+impl<'p, S, V> ParameterSlider<'p, S, V>
+where
+    V: Asf64 + Copy + Debug,
+{
+    #[must_use]
+    pub fn new(parameter: &'p Parameter<S, V>) -> Self {
+        Self { parameter, enabled: true, config_warnings: &[] }
+    }
+
+    #[must_use]
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Warnings involving the parameter are shown next to the slider
+    #[must_use]
+    pub fn config_warnings(mut self, config_warnings: &'p [ConfigWarning]) -> Self {
+        self.config_warnings = config_warnings;
+        self
+    }
+
+    /// Returns whether `set` was called
+    pub fn show(self, ui: &mut Ui, get: impl Fn() -> V, mut set: impl FnMut(V)) -> bool {
+        let mut changed = false;
+        ui.label(self.parameter.label);
+        add_slider::<V, S, V>(ui, self.enabled, self.parameter, self.config_warnings, None, |value| match value {
+            None => get().get(),
+            Some(value) => {
+                let value = V::from(value);
+                set(value);
+                changed = true;
+                value.get()
+            }
+        });
+        changed
+    }
+}
+
+/// Shows the detection settings as a grid, applying changes through `apply_static`, `apply_dynamic` and `apply_gui`.
+///
+/// ```no_run
+/// use gui::{eframe::egui, render_settings_panel, BPMDetectionParameters, PanelOptions};
+///
+/// fn settings_window<C: BPMDetectionParameters>(ctx: &egui::Context, config: &mut C) {
+///     egui::Window::new("Settings").show(ctx, |ui| {
+///         render_settings_panel(ui, config, &PanelOptions::new().with_display_settings(false));
+///     });
+/// }
+/// ```
+pub fn render_settings_panel<C: BPMDetectionParameters>(ui: &mut Ui, config: &mut C, options: &PanelOptions) {
+    let config_warnings = options.config_warnings.as_slice();
+    let modulated_values = config.get_modulated_values();
+    egui::Grid::new("").num_columns(2).spacing([40.0, 4.0]).striped(true).show(ui, |ui| {
+        if options.display_settings {
+            let slide_adder_gui = SlideAdder::builder(ui, BPMDetectionParameters::apply_gui, config, config_warnings)
+                .with_modulated_values(&modulated_values);
+            let mut gui_sliders = slide_adder_gui.for_config(BPMDetectionParameters::get_gui_config_mut);
+            gui_sliders.add(&GUIConfig::INTERPOLATION_DURATION);
+            gui_sliders.add(&GUIConfig::INTERPOLATION_CURVE);
+            gui_sliders.add(&GUIConfig::DRIFT_TOLERANCE);
+            gui_sliders.add(&GUIConfig::PHASE_SPLIT_WINDOW);
+        }
+
+        let sliders = SlideAdder::builder(ui, BPMDetectionParameters::apply_static, config, config_warnings)
+            .with_modulated_values(&modulated_values);
+        let mut sliders_static_parameters =
+            sliders.for_config(BPMDetectionParameters::get_static_bpm_detection_parameters_mut);
+        let mut normal_distribution = sliders.for_config(BPMDetectionParameters::get_normal_distribution_mut);
+
+        sliders_static_parameters.add(&StaticBPMDetectionParameters::BPM_CENTER);
+        sliders_static_parameters.add(&StaticBPMDetectionParameters::BPM_RANGE);
+        sliders_static_parameters.add(&StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION);
+        sliders_static_parameters.add(&StaticBPMDetectionParameters::NOTE_LOW);
+        sliders_static_parameters.add(&StaticBPMDetectionParameters::NOTE_HIGH);
+        sliders_static_parameters.add_on_off(&StaticBPMDetectionParameters::HISTOGRAM_SMOOTHING);
+        normal_distribution.add(&NormalDistributionConfig::STD_DEV);
+        normal_distribution.add(&NormalDistributionConfig::RESOLUTION);
+        normal_distribution.add(&NormalDistributionConfig::IMPRECISION);
+        normal_distribution.add(&NormalDistributionConfig::FACTOR);
+
+        let sliders_live = SlideAdder::builder(ui, BPMDetectionParameters::apply_dynamic, config, config_warnings)
+            .with_modulated_values(&modulated_values);
+        let mut slider_bpm_detection_live =
+            sliders_live.for_config(BPMDetectionParameters::get_dynamic_bpm_detection_parameters_mut);
+        slider_bpm_detection_live.add(&DynamicBPMDetectionParameters::BEATS_LOOKBACK);
+        slider_bpm_detection_live.add(&DynamicBPMDetectionParameters::MAX_SIMULTANEOUS_ONSETS);
+        slider_bpm_detection_live.add(&DynamicBPMDetectionParameters::PARAMETER_RAMP);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::AUTO_VELOCITY_GATE);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::MINIMUM_VELOCITY);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::AUTO_ZOOM);
+
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::NORMAL_DISTRIBUTION);
+
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::TIME_DISTANCE);
+
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::CURRENT_VELOCITY);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::VELOCITY_FROM);
+
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::IN_RANGE);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::MULTIPLIER_FACTOR);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::SUBDIVISION_FACTOR);
+
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::OCTAVE_DISTANCE);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::PITCH_DISTANCE);
+        slider_bpm_detection_live.add_on_off(&DynamicBPMDetectionParameters::HIGH_TEMPO_BIAS);
+
+        let mut send_tempo_enabled = config.get_send_tempo();
+        if ui.toggle_value(&mut send_tempo_enabled, "Send tempo").changed() {
+            config.set_send_tempo(send_tempo_enabled);
+        }
+        ui.end_row();
+
+        if let Some(mut tempo_source) = config.get_tempo_source() {
+            ui.label("Tempo source");
+            let previous = tempo_source;
+            egui::ComboBox::from_id_source("tempo_source").selected_text(tempo_source.label()).show_ui(ui, |ui| {
+                for option in TempoSource::ALL {
+                    ui.selectable_value(&mut tempo_source, option, option.label());
+                }
+            });
+            if tempo_source != previous {
+                config.set_tempo_source(tempo_source);
+            }
+            ui.end_row();
+        }
+
+        if let Some(midi_inputs) = config.get_midi_inputs() {
+            ui.label("MIDI input");
+            ui.vertical(|ui| {
+                if !midi_inputs.inputs.is_empty() {
+                    let mut selected = midi_inputs.selected.clone();
+                    let selected_name = midi_inputs
+                        .inputs
+                        .iter()
+                        .find(|(id, _)| Some(id) == selected.as_ref())
+                        .map_or("none", |(_, name)| name.as_str());
+                    egui::ComboBox::from_id_source("midi_input").selected_text(selected_name).show_ui(ui, |ui| {
+                        for (id, name) in &midi_inputs.inputs {
+                            ui.selectable_value(&mut selected, Some(id.clone()), name);
+                        }
+                    });
+                    if let Some(id) = selected.filter(|id| Some(id) != midi_inputs.selected.as_ref()) {
+                        config.select_midi_input(&id);
+                    }
+                }
+                if let Some(message) = &midi_inputs.message {
+                    ui.colored_label(egui::Color32::YELLOW, message);
+                }
+            });
+            ui.end_row();
+        }
+
+        if !options.display_settings {
+            return;
+        }
+
+        ui.checkbox(&mut config.get_gui_config_mut().show_note_strip, "Note strip");
+        ui.end_row();
+
+        ui.checkbox(&mut config.get_gui_config_mut().show_drift_trend, "Drift trend");
+        ui.end_row();
+
+        ui.checkbox(&mut config.get_gui_config_mut().phase_split_view, "On-beat split").on_hover_text(
+            "Stack the bars by whether the notes fell on the beats counted from the first note, on-beat at the bottom",
+        );
+        ui.end_row();
+
+        let metronome = &mut config.get_gui_config_mut().metronome;
+        ui.checkbox(&mut metronome.enabled, "Beat flash")
+            .on_hover_text("Flash on each beat predicted from the detected tempo, counted from the first note");
+        ui.add_enabled_ui(metronome.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut metronome.color);
+                ui.checkbox(&mut metronome.downbeat_only, "Downbeat only");
+            });
+        });
+        ui.end_row();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.label("Always on top");
+            let always_on_top = &mut config.get_gui_config_mut().window_behavior.always_on_top;
+            egui::ComboBox::from_id_source("always_on_top").selected_text(always_on_top.label()).show_ui(ui, |ui| {
+                for option in AlwaysOnTop::ALL {
+                    ui.selectable_value(always_on_top, option, option.label());
+                }
+            });
+            ui.end_row();
+        }
+    });
+
+    collapsing_section(ui, options.ui_state.as_ref(), "Advanced", "advanced_settings", |ui| {
+        weight_response_setting(ui, config);
+        age_anchor_setting(ui, config);
+        fold_ratios_setting(ui, config);
+        channels_setting(ui, config);
+    });
+}
+
+// closed by default, the UI state keeps it open once opened
+fn collapsing_section(
+    ui: &mut Ui,
+    ui_state: Option<&UiStateHandle>,
+    title: &str,
+    id: &str,
+    add_contents: impl FnOnce(&mut Ui),
+) {
+    let Some(ui_state) = ui_state else {
+        egui::CollapsingHeader::new(title).id_source(id).show(ui, add_contents);
+        return;
+    };
+    let open = ui_state.is_open(id, false);
+    let collapsing_response = egui::CollapsingHeader::new(title).id_source(id).open(Some(open)).show(ui, add_contents);
+    if collapsing_response.header_response.clicked() {
+        ui_state.update(
+            |ui_state| {
+                ui_state.open_sections.insert(id.to_string(), !open);
+            },
+            instant::Instant::now(),
+        );
+    }
+}
+
+fn weight_response_setting<C: BPMDetectionParameters>(ui: &mut Ui, config: &mut C) {
+    let weight_response = config.get_dynamic_bpm_detection_parameters().weight_response;
+    let mut edited = weight_response;
+    ui.horizontal(|ui| {
+        ui.label("Weight response").on_hover_text(
+            "How each criterion counts before its weight applies. Log10 keeps the poor notes in, doubling a weight \
+             less than doubles its influence, more so with a larger scale. Linear makes the influence follow the \
+             weight. Power leaves the poor notes out as the exponent grows, the estimate gets sharper and less stable",
+        );
+        egui::ComboBox::from_id_source("weight_response").selected_text(weight_response.label()).show_ui(ui, |ui| {
+            for option in WeightResponse::ALL {
+                if ui.selectable_label(edited.same_kind(option), option.label()).clicked() && !edited.same_kind(option)
+                {
+                    edited = option;
+                }
+            }
+        });
+        match &mut edited {
+            WeightResponse::Log10 { scale } => {
+                ui.add(egui::DragValue::new(scale).clamp_range(0.1..=1000.0).speed(0.1).prefix("scale "));
+            }
+            WeightResponse::Power { exp } => {
+                ui.add(egui::DragValue::new(exp).clamp_range(0.1..=10.0).speed(0.01).prefix("exp "));
+            }
+            WeightResponse::Linear => {}
+        }
+    });
+    if edited != weight_response {
+        config.get_dynamic_bpm_detection_parameters_mut().weight_response = edited;
+        if let Err(e) = config.apply_dynamic() {
+            error!("could not apply the weight response: {e:?}");
+        }
+    }
+}
+
+fn age_anchor_setting<C: BPMDetectionParameters>(ui: &mut Ui, config: &mut C) {
+    let age_anchor = config.get_dynamic_bpm_detection_parameters().age_anchor;
+    let mut edited = age_anchor;
+    ui.horizontal(|ui| {
+        ui.label("Age measured in").on_hover_text(
+            "What the age weight measures the age of the notes against. Wall time spreads it over the time the notes \
+             kept span, so it decays faster at a slow tempo or with a short lookback. Beats counts it in beats of the \
+             estimate, halving every half-life, the same at any tempo",
+        );
+        egui::ComboBox::from_id_source("age_anchor").selected_text(age_anchor.label()).show_ui(ui, |ui| {
+            for option in AgeAnchor::ALL {
+                if ui.selectable_label(edited.same_kind(option), option.label()).clicked() && !edited.same_kind(option)
+                {
+                    edited = option;
+                }
+            }
+        });
+        if let AgeAnchor::Beats { half_life } = &mut edited {
+            ui.add(
+                egui::DragValue::new(half_life)
+                    .clamp_range(0.25..=64.0)
+                    .speed(0.05)
+                    .prefix("half-life ")
+                    .suffix(" beats"),
+            );
+        }
+    });
+    if edited != age_anchor {
+        config.get_dynamic_bpm_detection_parameters_mut().age_anchor = edited;
+        if let Err(e) = config.apply_dynamic() {
+            error!("could not apply the age anchor: {e:?}");
+        }
+    }
+}
+
+fn fold_ratios_setting<C: BPMDetectionParameters>(ui: &mut Ui, config: &mut C) {
+    let fold_ratios = config.get_dynamic_bpm_detection_parameters().fold_ratios.clone();
+    let mut edited = fold_ratios.clone();
+    ui.horizontal(|ui| {
+        ui.label("Fold ratios").on_hover_text(
+            "How intervals outside the BPM range relate to the beat. Multiples and subdivisions halve or double them \
+             until they fit, dotted notes and triplets let dotted rhythms and triplets reinforce the beat instead of \
+             a tempo of their own. They are tried in the order they were selected, the later ones count less",
+        );
+        for (ratio, label) in DynamicBPMDetectionParameters::FOLD_RATIOS {
+            let position = edited.iter().position(|selected| (selected - ratio).abs() < 1e-3);
+            let mut selected = position.is_some();
+            if ui.checkbox(&mut selected, label).changed() {
+                match position {
+                    Some(position) => {
+                        edited.remove(position);
+                    }
+                    None => edited.push(ratio),
+                }
+            }
+        }
+    });
+    if edited != fold_ratios {
+        config.get_dynamic_bpm_detection_parameters_mut().fold_ratios = edited;
+        if let Err(e) = config.apply_dynamic() {
+            error!("could not apply the fold ratios: {e:?}");
+        }
+    }
+}
+
+fn channels_setting<C: BPMDetectionParameters>(ui: &mut Ui, config: &mut C) {
+    let channels = config.get_static_bpm_detection_parameters().channels;
+    let mut edited = channels;
+    ui.horizontal_wrapped(|ui| {
+        ui.label("MIDI channels").on_hover_text(
+            "Channels the detection listens to, such as the one of a drum controller. Notes on the others are \
+             ignored, from the next note on. None selected listens to all of them",
+        );
+        for channel in 0..MIDI_CHANNELS {
+            let mut selected = edited.is_selected(channel);
+            if ui.toggle_value(&mut selected, (channel + 1).to_string()).changed() {
+                edited.select(channel, selected);
+            }
+        }
+        if edited.admits_all() {
+            ui.weak("all");
+        }
+    });
+    if edited != channels {
+        config.get_static_bpm_detection_parameters_mut().channels = edited;
+        if let Err(e) = config.apply_static() {
+            error!("could not apply the MIDI channels: {e:?}");
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, TimeZone};
+use midi::StaticBPMDetectionParameters;
+use std::{fmt::Display, io, io::Write};
+
+#[cfg(not(target_arch = "wasm32"))]
+use build::get_data_dir;
+#[cfg(not(target_arch = "wasm32"))]
+use errors::Result;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+#[must_use]
+pub fn snapshots_dir() -> PathBuf {
+    get_data_dir().join("snapshots")
+}
+
+/// File name without extension, so the CSV and the PNG of the same snapshot sort together
+pub fn snapshot_file_stem<Tz>(
+    time: &DateTime<Tz>,
+    static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+) -> String
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    format!(
+        "snapshot_{}_center{}_range{}_resolution{}",
+        time.format("%Y%m%d-%H%M%S%.3f"),
+        static_bpm_detection_parameters.bpm_center,
+        static_bpm_detection_parameters.bpm_range,
+        static_bpm_detection_parameters.histogram_resolution
+    )
+}
+
+/// Writes one `bpm,value` line per histogram bin, bins are mapped to BPM the same way as the plot's x axis
+pub fn write_csv(
+    mut writer: impl Write,
+    static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+    histogram_data_points: &[f32],
+) -> io::Result<()> {
+    writeln!(writer, "bpm,value")?;
+    for (index, value) in histogram_data_points.iter().enumerate() {
+        writeln!(writer, "{},{value}", static_bpm_detection_parameters.index_to_bpm(index))?;
+    }
+    writer.flush()
+}
+
+/// Writes the CSV in `directory`, which is created if needed, and returns its path
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_csv(
+    directory: &Path,
+    file_stem: &str,
+    static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+    histogram_data_points: &[f32],
+) -> Result<PathBuf> {
+    create_dir_all(directory)?;
+    let path = directory.join(format!("{file_stem}.csv"));
+    write_csv(BufWriter::new(File::create(&path)?), static_bpm_detection_parameters, histogram_data_points)?;
+    Ok(path)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn download_csv(
+    file_stem: &str,
+    static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+    histogram_data_points: &[f32],
+) -> errors::Result<()> {
+    use errors::Report;
+    use wasm_bindgen::JsCast;
+
+    let js_error = |e: wasm_bindgen::JsValue| Report::msg(format!("{e:?}"));
+
+    let mut csv = Vec::new();
+    write_csv(&mut csv, static_bpm_detection_parameters, histogram_data_points)?;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(csv.as_slice()));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("text/csv");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let document = web_sys::window().and_then(|window| window.document()).ok_or(Report::msg("no document"))?;
+    let anchor = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|element| Report::msg(format!("{element:?} is not an anchor")))?;
+    anchor.set_href(&url);
+    anchor.set_download(&format!("{file_stem}.csv"));
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_file_stem() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 13, 5, 9).unwrap();
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.5,
+            bpm_range: 70,
+            histogram_resolution: 600,
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_file_stem(&time, &static_bpm_detection_parameters),
+            "snapshot_20240301-130509.000_center100.5_range70_resolution600"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        let histogram_data_points = [0.0, 0.5, 1.0];
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &static_bpm_detection_parameters, &histogram_data_points).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("bpm,value"));
+        for (index, line) in lines.enumerate() {
+            let (bpm, value) = line.split_once(',').unwrap();
+            assert!((bpm.parse::<f32>().unwrap() - static_bpm_detection_parameters.index_to_bpm(index)).abs() < 1e-3);
+            assert!((value.parse::<f32>().unwrap() - histogram_data_points[index]).abs() < f32::EPSILON);
+        }
+        assert_eq!(csv.lines().count(), histogram_data_points.len() + 1);
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let directory = std::env::temp_dir().join(format!("bpm_snapshot_test_{}", std::process::id()));
+        let path = export_csv(&directory, "snapshot", &StaticBPMDetectionParameters::default(), &[1.0, 2.0]).unwrap();
+        assert_eq!(path, directory.join("snapshot.csv"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
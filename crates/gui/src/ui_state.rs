@@ -0,0 +1,217 @@
+//! Layout of the GUI as the user left it: which sections are open, the scale picked above the plot and whether the
+//! getting started steps were closed. It is not part of the configuration, each application stores it with its own
+//! state, see `BPMDetectionParameters::load_ui_state`. A change is saved once the layout settled, so that a burst of
+//! clicks is saved once
+
+use crate::config::YScale;
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+use sync::Mutex;
+
+#[cfg(not(target_arch = "wasm32"))]
+use build::get_data_dir;
+#[cfg(not(target_arch = "wasm32"))]
+use errors::{LogErrorWithExt, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+pub const SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+#[cfg(not(target_arch = "wasm32"))]
+const FILE_NAME: &str = "ui_state.toml";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "ui_state";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    // whether each collapsing section is open, by id, the ones never toggled keep their default
+    pub open_sections: BTreeMap<String, bool>,
+    // scale picked above the plot, none keeps the one of the configuration
+    pub y_scale: Option<YScale>,
+    // the getting started steps were closed, they are shown until then, see `onboarding`
+    pub onboarding_dismissed: bool,
+}
+
+impl UiState {
+    #[must_use]
+    pub fn is_open(&self, section: &str, default_open: bool) -> bool {
+        self.open_sections.get(section).copied().unwrap_or(default_open)
+    }
+
+    /// Under the data directory, for the applications storing it in a file
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn path() -> PathBuf {
+        get_data_dir().join(FILE_NAME)
+    }
+
+    /// None when it was never saved, or when it can't be read, which is logged
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn load_file() -> Option<Self> {
+        let serialized = fs::read_to_string(Self::path()).ok()?;
+        toml::from_str(&serialized).log_error_msg("could not read the UI state, starting from the default one").ok()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_file(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        Ok(fs::write(path, toml::to_string_pretty(self)?)?)
+    }
+
+    /// Saved by the browser for the page, none when it was never saved or can't be read
+    #[cfg(target_arch = "wasm32")]
+    #[must_use]
+    pub fn load_local_storage() -> Option<Self> {
+        let serialized = web_sys::window()?.local_storage().ok()??.get_item(STORAGE_KEY).ok()??;
+        toml::from_str(&serialized).ok()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save_local_storage(&self) {
+        let Some(storage) = web_sys::window().and_then(|window| window.local_storage().ok().flatten()) else {
+            log::error!("no local storage to save the UI state to");
+            return;
+        };
+        match toml::to_string(self) {
+            Ok(serialized) => {
+                if let Err(e) = storage.set_item(STORAGE_KEY, &serialized) {
+                    log::error!("could not save the UI state: {e:?}");
+                }
+            }
+            Err(e) => log::error!("could not serialize the UI state: {e:?}"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TrackedUiState {
+    ui_state: UiState,
+    // of the last change not saved yet, the state is saved once it is older than `SAVE_DEBOUNCE`
+    changed_at: Option<Instant>,
+}
+
+/// The state the panels of the GUI read and change, cheap to clone
+#[derive(Clone, Default)]
+pub struct UiStateHandle(Arc<Mutex<TrackedUiState>>);
+
+impl Debug for UiStateHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("UiStateHandle").field(&self.0.lock().ui_state).finish()
+    }
+}
+
+impl UiStateHandle {
+    #[must_use]
+    pub fn new(ui_state: UiState) -> Self {
+        Self(Arc::new(Mutex::new(TrackedUiState { ui_state, ..TrackedUiState::default() })))
+    }
+
+    #[must_use]
+    pub fn get(&self) -> UiState {
+        self.0.lock().ui_state.clone()
+    }
+
+    #[must_use]
+    pub fn is_open(&self, section: &str, default_open: bool) -> bool {
+        self.0.lock().ui_state.is_open(section, default_open)
+    }
+
+    #[must_use]
+    pub fn onboarding_dismissed(&self) -> bool {
+        self.0.lock().ui_state.onboarding_dismissed
+    }
+
+    /// The state is to be saved if `change` changed it
+    pub fn update(&self, change: impl FnOnce(&mut UiState), now: Instant) {
+        let mut tracked = self.0.lock();
+        let previous = tracked.ui_state.clone();
+        change(&mut tracked.ui_state);
+        if tracked.ui_state != previous {
+            tracked.changed_at = Some(now);
+        }
+    }
+
+    /// Time left before the changes are due to be saved, zero once due, none when there is nothing to save
+    #[must_use]
+    pub fn save_delay(&self, now: Instant) -> Option<Duration> {
+        let settled_at = self.0.lock().changed_at? + SAVE_DEBOUNCE;
+        Some(settled_at.saturating_duration_since(now))
+    }
+
+    /// The state to save once it didn't change for `SAVE_DEBOUNCE`, returned only once for the changes made until then
+    #[must_use]
+    pub fn take_due(&self, now: Instant) -> Option<UiState> {
+        let mut tracked = self.0.lock();
+        if tracked.changed_at? + SAVE_DEBOUNCE > now {
+            return None;
+        }
+        tracked.changed_at = None;
+        Some(tracked.ui_state.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let ui_state = UiState {
+            open_sections: [("advanced_settings".to_string(), true), ("other".to_string(), false)].into(),
+            y_scale: Some(YScale::Log),
+            onboarding_dismissed: true,
+        };
+        let serialized = toml::to_string_pretty(&ui_state).unwrap();
+        assert_eq!(toml::from_str::<UiState>(&serialized).unwrap(), ui_state);
+
+        // saved by an older version, or partly
+        assert_eq!(toml::from_str::<UiState>("").unwrap(), UiState::default());
+        let ui_state = toml::from_str::<UiState>("y_scale = \"Log\"").unwrap();
+        assert_eq!(ui_state.y_scale, Some(YScale::Log));
+        assert!(ui_state.is_open("advanced_settings", true));
+        assert!(!ui_state.is_open("advanced_settings", false));
+        assert!(!ui_state.onboarding_dismissed);
+    }
+
+    #[test]
+    fn test_debounced_save() {
+        let start = Instant::now();
+        let ui_state_handle = UiStateHandle::new(UiState::default());
+        assert_eq!(ui_state_handle.save_delay(start), None);
+
+        // unchanged
+        ui_state_handle.update(|ui_state| ui_state.y_scale = None, start);
+        assert_eq!(ui_state_handle.take_due(start + SAVE_DEBOUNCE * 2), None);
+
+        ui_state_handle.update(|ui_state| ui_state.y_scale = Some(YScale::Log), start);
+        assert_eq!(ui_state_handle.save_delay(start + SAVE_DEBOUNCE / 2), Some(SAVE_DEBOUNCE / 2));
+        assert_eq!(ui_state_handle.take_due(start + SAVE_DEBOUNCE / 2), None);
+        // a change before it is due postpones it
+        let later = start + SAVE_DEBOUNCE / 2;
+        ui_state_handle.update(
+            |ui_state| {
+                ui_state.open_sections.insert("advanced_settings".to_string(), true);
+            },
+            later,
+        );
+        assert_eq!(ui_state_handle.take_due(start + SAVE_DEBOUNCE), None);
+        assert_eq!(ui_state_handle.save_delay(start + SAVE_DEBOUNCE), Some(SAVE_DEBOUNCE / 2));
+
+        let saved = ui_state_handle.take_due(later + SAVE_DEBOUNCE).unwrap();
+        assert_eq!(saved.y_scale, Some(YScale::Log));
+        assert!(saved.is_open("advanced_settings", false));
+        // only once
+        assert_eq!(ui_state_handle.take_due(later + SAVE_DEBOUNCE * 2), None);
+        assert_eq!(ui_state_handle.save_delay(later + SAVE_DEBOUNCE * 2), None);
+    }
+}
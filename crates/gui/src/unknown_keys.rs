@@ -0,0 +1,116 @@
+//! Loading of the configurations: every section and value is optional, and keys that are not known, such as typos,
+//! are left out with a warning instead of failing the whole load.
+
+use log::warn;
+use serde::{Deserialize, Deserializer};
+
+/// Deserializes a configuration along with the dotted paths of the keys it left out
+pub fn deserialize_collecting<'de, T, D>(deserializer: D) -> Result<(T, Vec<String>), D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let mut unknown_keys = Vec::new();
+    let config = serde_ignored::deserialize(deserializer, |path| unknown_keys.push(path.to_string()))?;
+    Ok((config, unknown_keys))
+}
+
+/// Deserializes a configuration, warning about the keys it left out. `origin` tells where the configuration comes from
+pub fn deserialize_warning<'de, T, D>(deserializer: D, origin: &str) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let (config, unknown_keys) = deserialize_collecting(deserializer)?;
+    if !unknown_keys.is_empty() {
+        warn!("unknown keys in {origin} were ignored: {}", unknown_keys.join(", "));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GUIConfig;
+    use midi::{
+        BeatCounterConfig, DynamicBPMDetectionParameters, MidiServiceConfig, NormalDistributionConfig,
+        StaticBPMDetectionParameters,
+    };
+    use parameter::OnOff;
+    use std::fmt::Debug;
+
+    fn load<T: for<'de> Deserialize<'de>>(source: &str) -> (T, Vec<String>) {
+        deserialize_collecting(toml::de::Deserializer::new(source)).unwrap()
+    }
+
+    // each source is loaded, the check tells whether the expected value was kept or defaulted
+    fn check<T: for<'de> Deserialize<'de> + Debug>(cases: &[(&str, &[&str], fn(&T) -> bool)]) {
+        for (source, expected_unknown_keys, check) in cases {
+            let (config, unknown_keys) = load::<T>(source);
+            assert_eq!(unknown_keys, *expected_unknown_keys, "{source}");
+            assert!(check(&config), "{source}: {config:?}");
+        }
+    }
+
+    #[test]
+    fn test_bpm_detection_parameters() {
+        check::<StaticBPMDetectionParameters>(&[
+            ("", &[], |config| *config == StaticBPMDetectionParameters::default()),
+            ("bpm_center = 100.0", &[], |config| {
+                (config.bpm_center - 100.0).abs() < f32::EPSILON
+                    && config.bpm_range == StaticBPMDetectionParameters::BPM_RANGE.default
+            }),
+            ("bpm_centre = 100.0\nbpm_range = 60", &["bpm_centre"], |config| {
+                (config.bpm_center - StaticBPMDetectionParameters::BPM_CENTER.default).abs() < f32::EPSILON
+                    && config.bpm_range == 60
+            }),
+            ("[normal_distribution]\nfactorr = 3.0", &["normal_distribution.factorr"], |config| {
+                config.normal_distribution == NormalDistributionConfig::default()
+            }),
+        ]);
+
+        check::<DynamicBPMDetectionParameters>(&[
+            ("beats_lookback = 6", &[], |config| {
+                config.beats_lookback == 6 && config.auto_zoom == DynamicBPMDetectionParameters::default().auto_zoom
+            }),
+            ("[age_weight]\nvalue = 2.0\nenbled = false", &["age_weight.enbled"], |config| {
+                config.age_weight == OnOff::On(2.0)
+            }),
+            ("beats_lookbak = 6\n[age_wieght]\nenabled = true", &["beats_lookbak", "age_wieght"], |config| {
+                *config == DynamicBPMDetectionParameters::default()
+            }),
+        ]);
+    }
+
+    #[test]
+    fn test_gui_config() {
+        check::<GUIConfig>(&[
+            ("", &[], |config| (config.drift_tolerance - GUIConfig::DRIFT_TOLERANCE.default).abs() < f32::EPSILON),
+            ("drift_tolerance = 2.0\n[window_behavior]", &[], |config| {
+                (config.drift_tolerance - 2.0).abs() < f32::EPSILON
+            }),
+            (
+                "y_scal = \"Log\"\n[window_behavior]\nalways_on_tops = \"Never\"",
+                &["y_scal", "window_behavior.always_on_tops"],
+                |config| config.y_scale == crate::YScale::Linear,
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_midi_service_config() {
+        check::<MidiServiceConfig>(&[
+            ("", &[], |config| *config == MidiServiceConfig::default()),
+            ("device_name = \"Drums\"\n[beat_counter]\nreset_after_bars = 4", &[], |config| {
+                config.device_name == "Drums"
+                    && config.beat_counter == BeatCounterConfig { reset_after_bars: 4, ..BeatCounterConfig::default() }
+            }),
+            ("[beat_counter.time_signature]\nnumerator = 3", &[], |config| {
+                config.beat_counter.time_signature.numerator == 3 && config.beat_counter.time_signature.denominator == 4
+            }),
+            ("devicename = \"Drums\"\n[rate_limit]\nburts = 3", &["devicename", "rate_limit.burts"], |config| {
+                *config == MidiServiceConfig::default()
+            }),
+        ]);
+    }
+}
@@ -0,0 +1,112 @@
+use midi::bpm::sample_to_duration;
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+// while the host processes, a change applies once that much audio went by since it started
+const SAMPLE_SETTLE: Duration = Duration::from_millis(50);
+// while it doesn't, a change applies once that much time went by, long enough for the samples to come first otherwise
+pub const WALL_CLOCK_SETTLE: Duration = Duration::from_millis(250);
+
+/// Parameters of a group changed by the host, applied once the change settled. Settling is counted in samples, which
+/// only advance while the host processes. Hosts may keep the editor open without processing, on frozen tracks or with
+/// the engine suspended, the wall clock then takes over from the editor
+pub struct ChangeMarker {
+    origin: Instant,
+    // sample count when the change started
+    sample: AtomicUsize,
+    // microseconds from origin to the change, plus one so zero is no pending change
+    at: AtomicU64,
+}
+
+impl ChangeMarker {
+    /// Pending from the start, so the parameters saved by the host are read once
+    #[must_use]
+    pub fn pending() -> Self {
+        Self { origin: Instant::now(), sample: AtomicUsize::new(0), at: AtomicU64::new(1) }
+    }
+
+    /// A change already pending keeps its start
+    pub fn mark(&self, current_sample: usize) {
+        let at = self.origin.elapsed().as_micros() as u64 + 1;
+        if self.at.compare_exchange(0, at, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.sample.store(current_sample, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether a pending change settled by `current_sample`, it is then taken and must be applied
+    pub fn take_settled_samples(&self, current_sample: usize, sample_rate: u16) -> bool {
+        let at = self.at.load(Ordering::Relaxed);
+        if at == 0 || sample_rate == 0 {
+            return false;
+        }
+        let since_change = current_sample.saturating_sub(self.sample.load(Ordering::Relaxed));
+        sample_to_duration(sample_rate, since_change).to_std().unwrap_or_default() > SAMPLE_SETTLE && self.take(at)
+    }
+
+    /// Whether a pending change settled by `now`, it is then taken and must be applied
+    pub fn take_settled_wall_clock(&self, now: Instant) -> bool {
+        let at = self.at.load(Ordering::Relaxed);
+        if at == 0 {
+            return false;
+        }
+        let changed_at = self.origin + Duration::from_micros(at - 1);
+        now.saturating_duration_since(changed_at) >= WALL_CLOCK_SETTLE && self.take(at)
+    }
+
+    // the audio thread and the editor may both find the change settled, only one applies it
+    fn take(&self, at: u64) -> bool {
+        self.at.compare_exchange(at, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    #[cfg(test)]
+    pub fn is_pending(&self) -> bool {
+        self.at.load(Ordering::Relaxed) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u16 = 48000;
+
+    fn settled() -> ChangeMarker {
+        let change_marker = ChangeMarker::pending();
+        assert!(change_marker.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE));
+        change_marker
+    }
+
+    #[test]
+    fn test_processing() {
+        let change_marker = settled();
+        change_marker.mark(48000);
+        // another change while pending keeps the start
+        change_marker.mark(49000);
+        assert!(!change_marker.take_settled_samples(48000 + 2000, SAMPLE_RATE));
+        assert!(change_marker.take_settled_samples(48000 + 2500, SAMPLE_RATE));
+        assert!(!change_marker.is_pending());
+        assert!(!change_marker.take_settled_samples(48000 + 5000, SAMPLE_RATE));
+        // not initialized yet
+        change_marker.mark(0);
+        assert!(!change_marker.take_settled_samples(48000, 0));
+    }
+
+    #[test]
+    fn test_without_process_calls() {
+        let change_marker = settled();
+        let start = Instant::now();
+        change_marker.mark(48000);
+        // the samples don't advance
+        assert!(!change_marker.take_settled_samples(48000, SAMPLE_RATE));
+        assert!(!change_marker.take_settled_wall_clock(start));
+        assert!(change_marker.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE));
+        assert!(!change_marker.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE * 2));
+
+        // the processing came first
+        change_marker.mark(48000);
+        assert!(change_marker.take_settled_samples(96000, SAMPLE_RATE));
+        assert!(!change_marker.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE));
+    }
+}
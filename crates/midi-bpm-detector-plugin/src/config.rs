@@ -1,37 +1,118 @@
 use crate::{MidiBpmDetector, MidiBpmDetectorParams, Task};
 use errors::error_backtrace;
-use gui::{BPMDetectionParameters, GUIConfig};
-use midi::{DynamicBPMDetectionParameters, NormalDistributionConfig, StaticBPMDetectionParameters};
+use gui::{
+    effective_config::{Provenance, SharedProvenance},
+    unknown_keys, BPMDetectionParameters, GUIConfig, UiState,
+};
+use midi::{
+    BeatCounterConfig, DynamicBPMDetectionParameters, FeelAmbiguityConfig, LatencySummary, RateLimitedNotes,
+    RateLimiterConfig, RemoteControlServerConfig, SharedTempoSource, StaticBPMDetectionParameters, TempoLatency,
+    TempoSource,
+};
 
 use crate::{
-    params::{apply_duration_param, apply_float_param, apply_int_param, apply_onoff_param},
+    evaluation_scheduler::EvaluationSchedulingConfig,
+    param_writes::{ParamWriter, PendingParamWrites},
+    params::{apply_duration_param, apply_float_param, ParamReader},
+    remote_controls::RemoteControlsConfig,
     task_executor::UpdateOrigin,
 };
 use errors::info;
-use nih_plug::prelude::{AsyncExecutor, ParamSetter};
+use nih_plug::prelude::AsyncExecutor;
 use nih_plug_egui::egui::mutex::RwLock;
 use serde::{Deserialize, Serialize};
 use std::{
-    sync::{atomic::Ordering, Arc},
+    sync::{atomic::Ordering, Arc, PoisonError},
     time::{Duration, Instant},
 };
 use sync::ArcAtomicBool;
 
-const CONFIG: &str = include_str!("../config/base_config.toml");
+// config/base_config.toml at the root of the workspace merged with config/overlay.toml, see `build::create_base_config`
+const CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/base_config.toml"));
 
+// every field defaults on its own, the default of the whole configuration is the built-in one, which is deserialized
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
-    #[serde(rename = "GUI")]
+    #[serde(rename = "GUI", default)]
     pub gui_config: GUIConfig,
+    #[serde(default)]
     pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    #[serde(default)]
     pub static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    #[serde(default)]
     pub send_tempo: ArcAtomicBool,
+    #[serde(default)]
+    pub tempo_source: SharedTempoSource,
+    // estimates of a lower confidence are not sent to the DAW, see `BpmEstimate`
+    #[serde(default)]
+    pub min_tempo_confidence: f32,
+    #[serde(default)]
+    pub feel_ambiguity: FeelAmbiguityConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimiterConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub evaluation_scheduling: EvaluationSchedulingConfig,
+    #[serde(default)]
+    pub remote_controls: RemoteControlsConfig,
+    // values of the parameters the detection runs with when the host modulates them
+    #[serde(default)]
+    pub host_modulation: HostModulation,
+    // lets the TUI connect with `--connect`, mirror the estimates and change the parameters
+    #[serde(default)]
+    pub remote_control_server: RemoteControlServerConfig,
+    // the time signature of the transport is used when the host provides one
+    #[serde(default)]
+    pub beat_counter: BeatCounterConfig,
+    // diagnostic, shared by all clones of the configuration
+    #[serde(skip)]
+    pub tempo_latency: TempoLatency,
+    // mirrors the bypass parameter, which is persisted by the host
+    #[serde(skip)]
+    pub bypass_detection: ArcAtomicBool,
+    // origin of the values, shared by all clones of the configuration
+    #[serde(skip)]
+    pub provenance: SharedProvenance,
+    // previous starts kept failing, the parameters of the host are left alone, see `init_markers`
+    #[serde(skip)]
+    pub safe_mode: ArcAtomicBool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    // warn when queued events were not processed for that long
+    pub stall_threshold: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self { stall_threshold: Duration::from_secs(2) }
+    }
+}
+
+/// CLAP hosts can modulate a parameter on top of the value it was set to. The GUI shows the value it was set to, which
+/// is the one it writes back, and marks the modulated one over the slider
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostModulation {
+    // the detection runs with the modulated values
+    #[default]
+    Follow,
+    // the detection runs with the values the parameters were set to, the modulation only shows in the GUI
+    Ignore,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        match Config::deserialize(toml::de::Deserializer::new(CONFIG)) {
-            Ok(config) => config,
+        match unknown_keys::deserialize_warning::<Self, _>(
+            toml::de::Deserializer::new(CONFIG),
+            "the built-in configuration",
+        ) {
+            Ok(mut config) => {
+                config.provenance = SharedProvenance::new(Provenance::new(&config));
+                config
+            }
             Err(err) => {
                 error_backtrace!("{err}");
                 panic!("invalid built-in configuration");
@@ -50,7 +131,11 @@ pub struct LiveConfig {
     delayed_update_static_bpm_detection_parameters: Option<Instant>,
     dynamic_bpm_detection_parameters_changed: bool,
     static_bpm_detection_parameters_changed: bool,
+    gui_parameters_changed: bool,
+    pending_param_writes: PendingParamWrites,
     pub send_tempo_changed: ArcAtomicBool,
+    // counted by the rate limiter of the audio thread, the configuration is replaced on each `load_config`
+    rate_limited_notes: RateLimitedNotes,
 }
 
 impl LiveConfig {
@@ -60,6 +145,7 @@ impl LiveConfig {
         async_executor: AsyncExecutor<MidiBpmDetector>,
         force_evaluate_bpm_detection: ArcAtomicBool,
         params: Arc<MidiBpmDetectorParams>,
+        rate_limited_notes: RateLimitedNotes,
     ) -> Self {
         Self {
             config,
@@ -70,8 +156,11 @@ impl LiveConfig {
             delayed_update_static_bpm_detection_parameters: None,
             dynamic_bpm_detection_parameters_changed: false,
             static_bpm_detection_parameters_changed: false,
+            gui_parameters_changed: false,
+            pending_param_writes: PendingParamWrites::default(),
             params,
             send_tempo_changed: ArcAtomicBool::default(),
+            rate_limited_notes,
         }
     }
 
@@ -103,134 +192,86 @@ impl LiveConfig {
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub fn apply_changes_to_daw_parameters(&mut self, param_setter: &ParamSetter) {
-        if self.dynamic_bpm_detection_parameters_changed {
+    /// Loads the configuration the executor applied. The parameters it read modulated are shown at the value they were
+    /// set to, so that they are not written back to the host at the modulated value
+    pub fn load_config(&mut self, config: Config) {
+        self.config = config;
+        if self.config.host_modulation == HostModulation::Ignore {
+            return;
+        }
+        let params = &self.params;
+        show_base_values(
+            &mut self.config.static_bpm_detection_parameters,
+            |reader, static_bpm_detection_parameters| {
+                params.read_static(reader, static_bpm_detection_parameters);
+            },
+        );
+        show_base_values(
+            &mut self.config.dynamic_bpm_detection_parameters,
+            |reader, dynamic_bpm_detection_parameters| {
+                params.read_dynamic(reader, dynamic_bpm_detection_parameters);
+            },
+        );
+        let gui_config = &mut self.config.gui_config;
+        let mut shown = (gui_config.interpolation_duration, gui_config.interpolation_curve);
+        show_base_values(&mut shown, |reader, shown| *shown = params.read_gui(reader));
+        (gui_config.interpolation_duration, gui_config.interpolation_curve) = shown;
+    }
+
+    /// The parameters of the configuration were changed elsewhere than in the GUI, such as from the TUI. They are
+    /// written to the host on the next `apply_changes_to_daw_parameters`
+    pub fn parameters_changed_elsewhere(&mut self) {
+        self.dynamic_bpm_detection_parameters_changed = true;
+        self.static_bpm_detection_parameters_changed = true;
+    }
+
+    /// The configuration follows the GUI right away, the host gets the changes once `dragging` is over
+    pub fn apply_changes_to_daw_parameters(&mut self, writer: &impl ParamWriter, dragging: bool) {
+        if self.config.safe_mode.load(Ordering::Relaxed) {
+            // the state saved by the host is kept for when the plugin starts normally again
+            self.dynamic_bpm_detection_parameters_changed = false;
+            self.static_bpm_detection_parameters_changed = false;
+            self.gui_parameters_changed = false;
+            return;
+        }
+        let now = Instant::now();
+        let pending = &mut self.pending_param_writes;
+        if self.gui_parameters_changed {
             apply_float_param(
                 &GUIConfig::INTERPOLATION_CURVE,
                 &self.params.gui_params.interpolation_curve,
                 &mut self.config.gui_config,
-                param_setter,
+                pending,
+                now,
             );
             apply_duration_param(
                 &GUIConfig::INTERPOLATION_DURATION,
                 &self.params.gui_params.interpolation_duration,
                 &mut self.config.gui_config,
-                param_setter,
-            );
-            apply_int_param(
-                &DynamicBPMDetectionParameters::BEATS_LOOKBACK,
-                &self.params.dynamic_params.beats_lookback,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::CURRENT_VELOCITY,
-                &self.params.dynamic_params.velocity_current_note_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::VELOCITY_FROM,
-                &self.params.dynamic_params.velocity_note_from_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::TIME_DISTANCE,
-                &self.params.dynamic_params.age_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::OCTAVE_DISTANCE,
-                &self.params.dynamic_params.octave_distance_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::PITCH_DISTANCE,
-                &self.params.dynamic_params.pitch_distance_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::MULTIPLIER_FACTOR,
-                &self.params.dynamic_params.multiplier_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::SUBDIVISION_FACTOR,
-                &self.params.dynamic_params.subdivision_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::IN_RANGE,
-                &self.params.dynamic_params.in_beat_range_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::NORMAL_DISTRIBUTION,
-                &self.params.dynamic_params.normal_distribution_weight,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_onoff_param(
-                &DynamicBPMDetectionParameters::HIGH_TEMPO_BIAS,
-                &self.params.dynamic_params.high_tempo_bias,
-                &mut self.config.dynamic_bpm_detection_parameters,
-                param_setter,
+                pending,
+                now,
             );
+            self.gui_parameters_changed = false;
+        }
+        if self.dynamic_bpm_detection_parameters_changed {
+            self.params.write_dynamic(&mut self.config.dynamic_bpm_detection_parameters, pending, now);
             self.dynamic_bpm_detection_parameters_changed = false;
         }
         if self.static_bpm_detection_parameters_changed {
-            apply_float_param(
-                &StaticBPMDetectionParameters::BPM_CENTER,
-                &self.params.static_params.bpm_center,
-                &mut self.config.static_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_int_param(
-                &StaticBPMDetectionParameters::BPM_RANGE,
-                &self.params.static_params.bpm_range,
-                &mut self.config.static_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_float_param(
-                &StaticBPMDetectionParameters::SAMPLE_RATE,
-                &self.params.static_params.sample_rate,
-                &mut self.config.static_bpm_detection_parameters,
-                param_setter,
-            );
-            apply_float_param(
-                &NormalDistributionConfig::STD_DEV,
-                &self.params.static_params.normal_distribution.std_dev,
-                &mut self.config.static_bpm_detection_parameters.normal_distribution,
-                param_setter,
-            );
-            apply_float_param(
-                &NormalDistributionConfig::FACTOR,
-                &self.params.static_params.normal_distribution.factor,
-                &mut self.config.static_bpm_detection_parameters.normal_distribution,
-                param_setter,
-            );
-            apply_float_param(
-                &NormalDistributionConfig::IMPRECISION,
-                &self.params.static_params.normal_distribution.imprecision,
-                &mut self.config.static_bpm_detection_parameters.normal_distribution,
-                param_setter,
-            );
-            apply_float_param(
-                &NormalDistributionConfig::RESOLUTION,
-                &self.params.static_params.normal_distribution.resolution,
-                &mut self.config.static_bpm_detection_parameters.normal_distribution,
-                param_setter,
-            );
+            self.params.write_static(&mut self.config.static_bpm_detection_parameters, pending, now);
             self.static_bpm_detection_parameters_changed = false;
         }
+        pending.update(&self.params, writer, dragging, now);
+    }
+}
+
+// the values the parameters were set to replace the modulated ones, unless `shown` holds other values, such as the
+// ones held back by `ToggleHysteresis` or received from the TUI
+fn show_base_values<T: Clone + PartialEq>(shown: &mut T, read: impl Fn(&dyn ParamReader, &mut T)) {
+    let mut modulated = shown.clone();
+    read(&HostModulation::Follow, &mut modulated);
+    if modulated == *shown {
+        read(&HostModulation::Ignore, shown);
     }
 }
 
@@ -270,6 +311,54 @@ impl BPMDetectionParameters for LiveConfig {
         self.config.send_tempo.store(enabled, Ordering::SeqCst);
     }
 
+    fn get_tempo_source(&self) -> Option<TempoSource> {
+        Some(self.config.tempo_source.load(Ordering::Relaxed))
+    }
+
+    fn set_tempo_source(&mut self, tempo_source: TempoSource) {
+        self.config.tempo_source.store(tempo_source, Ordering::Relaxed);
+    }
+
+    fn get_rate_limited_notes(&self) -> u64 {
+        self.rate_limited_notes.get()
+    }
+
+    fn get_tempo_latency(&self) -> Option<LatencySummary> {
+        self.config.tempo_latency.summary()
+    }
+
+    fn midi_input_step(&self) -> &'static str {
+        "Route the MIDI of a track or an instrument to the plugin in the host"
+    }
+
+    fn is_detection_bypassed(&self) -> bool {
+        self.config.bypass_detection.load(Ordering::Relaxed)
+    }
+
+    fn get_safe_mode_notice(&self) -> Option<String> {
+        self.config.safe_mode.load(Ordering::Relaxed).then(|| {
+            "Safe mode: previous starts failed, the parameters saved by the host are not applied nor changed"
+                .to_string()
+        })
+    }
+
+    fn effective_config(&self) -> Option<String> {
+        Some(self.config.provenance.render(&self.config))
+    }
+
+    fn get_modulated_values(&self) -> Vec<(String, f64)> {
+        self.params.modulated_values()
+    }
+
+    fn load_ui_state(&self) -> Option<UiState> {
+        Some(self.params.ui_state.read().unwrap_or_else(PoisonError::into_inner).clone())
+    }
+
+    // the host saves it along with the project
+    fn save_ui_state(&mut self, ui_state: &UiState) {
+        *self.params.ui_state.write().unwrap_or_else(PoisonError::into_inner) = ui_state.clone();
+    }
+
     fn apply_static(&mut self) -> Result<(), Self::Error> {
         self.static_bpm_detection_parameters_changed = true;
         if self.delayed_update_static_bpm_detection_parameters.is_none() {
@@ -285,4 +374,50 @@ impl BPMDetectionParameters for LiveConfig {
         }
         Ok(())
     }
+
+    // the executor doesn't use the GUI config, it is only kept in the shared configuration for the next editor
+    fn apply_gui(&mut self) -> Result<(), Self::Error> {
+        self.gui_parameters_changed = true;
+        self.shared_config.write().gui_config = self.config.gui_config.clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config() {
+        let cases: [(&str, &[&str], u8); 4] = [
+            ("", &[], 8),
+            ("[dynamic_bpm_detection_parameters]\nbeats_lookback = 4", &[], 4),
+            ("send_temp = true\n[watchdog]\nstall_treshold = 1", &["send_temp", "watchdog.stall_treshold"], 8),
+            ("[rate_limit]\nunknown = 1\n[[remote_controls.pages]]\nname = \"Knobs\"", &["rate_limit.unknown"], 8),
+        ];
+        for (source, expected_unknown_keys, beats_lookback) in cases {
+            let (config, unknown_keys): (Config, _) =
+                unknown_keys::deserialize_collecting(toml::de::Deserializer::new(source)).unwrap();
+            assert_eq!(unknown_keys, expected_unknown_keys, "{source}");
+            assert_eq!(config.dynamic_bpm_detection_parameters.beats_lookback, beats_lookback, "{source}");
+            assert!(!config.send_tempo.load(Ordering::Relaxed), "{source}");
+        }
+
+        // the built-in configuration only has known keys
+        let (_, unknown_keys): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(CONFIG)).unwrap();
+        assert!(unknown_keys.is_empty(), "{unknown_keys:?}");
+    }
+
+    #[test]
+    fn test_shared_base_config() {
+        // what every frontend starts from, so the detection parameters are the same in all of them
+        let shared: Config = toml::from_str(include_str!("../../../config/base_config.toml")).unwrap();
+        let (config, unknown_keys): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(CONFIG)).unwrap();
+        assert!(unknown_keys.is_empty(), "{unknown_keys:?}");
+        assert_eq!(config.static_bpm_detection_parameters, shared.static_bpm_detection_parameters);
+        assert_eq!(config.dynamic_bpm_detection_parameters, shared.dynamic_bpm_detection_parameters);
+        assert_eq!(config.gui_config.interpolation_curve.to_bits(), shared.gui_config.interpolation_curve.to_bits());
+    }
 }
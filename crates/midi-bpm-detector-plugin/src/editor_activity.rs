@@ -0,0 +1,153 @@
+use crate::param_writes::HostParamWriter;
+use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// the editor stays active that long after its window is destroyed
+pub const EDITOR_GRACE: Duration = Duration::from_secs(1);
+
+const CLOSED: u64 = 0;
+const OPEN: u64 = u64::MAX;
+
+/// Whether the editor is there, as seen from the audio thread, the task executor and the editor itself. It decides
+/// whether `process()` keeps the plugin alive and whether the executor keeps the remote the GUI renders from.
+/// `EguiState::is_open` lags the window in some hosts, in both directions, this follows the lifecycle of the window
+/// instead:
+/// - closed or closing → open: the editor is built for a new window
+/// - open → closing: the window is destroyed
+/// - closing → closed: once the grace period went by without a new window
+///
+/// So a host that closes and reopens the window at once, or reports it closed for a moment, doesn't lose the remote of
+/// the window being rendered, and one that reports it open after it was closed doesn't keep the plugin alive for it
+pub struct EditorActivity {
+    origin: Instant,
+    grace: Duration,
+    // `OPEN`, `CLOSED`, or microseconds from origin to the destruction of the window plus one while closing
+    state: AtomicU64,
+}
+
+impl EditorActivity {
+    #[must_use]
+    pub fn new(grace: Duration) -> Self {
+        Self { origin: Instant::now(), grace, state: AtomicU64::new(CLOSED) }
+    }
+
+    /// Called when the editor is built for a new window
+    pub fn built(&self) {
+        self.state.store(OPEN, Ordering::Relaxed);
+    }
+
+    /// Called when the window is destroyed
+    pub fn destroyed(&self, now: Instant) {
+        let at = now.saturating_duration_since(self.origin).as_micros() as u64 + 1;
+        self.state.store(at.min(OPEN - 1), Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_active(&self, now: Instant) -> bool {
+        match self.state.load(Ordering::Relaxed) {
+            CLOSED => false,
+            OPEN => true,
+            at => {
+                let destroyed_at = self.origin + Duration::from_micros(at - 1);
+                now.saturating_duration_since(destroyed_at) < self.grace
+            }
+        }
+    }
+}
+
+/// The egui editor, telling `EditorActivity` when its window is destroyed, which it has no callback for, and handing
+/// the context of its window to `HostParamWriter`
+pub struct TrackedEditor {
+    inner: Box<dyn Editor>,
+    editor_activity: Arc<EditorActivity>,
+    host_param_writer: HostParamWriter,
+}
+
+impl TrackedEditor {
+    #[must_use]
+    pub fn new(
+        inner: Box<dyn Editor>,
+        editor_activity: Arc<EditorActivity>,
+        host_param_writer: HostParamWriter,
+    ) -> Self {
+        Self { inner, editor_activity, host_param_writer }
+    }
+}
+
+struct WindowHandle {
+    _inner: Box<dyn Any + Send>,
+    editor_activity: Arc<EditorActivity>,
+}
+
+impl Drop for WindowHandle {
+    fn drop(&mut self) {
+        self.editor_activity.destroyed(Instant::now());
+    }
+}
+
+impl Editor for TrackedEditor {
+    fn spawn(&self, parent: ParentWindowHandle, context: Arc<dyn GuiContext>) -> Box<dyn Any + Send> {
+        self.host_param_writer.set(Some(Arc::new(context.clone())));
+        Box::new(WindowHandle {
+            _inner: self.inner.spawn(parent, context),
+            editor_activity: self.editor_activity.clone(),
+        })
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.inner.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        self.inner.set_scale_factor(factor)
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.inner.param_value_changed(id, normalized_value);
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.inner.param_modulation_changed(id, modulation_offset);
+    }
+
+    fn param_values_changed(&self) {
+        self.inner.param_values_changed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let editor_activity = EditorActivity::new(EDITOR_GRACE);
+        let now = Instant::now();
+        assert!(!editor_activity.is_active(now));
+
+        editor_activity.built();
+        assert!(editor_activity.is_active(now + Duration::from_secs(1000)));
+
+        // closed for a moment, then open again
+        editor_activity.destroyed(now);
+        assert!(editor_activity.is_active(now + EDITOR_GRACE / 2));
+        editor_activity.built();
+        assert!(editor_activity.is_active(now + EDITOR_GRACE * 2));
+
+        // closed for good
+        let closed_at = now + Duration::from_secs(10);
+        editor_activity.destroyed(closed_at);
+        assert!(editor_activity.is_active(closed_at));
+        assert!(editor_activity.is_active(closed_at + EDITOR_GRACE - Duration::from_millis(1)));
+        assert!(!editor_activity.is_active(closed_at + EDITOR_GRACE));
+        // clock reads racing with the destruction
+        assert!(editor_activity.is_active(now));
+    }
+}
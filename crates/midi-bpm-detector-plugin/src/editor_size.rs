@@ -0,0 +1,49 @@
+//! Size of the editor window, saved with the project through `MidiBpmDetectorParams::editor_state`. The window opens
+//! at the last size it had, a saved size too small to use is brought up to `MIN_EDITOR_SIZE`
+
+use nih_plug_egui::{egui::Context, EguiState};
+use std::sync::Arc;
+
+pub const DEFAULT_EDITOR_SIZE: (u32, u32) = (1200, 600);
+pub const MIN_EDITOR_SIZE: (u32, u32) = (400, 300);
+
+#[must_use]
+pub fn default_editor_state() -> Arc<EguiState> {
+    EguiState::from_size(DEFAULT_EDITOR_SIZE.0, DEFAULT_EDITOR_SIZE.1)
+}
+
+#[must_use]
+pub fn clamped((width, height): (u32, u32)) -> (u32, u32) {
+    (width.max(MIN_EDITOR_SIZE.0), height.max(MIN_EDITOR_SIZE.1))
+}
+
+/// Before the editor opens, in case the restored state is corrupted
+pub fn restore(editor_state: &EguiState) {
+    let size = editor_state.size();
+    if clamped(size) != size {
+        editor_state.set_requested_size(clamped(size));
+    }
+}
+
+/// Follows the window as it is resized, so that the state saved with the project holds its last size
+pub fn track(editor_state: &EguiState, egui_ctx: &Context) {
+    let window_size = egui_ctx.input(|input| input.screen_rect.size());
+    let size = (window_size.x.round() as u32, window_size.y.round() as u32);
+    if size != editor_state.size() && clamped(size) == size {
+        editor_state.set_requested_size(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamped() {
+        assert_eq!(clamped(DEFAULT_EDITOR_SIZE), DEFAULT_EDITOR_SIZE);
+        assert_eq!(clamped(MIN_EDITOR_SIZE), MIN_EDITOR_SIZE);
+        assert_eq!(clamped((0, 0)), MIN_EDITOR_SIZE);
+        assert_eq!(clamped((1600, 10)), (1600, MIN_EDITOR_SIZE.1));
+        assert_eq!(clamped((10, 900)), (MIN_EDITOR_SIZE.0, 900));
+    }
+}
@@ -0,0 +1,381 @@
+use midi::bpm::sample_to_duration;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// instances evaluate at most that far apart from the configured debounce
+const MAX_JITTER: Duration = Duration::from_millis(20);
+const NEVER: u64 = u64::MAX;
+
+static NEXT_INSTANCE_ID: AtomicU64 = AtomicU64::new(0);
+static REGISTRY: Lazy<Arc<EvaluationRegistry>> = Lazy::new(Arc::default);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvaluationSchedulingConfig {
+    // minimum time between two evaluations of a coordinated instance, offset by up to `jitter` in either direction
+    // depending on the instance, so instances receiving the same notes drift apart
+    pub debounce: Duration,
+    // capped to 20ms
+    pub jitter: Duration,
+    // stagger the evaluations of the instances loaded in the same process that enable it. Without it, or while a
+    // single instance enables it, every evaluation runs as soon as it is requested
+    pub coordinate_instances: bool,
+    // two coordinated instances don't evaluate within that interval, unless waiting would delay an evaluation by more
+    // than `debounce`
+    pub guard_interval: Duration,
+    // weights switched on and off by the host force an evaluation at most that often, see `ToggleHysteresis`
+    pub toggle_interval: Duration,
+}
+
+impl Default for EvaluationSchedulingConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(40),
+            jitter: Duration::from_millis(20),
+            coordinate_instances: false,
+            guard_interval: Duration::from_millis(5),
+            toggle_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+pub fn next_instance_id() -> u64 {
+    NEXT_INSTANCE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// `debounce` offset by a value within ±`jitter` (at most 20ms) that only depends on the instance id
+#[must_use]
+pub fn jittered_debounce(debounce: Duration, jitter: Duration, instance_id: u64) -> Duration {
+    let jitter = jitter.min(MAX_JITTER);
+    let mut hasher = DefaultHasher::new();
+    instance_id.hash(&mut hasher);
+    let offset = Duration::from_micros(hasher.finish() % (jitter.as_micros() as u64 * 2 + 1));
+    (debounce + offset).saturating_sub(jitter)
+}
+
+/// Last evaluation of the coordinated instances of the process
+pub struct EvaluationRegistry {
+    origin: Instant,
+    // microseconds since origin
+    last_evaluation: AtomicU64,
+    // coordinated instances alive
+    instances: AtomicUsize,
+}
+
+impl Default for EvaluationRegistry {
+    fn default() -> Self {
+        Self { origin: Instant::now(), last_evaluation: AtomicU64::new(NEVER), instances: AtomicUsize::new(0) }
+    }
+}
+
+impl EvaluationRegistry {
+    fn micros(&self, instant: Instant) -> u64 {
+        instant.saturating_duration_since(self.origin).as_micros() as u64
+    }
+
+    /// Takes the evaluation slot at `now` if no other instance evaluated within `guard_interval`
+    fn claim(&self, now: Instant, guard_interval: Duration) -> bool {
+        let now = self.micros(now);
+        let guard_interval = guard_interval.as_micros() as u64;
+        let mut last_evaluation = self.last_evaluation.load(Ordering::Relaxed);
+        loop {
+            if last_evaluation != NEVER && last_evaluation.abs_diff(now) < guard_interval {
+                return false;
+            }
+            match self.last_evaluation.compare_exchange_weak(last_evaluation, now, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(current) => last_evaluation = current,
+            }
+        }
+    }
+
+    fn record(&self, now: Instant) {
+        let now = self.micros(now);
+        // NEVER is the largest value, it can't be kept by fetch_max
+        if self.last_evaluation.compare_exchange(NEVER, now, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            self.last_evaluation.fetch_max(now, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Decides, from the background task executor, whether a requested evaluation runs now or is postponed to a later
+/// task, see `DeferredEvaluation`
+pub struct EvaluationScheduler {
+    debounce: Duration,
+    coordinate_instances: bool,
+    guard_interval: Duration,
+    registry: Arc<EvaluationRegistry>,
+    last_evaluation: Option<Instant>,
+    // since when an evaluation is only held back by the other instances
+    due_since: Option<Instant>,
+}
+
+impl EvaluationScheduler {
+    #[must_use]
+    pub fn new(config: &EvaluationSchedulingConfig, instance_id: u64) -> Self {
+        Self::with_registry(config, instance_id, REGISTRY.clone())
+    }
+
+    #[must_use]
+    pub fn with_registry(
+        config: &EvaluationSchedulingConfig,
+        instance_id: u64,
+        registry: Arc<EvaluationRegistry>,
+    ) -> Self {
+        if config.coordinate_instances {
+            registry.instances.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            debounce: jittered_debounce(config.debounce, config.jitter, instance_id),
+            coordinate_instances: config.coordinate_instances,
+            guard_interval: config.guard_interval,
+            registry,
+            last_evaluation: None,
+            due_since: None,
+        }
+    }
+
+    // with another coordinated instance, alone there is nothing to stagger
+    fn is_coordinated(&self) -> bool {
+        self.coordinate_instances && self.registry.instances.load(Ordering::Relaxed) > 1
+    }
+
+    /// None when the evaluation requested at `now` runs now, otherwise how long to wait before requesting it again.
+    /// Forced evaluations, following a configuration change, always run
+    pub fn evaluation_delay(&mut self, now: Instant, forced: bool) -> Option<Duration> {
+        if !forced && self.is_coordinated() {
+            if let Some(last_evaluation) = self.last_evaluation {
+                let next_evaluation = last_evaluation + self.debounce;
+                if now < next_evaluation {
+                    return Some(next_evaluation - now);
+                }
+            }
+            // held back by the other instances until it is overdue
+            let overdue_at = *self.due_since.get_or_insert(now) + self.debounce;
+            if now < overdue_at && !self.registry.claim(now, self.guard_interval) {
+                return Some(self.guard_interval.min(overdue_at - now));
+            }
+        }
+        if self.coordinate_instances {
+            self.registry.record(now);
+        }
+        self.last_evaluation = Some(now);
+        self.due_since = None;
+        None
+    }
+}
+
+impl Drop for EvaluationScheduler {
+    fn drop(&mut self) {
+        if self.coordinate_instances {
+            self.registry.instances.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// An evaluation the executor postponed. The audio thread requests it with a single task once the delay went by,
+/// instead of a task on every block until then
+#[derive(Default)]
+pub struct DeferredEvaluation {
+    // sample count when it was postponed
+    sample: AtomicUsize,
+    // microseconds to wait from there, plus one so zero is nothing postponed
+    delay: AtomicU64,
+}
+
+impl DeferredEvaluation {
+    /// Replaces the evaluation postponed before
+    pub fn defer(&self, current_sample: usize, delay: Duration) {
+        self.sample.store(current_sample, Ordering::Relaxed);
+        self.delay.store(delay.as_micros() as u64 + 1, Ordering::Relaxed);
+    }
+
+    /// A task ran meanwhile, the executor postpones again what it still has to evaluate
+    pub fn cancel(&self) {
+        self.delay.store(0, Ordering::Relaxed);
+    }
+
+    /// Whether the postponed evaluation is due by `current_sample`, it is then taken and a task must be requested
+    pub fn take_due(&self, current_sample: usize, sample_rate: u16) -> bool {
+        let delay = self.delay.load(Ordering::Relaxed);
+        if delay == 0 || sample_rate == 0 {
+            return false;
+        }
+        let since = current_sample.saturating_sub(self.sample.load(Ordering::Relaxed));
+        let elapsed = sample_to_duration(sample_rate, since).to_std().unwrap_or_default();
+        elapsed >= Duration::from_micros(delay - 1)
+            && self.delay.compare_exchange(delay, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    #[cfg(test)]
+    pub fn is_deferred(&self) -> bool {
+        self.delay.load(Ordering::Relaxed) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_millis(50);
+    const JITTER: Duration = Duration::from_millis(20);
+    const GUARD_INTERVAL: Duration = Duration::from_millis(5);
+    const STEP: Duration = Duration::from_millis(1);
+
+    fn config() -> EvaluationSchedulingConfig {
+        EvaluationSchedulingConfig {
+            debounce: DEBOUNCE,
+            jitter: JITTER,
+            coordinate_instances: true,
+            guard_interval: GUARD_INTERVAL,
+            toggle_interval: Duration::from_millis(100),
+        }
+    }
+
+    /// Every instance is asked to evaluate at the same moments, every `period`, as happens on clock boundaries. Returns
+    /// for each evaluation which instance ran it, when, and how long after its request
+    fn simulate(instances: u64, period: Duration, duration: Duration) -> Vec<(u64, Duration, Duration)> {
+        let registry = Arc::new(EvaluationRegistry::default());
+        let mut schedulers = (0..instances)
+            .map(|instance_id| EvaluationScheduler::with_registry(&config(), instance_id, registry.clone()))
+            .collect::<Vec<_>>();
+        let mut requested_at = vec![None; schedulers.len()];
+        let mut evaluations = Vec::new();
+
+        let start = registry.origin;
+        let mut elapsed = Duration::ZERO;
+        while elapsed < duration {
+            let requested = elapsed.as_micros() % period.as_micros() == 0;
+            for (instance_id, (scheduler, requested_at)) in
+                schedulers.iter_mut().zip(requested_at.iter_mut()).enumerate()
+            {
+                if requested {
+                    requested_at.get_or_insert(elapsed);
+                }
+                if let Some(request) = *requested_at {
+                    if scheduler.evaluation_delay(start + elapsed, false).is_none() {
+                        evaluations.push((instance_id as u64, elapsed, elapsed - request));
+                        *requested_at = None;
+                    }
+                }
+            }
+            elapsed += STEP;
+        }
+        evaluations
+    }
+
+    #[test]
+    fn test_jittered_debounce() {
+        for instance_id in 0..1000 {
+            let debounce = jittered_debounce(DEBOUNCE, JITTER, instance_id);
+            assert!(debounce >= DEBOUNCE - JITTER && debounce <= DEBOUNCE + JITTER, "{debounce:?}");
+            assert_eq!(debounce, jittered_debounce(DEBOUNCE, JITTER, instance_id));
+        }
+        // jitter is capped
+        let debounce = jittered_debounce(Duration::from_millis(100), Duration::from_secs(1), 3);
+        assert!(debounce >= Duration::from_millis(80) && debounce <= Duration::from_millis(120), "{debounce:?}");
+        // never negative
+        assert!(jittered_debounce(Duration::ZERO, JITTER, 3) <= JITTER);
+    }
+
+    #[test]
+    fn test_instances_are_staggered() {
+        let evaluations = simulate(6, Duration::from_millis(200), Duration::from_secs(10));
+        assert_eq!(evaluations.len(), 6 * 50);
+
+        // the guard interval fits as many times as there are instances in the shortest debounce period
+        let mut times = evaluations.iter().map(|(_, at, _)| *at).collect::<Vec<_>>();
+        times.sort();
+        for window in times.windows(2) {
+            assert!(window[1] - window[0] >= GUARD_INTERVAL, "{window:?}");
+        }
+    }
+
+    #[test]
+    fn test_coordination_delay_is_bounded() {
+        // more instances than fit in a debounce period, some have to evaluate within the guard interval
+        let evaluations = simulate(20, Duration::from_millis(200), Duration::from_secs(10));
+        assert_eq!(evaluations.len(), 20 * 50);
+        for (instance_id, at, latency) in evaluations {
+            let debounce = jittered_debounce(DEBOUNCE, JITTER, instance_id);
+            // evaluations are only requested again on the next step of the simulation
+            assert!(latency <= debounce + STEP, "evaluation of {instance_id} at {at:?} delayed by {latency:?}");
+        }
+    }
+
+    #[test]
+    fn test_forced_evaluation() {
+        let registry = Arc::new(EvaluationRegistry::default());
+        let mut first = EvaluationScheduler::with_registry(&config(), 0, registry.clone());
+        let mut second = EvaluationScheduler::with_registry(&config(), 1, registry.clone());
+        let now = registry.origin + Duration::from_secs(1);
+
+        assert_eq!(first.evaluation_delay(now, false), None);
+        assert_eq!(second.evaluation_delay(now, false), Some(GUARD_INTERVAL));
+        assert_eq!(second.evaluation_delay(now, true), None);
+        // within the debounce of the first instance
+        let debounce = jittered_debounce(DEBOUNCE, JITTER, 0);
+        assert_eq!(first.evaluation_delay(now + STEP, false), Some(debounce - STEP));
+        assert_eq!(first.evaluation_delay(now + STEP, true), None);
+    }
+
+    #[test]
+    fn test_without_coordination() {
+        let registry = Arc::new(EvaluationRegistry::default());
+        let config = EvaluationSchedulingConfig { coordinate_instances: false, ..config() };
+        let mut schedulers = (0..6)
+            .map(|instance_id| EvaluationScheduler::with_registry(&config, instance_id, registry.clone()))
+            .collect::<Vec<_>>();
+        let now = registry.origin + Duration::from_secs(1);
+        // neither staggered nor debounced
+        for step in 0..3 {
+            for scheduler in &mut schedulers {
+                assert_eq!(scheduler.evaluation_delay(now + STEP * step, false), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_coordinated_instance() {
+        let registry = Arc::new(EvaluationRegistry::default());
+        let now = registry.origin + Duration::from_secs(1);
+        let mut first = EvaluationScheduler::with_registry(&config(), 0, registry.clone());
+        assert_eq!(first.evaluation_delay(now, false), None);
+        assert_eq!(first.evaluation_delay(now + STEP, false), None);
+
+        // debounced while another one is loaded, and again once it is removed
+        let second = EvaluationScheduler::with_registry(&config(), 1, registry.clone());
+        assert!(first.evaluation_delay(now + STEP * 2, false).is_some());
+        drop(second);
+        assert_eq!(first.evaluation_delay(now + STEP * 2, false), None);
+    }
+
+    #[test]
+    fn test_deferred_evaluation() {
+        const SAMPLE_RATE: u16 = 48000;
+        let deferred_evaluation = DeferredEvaluation::default();
+        assert!(!deferred_evaluation.take_due(48000, SAMPLE_RATE));
+
+        deferred_evaluation.defer(48000, Duration::from_millis(10));
+        assert!(!deferred_evaluation.take_due(48000 + 479, SAMPLE_RATE));
+        // not before the sample rate is known
+        assert!(!deferred_evaluation.take_due(48000 + 480, 0));
+        assert!(deferred_evaluation.take_due(48000 + 480, SAMPLE_RATE));
+        // a single task
+        assert!(!deferred_evaluation.take_due(48000 + 960, SAMPLE_RATE));
+
+        deferred_evaluation.defer(96000, Duration::ZERO);
+        deferred_evaluation.cancel();
+        assert!(!deferred_evaluation.take_due(96000, SAMPLE_RATE));
+    }
+}
@@ -1,26 +1,44 @@
 use crate::{
+    change_marker::{ChangeMarker, WALL_CLOCK_SETTLE},
     config::{Config, LiveConfig},
+    editor_activity::EditorActivity,
+    editor_size,
+    task_executor::{Task, UpdateOrigin},
+    watchdog::{Heartbeat, StallDetector},
     MidiBpmDetector, MidiBpmDetectorParams,
 };
 use crossbeam::atomic::AtomicCell;
 use gui::{create_gui, BPMDetectionGUI, BPMDetectionParameters, GuiRemote};
+use midi::RateLimitedNotes;
 use nih_plug::prelude::{AsyncExecutor, ParamSetter};
 use nih_plug_egui::{
-    egui::{mutex::RwLock, Context},
-    EguiState,
+    egui,
+    egui::{mutex::RwLock, Color32, Context},
+};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
-use std::sync::{atomic::Ordering, Arc};
 
 use sync::ArcAtomicBool;
 
 pub struct GuiEditor {
-    pub editor_state: Arc<EguiState>,
     pub bpm_detection_gui: Option<BPMDetectionGUI<LiveConfig>>,
     pub gui_remote_receiver: Arc<AtomicCell<Option<GuiRemote>>>,
+    pub editor_activity: Arc<EditorActivity>,
     pub force_evaluate_bpm_detection: ArcAtomicBool,
     pub config: Arc<RwLock<Config>>,
     pub gui_must_update_config: ArcAtomicBool,
+    // parameters were received from the TUI while the task executor had no context to write them to the host with
+    pub remote_parameters_changed: ArcAtomicBool,
     pub params: Arc<MidiBpmDetectorParams>,
+    pub heartbeat: Arc<Heartbeat>,
+    pub stall_detector: StallDetector,
+    pub static_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+    pub dynamic_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+    pub gui_parameters_changed_at: Arc<ChangeMarker>,
+    // counted by the rate limiter of the audio thread
+    pub rate_limited_notes: RateLimitedNotes,
 }
 
 impl GuiEditor {
@@ -32,6 +50,7 @@ impl GuiEditor {
             async_executor,
             self.force_evaluate_bpm_detection.clone(),
             self.params.clone(),
+            self.rate_limited_notes.clone(),
         );
         let send_tempo_changed = live_config.send_tempo_changed.clone();
         let (gui_remote, gui_builder) = create_gui(live_config);
@@ -46,13 +65,46 @@ impl GuiEditor {
         });
         let gui = gui_builder.build(egui_ctx.clone());
         self.bpm_detection_gui = Some(gui);
+        // active before the executor receives the remote, so it doesn't drop it
+        self.editor_activity.built();
         self.gui_remote_receiver.store(Some(gui_remote));
         self.force_evaluate_bpm_detection.store(true, Ordering::Relaxed);
     }
 
-    pub fn update(&mut self, setter: &ParamSetter, egui_ctx: &Context) {
-        let should_drop = match (self.editor_state.is_open(), &mut self.bpm_detection_gui) {
+    pub fn update(
+        &mut self,
+        setter: &ParamSetter,
+        egui_ctx: &Context,
+        async_executor: &AsyncExecutor<MidiBpmDetector>,
+    ) {
+        // checked first, as what follows may be blocked by a stalled executor
+        if let Some(stalled_for) = self.stall_detector.check(&self.heartbeat, Instant::now()) {
+            egui::TopBottomPanel::top("watchdog").show(egui_ctx, |ui| {
+                ui.colored_label(
+                    Color32::YELLOW,
+                    format!("background worker stalled for {:.1}s", stalled_for.as_secs_f32()),
+                );
+            });
+            egui_ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        let should_drop = match (self.editor_activity.is_active(Instant::now()), &mut self.bpm_detection_gui) {
             (true, Some(bpm_detection_gui)) => {
+                // changes from the host are applied from process() as the samples advance, a host that doesn't process
+                // while the editor is open would leave them pending
+                let now = Instant::now();
+                if self.static_bpm_detection_parameters_changed_at.take_settled_wall_clock(now) {
+                    async_executor.execute_background(Task::StaticBPMDetectionParameters(UpdateOrigin::Daw));
+                }
+                if self.dynamic_bpm_detection_parameters_changed_at.take_settled_wall_clock(now) {
+                    async_executor.execute_background(Task::DynamicBPMDetectionParameters(UpdateOrigin::Daw));
+                }
+                if self.gui_parameters_changed_at.take_settled_wall_clock(now) {
+                    async_executor.execute_background(Task::GUIParameters);
+                }
+                egui_ctx.request_repaint_after(WALL_CLOCK_SETTLE);
+                editor_size::track(&self.params.editor_state, egui_ctx);
+
                 if bpm_detection_gui.live_parameters.send_tempo_changed.fetch_xor(true, Ordering::Relaxed) {
                     let send_tempo = bpm_detection_gui.live_parameters.get_send_tempo();
                     setter.begin_set_parameter(&self.params.send_tempo);
@@ -63,12 +115,17 @@ impl GuiEditor {
                 bpm_detection_gui.live_parameters.apply_delayed_updates();
 
                 if self.gui_must_update_config.take(Ordering::Relaxed) {
-                    bpm_detection_gui.live_parameters.config = self.config.read().clone();
+                    bpm_detection_gui.live_parameters.load_config(self.config.read().clone());
+                    if self.remote_parameters_changed.take(Ordering::Relaxed) {
+                        bpm_detection_gui.live_parameters.parameters_changed_elsewhere();
+                    }
                 }
 
                 // error may happen if corresponding remote was dropped
                 if bpm_detection_gui.update(egui_ctx).is_ok() {
-                    bpm_detection_gui.live_parameters.apply_changes_to_daw_parameters(setter);
+                    // written to the host once the slider is released
+                    let dragging = egui_ctx.dragged_id().is_some();
+                    bpm_detection_gui.live_parameters.apply_changes_to_daw_parameters(setter, dragging);
                     false
                 } else {
                     true
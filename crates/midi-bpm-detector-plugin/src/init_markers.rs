@@ -0,0 +1,97 @@
+//! Detects a plugin that keeps failing to start. Each instance writes a marker file when it starts and removes it once
+//! it evaluated the tempo or was shut down cleanly: markers left by other processes are starts that crashed or hung.
+
+use errors::{error, info};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const PREFIX: &str = "init-";
+// more stale markers than that and the instance starts in safe mode
+const MAX_STALE_MARKERS: usize = 2;
+
+/// Marker of a start that is not known to have succeeded yet
+#[derive(Debug)]
+pub struct InitMarker {
+    path: PathBuf,
+}
+
+impl InitMarker {
+    /// The start succeeded
+    pub fn clear(self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            error!("could not remove {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Writes the marker of this instance. Returns it along with whether to start in safe mode, in which case the stale
+/// markers are removed: the next start tries the saved state again
+pub fn begin(directory: &Path, process_id: u32, instance_id: u64) -> io::Result<(InitMarker, bool)> {
+    fs::create_dir_all(directory)?;
+    let stale_markers = stale_markers(directory, process_id)?;
+    let safe_mode = stale_markers.len() > MAX_STALE_MARKERS;
+    if safe_mode {
+        info!("{} starts did not complete, starting in safe mode", stale_markers.len());
+        for stale_marker in stale_markers {
+            fs::remove_file(stale_marker)?;
+        }
+    }
+    let path = directory.join(format!("{PREFIX}{process_id}-{instance_id}"));
+    fs::write(&path, [])?;
+    Ok((InitMarker { path }, safe_mode))
+}
+
+// markers of other processes, those of this process belong to instances that are still starting
+fn stale_markers(directory: &Path, process_id: u32) -> io::Result<Vec<PathBuf>> {
+    let own_prefix = format!("{PREFIX}{process_id}-");
+    let mut stale_markers = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with(PREFIX) && !file_name.starts_with(&own_prefix) {
+            stale_markers.push(entry.path());
+        }
+    }
+    Ok(stale_markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers() {
+        let directory = std::env::temp_dir().join(format!("init_markers_test_{}", std::process::id()));
+
+        // a successful start leaves nothing behind
+        let (marker, safe_mode) = begin(&directory, 1, 0).unwrap();
+        assert!(!safe_mode);
+        marker.clear();
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+
+        // three starts crash
+        for process_id in 2..5 {
+            let (_, safe_mode) = begin(&directory, process_id, 0).unwrap();
+            assert!(!safe_mode);
+        }
+        // other instances of the same process are still starting, they don't count
+        let (_, safe_mode) = begin(&directory, 4, 1).unwrap();
+        assert!(!safe_mode);
+
+        let (marker, safe_mode) = begin(&directory, 5, 0).unwrap();
+        assert!(safe_mode);
+        // only this start is left
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        marker.clear();
+
+        // back to the saved state
+        let (marker, safe_mode) = begin(&directory, 6, 0).unwrap();
+        assert!(!safe_mode);
+        marker.clear();
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -5,11 +5,22 @@
 #![allow(clippy::similar_names)]
 #![allow(clippy::module_name_repetitions)]
 
+mod change_marker;
 mod config;
+mod editor_activity;
+mod editor_size;
+mod evaluation_scheduler;
 mod gui;
+mod init_markers;
+mod midi_clock;
+mod param_writes;
 mod params;
+mod remote_controls;
 mod task_executor;
+mod toggle_hysteresis;
+mod watchdog;
 
+use atomic_float::AtomicF32;
 use chrono::Duration;
 use crossbeam::atomic::AtomicCell;
 use nih_plug::prelude::*;
@@ -17,16 +28,22 @@ use nih_plug_egui::create_egui_editor;
 use std::sync::atomic::AtomicUsize;
 
 use std::{
-    mem::MaybeUninit,
-    sync::{atomic::Ordering, Arc},
+    mem::{self, MaybeUninit},
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::Ordering, Arc, PoisonError},
+    time::Instant,
 };
 
-use sync::{ArcAtomicBool, ArcAtomicOptional};
+use build::get_data_dir;
+use errors::{register_crash_section, report_caught_panic, CrashSection};
+use gui::effective_config::{register_parameters_crash_section, DetectionParameters};
+use sync::{ArcAtomicBool, ArcAtomicOptional, Mutex};
 
 use midi::{
     bpm::sample_to_duration,
     midi_messages::{wmidi, MidiNoteOn},
-    BPMDetection, TimedMidiNoteOn,
+    AutoZoom, BPMDetection, BeatCounter, ChordFilter, ClockAnchor, DawLink, EgressHub, ParameterRamp, RateLimitedNotes,
+    RateLimiter, TempoChangeDetector, TimeSignature, TimedMidiNoteOn, VelocityGate,
 };
 
 use nih_plug::{log::error, midi::MidiResult};
@@ -35,10 +52,19 @@ use nih_plug_egui::egui::mutex::RwLock;
 use ringbuf::{producer::PostponedProducer, SharedRb, StaticRb};
 
 use crate::{
+    change_marker::ChangeMarker,
     config::Config,
+    editor_activity::{EditorActivity, TrackedEditor, EDITOR_GRACE},
+    evaluation_scheduler::{next_instance_id, DeferredEvaluation, EvaluationScheduler},
     gui::GuiEditor,
+    init_markers::InitMarker,
+    midi_clock::{BlockClock, MidiClock},
+    param_writes::HostParamWriter,
     params::MidiBpmDetectorParams,
-    task_executor::{Event, Task, UpdateOrigin},
+    remote_controls::layout,
+    task_executor::{Event, NoteBatch, Task, UpdateOrigin, NOTE_BATCH},
+    toggle_hysteresis::ToggleHysteresis,
+    watchdog::{Heartbeat, StallDetector},
 };
 
 pub struct MidiBpmDetector {
@@ -48,11 +74,37 @@ pub struct MidiBpmDetector {
     // should recompute bpm evaluation, even if there is no new notes. Happens after config change
     // or GUI just reopened
     force_evaluate_bpm_detection: ArcAtomicBool,
+    // an evaluation was postponed by the scheduler, the executor must be called again even without new notes
+    deferred_evaluation: Arc<DeferredEvaluation>,
     events_sender: PostponedProducer<Event, Arc<SharedRb<Event, [MaybeUninit<Event>; 1000]>>>,
     task_executor: Option<task_executor::TaskExecutor>,
     gui_editor: Option<GuiEditor>,
-    static_bpm_detection_parameters_changed_at: ArcAtomicOptional<usize>,
-    dynamic_bpm_detection_parameters_changed_at: ArcAtomicOptional<usize>,
+    editor_activity: Arc<EditorActivity>,
+    host_param_writer: HostParamWriter,
+    static_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+    dynamic_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+    gui_parameters_changed_at: Arc<ChangeMarker>,
+    rate_limiter: RateLimiter,
+    heartbeat: Arc<Heartbeat>,
+    // set from the bypass parameter
+    bypass_detection: ArcAtomicBool,
+    // bypass state of the previous process block
+    detection_bypassed: bool,
+    // note timestamps are derived from the sample count, this relates them to the wall clock. Taken again whenever
+    // they drift apart by more than a block, as when the host stops processing and resumes
+    clock_anchor: Arc<AtomicCell<Option<ClockAnchor>>>,
+    // last time signature of the transport sent to the executor
+    time_signature: Option<TimeSignature>,
+    instance_id: u64,
+    // written by the first initialization, see `init_markers`
+    init_marker: Arc<AtomicCell<Option<InitMarker>>>,
+    init_started: bool,
+    safe_mode: ArcAtomicBool,
+    // last confident estimate of the executor, NaN until there is one or while bypassed
+    midi_clock_bpm: Arc<AtomicF32>,
+    block_clock: BlockClock,
+    // sections of this instance in the crash report
+    _crash_sections: [CrashSection; 2],
 }
 
 impl Default for MidiBpmDetector {
@@ -62,51 +114,129 @@ impl Default for MidiBpmDetector {
         let events_sender = events_sender.into_postponed();
         let events_receiver = events_receiver.into_postponed();
         let gui_remote_receiver = Arc::new(AtomicCell::new(None));
+        let clock_anchor = Arc::new(AtomicCell::new(None));
+        let init_marker = Arc::new(AtomicCell::new(None));
+        let instance_id = next_instance_id();
         let gui_remote = None;
         let daw_port = ArcAtomicOptional::<u16>::new(None);
+        let editor_activity = Arc::new(EditorActivity::new(EDITOR_GRACE));
+        let host_param_writer = HostParamWriter::default();
+        let midi_clock_bpm = Arc::new(AtomicF32::new(f32::NAN));
 
         let mut config = Config::default();
         let bpm_detection = BPMDetection::new(config.static_bpm_detection_parameters.clone());
+        // built along with the limiter rather than with the configuration, which the GUI reloads
+        let rate_limited_notes = RateLimitedNotes::default();
+        let rate_limiter = RateLimiter::new(&config.rate_limit, &rate_limited_notes);
+        let heartbeat = Arc::new(Heartbeat::default());
 
-        // set a dummy value so GUI params are updated from saved daw parameters at startup
-        let static_bpm_detection_parameters_changed_at = ArcAtomicOptional::<usize>::new(Some(1));
-        let dynamic_bpm_detection_parameters_changed_at = ArcAtomicOptional::<usize>::new(Some(1));
+        // pending so GUI params are updated from saved daw parameters at startup
+        let static_bpm_detection_parameters_changed_at = Arc::new(ChangeMarker::pending());
+        let dynamic_bpm_detection_parameters_changed_at = Arc::new(ChangeMarker::pending());
+        let gui_parameters_changed_at = Arc::new(ChangeMarker::pending());
 
         let params = Arc::new(MidiBpmDetectorParams::new(
             &mut config,
             static_bpm_detection_parameters_changed_at.clone(),
             dynamic_bpm_detection_parameters_changed_at.clone(),
+            gui_parameters_changed_at.clone(),
             current_sample.clone(),
             daw_port.clone(),
         ));
 
+        let detection_parameters = Arc::new(Mutex::new(DetectionParameters {
+            static_bpm_detection_parameters: config.static_bpm_detection_parameters.clone(),
+            dynamic_bpm_detection_parameters: config.dynamic_bpm_detection_parameters.clone(),
+        }));
+        let crash_sections = [
+            register_parameters_crash_section(
+                format!("instance {instance_id}: detection parameters"),
+                detection_parameters.clone(),
+            ),
+            register_crash_section(format!("instance {instance_id}: diagnostics"), {
+                let rate_limited_notes = rate_limited_notes.clone();
+                let tempo_latency = config.tempo_latency.clone();
+                let heartbeat = heartbeat.clone();
+                move || {
+                    format!(
+                        "rate limited notes: {}\ntempo latency: {}\npending events: {}\nlast task: {:?}{}",
+                        rate_limited_notes.get(),
+                        tempo_latency.try_summary().map_or_else(|| "-".to_string(), |summary| summary.to_string()),
+                        heartbeat.pending_events(),
+                        heartbeat.last_task(),
+                        if heartbeat.is_running() { ", running" } else { "" }
+                    )
+                }
+            }),
+        ];
+
         let shared_config = Arc::new(RwLock::new(config.clone()));
         let gui_must_update_config = ArcAtomicBool::new(false);
+        let deferred_evaluation = Arc::new(DeferredEvaluation::default());
+        let remote_parameters_changed = ArcAtomicBool::new(false);
 
         let task_executor = task_executor::TaskExecutor {
             bpm_detection,
+            chord_filter: ChordFilter::default(),
+            parameter_ramp: ParameterRamp::new(&config.dynamic_bpm_detection_parameters),
+            auto_zoom: AutoZoom::new(&config.static_bpm_detection_parameters),
             dynamic_bpm_detection_parameters: config.dynamic_bpm_detection_parameters,
             gui_remote,
             params: params.clone(),
+            param_reader: Box::new(config.host_modulation),
             gui_remote_receiver: gui_remote_receiver.clone(),
+            editor_activity: editor_activity.clone(),
             events_receiver,
             config: shared_config.clone(),
             gui_must_update_config: gui_must_update_config.clone(),
             daw_port,
-            daw_connection: None,
+            egress_hub: EgressHub::new(|| vec![Box::new(DawLink::default())]),
             send_tempo: config.send_tempo.clone(),
+            tempo_source: config.tempo_source.clone(),
+            min_tempo_confidence: config.min_tempo_confidence,
+            feel_ambiguity: config.feel_ambiguity.clone(),
+            daw_bpm: None,
+            heartbeat: heartbeat.clone(),
+            evaluation_scheduler: EvaluationScheduler::new(&config.evaluation_scheduling, instance_id),
+            deferred_evaluation: deferred_evaluation.clone(),
+            evaluation_deferred: false,
+            clock_anchor: clock_anchor.clone(),
+            tempo_latency: config.tempo_latency.clone(),
+            bypass_detection: config.bypass_detection.clone(),
+            bypassed: false,
+            beat_counter: BeatCounter::new(&config.beat_counter),
+            velocity_gate: VelocityGate::default(),
+            tempo_change: TempoChangeDetector::default(),
+            init_marker: init_marker.clone(),
+            safe_mode: config.safe_mode.clone(),
+            toggle_hysteresis: ToggleHysteresis::new(config.evaluation_scheduling.toggle_interval),
+            dynamic_bpm_detection_parameters_changed_at: dynamic_bpm_detection_parameters_changed_at.clone(),
+            current_sample: current_sample.clone(),
+            detection_parameters,
+            remote_control_server: None,
+            remote_control_port: None,
+            remote_parameters_changed: remote_parameters_changed.clone(),
+            host_param_writer: host_param_writer.clone(),
+            midi_clock_bpm: midi_clock_bpm.clone(),
         };
 
         let force_evaluate_bpm_detection = ArcAtomicBool::new(false);
 
         let gui_editor = GuiEditor {
-            editor_state: params.editor_state.clone(),
             bpm_detection_gui: None,
             gui_remote_receiver: gui_remote_receiver.clone(),
+            editor_activity: editor_activity.clone(),
             force_evaluate_bpm_detection: force_evaluate_bpm_detection.clone(),
             config: shared_config,
             params: params.clone(),
             gui_must_update_config,
+            remote_parameters_changed,
+            heartbeat: heartbeat.clone(),
+            stall_detector: StallDetector::new(config.watchdog.stall_threshold),
+            static_bpm_detection_parameters_changed_at: static_bpm_detection_parameters_changed_at.clone(),
+            dynamic_bpm_detection_parameters_changed_at: dynamic_bpm_detection_parameters_changed_at.clone(),
+            gui_parameters_changed_at: gui_parameters_changed_at.clone(),
+            rate_limited_notes,
         };
 
         Self {
@@ -114,11 +244,28 @@ impl Default for MidiBpmDetector {
             current_sample,
             sample_rate: 0,
             force_evaluate_bpm_detection,
+            deferred_evaluation,
             events_sender,
             task_executor: Some(task_executor),
             gui_editor: Some(gui_editor),
+            editor_activity,
+            host_param_writer,
             static_bpm_detection_parameters_changed_at,
             dynamic_bpm_detection_parameters_changed_at,
+            gui_parameters_changed_at,
+            rate_limiter,
+            heartbeat,
+            bypass_detection: config.bypass_detection.clone(),
+            detection_bypassed: false,
+            clock_anchor,
+            time_signature: None,
+            instance_id,
+            init_marker,
+            init_started: false,
+            safe_mode: config.safe_mode.clone(),
+            midi_clock_bpm,
+            block_clock: BlockClock::default(),
+            _crash_sections: crash_sections,
         }
     }
 }
@@ -128,10 +275,8 @@ impl Plugin for MidiBpmDetector {
     // documentation for more information. `()` means that the midi-bpm-detector-plugin does not have any background
     // tasks.
     type BackgroundTask = Task;
-    // If the midi-bpm-detector-plugin can send or receive SysEx messages, it can define a type to wrap around those
-    // messages here. The type implements the `SysExMessage` trait, which allows conversion to and
-    // from plain byte buffers.
-    type SysExMessage = ();
+    // the MIDI clock is sent as raw bytes, see `midi_clock`
+    type SysExMessage = MidiClock;
 
     // The first audio IO layout is used as the default. The other layouts may be selected either
     // explicitly or automatically by the host or the user depending on the midi-bpm-detector-plugin API/backend.
@@ -168,12 +313,17 @@ impl Plugin for MidiBpmDetector {
 
     fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         let gui_editor = self.gui_editor.take().unwrap();
+        editor_size::restore(&self.params.editor_state);
         create_egui_editor(
             self.params.editor_state.clone(),
             (async_executor, gui_editor),
             |egui_ctx, (async_executor, gui_editor)| gui_editor.build(egui_ctx, async_executor.clone()),
-            |egui_ctx, setter, (_async_executor, gui_editor)| gui_editor.update(setter, egui_ctx),
+            |egui_ctx, setter, (async_executor, gui_editor)| gui_editor.update(setter, egui_ctx, async_executor),
         )
+        .map(|editor| {
+            Box::new(TrackedEditor::new(editor, self.editor_activity.clone(), self.host_param_writer.clone()))
+                as Box<dyn Editor>
+        })
     }
 
     fn initialize(
@@ -183,9 +333,28 @@ impl Plugin for MidiBpmDetector {
         _context: &mut impl InitContext<Self>,
     ) -> bool {
         self.sample_rate = buffer_config.sample_rate as u16;
+        self.block_clock.set_sample_rate(buffer_config.sample_rate);
+        self.clock_anchor.store(Some(ClockAnchor::new(self.current_time(), Instant::now())));
+        if !mem::replace(&mut self.init_started, true) {
+            match init_markers::begin(&get_data_dir().join("plugin"), std::process::id(), self.instance_id) {
+                Ok((init_marker, safe_mode)) => {
+                    self.safe_mode.store(safe_mode, Ordering::Relaxed);
+                    self.init_marker.store(Some(init_marker));
+                }
+                Err(e) => error!("could not write the init marker: {e}"),
+            }
+        }
         true
     }
 
+    fn deactivate(&mut self) {
+        // shut down cleanly, even if nothing was evaluated
+        if let Some(init_marker) = self.init_marker.take() {
+            init_marker.clear();
+        }
+        self.host_param_writer.set(None);
+    }
+
     fn reset(&mut self) {
         // Reset buffers and envelopes here. This can be called from the audio thread and may not
         // allocate. You can remove this function if you do not need it.
@@ -197,55 +366,78 @@ impl Plugin for MidiBpmDetector {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        if let Some(static_bpm_detection_parameters_changed_at) =
-            self.static_bpm_detection_parameters_changed_at.load(Ordering::Relaxed)
-        {
-            let duration_since_change = sample_to_duration(
-                self.sample_rate,
-                self.current_sample.load(Ordering::Relaxed).saturating_sub(static_bpm_detection_parameters_changed_at),
-            );
-            if duration_since_change > Duration::milliseconds(50) {
-                context.execute_background(Task::StaticBPMDetectionParameters(UpdateOrigin::Daw));
-                self.static_bpm_detection_parameters_changed_at.store(None, Ordering::Relaxed);
-            }
+        // the host keeps running without this block
+        panic::catch_unwind(AssertUnwindSafe(|| self.process_block(buffer, context))).unwrap_or_else(|payload| {
+            report_caught_panic(payload.as_ref());
+            ProcessStatus::Error("the detection panicked, see the crash report")
+        })
+    }
+}
+
+impl MidiBpmDetector {
+    fn process_block(&mut self, buffer: &Buffer, context: &mut impl ProcessContext<Self>) -> ProcessStatus {
+        let current_sample = self.current_sample.load(Ordering::Relaxed);
+        self.anchor_clock(buffer.samples());
+        if self.static_bpm_detection_parameters_changed_at.take_settled_samples(current_sample, self.sample_rate) {
+            context.execute_background(Task::StaticBPMDetectionParameters(UpdateOrigin::Daw));
         }
-        if let Some(dynamic_bpm_detection_parameters_changed_at) =
-            self.dynamic_bpm_detection_parameters_changed_at.load(Ordering::Relaxed)
-        {
-            let duration_since_change = sample_to_duration(
-                self.sample_rate,
-                self.current_sample.load(Ordering::Relaxed).saturating_sub(dynamic_bpm_detection_parameters_changed_at),
-            );
-            if duration_since_change > Duration::milliseconds(50) {
-                context.execute_background(Task::DynamicBPMDetectionParameters(UpdateOrigin::Daw));
-                self.dynamic_bpm_detection_parameters_changed_at.store(None, Ordering::Relaxed);
-            }
+        if self.dynamic_bpm_detection_parameters_changed_at.take_settled_samples(current_sample, self.sample_rate) {
+            context.execute_background(Task::DynamicBPMDetectionParameters(UpdateOrigin::Daw));
         }
+        if self.gui_parameters_changed_at.take_settled_samples(current_sample, self.sample_rate) {
+            context.execute_background(Task::GUIParameters);
+        }
+        let midi_clock_bpm = (self.params.send_midi_clock.value() && !self.bypass_detection.load(Ordering::Relaxed))
+            .then(|| self.midi_clock_bpm.load(Ordering::Relaxed));
+        self.block_clock.begin_block(midi_clock_bpm);
         self.receive_notes(context);
+        let samples = u32::try_from(buffer.samples()).unwrap_or(u32::MAX);
+        self.send_midi_clock(samples, context);
+        self.block_clock.end_block(samples);
         self.current_sample.fetch_add(buffer.samples(), Ordering::Relaxed);
-        if self.params.editor_state.is_open() {
+        if self.editor_activity.is_active(Instant::now()) {
             ProcessStatus::KeepAlive
         } else {
             ProcessStatus::Normal
         }
     }
-}
 
-impl MidiBpmDetector {
     fn receive_notes<P>(&mut self, context: &mut P) -> bool
     where
         P: ProcessContext<Self>,
     {
         let current_sample = self.current_sample.load(Ordering::Relaxed);
         let mut has_new_events = false;
-        if let Some(bpm) = context.transport().tempo {
-            if self.events_sender.push(Event::DawBPM(bpm as f32)).is_err() {
+        let mut pushed_events = 0;
+        let mut notes = NoteBatch::new();
+        let bypassed = self.bypass_detection.load(Ordering::Relaxed);
+        // one more task lets the executor drop what it was holding, or evaluate again on resume
+        let bypass_changed = bypassed != mem::replace(&mut self.detection_bypassed, bypassed);
+        if let (Some(bpm), false) = (context.transport().tempo, bypassed) {
+            if self.events_sender.push(Event::DawBPM(bpm as f32)).is_ok() {
+                pushed_events += 1;
+            } else {
                 error!("event ringbuffer is full");
             }
             has_new_events = true;
         }
+        if let Some(time_signature) = transport_time_signature(context.transport()).filter(|_| !bypassed) {
+            if self.time_signature != Some(time_signature) {
+                if self.events_sender.push(Event::TimeSignature(time_signature)).is_ok() {
+                    pushed_events += 1;
+                    self.time_signature = Some(time_signature);
+                } else {
+                    error!("event ringbuffer is full");
+                }
+                has_new_events = true;
+            }
+        }
         while let Some(event) = context.next_event() {
+            self.send_midi_clock(event.timing(), context);
             context.send_event(event);
+            if bypassed {
+                continue;
+            }
             let Some(midi_event) = event.as_midi() else {
                 continue;
             };
@@ -255,6 +447,16 @@ impl MidiBpmDetector {
             let Ok(midi_message) = wmidi::MidiMessage::from_bytes(&bytes) else {
                 continue;
             };
+            if let wmidi::MidiMessage::NoteOff(channel, note, _) = midi_message {
+                // the notes it may release are ahead of it
+                self.push_notes(&mut notes, &mut pushed_events);
+                if self.events_sender.push(Event::NoteOff { channel: channel.index(), note: note as u8 }).is_ok() {
+                    pushed_events += 1;
+                } else {
+                    error!("event ringbuffer is full");
+                }
+                continue;
+            }
             let Ok(midi_note_on) = MidiNoteOn::try_from(midi_message.to_owned()) else {
                 continue;
             };
@@ -262,32 +464,83 @@ impl MidiBpmDetector {
             let note_sample = current_sample + event.timing() as usize;
             let timestamp = sample_to_duration(self.sample_rate, note_sample);
 
-            if self
-                .events_sender
-                .push(Event::TimedMidiNoteOn(TimedMidiNoteOn { timestamp, midi_message: midi_note_on }))
-                .is_err()
-            {
-                error!("event ringbuffer is full");
-            };
+            if !self.rate_limiter.allow(timestamp) {
+                continue;
+            }
+
+            if notes.len() == NOTE_BATCH {
+                self.push_notes(&mut notes, &mut pushed_events);
+            }
+            notes.push(TimedMidiNoteOn { timestamp, midi_message: midi_note_on });
 
             has_new_events = true;
         }
-
-        let force_evaluate_bpm_detection = self.force_evaluate_bpm_detection.take(Ordering::Relaxed);
-        if has_new_events || force_evaluate_bpm_detection {
+        self.push_notes(&mut notes, &mut pushed_events);
+
+        let force_evaluate_bpm_detection =
+            self.force_evaluate_bpm_detection.take(Ordering::Relaxed) || (bypass_changed && !bypassed);
+        if bypass_changed
+            || (!bypassed
+                && (has_new_events
+                    || force_evaluate_bpm_detection
+                    || self.deferred_evaluation.take_due(current_sample, self.sample_rate)))
+        {
             context.execute_background(Task::ProcessNotes(force_evaluate_bpm_detection));
         }
 
+        // counted before the events become visible to the executor
+        self.heartbeat.events_pushed(pushed_events);
         self.events_sender.sync();
         has_new_events
     }
 
-    #[allow(unused)]
+    // the notes gathered so far as a single event, left empty. Dropped when the ring buffer is full
+    fn push_notes(&mut self, notes: &mut NoteBatch, pushed_events: &mut usize) {
+        if notes.is_empty() {
+            return;
+        }
+        if self.events_sender.push(Event::Notes(mem::take(notes))).is_ok() {
+            *pushed_events += 1;
+        } else {
+            error!("event ringbuffer is full");
+        }
+    }
+
+    // the ticks of the block before `until`
+    fn send_midi_clock<P>(&mut self, until: u32, context: &mut P)
+    where
+        P: ProcessContext<Self>,
+    {
+        while let Some(timing) = self.block_clock.next_tick(until) {
+            context.send_event(NoteEvent::MidiSysEx { timing, message: MidiClock });
+        }
+    }
+
+    // the sample count only moves on while the host processes, the anchor of the wall clock is taken again once it
+    // is off by more than the block, the latencies measured would be skewed by the pause otherwise
+    fn anchor_clock(&self, samples: usize) {
+        let current_time = self.current_time();
+        let now = Instant::now();
+        let block_duration = sample_to_duration(self.sample_rate, samples).to_std().unwrap_or_default();
+        let drifted =
+            self.clock_anchor.load().is_none_or(|clock_anchor| clock_anchor.drifted(current_time, now, block_duration));
+        if drifted {
+            self.clock_anchor.store(Some(ClockAnchor::new(current_time, now)));
+        }
+    }
+
     fn current_time(&self) -> Duration {
         sample_to_duration(self.sample_rate, self.current_sample.load(Ordering::Relaxed))
     }
 }
 
+// none when the host does not provide it, the configured time signature is used then
+fn transport_time_signature(transport: &Transport) -> Option<TimeSignature> {
+    let numerator = u8::try_from(transport.time_sig_numerator?).ok()?;
+    let denominator = u8::try_from(transport.time_sig_denominator?).ok()?;
+    (numerator > 0 && denominator > 0).then_some(TimeSignature { numerator, denominator })
+}
+
 impl ClapPlugin for MidiBpmDetector {
     const CLAP_DESCRIPTION: Option<&'static str> =
         Some("Midi midi-bpm-detector-plugin that will estimate the BPM of the midi input");
@@ -297,36 +550,20 @@ impl ClapPlugin for MidiBpmDetector {
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
 
     fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
-        context.add_section("Send tempo", |section| {
-            section.add_page("Send tempo", |page| page.add_param(&self.params.send_tempo));
-        });
-        context.add_section("Static parameters", |section| {
-            section.add_page("Range and resolution", |page| {
-                page.add_param(&self.params.static_params.bpm_center);
-                page.add_param(&self.params.static_params.bpm_range);
-                page.add_param(&self.params.static_params.sample_rate);
+        let remote_controls_config = self.params.remote_controls.read().unwrap_or_else(PoisonError::into_inner).clone();
+        for section in layout(&remote_controls_config, |id| self.params.param_by_id(id).is_some()) {
+            context.add_section(section.name, |section_context| {
+                for page in section.pages {
+                    section_context.add_page(page.name, |page_context| {
+                        for id in &page.params {
+                            if let Some(param) = self.params.param_by_id(id) {
+                                param.add_to(page_context);
+                            }
+                        }
+                    });
+                }
             });
-            section.add_page("Normal distribution", |page| {
-                page.add_param(&self.params.static_params.normal_distribution.resolution);
-                page.add_param(&self.params.static_params.normal_distribution.factor);
-                page.add_param(&self.params.static_params.normal_distribution.imprecision);
-                page.add_param(&self.params.static_params.normal_distribution.std_dev);
-            });
-        });
-        context.add_section("Dynamic parameters", |section| {
-            section.add_page("Dynamic parameters", |page| {
-                page.add_param(&self.params.dynamic_params.beats_lookback);
-                page.add_param(&self.params.dynamic_params.velocity_current_note_weight);
-                page.add_param(&self.params.dynamic_params.velocity_note_from_weight);
-                page.add_param(&self.params.dynamic_params.age_weight);
-                page.add_param(&self.params.dynamic_params.octave_distance_weight);
-                page.add_param(&self.params.dynamic_params.pitch_distance_weight);
-                page.add_param(&self.params.dynamic_params.multiplier_weight);
-                page.add_param(&self.params.dynamic_params.subdivision_weight);
-                page.add_param(&self.params.dynamic_params.normal_distribution_weight);
-                page.add_param(&self.params.dynamic_params.high_tempo_bias);
-            });
-        });
+        }
     }
 }
 
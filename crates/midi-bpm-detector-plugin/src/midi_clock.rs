@@ -0,0 +1,129 @@
+//! MIDI clock sent on the output of the plugin at the estimated tempo, for hosts and devices that can follow it
+//! without the DAW link. Ticks are scheduled within each block at 24 per quarter note, the phase carries over from one
+//! block to the next so the tempo can change between blocks without a gap
+
+use midi::bpm::MIDI_CLOCKS_PER_BEAT;
+use nih_plug::midi::sysex::SysExMessage;
+
+/// The timing clock message. The pinned nih_plug can only send raw bytes as SysEx, and it is the only message sent
+/// that way
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MidiClock;
+
+impl MidiClock {
+    const TIMING_CLOCK: u8 = 0xF8;
+}
+
+impl SysExMessage for MidiClock {
+    type Buffer = [u8; 1];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        (buffer == [Self::TIMING_CLOCK]).then_some(Self)
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        ([Self::TIMING_CLOCK], 1)
+    }
+}
+
+/// Where the ticks fall within the blocks of the host, in samples
+#[derive(Default)]
+pub struct BlockClock {
+    sample_rate: f32,
+    // of the current block, none while the clock is stopped
+    samples_per_tick: Option<f64>,
+    // in samples from the start of the current block
+    next_tick: f64,
+}
+
+impl BlockClock {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.samples_per_tick = None;
+    }
+
+    /// Starts a block at `bpm`. The clock stops while there is no estimate, and starts again with a tick at the start
+    /// of the first block that has one
+    pub fn begin_block(&mut self, bpm: Option<f32>) {
+        let samples_per_tick = bpm
+            .filter(|bpm| bpm.is_finite() && *bpm > 0.0 && self.sample_rate > 0.0)
+            .map(|bpm| f64::from(self.sample_rate) * 60.0 / (f64::from(bpm) * f64::from(MIDI_CLOCKS_PER_BEAT)));
+        self.next_tick = match (self.samples_per_tick, samples_per_tick) {
+            // what is left of the current tick is kept, at the new tempo
+            (Some(previous), Some(samples_per_tick)) => self.next_tick / previous * samples_per_tick,
+            _ => 0.0,
+        };
+        self.samples_per_tick = samples_per_tick;
+    }
+
+    /// Offset of the next tick within the block if it comes before `until`, in samples. Called until it returns none,
+    /// before sending the events at `until` so the output stays in order
+    pub fn next_tick(&mut self, until: u32) -> Option<u32> {
+        let samples_per_tick = self.samples_per_tick?;
+        (self.next_tick < f64::from(until)).then(|| {
+            let offset = self.next_tick as u32;
+            self.next_tick += samples_per_tick;
+            offset
+        })
+    }
+
+    /// Ends a block of `samples`, once every tick in it was taken
+    pub fn end_block(&mut self, samples: u32) {
+        if self.samples_per_tick.is_some() {
+            self.next_tick = (self.next_tick - f64::from(samples)).max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(block_clock: &mut BlockClock, bpm: Option<f32>, samples: u32) -> Vec<u32> {
+        block_clock.begin_block(bpm);
+        let ticks = std::iter::from_fn(|| block_clock.next_tick(samples)).collect();
+        block_clock.end_block(samples);
+        ticks
+    }
+
+    #[test]
+    fn test_ticks_across_blocks() {
+        let mut block_clock = BlockClock::default();
+        block_clock.set_sample_rate(48000.0);
+        // 125 BPM is a tick every 960 samples
+        assert_eq!(ticks(&mut block_clock, Some(125.0), 2000), [0, 960, 1920]);
+        assert_eq!(ticks(&mut block_clock, Some(125.0), 2000), [880, 1840]);
+        // a quarter note over blocks of 512 samples
+        let count = (0..45).map(|_| ticks(&mut block_clock, Some(125.0), 512).len()).sum::<usize>();
+        assert_eq!(count, 24);
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        let mut block_clock = BlockClock::default();
+        block_clock.set_sample_rate(48000.0);
+        assert_eq!(ticks(&mut block_clock, Some(125.0), 480), [0]);
+        // half of the tick is left, at half the tempo it takes as long as a whole tick did
+        assert_eq!(ticks(&mut block_clock, Some(62.5), 2000), [960]);
+    }
+
+    #[test]
+    fn test_no_estimate() {
+        let mut block_clock = BlockClock::default();
+        assert!(ticks(&mut block_clock, Some(125.0), 2000).is_empty(), "no sample rate yet");
+        block_clock.set_sample_rate(48000.0);
+        assert!(ticks(&mut block_clock, None, 2000).is_empty());
+        assert!(ticks(&mut block_clock, Some(f32::NAN), 2000).is_empty());
+        assert_eq!(ticks(&mut block_clock, Some(125.0), 1000), [0, 960]);
+        assert!(ticks(&mut block_clock, Some(f32::NAN), 2000).is_empty());
+        // restarts on a tick
+        assert_eq!(ticks(&mut block_clock, Some(125.0), 1000), [0, 960]);
+    }
+
+    #[test]
+    fn test_clock_message() {
+        assert_eq!(MidiClock.to_buffer(), ([0xF8], 1));
+        assert_eq!(MidiClock::from_buffer(&[0xF8]), Some(MidiClock));
+        assert_eq!(MidiClock::from_buffer(&[0xF0, 0x7E, 0xF7]), None);
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{params::ParamRef, MidiBpmDetectorParams};
+use nih_plug::prelude::{GuiContext, Param, ParamPtr, ParamSetter};
+use std::{
+    mem,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use sync::Mutex;
+
+// pending values are written once the GUI was left alone for that long, even if a drag is still going on
+const PAUSE: Duration = Duration::from_millis(150);
+// relative difference under which a float parameter is left as it is, its normalized value doesn't round-trip exactly
+const PLAIN_EPSILON: f32 = 1e-5;
+
+/// Parameters written to the host together, as one logical gesture. The GUI parameters go with the dynamic ones, they
+/// are applied along with them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParamGroup {
+    Static,
+    Dynamic,
+}
+
+/// Parameter changes sent to the host, implemented by `ParamSetter`
+pub trait ParamWriter {
+    fn begin(&self, param: ParamRef<'_>);
+    fn set_normalized(&self, param: ParamRef<'_>, normalized: f32);
+    fn end(&self, param: ParamRef<'_>);
+}
+
+impl ParamWriter for ParamSetter<'_> {
+    fn begin(&self, param: ParamRef<'_>) {
+        match param {
+            ParamRef::Float(param) => self.begin_set_parameter(param),
+            ParamRef::Int(param) => self.begin_set_parameter(param),
+            ParamRef::Bool(param) => self.begin_set_parameter(param),
+        }
+    }
+
+    fn set_normalized(&self, param: ParamRef<'_>, normalized: f32) {
+        match param {
+            ParamRef::Float(param) => self.set_parameter_normalized(param, normalized),
+            ParamRef::Int(param) => self.set_parameter_normalized(param, normalized),
+            ParamRef::Bool(param) => self.set_parameter_normalized(param, normalized),
+        }
+    }
+
+    fn end(&self, param: ParamRef<'_>) {
+        match param {
+            ParamRef::Float(param) => self.end_set_parameter(param),
+            ParamRef::Int(param) => self.end_set_parameter(param),
+            ParamRef::Bool(param) => self.end_set_parameter(param),
+        }
+    }
+}
+
+impl ParamWriter for Arc<dyn GuiContext> {
+    fn begin(&self, param: ParamRef<'_>) {
+        ParamSetter::new(self.as_ref()).begin(param);
+    }
+
+    fn set_normalized(&self, param: ParamRef<'_>, normalized: f32) {
+        ParamSetter::new(self.as_ref()).set_normalized(param, normalized);
+    }
+
+    fn end(&self, param: ParamRef<'_>) {
+        ParamSetter::new(self.as_ref()).end(param);
+    }
+}
+
+/// Writes to the host from outside the editor, open or not, through the context of the last editor window. The context
+/// holds the wrapper of the plugin, it is released on `deactivate` so that the plugin can be dropped
+#[derive(Clone, Default)]
+pub struct HostParamWriter(Arc<Mutex<Option<Arc<dyn ParamWriter + Send + Sync>>>>);
+
+impl HostParamWriter {
+    pub fn set(&self, writer: Option<Arc<dyn ParamWriter + Send + Sync>>) {
+        *self.0.lock() = writer;
+    }
+
+    /// Writes what `pending` holds right away, false when there is no context as no editor window was opened yet
+    pub fn write(&self, params: &MidiBpmDetectorParams, pending: &mut PendingParamWrites) -> bool {
+        let Some(writer) = self.0.lock().clone() else {
+            return false;
+        };
+        pending.write(params, writer.as_ref());
+        true
+    }
+}
+
+/// Changes made in the GUI, held back while a slider is dragged. Some hosts record every gesture in their undo
+/// history, a drag ends up as a single gesture per group of parameters
+#[derive(Default)]
+pub struct PendingParamWrites {
+    // normalized values, in the order the parameters were first changed
+    values: Vec<(ParamPtr, f32)>,
+    // an on/off switch was flipped, nothing to wait for
+    immediate: bool,
+    last_change: Option<Instant>,
+    dragging: bool,
+}
+
+impl PendingParamWrites {
+    /// Queues `plain` for `param`, unless that is already its value
+    pub fn push<P: Param>(&mut self, param: &P, plain: P::Plain, now: Instant) {
+        let normalized = param.preview_normalized(plain);
+        let param_ptr = param.as_ptr();
+        if let Some((_, value)) = self.values.iter_mut().find(|(pending, _)| *pending == param_ptr) {
+            *value = normalized;
+        } else if (param.unmodulated_normalized_value() - normalized).abs() > f32::EPSILON {
+            self.values.push((param_ptr, normalized));
+        } else {
+            return;
+        }
+        self.last_change = Some(now);
+    }
+
+    /// Like `push`, written on the next `update`
+    pub fn push_immediate<P: Param>(&mut self, param: &P, plain: P::Plain, now: Instant) {
+        let pending = self.values.len();
+        self.push(param, plain, now);
+        self.immediate |= self.values.len() > pending;
+    }
+
+    /// Called every frame, writes the pending values when the drag ended, after a pause, or when something must be
+    /// written right away. The parameters of a group are written within a single gesture: all of them begin, are set,
+    /// then end, in the order they are declared, so the host records one edit it can undo at once. Values the host
+    /// already has are left out, the automation lanes stay untouched
+    pub fn update(&mut self, params: &MidiBpmDetectorParams, writer: &impl ParamWriter, dragging: bool, now: Instant) {
+        let drag_ended = mem::replace(&mut self.dragging, dragging) && !dragging;
+        let paused = self.last_change.is_some_and(|last_change| now.saturating_duration_since(last_change) >= PAUSE);
+        if self.values.is_empty() || !(self.immediate || drag_ended || paused) {
+            return;
+        }
+        self.write(params, writer);
+    }
+
+    /// Writes the pending values without waiting, as `update` does
+    pub fn write(&mut self, params: &MidiBpmDetectorParams, writer: &(impl ParamWriter + ?Sized)) {
+        let mut writes = self
+            .values
+            .drain(..)
+            .filter_map(|(param_ptr, normalized)| {
+                let param = params.param_by_ptr(param_ptr)?;
+                (!is_unchanged(param, normalized)).then(|| (params.write_order(param_ptr), param, normalized))
+            })
+            .collect::<Vec<_>>();
+        writes.sort_by_key(|(write_order, _, _)| *write_order);
+        for group in [ParamGroup::Static, ParamGroup::Dynamic] {
+            let batch = writes.iter().filter(|((param_group, _), _, _)| *param_group == group).collect::<Vec<_>>();
+            for (_, param, _) in &batch {
+                writer.begin(*param);
+            }
+            for (_, param, normalized) in &batch {
+                writer.set_normalized(*param, *normalized);
+            }
+            for (_, param, _) in &batch {
+                writer.end(*param);
+            }
+        }
+        self.immediate = false;
+        self.last_change = None;
+    }
+}
+
+// the host already has that value, compared on the plain value as the normalized one depends on the range
+fn is_unchanged(param: ParamRef<'_>, normalized: f32) -> bool {
+    match param {
+        ParamRef::Float(param) => {
+            let current = param.unmodulated_plain_value();
+            (param.preview_plain(normalized) - current).abs() <= PLAIN_EPSILON * current.abs().max(1.0)
+        }
+        ParamRef::Int(param) => param.preview_plain(normalized) == param.unmodulated_plain_value(),
+        ParamRef::Bool(param) => param.preview_plain(normalized) == param.unmodulated_plain_value(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        change_marker::ChangeMarker,
+        config::Config,
+        params::{apply_float_param, apply_int_param, apply_onoff_param},
+    };
+    use midi::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+    use nih_plug::prelude::FloatParam;
+    use parameter::OnOff;
+    use std::{cell::RefCell, sync::Arc};
+    use sync::ArcAtomicOptional;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Begin(ParamPtr),
+        Set(ParamPtr, f32),
+        End(ParamPtr),
+    }
+
+    #[derive(Default)]
+    struct FakeSetter {
+        calls: RefCell<Vec<Call>>,
+    }
+
+    impl FakeSetter {
+        fn gestures(&self, param: &impl Param) -> usize {
+            self.calls.borrow().iter().filter(|call| **call == Call::Begin(param.as_ptr())).count()
+        }
+
+        fn last_value(&self, param: &impl Param) -> Option<f32> {
+            self.calls.borrow().iter().rev().find_map(|call| match call {
+                Call::Set(param_ptr, normalized) if *param_ptr == param.as_ptr() => Some(*normalized),
+                _ => None,
+            })
+        }
+    }
+
+    impl ParamWriter for FakeSetter {
+        fn begin(&self, param: ParamRef<'_>) {
+            self.calls.borrow_mut().push(Call::Begin(param.as_ptr()));
+        }
+
+        fn set_normalized(&self, param: ParamRef<'_>, normalized: f32) {
+            self.calls.borrow_mut().push(Call::Set(param.as_ptr(), normalized));
+        }
+
+        fn end(&self, param: ParamRef<'_>) {
+            self.calls.borrow_mut().push(Call::End(param.as_ptr()));
+        }
+    }
+
+    fn params(config: &mut Config) -> MidiBpmDetectorParams {
+        MidiBpmDetectorParams::new(
+            config,
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::default(),
+            ArcAtomicOptional::new(None),
+        )
+    }
+
+    // what `LiveConfig` queues for the static parameters after a change in the GUI
+    fn apply_static(
+        params: &MidiBpmDetectorParams,
+        static_bpm_detection_parameters: &mut StaticBPMDetectionParameters,
+        pending: &mut PendingParamWrites,
+        now: Instant,
+    ) {
+        let static_params = &params.static_params;
+        apply_float_param(
+            &StaticBPMDetectionParameters::BPM_CENTER,
+            &static_params.bpm_center,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_int_param(
+            &StaticBPMDetectionParameters::BPM_RANGE,
+            &static_params.bpm_range,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION,
+            &static_params.histogram_resolution,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+    }
+
+    #[test]
+    fn test_drag() {
+        let mut config = Config::default();
+        let params = params(&mut config);
+        let setter = FakeSetter::default();
+        let mut pending = PendingParamWrites::default();
+        let mut static_bpm_detection_parameters = config.static_bpm_detection_parameters.clone();
+        let start = Instant::now();
+
+        // 60 frames of a drag on the BPM center, one frame every 16ms
+        let mut now = start;
+        for _ in 0..60 {
+            static_bpm_detection_parameters.bpm_center += 0.5;
+            apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, now);
+            pending.update(&params, &setter, true, now);
+            now += Duration::from_millis(16);
+        }
+        assert!(setter.calls.borrow().is_empty());
+
+        pending.update(&params, &setter, false, now);
+        let static_params = &params.static_params;
+        assert_eq!(setter.gestures(&static_params.bpm_center), 1);
+        assert_eq!(setter.gestures(&static_params.bpm_range), 0);
+        assert_eq!(setter.gestures(&static_params.histogram_resolution), 0);
+        assert_eq!(
+            setter.last_value(&static_params.bpm_center),
+            Some(static_params.bpm_center.preview_normalized(static_bpm_detection_parameters.bpm_center))
+        );
+        assert_eq!(
+            *setter.calls.borrow(),
+            vec![
+                Call::Begin(static_params.bpm_center.as_ptr()),
+                Call::Set(
+                    static_params.bpm_center.as_ptr(),
+                    static_params.bpm_center.preview_normalized(static_bpm_detection_parameters.bpm_center)
+                ),
+                Call::End(static_params.bpm_center.as_ptr()),
+            ]
+        );
+
+        // nothing left to write
+        pending.update(&params, &setter, false, now + PAUSE);
+        assert_eq!(setter.calls.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut config = Config::default();
+        config.dynamic_bpm_detection_parameters.age_weight = OnOff::On(3.0);
+        let params = params(&mut config);
+        let setter = FakeSetter::default();
+        let mut pending = PendingParamWrites::default();
+        let mut static_bpm_detection_parameters = config.static_bpm_detection_parameters.clone();
+        let mut dynamic_bpm_detection_parameters = config.dynamic_bpm_detection_parameters.clone();
+        let now = Instant::now();
+
+        // changed in the reverse order of their declaration, the dynamic one first
+        dynamic_bpm_detection_parameters.age_weight = OnOff::On(4.0);
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::TIME_DISTANCE,
+            &params.dynamic_params.age_weight,
+            &mut dynamic_bpm_detection_parameters,
+            &mut pending,
+            now,
+        );
+        static_bpm_detection_parameters.histogram_resolution += 100;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, now);
+        static_bpm_detection_parameters.bpm_center += 10.0;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, now);
+        pending.update(&params, &setter, false, now + PAUSE);
+
+        let static_params = &params.static_params;
+        let normalized = |param: &FloatParam, plain: f32| param.preview_normalized(plain);
+        let bpm_center = static_params.bpm_center.as_ptr();
+        let histogram_resolution = static_params.histogram_resolution.as_ptr();
+        let age_weight = params.dynamic_params.age_weight.as_ptr();
+        assert_eq!(
+            *setter.calls.borrow(),
+            vec![
+                Call::Begin(bpm_center),
+                Call::Begin(histogram_resolution),
+                Call::Set(
+                    bpm_center,
+                    normalized(&static_params.bpm_center, static_bpm_detection_parameters.bpm_center)
+                ),
+                Call::Set(
+                    histogram_resolution,
+                    normalized(
+                        &static_params.histogram_resolution,
+                        f32::from(static_bpm_detection_parameters.histogram_resolution)
+                    )
+                ),
+                Call::End(bpm_center),
+                Call::End(histogram_resolution),
+                Call::Begin(age_weight),
+                Call::Set(age_weight, normalized(&params.dynamic_params.age_weight, 4.0)),
+                Call::End(age_weight),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let mut config = Config::default();
+        let params = params(&mut config);
+        let setter = FakeSetter::default();
+        let mut pending = PendingParamWrites::default();
+        let mut static_bpm_detection_parameters = config.static_bpm_detection_parameters.clone();
+        let start = Instant::now();
+
+        // dragged away and back to where it was
+        static_bpm_detection_parameters.bpm_center += 10.0;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        static_bpm_detection_parameters.bpm_center -= 10.0;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        static_bpm_detection_parameters.bpm_range += 5;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        pending.update(&params, &setter, false, start + PAUSE);
+        assert_eq!(setter.gestures(&params.static_params.bpm_center), 0);
+        assert_eq!(setter.gestures(&params.static_params.bpm_range), 1);
+
+        // nothing left to write, not even an empty gesture
+        static_bpm_detection_parameters.bpm_center += 10.0;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        static_bpm_detection_parameters.bpm_center -= 10.0;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        pending.update(&params, &setter, false, start + PAUSE * 2);
+        assert_eq!(setter.calls.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut config = Config::default();
+        let params = params(&mut config);
+        let setter = FakeSetter::default();
+        let mut pending = PendingParamWrites::default();
+        let mut static_bpm_detection_parameters = config.static_bpm_detection_parameters.clone();
+        let start = Instant::now();
+
+        static_bpm_detection_parameters.bpm_range += 10;
+        apply_static(&params, &mut static_bpm_detection_parameters, &mut pending, start);
+        pending.update(&params, &setter, true, start + PAUSE / 2);
+        assert_eq!(setter.gestures(&params.static_params.bpm_range), 0);
+
+        // the slider is held still
+        pending.update(&params, &setter, true, start + PAUSE);
+        assert_eq!(setter.gestures(&params.static_params.bpm_range), 1);
+    }
+
+    #[test]
+    fn test_on_off_flip() {
+        let mut config = Config::default();
+        config.dynamic_bpm_detection_parameters.age_weight = OnOff::On(3.0);
+        let params = params(&mut config);
+        let setter = FakeSetter::default();
+        let mut pending = PendingParamWrites::default();
+        let mut dynamic_bpm_detection_parameters = config.dynamic_bpm_detection_parameters.clone();
+        let now = Instant::now();
+
+        // switched off in the middle of a drag, written without waiting for its end
+        dynamic_bpm_detection_parameters.age_weight = OnOff::Off(3.0);
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::TIME_DISTANCE,
+            &params.dynamic_params.age_weight,
+            &mut dynamic_bpm_detection_parameters,
+            &mut pending,
+            now,
+        );
+        pending.update(&params, &setter, true, now);
+        assert_eq!(setter.gestures(&params.dynamic_params.age_weight), 1);
+        assert_eq!(setter.last_value(&params.dynamic_params.age_weight), Some(0.0));
+    }
+}
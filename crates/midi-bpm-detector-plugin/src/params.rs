@@ -1,9 +1,18 @@
-use crate::config::Config;
-use gui::GUIConfig;
-use midi::{DynamicBPMDetectionParameters, NormalDistributionConfig, StaticBPMDetectionParameters};
+use crate::{
+    change_marker::ChangeMarker,
+    config::{Config, HostModulation},
+    editor_size::default_editor_state,
+    param_writes::{ParamGroup, PendingParamWrites},
+    remote_controls::RemoteControlsConfig,
+};
+use gui::{GUIConfig, UiState};
+use midi::{
+    channel_filter::MIDI_CHANNELS, DynamicBPMDetectionParameters, NormalDistributionConfig,
+    StaticBPMDetectionParameters,
+};
 use nih_plug::{
     params::{BoolParam, FloatParam, IntParam, Param, Params},
-    prelude::{FloatRange, IntRange, ParamSetter},
+    prelude::{FloatRange, IntRange, ParamPtr, RemoteControlsPage},
 };
 use nih_plug_egui::EguiState;
 use num_traits::ToPrimitive;
@@ -11,9 +20,9 @@ use parameter::{OnOff, Parameter};
 use std::{
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, RwLock,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use sync::ArcAtomicOptional;
 
@@ -49,6 +58,11 @@ pub struct DynamicParams {
     pub normal_distribution_weight: FloatParam,
     #[id = "high_tempo_bias"]
     pub high_tempo_bias: FloatParam,
+    // the threshold is kept while switched off
+    #[id = "minimum_velocity"]
+    pub minimum_velocity: FloatParam,
+    #[id = "minimum_velocity_enabled"]
+    pub minimum_velocity_enabled: BoolParam,
 }
 
 #[derive(Params)]
@@ -63,24 +77,48 @@ pub struct NormalDistributionParams {
     pub resolution: FloatParam,
 }
 
+// one per MIDI channel, the ids are suffixed with the channel number
+#[derive(Params)]
+pub struct ChannelParams {
+    #[id = "channel"]
+    pub selected: BoolParam,
+}
+
 #[derive(Params)]
 pub struct StaticParams {
     #[id = "lower_bound"]
     pub bpm_center: FloatParam,
     #[id = "upper_bound"]
     pub bpm_range: IntParam,
+    // id kept so automation and saved projects still apply
     #[id = "sample_rate"]
-    pub sample_rate: FloatParam,
+    pub histogram_resolution: FloatParam,
+    // the detection listens to the notes within, to all of them when the lowest is above the highest
+    #[id = "note_low"]
+    pub note_low: IntParam,
+    #[id = "note_high"]
+    pub note_high: IntParam,
     #[nested(group = "normal_distribution")]
     pub normal_distribution: NormalDistributionParams,
+    // the detection listens to the selected channels, to all of them when none is, see `midi::ChannelFilter`
+    #[nested(array, group = "channels")]
+    pub channels: [ChannelParams; MIDI_CHANNELS as usize],
 }
 
 #[derive(Params)]
 pub struct MidiBpmDetectorParams {
+    // saved with the project, the editor opens at the size it was left at, see `editor_size`
+    #[persist = "editor-state"]
     pub editor_state: Arc<EguiState>,
 
     #[id = "send_tempo"]
     pub send_tempo: BoolParam,
+    // notes still go through, but are not detected and no tempo is sent
+    #[id = "bypass_detection"]
+    pub bypass_detection: BoolParam,
+    // MIDI clock at the estimated tempo on the MIDI output, see `midi_clock`
+    #[id = "send_midi_clock"]
+    pub send_midi_clock: BoolParam,
 
     #[nested(group = "GUI")]
     pub gui_params: GUIParams,
@@ -91,14 +129,48 @@ pub struct MidiBpmDetectorParams {
 
     #[id = "daw_port"]
     pub daw_port: IntParam,
+
+    #[persist = "remote_controls"]
+    pub remote_controls: Arc<RwLock<RemoteControlsConfig>>,
+
+    // layout of the editor, saved with the project as it is specific to the instance
+    #[persist = "ui_state"]
+    pub ui_state: Arc<RwLock<UiState>>,
+}
+
+/// A parameter looked up by id, with its concrete type
+#[derive(Clone, Copy)]
+pub enum ParamRef<'a> {
+    Float(&'a FloatParam),
+    Int(&'a IntParam),
+    Bool(&'a BoolParam),
+}
+
+impl ParamRef<'_> {
+    pub fn as_ptr(self) -> ParamPtr {
+        match self {
+            Self::Float(param) => param.as_ptr(),
+            Self::Int(param) => param.as_ptr(),
+            Self::Bool(param) => param.as_ptr(),
+        }
+    }
+
+    pub fn add_to(self, page: &mut impl RemoteControlsPage) {
+        match self {
+            Self::Float(param) => page.add_param(param),
+            Self::Int(param) => page.add_param(param),
+            Self::Bool(param) => page.add_param(param),
+        }
+    }
 }
 
 #[allow(clippy::too_many_lines)]
 impl MidiBpmDetectorParams {
     pub fn new(
         config: &mut Config,
-        static_bpm_detection_parameters_changed_at: ArcAtomicOptional<usize>,
-        dynamic_bpm_detection_parameters_changed_at: ArcAtomicOptional<usize>,
+        static_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+        dynamic_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+        gui_parameters_changed_at: Arc<ChangeMarker>,
         current_sample: Arc<AtomicUsize>,
         daw_port: ArcAtomicOptional<u16>,
     ) -> Self {
@@ -106,34 +178,51 @@ impl MidiBpmDetectorParams {
             let static_bpm_detection_parameters_changed_at = static_bpm_detection_parameters_changed_at.clone();
             let current_sample = current_sample.clone();
             move |_: f32| {
-                static_bpm_detection_parameters_changed_at
-                    .store_if_none(Some(current_sample.load(Ordering::Relaxed)), Ordering::Relaxed);
+                static_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
             }
         });
         let static_parameters_change_u16: Arc<dyn Fn(i32) + Send + Sync> = Arc::new({
+            let static_bpm_detection_parameters_changed_at = static_bpm_detection_parameters_changed_at.clone();
             let current_sample = current_sample.clone();
             move |_: i32| {
-                static_bpm_detection_parameters_changed_at
-                    .store_if_none(Some(current_sample.load(Ordering::Relaxed)), Ordering::Relaxed);
+                static_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
+            }
+        });
+        let static_parameters_change_bool: Arc<dyn Fn(bool) + Send + Sync> = Arc::new({
+            let current_sample = current_sample.clone();
+            move |_: bool| {
+                static_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
             }
         });
         let dynamic_parameters_change_f32: Arc<dyn Fn(f32) + Send + Sync> = Arc::new({
             let dynamic_bpm_detection_parameters_changed_at = dynamic_bpm_detection_parameters_changed_at.clone();
             let current_sample = current_sample.clone();
             move |_: f32| {
-                dynamic_bpm_detection_parameters_changed_at
-                    .store_if_none(Some(current_sample.load(Ordering::Relaxed)), Ordering::Relaxed);
+                dynamic_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
             }
         });
         let dynamic_parameters_change_u8: Arc<dyn Fn(i32) + Send + Sync> = Arc::new({
+            let dynamic_bpm_detection_parameters_changed_at = dynamic_bpm_detection_parameters_changed_at.clone();
+            let current_sample = current_sample.clone();
             move |_: i32| {
-                dynamic_bpm_detection_parameters_changed_at
-                    .store_if_none(Some(current_sample.load(Ordering::Relaxed)), Ordering::Relaxed);
+                dynamic_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
+            }
+        });
+        let dynamic_parameters_change_bool: Arc<dyn Fn(bool) + Send + Sync> = Arc::new({
+            let current_sample = current_sample.clone();
+            move |_: bool| {
+                dynamic_bpm_detection_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
+            }
+        });
+        // they only change how the estimates are shown, the detection isn't evaluated again for them
+        let gui_parameters_change_f32: Arc<dyn Fn(f32) + Send + Sync> = Arc::new({
+            move |_: f32| {
+                gui_parameters_changed_at.mark(current_sample.load(Ordering::Relaxed));
             }
         });
 
         Self {
-            editor_state: EguiState::from_size(1200, 600),
+            editor_state: default_editor_state(),
             send_tempo: BoolParam::new("Send tempo", config.send_tempo.load(Ordering::Relaxed)).with_callback(
                 Arc::new({
                     let send_tempo = config.send_tempo.clone();
@@ -142,22 +231,34 @@ impl MidiBpmDetectorParams {
                     }
                 }),
             ),
+            bypass_detection: BoolParam::new("Bypass detection", config.bypass_detection.load(Ordering::Relaxed))
+                .with_callback(Arc::new({
+                    let bypass_detection = config.bypass_detection.clone();
+                    move |value| {
+                        bypass_detection.store(value, Ordering::Relaxed);
+                    }
+                })),
+            send_midi_clock: BoolParam::new("Send MIDI clock", false),
             gui_params: GUIParams {
                 interpolation_duration: GUIConfig::INTERPOLATION_DURATION
-                    .to_param(&mut config.gui_config, &dynamic_parameters_change_f32),
+                    .to_param(&mut config.gui_config, &gui_parameters_change_f32),
                 interpolation_curve: GUIConfig::INTERPOLATION_CURVE
-                    .to_param(&mut config.gui_config, &dynamic_parameters_change_f32),
+                    .to_param(&mut config.gui_config, &gui_parameters_change_f32),
             },
             static_params: StaticParams {
                 bpm_center: StaticBPMDetectionParameters::BPM_CENTER
                     .to_param(&mut config.static_bpm_detection_parameters, &static_parameters_change_f32),
                 bpm_range: StaticBPMDetectionParameters::BPM_RANGE
                     .to_param(&mut config.static_bpm_detection_parameters, &static_parameters_change_u16),
-                sample_rate: u16_range_to_logarithmic_param(
-                    &StaticBPMDetectionParameters::SAMPLE_RATE,
+                histogram_resolution: u16_range_to_logarithmic_param(
+                    &StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION,
                     &mut config.static_bpm_detection_parameters,
                     &static_parameters_change_f32,
                 ),
+                note_low: StaticBPMDetectionParameters::NOTE_LOW
+                    .to_param(&mut config.static_bpm_detection_parameters, &static_parameters_change_u16),
+                note_high: StaticBPMDetectionParameters::NOTE_HIGH
+                    .to_param(&mut config.static_bpm_detection_parameters, &static_parameters_change_u16),
                 normal_distribution: NormalDistributionParams {
                     std_dev: NormalDistributionConfig::STD_DEV.to_param(
                         &mut config.static_bpm_detection_parameters.normal_distribution,
@@ -176,6 +277,14 @@ impl MidiBpmDetectorParams {
                         &static_parameters_change_f32,
                     ),
                 },
+                channels: std::array::from_fn(|index| {
+                    let channel = index as u8;
+                    let selected = config.static_bpm_detection_parameters.channels.is_selected(channel);
+                    ChannelParams {
+                        selected: BoolParam::new(format!("Channel {}", channel + 1), selected)
+                            .with_callback(static_parameters_change_bool.clone()),
+                    }
+                }),
             },
             dynamic_params: DynamicParams {
                 beats_lookback: DynamicBPMDetectionParameters::BEATS_LOOKBACK
@@ -200,12 +309,370 @@ impl MidiBpmDetectorParams {
                     .to_param(&mut config.dynamic_bpm_detection_parameters, &dynamic_parameters_change_f32),
                 high_tempo_bias: DynamicBPMDetectionParameters::HIGH_TEMPO_BIAS
                     .to_param(&mut config.dynamic_bpm_detection_parameters, &dynamic_parameters_change_f32),
+                minimum_velocity: DynamicBPMDetectionParameters::MINIMUM_VELOCITY
+                    .to_param(&mut config.dynamic_bpm_detection_parameters, &dynamic_parameters_change_f32),
+                minimum_velocity_enabled: BoolParam::new(
+                    format!("{} enabled", DynamicBPMDetectionParameters::MINIMUM_VELOCITY.label),
+                    matches!(config.dynamic_bpm_detection_parameters.minimum_velocity, OnOff::On(_)),
+                )
+                .with_callback(dynamic_parameters_change_bool),
             },
             daw_port: IntParam::new("DAW Port", 0, IntRange::Linear { min: 0, max: 65535 }).with_callback(Arc::new(
                 move |value| {
                     daw_port.store(Some(value.to_u16().unwrap()), Ordering::Relaxed);
                 },
             )),
+            remote_controls: Arc::new(RwLock::new(config.remote_controls.clone())),
+            ui_state: Arc::new(RwLock::new(UiState::default())),
+        }
+    }
+
+    /// Ids as declared on the fields, nested groups don't prefix them
+    #[must_use]
+    pub fn param_by_id(&self, id: &str) -> Option<ParamRef<'_>> {
+        let static_params = &self.static_params;
+        let normal_distribution = &static_params.normal_distribution;
+        let dynamic_params = &self.dynamic_params;
+        Some(match id {
+            "send_tempo" => ParamRef::Bool(&self.send_tempo),
+            "bypass_detection" => ParamRef::Bool(&self.bypass_detection),
+            "send_midi_clock" => ParamRef::Bool(&self.send_midi_clock),
+            "daw_port" => ParamRef::Int(&self.daw_port),
+            "interpolation_duration" => ParamRef::Float(&self.gui_params.interpolation_duration),
+            "interpolation_curve" => ParamRef::Float(&self.gui_params.interpolation_curve),
+            "lower_bound" => ParamRef::Float(&static_params.bpm_center),
+            "upper_bound" => ParamRef::Int(&static_params.bpm_range),
+            "sample_rate" => ParamRef::Float(&static_params.histogram_resolution),
+            "note_low" => ParamRef::Int(&static_params.note_low),
+            "note_high" => ParamRef::Int(&static_params.note_high),
+            "std_dev" => ParamRef::Float(&normal_distribution.std_dev),
+            "factor" => ParamRef::Float(&normal_distribution.factor),
+            "imprecision" => ParamRef::Float(&normal_distribution.imprecision),
+            "resolution" => ParamRef::Float(&normal_distribution.resolution),
+            "beats_lookback" => ParamRef::Int(&dynamic_params.beats_lookback),
+            "velocity_current_note_weight" => ParamRef::Float(&dynamic_params.velocity_current_note_weight),
+            "velocity_note_from_weight" => ParamRef::Float(&dynamic_params.velocity_note_from_weight),
+            "age_weight" => ParamRef::Float(&dynamic_params.age_weight),
+            "octave_distance_weight" => ParamRef::Float(&dynamic_params.octave_distance_weight),
+            "pitch_distance_weight" => ParamRef::Float(&dynamic_params.pitch_distance_weight),
+            "multiplier_weight" => ParamRef::Float(&dynamic_params.multiplier_weight),
+            "subdivision_weight" => ParamRef::Float(&dynamic_params.subdivision_weight),
+            "in_beat_range_weight" => ParamRef::Float(&dynamic_params.in_beat_range_weight),
+            "normal_distribution_weight" => ParamRef::Float(&dynamic_params.normal_distribution_weight),
+            "high_tempo_bias" => ParamRef::Float(&dynamic_params.high_tempo_bias),
+            "minimum_velocity" => ParamRef::Float(&dynamic_params.minimum_velocity),
+            "minimum_velocity_enabled" => ParamRef::Bool(&dynamic_params.minimum_velocity_enabled),
+            _ => ParamRef::Bool(&static_params.channels.get(usize::from(channel_index(id)?))?.selected),
+        })
+    }
+
+    pub fn param_by_ptr(&self, param_ptr: ParamPtr) -> Option<ParamRef<'_>> {
+        self.param_map().into_iter().find(|(_, ptr, _)| *ptr == param_ptr).and_then(|(id, _, _)| self.param_by_id(&id))
+    }
+
+    /// Queues the static parameters of the detection the host doesn't have yet
+    pub fn write_static(
+        &self,
+        static_bpm_detection_parameters: &mut StaticBPMDetectionParameters,
+        pending: &mut PendingParamWrites,
+        now: Instant,
+    ) {
+        apply_float_param(
+            &StaticBPMDetectionParameters::BPM_CENTER,
+            &self.static_params.bpm_center,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_int_param(
+            &StaticBPMDetectionParameters::BPM_RANGE,
+            &self.static_params.bpm_range,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION,
+            &self.static_params.histogram_resolution,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_int_param(
+            &StaticBPMDetectionParameters::NOTE_LOW,
+            &self.static_params.note_low,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_int_param(
+            &StaticBPMDetectionParameters::NOTE_HIGH,
+            &self.static_params.note_high,
+            static_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &NormalDistributionConfig::STD_DEV,
+            &self.static_params.normal_distribution.std_dev,
+            &mut static_bpm_detection_parameters.normal_distribution,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &NormalDistributionConfig::FACTOR,
+            &self.static_params.normal_distribution.factor,
+            &mut static_bpm_detection_parameters.normal_distribution,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &NormalDistributionConfig::IMPRECISION,
+            &self.static_params.normal_distribution.imprecision,
+            &mut static_bpm_detection_parameters.normal_distribution,
+            pending,
+            now,
+        );
+        apply_float_param(
+            &NormalDistributionConfig::RESOLUTION,
+            &self.static_params.normal_distribution.resolution,
+            &mut static_bpm_detection_parameters.normal_distribution,
+            pending,
+            now,
+        );
+        for (channel, channel_params) in (0..).zip(&self.static_params.channels) {
+            let selected = static_bpm_detection_parameters.channels.is_selected(channel);
+            pending.push_immediate(&channel_params.selected, selected, now);
+        }
+    }
+
+    /// Queues the dynamic parameters of the detection the host doesn't have yet
+    pub fn write_dynamic(
+        &self,
+        dynamic_bpm_detection_parameters: &mut DynamicBPMDetectionParameters,
+        pending: &mut PendingParamWrites,
+        now: Instant,
+    ) {
+        apply_int_param(
+            &DynamicBPMDetectionParameters::BEATS_LOOKBACK,
+            &self.dynamic_params.beats_lookback,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::CURRENT_VELOCITY,
+            &self.dynamic_params.velocity_current_note_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::VELOCITY_FROM,
+            &self.dynamic_params.velocity_note_from_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::TIME_DISTANCE,
+            &self.dynamic_params.age_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::OCTAVE_DISTANCE,
+            &self.dynamic_params.octave_distance_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::PITCH_DISTANCE,
+            &self.dynamic_params.pitch_distance_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::MULTIPLIER_FACTOR,
+            &self.dynamic_params.multiplier_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::SUBDIVISION_FACTOR,
+            &self.dynamic_params.subdivision_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::IN_RANGE,
+            &self.dynamic_params.in_beat_range_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::NORMAL_DISTRIBUTION,
+            &self.dynamic_params.normal_distribution_weight,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        apply_onoff_param(
+            &DynamicBPMDetectionParameters::HIGH_TEMPO_BIAS,
+            &self.dynamic_params.high_tempo_bias,
+            dynamic_bpm_detection_parameters,
+            pending,
+            now,
+        );
+        let minimum_velocity = dynamic_bpm_detection_parameters.minimum_velocity;
+        pending.push(&self.dynamic_params.minimum_velocity, f32::from(minimum_velocity.value()), now);
+        pending.push_immediate(
+            &self.dynamic_params.minimum_velocity_enabled,
+            matches!(minimum_velocity, OnOff::On(_)),
+            now,
+        );
+    }
+
+    /// Static parameters of the detection, as `reader` reads them from the host
+    pub fn read_static(
+        &self,
+        reader: &dyn ParamReader,
+        static_bpm_detection_parameters: &mut StaticBPMDetectionParameters,
+    ) {
+        let static_params = &self.static_params;
+        let normal_distribution = &static_params.normal_distribution;
+        static_bpm_detection_parameters.bpm_center = reader.float(&static_params.bpm_center);
+        static_bpm_detection_parameters.bpm_range = reader.int(&static_params.bpm_range) as u16;
+        static_bpm_detection_parameters.histogram_resolution = reader.float(&static_params.histogram_resolution) as u16;
+        static_bpm_detection_parameters.note_low = reader.int(&static_params.note_low) as u8;
+        static_bpm_detection_parameters.note_high = reader.int(&static_params.note_high) as u8;
+        static_bpm_detection_parameters.normal_distribution.std_dev =
+            f64::from(reader.float(&normal_distribution.std_dev));
+        static_bpm_detection_parameters.normal_distribution.factor = reader.float(&normal_distribution.factor);
+        static_bpm_detection_parameters.normal_distribution.imprecision =
+            reader.float(&normal_distribution.imprecision);
+        static_bpm_detection_parameters.normal_distribution.resolution = reader.float(&normal_distribution.resolution);
+        for (channel, channel_params) in (0..).zip(&static_params.channels) {
+            static_bpm_detection_parameters.channels.select(channel, reader.bool(&channel_params.selected));
+        }
+    }
+
+    /// Dynamic parameters of the detection, as `reader` reads them from the host. The weights are read as switched on,
+    /// see `ToggleHysteresis`
+    pub fn read_dynamic(
+        &self,
+        reader: &dyn ParamReader,
+        dynamic_bpm_detection_parameters: &mut DynamicBPMDetectionParameters,
+    ) {
+        let dynamic_params = &self.dynamic_params;
+        dynamic_bpm_detection_parameters.beats_lookback = reader.int(&dynamic_params.beats_lookback) as u8;
+        dynamic_bpm_detection_parameters.velocity_current_note_weight =
+            OnOff::On(reader.float(&dynamic_params.velocity_current_note_weight));
+        dynamic_bpm_detection_parameters.velocity_note_from_weight =
+            OnOff::On(reader.float(&dynamic_params.velocity_note_from_weight));
+        dynamic_bpm_detection_parameters.age_weight = OnOff::On(reader.float(&dynamic_params.age_weight));
+        dynamic_bpm_detection_parameters.octave_distance_weight =
+            OnOff::On(reader.float(&dynamic_params.octave_distance_weight));
+        dynamic_bpm_detection_parameters.pitch_distance_weight =
+            OnOff::On(reader.float(&dynamic_params.pitch_distance_weight));
+        dynamic_bpm_detection_parameters.multiplier_weight = OnOff::On(reader.float(&dynamic_params.multiplier_weight));
+        dynamic_bpm_detection_parameters.subdivision_weight =
+            OnOff::On(reader.float(&dynamic_params.subdivision_weight));
+        dynamic_bpm_detection_parameters.in_beat_range_weight =
+            OnOff::On(reader.float(&dynamic_params.in_beat_range_weight));
+        dynamic_bpm_detection_parameters.normal_distribution_weight =
+            OnOff::On(reader.float(&dynamic_params.normal_distribution_weight));
+        dynamic_bpm_detection_parameters.high_tempo_bias = OnOff::On(reader.float(&dynamic_params.high_tempo_bias));
+        let minimum_velocity = reader.float(&dynamic_params.minimum_velocity).round() as u8;
+        dynamic_bpm_detection_parameters.minimum_velocity = if reader.bool(&dynamic_params.minimum_velocity_enabled) {
+            OnOff::On(minimum_velocity)
+        } else {
+            OnOff::Off(minimum_velocity)
+        };
+    }
+
+    /// Interpolation duration and curve of the GUI, as `reader` reads them from the host
+    #[must_use]
+    pub fn read_gui(&self, reader: &dyn ParamReader) -> (Duration, f32) {
+        (
+            Duration::from_secs_f32(reader.float(&self.gui_params.interpolation_duration)),
+            reader.float(&self.gui_params.interpolation_curve),
+        )
+    }
+
+    /// Values the host modulates the parameters to, by name, for the ones that differ from the value they were set to
+    #[must_use]
+    pub fn modulated_values(&self) -> Vec<(String, f64)> {
+        self.param_map()
+            .into_iter()
+            .filter_map(|(id, _, _)| {
+                let (name, modulated) = match self.param_by_id(&id)? {
+                    ParamRef::Float(param) => (param.name(), modulated_value(param).map(f64::from)),
+                    ParamRef::Int(param) => (param.name(), modulated_value(param).map(f64::from)),
+                    // switches have no slider to mark
+                    ParamRef::Bool(_) => return None,
+                };
+                Some((name.to_string(), modulated?))
+            })
+            .collect()
+    }
+
+    /// Group the parameter is written with, and its position among the declared parameters
+    pub fn write_order(&self, param_ptr: ParamPtr) -> (ParamGroup, usize) {
+        let group = if self.static_params.param_map().iter().any(|(_, ptr, _)| *ptr == param_ptr) {
+            ParamGroup::Static
+        } else {
+            ParamGroup::Dynamic
+        };
+        let position = self.param_map().iter().position(|(_, ptr, _)| *ptr == param_ptr).unwrap_or(usize::MAX);
+        (group, position)
+    }
+}
+
+// none when the host doesn't modulate it, compared as normalized as the parameters are modulated that way
+fn modulated_value<P: Param>(param: &P) -> Option<P::Plain> {
+    let offset = param.modulated_normalized_value() - param.unmodulated_normalized_value();
+    (offset.abs() > f32::EPSILON).then(|| param.modulated_plain_value())
+}
+
+/// Channel of a channel switch id, starting at 0
+#[must_use]
+pub fn channel_index(id: &str) -> Option<u8> {
+    id.strip_prefix("channel_")?.parse::<u8>().ok()?.checked_sub(1).filter(|channel| *channel < MIDI_CHANNELS)
+}
+
+/// Reads the values of the parameters from the host, implemented by `HostModulation`
+pub trait ParamReader {
+    fn float(&self, param: &FloatParam) -> f32;
+    fn int(&self, param: &IntParam) -> i32;
+    fn bool(&self, param: &BoolParam) -> bool;
+}
+
+impl ParamReader for HostModulation {
+    fn float(&self, param: &FloatParam) -> f32 {
+        match self {
+            Self::Follow => param.modulated_plain_value(),
+            Self::Ignore => param.unmodulated_plain_value(),
+        }
+    }
+
+    fn int(&self, param: &IntParam) -> i32 {
+        match self {
+            Self::Follow => param.modulated_plain_value(),
+            Self::Ignore => param.unmodulated_plain_value(),
+        }
+    }
+
+    fn bool(&self, param: &BoolParam) -> bool {
+        match self {
+            Self::Follow => param.modulated_plain_value(),
+            Self::Ignore => param.unmodulated_plain_value(),
         }
     }
 }
@@ -218,46 +685,57 @@ pub trait ToParam<T> {
     fn to_param(&self, config: &mut T, callback: &Arc<dyn Fn(Self::ParamType) + Send + Sync>) -> Self::Param;
 }
 
-pub fn apply_float_param<T, V>(parameter: &Parameter<T, V>, param: &FloatParam, config: &mut T, setter: &ParamSetter)
-where
+pub fn apply_float_param<T, V>(
+    parameter: &Parameter<T, V>,
+    param: &FloatParam,
+    config: &mut T,
+    pending: &mut PendingParamWrites,
+    now: Instant,
+) where
     V: 'static + ToPrimitive + Copy,
 {
-    setter.begin_set_parameter(param);
-    setter.set_parameter(param, (parameter.get_mut)(config).to_f32().unwrap());
-    setter.end_set_parameter(param);
+    pending.push(param, (parameter.get_mut)(config).to_f32().unwrap(), now);
 }
 
 pub fn apply_onoff_param<T, V>(
     parameter: &Parameter<T, OnOff<V>>,
     param: &FloatParam,
     config: &mut T,
-    setter: &ParamSetter,
+    pending: &mut PendingParamWrites,
+    now: Instant,
 ) where
     V: 'static + ToPrimitive + Copy + num_traits::One + num_traits::Zero + std::ops::Mul<Output = V>,
 {
-    setter.begin_set_parameter(param);
-    setter.set_parameter(param, (parameter.get_mut)(config).weight().to_f32().unwrap());
-    setter.end_set_parameter(param);
+    let value = (parameter.get_mut)(config);
+    let weight = value.weight().to_f32().unwrap();
+    // the host only knows the weight, which is zero when switched off
+    if matches!(value, OnOff::Off(_)) == (param.unmodulated_normalized_value() <= 0.0) {
+        pending.push(param, weight, now);
+    } else {
+        pending.push_immediate(param, weight, now);
+    }
 }
 
-pub fn apply_int_param<T, V>(parameter: &Parameter<T, V>, param: &IntParam, config: &mut T, setter: &ParamSetter)
-where
+pub fn apply_int_param<T, V>(
+    parameter: &Parameter<T, V>,
+    param: &IntParam,
+    config: &mut T,
+    pending: &mut PendingParamWrites,
+    now: Instant,
+) where
     V: 'static + ToPrimitive + Copy,
 {
-    setter.begin_set_parameter(param);
-    setter.set_parameter(param, (parameter.get_mut)(config).to_i32().unwrap());
-    setter.end_set_parameter(param);
+    pending.push(param, (parameter.get_mut)(config).to_i32().unwrap(), now);
 }
 
 pub fn apply_duration_param<T>(
     parameter: &Parameter<T, Duration>,
     param: &FloatParam,
     config: &mut T,
-    setter: &ParamSetter,
+    pending: &mut PendingParamWrites,
+    now: Instant,
 ) {
-    setter.begin_set_parameter(param);
-    setter.set_parameter(param, (parameter.get_mut)(config).as_secs_f32());
-    setter.end_set_parameter(param);
+    pending.push(param, (parameter.get_mut)(config).as_secs_f32(), now);
 }
 
 macro_rules! impl_to_param_for_float {
@@ -362,6 +840,28 @@ impl<T> ToParam<T> for Parameter<T, OnOff<f32>> {
     }
 }
 
+// the value, whether it is switched on is a switch of its own
+impl<T> ToParam<T> for Parameter<T, OnOff<u8>> {
+    type Param = FloatParam;
+    type ParamType = f32;
+    type Type = f32;
+
+    fn to_param(&self, config: &mut T, callback: &Arc<dyn Fn(Self::ParamType) + Send + Sync>) -> Self::Param {
+        let mut param = FloatParam::new(
+            self.label,
+            f32::from((self.get_mut)(config).value()),
+            FloatRange::Linear { min: *self.range.start() as f32, max: *self.range.end() as f32 },
+        )
+        .with_callback(callback.clone())
+        .with_step_size(self.step.max(1.0) as f32)
+        .with_value_to_string(Arc::new(|value| format!("{value:.0}")));
+        if let Some(unit) = self.unit {
+            param = param.with_unit(unit);
+        }
+        param
+    }
+}
+
 impl_to_param_for_float!(f32);
 impl_to_param_for_float!(f64);
 
@@ -388,3 +888,77 @@ pub fn u16_range_to_logarithmic_param<T>(
     }
     param
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use gui::parameter_catalog;
+
+    // plugin settings, not parameters of the detection
+    const PLUGIN_ONLY: [&str; 4] = ["send_tempo", "bypass_detection", "send_midi_clock", "daw_port"];
+    // switch of a parameter of the catalog, described along with its value
+    const ENABLED_SWITCHES: [&str; 1] = ["minimum_velocity_enabled"];
+
+    #[test]
+    fn test_catalog_matches_params() {
+        let params = MidiBpmDetectorParams::new(
+            &mut Config::default(),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::default(),
+            ArcAtomicOptional::new(None),
+        );
+        let catalog = parameter_catalog();
+        for (id, _, _) in params.param_map() {
+            // the channel switches select a set of channels, they are not values with a range
+            if PLUGIN_ONLY.contains(&id.as_str())
+                || ENABLED_SWITCHES.contains(&id.as_str())
+                || channel_index(&id).is_some()
+            {
+                continue;
+            }
+            let descriptor = catalog
+                .iter()
+                .find(|descriptor| descriptor.plugin_id == Some(id.as_str()))
+                .unwrap_or_else(|| panic!("{id} is not in the catalog"));
+            let (min, max) = match params.param_by_id(&id).unwrap() {
+                ParamRef::Float(param) => (f64::from(param.preview_plain(0.0)), f64::from(param.preview_plain(1.0))),
+                ParamRef::Int(param) => (f64::from(param.preview_plain(0.0)), f64::from(param.preview_plain(1.0))),
+                ParamRef::Bool(_) => panic!("{id} is a switch"),
+            };
+            assert!((min - descriptor.min).abs() < 1e-6, "{id}: {min} != {}", descriptor.min);
+            assert!((max - descriptor.max).abs() < 1e-6, "{id}: {max} != {}", descriptor.max);
+        }
+        // and the other way around, the catalog doesn't name a parameter the plugin doesn't have
+        for plugin_id in catalog.iter().filter_map(|descriptor| descriptor.plugin_id) {
+            assert!(params.param_by_id(plugin_id).is_some(), "{plugin_id} is not a plugin parameter");
+        }
+    }
+
+    #[test]
+    fn test_channel_switches() {
+        let mut config = Config::default();
+        config.static_bpm_detection_parameters.channels.select(9, true);
+        let params = MidiBpmDetectorParams::new(
+            &mut config,
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::default(),
+            ArcAtomicOptional::new(None),
+        );
+        let ids = params.param_map().into_iter().map(|(id, _, _)| id).collect::<Vec<_>>();
+        for channel in 1..=MIDI_CHANNELS {
+            let id = format!("channel_{channel}");
+            assert!(ids.contains(&id), "{id} is not a plugin parameter");
+            let Some(ParamRef::Bool(param)) = params.param_by_id(&id) else {
+                panic!("{id} is not a switch");
+            };
+            assert_eq!(param.unmodulated_plain_value(), channel == 10, "{id}");
+        }
+        assert!(params.param_by_id("channel_0").is_none());
+        assert!(params.param_by_id("channel_17").is_none());
+    }
+}
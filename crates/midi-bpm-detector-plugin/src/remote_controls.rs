@@ -0,0 +1,231 @@
+use errors::{error, info};
+use serde::{Deserialize, Serialize};
+
+// a CLAP remote controls page maps to the eight knobs of a controller
+pub const PARAMS_PER_PAGE: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteControlsPageConfig {
+    // consecutive pages of the same section are grouped together
+    pub section: String,
+    pub name: String,
+    // parameter ids, in knob order
+    pub params: Vec<String>,
+}
+
+impl RemoteControlsPageConfig {
+    fn new(section: &str, name: &str, params: &[&str]) -> Self {
+        Self {
+            section: section.to_string(),
+            name: name.to_string(),
+            params: params.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Pages offered to CLAP hosts for hardware controllers. Persisted with the plugin state, changes are picked up when
+/// the plugin is reloaded
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteControlsConfig {
+    pub pages: Vec<RemoteControlsPageConfig>,
+}
+
+impl Default for RemoteControlsConfig {
+    fn default() -> Self {
+        Self {
+            pages: vec![
+                RemoteControlsPageConfig::new(
+                    "Send tempo",
+                    "Send tempo",
+                    &["send_tempo", "bypass_detection", "send_midi_clock"],
+                ),
+                RemoteControlsPageConfig::new(
+                    "Static parameters",
+                    "Range and resolution",
+                    &["lower_bound", "upper_bound", "sample_rate", "note_low", "note_high"],
+                ),
+                RemoteControlsPageConfig::new(
+                    "Static parameters",
+                    "Normal distribution",
+                    &["std_dev", "factor", "imprecision", "resolution"],
+                ),
+                RemoteControlsPageConfig::new(
+                    "Dynamic parameters",
+                    "Dynamic parameters",
+                    &[
+                        "beats_lookback",
+                        "velocity_current_note_weight",
+                        "velocity_note_from_weight",
+                        "age_weight",
+                        "octave_distance_weight",
+                        "pitch_distance_weight",
+                        "multiplier_weight",
+                        "subdivision_weight",
+                        "normal_distribution_weight",
+                        "high_tempo_bias",
+                    ],
+                ),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page {
+    pub name: String,
+    pub params: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub pages: Vec<Page>,
+}
+
+/// Resolves the configured pages against the parameters of the plugin. Unknown ids are logged and skipped, pages left
+/// empty are dropped, and pages longer than `PARAMS_PER_PAGE` continue on numbered pages
+#[must_use]
+pub fn layout(remote_controls_config: &RemoteControlsConfig, is_known: impl Fn(&str) -> bool) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for page_config in &remote_controls_config.pages {
+        let params = page_config
+            .params
+            .iter()
+            .filter(|id| {
+                let known = is_known(id);
+                if !known {
+                    error!("unknown parameter '{id}' in remote controls page '{}', skipping", page_config.name);
+                }
+                known
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if params.len() > PARAMS_PER_PAGE {
+            info!(
+                "remote controls page '{}' has {} parameters, continuing on more pages of {PARAMS_PER_PAGE}",
+                page_config.name,
+                params.len()
+            );
+        }
+
+        let pages = params.chunks(PARAMS_PER_PAGE).enumerate().map(|(n, params)| Page {
+            name: if n == 0 { page_config.name.clone() } else { format!("{} {}", page_config.name, n + 1) },
+            params: params.to_vec(),
+        });
+        match sections.last_mut() {
+            Some(section) if section.name == page_config.section => section.pages.extend(pages),
+            _ => sections.push(Section { name: page_config.section.clone(), pages: pages.collect() }),
+        }
+    }
+    sections.retain(|section| !section.pages.is_empty());
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{change_marker::ChangeMarker, config::Config, params::MidiBpmDetectorParams};
+    use nih_plug::params::Params;
+    use std::sync::Arc;
+    use sync::ArcAtomicOptional;
+
+    fn page(name: &str, params: &[&str]) -> Page {
+        Page { name: name.to_string(), params: params.iter().map(ToString::to_string).collect() }
+    }
+
+    #[test]
+    fn test_default_layout() {
+        let sections = layout(&RemoteControlsConfig::default(), |_| true);
+        assert_eq!(
+            sections.iter().map(|section| section.name.as_str()).collect::<Vec<_>>(),
+            vec!["Send tempo", "Static parameters", "Dynamic parameters"]
+        );
+        assert_eq!(sections[1].pages.len(), 2);
+        // ten dynamic parameters
+        assert_eq!(
+            sections[2].pages.iter().map(|page| page.name.as_str()).collect::<Vec<_>>(),
+            vec!["Dynamic parameters", "Dynamic parameters 2"]
+        );
+        assert_eq!(sections[2].pages[0].params.len(), PARAMS_PER_PAGE);
+        assert_eq!(sections[2].pages[1].params, vec!["normal_distribution_weight", "high_tempo_bias"]);
+    }
+
+    #[test]
+    fn test_unknown_ids() {
+        let remote_controls_config = RemoteControlsConfig {
+            pages: vec![
+                RemoteControlsPageConfig::new("Main", "Main", &["send_tempo", "typo", "lower_bound"]),
+                RemoteControlsPageConfig::new("Main", "Gone", &["removed"]),
+                RemoteControlsPageConfig::new("Other", "Gone", &["removed"]),
+            ],
+        };
+        let sections = layout(&remote_controls_config, |id| id != "typo" && id != "removed");
+        assert_eq!(
+            sections,
+            vec![Section { name: "Main".to_string(), pages: vec![page("Main", &["send_tempo", "lower_bound"])] }]
+        );
+    }
+
+    #[test]
+    fn test_page_limit() {
+        let ids = (0..=PARAMS_PER_PAGE * 2).map(|n| format!("param_{n}")).collect::<Vec<_>>();
+        let remote_controls_config = RemoteControlsConfig {
+            pages: vec![
+                RemoteControlsPageConfig::new("Main", "First", &["send_tempo", "lower_bound"]),
+                RemoteControlsPageConfig { section: "Main".to_string(), name: "Long".to_string(), params: ids.clone() },
+            ],
+        };
+        let sections = layout(&remote_controls_config, |_| true);
+        assert_eq!(sections.len(), 1);
+        let pages = &sections[0].pages;
+        assert_eq!(
+            pages.iter().map(|page| page.name.as_str()).collect::<Vec<_>>(),
+            vec!["First", "Long", "Long 2", "Long 3"]
+        );
+        assert!(pages.iter().all(|page| page.params.len() <= PARAMS_PER_PAGE));
+        // order is kept across the continuation pages
+        assert_eq!(pages[1..].iter().flat_map(|page| page.params.clone()).collect::<Vec<_>>(), ids);
+    }
+
+    #[test]
+    fn test_param_ids_resolve() {
+        let params = MidiBpmDetectorParams::new(
+            &mut Config::default(),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::new(ChangeMarker::pending()),
+            Arc::default(),
+            ArcAtomicOptional::new(None),
+        );
+        for (id, _, _) in params.param_map() {
+            assert!(params.param_by_id(&id).is_some(), "{id} can't be used in remote controls");
+        }
+        for page in RemoteControlsConfig::default().pages {
+            assert!(page.params.iter().all(|id| params.param_by_id(id).is_some()));
+        }
+        assert!(params.param_by_id("typo").is_none());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let remote_controls_config: RemoteControlsConfig = toml::from_str(
+            r#"
+            [[pages]]
+            section = "Live"
+            name = "Live"
+            params = ["send_tempo", "lower_bound"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            remote_controls_config,
+            RemoteControlsConfig {
+                pages: vec![RemoteControlsPageConfig::new("Live", "Live", &["send_tempo", "lower_bound"])]
+            }
+        );
+        let remote_controls_config: RemoteControlsConfig = toml::from_str("").unwrap();
+        assert_eq!(remote_controls_config, RemoteControlsConfig::default());
+    }
+}
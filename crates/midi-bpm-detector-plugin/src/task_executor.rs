@@ -1,25 +1,44 @@
-use crate::{config::Config, MidiBpmDetectorParams};
+use crate::{
+    change_marker::ChangeMarker,
+    config::Config,
+    editor_activity::EditorActivity,
+    evaluation_scheduler::{DeferredEvaluation, EvaluationScheduler},
+    init_markers::InitMarker,
+    param_writes::{HostParamWriter, PendingParamWrites},
+    params::ParamReader,
+    watchdog::{Heartbeat, TaskKind},
+    MidiBpmDetectorParams,
+};
+use atomic_float::AtomicF32;
 use crossbeam::atomic::AtomicCell;
-use errors::{error, info, LogErrorWithExt};
-use gui::GuiRemote;
+use errors::{error, info, report_caught_panic, LogErrorWithExt};
+use gui::{
+    effective_config::{snapshot, DetectionParameters, Origin},
+    GuiRemote,
+};
 use midi::{
-    bpm_detection_receiver::BPMDetectionReceiver, BPMDetection, DynamicBPMDetectionParameters, TimedMidiNoteOn,
+    bpm_detection_receiver::BPMDetectionReceiver, tempo_source::output_tempo, validate_interaction, AutoZoom,
+    BPMDetection, BeatCounter, ChordFilter, ClockAnchor, DawMessage, DynamicBPMDetectionParameters, Egress, EgressHub,
+    FeelAmbiguityConfig, ParameterRamp, PhaseSplit, RemoteControlServer, RemoteMessage, SharedTempoSource,
+    StaticBPMDetectionParameters, TempoChangeDetector, TempoLatency, TimeSignature, TimedMidiNoteOn, VelocityGate,
 };
 use nih_plug::params::Param;
 use nih_plug_egui::egui::mutex::RwLock;
-use parameter::OnOff;
 use ringbuf::{
     ring_buffer::{RbReadCache, RbWrap},
     Consumer, SharedRb,
 };
+use smallvec::SmallVec;
 use std::{
-    io::Write,
-    mem::MaybeUninit,
-    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
-    sync::{atomic::Ordering, Arc},
-    time::Duration,
+    mem::{self, MaybeUninit},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
-use sync::{ArcAtomicBool, ArcAtomicOptional};
+use sync::{ArcAtomicBool, ArcAtomicOptional, Mutex};
 
 #[derive(Eq, PartialEq)]
 pub enum UpdateOrigin {
@@ -31,198 +50,1069 @@ pub enum Task {
     ProcessNotes(bool),
     StaticBPMDetectionParameters(UpdateOrigin),
     DynamicBPMDetectionParameters(UpdateOrigin),
+    // only read from the host, the GUI writes the configuration itself. The detection is left alone
+    GUIParameters,
+}
+
+impl From<&Task> for TaskKind {
+    fn from(task: &Task) -> Self {
+        match task {
+            Task::ProcessNotes(_) => Self::ProcessNotes,
+            Task::StaticBPMDetectionParameters(_) => Self::StaticBPMDetectionParameters,
+            Task::DynamicBPMDetectionParameters(_) => Self::DynamicBPMDetectionParameters,
+            Task::GUIParameters => Self::GUIParameters,
+        }
+    }
 }
 
+/// Notes of a buffer pushed as a single event. A fuller buffer takes several events, the batch never spills onto the
+/// heap on the audio thread
+pub const NOTE_BATCH: usize = 4;
+pub type NoteBatch = SmallVec<[TimedMidiNoteOn; NOTE_BATCH]>;
+
 pub enum Event {
-    TimedMidiNoteOn(TimedMidiNoteOn),
+    // in order, none of them is released by a note off pushed after them
+    Notes(NoteBatch),
+    NoteOff { channel: u8, note: u8 },
     DawBPM(f32),
+    TimeSignature(TimeSignature),
 }
 
 pub struct TaskExecutor {
     pub bpm_detection: BPMDetection,
+    pub chord_filter: ChordFilter,
     pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    pub parameter_ramp: ParameterRamp,
+    pub auto_zoom: AutoZoom,
     pub gui_remote: Option<GuiRemote>,
     pub params: Arc<MidiBpmDetectorParams>,
+    pub param_reader: Box<dyn ParamReader + Send>,
     pub gui_remote_receiver: Arc<AtomicCell<Option<GuiRemote>>>,
+    pub editor_activity: Arc<EditorActivity>,
     pub events_receiver: Consumer<Event, RbWrap<RbReadCache<Event, Arc<SharedRb<Event, [MaybeUninit<Event>; 1000]>>>>>,
     pub config: Arc<RwLock<Config>>,
     // when gui_must_update_config is set, GUI loads up this config
     pub gui_must_update_config: ArcAtomicBool,
     pub daw_port: ArcAtomicOptional<u16>,
-    pub daw_connection: Option<TcpStream>,
+    pub egress_hub: EgressHub,
     pub send_tempo: ArcAtomicBool,
+    pub tempo_source: SharedTempoSource,
+    pub min_tempo_confidence: f32,
+    pub feel_ambiguity: FeelAmbiguityConfig,
+    pub daw_bpm: Option<f32>,
+    pub heartbeat: Arc<Heartbeat>,
+    pub evaluation_scheduler: EvaluationScheduler,
+    pub deferred_evaluation: Arc<DeferredEvaluation>,
+    // the notes received are to be evaluated on the next task, the scheduler postponed them
+    pub evaluation_deferred: bool,
+    pub clock_anchor: Arc<AtomicCell<Option<ClockAnchor>>>,
+    pub tempo_latency: TempoLatency,
+    pub bypass_detection: ArcAtomicBool,
+    // bypass state of the previous task, the detection evaluates again when it resumes
+    pub bypassed: bool,
+    pub beat_counter: BeatCounter,
+    pub velocity_gate: VelocityGate,
+    pub tempo_change: TempoChangeDetector,
+    // cleared by the first evaluation, see `init_markers`
+    pub init_marker: Arc<AtomicCell<Option<InitMarker>>>,
+    pub safe_mode: ArcAtomicBool,
+    pub toggle_hysteresis: ToggleHysteresis,
+    // set again to read the parameters of the host once the held switches settled
+    pub dynamic_bpm_detection_parameters_changed_at: Arc<ChangeMarker>,
+    pub current_sample: Arc<AtomicUsize>,
+    // parameters of the detection once applied, written in the crash report
+    pub detection_parameters: Arc<Mutex<DetectionParameters>>,
+    pub remote_control_server: Option<RemoteControlServer>,
+    pub remote_control_port: Option<u16>,
+    // set when parameters received from the TUI could not be written to the host yet, the editor writes them
+    pub remote_parameters_changed: ArcAtomicBool,
+    pub host_param_writer: HostParamWriter,
+    // tempo of the MIDI clock sent by the plugin, NaN stops it
+    pub midi_clock_bpm: Arc<AtomicF32>,
 }
 
 impl TaskExecutor {
-    #[allow(clippy::too_many_lines)]
     pub fn execute(&mut self, task: Task) {
+        let task_kind = TaskKind::from(&task);
+        self.heartbeat.task_started(task_kind);
+        // neither the host nor the next tasks go down with this one
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.execute_task(task))) {
+            error!("{task_kind:?} task panicked");
+            report_caught_panic(payload.as_ref());
+        }
+        if task_kind != TaskKind::ProcessNotes {
+            self.parameters_applied();
+        }
+        self.heartbeat.task_finished();
+    }
+
+    fn parameters_applied(&self) {
+        *self.detection_parameters.lock() = DetectionParameters {
+            static_bpm_detection_parameters: self.auto_zoom.configured().clone(),
+            dynamic_bpm_detection_parameters: self.dynamic_bpm_detection_parameters.clone(),
+        };
+        if let Some(remote_control_server) = &self.remote_control_server {
+            remote_control_server.publish(&RemoteMessage::StaticParameters(self.auto_zoom.configured().clone()));
+            remote_control_server
+                .publish(&RemoteMessage::DynamicParameters(self.dynamic_bpm_detection_parameters.clone()));
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn execute_task(&mut self, task: Task) {
         if let Some(daw_port) = self.daw_port.take(Ordering::Relaxed) {
-            self.daw_connection = TcpStream::connect_timeout(
-                &SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), daw_port),
-                Duration::from_millis(10),
-            )
-            .log_error_msg("could not connect to daw, ignoring")
-            .ok();
+            self.egress_hub.publish(Egress::DawPort(daw_port));
         }
+        let remote_parameters_applied = self.serve_remote_control();
+        let task = match task {
+            Task::ProcessNotes(force_evaluate_bpm_detection) => {
+                Task::ProcessNotes(force_evaluate_bpm_detection || remote_parameters_applied)
+            }
+            task => task,
+        };
 
         match task {
             Task::ProcessNotes(force_evaluate_bpm_detection) => {
-                let mut evaluate_bpm_detection = force_evaluate_bpm_detection;
-                if !self.params.editor_state.is_open() {
+                if !self.editor_activity.is_active(Instant::now()) {
                     self.gui_remote = None;
                 }
                 if let Some(new_gui_remote) = self.gui_remote_receiver.take() {
                     self.gui_remote = Some(new_gui_remote);
+                    self.report_config_warnings();
+                }
+                if self.bypass_detection.load(Ordering::Relaxed) {
+                    self.bypass();
+                    return;
                 }
+                let force_evaluate_bpm_detection = mem::take(&mut self.bypassed) || force_evaluate_bpm_detection;
+                let mut evaluate_bpm_detection =
+                    mem::take(&mut self.evaluation_deferred) || force_evaluate_bpm_detection;
+                let mut consumed_events = 0;
                 for event in self.events_receiver.pop_iter() {
+                    consumed_events += 1;
                     match event {
-                        Event::TimedMidiNoteOn(timed_midi_note_on) => {
-                            evaluate_bpm_detection = true;
-                            self.bpm_detection.receive_midi_message(timed_midi_note_on);
+                        Event::Notes(notes) => {
+                            let mut onsets = NoteBatch::new();
+                            for timed_midi_note_on in notes {
+                                if let Some(gui_remote) = &self.gui_remote {
+                                    gui_remote.push_note(&timed_midi_note_on);
+                                }
+                                self.chord_filter.note_on(
+                                    self.dynamic_bpm_detection_parameters.max_simultaneous_onsets,
+                                    timed_midi_note_on,
+                                    Instant::now(),
+                                    |onset| {
+                                        let auto_velocity_gate =
+                                            self.dynamic_bpm_detection_parameters.auto_velocity_gate;
+                                        if self.velocity_gate.admit(onset.midi_message.velocity, auto_velocity_gate) {
+                                            onsets.push(onset);
+                                        }
+                                    },
+                                );
+                            }
+                            for onset in &onsets {
+                                self.beat_counter.note(onset.timestamp);
+                            }
+                            evaluate_bpm_detection |= self.bpm_detection.receive_batch(&onsets) > 0;
                         }
+                        Event::NoteOff { channel, note } => self.chord_filter.note_off(channel, note),
                         Event::DawBPM(bpm) => {
+                            self.daw_bpm = Some(bpm);
                             if let Some(gui_remote) = &self.gui_remote {
                                 gui_remote.receive_daw_bpm(bpm);
                             }
                         }
+                        Event::TimeSignature(time_signature) => self.beat_counter.set_time_signature(time_signature),
                     }
                 }
                 self.events_receiver.sync();
+                self.heartbeat.events_consumed(consumed_events);
+                self.chord_filter.flush(
+                    self.dynamic_bpm_detection_parameters.max_simultaneous_onsets,
+                    Instant::now(),
+                    |onset| {
+                        let auto_velocity_gate = self.dynamic_bpm_detection_parameters.auto_velocity_gate;
+                        if !self.velocity_gate.admit(onset.midi_message.velocity, auto_velocity_gate) {
+                            return;
+                        }
+                        evaluate_bpm_detection = true;
+                        self.beat_counter.note(onset.timestamp);
+                        self.bpm_detection.receive_midi_message(onset);
+                    },
+                );
+                // a task is requested again once the chord is complete
+                let mut next_task_in = self.chord_filter.flush_in(Instant::now());
                 if evaluate_bpm_detection {
-                    let bpm_detection_result = self.bpm_detection.compute_bpm(&self.dynamic_bpm_detection_parameters);
-
-                    if let (Some((_, bpm)), true) = (bpm_detection_result, self.send_tempo.load(Ordering::Relaxed)) {
-                        if let Some(daw_connection) = &mut self.daw_connection {
-                            let mut buffer = [0u8; 8];
-                            buffer[..4].copy_from_slice(&4u32.to_be_bytes());
-                            buffer[4..].copy_from_slice(&bpm.to_be_bytes());
-
-                            let must_close = match daw_connection.write(&buffer) {
-                                Ok(sent) => {
-                                    if sent == 8 {
-                                        info!("sent RPM");
-                                        false
-                                    } else {
-                                        error!("only {sent} bytes could be sent, closing daw connection");
-                                        true
-                                    }
-                                }
-                                Err(err) => {
-                                    error!("error while sending to daw {err:?}, closing");
-                                    true
-                                }
-                            };
-                            if must_close {
-                                self.daw_connection = None;
-                            }
-                        };
+                    if let Some(delay) =
+                        self.evaluation_scheduler.evaluation_delay(Instant::now(), force_evaluate_bpm_detection)
+                    {
+                        // staggered with other instances, notes are kept and evaluated on a later task
+                        self.evaluation_deferred = true;
+                        evaluate_bpm_detection = false;
+                        next_task_in = Some(next_task_in.map_or(delay, |next_task_in| next_task_in.min(delay)));
+                    }
+                }
+                match next_task_in {
+                    Some(delay) => self.deferred_evaluation.defer(self.current_sample.load(Ordering::Relaxed), delay),
+                    None => self.deferred_evaluation.cancel(),
+                }
+                if evaluate_bpm_detection {
+                    let newest_note = self.bpm_detection.newest_note_timestamp();
+                    let effective_parameters = self.parameter_ramp.apply(
+                        &self.dynamic_bpm_detection_parameters,
+                        newest_note.unwrap_or_else(chrono::Duration::zero),
+                    );
+                    let phase_split_window =
+                        self.gui_remote.as_ref().and_then(BPMDetectionReceiver::phase_split_window);
+                    self.bpm_detection.split_by_phase(
+                        phase_split_window
+                            .zip(self.beat_counter.phase())
+                            .map(|(window, beat_phase)| PhaseSplit { beat_phase, window }),
+                    );
+                    let estimate = self.bpm_detection.compute_bpm(effective_parameters).map(|(_, estimate)| estimate);
+                    let bpm = estimate.map(|estimate| estimate.bpm);
+                    if let (Some(bpm), Some(newest_note)) = (bpm, newest_note) {
+                        self.beat_counter.tempo(bpm, newest_note);
+                        self.tempo_change.estimate(newest_note, bpm);
+                    }
+                    if bpm.is_some() {
+                        if let Some(init_marker) = self.init_marker.take() {
+                            init_marker.clear();
+                        }
                     }
 
-                    if self.params.editor_state.is_open() {
-                        if let Some(gui_remote) = &mut self.gui_remote {
-                            if let Some((histogram_data_points, bpm)) = bpm_detection_result {
-                                gui_remote.receive_bpm_histogram_data(histogram_data_points, bpm);
-                            } else {
-                                // happens when we still have no data but still have to see parameter changes
-                                gui_remote.request_repaint();
+                    let confident_estimate =
+                        estimate.filter(|estimate| estimate.confidence >= self.min_tempo_confidence);
+                    if let Some(estimate) = confident_estimate {
+                        self.midi_clock_bpm.store(estimate.bpm, Ordering::Relaxed);
+                    }
+                    if let (Some(estimate), true) = (confident_estimate, self.send_tempo.load(Ordering::Relaxed)) {
+                        let tempo_source = self.tempo_source.load(Ordering::Relaxed);
+                        let bpm = output_tempo(tempo_source, estimate.bpm, self.daw_bpm, estimate.confidence);
+                        self.egress_hub.publish(Egress::Daw(DawMessage::Tempo(bpm)));
+                        // while the connection takes the tempos, this one is on its way. Tempos that are not
+                        // delivered have no latency, they are left out of the statistics
+                        if self.egress_hub.tempo_delivered() {
+                            if let (Some(clock_anchor), Some(newest_note)) = (self.clock_anchor.load(), newest_note) {
+                                self.tempo_latency.record_delivery(&clock_anchor, newest_note, Instant::now());
                             }
                         }
                     }
+
+                    if let Some(gui_remote) = &mut self.gui_remote {
+                        gui_remote.receive_bar_position(self.beat_counter.position());
+                        gui_remote.receive_beat_phase(self.beat_counter.phase());
+                        gui_remote.receive_velocity_gate(self.velocity_gate.threshold());
+                        if let Some(estimate) = estimate {
+                            let bpm = estimate.bpm;
+                            gui_remote.receive_auto_zoom(self.auto_zoom.zoomed());
+                            gui_remote.receive_phase_split_histogram(self.bpm_detection.phase_split_histogram());
+                            gui_remote.receive_resolution_floor(self.bpm_detection.resolution_floor());
+                            let beats_lookback = self.dynamic_bpm_detection_parameters.beats_lookback;
+                            gui_remote.receive_note_freshness(self.tempo_change.change_point().and_then(
+                                |change_point| self.bpm_detection.recent_share(change_point, bpm, beats_lookback),
+                            ));
+                            gui_remote
+                                .receive_feel_ambiguity(self.bpm_detection.feel_ambiguity(bpm, &self.feel_ambiguity));
+                            gui_remote.receive_bpm_histogram_data(self.bpm_detection.histogram_data_points(), estimate);
+                        } else {
+                            // happens when we still have no data but still have to see parameter changes
+                            gui_remote.request_repaint();
+                        }
+                    }
+                    if let (Some(estimate), Some(remote_control_server)) = (estimate, &self.remote_control_server) {
+                        remote_control_server.publish(&RemoteMessage::Estimate {
+                            bpm: estimate.bpm,
+                            confidence: estimate.confidence,
+                            histogram_data_points: self.bpm_detection.histogram_data_points().to_vec(),
+                        });
+                    }
+
+                    if let (Some(bpm), Some(newest_note)) = (bpm, newest_note) {
+                        let note_density =
+                            self.bpm_detection.note_density(bpm, self.dynamic_bpm_detection_parameters.beats_lookback);
+                        let auto_zoom = self.dynamic_bpm_detection_parameters.auto_zoom;
+                        if let Some(effective) = self.auto_zoom.update(auto_zoom, bpm, note_density, newest_note) {
+                            self.bpm_detection.update_static_parameters(effective);
+                        }
+                    }
                 }
             }
 
             Task::StaticBPMDetectionParameters(origin) => {
                 match origin {
+                    UpdateOrigin::Daw if self.safe_mode.load(Ordering::Relaxed) => {
+                        info!("safe mode, the parameters of the host are not applied");
+                    }
                     UpdateOrigin::Daw => {
                         let config = {
                             let mut config = self.config.write();
-                            config.static_bpm_detection_parameters.bpm_center =
-                                self.params.static_params.bpm_center.unmodulated_plain_value();
-                            config.static_bpm_detection_parameters.bpm_range =
-                                self.params.static_params.bpm_range.unmodulated_plain_value() as u16;
-                            config.static_bpm_detection_parameters.sample_rate =
-                                self.params.static_params.sample_rate.unmodulated_plain_value() as u16;
-
-                            config.static_bpm_detection_parameters.normal_distribution.std_dev = f64::from(
-                                self.params.static_params.normal_distribution.std_dev.unmodulated_plain_value(),
-                            );
-                            config.static_bpm_detection_parameters.normal_distribution.factor =
-                                self.params.static_params.normal_distribution.factor.unmodulated_plain_value();
-                            config.static_bpm_detection_parameters.normal_distribution.imprecision =
-                                self.params.static_params.normal_distribution.imprecision.unmodulated_plain_value();
-                            config.static_bpm_detection_parameters.normal_distribution.resolution =
-                                self.params.static_params.normal_distribution.resolution.unmodulated_plain_value();
-
+                            let before = snapshot(&*config);
+                            self.params.read_static(&*self.param_reader, &mut config.static_bpm_detection_parameters);
+                            config.provenance.record_change(Origin::Daw, &before, &*config);
                             config.static_bpm_detection_parameters.clone()
                         };
                         self.gui_must_update_config.store(true, Ordering::Relaxed);
+                        self.auto_zoom.set_configured(&config);
                         self.bpm_detection.update_static_parameters(config);
                         self.execute(Task::ProcessNotes(true));
                     }
                     UpdateOrigin::Gui => {
                         let config = self.config.read();
                         let static_bpm_detection_parameters = &config.static_bpm_detection_parameters;
+                        self.auto_zoom.set_configured(static_bpm_detection_parameters);
                         self.bpm_detection.update_static_parameters(static_bpm_detection_parameters.clone());
                         // TODO GUI has a delay + bpm recompute mechanism on its side, but when it's daw,
                         // note receiver delays but recompute happens here, which is hard to follow
                     }
                 };
+                self.report_config_warnings();
             }
             Task::DynamicBPMDetectionParameters(origin) => {
                 match origin {
+                    UpdateOrigin::Daw if self.safe_mode.load(Ordering::Relaxed) => {
+                        info!("safe mode, the parameters of the host are not applied");
+                    }
                     UpdateOrigin::Daw => {
-                        {
+                        let from_host = {
                             let mut config = self.config.write();
-
-                            config.gui_config.interpolation_duration = Duration::from_secs_f32(
-                                self.params.gui_params.interpolation_duration.unmodulated_plain_value(),
-                            );
-                            config.gui_config.interpolation_curve =
-                                self.params.gui_params.interpolation_curve.unmodulated_plain_value();
-                            config.gui_config.interpolation_duration = Duration::from_secs_f32(
-                                self.params.gui_params.interpolation_duration.unmodulated_plain_value(),
-                            );
-
-                            config.dynamic_bpm_detection_parameters.beats_lookback =
-                                self.params.dynamic_params.beats_lookback.unmodulated_plain_value() as u8;
-                            config.dynamic_bpm_detection_parameters.velocity_current_note_weight = OnOff::On(
-                                self.params.dynamic_params.velocity_current_note_weight.unmodulated_plain_value(),
-                            );
-                            config.dynamic_bpm_detection_parameters.velocity_note_from_weight = OnOff::On(
-                                self.params.dynamic_params.velocity_note_from_weight.unmodulated_plain_value(),
-                            );
-                            config.dynamic_bpm_detection_parameters.age_weight =
-                                OnOff::On(self.params.dynamic_params.age_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.octave_distance_weight =
-                                OnOff::On(self.params.dynamic_params.octave_distance_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.pitch_distance_weight =
-                                OnOff::On(self.params.dynamic_params.pitch_distance_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.multiplier_weight =
-                                OnOff::On(self.params.dynamic_params.multiplier_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.subdivision_weight =
-                                OnOff::On(self.params.dynamic_params.subdivision_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.in_beat_range_weight =
-                                OnOff::On(self.params.dynamic_params.in_beat_range_weight.unmodulated_plain_value());
-                            config.dynamic_bpm_detection_parameters.normal_distribution_weight = OnOff::On(
-                                self.params.dynamic_params.normal_distribution_weight.unmodulated_plain_value(),
-                            );
-                            config.dynamic_bpm_detection_parameters.high_tempo_bias =
-                                OnOff::On(self.params.dynamic_params.high_tempo_bias.unmodulated_plain_value());
-
+                            let before = snapshot(&*config);
                             config
                                 .send_tempo
                                 .store(self.params.send_tempo.unmodulated_plain_value(), Ordering::Relaxed);
-                            self.dynamic_bpm_detection_parameters = config.dynamic_bpm_detection_parameters.clone();
-                        }
+                            config.provenance.record_change(Origin::Daw, &before, &*config);
+
+                            let mut from_host = config.dynamic_bpm_detection_parameters.clone();
+                            self.params.read_dynamic(&*self.param_reader, &mut from_host);
+                            from_host
+                        };
+                        self.receive_host_dynamic_parameters(from_host);
                         self.gui_must_update_config.store(true, Ordering::Relaxed);
-                        self.execute(Task::ProcessNotes(true)); // does not change anything
                     }
                     UpdateOrigin::Gui => {
                         let config = self.config.read();
                         self.dynamic_bpm_detection_parameters = config.dynamic_bpm_detection_parameters.clone();
                     }
                 }
+                self.report_config_warnings();
+            }
+            Task::GUIParameters => {
+                if self.safe_mode.load(Ordering::Relaxed) {
+                    info!("safe mode, the parameters of the host are not applied");
+                } else {
+                    {
+                        let mut config = self.config.write();
+                        let before = snapshot(&*config);
+                        (config.gui_config.interpolation_duration, config.gui_config.interpolation_curve) =
+                            self.params.read_gui(&*self.param_reader);
+                        config.provenance.record_change(Origin::Daw, &before, &*config);
+                    }
+                    self.gui_must_update_config.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // the server is started, moved or stopped as configured, then the parameters sent by the TUI are applied as if they
+    // were changed from the GUI. Returns whether any were
+    fn serve_remote_control(&mut self) -> bool {
+        let remote_control_server = self.config.read().remote_control_server.clone();
+        let port = remote_control_server.enabled.then_some(remote_control_server.port);
+        if port != self.remote_control_port {
+            self.remote_control_port = port;
+            self.remote_control_server = port.and_then(|port| {
+                RemoteControlServer::bind(port).log_error_msg("could not start the remote control server").ok()
+            });
+            self.parameters_applied();
+        }
+        let Some(remote_control_server) = &self.remote_control_server else {
+            return false;
+        };
+        let requests = remote_control_server.requests().collect::<Vec<_>>();
+        if requests.is_empty() {
+            return false;
+        }
+        for request in requests {
+            match request {
+                RemoteMessage::StaticParameters(static_bpm_detection_parameters) => {
+                    self.receive_remote_static_parameters(static_bpm_detection_parameters);
+                }
+                RemoteMessage::DynamicParameters(dynamic_bpm_detection_parameters) => {
+                    let mut config = self.config.write();
+                    let before = snapshot(&*config);
+                    config.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters.clone();
+                    config.provenance.record_change(Origin::Live, &before, &*config);
+                    self.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters;
+                }
+                RemoteMessage::Estimate { .. } => {}
             }
         }
+        self.gui_must_update_config.store(true, Ordering::Relaxed);
+        if !self.write_to_host() {
+            self.remote_parameters_changed.store(true, Ordering::Relaxed);
+        }
+        self.parameters_applied();
+        self.report_config_warnings();
+        true
+    }
+
+    // false when there is no context to write with yet
+    fn write_to_host(&self) -> bool {
+        if self.safe_mode.load(Ordering::Relaxed) {
+            return true;
+        }
+        let (mut static_bpm_detection_parameters, mut dynamic_bpm_detection_parameters) = {
+            let config = self.config.read();
+            (config.static_bpm_detection_parameters.clone(), config.dynamic_bpm_detection_parameters.clone())
+        };
+        let mut pending = PendingParamWrites::default();
+        let now = Instant::now();
+        self.params.write_static(&mut static_bpm_detection_parameters, &mut pending, now);
+        self.params.write_dynamic(&mut dynamic_bpm_detection_parameters, &mut pending, now);
+        self.host_param_writer.write(&self.params, &mut pending)
+    }
+
+    fn receive_remote_static_parameters(&mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) {
+        {
+            let mut config = self.config.write();
+            let before = snapshot(&*config);
+            config.static_bpm_detection_parameters = static_bpm_detection_parameters.clone();
+            config.provenance.record_change(Origin::Live, &before, &*config);
+        }
+        self.auto_zoom.set_configured(&static_bpm_detection_parameters);
+        self.bpm_detection.update_static_parameters(static_bpm_detection_parameters);
+    }
+
+    // switches flipped by the host are held back, see `ToggleHysteresis`
+    fn receive_host_dynamic_parameters(&mut self, from_host: DynamicBPMDetectionParameters) {
+        match self.toggle_hysteresis.admit(&self.dynamic_bpm_detection_parameters, &from_host, Instant::now()) {
+            Admission::Apply => {
+                {
+                    let mut config = self.config.write();
+                    let before = snapshot(&*config);
+                    config.dynamic_bpm_detection_parameters = from_host.clone();
+                    config.provenance.record_change(Origin::Daw, &before, &*config);
+                }
+                self.dynamic_bpm_detection_parameters = from_host;
+                self.execute(Task::ProcessNotes(true));
+            }
+            Admission::Hold => {
+                self.dynamic_bpm_detection_parameters_changed_at.mark(self.current_sample.load(Ordering::Relaxed));
+            }
+            Admission::Unchanged => {}
+        }
+    }
+
+    // drops the notes received until the bypass, nothing is evaluated nor sent
+    fn bypass(&mut self) {
+        let consumed_events = self.events_receiver.pop_iter().count();
+        self.events_receiver.sync();
+        self.heartbeat.events_consumed(consumed_events);
+        self.chord_filter = ChordFilter::default();
+        self.beat_counter.reset();
+        self.evaluation_deferred = false;
+        self.deferred_evaluation.cancel();
+        self.midi_clock_bpm.store(f32::NAN, Ordering::Relaxed);
+        if !mem::replace(&mut self.bypassed, true) {
+            if let Some(gui_remote) = &self.gui_remote {
+                gui_remote.receive_bar_position(None);
+                gui_remote.receive_beat_phase(None);
+                gui_remote.request_repaint();
+            }
+        }
+    }
+
+    fn report_config_warnings(&self) {
+        if let Some(gui_remote) = &self.gui_remote {
+            gui_remote.receive_config_warnings(&validate_interaction(
+                self.auto_zoom.configured(),
+                &self.dynamic_bpm_detection_parameters,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        change_marker::WALL_CLOCK_SETTLE,
+        config::{Config, HostModulation},
+        evaluation_scheduler::{EvaluationRegistry, EvaluationSchedulingConfig},
+        param_writes::ParamWriter,
+        params::ParamRef,
+    };
+    use gui::{create_gui, BPMDetectionParameters, GUIConfig};
+    use midi::{midi_messages::MidiNoteOn, DawLink, RemoteControlClient, RemoteControlServerConfig};
+    use nih_plug::params::{BoolParam, FloatParam, IntParam};
+    use parameter::OnOff;
+    use ringbuf::{producer::PostponedProducer, StaticRb};
+    use std::{
+        io::Read,
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        sync::mpsc,
+        thread,
+        time::Duration,
+    };
+
+    // shorter than the one of the plugin, so the tests don't wait for it
+    const EDITOR_GRACE: Duration = Duration::from_millis(50);
+
+    // parameters for a GUI remote without an editor
+    struct TestParameters(Config);
+
+    impl BPMDetectionParameters for TestParameters {
+        type Error = ();
+
+        fn get_dynamic_bpm_detection_parameters(&self) -> &DynamicBPMDetectionParameters {
+            &self.0.dynamic_bpm_detection_parameters
+        }
+
+        fn get_dynamic_bpm_detection_parameters_mut(&mut self) -> &mut DynamicBPMDetectionParameters {
+            &mut self.0.dynamic_bpm_detection_parameters
+        }
+
+        fn get_static_bpm_detection_parameters(&self) -> &StaticBPMDetectionParameters {
+            &self.0.static_bpm_detection_parameters
+        }
+
+        fn get_static_bpm_detection_parameters_mut(&mut self) -> &mut StaticBPMDetectionParameters {
+            &mut self.0.static_bpm_detection_parameters
+        }
+
+        fn get_gui_config(&self) -> &GUIConfig {
+            &self.0.gui_config
+        }
+
+        fn get_gui_config_mut(&mut self) -> &mut GUIConfig {
+            &mut self.0.gui_config
+        }
+
+        fn get_send_tempo(&self) -> bool {
+            self.0.send_tempo.load(Ordering::Relaxed)
+        }
+
+        fn set_send_tempo(&mut self, enabled: bool) {
+            self.0.send_tempo.store(enabled, Ordering::Relaxed);
+        }
+
+        fn apply_static(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn apply_dynamic(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    // the host modulates the parameters named in `modulated`
+    struct ModulatingHost {
+        host_modulation: HostModulation,
+        modulated: Vec<(&'static str, f32)>,
+    }
+
+    impl ParamReader for ModulatingHost {
+        fn float(&self, param: &FloatParam) -> f32 {
+            let modulated = self.modulated.iter().find(|(name, _)| *name == param.name());
+            match (self.host_modulation, modulated) {
+                (HostModulation::Follow, Some((_, value))) => *value,
+                _ => self.host_modulation.float(param),
+            }
+        }
+
+        fn int(&self, param: &IntParam) -> i32 {
+            self.host_modulation.int(param)
+        }
+
+        fn bool(&self, param: &BoolParam) -> bool {
+            self.host_modulation.bool(param)
+        }
+    }
+
+    // normalized values written to the host
+    #[derive(Default)]
+    struct HostRecorder(Mutex<Vec<f32>>);
+
+    impl ParamWriter for HostRecorder {
+        fn begin(&self, _param: ParamRef<'_>) {}
+
+        fn set_normalized(&self, _param: ParamRef<'_>, normalized: f32) {
+            self.0.lock().push(normalized);
+        }
+
+        fn end(&self, _param: ParamRef<'_>) {}
+    }
+
+    struct Harness {
+        task_executor: TaskExecutor,
+        events_sender: PostponedProducer<Event, Arc<SharedRb<Event, [MaybeUninit<Event>; 1000]>>>,
+        // receiving end of the tempo sent to the DAW
+        daw: TcpStream,
+        bypass_detection: ArcAtomicBool,
+        next_note: i64,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let mut config = Config::default();
+            config.send_tempo.store(true, Ordering::Relaxed);
+            let dynamic_bpm_detection_parameters_changed_at = Arc::new(ChangeMarker::pending());
+            dynamic_bpm_detection_parameters_changed_at.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE);
+            let current_sample = Arc::new(AtomicUsize::new(48000));
+            let params = Arc::new(MidiBpmDetectorParams::new(
+                &mut config,
+                Arc::new(ChangeMarker::pending()),
+                dynamic_bpm_detection_parameters_changed_at.clone(),
+                Arc::new(ChangeMarker::pending()),
+                current_sample.clone(),
+                ArcAtomicOptional::new(None),
+            ));
+            let (events_sender, events_receiver) = StaticRb::<Event, 1000>::default().split();
+
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let daw_connection = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (daw, _) = listener.accept().unwrap();
+            daw.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+
+            let task_executor = TaskExecutor {
+                bpm_detection: BPMDetection::new(config.static_bpm_detection_parameters.clone()),
+                chord_filter: ChordFilter::default(),
+                dynamic_bpm_detection_parameters: config.dynamic_bpm_detection_parameters.clone(),
+                parameter_ramp: ParameterRamp::new(&config.dynamic_bpm_detection_parameters),
+                auto_zoom: AutoZoom::new(&config.static_bpm_detection_parameters),
+                gui_remote: None,
+                params,
+                param_reader: Box::new(config.host_modulation),
+                gui_remote_receiver: Arc::default(),
+                editor_activity: Arc::new(EditorActivity::new(EDITOR_GRACE)),
+                events_receiver: events_receiver.into_postponed(),
+                config: Arc::new(RwLock::new(config.clone())),
+                gui_must_update_config: ArcAtomicBool::default(),
+                daw_port: ArcAtomicOptional::new(None),
+                egress_hub: {
+                    let mut daw_connection = Some(daw_connection);
+                    EgressHub::new(move || {
+                        vec![Box::new(daw_connection.take().map_or_else(DawLink::default, DawLink::connected))]
+                    })
+                },
+                send_tempo: config.send_tempo.clone(),
+                tempo_source: config.tempo_source.clone(),
+                min_tempo_confidence: config.min_tempo_confidence,
+                feel_ambiguity: config.feel_ambiguity.clone(),
+                daw_bpm: None,
+                heartbeat: Arc::default(),
+                evaluation_scheduler: EvaluationScheduler::new(&config.evaluation_scheduling, 0),
+                deferred_evaluation: Arc::default(),
+                evaluation_deferred: false,
+                clock_anchor: Arc::default(),
+                tempo_latency: config.tempo_latency.clone(),
+                bypass_detection: config.bypass_detection.clone(),
+                bypassed: false,
+                beat_counter: BeatCounter::new(&config.beat_counter),
+                velocity_gate: VelocityGate::default(),
+                tempo_change: TempoChangeDetector::default(),
+                init_marker: Arc::default(),
+                safe_mode: config.safe_mode.clone(),
+                toggle_hysteresis: ToggleHysteresis::new(config.evaluation_scheduling.toggle_interval),
+                dynamic_bpm_detection_parameters_changed_at,
+                current_sample,
+                detection_parameters: Arc::default(),
+                remote_control_server: None,
+                remote_control_port: None,
+                remote_parameters_changed: ArcAtomicBool::default(),
+                host_param_writer: HostParamWriter::default(),
+                midi_clock_bpm: Arc::new(AtomicF32::new(f32::NAN)),
+            };
+            Self {
+                task_executor,
+                events_sender: events_sender.into_postponed(),
+                daw,
+                bypass_detection: config.bypass_detection,
+                next_note: 0,
+            }
+        }
+
+        // quarter notes at 120 BPM, following the previous ones
+        fn push_notes(&mut self, count: usize) {
+            let notes = (self.next_note..)
+                .take(count)
+                .map(|next_note| TimedMidiNoteOn {
+                    timestamp: chrono::Duration::milliseconds(next_note * 500),
+                    midi_message: MidiNoteOn { channel: 0, note: 60, velocity: 100 },
+                })
+                .collect::<Vec<_>>();
+            self.next_note += i64::try_from(count).unwrap();
+            // batched as `receive_notes` does
+            let batches = notes.chunks(NOTE_BATCH);
+            let pushed_events = batches.len();
+            for batch in batches {
+                assert!(self.events_sender.push(Event::Notes(batch.iter().cloned().collect())).is_ok());
+            }
+            self.task_executor.heartbeat.events_pushed(pushed_events);
+            self.events_sender.sync();
+        }
+
+        // staggered with the other instances coordinated through `registry`
+        fn coordinate(&mut self, registry: &Arc<EvaluationRegistry>, instance_id: u64, guard_interval: Duration) {
+            let config = EvaluationSchedulingConfig {
+                coordinate_instances: true,
+                debounce: guard_interval * 2,
+                guard_interval,
+                ..EvaluationSchedulingConfig::default()
+            };
+            self.task_executor.evaluation_scheduler =
+                EvaluationScheduler::with_registry(&config, instance_id, registry.clone());
+        }
+
+        // the task the audio thread requests for a postponed evaluation, once `elapsed` of audio went by
+        fn deferred_task_due(&self, elapsed: Duration) -> bool {
+            let current_sample = self.task_executor.current_sample.load(Ordering::Relaxed);
+            let elapsed_samples = usize::try_from(elapsed.as_micros() * 48 / 1000).unwrap();
+            self.task_executor.deferred_evaluation.take_due(current_sample + elapsed_samples, 48000)
+        }
+
+        fn deferred_evaluation_pending(&self) -> bool {
+            self.task_executor.deferred_evaluation.is_deferred()
+        }
+
+        fn sent_tempos(&mut self) -> usize {
+            let mut buffer = [0u8; 64];
+            let mut received = 0;
+            while let Ok(read) = self.daw.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                received += read;
+            }
+            received / 8
+        }
+    }
+
+    #[test]
+    fn test_bypass() {
+        let mut harness = Harness::new();
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        assert!(!harness.task_executor.midi_clock_bpm.load(Ordering::Relaxed).is_nan());
+        let newest_note = harness.task_executor.bpm_detection.newest_note_timestamp();
+
+        harness.bypass_detection.store(true, Ordering::Relaxed);
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        harness.task_executor.execute(Task::ProcessNotes(true));
+        harness.task_executor.execute(Task::DynamicBPMDetectionParameters(UpdateOrigin::Daw));
+        assert_eq!(harness.sent_tempos(), 0);
+        // the notes are dropped, nothing is left waiting for the executor
+        assert_eq!(harness.task_executor.bpm_detection.newest_note_timestamp(), newest_note);
+        assert_eq!(harness.task_executor.heartbeat.pending_events(), 0);
+        assert!(!harness.task_executor.evaluation_deferred);
+        assert!(!harness.task_executor.deferred_evaluation.is_deferred());
+        // the MIDI clock stops
+        assert!(harness.task_executor.midi_clock_bpm.load(Ordering::Relaxed).is_nan());
+
+        // resuming evaluates once, without new notes
+        harness.bypass_detection.store(false, Ordering::Relaxed);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 0);
+
+        harness.push_notes(1);
+        harness.task_executor.execute(Task::ProcessNotes(true));
+        assert_eq!(harness.sent_tempos(), 1);
+    }
+
+    #[test]
+    fn test_coordinated_instances() {
+        const GUARD_INTERVAL: Duration = Duration::from_millis(50);
+        let registry = Arc::new(EvaluationRegistry::default());
+        let mut first = Harness::new();
+        let mut second = Harness::new();
+        first.coordinate(&registry, 0, GUARD_INTERVAL);
+        second.coordinate(&registry, 1, GUARD_INTERVAL);
+
+        // the same notes reach both instances
+        first.push_notes(8);
+        second.push_notes(8);
+        first.task_executor.execute(Task::ProcessNotes(false));
+        second.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(first.sent_tempos(), 1);
+        assert_eq!(second.sent_tempos(), 0);
+        assert!(!first.deferred_evaluation_pending());
+        assert!(second.task_executor.evaluation_deferred);
+
+        // a single task is requested for it, once due
+        assert!(!second.deferred_task_due(Duration::ZERO));
+        assert!(!second.deferred_task_due(GUARD_INTERVAL / 2));
+        thread::sleep(GUARD_INTERVAL);
+        assert!(second.deferred_task_due(GUARD_INTERVAL * 2));
+        assert!(!second.deferred_task_due(GUARD_INTERVAL * 3));
+        second.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(second.sent_tempos(), 1);
+        assert!(!second.task_executor.evaluation_deferred);
+        assert!(!second.deferred_evaluation_pending());
+    }
+
+    #[test]
+    fn test_single_coordinated_instance() {
+        // nothing to stagger with, every evaluation runs right away
+        let registry = Arc::new(EvaluationRegistry::default());
+        let mut harness = Harness::new();
+        harness.coordinate(&registry, 0, Duration::from_secs(1));
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        harness.push_notes(1);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        assert!(!harness.deferred_evaluation_pending());
+
+        // once another instance is loaded
+        let mut other = Harness::new();
+        other.coordinate(&registry, 1, Duration::from_secs(1));
+        harness.push_notes(1);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 0);
+        assert!(harness.deferred_evaluation_pending());
+    }
+
+    #[test]
+    fn test_min_tempo_confidence() {
+        let mut harness = Harness::new();
+        // no estimate is that confident
+        harness.task_executor.min_tempo_confidence = 1.5;
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 0);
+
+        harness.task_executor.min_tempo_confidence = 0.0;
+        harness.push_notes(1);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+    }
+
+    #[test]
+    fn test_toggle_storm() {
+        let mut harness = Harness::new();
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+
+        let applied = harness.task_executor.dynamic_bpm_detection_parameters.clone();
+        assert!(applied.subdivision_weight.weight() > 0.0);
+        let switched = |on: bool| DynamicBPMDetectionParameters {
+            subdivision_weight: OnOff::On(if on { applied.subdivision_weight.weight() } else { 0.0 }),
+            ..applied.clone()
+        };
+        let start = Instant::now();
+        // as read from the host, 100 times within 50ms, ending switched off
+        for flip in 0..100 {
+            harness.task_executor.receive_host_dynamic_parameters(switched(flip % 2 == 0));
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(harness.sent_tempos(), 0);
+        assert_eq!(harness.task_executor.dynamic_bpm_detection_parameters, applied);
+        assert_eq!(harness.task_executor.config.read().dynamic_bpm_detection_parameters, applied);
+        // the host is asked again for its parameters
+        let changed_at = &harness.task_executor.dynamic_bpm_detection_parameters_changed_at;
+        assert!(changed_at.take_settled_wall_clock(Instant::now() + WALL_CLOCK_SETTLE));
+
+        std::thread::sleep(Config::default().evaluation_scheduling.toggle_interval);
+        harness.task_executor.receive_host_dynamic_parameters(switched(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        assert_eq!(harness.task_executor.dynamic_bpm_detection_parameters, switched(false));
+        assert_eq!(harness.task_executor.config.read().dynamic_bpm_detection_parameters, switched(false));
+        assert!(!harness.task_executor.dynamic_bpm_detection_parameters_changed_at.is_pending());
+
+        // reading the same parameters again changes nothing
+        harness.task_executor.receive_host_dynamic_parameters(switched(false));
+        assert_eq!(harness.sent_tempos(), 0);
+
+        // other changes apply right away
+        let multiplier_weight = DynamicBPMDetectionParameters {
+            multiplier_weight: OnOff::On(applied.multiplier_weight.weight() + 0.1),
+            ..switched(false)
+        };
+        harness.task_executor.receive_host_dynamic_parameters(multiplier_weight.clone());
+        assert_eq!(harness.sent_tempos(), 1);
+        assert_eq!(harness.task_executor.dynamic_bpm_detection_parameters, multiplier_weight);
+    }
+
+    #[test]
+    fn test_crash_report_parameters() {
+        let mut harness = Harness::new();
+        harness.task_executor.config.write().dynamic_bpm_detection_parameters.beats_lookback = 16;
+        harness.task_executor.execute(Task::DynamicBPMDetectionParameters(UpdateOrigin::Gui));
+        let detection_parameters = harness.task_executor.detection_parameters.lock().clone();
+        assert_eq!(detection_parameters.dynamic_bpm_detection_parameters.beats_lookback, 16);
+        assert_eq!(&detection_parameters.static_bpm_detection_parameters, harness.task_executor.auto_zoom.configured());
+    }
+
+    #[test]
+    fn test_editor_flicker() {
+        let mut harness = Harness::new();
+        let editor_activity = harness.task_executor.editor_activity.clone();
+        // `is_open` of the editor state stays false all along, no window is ever spawned
+        assert!(!harness.task_executor.params.editor_state.is_open());
+        let (gui_remote, _gui_builder) = create_gui(TestParameters(Config::default()));
+        harness.task_executor.gui_remote_receiver.store(Some(gui_remote));
+        editor_activity.built();
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert!(harness.task_executor.gui_remote.is_some());
+
+        // the host closes the window and opens it again right away
+        editor_activity.destroyed(Instant::now());
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert!(harness.task_executor.gui_remote.is_some());
+        editor_activity.built();
+        std::thread::sleep(EDITOR_GRACE);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert!(harness.task_executor.gui_remote.is_some());
+
+        // closed for good
+        editor_activity.destroyed(Instant::now());
+        std::thread::sleep(EDITOR_GRACE);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert!(harness.task_executor.gui_remote.is_none());
+    }
+
+    #[test]
+    fn test_remote_control() {
+        let mut harness = Harness::new();
+        harness.task_executor.config.write().remote_control_server =
+            RemoteControlServerConfig { enabled: true, port: 0 };
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        let address =
+            harness.task_executor.remote_control_server.as_ref().expect("server was not started").local_addr();
+
+        // the TUI end
+        let (sender, received) = mpsc::channel();
+        let mut client = RemoteControlClient::connect(address, move |message| {
+            sender.send(message).ok();
+        })
+        .unwrap();
+        let receive = || received.recv_timeout(Duration::from_secs(5)).expect("nothing received");
+
+        // the parameters the plugin runs with, then its estimates
+        let configured = harness.task_executor.auto_zoom.configured().clone();
+        assert_eq!(receive(), RemoteMessage::StaticParameters(configured.clone()));
+        assert_eq!(
+            receive(),
+            RemoteMessage::DynamicParameters(harness.task_executor.dynamic_bpm_detection_parameters.clone())
+        );
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        let RemoteMessage::Estimate { bpm, confidence, histogram_data_points } = receive() else {
+            panic!("no estimate received");
+        };
+        assert!(bpm.is_finite());
+        assert!((0.0..=1.0).contains(&confidence), "{confidence}");
+        assert_eq!(histogram_data_points, harness.task_executor.bpm_detection.histogram_data_points());
+        assert_eq!(harness.sent_tempos(), 1);
+
+        // changed in the TUI, applied by the plugin, which evaluates again and sends the parameters back
+        let changed = StaticBPMDetectionParameters { bpm_center: configured.bpm_center + 20.0, ..configured };
+        client.send(&RemoteMessage::StaticParameters(changed.clone())).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while harness.task_executor.auto_zoom.configured() != &changed {
+            assert!(Instant::now() < deadline, "parameters were not applied");
+            std::thread::sleep(Duration::from_millis(10));
+            harness.task_executor.execute(Task::ProcessNotes(false));
+        }
+        assert_eq!(harness.task_executor.config.read().static_bpm_detection_parameters, changed);
+        // written to the host by the editor, no editor window was opened yet
+        assert!(harness.task_executor.gui_must_update_config.load(Ordering::Relaxed));
+        assert!(harness.task_executor.remote_parameters_changed.take(Ordering::Relaxed));
+        assert_eq!(receive(), RemoteMessage::StaticParameters(changed.clone()));
+        assert!(matches!(receive(), RemoteMessage::DynamicParameters(_)));
+        assert!(matches!(receive(), RemoteMessage::Estimate { .. }));
+        assert_eq!(harness.sent_tempos(), 1);
+
+        // written to the host right away once there is a context, whether the editor is open or not
+        let host = Arc::new(HostRecorder::default());
+        harness.task_executor.host_param_writer.set(Some(host.clone()));
+        let changed = StaticBPMDetectionParameters { bpm_center: changed.bpm_center + 20.0, ..changed };
+        client.send(&RemoteMessage::StaticParameters(changed.clone())).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while harness.task_executor.auto_zoom.configured() != &changed {
+            assert!(Instant::now() < deadline, "parameters were not applied");
+            std::thread::sleep(Duration::from_millis(10));
+            harness.task_executor.execute(Task::ProcessNotes(false));
+        }
+        let bpm_center = harness.task_executor.params.static_params.bpm_center.preview_normalized(changed.bpm_center);
+        assert_eq!(*host.0.lock(), vec![bpm_center]);
+        assert!(!harness.task_executor.remote_parameters_changed.load(Ordering::Relaxed));
+
+        // turned off
+        harness.task_executor.config.write().remote_control_server.enabled = false;
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert!(harness.task_executor.remote_control_server.is_none());
+    }
+
+    #[test]
+    fn test_host_modulation() {
+        let bpm_center = StaticBPMDetectionParameters::BPM_CENTER.label;
+        let age_weight = DynamicBPMDetectionParameters::TIME_DISTANCE.label;
+        for host_modulation in [HostModulation::Follow, HostModulation::Ignore] {
+            let mut harness = Harness::new();
+            let params = harness.task_executor.params.clone();
+            let set = (
+                params.static_params.bpm_center.unmodulated_plain_value(),
+                params.dynamic_params.age_weight.unmodulated_plain_value(),
+            );
+            let modulated = (set.0 + 20.0, set.1 + 1.0);
+            harness.task_executor.param_reader = Box::new(ModulatingHost {
+                host_modulation,
+                modulated: vec![(bpm_center, modulated.0), (age_weight, modulated.1)],
+            });
+            harness.task_executor.execute(Task::StaticBPMDetectionParameters(UpdateOrigin::Daw));
+            harness.task_executor.execute(Task::DynamicBPMDetectionParameters(UpdateOrigin::Daw));
+
+            let expected = match host_modulation {
+                HostModulation::Follow => modulated,
+                HostModulation::Ignore => set,
+            };
+            let config = harness.task_executor.config.read();
+            let applied = config.static_bpm_detection_parameters.bpm_center;
+            assert!((applied - expected.0).abs() < 1e-4, "{host_modulation:?}: {applied}");
+            assert_eq!(
+                config.dynamic_bpm_detection_parameters.age_weight,
+                OnOff::On(expected.1),
+                "{host_modulation:?}"
+            );
+            // what the detection runs with
+            assert!((harness.task_executor.auto_zoom.configured().bpm_center - expected.0).abs() < 1e-4);
+            assert_eq!(harness.task_executor.dynamic_bpm_detection_parameters.age_weight, OnOff::On(expected.1));
+        }
+    }
+
+    #[test]
+    fn test_gui_parameters() {
+        let mut harness = Harness::new();
+        harness.push_notes(8);
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 1);
+        let applied = harness.task_executor.dynamic_bpm_detection_parameters.clone();
+
+        let interpolation_curve =
+            harness.task_executor.params.gui_params.interpolation_curve.unmodulated_plain_value() * 0.5;
+        harness.task_executor.param_reader = Box::new(ModulatingHost {
+            host_modulation: HostModulation::Follow,
+            modulated: vec![(GUIConfig::INTERPOLATION_CURVE.label, interpolation_curve)],
+        });
+        harness.task_executor.execute(Task::GUIParameters);
+        let applied_curve = harness.task_executor.config.read().gui_config.interpolation_curve;
+        assert_eq!(applied_curve.to_bits(), interpolation_curve.to_bits());
+        assert!(harness.task_executor.gui_must_update_config.load(Ordering::Relaxed));
+
+        // the detection is neither updated nor evaluated again
+        assert_eq!(harness.task_executor.dynamic_bpm_detection_parameters, applied);
+        assert!(!harness.task_executor.dynamic_bpm_detection_parameters_changed_at.is_pending());
+        assert!(!harness.task_executor.evaluation_deferred);
+        assert!(!harness.task_executor.deferred_evaluation.is_deferred());
+        harness.task_executor.execute(Task::ProcessNotes(false));
+        assert_eq!(harness.sent_tempos(), 0);
     }
 }
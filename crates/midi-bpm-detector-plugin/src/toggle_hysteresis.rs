@@ -0,0 +1,135 @@
+use midi::DynamicBPMDetectionParameters;
+use parameter::OnOff;
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+type WeightMut = fn(&mut DynamicBPMDetectionParameters) -> &mut OnOff<f32>;
+
+// weights the host can switch off, it only knows the weight which is zero when switched off
+const WEIGHTS: [WeightMut; 10] = [
+    DynamicBPMDetectionParameters::velocity_current_note_weight_mut,
+    DynamicBPMDetectionParameters::velocity_note_from_weight_mut,
+    DynamicBPMDetectionParameters::age_weight_mut,
+    DynamicBPMDetectionParameters::octave_distance_weight_mut,
+    DynamicBPMDetectionParameters::pitch_distance_weight_mut,
+    DynamicBPMDetectionParameters::multiplier_weight_mut,
+    DynamicBPMDetectionParameters::subdivision_weight_mut,
+    DynamicBPMDetectionParameters::in_beat_range_weight_mut,
+    DynamicBPMDetectionParameters::normal_distribution_weight_mut,
+    DynamicBPMDetectionParameters::high_tempo_bias_mut,
+];
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Admission {
+    Apply,
+    // the parameters of the host must be read again later
+    Hold,
+    Unchanged,
+}
+
+/// Automation of the host switching weights on and off. Parameters that only switch weights are held back until
+/// `interval` after the first of them, the switches flipped in between end up in the state the host has then, so a
+/// toggling storm applies once and forces at most one evaluation per interval. Any other change applies right away
+pub struct ToggleHysteresis {
+    interval: Duration,
+    // first switch that was held back
+    held_since: Option<Instant>,
+}
+
+impl ToggleHysteresis {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, held_since: None }
+    }
+
+    /// Whether `from_host` replaces the `applied` parameters at `now`
+    pub fn admit(
+        &mut self,
+        applied: &DynamicBPMDetectionParameters,
+        from_host: &DynamicBPMDetectionParameters,
+        now: Instant,
+    ) -> Admission {
+        if from_host == applied {
+            return Admission::Unchanged;
+        }
+        if !switches_only(applied, from_host) {
+            self.held_since = None;
+            return Admission::Apply;
+        }
+        let held_since = *self.held_since.get_or_insert(now);
+        if now.saturating_duration_since(held_since) < self.interval {
+            return Admission::Hold;
+        }
+        self.held_since = None;
+        Admission::Apply
+    }
+}
+
+fn is_on(weight: OnOff<f32>) -> bool {
+    weight.weight() > 0.0
+}
+
+// the weights that differ are switched on or off, nothing else changed
+fn switches_only(applied: &DynamicBPMDetectionParameters, from_host: &DynamicBPMDetectionParameters) -> bool {
+    let mut applied = applied.clone();
+    let mut from_host = from_host.clone();
+    for weight_mut in WEIGHTS {
+        let applied_weight = *weight_mut(&mut applied);
+        let host_weight = mem::replace(weight_mut(&mut from_host), applied_weight);
+        if host_weight != applied_weight && is_on(host_weight) == is_on(applied_weight) {
+            return false;
+        }
+    }
+    applied == from_host
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn params(subdivision_weight: f32, multiplier_weight: f32) -> DynamicBPMDetectionParameters {
+        DynamicBPMDetectionParameters {
+            subdivision_weight: OnOff::On(subdivision_weight),
+            multiplier_weight: OnOff::On(multiplier_weight),
+            ..DynamicBPMDetectionParameters::default()
+        }
+    }
+
+    #[test]
+    fn test_admit() {
+        let start = Instant::now();
+        let mut toggle_hysteresis = ToggleHysteresis::new(INTERVAL);
+        let applied = params(0.5, 0.7);
+
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.5, 0.7), start), Admission::Unchanged);
+        // a weight moving without being switched applies right away
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.6, 0.7), start), Admission::Apply);
+        // switched off by a weight of zero, switched on by any other
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.0, 0.7), start), Admission::Hold);
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.0, 0.7), start + INTERVAL / 2), Admission::Hold);
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.0, 0.7), start + INTERVAL), Admission::Apply);
+
+        let applied = params(0.0, 0.7);
+        let start = start + INTERVAL * 3;
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.8, 0.7), start), Admission::Hold);
+        // a held switch along with another change
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.8, 0.9), start + INTERVAL / 2), Admission::Apply);
+        // the interval starts over with the next switch
+        assert_eq!(toggle_hysteresis.admit(&applied, &params(0.8, 0.7), start + INTERVAL * 3 / 2), Admission::Hold);
+    }
+
+    #[test]
+    fn test_switches_only() {
+        assert!(switches_only(&params(0.5, 0.7), &params(0.0, 0.0)));
+        assert!(!switches_only(&params(0.5, 0.7), &params(0.0, 0.8)));
+        let off = DynamicBPMDetectionParameters { subdivision_weight: OnOff::Off(0.5), ..params(0.5, 0.7) };
+        assert!(switches_only(&off, &params(0.5, 0.7)));
+        assert!(!switches_only(&off, &params(0.0, 0.7)));
+        let lookback = DynamicBPMDetectionParameters { beats_lookback: 4, ..params(0.0, 0.7) };
+        assert!(!switches_only(&params(0.5, 0.7), &lookback));
+    }
+}
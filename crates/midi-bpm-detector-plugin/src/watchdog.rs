@@ -0,0 +1,193 @@
+use errors::error;
+use std::{
+    sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TaskKind {
+    None,
+    ProcessNotes,
+    StaticBPMDetectionParameters,
+    DynamicBPMDetectionParameters,
+    GUIParameters,
+}
+
+impl From<u8> for TaskKind {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::ProcessNotes,
+            2 => Self::StaticBPMDetectionParameters,
+            3 => Self::DynamicBPMDetectionParameters,
+            4 => Self::GUIParameters,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Progress report of the background task executor. The audio thread only counts the events it pushes, the executor
+/// records when it starts and finishes tasks, and the GUI thread reads it to detect stalls.
+pub struct Heartbeat {
+    origin: Instant,
+    // microseconds since origin
+    last_progress: AtomicU64,
+    last_task: AtomicU8,
+    running: AtomicU8,
+    pending_events: AtomicUsize,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+            last_progress: AtomicU64::new(0),
+            last_task: AtomicU8::new(TaskKind::None as u8),
+            running: AtomicU8::new(0),
+            pending_events: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Heartbeat {
+    fn beat(&self) {
+        self.last_progress.store(self.origin.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn task_started(&self, task_kind: TaskKind) {
+        self.last_task.store(task_kind as u8, Ordering::Relaxed);
+        self.running.fetch_add(1, Ordering::Relaxed);
+        self.beat();
+    }
+
+    pub fn task_finished(&self) {
+        self.running.fetch_sub(1, Ordering::Relaxed);
+        self.beat();
+    }
+
+    // must be called before the events are made visible to the consumer, so the count never goes below zero
+    pub fn events_pushed(&self, count: usize) {
+        if count > 0 {
+            self.pending_events.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    pub fn events_consumed(&self, count: usize) {
+        if count > 0 {
+            self.pending_events.fetch_sub(count, Ordering::Relaxed);
+        }
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.pending_events.load(Ordering::Relaxed)
+    }
+
+    pub fn last_task(&self) -> TaskKind {
+        TaskKind::from(self.last_task.load(Ordering::Relaxed))
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn last_progress(&self) -> Instant {
+        self.origin + Duration::from_micros(self.last_progress.load(Ordering::Relaxed))
+    }
+}
+
+/// How long the executor has made no progress while events were waiting, if that exceeds `threshold`.
+/// `waiting_since` is when events were first seen pending, so a note arriving after a long idle period is not
+/// mistaken for a stall.
+#[must_use]
+pub fn staleness(
+    now: Instant,
+    last_progress: Instant,
+    waiting_since: Instant,
+    threshold: Duration,
+) -> Option<Duration> {
+    let stalled_for = now.saturating_duration_since(last_progress.max(waiting_since));
+    (stalled_for >= threshold).then_some(stalled_for)
+}
+
+/// Polled from the GUI thread
+pub struct StallDetector {
+    threshold: Duration,
+    waiting_since: Option<Instant>,
+    reported: bool,
+}
+
+impl StallDetector {
+    #[must_use]
+    pub fn new(threshold: Duration) -> Self {
+        Self { threshold, waiting_since: None, reported: false }
+    }
+
+    pub fn check(&mut self, heartbeat: &Heartbeat, now: Instant) -> Option<Duration> {
+        if heartbeat.pending_events() == 0 {
+            self.waiting_since = None;
+            self.reported = false;
+            return None;
+        }
+        let waiting_since = *self.waiting_since.get_or_insert(now);
+        let Some(stalled_for) = staleness(now, heartbeat.last_progress(), waiting_since, self.threshold) else {
+            self.reported = false;
+            return None;
+        };
+        if !self.reported {
+            self.reported = true;
+            error!(
+                "background worker stalled for {:.1}s: {} pending events, last task {:?} {}",
+                stalled_for.as_secs_f32(),
+                heartbeat.pending_events(),
+                heartbeat.last_task(),
+                if heartbeat.is_running() { "still running" } else { "finished" }
+            );
+        }
+        Some(stalled_for)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn test_staleness() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        assert_eq!(staleness(at(1000), at(0), at(0), THRESHOLD), None);
+        assert_eq!(staleness(at(3200), at(0), at(0), THRESHOLD), Some(Duration::from_millis(3200)));
+        // events only waiting for a short time after a long idle period
+        assert_eq!(staleness(at(60_000), at(0), at(59_000), THRESHOLD), None);
+        assert_eq!(staleness(at(62_000), at(0), at(59_000), THRESHOLD), Some(Duration::from_millis(3000)));
+        // progress more recent than the first sight of pending events
+        assert_eq!(staleness(at(5000), at(4000), at(1000), THRESHOLD), None);
+        // clock reads racing with the executor
+        assert_eq!(staleness(at(0), at(10), at(0), Duration::ZERO), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_stall_detector() {
+        let heartbeat = Heartbeat::default();
+        let mut stall_detector = StallDetector::new(THRESHOLD);
+        let now = Instant::now();
+
+        assert_eq!(stall_detector.check(&heartbeat, now + Duration::from_secs(10)), None);
+
+        heartbeat.events_pushed(3);
+        heartbeat.task_started(TaskKind::ProcessNotes);
+        let now = Instant::now();
+        assert_eq!(stall_detector.check(&heartbeat, now), None);
+        assert!(stall_detector.check(&heartbeat, now + Duration::from_secs(3)).is_some());
+        assert_eq!(heartbeat.last_task(), TaskKind::ProcessNotes);
+        assert!(heartbeat.is_running());
+
+        heartbeat.events_consumed(3);
+        heartbeat.task_finished();
+        assert_eq!(stall_detector.check(&heartbeat, now + Duration::from_secs(4)), None);
+        assert!(!heartbeat.is_running());
+    }
+}
@@ -0,0 +1,62 @@
+//! An evaluation with the histogram summed in f32 against f64, over lookbacks of increasing length, then with the
+//! fine histogram of the multi-resolution mode and the phase split summed along with it
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use midi::{
+    bpm::bpm_to_beat_duration, midi_messages::MidiNoteOn, Accumulator, BPMDetection, BeatPhase,
+    DynamicBPMDetectionParameters, MultiResolutionConfig, PhaseSplit, StaticBPMDetectionParameters, TimedMidiNoteOn,
+};
+
+// eighth notes at 123 BPM, on changing pitches and velocities
+fn detection(accumulator: Accumulator, beats: u8) -> BPMDetection {
+    let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+        bpm_center: 120.0,
+        histogram_resolution: 2000,
+        accumulator,
+        ..StaticBPMDetectionParameters::default()
+    });
+    for index in 0..beats * 2 {
+        bpm_detection.receive_midi_message(TimedMidiNoteOn {
+            timestamp: bpm_to_beat_duration(123.0f32) * i32::from(index) / 2,
+            midi_message: MidiNoteOn { channel: 0, note: 48 + index % 24 * 7 % 24, velocity: 60 + index % 8 * 7 },
+        });
+    }
+    bpm_detection
+}
+
+// the detection above, with every histogram an evaluation can sum
+fn split_detection(accumulator: Accumulator, beats: u8) -> BPMDetection {
+    let mut bpm_detection = detection(accumulator, beats);
+    bpm_detection.set_multi_resolution(&MultiResolutionConfig { enabled: true, ..MultiResolutionConfig::default() });
+    bpm_detection.split_by_phase(Some(PhaseSplit {
+        beat_phase: BeatPhase { elapsed_beats: f64::from(beats), bpm: 123.0, bar_length: 4.0 },
+        window: 0.1,
+    }));
+    bpm_detection
+}
+
+fn compute_bpm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("accumulator");
+    for beats in [16, 32, 64] {
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: beats, ..DynamicBPMDetectionParameters::default() };
+        for (name, accumulator, detection) in [
+            ("f32", Accumulator::F32, detection as fn(Accumulator, u8) -> BPMDetection),
+            ("f64", Accumulator::F64, detection),
+            ("f32_split", Accumulator::F32, split_detection),
+            ("f64_split", Accumulator::F64, split_detection),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, beats), &beats, |b, &beats| {
+                // the notes span the lookback, every evaluation runs on all of them
+                let mut bpm_detection = detection(accumulator, beats);
+                b.iter(|| {
+                    bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).map(|(_, estimate)| estimate.bpm)
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compute_bpm);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! 10k notes received one by one against the same notes received as a single batch, in order and out of order
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use midi::{
+    bpm::bpm_to_beat_duration, midi_messages::MidiNoteOn, BPMDetection, StaticBPMDetectionParameters, TimedMidiNoteOn,
+};
+
+const NOTES: i32 = 10_000;
+
+// sixteenth notes at 123 BPM, on changing pitches and velocities
+fn notes() -> Vec<TimedMidiNoteOn> {
+    (0..NOTES)
+        .zip((0u8..24).cycle())
+        .map(|(index, step)| TimedMidiNoteOn {
+            timestamp: bpm_to_beat_duration(123.0f32) * index / 4,
+            midi_message: MidiNoteOn { channel: 0, note: 48 + step, velocity: 60 + step % 8 * 7 },
+        })
+        .collect()
+}
+
+fn receive(c: &mut Criterion) {
+    let notes = notes();
+    // swapped pairs, the batch is sorted before being taken
+    let mut shuffled = notes.clone();
+    for pair in shuffled.chunks_mut(2) {
+        pair.reverse();
+    }
+    let detection = || BPMDetection::new(StaticBPMDetectionParameters::default());
+
+    let mut group = c.benchmark_group("receive_10k_notes");
+    group.bench_function("one_by_one", |b| {
+        b.iter_batched(
+            detection,
+            |mut bpm_detection| {
+                for note in &notes {
+                    bpm_detection.receive_midi_message(note.clone());
+                }
+                bpm_detection
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.bench_function("batch", |b| {
+        b.iter_batched(
+            detection,
+            |mut bpm_detection| {
+                bpm_detection.receive_batch(&notes);
+                bpm_detection
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.bench_function("batch_out_of_order", |b| {
+        b.iter_batched(
+            detection,
+            |mut bpm_detection| {
+                bpm_detection.receive_batch(&shuffled);
+                bpm_detection
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, receive);
+criterion_main!(benches);
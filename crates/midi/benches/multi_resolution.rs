@@ -0,0 +1,51 @@
+//! A histogram at the configured resolution over the whole range, against a coarse one with a fine one around the
+//! estimate, over ranges of increasing width
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use midi::{
+    bpm::bpm_to_beat_duration, midi_messages::MidiNoteOn, BPMDetection, DynamicBPMDetectionParameters,
+    MultiResolutionConfig, StaticBPMDetectionParameters, TimedMidiNoteOn,
+};
+
+// a beat and an offbeat at 123 BPM, on changing pitches and velocities
+fn notes() -> impl Iterator<Item = TimedMidiNoteOn> {
+    (0u8..64).map(|index| TimedMidiNoteOn {
+        timestamp: bpm_to_beat_duration(123.0f32) * i32::from(index) / 2,
+        midi_message: MidiNoteOn { channel: 0, note: 48 + index % 24 * 7 % 24, velocity: 60 + index % 8 * 7 },
+    })
+}
+
+fn detection(bpm_range: u16, multi_resolution: bool) -> BPMDetection {
+    let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+        bpm_center: 120.0,
+        bpm_range,
+        histogram_resolution: 2000,
+        ..StaticBPMDetectionParameters::default()
+    });
+    bpm_detection.set_multi_resolution(&MultiResolutionConfig { enabled: multi_resolution, ..Default::default() });
+    for note in notes() {
+        bpm_detection.receive_midi_message(note);
+    }
+    bpm_detection
+}
+
+fn compute_bpm(c: &mut Criterion) {
+    let dynamic_bpm_detection_parameters =
+        DynamicBPMDetectionParameters { beats_lookback: 32, ..DynamicBPMDetectionParameters::default() };
+    let mut group = c.benchmark_group("compute_bpm");
+    for bpm_range in [20, 60, 100] {
+        for (name, multi_resolution) in [("single", false), ("multi_resolution", true)] {
+            group.bench_with_input(BenchmarkId::new(name, bpm_range), &bpm_range, |b, &bpm_range| {
+                // the notes span less than the lookback, every evaluation runs on all of them
+                let mut bpm_detection = detection(bpm_range, multi_resolution);
+                b.iter(|| {
+                    bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).map(|(_, estimate)| estimate.bpm)
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compute_bpm);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! The detection embedded through `Detector`, on notes read from the standard input, one per line as
+//! `<seconds> <note> <velocity> [channel]`. The estimate is printed after each note once there is one.
+//!
+//! printf '%s\n' '0.0 36 100' '0.5 38 90' '1.0 36 100' '1.5 38 90' '2.0 36 100' | cargo run -p midi --example simple
+
+use midi::Detector;
+use std::{
+    io::{self, BufRead},
+    time::{Duration, Instant},
+};
+
+// `<seconds> <note> <velocity> [channel]`, the channel is 0 when left out
+fn parse(line: &str) -> Option<(Duration, u8, u8, u8)> {
+    let mut fields = line.split_whitespace();
+    let seconds = Duration::try_from_secs_f64(fields.next()?.parse().ok()?).ok()?;
+    let note = fields.next()?.parse().ok()?;
+    let velocity = fields.next()?.parse().ok()?;
+    let channel = fields.next().map_or(Some(0), |channel| channel.parse().ok())?;
+    Some((seconds, note, velocity, channel))
+}
+
+fn main() -> io::Result<()> {
+    let mut detector = Detector::builder().bpm_center(120.0).bpm_range(80).beats_lookback(16).build();
+    let start = Instant::now();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((seconds, note, velocity, channel)) = parse(&line) else {
+            eprintln!("skipped, expected <seconds> <note> <velocity> [channel]: {line}");
+            continue;
+        };
+        detector.push_note(start + seconds, note, velocity, channel);
+        if let Some(estimate) = detector.estimate() {
+            println!("{:8.3}s  {:6.2} BPM  confidence {:.2}", seconds.as_secs_f64(), estimate.bpm, estimate.confidence);
+        }
+    }
+    Ok(())
+}
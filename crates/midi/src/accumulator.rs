@@ -0,0 +1,152 @@
+//! Type the histogram is summed in. A bin takes a contribution from every pair of notes around its tempo, many thousands
+//! with long lookbacks and dense playing. In f32 a contribution smaller than half a unit in the last place of the bin,
+//! about 6e-8 of its value, leaves it unchanged: the peaks stop growing with the many small contributions of the older
+//! or softer notes while a few large ones still count, and the secondary peaks flatten under the highest one.
+//!
+//! Summing in f64 keeps them. The histogram is converted back to f32 once summed, so the estimate is picked and the
+//! receivers get it as before. The fine histogram of the multi-resolution mode and the on-beat and off-beat shares of a
+//! split histogram are summed alike. The contributions themselves are computed in f32 either way, which is most of the cost
+//! of an evaluation: f64 only adds a buffer of the size of the histogram and a conversion pass over it, see the
+//! `accumulator` bench. It is worth it with lookbacks of many bars, or high velocity or age weights spreading the
+//! contributions over several orders of magnitude. With the default parameters both give the same estimates
+
+use crate::bpm::max_histogram_data_buffer_size;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Accumulator {
+    #[default]
+    F32,
+    F64,
+}
+
+/// A bin of the histogram while it is summed
+pub(crate) trait Bin: Copy {
+    fn add(&mut self, value: f32);
+}
+
+impl Bin for f32 {
+    #[inline]
+    fn add(&mut self, value: f32) {
+        *self += value;
+    }
+}
+
+impl Bin for f64 {
+    #[inline]
+    fn add(&mut self, value: f32) {
+        *self += f64::from(value);
+    }
+}
+
+/// Bins of a histogram summed in f64, next to its f32 ones. Empty with `Accumulator::F32`, which sums the f32 ones in
+/// place
+#[derive(Debug, Default)]
+pub(crate) struct WideBins(Vec<f64>);
+
+/// The bins a histogram is summed in, see `WideBins::bins`
+pub(crate) enum Bins<'a> {
+    F32(&'a mut [f32]),
+    F64(&'a mut [f64]),
+}
+
+impl WideBins {
+    /// Lays out `len` cleared bins when `accumulator` sums in f64, none otherwise. The capacity is kept for the largest
+    /// histogram unless `low_memory` is set, then it is only what the bins take
+    pub(crate) fn allocate(&mut self, accumulator: Accumulator, len: usize, low_memory: bool) {
+        let needed = match accumulator {
+            Accumulator::F32 => 0,
+            Accumulator::F64 => len,
+        };
+        self.0.clear();
+        if low_memory {
+            self.0.shrink_to(needed);
+            self.0.reserve_exact(needed);
+        } else if needed > 0 {
+            self.0.reserve(max_histogram_data_buffer_size());
+        }
+        self.0.resize(needed, 0.0);
+    }
+
+    /// Lays out `len` cleared bins when `accumulator` sums in f64, none otherwise, growing the capacity as needed
+    pub(crate) fn clear(&mut self, accumulator: Accumulator, len: usize) {
+        self.0.clear();
+        if accumulator == Accumulator::F64 {
+            self.0.resize(len, 0.0);
+        }
+    }
+
+    /// These bins when the histogram is summed in f64, its f32 `data_points` otherwise
+    pub(crate) fn bins<'a>(&'a mut self, data_points: &'a mut [f32]) -> Bins<'a> {
+        if self.0.is_empty() {
+            Bins::F32(data_points)
+        } else {
+            Bins::F64(&mut self.0)
+        }
+    }
+
+    /// Converts the bins into the f32 `data_points` once summed, they are left as they are when summed in place
+    pub(crate) fn finish(&self, data_points: &mut [f32]) {
+        for (value, wide_value) in data_points.iter_mut().zip(&self.0) {
+            *value = *wide_value as f32;
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[cfg(any(test, feature = "memory-stats"))]
+    pub(crate) fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a single large contribution to the first bin, a slightly smaller one to the second, then many small ones to the
+    // second that make it the highest
+    fn accumulate<B: Bin + Default>() -> [B; 2] {
+        let mut bins = [B::default(); 2];
+        bins[0].add(100_000_000.0);
+        bins[1].add(99_999_000.0);
+        for _ in 0..5000 {
+            bins[1].add(1.0);
+        }
+        bins
+    }
+
+    #[test]
+    fn test_small_contributions() {
+        // a unit in the last place is 8 at that magnitude, the small contributions are rounded away
+        let [first, second] = accumulate::<f32>();
+        assert_eq!(second.to_bits(), 99_999_000.0f32.to_bits());
+        assert!(first > second);
+
+        let [first, second] = accumulate::<f64>();
+        assert_eq!(second.to_bits(), 100_004_000.0f64.to_bits());
+        assert!(second > first);
+    }
+
+    #[test]
+    fn test_wide_bins() {
+        let mut data_points = vec![1.0f32; 3];
+        let mut wide_bins = WideBins::default();
+        wide_bins.clear(Accumulator::F32, 3);
+        assert!(matches!(wide_bins.bins(&mut data_points), Bins::F32(_)));
+        // summed in place, nothing to convert
+        wide_bins.finish(&mut data_points);
+        assert_eq!(data_points, vec![1.0; 3]);
+
+        wide_bins.clear(Accumulator::F64, 3);
+        let Bins::F64(bins) = wide_bins.bins(&mut data_points) else {
+            panic!("summed in f32");
+        };
+        bins[1].add(2.0);
+        wide_bins.finish(&mut data_points);
+        assert_eq!(data_points, vec![0.0, 2.0, 0.0]);
+    }
+}
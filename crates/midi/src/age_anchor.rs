@@ -0,0 +1,99 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+/// What the age of the newer note of a pair is measured against, giving the criterion the `age` weight applies to:
+/// - `WallTime` against the time spanned by the notes kept, the newest note counts fully and the oldest not at all. The
+///   same weight then decays twice as fast at half the tempo, or with half as many beats kept
+/// - `Beats` in beats of the previous estimate, the criterion halves every `half_life` beats whatever the tempo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum AgeAnchor {
+    #[default]
+    WallTime,
+    Beats {
+        half_life: f32,
+    },
+}
+
+impl AgeAnchor {
+    pub const ALL: [Self; 2] = [Self::WallTime, Self::Beats { half_life: 4.0 }];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::WallTime => "Wall time",
+            Self::Beats { .. } => "Beats",
+        }
+    }
+
+    /// Same kind as `other`, regardless of its setting
+    #[must_use]
+    pub fn same_kind(self, other: Self) -> bool {
+        self.label() == other.label()
+    }
+
+    /// Age criterion of a note `note_age` older than the newest one, from 1 for the newest towards 0. `maximum_interval`
+    /// is the time spanned by the notes, `beat_duration` the one of the tempo the age is counted in
+    #[must_use]
+    #[inline]
+    pub fn criterion(self, note_age: Duration, maximum_interval: Duration, beat_duration: Duration) -> f32 {
+        let microseconds = |duration: Duration| duration.num_microseconds().unwrap_or(i64::MAX) as f32;
+        match self {
+            Self::WallTime => microseconds(maximum_interval - note_age) / microseconds(maximum_interval),
+            Self::Beats { half_life } => (-microseconds(note_age) / microseconds(beat_duration) / half_life).exp2(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpm::bpm_to_beat_duration;
+
+    #[test]
+    fn test_wall_time_is_unchanged() {
+        let maximum_interval = Duration::milliseconds(4000);
+        for note_age in [0, 1000, 2500, 4000].map(Duration::milliseconds) {
+            // the criterion the detection was tuned with
+            let previous = (maximum_interval - note_age).num_microseconds().unwrap() as f32
+                / maximum_interval.num_microseconds().unwrap() as f32;
+            let criterion = AgeAnchor::WallTime.criterion(note_age, maximum_interval, Duration::milliseconds(500));
+            assert_eq!(criterion.to_bits(), previous.to_bits(), "{note_age}");
+        }
+    }
+
+    #[test]
+    fn test_same_age_in_beats_at_different_tempos() {
+        // the notes kept span 8 seconds at both tempos
+        let maximum_interval = Duration::seconds(8);
+        let beats_anchor = AgeAnchor::Beats { half_life: 4.0 };
+        let criteria = |anchor: AgeAnchor, beats: i32| {
+            [60.0, 180.0].map(|bpm| {
+                let beat_duration = bpm_to_beat_duration(bpm);
+                anchor.criterion(beat_duration * beats, maximum_interval, beat_duration)
+            })
+        };
+
+        // two beats ago is a quarter of the buffer at 60 BPM, a twelfth at 180 BPM
+        let [slow, fast] = criteria(AgeAnchor::WallTime, 2);
+        assert!((slow - 0.75).abs() < 1e-3 && (fast - 11.0 / 12.0).abs() < 1e-3, "{slow} {fast}");
+        // halved every 4 beats at both tempos
+        for beats in [0, 2, 4, 8] {
+            let [slow, fast] = criteria(beats_anchor, beats);
+            let expected = 0.5f32.powf(beats as f32 / 4.0);
+            assert!((slow - expected).abs() < 1e-3 && (fast - expected).abs() < 1e-3, "{beats}: {slow} {fast}");
+        }
+    }
+
+    #[test]
+    fn test_beats_ignore_the_buffer_length() {
+        let beat_duration = bpm_to_beat_duration(120.0);
+        let anchor = AgeAnchor::Beats { half_life: 2.0 };
+        let short = anchor.criterion(beat_duration * 2, beat_duration * 4, beat_duration);
+        let long = anchor.criterion(beat_duration * 2, beat_duration * 32, beat_duration);
+        assert!((short - 0.5).abs() < 1e-3 && (long - 0.5).abs() < 1e-3, "{short} {long}");
+        let short = AgeAnchor::WallTime.criterion(beat_duration * 2, beat_duration * 4, beat_duration);
+        let long = AgeAnchor::WallTime.criterion(beat_duration * 2, beat_duration * 32, beat_duration);
+        assert!(long > short + 0.4, "{short} {long}");
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+
+use chrono::Duration;
+use parameter::OnOff;
+
+use crate::StaticBPMDetectionParameters;
+
+// estimates within that many seconds are compared to tell whether the tempo is locked
+const STABILITY_WINDOW: i64 = 4;
+// in BPM, largest spread of the recent estimates for the tempo to be locked
+const STABLE_SPREAD: f32 = 1.0;
+// below that note density, the notes don't support the estimate anymore. See `note_density_confidence`
+const MIN_CONFIDENCE: f32 = 0.5;
+// seconds the tempo has to stay locked before narrowing, from the least to the most aggressive
+const LOCK_DURATION: (f32, f32) = (8.0, 2.0);
+// share of the configured range left out by the most aggressive narrowing
+const MAX_NARROWING: f32 = 0.75;
+// in BPM, the narrowed range is never smaller
+const MIN_RANGE: u16 = 6;
+// share of the narrowed range at each end where an estimate means the tempo is leaving it
+const EDGE_MARGIN: f32 = 0.1;
+
+/// Narrows the detection around the estimate once the tempo is locked, for a finer histogram, and goes back to the
+/// configured parameters when it isn't anymore. The configured parameters are left as they are, the narrowed ones only
+/// exist here and in the detection
+#[derive(Clone, Debug)]
+pub struct AutoZoom {
+    configured: StaticBPMDetectionParameters,
+    zoomed: Option<StaticBPMDetectionParameters>,
+    estimates: VecDeque<(Duration, f32)>,
+    stable_since: Option<Duration>,
+    // the first estimate after a change of parameters doesn't relate to the notes, see
+    // `BPMDetection::update_static_parameters`
+    settling: bool,
+}
+
+impl AutoZoom {
+    #[must_use]
+    pub fn new(configured: &StaticBPMDetectionParameters) -> Self {
+        Self {
+            configured: configured.clone(),
+            zoomed: None,
+            estimates: VecDeque::new(),
+            stable_since: None,
+            settling: false,
+        }
+    }
+
+    /// Parameters as configured by the user
+    #[must_use]
+    pub fn configured(&self) -> &StaticBPMDetectionParameters {
+        &self.configured
+    }
+
+    /// Narrowed parameters the detection runs with, none when it runs with the configured ones
+    #[must_use]
+    pub fn zoomed(&self) -> Option<&StaticBPMDetectionParameters> {
+        self.zoomed.as_ref()
+    }
+
+    /// The user changed the parameters, they apply as they are until the tempo locks again
+    pub fn set_configured(&mut self, configured: &StaticBPMDetectionParameters) {
+        *self = Self { settling: true, ..Self::new(configured) };
+    }
+
+    /// Follows an estimate of the detection, returns the parameters to run it with when they change
+    pub fn update(
+        &mut self,
+        auto_zoom: OnOff<f32>,
+        bpm: f32,
+        confidence: f32,
+        timestamp: Duration,
+    ) -> Option<StaticBPMDetectionParameters> {
+        let OnOff::On(aggressiveness) = auto_zoom else {
+            return self.widen();
+        };
+        if std::mem::take(&mut self.settling) {
+            return None;
+        }
+        // timestamps going back start over with a new device
+        if self.estimates.back().is_some_and(|(last, _)| *last > timestamp) {
+            self.estimates.clear();
+            self.stable_since = None;
+        }
+        self.estimates.push_back((timestamp, bpm));
+        while self
+            .estimates
+            .front()
+            .is_some_and(|(oldest, _)| timestamp - *oldest > Duration::seconds(STABILITY_WINDOW))
+        {
+            self.estimates.pop_front();
+        }
+        let (lowest, highest) =
+            self.estimates.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lowest, highest), (_, bpm)| {
+                (lowest.min(*bpm), highest.max(*bpm))
+            });
+        let stable = highest - lowest <= STABLE_SPREAD && confidence >= MIN_CONFIDENCE;
+
+        if let Some(zoomed) = &self.zoomed {
+            let margin = (zoomed.highest_bpm() - zoomed.lowest_bpm()) * EDGE_MARGIN;
+            let at_edge = bpm < zoomed.lowest_bpm() + margin || bpm > zoomed.highest_bpm() - margin;
+            return if stable && !at_edge { None } else { self.widen() };
+        }
+        if !stable {
+            self.stable_since = None;
+            return None;
+        }
+        let stable_since = *self.stable_since.get_or_insert(timestamp);
+        let aggressiveness = aggressiveness.clamp(0.0, 1.0);
+        let lock_duration = LOCK_DURATION.0 + (LOCK_DURATION.1 - LOCK_DURATION.0) * aggressiveness;
+        if (timestamp - stable_since).num_milliseconds() as f32 / 1000.0 < lock_duration {
+            return None;
+        }
+        self.narrow(bpm, aggressiveness)
+    }
+
+    /// Forgets the lock, for notes that don't follow the previous ones. Returns the configured parameters when narrowed
+    pub fn reset(&mut self) -> Option<StaticBPMDetectionParameters> {
+        let configured = self.widen();
+        self.estimates.clear();
+        self.stable_since = None;
+        configured
+    }
+
+    fn narrow(&mut self, bpm: f32, aggressiveness: f32) -> Option<StaticBPMDetectionParameters> {
+        let configured = &self.configured;
+        let bpm_range = (f32::from(configured.bpm_range) * (1.0 - aggressiveness * MAX_NARROWING)).round() as u16;
+        let bpm_range = bpm_range.max(MIN_RANGE);
+        if bpm_range >= configured.bpm_range {
+            return None;
+        }
+        // the narrowed range stays within the configured one
+        let below = f32::from(bpm_range / 2);
+        let above = f32::from(bpm_range) - below;
+        let bpm_center = bpm.clamp(configured.lowest_bpm() + below, (configured.highest_bpm() - above).max(1.0));
+        // the same number of bins over a smaller range
+        let resolution_range = &StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION.range;
+        let histogram_resolution = (f32::from(configured.histogram_resolution) * f32::from(configured.bpm_range)
+            / f32::from(bpm_range))
+        .min(*resolution_range.end() as f32) as u16;
+
+        let zoomed = StaticBPMDetectionParameters { bpm_center, bpm_range, histogram_resolution, ..configured.clone() };
+        self.zoomed = Some(zoomed.clone());
+        self.restart();
+        Some(zoomed)
+    }
+
+    fn widen(&mut self) -> Option<StaticBPMDetectionParameters> {
+        self.zoomed.take()?;
+        self.restart();
+        Some(self.configured.clone())
+    }
+
+    fn restart(&mut self) {
+        self.estimates.clear();
+        self.stable_since = None;
+        self.settling = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured() -> StaticBPMDetectionParameters {
+        // 90 to 150 BPM
+        StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            bpm_range: 60,
+            histogram_resolution: 400,
+            ..StaticBPMDetectionParameters::default()
+        }
+    }
+
+    // effective ranges issued while estimating `bpms`, one estimate every half second starting at `start_ms`
+    fn issued(auto_zoom: &mut AutoZoom, aggressiveness: f32, start_ms: i64, bpms: &[f32]) -> Vec<(usize, f32, f32)> {
+        bpms.iter()
+            .enumerate()
+            .filter_map(|(index, bpm)| {
+                let timestamp = Duration::milliseconds(start_ms + index as i64 * 500);
+                auto_zoom
+                    .update(OnOff::On(aggressiveness), *bpm, 1.0, timestamp)
+                    .map(|issued| (index, issued.lowest_bpm(), issued.highest_bpm()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lock_narrow_change_widen() {
+        let mut auto_zoom = AutoZoom::new(&configured());
+        // locked on 120 BPM, narrowed after 5 seconds with an aggressiveness of 0.5
+        let ranges = issued(&mut auto_zoom, 0.5, 0, &[120.0; 11]);
+        assert_eq!(ranges, [(10, 101.0, 139.0)]);
+        let zoomed = auto_zoom.zoomed().unwrap();
+        assert_eq!(zoomed.bpm_range, 38);
+        // about as many bins as before
+        assert_eq!(zoomed.histogram_resolution, 631);
+        // the configured parameters are untouched
+        assert_eq!(auto_zoom.configured(), &configured());
+
+        // still locked, the first estimate after narrowing is skipped
+        assert_eq!(issued(&mut auto_zoom, 0.5, 6000, &[137.0, 120.2, 119.8, 120.1]), []);
+
+        // the tempo moves to the edge of the narrowed range, back to the configured one
+        assert_eq!(issued(&mut auto_zoom, 0.5, 8000, &[137.0]), [(0, 90.0, 150.0)]);
+        assert_eq!(auto_zoom.zoomed(), None);
+
+        // locks again on the new tempo, the narrowed range stays within the configured one
+        let ranges = issued(&mut auto_zoom, 0.5, 9000, &[146.0; 12]);
+        assert_eq!(ranges, [(11, 112.0, 150.0)]);
+    }
+
+    #[test]
+    fn test_widen_on_loss() {
+        let mut auto_zoom = AutoZoom::new(&configured());
+        assert_eq!(issued(&mut auto_zoom, 1.0, 0, &[100.0; 6]).len(), 1);
+        // the most aggressive narrowing keeps a quarter of the range
+        assert_eq!(auto_zoom.zoomed().map(|zoomed| zoomed.bpm_range), Some(15));
+
+        // the estimates spread out within the narrowed range
+        assert_eq!(issued(&mut auto_zoom, 1.0, 3000, &[100.0, 100.0, 102.5]), [(2, 90.0, 150.0)]);
+
+        // notes stop matching the estimate
+        assert_eq!(issued(&mut auto_zoom, 1.0, 5000, &[100.0; 7]).len(), 1);
+        let timestamp = Duration::seconds(9);
+        assert_eq!(auto_zoom.update(OnOff::On(1.0), 100.0, 0.25, timestamp).unwrap(), configured());
+
+        // switched off
+        assert_eq!(issued(&mut auto_zoom, 1.0, 10_000, &[100.0; 6]).len(), 1);
+        assert_eq!(auto_zoom.update(OnOff::Off(1.0), 100.0, 1.0, Duration::seconds(14)).unwrap(), configured());
+        assert_eq!(auto_zoom.update(OnOff::Off(1.0), 100.0, 1.0, Duration::seconds(15)), None);
+    }
+
+    #[test]
+    fn test_no_lock() {
+        let mut auto_zoom = AutoZoom::new(&configured());
+        // the tempo keeps moving
+        let bpms = (0..20).map(|index| 110.0 + index as f32).collect::<Vec<_>>();
+        assert_eq!(issued(&mut auto_zoom, 1.0, 0, &bpms), []);
+        // nothing to narrow with no aggressiveness
+        assert_eq!(issued(&mut auto_zoom, 0.0, 10_000, &[120.0; 40]), []);
+
+        // a new configuration starts over, its first estimate is skipped
+        let mut auto_zoom = AutoZoom::new(&configured());
+        issued(&mut auto_zoom, 1.0, 0, &[120.0; 3]);
+        auto_zoom.set_configured(&StaticBPMDetectionParameters { bpm_range: 40, ..configured() });
+        assert_eq!(issued(&mut auto_zoom, 1.0, 1500, &[120.0; 5]), []);
+        assert_eq!(issued(&mut auto_zoom, 1.0, 4000, &[120.0]), [(0, 115.0, 125.0)]);
+    }
+}
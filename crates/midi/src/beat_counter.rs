@@ -0,0 +1,293 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+// counts are rounded down, a count landing right on a beat must not be one short
+const EPSILON: f64 = 1e-3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self { numerator: 4, denominator: 4 }
+    }
+}
+
+impl TimeSignature {
+    /// Length of a bar in beats of the detected tempo, which are quarter notes
+    #[must_use]
+    pub fn bar_length(self) -> f64 {
+        f64::from(self.numerator.max(1)) * 4.0 / f64::from(self.denominator.max(1))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeatCounterConfig {
+    // when running as a plugin, the time signature of the transport is used instead
+    pub time_signature: TimeSignature,
+    // the count starts over when no note was played for that many bars
+    pub reset_after_bars: u8,
+}
+
+impl Default for BeatCounterConfig {
+    fn default() -> Self {
+        Self { time_signature: TimeSignature::default(), reset_after_bars: 2 }
+    }
+}
+
+/// Beats and bars elapsed since the count started, both starting at zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarPosition {
+    pub beats: u64,
+    pub bars: u64,
+    // beat within the current bar
+    pub beat: u64,
+}
+
+impl Display for BarPosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bar {}.{}", self.bars + 1, self.beat + 1)
+    }
+}
+
+/// Where the count stands at the newest note, the next beats follow at the tempo in effect. The first note is on a
+/// beat, as is the first beat of the first bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatPhase {
+    pub elapsed_beats: f64,
+    pub bpm: f32,
+    // in beats, see `TimeSignature::bar_length`
+    pub bar_length: f64,
+}
+
+/// Beats played since the first note, integrated over time with the tempo in effect: a tempo change only applies from
+/// the moment it was detected, the beats counted until then are kept
+#[derive(Clone, Debug)]
+pub struct BeatCounter {
+    time_signature: TimeSignature,
+    reset_after_bars: u8,
+    elapsed_beats: f64,
+    // note timestamp up to which `elapsed_beats` is counted, none until the first note
+    counted_until: Option<Duration>,
+    last_note: Option<Duration>,
+    bpm: Option<f32>,
+}
+
+impl BeatCounter {
+    #[must_use]
+    pub fn new(beat_counter_config: &BeatCounterConfig) -> Self {
+        Self {
+            time_signature: beat_counter_config.time_signature,
+            reset_after_bars: beat_counter_config.reset_after_bars,
+            elapsed_beats: 0.0,
+            counted_until: None,
+            last_note: None,
+            bpm: None,
+        }
+    }
+
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.time_signature = time_signature;
+    }
+
+    /// The count starts again from the next note, the tempo is kept
+    pub fn reset(&mut self) {
+        self.elapsed_beats = 0.0;
+        self.counted_until = None;
+        self.last_note = None;
+    }
+
+    /// Onset of a note, at its timestamp
+    pub fn note(&mut self, timestamp: Duration) {
+        if let (Some(last_note), Some(bpm)) = (self.last_note, self.bpm) {
+            let gap = seconds(timestamp - last_note);
+            let longest_gap =
+                f64::from(self.reset_after_bars) * self.time_signature.bar_length() * 60.0 / f64::from(bpm);
+            // a gap, or timestamps starting over
+            if gap > longest_gap || gap < 0.0 {
+                self.reset();
+            }
+        }
+        if self.counted_until.is_none() {
+            self.counted_until = Some(timestamp);
+        }
+        self.advance(timestamp);
+        self.last_note = Some(timestamp);
+    }
+
+    /// Tempo detected at `timestamp`, the time before it is counted with the previous tempo. Until the first tempo is
+    /// known, nothing is counted and the first tempo also applies to the time before it
+    pub fn tempo(&mut self, bpm: f32, timestamp: Duration) {
+        if bpm.is_finite() && bpm > 0.0 {
+            self.advance(timestamp);
+            self.bpm = Some(bpm);
+        }
+    }
+
+    /// None until a tempo is known and a note was played
+    #[must_use]
+    pub fn position(&self) -> Option<BarPosition> {
+        self.bpm?;
+        self.counted_until?;
+        let elapsed_beats = self.elapsed_beats + EPSILON;
+        let bar_length = self.time_signature.bar_length();
+        let bars = (elapsed_beats / bar_length).floor();
+        Some(BarPosition {
+            beats: elapsed_beats.floor() as u64,
+            bars: bars as u64,
+            beat: (elapsed_beats - bars * bar_length).floor() as u64,
+        })
+    }
+
+    /// None until a tempo is known and a note was played
+    #[must_use]
+    pub fn phase(&self) -> Option<BeatPhase> {
+        self.counted_until?;
+        Some(BeatPhase {
+            elapsed_beats: self.elapsed_beats,
+            bpm: self.bpm?,
+            bar_length: self.time_signature.bar_length(),
+        })
+    }
+
+    fn advance(&mut self, timestamp: Duration) {
+        let (Some(counted_until), Some(bpm)) = (self.counted_until, self.bpm) else {
+            return;
+        };
+        if timestamp > counted_until {
+            self.elapsed_beats += seconds(timestamp - counted_until) * f64::from(bpm) / 60.0;
+            self.counted_until = Some(timestamp);
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // notes on every beat, the tempo of each beat taken from `tempos`. When `lagging`, the detection only knows the
+    // tempo of a beat once it is over, as a real detection would
+    fn count(
+        tempos: impl IntoIterator<Item = f32>,
+        time_signature: TimeSignature,
+        lagging: bool,
+    ) -> (BeatCounter, f64) {
+        let mut beat_counter = BeatCounter::new(&BeatCounterConfig { time_signature, ..BeatCounterConfig::default() });
+        let mut elapsed = 0.0;
+        let mut beats = 0.0;
+        let mut previous_bpm = None;
+        for bpm in tempos {
+            let timestamp = Duration::microseconds((elapsed * 1_000_000.0f64).round() as i64);
+            beat_counter.note(timestamp);
+            let detected_bpm = if lagging { previous_bpm.unwrap_or(bpm) } else { bpm };
+            beat_counter.tempo(detected_bpm, timestamp);
+            previous_bpm = Some(bpm);
+            elapsed += 60.0 / f64::from(bpm);
+            beats += 1.0;
+        }
+        beat_counter.note(Duration::microseconds((elapsed * 1_000_000.0f64).round() as i64));
+        (beat_counter, beats)
+    }
+
+    fn assert_close(beat_counter: &BeatCounter, beats: f64, tolerance: f64) {
+        let error = (beat_counter.elapsed_beats - beats).abs();
+        assert!(error < tolerance, "counted {} instead of {beats}", beat_counter.elapsed_beats);
+    }
+
+    #[test]
+    fn test_steady_tempo() {
+        let (beat_counter, beats) = count([120.0; 32], TimeSignature::default(), false);
+        assert_close(&beat_counter, beats, 0.01);
+        assert_eq!(beat_counter.position(), Some(BarPosition { beats: 32, bars: 8, beat: 0 }));
+        assert_eq!(beat_counter.position().unwrap().to_string(), "bar 9.1");
+    }
+
+    #[test]
+    fn test_phase() {
+        let mut beat_counter = BeatCounter::new(&BeatCounterConfig {
+            time_signature: TimeSignature { numerator: 7, denominator: 8 },
+            ..BeatCounterConfig::default()
+        });
+        beat_counter.note(Duration::zero());
+        assert_eq!(beat_counter.phase(), None);
+        beat_counter.tempo(120.0, Duration::zero());
+        beat_counter.note(Duration::milliseconds(1250));
+        let phase = beat_counter.phase().unwrap();
+        assert!((phase.elapsed_beats - 2.5).abs() < 1e-9);
+        assert!((phase.bpm - 120.0).abs() < f32::EPSILON);
+        assert!((phase.bar_length - 3.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        let tempos = || [120.0; 16].into_iter().chain([90.0; 16]).chain([150.0; 16]);
+        let (beat_counter, beats) = count(tempos(), TimeSignature::default(), false);
+        assert_close(&beat_counter, beats, 0.01);
+        // dividing the elapsed time by the last tempo would be way off
+        let naive = seconds(beat_counter.counted_until.unwrap()) * 150.0 / 60.0;
+        assert!((naive - beats).abs() > 4.0);
+
+        // a late detection miscounts the beat following each change only
+        let (beat_counter, beats) = count(tempos(), TimeSignature::default(), true);
+        assert_close(&beat_counter, beats, 0.5);
+    }
+
+    #[test]
+    fn test_ramp() {
+        let tempos = || (0..64).map(|beat| 100.0 + beat as f32 * 40.0 / 64.0);
+        let time_signature = TimeSignature { numerator: 6, denominator: 8 };
+        let (beat_counter, beats) = count(tempos(), time_signature, false);
+        assert_close(&beat_counter, beats, 0.01);
+        // bars of three quarter notes
+        assert_eq!(beat_counter.position(), Some(BarPosition { beats: 64, bars: 21, beat: 1 }));
+
+        let (beat_counter, beats) = count(tempos(), time_signature, true);
+        assert_close(&beat_counter, beats, 0.5);
+    }
+
+    #[test]
+    fn test_late_tempo() {
+        // the first estimate comes after a few notes, and applies to them
+        let mut beat_counter = BeatCounter::new(&BeatCounterConfig::default());
+        for beat in 0..4 {
+            beat_counter.note(Duration::milliseconds(beat * 500));
+        }
+        assert_eq!(beat_counter.position(), None);
+        beat_counter.tempo(120.0, Duration::milliseconds(1500));
+        beat_counter.note(Duration::milliseconds(2000));
+        assert_close(&beat_counter, 4.0, 0.01);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut beat_counter = BeatCounter::new(&BeatCounterConfig::default());
+        beat_counter.note(Duration::zero());
+        beat_counter.tempo(120.0, Duration::zero());
+        beat_counter.note(Duration::seconds(3));
+        assert_close(&beat_counter, 6.0, 0.01);
+
+        // two bars at 120 BPM last four seconds
+        beat_counter.note(Duration::milliseconds(6500));
+        assert_close(&beat_counter, 13.0, 0.01);
+        beat_counter.note(Duration::milliseconds(10_600));
+        assert_close(&beat_counter, 0.0, 0.01);
+        beat_counter.note(Duration::milliseconds(11_100));
+        assert_close(&beat_counter, 1.0, 0.01);
+
+        beat_counter.reset();
+        assert_eq!(beat_counter.position(), None);
+        assert_eq!(beat_counter.phase(), None);
+        beat_counter.note(Duration::seconds(13));
+        assert_eq!(beat_counter.position(), Some(BarPosition { beats: 0, bars: 0, beat: 0 }));
+    }
+}
@@ -1,10 +1,21 @@
-use crate::{DurationOps, NormalDistributionConfig};
+use crate::{
+    midi_messages::MidiNoteOn, Accumulator, AgeAnchor, ChannelFilter, DurationOps, NormalDistributionConfig,
+    WeightResponse,
+};
 use chrono::Duration;
 use derivative::Derivative;
 
-use parameter::{Asf64, MutGetters, OnOff, Parameter};
-use serde::{Deserialize, Serialize};
-use std::time::Duration as StdDuration;
+use log::warn;
+use parameter::{MutGetters, OnOff, Parameter};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+    time::Duration as StdDuration,
+};
+
+// below that fraction of a histogram bin, most points of the normal distribution are never read
+const MIN_RESOLUTION_PER_BIN: f32 = 0.1;
 
 #[derive(Clone, Debug, Derivative, Serialize, Deserialize, MutGetters)]
 #[derivative(PartialEq, Eq)]
@@ -14,9 +25,32 @@ pub struct StaticBPMDetectionParameters {
     #[derivative(PartialEq(compare_with = "f32::eq"))]
     pub bpm_center: f32,
     pub bpm_range: u16,
-    // per second
-    pub sample_rate: u16,
+    // histogram bins per second of beat duration. Unrelated to the audio sample rate, which older versions made it
+    // easy to confuse with
+    #[serde(alias = "sample_rate", deserialize_with = "deserialize_histogram_resolution")]
+    pub histogram_resolution: u16,
+    // notes on the other channels are left out of the detection as they are received, see `ChannelFilter`
+    pub channels: ChannelFilter,
+    // notes outside the range are left out of the detection as they are received, it is disabled when the lowest note
+    // is above the highest one
+    pub note_low: u8,
+    pub note_high: u8,
     pub normal_distribution: NormalDistributionConfig,
+    // moving average over the histogram before its peak is picked, the value is the width of the window in
+    // milliseconds of beat duration
+    pub histogram_smoothing: OnOff<f32>,
+    // [low, high] BPM pairs left out of the peak selection, for an artifact that keeps winning over the played tempo.
+    // The histogram keeps them, they are only weighted when the estimate is picked
+    pub excluded_ranges: Vec<(f32, f32)>,
+    // weight of the bins within the excluded ranges, 0 leaves them out entirely
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub excluded_range_factor: f32,
+    // the histogram bins are kept at least that many times as wide as the measured precision of the note timestamps,
+    // see `InputPrecision`. 0 leaves the histogram resolution as configured
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub resolution_floor_factor: f32,
+    // type the histogram is summed in, see `accumulator`
+    pub accumulator: Accumulator,
 }
 
 impl Default for StaticBPMDetectionParameters {
@@ -24,8 +58,16 @@ impl Default for StaticBPMDetectionParameters {
         Self {
             bpm_range: Self::BPM_RANGE.default,
             bpm_center: Self::BPM_CENTER.default,
-            sample_rate: Self::SAMPLE_RATE.default,
+            histogram_resolution: Self::HISTOGRAM_RESOLUTION.default,
+            channels: ChannelFilter::ALL,
+            note_low: Self::NOTE_LOW.default,
+            note_high: Self::NOTE_HIGH.default,
             normal_distribution: NormalDistributionConfig::default(),
+            histogram_smoothing: Self::HISTOGRAM_SMOOTHING.default,
+            excluded_ranges: Vec::new(),
+            excluded_range_factor: 0.0,
+            resolution_floor_factor: 1.0,
+            accumulator: Accumulator::F32,
         }
     }
 }
@@ -35,8 +77,45 @@ impl StaticBPMDetectionParameters {
         Parameter::new("BPM center", None, 1.0..=150.0, 0.01, false, 90.0, Self::bpm_center_mut);
     pub const BPM_RANGE: Parameter<Self, u16> =
         Parameter::new("BPM range", None, 1.0..=100.0, 1.0, false, 40, Self::bpm_range_mut);
-    pub const SAMPLE_RATE: Parameter<Self, u16> =
-        Parameter::new("BPM sample rate", Some("samples/second"), 1.0..=1_0000., 1.0, true, 450, Self::sample_rate_mut);
+    pub const HISTOGRAM_RESOLUTION: Parameter<Self, u16> = Parameter::new(
+        "Histogram resolution",
+        Some("bins/s"),
+        1.0..=2000.0,
+        1.0,
+        true,
+        450,
+        Self::histogram_resolution_mut,
+    );
+    pub const HISTOGRAM_SMOOTHING: Parameter<Self, OnOff<f32>> = Parameter::new(
+        "Histogram smoothing",
+        Some("ms"),
+        1.0..=50.0,
+        0.0,
+        false,
+        OnOff::Off(5.0),
+        Self::histogram_smoothing_mut,
+    );
+    pub const NOTE_HIGH: Parameter<Self, u8> =
+        Parameter::new("Highest note", None, 0.0..=127.0, 1.0, false, 127, Self::note_high_mut);
+    pub const NOTE_LOW: Parameter<Self, u8> =
+        Parameter::new("Lowest note", None, 0.0..=127.0, 1.0, false, 0, Self::note_low_mut);
+}
+
+// configurations saved before the range was reduced may hold audio sample rates such as 44100
+fn deserialize_histogram_resolution<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = u64::deserialize(deserializer)?;
+    let range = &StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION.range;
+    let clamped = (value as f64).clamp(*range.start(), *range.end()) as u16;
+    if u64::from(clamped) != value {
+        warn!(
+            "histogram resolution {value} is out of range, using {clamped} instead. This setting is the number of \
+             histogram bins per second, not the audio sample rate"
+        );
+    }
+    Ok(clamped)
 }
 
 #[derive(Clone, Debug, Derivative, Serialize, Deserialize, MutGetters)]
@@ -55,6 +134,24 @@ pub struct DynamicBPMDetectionParameters {
     pub in_beat_range_weight: OnOff<f32>,
     pub normal_distribution_weight: OnOff<f32>,
     pub high_tempo_bias: OnOff<f32>,
+    // chords of more notes are collapsed into their loudest one, 0 disables it. See `ChordFilter`
+    pub max_simultaneous_onsets: u8,
+    // sensitivity of the velocity gate learned from the notes, see `VelocityGate`
+    pub auto_velocity_gate: OnOff<f32>,
+    // notes softer than that take no part in the intervals, they stay in the buffer for when it is lowered
+    pub minimum_velocity: OnOff<u8>,
+    // weight jumps are spread over that many milliseconds, 0 applies them right away. See `ParameterRamp`
+    pub parameter_ramp_ms: u16,
+    // narrows the BPM range around a locked tempo, the value is how soon and how much. See `AutoZoom`
+    pub auto_zoom: OnOff<f32>,
+    // how each criterion counts before its weight applies, see `WeightResponse`
+    pub weight_response: WeightResponse,
+    // what the age of the notes is measured against before `age_weight` applies, see `AgeAnchor`
+    pub age_anchor: AgeAnchor,
+    // an interval outside the range is taken for `ratio / 2` times the beat, halved or doubled until it fits, with each
+    // ratio in turn, the later ones credited less. 2 relates it to the beat by powers of two only, 1.5 or 3 add dotted
+    // notes, 2/3 or 4/3 triplets
+    pub fold_ratios: Vec<f32>,
 }
 
 impl Default for DynamicBPMDetectionParameters {
@@ -71,11 +168,30 @@ impl Default for DynamicBPMDetectionParameters {
             in_beat_range_weight: Self::IN_RANGE.default,
             normal_distribution_weight: Self::NORMAL_DISTRIBUTION.default,
             high_tempo_bias: Self::HIGH_TEMPO_BIAS.default,
+            max_simultaneous_onsets: Self::MAX_SIMULTANEOUS_ONSETS.default,
+            auto_velocity_gate: Self::AUTO_VELOCITY_GATE.default,
+            minimum_velocity: Self::MINIMUM_VELOCITY.default,
+            parameter_ramp_ms: Self::PARAMETER_RAMP.default,
+            auto_zoom: Self::AUTO_ZOOM.default,
+            weight_response: WeightResponse::default(),
+            age_anchor: AgeAnchor::default(),
+            fold_ratios: vec![2.0],
         }
     }
 }
 
 impl DynamicBPMDetectionParameters {
+    pub const AUTO_VELOCITY_GATE: Parameter<Self, OnOff<f32>> = Parameter::new(
+        "Auto velocity gate",
+        None,
+        0.0..=1.0,
+        0.0,
+        false,
+        OnOff::Off(0.5),
+        Self::auto_velocity_gate_mut,
+    );
+    pub const AUTO_ZOOM: Parameter<Self, OnOff<f32>> =
+        Parameter::new("Auto zoom", None, 0.0..=1.0, 0.0, false, OnOff::Off(0.5), Self::auto_zoom_mut);
     pub const BEATS_LOOKBACK: Parameter<Self, u8> =
         Parameter::new("Beats Lookback", None, 2.0..=32.0, 1.0, false, 8, Self::beats_lookback_mut);
     pub const CURRENT_VELOCITY: Parameter<Self, OnOff<f32>> = Parameter::new(
@@ -87,10 +203,17 @@ impl DynamicBPMDetectionParameters {
         OnOff::On(0.7),
         Self::velocity_current_note_weight_mut,
     );
+    /// Fold ratios offered by the settings, with their labels, see `fold_ratios`
+    pub const FOLD_RATIOS: [(f32, &'static str); 3] =
+        [(2.0, "Multiples and subdivisions"), (1.5, "Dotted notes"), (2.0 / 3.0, "Triplets")];
     pub const HIGH_TEMPO_BIAS: Parameter<Self, OnOff<f32>> =
         Parameter::new("High tempo bias", None, 0.0..=3.0, 0.0, false, OnOff::On(0.2), Self::high_tempo_bias_mut);
     pub const IN_RANGE: Parameter<Self, OnOff<f32>> =
         Parameter::new("In beat range", None, 0.0..=3.0, 0.0, false, OnOff::On(0.75), Self::in_beat_range_weight_mut);
+    pub const MAX_SIMULTANEOUS_ONSETS: Parameter<Self, u8> =
+        Parameter::new("Max simultaneous onsets", None, 0.0..=16.0, 1.0, false, 0, Self::max_simultaneous_onsets_mut);
+    pub const MINIMUM_VELOCITY: Parameter<Self, OnOff<u8>> =
+        Parameter::new("Minimum velocity", None, 0.0..=127.0, 1.0, false, OnOff::Off(10), Self::minimum_velocity_mut);
     pub const MULTIPLIER_FACTOR: Parameter<Self, OnOff<f32>> =
         Parameter::new("Multiplier", None, 0.0..=3.0, 0.0, false, OnOff::On(0.66), Self::multiplier_weight_mut);
     pub const NORMAL_DISTRIBUTION: Parameter<Self, OnOff<f32>> = Parameter::new(
@@ -111,6 +234,8 @@ impl DynamicBPMDetectionParameters {
         OnOff::On(0.6),
         Self::octave_distance_weight_mut,
     );
+    pub const PARAMETER_RAMP: Parameter<Self, u16> =
+        Parameter::new("Parameter ramp", Some("ms"), 0.0..=2000.0, 1.0, false, 250, Self::parameter_ramp_ms_mut);
     pub const PITCH_DISTANCE: Parameter<Self, OnOff<f32>> =
         Parameter::new("Pitch distance", None, 0.5..=20.0, 0.0, true, OnOff::On(0.6), Self::pitch_distance_weight_mut);
     pub const SUBDIVISION_FACTOR: Parameter<Self, OnOff<f32>> =
@@ -129,6 +254,13 @@ impl DynamicBPMDetectionParameters {
 }
 
 impl StaticBPMDetectionParameters {
+    /// Whether a note is detected, from its channel and pitch
+    #[must_use]
+    pub fn admits(&self, midi_note_on: &MidiNoteOn) -> bool {
+        self.channels.admits(midi_note_on.channel)
+            && (self.note_low > self.note_high || (self.note_low..=self.note_high).contains(&midi_note_on.note))
+    }
+
     #[must_use]
     #[inline]
     pub fn highest_bpm(&self) -> f32 {
@@ -140,10 +272,15 @@ impl StaticBPMDetectionParameters {
         (self.bpm_center - Into::<f32>::into(self.bpm_range / 2)).max(1.0)
     }
 
+    // bins are the samples of the histogram resolution from the one of the highest tempo on, see `duration_to_sample`.
+    // Histograms of the same resolution share them, whatever their range
+    fn first_sample(&self) -> usize {
+        self.duration_to_sample(bpm_to_beat_duration(self.highest_bpm()))
+    }
+
+    /// Bin holding `duration`, each one spans the half-open interval of one sample around `index_to_duration`
     pub(crate) fn duration_to_index(&self, duration: Duration, buffer_size: usize) -> Option<usize> {
-        let index = self
-            .duration_to_sample(duration)
-            .checked_sub(self.duration_to_sample(bpm_to_beat_duration(self.highest_bpm())))?;
+        let index = self.duration_to_sample(duration).checked_sub(self.first_sample())?;
         (index < buffer_size).then_some(index)
     }
 
@@ -151,13 +288,14 @@ impl StaticBPMDetectionParameters {
     pub fn buffer_size(&self) -> usize {
         bpm_to_beat_duration(self.lowest_bpm())
             .checked_sub(&bpm_to_beat_duration(self.highest_bpm()))
-            .map(|duration| duration_to_sample(self.sample_rate, duration))
+            .map(|duration| duration_to_sample(self.histogram_resolution, duration))
             .expect("programming error, bpm_lower_bound > bpm_upper_bound")
     }
 
+    /// Center of the bin, which `duration_to_index` maps back to `index`
     #[inline]
     pub(crate) fn index_to_duration(&self, index: usize) -> Duration {
-        sample_to_duration(self.sample_rate, index) + bpm_to_beat_duration(self.highest_bpm())
+        sample_to_duration(self.histogram_resolution, self.first_sample() + index)
     }
 
     #[must_use]
@@ -166,44 +304,239 @@ impl StaticBPMDetectionParameters {
         beat_duration_to_bpm(self.index_to_duration(index))
     }
 
+    /// Bins averaged with a bin on each side of it, see `histogram_smoothing`. None when the smoothing is off or its
+    /// window is narrower than a bin
+    #[must_use]
+    pub fn smoothing_half_width(&self) -> Option<usize> {
+        let OnOff::On(width_ms) = self.histogram_smoothing else {
+            return None;
+        };
+        let half_width = (width_ms / 1000.0 * f32::from(self.histogram_resolution) / 2.0).round() as usize;
+        (half_width > 0).then_some(half_width)
+    }
+
     #[must_use]
     pub fn duration_to_sample(&self, duration: Duration) -> usize {
-        duration_to_sample(self.sample_rate, duration)
+        duration_to_sample(self.histogram_resolution, duration)
     }
+
+    /// Whether `bpm` is within one of `excluded_ranges`, their bounds are included and may be given in any order
+    #[must_use]
+    pub fn excludes(&self, bpm: f32) -> bool {
+        self.excluded_ranges.iter().any(|&(low, high)| (low.min(high)..=low.max(high)).contains(&bpm))
+    }
+
+    /// Whether the excluded ranges leave no bin of the histogram to pick the estimate from
+    #[must_use]
+    pub fn excludes_every_bin(&self) -> bool {
+        !self.excluded_ranges.is_empty() && (0..self.buffer_size()).all(|index| self.excludes(self.index_to_bpm(index)))
+    }
+
+    /// Bins of the histogram whose tempo is excluded, as contiguous index ranges. Empty when they would be all of
+    /// them, the estimate is then picked as if there were no excluded range
+    #[must_use]
+    pub fn excluded_bins(&self) -> Vec<Range<usize>> {
+        let mut excluded_bins: Vec<Range<usize>> = Vec::new();
+        if self.excluded_ranges.is_empty() || self.excludes_every_bin() {
+            return excluded_bins;
+        }
+        for index in (0..self.buffer_size()).filter(|index| self.excludes(self.index_to_bpm(*index))) {
+            match excluded_bins.last_mut() {
+                Some(last) if last.end == index => last.end += 1,
+                _ => excluded_bins.push(index..index + 1),
+            }
+        }
+        excluded_bins
+    }
+}
+
+/// Combination of parameters that leaves the detection with little or nothing to work with, while each value is within
+/// its own range
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigWarning {
+    // when the detected tempo is at the top of the range, notes are dropped before a beat of the lowest BPM elapses
+    BeatsLookbackTooShort { beats_lookback: u8, minimum: u8 },
+    // in milliseconds, every interval is spread over the whole histogram
+    ImprecisionExceedsHistogram { imprecision: f32, histogram_span: f32 },
+    // in milliseconds, the normal distribution is computed far more finely than the histogram can use
+    ResolutionBelowHistogramBin { resolution: f32, bin_duration: f32 },
+    // the note range is disabled rather than leaving every note out
+    NoteRangeInverted { note_low: u8, note_high: u8 },
+    // the excluded ranges are ignored rather than leaving no tempo to detect
+    ExcludedRangesCoverRange { lowest_bpm: f32, highest_bpm: f32 },
 }
 
+impl ConfigWarning {
+    /// Labels of the parameters involved, as shown next to their sliders
+    #[must_use]
+    pub fn parameters(&self) -> [&'static str; 2] {
+        match self {
+            Self::BeatsLookbackTooShort { .. } => {
+                [DynamicBPMDetectionParameters::BEATS_LOOKBACK.label, StaticBPMDetectionParameters::BPM_RANGE.label]
+            }
+            Self::ImprecisionExceedsHistogram { .. } => {
+                [NormalDistributionConfig::IMPRECISION.label, StaticBPMDetectionParameters::BPM_RANGE.label]
+            }
+            Self::ResolutionBelowHistogramBin { .. } => {
+                [NormalDistributionConfig::RESOLUTION.label, StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION.label]
+            }
+            Self::NoteRangeInverted { .. } => {
+                [StaticBPMDetectionParameters::NOTE_LOW.label, StaticBPMDetectionParameters::NOTE_HIGH.label]
+            }
+            Self::ExcludedRangesCoverRange { .. } => {
+                [StaticBPMDetectionParameters::BPM_CENTER.label, StaticBPMDetectionParameters::BPM_RANGE.label]
+            }
+        }
+    }
+}
+
+impl Display for ConfigWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BeatsLookbackTooShort { beats_lookback, minimum } => write!(
+                f,
+                "a lookback of {beats_lookback} beats is too short for this BPM range, the lowest tempos can't be \
+                 detected once a high tempo is found. Use at least {minimum} beats or a narrower range"
+            ),
+            Self::ImprecisionExceedsHistogram { imprecision, histogram_span } => write!(
+                f,
+                "the normal distribution cutoff ({imprecision:.0}ms) is wider than the whole BPM range \
+                 ({histogram_span:.0}ms), every note is spread over all tempos"
+            ),
+            Self::ResolutionBelowHistogramBin { resolution, bin_duration } => write!(
+                f,
+                "the normal distribution resolution ({resolution}ms) is much finer than a histogram bin \
+                 ({bin_duration:.2}ms), raise it or the histogram resolution"
+            ),
+            Self::NoteRangeInverted { note_low, note_high } => write!(
+                f,
+                "the lowest note ({note_low}) is above the highest one ({note_high}), the note range is ignored and \
+                 every note is detected"
+            ),
+            Self::ExcludedRangesCoverRange { lowest_bpm, highest_bpm } => write!(
+                f,
+                "the excluded ranges cover the whole BPM range ({lowest_bpm:.2} to {highest_bpm:.2}), they are \
+                 ignored until some tempo is left to detect"
+            ),
+        }
+    }
+}
+
+/// Checks the parameters against each other, the returned warnings are empty for a usable configuration
+#[must_use]
+pub fn validate_interaction(
+    static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+    dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+) -> Vec<ConfigWarning> {
+    let mut config_warnings = Vec::new();
+
+    let tempo_ratio = static_bpm_detection_parameters.highest_bpm() / static_bpm_detection_parameters.lowest_bpm();
+    if f32::from(dynamic_bpm_detection_parameters.beats_lookback) < tempo_ratio {
+        config_warnings.push(ConfigWarning::BeatsLookbackTooShort {
+            beats_lookback: dynamic_bpm_detection_parameters.beats_lookback,
+            minimum: tempo_ratio.ceil().min(f32::from(u8::MAX)) as u8,
+        });
+    }
+
+    let normal_distribution = &static_bpm_detection_parameters.normal_distribution;
+    let histogram_span = (bpm_to_beat_duration(static_bpm_detection_parameters.lowest_bpm())
+        - bpm_to_beat_duration(static_bpm_detection_parameters.highest_bpm()))
+    .num_microseconds()
+    .unwrap_or(i64::MAX) as f32
+        / 1000.0;
+    if normal_distribution.imprecision >= histogram_span {
+        config_warnings.push(ConfigWarning::ImprecisionExceedsHistogram {
+            imprecision: normal_distribution.imprecision,
+            histogram_span,
+        });
+    }
+
+    let bin_duration = 1000.0 / f32::from(static_bpm_detection_parameters.histogram_resolution);
+    if normal_distribution.resolution < bin_duration * MIN_RESOLUTION_PER_BIN {
+        config_warnings.push(ConfigWarning::ResolutionBelowHistogramBin {
+            resolution: normal_distribution.resolution,
+            bin_duration,
+        });
+    }
+
+    if static_bpm_detection_parameters.note_low > static_bpm_detection_parameters.note_high {
+        config_warnings.push(ConfigWarning::NoteRangeInverted {
+            note_low: static_bpm_detection_parameters.note_low,
+            note_high: static_bpm_detection_parameters.note_high,
+        });
+    }
+
+    if static_bpm_detection_parameters.excludes_every_bin() {
+        config_warnings.push(ConfigWarning::ExcludedRangesCoverRange {
+            lowest_bpm: static_bpm_detection_parameters.lowest_bpm(),
+            highest_bpm: static_bpm_detection_parameters.highest_bpm(),
+        });
+    }
+
+    config_warnings
+}
+
+/// Sample holding `duration`: sample `n` holds the durations from half a sample before it, included, to half a sample
+/// after it, excluded. Durations before the first half sample are in sample 0
 #[must_use]
 pub fn duration_to_sample(sample_rate: u16, duration: Duration) -> usize {
-    (duration.num_nanoseconds().unwrap() as f64 * Asf64::get(&sample_rate) / 1_000_000_000.0).round() as usize
+    (duration.num_nanoseconds().unwrap() as f64 * parameter::Asf64::get(&sample_rate) / 1_000_000_000.0 + 0.5).floor()
+        as usize
 }
 
+/// Time of `sample`, to the nearest nanosecond, at the center of the durations `duration_to_sample` maps to it
 #[must_use]
 #[inline]
 pub fn sample_to_duration(sample_rate: u16, sample: usize) -> Duration {
-    let duration_secs = sample as f64 / Asf64::get(&sample_rate);
-    let duration_nanos = (duration_secs * 1_000_000_000.0) as i64;
+    let duration_secs = sample as f64 / parameter::Asf64::get(&sample_rate);
+    let duration_nanos = (duration_secs * 1_000_000_000.0).round() as i64;
     Duration::nanoseconds(duration_nanos)
 }
 
+// tempos the conversions are defined for. A tempo out of them, from a float edge case or a misconfiguration, is clamped,
+// and one that is not a number is taken as the lowest
+pub const MIN_BPM: f64 = 0.1;
+pub const MAX_BPM: f64 = 1000.0;
+pub const MIDI_CLOCKS_PER_BEAT: u16 = 24;
+
+fn std_beat_duration<U>(bpm: U) -> StdDuration
+where
+    U: DurationOps + Into<f64> + Copy,
+{
+    let minute = StdDuration::from_secs(60);
+    let value = bpm.into();
+    if (MIN_BPM..=MAX_BPM).contains(&value) {
+        <U as DurationOps>::saturating_div(minute, bpm)
+    } else {
+        <f64 as DurationOps>::saturating_div(minute, if value > MAX_BPM { MAX_BPM } else { MIN_BPM })
+    }
+}
+
 #[must_use]
 #[inline]
 pub fn bpm_to_beat_duration<U>(bpm: U) -> Duration
 where
-    U: DurationOps,
+    U: DurationOps + Into<f64> + Copy,
 {
-    Duration::from_std(U::div(StdDuration::from_secs(60), bpm)).unwrap()
+    Duration::from_std(std_beat_duration(bpm)).unwrap_or(Duration::MAX)
 }
 
+/// Tempo of a beat lasting `beat_duration`, within `MIN_BPM` and `MAX_BPM`
 #[must_use]
 #[inline]
 pub fn beat_duration_to_bpm(beat_duration: Duration) -> f32 {
-    let nanos = beat_duration.num_nanoseconds().unwrap();
-    60_000_000_000.0 / nanos as f32
+    // too long to be counted in nanoseconds, far below the lowest tempo anyway
+    let nanos = beat_duration.num_nanoseconds().unwrap_or(i64::MAX);
+    if nanos <= 0 {
+        return MAX_BPM as f32;
+    }
+    (60_000_000_000.0 / nanos as f32).clamp(MIN_BPM as f32, MAX_BPM as f32)
 }
 
 #[must_use]
 pub fn bpm_to_midi_clock_interval(bpm: f32) -> Duration {
-    Duration::from_std(bpm_to_beat_duration(bpm).to_std().unwrap().div_f32(24.)).unwrap()
+    Duration::from_std(<u16 as DurationOps>::saturating_div(std_beat_duration(bpm), MIDI_CLOCKS_PER_BEAT))
+        .unwrap_or(Duration::MAX)
 }
 
 #[must_use]
@@ -214,9 +547,302 @@ pub fn max_histogram_data_buffer_size() -> usize {
     let highest_bpm = (StaticBPMDetectionParameters::BPM_CENTER.range.end()
         + StaticBPMDetectionParameters::BPM_RANGE.range.end() / 2.0)
         .max(1.0);
+    let highest_histogram_resolution = *StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION.range.end() as u16;
 
     bpm_to_beat_duration(lowest_bpm)
         .checked_sub(&bpm_to_beat_duration(highest_bpm))
-        .map(|duration| duration_to_sample(48000, duration))
+        .map(|duration| duration_to_sample(highest_histogram_resolution, duration))
         .expect("programming error, bpm_lower_bound > bpm_upper_bound")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_resolution_alias() {
+        let legacy: StaticBPMDetectionParameters = serde_json::from_str(r#"{"sample_rate": 600}"#).unwrap();
+        assert_eq!(legacy.histogram_resolution, 600);
+        let current: StaticBPMDetectionParameters = serde_json::from_str(r#"{"histogram_resolution": 300}"#).unwrap();
+        assert_eq!(current.histogram_resolution, 300);
+        assert!(serde_json::to_string(&current).unwrap().contains(r#""histogram_resolution":300"#));
+    }
+
+    #[test]
+    fn test_histogram_resolution_clamping() {
+        let legacy: StaticBPMDetectionParameters = serde_json::from_str(r#"{"sample_rate": 44100}"#).unwrap();
+        assert_eq!(legacy.histogram_resolution, 2000);
+        let legacy: StaticBPMDetectionParameters = serde_json::from_str(r#"{"sample_rate": 96000}"#).unwrap();
+        assert_eq!(legacy.histogram_resolution, 2000);
+        let zero: StaticBPMDetectionParameters = serde_json::from_str(r#"{"histogram_resolution": 0}"#).unwrap();
+        assert_eq!(zero.histogram_resolution, 1);
+        assert!(legacy.buffer_size() <= max_histogram_data_buffer_size());
+    }
+
+    #[test]
+    fn test_default_parameters_have_no_warning() {
+        assert_eq!(
+            validate_interaction(&StaticBPMDetectionParameters::default(), &DynamicBPMDetectionParameters::default()),
+            vec![]
+        );
+        // 100 to 160 BPM
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 130.0,
+            bpm_range: 60,
+            ..StaticBPMDetectionParameters::default()
+        };
+        assert_eq!(
+            validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_beats_lookback_too_short() {
+        // 10 to 110 BPM
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 60.0,
+            bpm_range: 100,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 8, ..DynamicBPMDetectionParameters::default() };
+        assert_eq!(
+            validate_interaction(&static_bpm_detection_parameters, &dynamic_bpm_detection_parameters),
+            vec![ConfigWarning::BeatsLookbackTooShort { beats_lookback: 8, minimum: 11 }]
+        );
+
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 11, ..DynamicBPMDetectionParameters::default() };
+        assert_eq!(validate_interaction(&static_bpm_detection_parameters, &dynamic_bpm_detection_parameters), vec![]);
+    }
+
+    #[test]
+    fn test_imprecision_exceeds_histogram() {
+        // 149 to 151 BPM, about 5ms between the shortest and longest beat
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { bpm_center: 150.0, bpm_range: 2, ..StaticBPMDetectionParameters::default() };
+        let config_warnings =
+            validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default());
+        assert_eq!(config_warnings.len(), 1);
+        let ConfigWarning::ImprecisionExceedsHistogram { imprecision, histogram_span } = config_warnings[0] else {
+            panic!("{config_warnings:?}");
+        };
+        assert!((imprecision - 100.0).abs() < f32::EPSILON);
+        assert!((histogram_span - 5.33).abs() < 0.01, "{histogram_span}");
+    }
+
+    #[test]
+    fn test_note_range() {
+        let note = |note| MidiNoteOn { channel: 9, note, velocity: 100 };
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { note_low: 35, note_high: 40, ..StaticBPMDetectionParameters::default() };
+        assert!(static_bpm_detection_parameters.admits(&note(35)));
+        assert!(static_bpm_detection_parameters.admits(&note(40)));
+        assert!(!static_bpm_detection_parameters.admits(&note(34)));
+        assert!(!static_bpm_detection_parameters.admits(&note(41)));
+        assert_eq!(
+            validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default()),
+            vec![]
+        );
+
+        // inverted, disabled instead of dropping every note
+        let inverted =
+            StaticBPMDetectionParameters { note_low: 40, note_high: 35, ..StaticBPMDetectionParameters::default() };
+        assert!(inverted.admits(&note(0)) && inverted.admits(&note(127)));
+        assert_eq!(
+            validate_interaction(&inverted, &DynamicBPMDetectionParameters::default()),
+            vec![ConfigWarning::NoteRangeInverted { note_low: 40, note_high: 35 }]
+        );
+
+        // and with the channel filter
+        let mut channels = ChannelFilter::ALL;
+        channels.select(0, true);
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { channels, ..static_bpm_detection_parameters };
+        assert!(!static_bpm_detection_parameters.admits(&note(36)));
+    }
+
+    #[test]
+    fn test_excluded_ranges() {
+        // 70 to 110 BPM, the bounds of a range can be given in any order
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            excluded_ranges: vec![(80.0, 75.0), (100.0, 102.0)],
+            ..StaticBPMDetectionParameters::default()
+        };
+        assert!(static_bpm_detection_parameters.excludes(75.0));
+        assert!(static_bpm_detection_parameters.excludes(77.5));
+        assert!(!static_bpm_detection_parameters.excludes(90.0));
+
+        let excluded_bins = static_bpm_detection_parameters.excluded_bins();
+        assert_eq!(excluded_bins.len(), 2);
+        for index in 0..static_bpm_detection_parameters.buffer_size() {
+            let bpm = static_bpm_detection_parameters.index_to_bpm(index);
+            assert_eq!(
+                excluded_bins.iter().any(|excluded| excluded.contains(&index)),
+                static_bpm_detection_parameters.excludes(bpm),
+                "{bpm}"
+            );
+        }
+        assert_eq!(
+            validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default()),
+            vec![]
+        );
+
+        // together they leave nothing to detect, they are ignored
+        let covering = StaticBPMDetectionParameters {
+            excluded_ranges: vec![(60.0, 90.0), (90.0, 120.0)],
+            ..StaticBPMDetectionParameters::default()
+        };
+        let deserialized: StaticBPMDetectionParameters =
+            serde_json::from_str(r#"{"excluded_ranges": [[60.0, 90.0], [90.0, 120.0]]}"#).unwrap();
+        assert_eq!(deserialized, covering);
+        assert!(covering.excludes_every_bin());
+        assert!(covering.excluded_bins().is_empty());
+        assert_eq!(
+            validate_interaction(&covering, &DynamicBPMDetectionParameters::default()),
+            vec![ConfigWarning::ExcludedRangesCoverRange { lowest_bpm: 70.0, highest_bpm: 110.0 }]
+        );
+    }
+
+    #[test]
+    fn test_pathological_tempos() {
+        let shortest_beat = bpm_to_beat_duration(MAX_BPM);
+        let longest_beat = bpm_to_beat_duration(MIN_BPM);
+        assert_eq!(shortest_beat, Duration::milliseconds(60));
+        assert_eq!(longest_beat, Duration::seconds(600));
+
+        // from float edge cases and misconfigurations
+        for bpm in [
+            0.0,
+            -0.0,
+            -1.0,
+            f32::MIN_POSITIVE,
+            1e-30,
+            0.09,
+            1000.5,
+            1e30,
+            f32::MAX,
+            f32::INFINITY,
+            f32::NAN,
+            f32::NEG_INFINITY,
+            f32::MIN,
+        ] {
+            let beat_duration = bpm_to_beat_duration(bpm);
+            assert!(beat_duration >= shortest_beat && beat_duration <= longest_beat, "{bpm}: {beat_duration}");
+            let midi_clock_interval = bpm_to_midi_clock_interval(bpm);
+            assert!((midi_clock_interval * 24 - beat_duration).num_nanoseconds().unwrap().abs() < 24, "{bpm}");
+            let bpm = beat_duration_to_bpm(beat_duration);
+            assert!((MIN_BPM as f32..=MAX_BPM as f32).contains(&bpm), "{bpm}");
+        }
+        assert_eq!(bpm_to_beat_duration(0.0), longest_beat);
+        assert_eq!(bpm_to_beat_duration(f32::NAN), longest_beat);
+        assert_eq!(bpm_to_beat_duration(1e30), shortest_beat);
+        assert_eq!(bpm_to_beat_duration(u16::MAX), shortest_beat);
+        assert_eq!(bpm_to_beat_duration(120u16), Duration::milliseconds(500));
+        assert_eq!(bpm_to_midi_clock_interval(120.0), Duration::nanoseconds(20_833_333));
+
+        for beat_duration in [
+            Duration::zero(),
+            Duration::nanoseconds(-1),
+            Duration::nanoseconds(1),
+            Duration::MIN,
+            Duration::MAX,
+            Duration::days(365),
+        ] {
+            let bpm = beat_duration_to_bpm(beat_duration);
+            assert!((MIN_BPM as f32..=MAX_BPM as f32).contains(&bpm), "{beat_duration}: {bpm}");
+        }
+
+        // the parameters are checked rather than trusted, a range clamped to a single tempo leaves no span
+        for (bpm_center, bpm_range, no_span) in
+            [(0.0, u16::MAX, false), (f32::MAX, u16::MAX, true), (f32::NAN, 0, true), (f32::NEG_INFINITY, 1, false)]
+        {
+            let static_bpm_detection_parameters =
+                StaticBPMDetectionParameters { bpm_center, bpm_range, ..StaticBPMDetectionParameters::default() };
+            let config_warnings =
+                validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default());
+            assert_eq!(
+                config_warnings
+                    .iter()
+                    .any(|config_warning| matches!(config_warning, ConfigWarning::ImprecisionExceedsHistogram { .. })),
+                no_span,
+                "{bpm_center} {bpm_range}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversions_are_monotonic() {
+        // from the lowest to the highest tempo, 1% apart
+        let bpms = std::iter::successors(Some(MIN_BPM as f32), |bpm| Some(bpm * 1.01))
+            .take_while(|bpm| f64::from(*bpm) <= MAX_BPM)
+            .collect::<Vec<_>>();
+        assert!(bpms.len() > 900);
+        let beat_durations = bpms.iter().map(|bpm| bpm_to_beat_duration(*bpm)).collect::<Vec<_>>();
+        assert!(beat_durations.windows(2).all(|pair| pair[0] > pair[1]));
+        let round_trip =
+            beat_durations.iter().map(|beat_duration| beat_duration_to_bpm(*beat_duration)).collect::<Vec<_>>();
+        assert!(round_trip.windows(2).all(|pair| pair[0] < pair[1]));
+        for (bpm, round_trip) in bpms.iter().zip(&round_trip) {
+            assert!((bpm - round_trip).abs() / bpm < 1e-4, "{bpm} {round_trip}");
+        }
+        let midi_clock_intervals = bpms.iter().map(|bpm| bpm_to_midi_clock_interval(*bpm)).collect::<Vec<_>>();
+        assert!(midi_clock_intervals.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        for histogram_resolution in [1, 7, 10, 100, 333, 1000, 1999, 2000] {
+            for (bpm_center, bpm_range) in [(1.0, 1), (42.5, 17), (90.0, 40), (120.0, 100), (150.0, 1), (150.0, 100)] {
+                let parameters = StaticBPMDetectionParameters {
+                    bpm_center,
+                    bpm_range,
+                    histogram_resolution,
+                    ..StaticBPMDetectionParameters::default()
+                };
+                let buffer_size = parameters.buffer_size();
+                for index in 0..buffer_size {
+                    let duration = parameters.index_to_duration(index);
+                    assert_eq!(parameters.duration_to_index(duration, buffer_size), Some(index), "{parameters:?}");
+                }
+                assert_eq!(parameters.duration_to_index(parameters.index_to_duration(buffer_size), buffer_size), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bins_are_half_open() {
+        let parameters =
+            StaticBPMDetectionParameters { histogram_resolution: 1000, ..StaticBPMDetectionParameters::default() };
+        let buffer_size = parameters.buffer_size();
+        // a sample lasts 1ms, a bin spans from half a millisecond before its center, included, to half a millisecond
+        // after it, excluded
+        let center = parameters.index_to_duration(10);
+        let at = |offset: i64| parameters.duration_to_index(center + Duration::microseconds(offset), buffer_size);
+        assert_eq!(at(-500), Some(10));
+        assert_eq!(at(-501), Some(9));
+        assert_eq!(at(499), Some(10));
+        assert_eq!(at(500), Some(11));
+        // the first bin starts half a sample before its center, the durations shorter are out of the histogram
+        let first = parameters.index_to_duration(0);
+        assert_eq!(parameters.duration_to_index(first - Duration::microseconds(500), buffer_size), Some(0));
+        assert_eq!(parameters.duration_to_index(first - Duration::microseconds(501), buffer_size), None);
+    }
+
+    #[test]
+    fn test_resolution_below_histogram_bin() {
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { histogram_resolution: 10, ..StaticBPMDetectionParameters::default() };
+        let config_warnings =
+            validate_interaction(&static_bpm_detection_parameters, &DynamicBPMDetectionParameters::default());
+        assert_eq!(
+            config_warnings,
+            vec![ConfigWarning::ResolutionBelowHistogramBin { resolution: 0.6, bin_duration: 100.0 }]
+        );
+        assert_eq!(
+            config_warnings[0].parameters(),
+            [NormalDistributionConfig::RESOLUTION.label, StaticBPMDetectionParameters::HISTOGRAM_RESOLUTION.label]
+        );
+    }
+}
@@ -1,38 +1,167 @@
 use crate::{
+    accumulator::{Bin, Bins, WideBins},
     bpm::{beat_duration_to_bpm, bpm_to_beat_duration, sample_to_duration},
+    feel_ambiguity::{Feel, FeelAmbiguity, FeelAmbiguityConfig},
+    input_precision::{capped_resolution, InputPrecision, ResolutionFloor},
+    multi_resolution::{FineHistogram, MultiResolutionConfig, MultiResolutionHistogram},
     normal_distribution::NormalDistribution,
-    DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedMidiNoteOn,
+    tempo_source::note_density_confidence,
+    BeatPhase, DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedMidiNoteOn,
 };
 use chrono::Duration;
 use itertools::Itertools;
+use parameter::OnOff;
+use serde::{Deserialize, Serialize};
 
-use crate::bpm::max_histogram_data_buffer_size;
-use arraydeque::{ArrayDeque, Wrapping};
+use crate::{bpm::max_histogram_data_buffer_size, memory::LOW_MEMORY_NOTE_CAPACITY};
+use std::{collections::VecDeque, ops::Range};
 
 pub const NOTE_CAPACITY: usize = 10000;
 
+// added to the histogram at the seeded tempo, relative to its peak, at first
+const SEED_STRENGTH: f32 = 4.0;
+// share of its strength a seed keeps after each evaluation
+const SEED_DECAY: f32 = 0.9;
+// a weaker seed is dropped
+const MIN_SEED_STRENGTH: f32 = 0.05;
+// width of the bias around the seed, and how close the unbiased estimate must be for the notes to take over, as a share
+// of the seeded tempo
+const SEED_WIDTH: f32 = 0.02;
+
+// tempo given by the user, favoured by the evaluations until the notes agree with it or it fades out
+#[derive(Clone, Copy, Debug)]
+struct Seed {
+    bpm: f32,
+    strength: f32,
+}
+
+/// Tempo of an evaluation, with how clearly the histogram points to it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BpmEstimate {
+    pub bpm: f32,
+    // from 0 when another peak of the histogram is as high as the winning one, to 1 when there is no other peak
+    pub confidence: f32,
+}
+
+/// Beat grid the histogram is split along, see `BPMDetection::split_by_phase`
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseSplit {
+    // count at the newest note
+    pub beat_phase: BeatPhase,
+    // a note closer than that to a beat of the grid, in beats, is on-beat
+    pub window: f32,
+}
+
+impl PhaseSplit {
+    fn is_on_beat(&self, newest: Duration, timestamp: Duration) -> bool {
+        let before_newest = (newest - timestamp).num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0;
+        let beats = self.beat_phase.elapsed_beats - before_newest * f64::from(self.beat_phase.bpm) / 60.0;
+        (beats - beats.round()).abs() <= f64::from(self.window)
+    }
+}
+
 pub struct BPMDetection {
     interval_high: Duration,
     interval_low: Duration,
     normal_distribution: NormalDistribution,
-    notes: ArrayDeque<TimedMidiNoteOn, NOTE_CAPACITY, Wrapping>,
+    notes: VecDeque<TimedMidiNoteOn>,
+    // the oldest note is dropped to make room beyond that
+    note_capacity: usize,
     static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    // the configured parameters with the histogram resolution capped to the precision of the notes received
+    effective_parameters: StaticBPMDetectionParameters,
+    input_precision: InputPrecision,
+    // parameters the histogram is laid on, the configured ones unless it is the coarse one of the multi-resolution mode
+    histogram_parameters: StaticBPMDetectionParameters,
     histogram_data_points: Vec<f32>,
+    // the histogram as it is summed with `Accumulator::F64`, converted into `histogram_data_points` once summed
+    wide_data_points: WideBins,
+    // none in single-resolution mode, see `MultiResolutionConfig`
+    fine_histogram: Option<FineHistogram>,
+    // the age of the notes to keep depends on the estimate. The first one after a change of static parameters may be
+    // far from the next ones, pruning with it could drop most of the notes for good
+    skip_next_pruning: bool,
+    // a frozen copy is evaluated again and again on the same notes, none of them is dropped
+    frozen: bool,
+    seed: Option<Seed>,
+    // the histogram only takes what the parameters need, instead of what the largest ones would
+    low_memory: bool,
+    // grid the next evaluations split the histogram along, none when it stays whole
+    phase_split: Option<PhaseSplit>,
+    // shares of the histogram by whether the newer note of each interval is on a beat, empty unless it was split
+    on_beat_data_points: Vec<f32>,
+    off_beat_data_points: Vec<f32>,
+    // the shares as they are summed with `Accumulator::F64`
+    on_beat_wide_data_points: WideBins,
+    off_beat_wide_data_points: WideBins,
+    // copy of a histogram while it is smoothed, allocated along with it when the smoothing is on
+    smoothing_buffer: Vec<f32>,
+    // bins of the histogram within the excluded ranges of its parameters, weighted down when the estimate is picked
+    excluded_bins: Vec<Range<usize>>,
+    // of the last evaluation, the age of the notes is counted in its beats when `AgeAnchor::Beats` is set
+    previous_bpm: Option<f32>,
 }
 
 impl BPMDetection {
     #[must_use]
     pub fn new(static_bpm_detection_parameters: StaticBPMDetectionParameters) -> Self {
-        let mut histogram_data_points = Vec::with_capacity(max_histogram_data_buffer_size());
+        Self::with_low_memory(static_bpm_detection_parameters, false)
+    }
+
+    /// Detection for devices with little memory when `low_memory` is set: it keeps up to `LOW_MEMORY_NOTE_CAPACITY`
+    /// notes and its histogram is allocated at the size of the parameters, see `memory`
+    #[must_use]
+    pub fn with_low_memory(static_bpm_detection_parameters: StaticBPMDetectionParameters, low_memory: bool) -> Self {
+        let (note_capacity, histogram_capacity) = if low_memory {
+            (LOW_MEMORY_NOTE_CAPACITY, static_bpm_detection_parameters.buffer_size())
+        } else {
+            (NOTE_CAPACITY, max_histogram_data_buffer_size())
+        };
+        let mut histogram_data_points = Vec::with_capacity(histogram_capacity);
         histogram_data_points.resize(static_bpm_detection_parameters.buffer_size(), 0.0);
-        Self {
+        let mut bpm_detection = Self {
             interval_low: bpm_to_beat_duration(static_bpm_detection_parameters.highest_bpm()),
             interval_high: bpm_to_beat_duration(static_bpm_detection_parameters.lowest_bpm()),
             normal_distribution: NormalDistribution::new(static_bpm_detection_parameters.normal_distribution.clone()),
             histogram_data_points,
+            wide_data_points: WideBins::default(),
+            histogram_parameters: static_bpm_detection_parameters.clone(),
+            effective_parameters: static_bpm_detection_parameters.clone(),
+            input_precision: InputPrecision::default(),
+            fine_histogram: None,
             static_bpm_detection_parameters,
-            notes: ArrayDeque::new(),
-        }
+            notes: VecDeque::with_capacity(note_capacity),
+            note_capacity,
+            skip_next_pruning: false,
+            frozen: false,
+            seed: None,
+            low_memory,
+            phase_split: None,
+            on_beat_data_points: Vec::new(),
+            off_beat_data_points: Vec::new(),
+            on_beat_wide_data_points: WideBins::default(),
+            off_beat_wide_data_points: WideBins::default(),
+            smoothing_buffer: Vec::new(),
+            excluded_bins: Vec::new(),
+            previous_bpm: None,
+        };
+        bpm_detection.allocate_smoothing_buffer();
+        bpm_detection.allocate_wide_data_points();
+        bpm_detection.excluded_bins = bpm_detection.histogram_parameters.excluded_bins();
+        bpm_detection
+    }
+
+    /// Copy of the notes received so far, as a fixed input. The evaluations of the copy keep all of its notes and
+    /// don't follow a seed, so its estimate only depends on the parameters
+    #[must_use]
+    pub fn freeze(&self) -> Self {
+        let mut frozen = Self::with_low_memory(self.static_bpm_detection_parameters.clone(), self.low_memory);
+        frozen.input_precision = self.input_precision.clone();
+        frozen.previous_bpm = self.previous_bpm;
+        frozen.resize_histogram();
+        frozen.notes.extend(self.notes.iter().cloned());
+        frozen.frozen = true;
+        frozen
     }
 
     pub fn update_static_parameters(&mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) {
@@ -41,35 +170,351 @@ impl BPMDetection {
         self.interval_high = bpm_to_beat_duration(self.static_bpm_detection_parameters.lowest_bpm());
         self.normal_distribution =
             NormalDistribution::new(self.static_bpm_detection_parameters.normal_distribution.clone());
-        self.histogram_data_points.resize(0, 0.0);
-        self.histogram_data_points.resize(self.static_bpm_detection_parameters.buffer_size(), 0.0);
+        self.resize_histogram();
+        self.skip_next_pruning = true;
+    }
+
+    /// Switches to the multi-resolution mode when `config` enables it, back to a single histogram otherwise
+    pub fn set_multi_resolution(&mut self, config: &MultiResolutionConfig) {
+        self.fine_histogram = config.enabled.then(|| FineHistogram::new(config.clone()));
+        self.resize_histogram();
+    }
+
+    fn resize_histogram(&mut self) {
+        self.effective_parameters = StaticBPMDetectionParameters {
+            histogram_resolution: self.capped_resolution(),
+            ..self.static_bpm_detection_parameters.clone()
+        };
+        self.histogram_parameters = match &self.fine_histogram {
+            Some(fine_histogram) => fine_histogram.config.coarse_parameters(&self.effective_parameters),
+            None => self.effective_parameters.clone(),
+        };
+        let buffer_size = self.histogram_parameters.buffer_size();
+        self.histogram_data_points.clear();
+        if self.low_memory {
+            self.histogram_data_points.shrink_to(buffer_size);
+            self.histogram_data_points.reserve_exact(buffer_size);
+        }
+        self.histogram_data_points.resize(buffer_size, 0.0);
+        self.allocate_smoothing_buffer();
+        self.allocate_wide_data_points();
+        self.excluded_bins = self.histogram_parameters.excluded_bins();
+    }
+
+    fn capped_resolution(&self) -> u16 {
+        let configured = &self.static_bpm_detection_parameters;
+        self.input_precision.precision().map_or(configured.histogram_resolution, |precision| {
+            capped_resolution(configured.histogram_resolution, precision, configured.resolution_floor_factor)
+        })
+    }
+
+    // the precision is measured as the notes come, the histogram follows it once it moved by more than a tenth
+    fn resolution_cap_moved(&self) -> bool {
+        let applied = self.effective_parameters.histogram_resolution;
+        self.capped_resolution().abs_diff(applied) * 10 > applied
+    }
+
+    /// Measured precision of the notes received and the histogram resolution it leaves, none until enough notes were
+    /// received. The configured resolution is kept as it is, see `static_parameters`
+    #[must_use]
+    pub fn resolution_floor(&self) -> Option<ResolutionFloor> {
+        Some(ResolutionFloor {
+            precision: self.input_precision.precision()?,
+            effective_resolution: self.effective_parameters.histogram_resolution,
+        })
+    }
+
+    // weight of a bin when the estimate is picked, the histogram itself is left as the notes made it
+    fn bin_weight(&self, index: usize) -> f32 {
+        if self.excluded_bins.iter().any(|excluded| excluded.contains(&index)) {
+            self.histogram_parameters.excluded_range_factor.clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    // so the evaluations don't allocate, and in low-memory mode only what the histogram takes while the smoothing is on
+    fn allocate_smoothing_buffer(&mut self) {
+        let needed = if self.histogram_parameters.smoothing_half_width().is_some() {
+            self.histogram_data_points.len()
+        } else {
+            0
+        };
+        self.smoothing_buffer.clear();
+        if self.low_memory {
+            self.smoothing_buffer.shrink_to(needed);
+            self.smoothing_buffer.reserve_exact(needed);
+        } else if needed > 0 {
+            self.smoothing_buffer.reserve(max_histogram_data_buffer_size());
+        }
+    }
+
+    // at the size of the histogram when it is summed in f64, allocated as the smoothing buffer is
+    fn allocate_wide_data_points(&mut self) {
+        self.wide_data_points.allocate(
+            self.histogram_parameters.accumulator,
+            self.histogram_data_points.len(),
+            self.low_memory,
+        );
     }
 
+    #[must_use]
+    pub fn static_parameters(&self) -> &StaticBPMDetectionParameters {
+        &self.static_bpm_detection_parameters
+    }
+
+    #[cfg(feature = "memory-stats")]
+    #[must_use]
+    pub fn memory_stats(&self, buffered_events_capacity: usize) -> crate::memory::MemoryStats {
+        crate::memory::MemoryStats {
+            buffered_events: buffered_events_capacity,
+            notes: self.notes.len(),
+            note_capacity: self.notes.capacity(),
+            histogram_data_points: self.histogram_data_points.capacity()
+                + self.smoothing_buffer.capacity()
+                // counted in f32 bins
+                + self.wide_data_points.capacity() * 2
+                + self.fine_histogram.as_ref().map_or(0, |fine_histogram| {
+                    fine_histogram.histogram_data_points.capacity() + fine_histogram.wide_data_points.capacity() * 2
+                }),
+        }
+    }
+
+    /// Forgets the notes received so far, for when the next ones don't share their timeline
+    pub fn clear_notes(&mut self) {
+        self.notes.clear();
+    }
+
+    /// Favours `bpm` in the next evaluations, such as a tapped tempo, until the notes agree with it
+    pub fn seed(&mut self, bpm: f32) {
+        self.seed = Some(Seed { bpm, strength: SEED_STRENGTH });
+    }
+
+    /// Favours the tempo of `feel` among the candidates of an ambiguity, as a tapped tempo would be
+    pub fn choose_feel(&mut self, feel_ambiguity: &FeelAmbiguity, feel: Feel) {
+        self.seed(feel_ambiguity.bpm(feel));
+    }
+
+    /// Straight and triplet candidates when the last histogram supports 3:2 of `bpm` nearly as much, see
+    /// `FeelAmbiguity::detect`
+    #[must_use]
+    pub fn feel_ambiguity(&self, bpm: f32, feel_ambiguity_config: &FeelAmbiguityConfig) -> Option<FeelAmbiguity> {
+        FeelAmbiguity::detect(
+            &self.histogram_data_points,
+            &self.histogram_parameters,
+            bpm,
+            feel_ambiguity_config.min_ratio_strength,
+        )
+    }
+
+    /// Tempo favoured by the evaluations, none once the notes took over
+    #[must_use]
+    pub fn seeded_bpm(&self) -> Option<f32> {
+        self.seed.map(|seed| seed.bpm)
+    }
+
+    /// Notes on a channel or outside the note range of the static parameters are ignored
     pub fn receive_midi_message(&mut self, midi_message: TimedMidiNoteOn) {
+        if !self.static_bpm_detection_parameters.admits(&midi_message.midi_message) {
+            return;
+        }
+        if self.notes.len() >= self.note_capacity {
+            self.notes.pop_front();
+        }
+        self.input_precision.note(midi_message.timestamp);
         self.notes.push_back(midi_message);
     }
 
+    /// Notes timestamped beforehand and delivered at once, such as the ones of a file or of a host buffer, taken as
+    /// `receive_midi_message` would take them one by one. The evaluation relies on the notes being in order: a batch
+    /// out of order is sorted first, and notes older than the newest one already received are rejected. Returns how
+    /// many notes were admitted, after the channel and note range filters
+    pub fn receive_batch(&mut self, notes: &[TimedMidiNoteOn]) -> usize {
+        if notes.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp) {
+            return self.extend_notes(notes);
+        }
+        let mut sorted = notes.to_vec();
+        sorted.sort_by_key(|note| note.timestamp);
+        self.extend_notes(&sorted)
+    }
+
+    // `notes` are in order
+    fn extend_notes(&mut self, notes: &[TimedMidiNoteOn]) -> usize {
+        let newest = self.newest_note_timestamp();
+        let static_bpm_detection_parameters = &self.static_bpm_detection_parameters;
+        let admitted = |note: &&TimedMidiNoteOn| {
+            newest.is_none_or(|newest| note.timestamp >= newest)
+                && static_bpm_detection_parameters.admits(&note.midi_message)
+        };
+        let count = notes.iter().filter(admitted).count();
+        // only the newest ones fit, the deque never grows beyond the capacity
+        for note in notes.iter().filter(admitted) {
+            self.input_precision.note(note.timestamp);
+        }
+        let taken = count.min(self.note_capacity);
+        let dropped = (self.notes.len() + taken).saturating_sub(self.note_capacity);
+        self.notes.drain(..dropped);
+        self.notes.extend(notes.iter().filter(admitted).skip(count - taken).cloned());
+        count
+    }
+
+    /// Timestamp of the newest note, the one the next evaluation is up to date with
+    #[must_use]
+    pub fn newest_note_timestamp(&self) -> Option<Duration> {
+        self.notes.back().map(|note| note.timestamp)
+    }
+
+    /// Confidence proxy for an estimate, from the notes within its lookback window. See `note_density_confidence`
+    #[must_use]
+    pub fn note_density(&self, bpm: f32, beats_lookback: u8) -> f32 {
+        let Some(now) = self.newest_note_timestamp() else {
+            return 0.0;
+        };
+        let lookback = bpm_to_beat_duration(bpm) * i32::from(beats_lookback);
+        let notes = self.notes.iter().rev().take_while(|note| now - note.timestamp <= lookback).count();
+        note_density_confidence(notes, beats_lookback)
+    }
+
+    /// Share of the notes within the lookback window of an estimate that were played at `since` or later, see
+    /// `TempoChangeDetector`. None without notes
+    #[must_use]
+    pub fn recent_share(&self, since: Duration, bpm: f32, beats_lookback: u8) -> Option<f32> {
+        let now = self.newest_note_timestamp()?;
+        let lookback = bpm_to_beat_duration(bpm) * i32::from(beats_lookback);
+        let (recent, total) = self
+            .notes
+            .iter()
+            .rev()
+            .take_while(|note| now - note.timestamp <= lookback)
+            .fold((0, 0), |(recent, total), note| (recent + usize::from(note.timestamp >= since), total + 1));
+        Some(recent as f32 / total as f32)
+    }
+
+    /// Splits the next histograms into on-beat and off-beat energy along `phase_split`, none leaves them whole
+    pub fn split_by_phase(&mut self, phase_split: Option<PhaseSplit>) {
+        self.phase_split = phase_split;
+    }
+
+    /// On-beat and off-beat shares of the histogram of the last evaluation, which they sum to. None unless it was split
+    #[must_use]
+    pub fn phase_split_histogram(&self) -> Option<(&[f32], &[f32])> {
+        (!self.on_beat_data_points.is_empty())
+            .then_some((self.on_beat_data_points.as_slice(), self.off_beat_data_points.as_slice()))
+    }
+
+    /// Histogram of the last evaluation, over the whole range
+    #[must_use]
+    pub fn histogram_data_points(&self) -> &[f32] {
+        &self.histogram_data_points
+    }
+
+    /// Parameters of the coarse histogram and the histogram around the estimate, none in single-resolution mode
+    #[must_use]
+    pub fn multi_resolution_histogram(&self) -> Option<MultiResolutionHistogram<'_>> {
+        self.fine_histogram.as_ref().map(|fine_histogram| MultiResolutionHistogram {
+            coarse_parameters: &self.histogram_parameters,
+            fine: fine_histogram
+                .parameters
+                .as_ref()
+                .map(|parameters| (parameters, fine_histogram.histogram_data_points.as_slice())),
+        })
+    }
+
     pub fn compute_bpm(
         &mut self,
         dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
-    ) -> Option<(&[f32], f32)> {
+    ) -> Option<(&[f32], BpmEstimate)> {
+        if self.resolution_cap_moved() {
+            self.resize_histogram();
+        }
         self.histogram_data_points.fill(0.0);
+        let accumulator = self.histogram_parameters.accumulator;
+        self.wide_data_points.clear(accumulator, self.histogram_data_points.len());
+        let channel_len = if self.phase_split.is_some() { self.histogram_data_points.len() } else { 0 };
+        for (channel, wide_channel) in [
+            (&mut self.on_beat_data_points, &mut self.on_beat_wide_data_points),
+            (&mut self.off_beat_data_points, &mut self.off_beat_wide_data_points),
+        ] {
+            channel.clear();
+            channel.resize(channel_len, 0.0);
+            wide_channel.clear(accumulator, channel_len);
+        }
+        if let Some(fine_histogram) = &mut self.fine_histogram {
+            fine_histogram.prepare(&self.effective_parameters);
+        }
 
         let now = self.notes.back()?.timestamp;
         let oldest = self.notes.front()?.timestamp;
 
-        let maximum_interval = now - oldest;
+        // notes can all share a timestamp, with doubled routing or buffers of no duration
+        let maximum_interval = (now - oldest).max(Duration::microseconds(1));
+
+        // the center of the range until a first estimate
+        let beat_duration =
+            bpm_to_beat_duration(self.previous_bpm.unwrap_or(self.static_bpm_detection_parameters.bpm_center));
 
         // consider all combinations of 2 notes, in increasing time order
-        self.process_combinations(&now, &maximum_interval, dynamic_bpm_detection_parameters);
+        self.process_combinations(&now, &maximum_interval, beat_duration, dynamic_bpm_detection_parameters);
+        self.wide_data_points.finish(&mut self.histogram_data_points);
+        self.on_beat_wide_data_points.finish(&mut self.on_beat_data_points);
+        self.off_beat_wide_data_points.finish(&mut self.off_beat_data_points);
+        if let Some(fine_histogram) = &mut self.fine_histogram {
+            fine_histogram.wide_data_points.finish(&mut fine_histogram.histogram_data_points);
+        }
+        // a single NaN would win the estimate below and blank the normalized histogram in the GUI
+        let fine_histogram_data_points =
+            self.fine_histogram.iter_mut().flat_map(|fine_histogram| fine_histogram.histogram_data_points.iter_mut());
+        for value in self
+            .histogram_data_points
+            .iter_mut()
+            .chain(fine_histogram_data_points)
+            .chain(self.on_beat_data_points.iter_mut())
+            .chain(self.off_beat_data_points.iter_mut())
+        {
+            if !value.is_finite() {
+                *value = 0.0;
+            }
+        }
+
+        if let Some(half_width) = self.histogram_parameters.smoothing_half_width() {
+            for data_points in
+                [&mut self.histogram_data_points, &mut self.on_beat_data_points, &mut self.off_beat_data_points]
+            {
+                smooth(data_points, &mut self.smoothing_buffer, half_width);
+            }
+        }
 
         let most_probable_interval = self
             .histogram_data_points
             .iter()
             .enumerate()
-            .max_by(|a, b| a.1.total_cmp(b.1))
-            .map(|(index, _)| self.static_bpm_detection_parameters.index_to_duration(index))?;
-        let bpm = beat_duration_to_bpm(most_probable_interval);
+            .map(|(index, value)| (index, value * self.bin_weight(index)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| self.histogram_parameters.index_to_duration(index))?;
+        let mut bpm = beat_duration_to_bpm(most_probable_interval);
+        // the coarse peak is refined once the window is around it, the next evaluation moves it there otherwise
+        if let Some(fine_histogram) = &self.fine_histogram {
+            if let Some(fine_bpm) = fine_histogram
+                .peak()
+                .filter(|fine_bpm| fine_histogram.contains(bpm) && !self.histogram_parameters.excludes(*fine_bpm))
+            {
+                bpm = fine_bpm;
+            }
+        }
+        let bpm = self.apply_seed(bpm);
+        if let Some(fine_histogram) = &mut self.fine_histogram {
+            fine_histogram.recenter(bpm);
+        }
+        self.previous_bpm = Some(bpm);
+        let estimate = BpmEstimate {
+            bpm,
+            confidence: peak_confidence(&self.histogram_data_points, |index| self.bin_weight(index)),
+        };
+
+        if self.skip_next_pruning || self.frozen {
+            self.skip_next_pruning = false;
+            return Some((&self.histogram_data_points, estimate));
+        }
 
         let max_note_age = bpm_to_beat_duration(bpm) * i32::from(dynamic_bpm_detection_parameters.beats_lookback);
 
@@ -85,7 +530,37 @@ impl BPMDetection {
             break;
         }
 
-        Some((&self.histogram_data_points, bpm))
+        Some((&self.histogram_data_points, estimate))
+    }
+
+    // the estimate once biased towards the seed, the histogram stays as the notes made it
+    fn apply_seed(&mut self, bpm: f32) -> f32 {
+        let Some(seed) = self.seed.as_mut() else {
+            return bpm;
+        };
+        let width = seed.bpm * SEED_WIDTH;
+        if (bpm - seed.bpm).abs() <= width {
+            self.seed = None;
+            return bpm;
+        }
+        let (seed_bpm, strength) = (seed.bpm, seed.strength);
+        seed.strength *= SEED_DECAY;
+        if seed.strength < MIN_SEED_STRENGTH {
+            self.seed = None;
+        }
+
+        // the tapped tempo may have no support from the notes yet
+        let bias = strength * self.histogram_data_points.iter().copied().fold(0.0, f32::max);
+        self.histogram_data_points
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let bin_bpm = self.histogram_parameters.index_to_bpm(index);
+                let distance = (bin_bpm - seed_bpm) / width;
+                (bin_bpm, (value + bias * (-0.5 * distance * distance).exp()) * self.bin_weight(index))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(bpm, |(bin_bpm, _)| bin_bpm)
     }
 
     #[allow(forbidden_lint_groups)]
@@ -94,117 +569,1052 @@ impl BPMDetection {
         &mut self,
         newest: &Duration,
         maximum_interval: &Duration,
+        beat_duration: Duration,
         dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
     ) {
-        for (note_from, note_to) in self.notes.iter().tuple_combinations() {
+        let imprecision = Duration::nanoseconds(
+            (self.normal_distribution.normal_distribution_config.imprecision * 1_000_000.0) as i64,
+        );
+        // beat durations of the fine histogram, from its highest tempo to its lowest
+        let fine_window =
+            self.fine_histogram.as_ref().and_then(|fine_histogram| fine_histogram.parameters.as_ref()).map(
+                |parameters| {
+                    (bpm_to_beat_duration(parameters.highest_bpm()), bpm_to_beat_duration(parameters.lowest_bpm()))
+                },
+            );
+        // the softer notes are skipped rather than dropped, lowering the threshold brings them back
+        let minimum_velocity = match dynamic_bpm_detection_parameters.minimum_velocity {
+            OnOff::On(minimum_velocity) => minimum_velocity,
+            OnOff::Off(_) => 0,
+        };
+        for (note_from, note_to) in
+            self.notes.iter().filter(|note| note.midi_message.velocity >= minimum_velocity).tuple_combinations()
+        {
             let note_age = *newest - note_to.timestamp;
-            let mut interval = note_to.timestamp - note_from.timestamp;
-
-            let (interval, in_range, subdivision, multiplier) = {
-                let mut in_range: f32 = 1.0;
-                let mut subdivision = f32::NAN;
-                let mut multiplier = f32::NAN;
-
-                if interval > self.interval_high {
-                    in_range = f32::NAN;
-                    loop {
-                        interval = interval / 2;
-                        multiplier = if multiplier.is_nan() { 1.0 } else { multiplier / 2. };
-                        if interval < self.interval_high {
-                            break;
-                        }
-                    }
-                } else if interval > Duration::milliseconds(1) && interval < self.interval_low {
-                    in_range = f32::NAN;
-                    for _ in 0..=8 {
-                        interval = interval * 2;
-                        subdivision = if subdivision.is_nan() { 1.0 } else { subdivision / 2. };
-                        if interval > self.interval_low {
-                            break;
-                        }
-                    }
-                    if interval < self.interval_low {
-                        subdivision = f32::NAN;
-                    }
-                };
+            let interval = note_to.timestamp - note_from.timestamp;
+            // simultaneous notes, or notes received out of order, carry no tempo
+            if interval <= Duration::zero() {
+                continue;
+            }
 
-                (interval, in_range, multiplier, subdivision)
-            };
+            // the only cost of the split when there is none
+            let on_beat = self.phase_split.map(|phase_split| phase_split.is_on_beat(*newest, note_to.timestamp));
 
-            if self
-                .static_bpm_detection_parameters
-                .duration_to_index(interval, self.histogram_data_points.len())
-                .is_none()
+            // only intervals outside the range are folded, with each ratio in turn, see `fold_ratios`
+            let within_range = interval <= self.interval_high
+                && !(interval > Duration::milliseconds(1) && interval < self.interval_low);
+            let fold_ratios = if within_range { &[2.0][..] } else { &dynamic_bpm_detection_parameters.fold_ratios[..] };
+            for (index, fold_ratio) in fold_ratios.iter().filter(|ratio| ratio.is_finite() && **ratio > 0.0).enumerate()
             {
-                // interval is outside the range of BPM we consider, including trying to multiply or divide the interval
-                continue;
+                // later ratios stand for less likely relationships to the beat
+                let credit = 1.0 / (index + 1) as f32;
+                let scaled = Duration::nanoseconds(
+                    (interval.num_nanoseconds().unwrap_or(i64::MAX) as f64 * 2.0 / f64::from(*fold_ratio)) as i64,
+                );
+                let (interval, in_range, subdivision, multiplier) =
+                    fold_interval(scaled, self.interval_low, self.interval_high);
+                let (in_range, subdivision, multiplier) =
+                    (in_range * credit, subdivision * credit, multiplier * credit);
+
+                if self.histogram_parameters.duration_to_index(interval, self.histogram_data_points.len()).is_none() {
+                    // interval is outside the range of BPM we consider, including trying to multiply or divide the
+                    // interval
+                    continue;
+                }
+
+                let pitch_distance = 1.
+                    - f32::from({
+                        let interval = (note_to.midi_message.note % 12).abs_diff(note_from.midi_message.note % 12);
+                        interval.min(12 - interval)
+                    }) / 12.0;
+                let octave_distance =
+                    1. - f32::from((note_to.midi_message.note / 12).abs_diff(note_from.midi_message.note / 12)) / 11.; // 11 is approximately the amount of octave that can be represented by midi
+
+                let age =
+                    dynamic_bpm_detection_parameters.age_anchor.criterion(note_age, *maximum_interval, beat_duration);
+                let velocity_note_from = f32::from(note_from.midi_message.velocity) / 127.;
+                let velocity_current_note = f32::from(note_to.midi_message.velocity) / 127.;
+
+                let high_tempo_bias = {
+                    let interval_low_num = self.interval_low.num_microseconds().unwrap() as f32;
+                    let interval_high_num = self.interval_high.num_microseconds().unwrap() as f32;
+                    let note_interval_num = interval.num_microseconds().unwrap() as f32;
+                    1.0 - (note_interval_num - interval_low_num) / (interval_high_num - interval_low_num)
+                };
+
+                let intensity: f32 = [
+                    (velocity_current_note, dynamic_bpm_detection_parameters.velocity_current_note_weight.weight()),
+                    (velocity_note_from, dynamic_bpm_detection_parameters.velocity_note_from_weight.weight()),
+                    (age, dynamic_bpm_detection_parameters.age_weight.weight()),
+                    (octave_distance, dynamic_bpm_detection_parameters.octave_distance_weight.weight()),
+                    (pitch_distance, dynamic_bpm_detection_parameters.pitch_distance_weight.weight()),
+                    (multiplier, dynamic_bpm_detection_parameters.multiplier_weight.weight()),
+                    (subdivision, dynamic_bpm_detection_parameters.subdivision_weight.weight()),
+                    (in_range, dynamic_bpm_detection_parameters.in_beat_range_weight.weight()),
+                    (high_tempo_bias, dynamic_bpm_detection_parameters.high_tempo_bias.weight()),
+                ]
+                .into_iter()
+                .map(|(c, w)| dynamic_bpm_detection_parameters.weight_response.apply(c) * w)
+                .filter(|criteria| criteria.is_finite() && *criteria > 0.0)
+                .sum();
+
+                let normal_weight = dynamic_bpm_detection_parameters.normal_distribution_weight.weight();
+                spread_interval(
+                    self.wide_data_points.bins(&mut self.histogram_data_points),
+                    &self.histogram_parameters,
+                    &self.normal_distribution,
+                    interval,
+                    imprecision,
+                    intensity,
+                    normal_weight,
+                );
+                if let Some(on_beat) = on_beat {
+                    spread_interval(
+                        if on_beat {
+                            self.on_beat_wide_data_points.bins(&mut self.on_beat_data_points)
+                        } else {
+                            self.off_beat_wide_data_points.bins(&mut self.off_beat_data_points)
+                        },
+                        &self.histogram_parameters,
+                        &self.normal_distribution,
+                        interval,
+                        imprecision,
+                        intensity,
+                        normal_weight,
+                    );
+                }
+                if let (
+                    Some(FineHistogram {
+                        parameters: Some(parameters), histogram_data_points, wide_data_points, ..
+                    }),
+                    Some((shortest, longest)),
+                ) = (&mut self.fine_histogram, fine_window)
+                {
+                    // most intervals don't reach the window
+                    if interval + imprecision >= shortest && interval - imprecision <= longest {
+                        spread_interval(
+                            wide_data_points.bins(histogram_data_points),
+                            parameters,
+                            &self.normal_distribution,
+                            interval,
+                            imprecision,
+                            intensity,
+                            normal_weight,
+                        );
+                    }
+                }
             }
+        }
+    }
+}
+
+// moving average over the bins within `half_width` of each one, fewer at the edges. The shares of a split histogram are
+// smoothed alike and still sum to it. `buffer` holds the values before smoothing, its capacity is kept
+fn smooth(data_points: &mut [f32], buffer: &mut Vec<f32>, half_width: usize) {
+    buffer.clear();
+    buffer.extend_from_slice(data_points);
+    let len = buffer.len();
+    let mut sum: f32 = buffer.iter().take(half_width).sum();
+    for (index, value) in data_points.iter_mut().enumerate() {
+        if let Some(entering) = buffer.get(index + half_width) {
+            sum += entering;
+        }
+        if let Some(leaving) = index.checked_sub(half_width + 1) {
+            sum -= buffer[leaving];
+        }
+        let window = (index + half_width).min(len - 1) + 1 - index.saturating_sub(half_width);
+        // the running sum drifts below zero around empty bins
+        *value = (sum / window as f32).max(0.0);
+    }
+}
 
-            let pitch_distance = 1.
-                - f32::from({
-                    let interval = (note_to.midi_message.note % 12).abs_diff(note_from.midi_message.note % 12);
-                    interval.min(12 - interval)
-                }) / 12.0;
-            let octave_distance =
-                1. - f32::from((note_to.midi_message.note / 12).abs_diff(note_from.midi_message.note / 12)) / 11.; // 11 is approximately the amount of octave that can be represented by midi
-
-            let age = (*maximum_interval - note_age).num_microseconds().unwrap() as f32
-                / maximum_interval.num_microseconds().unwrap() as f32;
-            let velocity_note_from = f32::from(note_from.midi_message.velocity) / 127.;
-            let velocity_current_note = f32::from(note_to.midi_message.velocity) / 127.;
-
-            let high_tempo_bias = {
-                let interval_low_num = self.interval_low.num_microseconds().unwrap() as f32;
-                let interval_high_num = self.interval_high.num_microseconds().unwrap() as f32;
-                let note_interval_num = interval.num_microseconds().unwrap() as f32;
-                1.0 - (note_interval_num - interval_low_num) / (interval_high_num - interval_low_num)
+// 1 minus the ratio of the second highest peak to the highest one, 0 when the histogram is empty. A plateau is a
+// single peak. Each bin counts as its value times its `weight`
+fn peak_confidence(histogram_data_points: &[f32], weight: impl Fn(usize) -> f32) -> f32 {
+    let weighted = |index: usize| histogram_data_points.get(index).map(|value| value * weight(index));
+    let (mut highest, mut second) = (0.0f32, 0.0f32);
+    for index in 0..histogram_data_points.len() {
+        let value = weighted(index).unwrap_or_default();
+        let rises = index == 0 || weighted(index - 1).is_some_and(|previous| value > previous);
+        let falls = !matches!(weighted(index + 1), Some(next) if next > value);
+        if !(rises && falls) {
+            continue;
+        }
+        if value > highest {
+            second = highest;
+            highest = value;
+        } else if value > second {
+            second = value;
+        }
+    }
+    if highest > 0.0 {
+        1.0 - second / highest
+    } else {
+        0.0
+    }
+}
+
+// `interval` halved or doubled until it is within the range, with its in range, multiplier and subdivision criteria.
+// The last two are 1 for the first fold and halved with each next one, not a number when there was none, and so is
+// the first when there was one
+fn fold_interval(mut interval: Duration, interval_low: Duration, interval_high: Duration) -> (Duration, f32, f32, f32) {
+    let mut in_range: f32 = 1.0;
+    let mut subdivision = f32::NAN;
+    let mut multiplier = f32::NAN;
+
+    if interval > interval_high {
+        in_range = f32::NAN;
+        loop {
+            interval = interval / 2;
+            multiplier = if multiplier.is_nan() { 1.0 } else { multiplier / 2. };
+            if interval < interval_high {
+                break;
+            }
+        }
+    } else if interval > Duration::milliseconds(1) && interval < interval_low {
+        in_range = f32::NAN;
+        for _ in 0..=8 {
+            interval = interval * 2;
+            subdivision = if subdivision.is_nan() { 1.0 } else { subdivision / 2. };
+            if interval > interval_low {
+                break;
+            }
+        }
+        if interval < interval_low {
+            subdivision = f32::NAN;
+        }
+    };
+
+    (interval, in_range, multiplier, subdivision)
+}
+
+// adds the contribution of `interval` to the bins within the imprecision around it, in the type they are summed in
+fn spread_interval(
+    bins: Bins<'_>,
+    histogram_parameters: &StaticBPMDetectionParameters,
+    normal_distribution: &NormalDistribution,
+    interval: Duration,
+    imprecision: Duration,
+    intensity: f32,
+    normal_weight: f32,
+) {
+    match bins {
+        Bins::F32(histogram_data_points) => spread_interval_in(
+            histogram_data_points,
+            histogram_parameters,
+            normal_distribution,
+            interval,
+            imprecision,
+            intensity,
+            normal_weight,
+        ),
+        Bins::F64(histogram_data_points) => spread_interval_in(
+            histogram_data_points,
+            histogram_parameters,
+            normal_distribution,
+            interval,
+            imprecision,
+            intensity,
+            normal_weight,
+        ),
+    }
+}
+
+// one point of the normal distribution per bin
+fn spread_interval_in<B: Bin>(
+    histogram_data_points: &mut [B],
+    histogram_parameters: &StaticBPMDetectionParameters,
+    normal_distribution: &NormalDistribution,
+    interval: Duration,
+    imprecision: Duration,
+    intensity: f32,
+    normal_weight: f32,
+) {
+    let duration_per_sample = sample_to_duration(histogram_parameters.histogram_resolution, 1);
+    let mut timestamp = -imprecision;
+    while timestamp <= imprecision {
+        if let Some(index) = histogram_parameters.duration_to_index(timestamp + interval, histogram_data_points.len()) {
+            let normal_value = if normal_weight > 0.0 {
+                (normal_distribution[timestamp]
+                    * 9.0
+                    // the normal distribution will have values up to 4, this adjusts to be around the same range
+                    * 2.0
+                    + 1.0)
+                    .log10()
+                    * normal_weight
+            } else {
+                0.0
             };
 
-            let intensity: f32 = [
-                (velocity_current_note, dynamic_bpm_detection_parameters.velocity_current_note_weight.weight()),
-                (velocity_note_from, dynamic_bpm_detection_parameters.velocity_note_from_weight.weight()),
-                (age, dynamic_bpm_detection_parameters.age_weight.weight()),
-                (octave_distance, dynamic_bpm_detection_parameters.octave_distance_weight.weight()),
-                (pitch_distance, dynamic_bpm_detection_parameters.pitch_distance_weight.weight()),
-                (multiplier, dynamic_bpm_detection_parameters.multiplier_weight.weight()),
-                (subdivision, dynamic_bpm_detection_parameters.subdivision_weight.weight()),
-                (in_range, dynamic_bpm_detection_parameters.in_beat_range_weight.weight()),
-                (high_tempo_bias, dynamic_bpm_detection_parameters.high_tempo_bias.weight()),
-            ]
-            .into_iter()
-            // We normalize the value to be between 1 and 10, so log10 will give a value between 0 and 1,
-            .map(|(c, w)| (c * 9.0 + 1.0).log10() * w)
-            .filter(|criteria| criteria.is_finite() && *criteria > 0.0)
-            .sum();
-
-            let imprecision = Duration::nanoseconds(
-                (self.normal_distribution.normal_distribution_config.imprecision * 1_000_000.0) as i64,
-            );
-            let duration_per_sample = sample_to_duration(self.static_bpm_detection_parameters.sample_rate, 1);
-            let mut timestamp = -imprecision;
-            let normal_weight = dynamic_bpm_detection_parameters.normal_distribution_weight.weight();
-            while timestamp <= imprecision {
-                if let Some(index) = self
-                    .static_bpm_detection_parameters
-                    .duration_to_index(timestamp + interval, self.histogram_data_points.len())
-                {
-                    let normal_value = if normal_weight > 0.0 {
-                        (self.normal_distribution[timestamp]
-                            * 9.0
-                            // the normal distribution will have values up to 4, this adjusts to be around the same range
-                            * 2.0
-                            + 1.0)
-                            .log10()
-                            * dynamic_bpm_detection_parameters.normal_distribution_weight.weight()
-                    } else {
-                        0.0
-                    };
-
-                    self.histogram_data_points[index] += 10.0f32.powf(intensity + normal_value);
-                };
+            let value = 10.0f32.powf(intensity + normal_value);
+            debug_assert!(value.is_finite(), "intensity {intensity}, normal value {normal_value}");
+            if value.is_finite() {
+                histogram_data_points[index].add(value);
+            }
+        };
+
+        timestamp += duration_per_sample;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{midi_messages::MidiNoteOn, Accumulator, AgeAnchor};
+
+    fn notes_at(bpm: f32, count: i32) -> impl Iterator<Item = TimedMidiNoteOn> {
+        (0..count).map(move |beat| TimedMidiNoteOn {
+            timestamp: bpm_to_beat_duration(bpm) * beat,
+            midi_message: MidiNoteOn { channel: 0, note: 60, velocity: 100 },
+        })
+    }
+
+    #[test]
+    fn test_resolution_floor() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            histogram_resolution: 2000,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+        // 119.91 BPM, with the timestamps rounded to the millisecond as some drivers deliver them
+        let notes = (0..80).map(|beat| TimedMidiNoteOn {
+            timestamp: Duration::milliseconds(beat * 500_370 / 1000),
+            midi_message: MidiNoteOn { channel: 0, note: 60, velocity: 100 },
+        });
+        for (index, note) in notes.enumerate() {
+            bpm_detection.receive_midi_message(note);
+            if index == 16 {
+                assert_eq!(bpm_detection.resolution_floor(), None);
+                let (histogram_data_points, _) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+                assert_eq!(histogram_data_points.len(), static_bpm_detection_parameters.buffer_size());
+            }
+        }
+
+        let (histogram_data_points, BpmEstimate { bpm, .. }) =
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let capped =
+            StaticBPMDetectionParameters { histogram_resolution: 1000, ..static_bpm_detection_parameters.clone() };
+        assert_eq!(histogram_data_points.len(), capped.buffer_size());
+        assert!((bpm - 119.91).abs() < 0.5, "{bpm}");
+        assert_eq!(
+            bpm_detection.resolution_floor(),
+            Some(ResolutionFloor { precision: std::time::Duration::from_millis(1), effective_resolution: 1000 })
+        );
+        // the configured resolution is kept, and applies again without the floor
+        assert_eq!(bpm_detection.static_parameters().histogram_resolution, 2000);
+        bpm_detection.update_static_parameters(StaticBPMDetectionParameters {
+            resolution_floor_factor: 0.0,
+            ..static_bpm_detection_parameters.clone()
+        });
+        let (histogram_data_points, _) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert_eq!(histogram_data_points.len(), static_bpm_detection_parameters.buffer_size());
+    }
+
+    #[test]
+    fn test_static_change_keeps_notes_until_next_estimate() {
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { bpm_center: 90.0, bpm_range: 40, ..StaticBPMDetectionParameters::default() };
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 8, ..DynamicBPMDetectionParameters::default() };
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+
+        // 7 beats at 90 BPM, all of them are kept under the initial parameters
+        for note in notes_at(90.0, 8) {
+            bpm_detection.receive_midi_message(note);
+        }
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 90.0).abs() < 1.0, "{bpm}");
+        assert_eq!(bpm_detection.notes.len(), 8);
+
+        // 130 to 170 BPM, the first estimate doesn't relate to the notes
+        bpm_detection.update_static_parameters(StaticBPMDetectionParameters {
+            bpm_center: 150.0,
+            ..static_bpm_detection_parameters
+        });
+        bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert_eq!(bpm_detection.notes.len(), 8);
+
+        // afterwards, notes older than the lookback of the new estimate are dropped as usual
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(bpm >= 130.0, "{bpm}");
+        assert!(bpm_detection.notes.len() < 8);
+    }
+
+    #[test]
+    fn test_smooth() {
+        let mut buffer = Vec::with_capacity(7);
+        let mut data_points = [0.0, 0.0, 0.0, 6.0, 0.0, 0.0, 3.0];
+        smooth(&mut data_points, &mut buffer, 1);
+        for (value, expected) in data_points.iter().zip([0.0, 0.0, 2.0, 2.0, 2.0, 1.0, 1.5]) {
+            assert!((value - expected).abs() < 1e-6, "{data_points:?}");
+        }
+        assert_eq!(buffer.capacity(), 7);
+
+        // a window wider than the histogram averages all of it
+        let mut data_points = [1.0, 2.0, 3.0];
+        smooth(&mut data_points, &mut buffer, 5);
+        assert!(data_points.iter().all(|value| (value - 2.0).abs() < 1e-6), "{data_points:?}");
+    }
+
+    #[test]
+    fn test_histogram_smoothing() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            histogram_resolution: 2000,
+            ..StaticBPMDetectionParameters::default()
+        };
+        assert_eq!(static_bpm_detection_parameters.smoothing_half_width(), None);
+        let smoothed_parameters = StaticBPMDetectionParameters {
+            histogram_smoothing: OnOff::On(5.0),
+            ..static_bpm_detection_parameters.clone()
+        };
+        // 5ms at 2000 bins per second
+        assert_eq!(smoothed_parameters.smoothing_half_width(), Some(5));
+
+        let mut raw = BPMDetection::with_low_memory(static_bpm_detection_parameters, true);
+        let mut smoothed = BPMDetection::with_low_memory(smoothed_parameters, true);
+        assert_eq!(raw.smoothing_buffer.capacity(), 0);
+        assert_eq!(smoothed.smoothing_buffer.capacity(), smoothed.histogram_data_points.len());
+        for note in notes_at(120.0, 12) {
+            raw.receive_midi_message(note.clone());
+            smoothed.receive_midi_message(note);
+        }
+        let (raw_histogram, raw_estimate) = raw.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let (smoothed_histogram, smoothed_estimate) = smoothed.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((smoothed_estimate.bpm - 120.0).abs() < 0.5, "{smoothed_estimate:?}");
+        let peak = |histogram: &[f32]| histogram.iter().copied().fold(0.0, f32::max);
+        assert!(peak(smoothed_histogram) < peak(raw_histogram), "{raw_estimate:?}");
+        // the evaluation reuses the buffer
+        assert_eq!(smoothed.smoothing_buffer.capacity(), smoothed.histogram_data_points.len());
+
+        smoothed.update_static_parameters(StaticBPMDetectionParameters::default());
+        assert_eq!(smoothed.smoothing_buffer.capacity(), 0);
+    }
+
+    #[test]
+    fn test_excluded_ranges() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        // 70 to 110 BPM
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        let excluded_parameters = StaticBPMDetectionParameters {
+            excluded_ranges: vec![(72.0, 78.0)],
+            ..static_bpm_detection_parameters.clone()
+        };
+        let attenuated_parameters =
+            StaticBPMDetectionParameters { excluded_range_factor: 1.0, ..excluded_parameters.clone() };
+
+        // a steady artifact at 75 BPM over fewer notes played at 100 BPM
+        let notes = notes_at(75.0, 24).chain(notes_at(100.0, 8)).collect_vec();
+        let mut bpm_detections =
+            [static_bpm_detection_parameters, excluded_parameters, attenuated_parameters].map(BPMDetection::new);
+        for bpm_detection in &mut bpm_detections {
+            assert_eq!(bpm_detection.receive_batch(&notes), 32);
+        }
+        let [plain, excluded, attenuated] = bpm_detections
+            .each_mut()
+            .map(|bpm_detection| bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap());
+
+        assert!((plain.1.bpm - 75.0).abs() < 1.0, "{:?}", plain.1);
+        assert!((excluded.1.bpm - 100.0).abs() < 1.0, "{:?}", excluded.1);
+        // a factor of 1 leaves the excluded bins as they are
+        assert_eq!(attenuated.1.bpm.to_bits(), plain.1.bpm.to_bits());
+        // the histogram is displayed as the notes made it
+        assert_eq!(
+            excluded.0.iter().map(|value| value.to_bits()).collect_vec(),
+            plain.0.iter().map(|value| value.to_bits()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        });
+        for note in notes_at(120.0, 12) {
+            bpm_detection.receive_midi_message(note);
+        }
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+
+        // the seed wins over the notes until it fades out
+        bpm_detection.seed(80.0);
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 80.0).abs() < 1.0, "{bpm}");
+        assert_eq!(bpm_detection.seeded_bpm(), Some(80.0));
+        for _ in 0..100 {
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters);
+        }
+        assert_eq!(bpm_detection.seeded_bpm(), None);
+
+        // the notes agree with the seed, they take over
+        bpm_detection.seed(120.5);
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+        assert_eq!(bpm_detection.seeded_bpm(), None);
+    }
+
+    #[test]
+    fn test_freeze() {
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 4, ..DynamicBPMDetectionParameters::default() };
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        });
+        for note in notes_at(120.0, 12) {
+            bpm_detection.receive_midi_message(note);
+        }
+        bpm_detection.seed(90.0);
+        let mut frozen = bpm_detection.freeze();
+        assert_eq!(frozen.notes.len(), 12);
+        assert_eq!(frozen.seeded_bpm(), None);
+
+        let (histogram_data_points, BpmEstimate { bpm, .. }) =
+            frozen.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let histogram_data_points = histogram_data_points.to_vec();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+        // unlike the live detection, the notes out of the lookback stay
+        bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(bpm_detection.notes.len() < 12);
+        assert_eq!(frozen.notes.len(), 12);
+        assert_eq!(frozen.compute_bpm(&dynamic_bpm_detection_parameters).unwrap().0, histogram_data_points);
+    }
+
+    #[test]
+    fn test_estimate_is_the_winning_bin() {
+        for histogram_resolution in [10, 75, 300, 2000] {
+            let static_bpm_detection_parameters =
+                StaticBPMDetectionParameters { histogram_resolution, ..StaticBPMDetectionParameters::default() };
+            let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+            for note in notes_at(97.3, 12) {
+                bpm_detection.receive_midi_message(note);
+            }
+            let (histogram_data_points, BpmEstimate { bpm, .. }) =
+                bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+            let argmax = histogram_data_points
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(index, _)| index)
+                .unwrap();
+            // as the GUI labels the bin
+            assert_eq!(bpm.to_bits(), static_bpm_detection_parameters.index_to_bpm(argmax).to_bits());
+        }
+    }
+
+    #[test]
+    fn test_accumulators_agree() {
+        let estimate = |accumulator: Accumulator| {
+            let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+                accumulator,
+                ..StaticBPMDetectionParameters::default()
+            });
+            for note in notes_at(97.3, 12) {
+                bpm_detection.receive_midi_message(note);
+            }
+            let (histogram_data_points, BpmEstimate { bpm, confidence }) =
+                bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+            (histogram_data_points.to_vec(), bpm, confidence)
+        };
+        let (narrow, narrow_bpm, narrow_confidence) = estimate(Accumulator::F32);
+        let (wide, wide_bpm, wide_confidence) = estimate(Accumulator::F64);
+        assert_eq!(narrow_bpm.to_bits(), wide_bpm.to_bits());
+        assert!((narrow_confidence - wide_confidence).abs() < 1e-4, "{narrow_confidence} {wide_confidence}");
+        // within the rounding of the f32 sums
+        let highest = narrow.iter().copied().fold(0.0, f32::max);
+        assert!(highest > 0.0);
+        assert_eq!(narrow.len(), wide.len());
+        for (narrow, wide) in narrow.iter().zip(&wide) {
+            assert!((narrow - wide).abs() <= highest * 1e-5, "{narrow} {wide}");
+        }
+    }
+
+    #[test]
+    fn test_accumulators_agree_on_every_histogram() {
+        // the fine histogram and the shares of the split histogram, summed along with the main one
+        let histograms = |accumulator: Accumulator| {
+            let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+                accumulator,
+                ..StaticBPMDetectionParameters::default()
+            });
+            bpm_detection
+                .set_multi_resolution(&MultiResolutionConfig { enabled: true, ..MultiResolutionConfig::default() });
+            bpm_detection.split_by_phase(Some(PhaseSplit {
+                beat_phase: BeatPhase { elapsed_beats: 11.5, bpm: 97.3, bar_length: 4.0 },
+                window: 0.1,
+            }));
+            // eighth notes, every other one on a beat
+            for note in notes_at(97.3 * 2.0, 24) {
+                bpm_detection.receive_midi_message(note);
+            }
+            // the fine histogram is laid around the first estimate
+            for _ in 0..2 {
+                bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+            }
+            let wide_len = bpm_detection.on_beat_wide_data_points.len()
+                + bpm_detection.off_beat_wide_data_points.len()
+                + bpm_detection.fine_histogram.as_ref().unwrap().wide_data_points.len();
+            let (on_beat, off_beat) = bpm_detection.phase_split_histogram().unwrap();
+            let (_, fine) = bpm_detection.multi_resolution_histogram().unwrap().fine.unwrap();
+            (wide_len, [on_beat.to_vec(), off_beat.to_vec(), fine.to_vec()])
+        };
+        let (narrow_len, narrow) = histograms(Accumulator::F32);
+        let (wide_len, wide) = histograms(Accumulator::F64);
+        assert_eq!(narrow_len, 0);
+        assert_eq!(wide_len, wide.iter().map(Vec::len).sum::<usize>());
+        for (narrow, wide) in narrow.iter().zip(&wide) {
+            let highest = narrow.iter().copied().fold(0.0, f32::max);
+            assert!(highest > 0.0);
+            assert_eq!(narrow.len(), wide.len());
+            for (narrow, wide) in narrow.iter().zip(wide) {
+                assert!((narrow - wide).abs() <= highest * 1e-5, "{narrow} {wide}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_data_points() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::with_low_memory(static_bpm_detection_parameters.clone(), true);
+        assert_eq!(bpm_detection.wide_data_points.len(), 0);
+        bpm_detection.update_static_parameters(StaticBPMDetectionParameters {
+            accumulator: Accumulator::F64,
+            ..static_bpm_detection_parameters.clone()
+        });
+        assert_eq!(bpm_detection.wide_data_points.len(), bpm_detection.histogram_data_points.len());
+        bpm_detection.update_static_parameters(static_bpm_detection_parameters);
+        assert_eq!(bpm_detection.wide_data_points.len(), 0);
+        assert_eq!(bpm_detection.wide_data_points.capacity(), 0);
+    }
+
+    #[test]
+    fn test_duplicate_timestamps() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        });
+        // each note is received twice, as with doubled routing
+        for note in notes_at(120.0, 12) {
+            let duplicate =
+                TimedMidiNoteOn { timestamp: note.timestamp, midi_message: MidiNoteOn { ..note.midi_message } };
+            bpm_detection.receive_midi_message(note);
+            bpm_detection.receive_midi_message(duplicate);
+        }
+        let (histogram_data_points, BpmEstimate { bpm, .. }) =
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(histogram_data_points.iter().all(|value| value.is_finite()));
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+    }
+
+    #[test]
+    fn test_zero_duration_buffer() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+        // a whole buffer of notes stamped with the same time
+        for note in 60..68 {
+            bpm_detection.receive_midi_message(TimedMidiNoteOn {
+                timestamp: Duration::zero(),
+                midi_message: MidiNoteOn { channel: 0, note, velocity: 100 },
+            });
+        }
+        let (histogram_data_points, BpmEstimate { bpm, .. }) =
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(histogram_data_points.iter().all(|value| value.is_finite()));
+        assert!(
+            bpm >= static_bpm_detection_parameters.lowest_bpm() && bpm <= static_bpm_detection_parameters.highest_bpm(),
+            "{bpm}"
+        );
+
+        // the notes that follow are detected as usual, once the buffer is out of the lookback
+        for note in notes_at(120.0, 12) {
+            bpm_detection
+                .receive_midi_message(TimedMidiNoteOn { timestamp: note.timestamp + Duration::seconds(10), ..note });
+        }
+        bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let (histogram_data_points, BpmEstimate { bpm, .. }) =
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(histogram_data_points.iter().all(|value| value.is_finite()));
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+    }
+
+    #[test]
+    fn test_multi_resolution() {
+        // no note is dropped, both see the same ones whatever their first estimate
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 32, ..DynamicBPMDetectionParameters::default() };
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            bpm_range: 100,
+            histogram_resolution: 1000,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut single = BPMDetection::new(static_bpm_detection_parameters.clone());
+        let mut multi = BPMDetection::new(static_bpm_detection_parameters.clone());
+        multi.set_multi_resolution(&MultiResolutionConfig { enabled: true, ..MultiResolutionConfig::default() });
+        assert!(multi.multi_resolution_histogram().unwrap().fine.is_none());
+        for (note, same_note) in notes_at(123.0, 12).zip(notes_at(123.0, 12)) {
+            single.receive_midi_message(note);
+            multi.receive_midi_message(same_note);
+        }
+
+        // the first estimate is the coarse one
+        let (coarse, BpmEstimate { bpm: coarse_bpm, .. }) =
+            multi.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!(coarse.len() < static_bpm_detection_parameters.buffer_size() / 5);
+        assert!((coarse_bpm - 123.0).abs() < 3.0, "{coarse_bpm}");
+
+        let (_, BpmEstimate { bpm: single_bpm, .. }) = single.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let (_, BpmEstimate { bpm: multi_bpm, .. }) = multi.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((multi_bpm - single_bpm).abs() < 1e-3, "{multi_bpm} {single_bpm}");
+
+        // the fine histogram is the part of the single one in its window
+        let (fine_parameters, fine) = multi.multi_resolution_histogram().unwrap().fine.unwrap();
+        assert!(fine_parameters.lowest_bpm() < single_bpm && single_bpm < fine_parameters.highest_bpm());
+        let offset = static_bpm_detection_parameters
+            .duration_to_index(fine_parameters.index_to_duration(0), single.histogram_data_points().len())
+            .unwrap();
+        for (fine_value, single_value) in fine.iter().zip(&single.histogram_data_points()[offset..]) {
+            assert!((fine_value - single_value).abs() <= single_value.abs() * 1e-5, "{fine_value} {single_value}");
+        }
+
+        // the window follows a change of tempo, the estimate is refined on the next evaluation
+        multi.clear_notes();
+        for note in notes_at(90.0, 12) {
+            multi.receive_midi_message(note);
+        }
+        let (_, BpmEstimate { bpm: coarse_bpm, .. }) = multi.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((coarse_bpm - 90.0).abs() < 3.0, "{coarse_bpm}");
+        let (_, BpmEstimate { bpm, .. }) = multi.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 90.0).abs() < 0.5, "{bpm}");
+        let (fine_parameters, _) = multi.multi_resolution_histogram().unwrap().fine.unwrap();
+        assert!(fine_parameters.lowest_bpm() < 90.0 && 90.0 < fine_parameters.highest_bpm());
+    }
+
+    #[test]
+    fn test_note_density() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters);
+        assert!(bpm_detection.note_density(120.0, 8) < f32::EPSILON);
+
+        // a note per second, 5 of them within 4 seconds: every beat at 60 BPM, every other beat at 120 BPM
+        for note in notes_at(60.0, 12) {
+            bpm_detection.receive_midi_message(note);
+        }
+        assert!((bpm_detection.note_density(60.0, 4) - 1.0).abs() < f32::EPSILON);
+        assert!((bpm_detection.note_density(120.0, 8) - 5.0 / 8.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_recent_share() {
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+        assert_eq!(bpm_detection.recent_share(Duration::zero(), 120.0, 8), None);
+
+        // 12 beats at 120 BPM then 6 at 100 BPM from 6s to 9s, 8 beats of lookback at 100 BPM reach 4.8s back
+        let notes = notes_at(120.0, 12).chain(
+            notes_at(100.0, 6)
+                .map(|note| TimedMidiNoteOn { timestamp: note.timestamp + Duration::milliseconds(6000), ..note }),
+        );
+        for note in notes {
+            bpm_detection.receive_midi_message(note);
+        }
+        let change_point = Duration::milliseconds(6000);
+        // the notes from 4.2s on, 3 before the change point and 6 from it
+        let share = bpm_detection.recent_share(change_point, 100.0, 8).unwrap();
+        assert!((share - 6.0 / 9.0).abs() < f32::EPSILON, "{share}");
+
+        // a shorter lookback only holds recent notes
+        let share = bpm_detection.recent_share(change_point, 100.0, 4).unwrap();
+        assert!((share - 1.0).abs() < f32::EPSILON, "{share}");
+        let share = bpm_detection.recent_share(Duration::milliseconds(20_000), 100.0, 8).unwrap();
+        assert!(share.abs() < f32::EPSILON, "{share}");
+    }
+
+    #[test]
+    fn test_receive_batch() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let notes = notes_at(90.0, 16).collect::<Vec<_>>();
+
+        let mut one_by_one = BPMDetection::new(static_bpm_detection_parameters.clone());
+        for note in notes.iter().cloned() {
+            one_by_one.receive_midi_message(note);
+        }
+        let mut batched = BPMDetection::new(static_bpm_detection_parameters);
+        assert_eq!(batched.receive_batch(&notes[..10]), 10);
+        assert_eq!(batched.receive_batch(&notes[10..]), 6);
+        assert!(batched.notes.iter().eq(&one_by_one.notes));
+
+        let (_, BpmEstimate { bpm: expected, .. }) = one_by_one.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let (_, BpmEstimate { bpm, .. }) = batched.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert_eq!(bpm.to_bits(), expected.to_bits());
+    }
+
+    #[test]
+    fn test_receive_batch_order() {
+        let notes = notes_at(120.0, 8).collect::<Vec<_>>();
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+
+        // sorted before being taken
+        let mut shuffled = notes[..4].to_vec();
+        shuffled.reverse();
+        assert_eq!(bpm_detection.receive_batch(&shuffled), 4);
+        assert!(bpm_detection.notes.iter().map(|note| note.timestamp).eq(notes[..4].iter().map(|note| note.timestamp)));
 
-                timestamp += duration_per_sample;
+        // the notes older than the newest one received are rejected, one at the same time is taken
+        assert_eq!(bpm_detection.receive_batch(&notes[1..]), 5);
+        assert_eq!(bpm_detection.notes.len(), 9);
+        assert_eq!(bpm_detection.newest_note_timestamp(), Some(notes[7].timestamp));
+    }
+
+    #[test]
+    fn test_receive_batch_capacity() {
+        let mut bpm_detection = BPMDetection::with_low_memory(StaticBPMDetectionParameters::default(), true);
+        let capacity = bpm_detection.note_capacity;
+        let notes = notes_at(120.0, capacity as i32 + 10).collect::<Vec<_>>();
+        bpm_detection.receive_batch(&notes[..20]);
+        assert_eq!(bpm_detection.receive_batch(&notes[20..]), capacity - 10);
+        assert_eq!(bpm_detection.notes.len(), capacity);
+        assert_eq!(bpm_detection.notes.front().map(|note| note.timestamp), Some(notes[10].timestamp));
+        assert_eq!(bpm_detection.newest_note_timestamp(), notes.last().map(|note| note.timestamp));
+    }
+
+    #[test]
+    fn test_channel_filter() {
+        let mut static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { bpm_center: 120.0, ..StaticBPMDetectionParameters::default() };
+        // drums on channel 10, an arpeggiator on channel 1 at another tempo
+        static_bpm_detection_parameters.channels.select(9, true);
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters);
+        let arpeggio = notes_at(137.0, 24)
+            .map(|note| TimedMidiNoteOn { timestamp: note.timestamp + Duration::milliseconds(7), ..note });
+        let drums = notes_at(120.0, 16)
+            .map(|note| TimedMidiNoteOn { midi_message: MidiNoteOn { channel: 9, ..note.midi_message }, ..note });
+        for note in arpeggio.chain(drums).sorted_by_key(|note| note.timestamp) {
+            bpm_detection.receive_midi_message(note);
+        }
+        assert_eq!(bpm_detection.notes.len(), 16);
+        let (_, BpmEstimate { bpm, .. }) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+    }
+
+    #[test]
+    fn test_choose_feel() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            bpm_range: 120,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let feel_ambiguity = FeelAmbiguity { straight_bpm: 100.0, triplet_bpm: 150.0, ratio_strength: 0.9 };
+        for (feel, expected) in [(Feel::Triplet, 150.0), (Feel::Straight, 100.0)] {
+            let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+            for note in notes_at(100.0, 16) {
+                bpm_detection.receive_midi_message(note);
+            }
+            bpm_detection.choose_feel(&feel_ambiguity, feel);
+            let (_, BpmEstimate { bpm, .. }) =
+                bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+            assert!((bpm - expected).abs() < expected * SEED_WIDTH, "{feel}: {bpm}");
+        }
+    }
+
+    #[test]
+    fn test_fold_ratios() {
+        // a bassline of dotted eighths at 110 BPM, none of its intervals is a beat or a power of two of it
+        let dotted_eighths = || notes_at(110.0 / 0.75, 16);
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 110.0,
+            bpm_range: 40,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let detect = |fold_ratios: Vec<f32>| {
+            let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+            for note in dotted_eighths() {
+                bpm_detection.receive_midi_message(note);
+            }
+            let dynamic_bpm_detection_parameters =
+                DynamicBPMDetectionParameters { fold_ratios, ..DynamicBPMDetectionParameters::default() };
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap().1.bpm
+        };
+
+        // folded by powers of two, the intervals land off the beat
+        let bpm = detect(vec![2.0]);
+        assert!((bpm - 110.0).abs() > 3.0, "{bpm}");
+        let bpm = detect(vec![2.0, 1.5, 3.0]);
+        assert!((bpm - 110.0).abs() < 1.5, "{bpm}");
+    }
+
+    // evaluated with and without the split, along a grid of 120 BPM where the newest note is at `newest_beat`
+    fn split<I: Iterator<Item = TimedMidiNoteOn>>(
+        notes: impl Fn() -> I,
+        newest_beat: f64,
+    ) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut whole = BPMDetection::new(static_bpm_detection_parameters.clone());
+        let mut split = BPMDetection::new(static_bpm_detection_parameters);
+        split.split_by_phase(Some(PhaseSplit {
+            beat_phase: BeatPhase { elapsed_beats: newest_beat, bpm: 120.0, bar_length: 4.0 },
+            window: 0.1,
+        }));
+        for (note, same_note) in notes().zip(notes()) {
+            whole.receive_midi_message(note);
+            split.receive_midi_message(same_note);
+        }
+        assert!(whole.phase_split_histogram().is_none());
+        let (histogram_data_points, _) = whole.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+        let histogram_data_points = histogram_data_points.to_vec();
+        assert!(whole.phase_split_histogram().is_none());
+
+        // the split leaves the histogram as it is
+        assert_eq!(split.compute_bpm(&dynamic_bpm_detection_parameters).unwrap().0, histogram_data_points);
+        let (on_beat, off_beat) = split.phase_split_histogram().unwrap();
+        (histogram_data_points, on_beat.to_vec(), off_beat.to_vec())
+    }
+
+    #[test]
+    fn test_age_anchor() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters {
+            age_anchor: AgeAnchor::Beats { half_life: 4.0 },
+            ..DynamicBPMDetectionParameters::default()
+        };
+        // both ends of the range, without an octave of either in it
+        for expected in [85.0, 150.0] {
+            let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+            for note in notes_at(expected, 16) {
+                bpm_detection.receive_midi_message(note);
+            }
+            // the first evaluation counts the beats at the center of the range, the next ones at its estimate
+            for _ in 0..2 {
+                let (_, BpmEstimate { bpm, .. }) =
+                    bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+                assert!((bpm - expected).abs() < 1.0, "{expected}: {bpm}");
             }
         }
     }
+
+    #[test]
+    fn test_minimum_velocity() {
+        let beat = bpm_to_beat_duration(100.0);
+        let clean = || notes_at(100.0, 24);
+        // a ghost note a third of a beat after each one, as a drummer's soft strokes
+        let ghost_notes = clean().take(23).map(|note| TimedMidiNoteOn {
+            timestamp: note.timestamp + beat / 3,
+            midi_message: MidiNoteOn { velocity: 3, ..note.midi_message },
+        });
+        let with_ghost_notes = clean().interleave(ghost_notes).collect::<Vec<_>>();
+        let detect = |notes: Vec<TimedMidiNoteOn>, minimum_velocity: OnOff<u8>| {
+            let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+            for note in notes {
+                bpm_detection.receive_midi_message(note);
+            }
+            let dynamic_bpm_detection_parameters =
+                DynamicBPMDetectionParameters { minimum_velocity, ..DynamicBPMDetectionParameters::default() };
+            let (histogram_data_points, BpmEstimate { bpm, .. }) =
+                bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).unwrap();
+            (histogram_data_points.iter().map(|value| value.to_bits()).collect::<Vec<_>>(), bpm)
+        };
+
+        let (clean_histogram, clean_bpm) = detect(clean().collect(), OnOff::On(10));
+        assert!((clean_bpm - 100.0).abs() < 0.5, "{clean_bpm}");
+        let (gated_histogram, gated_bpm) = detect(with_ghost_notes.clone(), OnOff::On(10));
+        assert_eq!(gated_bpm.to_bits(), clean_bpm.to_bits(), "{gated_bpm} {clean_bpm}");
+        assert_eq!(gated_histogram, clean_histogram);
+        // switched off, the ghost notes are counted again
+        let (ungated_histogram, _) = detect(with_ghost_notes, OnOff::Off(10));
+        assert_ne!(ungated_histogram, clean_histogram);
+    }
+
+    #[test]
+    fn test_phase_split_sums_to_histogram() {
+        let (histogram_data_points, on_beat, off_beat) = split(|| notes_at(240.0, 24), 11.5);
+        assert_eq!(on_beat.len(), histogram_data_points.len());
+        assert_eq!(off_beat.len(), histogram_data_points.len());
+        for ((value, on_beat), off_beat) in histogram_data_points.iter().zip(&on_beat).zip(&off_beat) {
+            assert!((on_beat + off_beat - value).abs() <= value * 1e-4, "{on_beat} + {off_beat} != {value}");
+        }
+        assert!(on_beat.iter().any(|value| *value > 0.0));
+        assert!(off_beat.iter().any(|value| *value > 0.0));
+
+        // back to a whole histogram
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+        bpm_detection.split_by_phase(Some(PhaseSplit {
+            beat_phase: BeatPhase { elapsed_beats: 0.0, bpm: 120.0, bar_length: 4.0 },
+            window: 0.1,
+        }));
+        bpm_detection.receive_midi_message(notes_at(120.0, 1).next().unwrap());
+        bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default());
+        assert!(bpm_detection.phase_split_histogram().is_some());
+        bpm_detection.split_by_phase(None);
+        bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default());
+        assert!(bpm_detection.phase_split_histogram().is_none());
+    }
+
+    #[test]
+    fn test_phase_split_syncopation() {
+        let off_beat_share = |(histogram_data_points, _, off_beat): (Vec<f32>, Vec<f32>, Vec<f32>)| {
+            off_beat.iter().sum::<f32>() / histogram_data_points.iter().sum::<f32>()
+        };
+
+        // every note on a beat
+        let straight = off_beat_share(split(|| notes_at(120.0, 16), 15.0));
+        assert!(straight < 0.01, "{straight}");
+
+        // a downbeat per bar, every other note is on the offbeat eighth
+        let syncopated = || {
+            notes_at(240.0, 32).filter(|note| {
+                let eighth = (note.timestamp.num_microseconds().unwrap() as f32 / 250_000.0).round() as i32;
+                eighth % 2 == 1 || eighth % 8 == 0
+            })
+        };
+        let syncopated = off_beat_share(split(syncopated, 15.5));
+        assert!(syncopated > 0.6, "{syncopated}");
+    }
+
+    #[test]
+    fn test_peak_confidence() {
+        assert_eq!(peak_confidence(&[], |_| 1.0).to_bits(), 0.0f32.to_bits());
+        assert_eq!(peak_confidence(&[0.0; 8], |_| 1.0).to_bits(), 0.0f32.to_bits());
+        // the slopes of a peak aren't peaks, nor are the bins of a plateau
+        assert_eq!(peak_confidence(&[0.0, 1.0, 3.0, 2.0, 1.0, 0.0], |_| 1.0).to_bits(), 1.0f32.to_bits());
+        assert_eq!(peak_confidence(&[0.0, 3.0, 3.0, 3.0, 0.0], |_| 1.0).to_bits(), 1.0f32.to_bits());
+        // a peak at the edge
+        assert!((peak_confidence(&[4.0, 2.0, 1.0, 3.0, 0.0], |_| 1.0) - 0.25).abs() < 1e-6);
+        // an excluded bin is no longer a peak
+        let excluded_edge = |index| if index == 0 { 0.0 } else { 1.0 };
+        assert!((peak_confidence(&[4.0, 2.0, 1.0, 3.0, 0.0], excluded_edge) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(peak_confidence(&[1.0, 2.0, 0.0, 2.0, 1.0], |_| 1.0).to_bits(), 0.0f32.to_bits());
+
+        let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+        for note in notes_at(100.0, 16) {
+            bpm_detection.receive_midi_message(note);
+        }
+        let (_, estimate) = bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+        assert!(estimate.confidence > 0.0 && estimate.confidence <= 1.0, "{estimate:?}");
+    }
 }
@@ -1,5 +1,67 @@
+use crate::{
+    BarPosition, BeatPhase, BpmEstimate, ConfigWarning, FeelAmbiguity, MultiResolutionHistogram, ResolutionFloor,
+    SessionSummary, StaticBPMDetectionParameters, TempoBand, TimedMidiNoteOn,
+};
+
 pub trait BPMDetectionReceiver: Clone + Send + Sync + 'static {
-    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], detected_bpm: f32);
+    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], estimate: BpmEstimate);
 
     fn receive_daw_bpm(&self, bpm: f32);
+
+    // tempo of the MIDI clock received, when it is tracked, none while it is being counted after a pause
+    fn receive_clock_bpm(&self, _bpm: Option<f32>) {}
+
+    // every note fed to the detection, for display purposes
+    fn receive_note(&self, _timed_midi_note_on: &TimedMidiNoteOn) {}
+
+    // sent whenever the parameters change, empty once the configuration is usable again
+    fn receive_config_warnings(&self, _config_warnings: &[ConfigWarning]) {}
+
+    // beats and bars counted since the playing started, none once the count was reset
+    fn receive_bar_position(&self, _bar_position: Option<BarPosition>) {}
+
+    // fractional count sent along with the bar position, to predict the next beats
+    fn receive_beat_phase(&self, _beat_phase: Option<BeatPhase>) {}
+
+    // velocity under which notes are left out by the learned gate, none when nothing is gated
+    fn receive_velocity_gate(&self, _threshold: Option<u8>) {}
+
+    // narrowed parameters the next histograms are computed with, none when back to the configured ones
+    fn receive_auto_zoom(&self, _zoomed: Option<&StaticBPMDetectionParameters>) {}
+
+    // tempo tapped by the user, none once the detection took over
+    fn receive_tapped_tempo(&self, _bpm: Option<f32>) {}
+
+    // histogram and tempo of the frozen notes, sent again when the parameters change, none once they are cleared
+    fn receive_frozen_bpm_histogram_data(&self, _frozen: Option<(&[f32], f32)>) {}
+
+    // stability of the tempo since the session started or the notes were cleared, sent after every evaluation
+    fn receive_session_summary(&self, _session_summary: SessionSummary) {}
+
+    // share of the notes within the lookback window played since the last tempo change, sent after every evaluation.
+    // None when no change was found since the notes were cleared
+    fn receive_note_freshness(&self, _freshness: Option<f32>) {}
+
+    // straight and triplet candidates when the histogram supports both, sent after every evaluation, none when it
+    // doesn't
+    fn receive_feel_ambiguity(&self, _feel_ambiguity: Option<FeelAmbiguity>) {}
+
+    // band of the configured tempo bands the output tempo is in, sent after every evaluation, none when it is in none
+    fn receive_tempo_band(&self, _tempo_band: Option<TempoBand>) {}
+
+    // sent before the histogram in multi-resolution mode, which is then the coarse one, none otherwise
+    fn receive_multi_resolution_histogram(&self, _multi_resolution_histogram: Option<MultiResolutionHistogram<'_>>) {}
+
+    // half width of the window around the beats of the estimated grid, in beats, when the histogram is to be split
+    // into on-beat and off-beat energy. Asked before each evaluation, none leaves the histogram whole
+    fn phase_split_window(&self) -> Option<f32> {
+        None
+    }
+
+    // on-beat and off-beat shares of the histogram, sent before it, none when it wasn't split
+    fn receive_phase_split_histogram(&self, _phase_split: Option<(&[f32], &[f32])>) {}
+
+    // measured precision of the note timestamps and the histogram resolution it leaves, sent before the histogram,
+    // none until enough notes were received
+    fn receive_resolution_floor(&self, _resolution_floor: Option<ResolutionFloor>) {}
 }
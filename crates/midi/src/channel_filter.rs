@@ -0,0 +1,108 @@
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub const MIDI_CHANNELS: u8 = 16;
+
+/// MIDI channels the detection listens to. None selected is the same as all of them, which is the default. In the
+/// configuration, a list of channels numbered from 1 as on most devices, empty for all
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelFilter {
+    // bit n is channel n, starting at 0 as in the MIDI messages
+    selected: u16,
+}
+
+impl ChannelFilter {
+    pub const ALL: Self = Self { selected: 0 };
+
+    /// `channel` starts at 0, as in the MIDI messages
+    #[must_use]
+    pub fn admits(self, channel: u8) -> bool {
+        self.selected == 0 || self.is_selected(channel)
+    }
+
+    #[must_use]
+    pub fn is_selected(self, channel: u8) -> bool {
+        channel < MIDI_CHANNELS && self.selected & (1 << channel) != 0
+    }
+
+    pub fn select(&mut self, channel: u8, selected: bool) {
+        if channel >= MIDI_CHANNELS {
+            return;
+        }
+        if selected {
+            self.selected |= 1 << channel;
+        } else {
+            self.selected &= !(1 << channel);
+        }
+    }
+
+    #[must_use]
+    pub fn admits_all(self) -> bool {
+        self.selected == 0
+    }
+}
+
+impl Serialize for ChannelFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer
+            .collect_seq((0..MIDI_CHANNELS).filter(|channel| self.is_selected(*channel)).map(|channel| channel + 1))
+    }
+}
+
+impl<'de> Deserialize<'de> for ChannelFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut channel_filter = Self::ALL;
+        for channel in Vec::<u8>::deserialize(deserializer)? {
+            if (1..=MIDI_CHANNELS).contains(&channel) {
+                channel_filter.select(channel - 1, true);
+            } else {
+                warn!("MIDI channel {channel} is out of range, channels are numbered from 1 to {MIDI_CHANNELS}");
+            }
+        }
+        Ok(channel_filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admits() {
+        let mut channel_filter = ChannelFilter::default();
+        assert!((0..MIDI_CHANNELS).all(|channel| channel_filter.admits(channel)));
+
+        channel_filter.select(9, true);
+        assert!(channel_filter.admits(9));
+        assert!(!channel_filter.admits(0));
+        assert!(!channel_filter.admits(MIDI_CHANNELS));
+
+        // unselecting the last one admits all channels again
+        channel_filter.select(9, false);
+        assert!(channel_filter.admits_all());
+        assert!(channel_filter.admits(0));
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut channel_filter = ChannelFilter::ALL;
+        assert_eq!(serde_json::to_string(&channel_filter).unwrap(), "[]");
+        channel_filter.select(0, true);
+        channel_filter.select(9, true);
+        assert_eq!(serde_json::to_string(&channel_filter).unwrap(), "[1,10]");
+        assert_eq!(serde_json::from_str::<ChannelFilter>("[1,10]").unwrap(), channel_filter);
+
+        // out of range channels are left out
+        assert_eq!(serde_json::from_str::<ChannelFilter>("[0,10,17]").unwrap(), {
+            let mut channel_filter = ChannelFilter::ALL;
+            channel_filter.select(9, true);
+            channel_filter
+        });
+    }
+}
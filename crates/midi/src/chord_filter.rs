@@ -0,0 +1,212 @@
+use chrono::Duration;
+use instant::Instant;
+use std::time::Duration as StdDuration;
+
+use crate::TimedMidiNoteOn;
+
+// note-ons closer than that to the first note of a chord belong to it
+pub const CHORD_WINDOW: StdDuration = StdDuration::from_millis(15);
+
+struct PendingNote {
+    note: TimedMidiNoteOn,
+    // cleared by a note-off. Applications that don't receive note-offs consider every note as held
+    held: bool,
+}
+
+/// Collapses chords into a single onset before they reach `BPMDetection`, so that their tones don't register as
+/// intervals close to zero. Note-ons starting within `CHORD_WINDOW` of each other form a chord; when more than
+/// `max_simultaneous_onsets` of them are still held once it is complete, only the loudest is kept, at the time the
+/// chord started. 0 lets every note through
+#[derive(Default)]
+pub struct ChordFilter {
+    pending: Vec<PendingNote>,
+    // wall clock time the pending chord started at, to complete it when no other note follows
+    pending_since: Option<Instant>,
+}
+
+impl ChordFilter {
+    pub fn note_on(
+        &mut self,
+        max_simultaneous_onsets: u8,
+        note: TimedMidiNoteOn,
+        now: Instant,
+        mut emit: impl FnMut(TimedMidiNoteOn),
+    ) {
+        if max_simultaneous_onsets == 0 {
+            // the filter may just have been disabled
+            self.complete(max_simultaneous_onsets, &mut emit);
+            emit(note);
+            return;
+        }
+        let window = Duration::from_std(CHORD_WINDOW).unwrap_or_default();
+        if self.pending.first().is_some_and(|first| note.timestamp - first.note.timestamp > window) {
+            self.complete(max_simultaneous_onsets, &mut emit);
+        }
+        if self.pending.is_empty() {
+            self.pending_since = Some(now);
+        }
+        self.pending.push(PendingNote { note, held: true });
+    }
+
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        if let Some(pending_note) = self.pending.iter_mut().rev().find(|pending_note| {
+            pending_note.held
+                && pending_note.note.midi_message.channel == channel
+                && pending_note.note.midi_message.note == note
+        }) {
+            pending_note.held = false;
+        }
+    }
+
+    /// Emits the pending chord once no more note can join it
+    pub fn flush(&mut self, max_simultaneous_onsets: u8, now: Instant, mut emit: impl FnMut(TimedMidiNoteOn)) {
+        if self.pending_since.is_some_and(|pending_since| now.saturating_duration_since(pending_since) > CHORD_WINDOW) {
+            self.complete(max_simultaneous_onsets, &mut emit);
+        }
+    }
+
+    /// Emits the pending chord right away, no more notes can join it
+    pub fn finish(&mut self, max_simultaneous_onsets: u8, mut emit: impl FnMut(TimedMidiNoteOn)) {
+        self.complete(max_simultaneous_onsets, &mut emit);
+    }
+
+    /// How long to wait before the pending chord can be flushed, if there is one
+    #[must_use]
+    pub fn flush_in(&self, now: Instant) -> Option<StdDuration> {
+        self.pending_since.map(|pending_since| {
+            (CHORD_WINDOW + StdDuration::from_millis(1)).saturating_sub(now.saturating_duration_since(pending_since))
+        })
+    }
+
+    fn complete(&mut self, max_simultaneous_onsets: u8, emit: &mut impl FnMut(TimedMidiNoteOn)) {
+        self.pending_since = None;
+        let held = self.pending.iter().filter(|pending_note| pending_note.held).count();
+        if max_simultaneous_onsets == 0 || held <= usize::from(max_simultaneous_onsets) {
+            for pending_note in self.pending.drain(..) {
+                emit(pending_note.note);
+            }
+            return;
+        }
+        let Some(start) = self.pending.first().map(|pending_note| pending_note.note.timestamp) else {
+            return;
+        };
+        // the first of the loudest notes
+        if let Some(loudest) = self.pending.drain(..).map(|pending_note| pending_note.note).reduce(|loudest, note| {
+            if note.midi_message.velocity > loudest.midi_message.velocity {
+                note
+            } else {
+                loudest
+            }
+        }) {
+            emit(TimedMidiNoteOn { timestamp: start, ..loudest });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_messages::MidiNoteOn;
+
+    fn note(millis: i64, note: u8, velocity: u8) -> TimedMidiNoteOn {
+        TimedMidiNoteOn {
+            timestamp: Duration::milliseconds(millis),
+            midi_message: MidiNoteOn { channel: 0, note, velocity },
+        }
+    }
+
+    fn summary(notes: &[TimedMidiNoteOn]) -> Vec<(i64, u8, u8)> {
+        notes
+            .iter()
+            .map(|note| (note.timestamp.num_milliseconds(), note.midi_message.note, note.midi_message.velocity))
+            .collect()
+    }
+
+    // plays the notes as if they arrived in real time, then waits for the last chord to complete
+    fn play(max_simultaneous_onsets: u8, notes: Vec<TimedMidiNoteOn>) -> Vec<TimedMidiNoteOn> {
+        let start = Instant::now();
+        let mut chord_filter = ChordFilter::default();
+        let mut onsets = Vec::new();
+        let mut now = start;
+        for note in notes {
+            now = start + note.timestamp.to_std().unwrap();
+            chord_filter.flush(max_simultaneous_onsets, now, |onset| onsets.push(onset));
+            chord_filter.note_on(max_simultaneous_onsets, note, now, |onset| onsets.push(onset));
+        }
+        chord_filter.flush(max_simultaneous_onsets, now + CHORD_WINDOW * 2, |onset| onsets.push(onset));
+        onsets
+    }
+
+    fn block_chords() -> Vec<TimedMidiNoteOn> {
+        vec![
+            note(0, 60, 80),
+            note(2, 64, 100),
+            note(3, 67, 90),
+            note(500, 62, 70),
+            note(501, 65, 70),
+            note(504, 69, 70),
+            note(505, 72, 70),
+        ]
+    }
+
+    #[test]
+    fn test_block_chords() {
+        // at the time of the first note, with the loudest tone
+        assert_eq!(summary(&play(2, block_chords())), vec![(0, 64, 100), (500, 62, 70)]);
+        // enough voices allowed
+        assert_eq!(play(4, block_chords()).len(), 7);
+        // disabled
+        assert_eq!(summary(&play(0, block_chords())), summary(&block_chords()));
+    }
+
+    #[test]
+    fn test_rolled_chord_over_the_window() {
+        let step = CHORD_WINDOW.as_millis() as i64 + 1;
+        let rolled_chord = || (0..4).map(|n| note(n * step, 60 + n as u8 * 4, 90)).collect::<Vec<_>>();
+        assert_eq!(summary(&play(1, rolled_chord())), summary(&rolled_chord()));
+    }
+
+    #[test]
+    fn test_interleaved_melodies() {
+        // two voices meeting on the beat, and a third one in between
+        let melodies = || {
+            (0..8)
+                .flat_map(|beat| {
+                    [
+                        note(beat * 500, 48 + beat as u8, 90),
+                        note(beat * 500 + 3, 72 - beat as u8, 90),
+                        note(beat * 500 + 125, 60, 70),
+                    ]
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&play(2, melodies())), summary(&melodies()));
+        // with a single voice allowed, the notes meeting on the beat are a chord
+        assert_eq!(play(1, melodies()).len(), 16);
+    }
+
+    #[test]
+    fn test_released_notes() {
+        let start = Instant::now();
+        let mut chord_filter = ChordFilter::default();
+        let mut onsets = Vec::new();
+        // a grace note released before the chord is complete is not one of its voices
+        chord_filter.note_on(2, note(0, 59, 60), start, |onset| onsets.push(onset));
+        chord_filter.note_off(0, 59);
+        chord_filter.note_on(2, note(5, 60, 100), start, |onset| onsets.push(onset));
+        chord_filter.note_on(2, note(6, 64, 90), start, |onset| onsets.push(onset));
+        assert_eq!(chord_filter.flush_in(start), Some(CHORD_WINDOW + StdDuration::from_millis(1)));
+        chord_filter.flush(2, start + CHORD_WINDOW * 2, |onset| onsets.push(onset));
+        assert_eq!(summary(&onsets), vec![(0, 59, 60), (5, 60, 100), (6, 64, 90)]);
+        assert_eq!(chord_filter.flush_in(start), None);
+
+        // a note-off for a note of a previous chord changes nothing
+        onsets.clear();
+        chord_filter.note_off(0, 60);
+        for n in 0..3 {
+            chord_filter.note_on(2, note(1000 + n, 60 + n as u8, 80), start, |onset| onsets.push(onset));
+        }
+        chord_filter.flush(2, start + CHORD_WINDOW * 2, |onset| onsets.push(onset));
+        assert_eq!(summary(&onsets), vec![(1000, 60, 80)]);
+    }
+}
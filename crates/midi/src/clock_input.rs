@@ -0,0 +1,140 @@
+//! Tempo of the MIDI clock received along with the notes, as hardware sequencers send it. It is reported next to the
+//! detected tempo, as the one of the DAW is, the ticks never take part in the detection
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub const TICKS_PER_QUARTER: usize = 24;
+// in milliseconds, slower than 5 BPM, a longer gap between ticks means the clock was stopped
+const MAX_TICK_INTERVAL: i64 = 500;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockInputConfig {
+    // the ticks are dropped as they are received unless enabled
+    pub enabled: bool,
+    // the tempo is averaged over that many ticks, and reported each time as many more were received
+    pub averaged_ticks: usize,
+}
+
+impl Default for ClockInputConfig {
+    fn default() -> Self {
+        Self { enabled: false, averaged_ticks: TICKS_PER_QUARTER }
+    }
+}
+
+/// What the last tick tells about the tempo of the clock
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockTick {
+    // the ticks of the next report are still being counted
+    Counting,
+    Tempo(f32),
+    // first tick after the clock was stopped, the tempo is unknown until enough ticks are received again
+    Restarted,
+}
+
+/// Times of the last ticks, timed as the notes are
+#[derive(Clone, Debug)]
+pub struct ClockTempo {
+    ticks: VecDeque<Duration>,
+    averaged_ticks: usize,
+    // since the last report
+    counted_ticks: usize,
+}
+
+impl ClockTempo {
+    #[must_use]
+    pub fn new(clock_input_config: &ClockInputConfig) -> Self {
+        let averaged_ticks = clock_input_config.averaged_ticks.max(1);
+        Self { ticks: VecDeque::with_capacity(averaged_ticks + 1), averaged_ticks, counted_ticks: 0 }
+    }
+
+    pub fn tick(&mut self, at: Duration) -> ClockTick {
+        let restarted =
+            self.ticks.back().is_some_and(|&previous| at - previous > Duration::milliseconds(MAX_TICK_INTERVAL));
+        if restarted {
+            self.clear();
+        }
+        self.ticks.push_back(at);
+        if self.ticks.len() > self.averaged_ticks + 1 {
+            self.ticks.pop_front();
+        }
+        if restarted {
+            return ClockTick::Restarted;
+        }
+        self.counted_ticks += 1;
+        if self.ticks.len() <= self.averaged_ticks || self.counted_ticks < self.averaged_ticks {
+            return ClockTick::Counting;
+        }
+        self.counted_ticks = 0;
+        let span = (at - self.ticks[0]).num_microseconds().unwrap_or(i64::MAX) as f32 / 1_000_000.0;
+        if span <= 0.0 {
+            return ClockTick::Counting;
+        }
+        let quarters = self.averaged_ticks as f32 / TICKS_PER_QUARTER as f32;
+        ClockTick::Tempo(quarters * 60.0 / span)
+    }
+
+    pub fn clear(&mut self) {
+        self.ticks.clear();
+        self.counted_ticks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(clock_tempo: &mut ClockTempo, from: Duration, bpm: f64, count: usize) -> Vec<ClockTick> {
+        let interval = 60_000_000.0 / bpm / TICKS_PER_QUARTER as f64;
+        (0..count)
+            .map(|tick| clock_tempo.tick(from + Duration::microseconds((tick as f64 * interval) as i64)))
+            .collect()
+    }
+
+    fn tempos(ticks: &[ClockTick]) -> Vec<f32> {
+        ticks
+            .iter()
+            .filter_map(|tick| match tick {
+                ClockTick::Tempo(bpm) => Some(*bpm),
+                ClockTick::Counting | ClockTick::Restarted => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_steady_clock() {
+        let mut clock_tempo = ClockTempo::new(&ClockInputConfig::default());
+        let reported = ticks(&mut clock_tempo, Duration::zero(), 120.0, 4 * TICKS_PER_QUARTER + 1);
+        // once per quarter, after the first one
+        let tempos = tempos(&reported);
+        assert_eq!(tempos.len(), 4);
+        assert!(tempos.iter().all(|bpm| (bpm - 120.0).abs() < 0.01), "{tempos:?}");
+        assert_eq!(reported[..TICKS_PER_QUARTER], [ClockTick::Counting; TICKS_PER_QUARTER]);
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        let mut clock_tempo = ClockTempo::new(&ClockInputConfig { averaged_ticks: 6, ..ClockInputConfig::default() });
+        let at_90 = ticks(&mut clock_tempo, Duration::zero(), 90.0, 13);
+        let last_tick = Duration::microseconds((12.0 * 60_000_000.0 / 90.0 / 24.0) as i64);
+        let at_150 = ticks(&mut clock_tempo, last_tick + Duration::microseconds(16_667), 150.0, 24);
+        assert!(tempos(&at_90).iter().all(|bpm| (bpm - 90.0).abs() < 0.01));
+        // a whole window after the change
+        let bpm = *tempos(&at_150).last().unwrap();
+        assert!((bpm - 150.0).abs() < 0.01, "{bpm}");
+    }
+
+    #[test]
+    fn test_stopped_clock() {
+        let mut clock_tempo = ClockTempo::new(&ClockInputConfig::default());
+        ticks(&mut clock_tempo, Duration::zero(), 120.0, 2 * TICKS_PER_QUARTER);
+        let resumed = ticks(&mut clock_tempo, Duration::seconds(10), 100.0, TICKS_PER_QUARTER + 1);
+        assert_eq!(resumed[0], ClockTick::Restarted);
+        // counted from the restart only
+        let tempos = tempos(&resumed);
+        assert_eq!(tempos.len(), 1);
+        assert!((tempos[0] - 100.0).abs() < 0.01, "{tempos:?}");
+    }
+}
@@ -0,0 +1,289 @@
+use crate::bpm::MIDI_CLOCKS_PER_BEAT;
+use derivative::Derivative;
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Longest lookahead, the configured one is capped to it
+pub const MAX_LOOKAHEAD: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
+pub struct ClockLookaheadConfig {
+    // how far ahead of the beat the MIDI clock runs while the tempo is steady, for gear that is slow to follow it.
+    // Zero disables it
+    pub lookahead: Duration,
+    // in BPM per second, a tempo changing faster than that isn't steady
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub max_slope: f32,
+    // in percent of the tempo, estimates spread wider than that aren't steady either
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub max_spread: f32,
+    // estimates that old and older are left out of the slope and the spread
+    pub window: Duration,
+    // the clock moves to and from the lookahead over that many beats
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub slew_beats: f32,
+    // diagnostic, lookahead the clock runs with, shared by all clones of the configuration
+    #[serde(skip)]
+    #[derivative(PartialEq = "ignore")]
+    pub applied: Arc<AtomicU64>,
+}
+
+impl Default for ClockLookaheadConfig {
+    fn default() -> Self {
+        Self {
+            lookahead: Duration::ZERO,
+            max_slope: 0.5,
+            max_spread: 2.0,
+            window: Duration::from_secs(4),
+            slew_beats: 4.0,
+            applied: Arc::default(),
+        }
+    }
+}
+
+impl ClockLookaheadConfig {
+    #[must_use]
+    pub fn lookahead(&self) -> Duration {
+        self.lookahead.min(MAX_LOOKAHEAD)
+    }
+
+    /// Lookahead the clock moves towards, the configured one while the tempo is steady
+    #[must_use]
+    pub fn target(&self, tempo_trend: &TempoTrend) -> Duration {
+        let steady = tempo_trend.slope().is_some_and(|slope| slope.abs() <= self.max_slope)
+            && tempo_trend.spread().is_some_and(|spread| spread <= self.max_spread);
+        if steady {
+            self.lookahead()
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Lookahead the clock currently runs with
+    #[must_use]
+    pub fn applied(&self) -> Duration {
+        Duration::from_micros(self.applied.load(Ordering::Relaxed))
+    }
+}
+
+/// Estimates of the last `window`, by the time of the newest note they were estimated from
+#[derive(Clone, Debug)]
+pub struct TempoTrend {
+    window: Duration,
+    estimates: VecDeque<(Duration, f32)>,
+}
+
+impl TempoTrend {
+    #[must_use]
+    pub fn new(config: &ClockLookaheadConfig) -> Self {
+        Self { window: config.window, estimates: VecDeque::new() }
+    }
+
+    pub fn estimate(&mut self, at: Duration, bpm: f32) {
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return;
+        }
+        if self.estimates.back().is_some_and(|(last, _)| *last > at) {
+            self.estimates.clear();
+        }
+        while self.estimates.front().is_some_and(|(oldest, _)| at.saturating_sub(*oldest) >= self.window) {
+            self.estimates.pop_front();
+        }
+        self.estimates.push_back((at, bpm));
+    }
+
+    pub fn clear(&mut self) {
+        self.estimates.clear();
+    }
+
+    /// In BPM per second, fitted by least squares, none until the estimates span some time
+    #[must_use]
+    pub fn slope(&self) -> Option<f32> {
+        let (first, _) = *self.estimates.front()?;
+        let count = self.estimates.len() as f64;
+        let points =
+            || self.estimates.iter().map(|(at, bpm)| (at.saturating_sub(first).as_secs_f64(), f64::from(*bpm)));
+        let mean_time = points().map(|(time, _)| time).sum::<f64>() / count;
+        let mean_bpm = points().map(|(_, bpm)| bpm).sum::<f64>() / count;
+        let covariance = points().map(|(time, bpm)| (time - mean_time) * (bpm - mean_bpm)).sum::<f64>();
+        let variance = points().map(|(time, _)| (time - mean_time).powi(2)).sum::<f64>();
+        (variance > 0.0).then(|| (covariance / variance) as f32)
+    }
+
+    /// Difference between the highest and the lowest estimate, in percent of their mean
+    #[must_use]
+    pub fn spread(&self) -> Option<f32> {
+        let bpms = || self.estimates.iter().map(|(_, bpm)| *bpm);
+        let highest = bpms().reduce(f32::max)?;
+        let lowest = bpms().reduce(f32::min)?;
+        let mean = bpms().sum::<f32>() / self.estimates.len() as f32;
+        Some(100.0 * (highest - lowest) / mean)
+    }
+}
+
+/// When to send the MIDI clock ticks. They follow a grid of `interval` steps, and are sent the lookahead earlier. The
+/// lookahead moves towards its target by a step per tick, so it is reached over `slew_beats` without any tick being
+/// sent twice or skipped
+#[derive(Clone, Debug)]
+pub struct ClockScheduler {
+    // tick of the grid the next one is sent for
+    grid_tick: Instant,
+    lookahead: Duration,
+    // largest change of the lookahead between two ticks
+    step: Duration,
+}
+
+impl ClockScheduler {
+    /// The first tick is one interval after `now`
+    #[must_use]
+    pub fn new(config: &ClockLookaheadConfig, now: Instant) -> Self {
+        let ticks = (config.slew_beats.max(1.0) * f32::from(MIDI_CLOCKS_PER_BEAT)) as u32;
+        Self { grid_tick: now, lookahead: Duration::ZERO, step: config.lookahead() / ticks.max(1) }
+    }
+
+    #[must_use]
+    pub fn lookahead(&self) -> Duration {
+        self.lookahead
+    }
+
+    /// When to send the next tick, on a grid of `interval` steps, moving the lookahead towards `target`. Called at
+    /// `now`, once the previous tick was sent
+    pub fn next_tick(&mut self, now: Instant, interval: Duration, target: Duration) -> Instant {
+        // ticks that close would not keep their order
+        let step = self.step.min(interval / 4);
+        self.lookahead = if target > self.lookahead {
+            (self.lookahead + step).min(target)
+        } else {
+            self.lookahead.saturating_sub(step).max(target)
+        };
+        self.grid_tick += interval;
+        let tick = self.grid_tick.checked_sub(self.lookahead).unwrap_or(self.grid_tick);
+        // after a stall the grid starts over, rather than sending the ticks it missed at once
+        if tick + interval < now {
+            self.grid_tick = now + self.lookahead;
+            return now;
+        }
+        tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 120 BPM
+    const INTERVAL: Duration = Duration::from_micros(500_000 / 24);
+
+    fn config() -> ClockLookaheadConfig {
+        ClockLookaheadConfig { lookahead: Duration::from_millis(12), ..ClockLookaheadConfig::default() }
+    }
+
+    fn trend(config: &ClockLookaheadConfig, bpms: impl IntoIterator<Item = f32>) -> TempoTrend {
+        let mut tempo_trend = TempoTrend::new(config);
+        for (index, bpm) in bpms.into_iter().enumerate() {
+            tempo_trend.estimate(Duration::from_millis(500) * index as u32, bpm);
+        }
+        tempo_trend
+    }
+
+    // ticks sent at the time they are scheduled, with the target given for each of them
+    fn run(
+        scheduler: &mut ClockScheduler,
+        start: Instant,
+        targets: impl IntoIterator<Item = Duration>,
+    ) -> Vec<Instant> {
+        let mut now = start;
+        targets
+            .into_iter()
+            .map(|target| {
+                now = scheduler.next_tick(now, INTERVAL, target).max(now);
+                now
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_target() {
+        let config = config();
+        assert_eq!(config.target(&trend(&config, [120.0, 120.2, 119.9, 120.1, 120.0])), Duration::from_millis(12));
+        // accelerating by 1 BPM per second
+        assert_eq!(config.target(&trend(&config, (0..8).map(|index| 120.0 + index as f32 * 0.5))), Duration::ZERO);
+        // steady on average, but all over the place
+        assert_eq!(config.target(&trend(&config, [120.0, 126.0, 114.0, 126.0, 114.0, 120.0])), Duration::ZERO);
+        // not a single estimate to tell
+        assert_eq!(config.target(&trend(&config, [120.0])), Duration::ZERO);
+        let capped = ClockLookaheadConfig { lookahead: Duration::from_millis(50), ..config.clone() };
+        assert_eq!(capped.target(&trend(&config, [120.0; 4])), MAX_LOOKAHEAD);
+
+        // older estimates leave the window
+        let mut tempo_trend = trend(&config, (0..8).map(|index| 120.0 + index as f32 * 2.0));
+        for index in 8..16 {
+            tempo_trend.estimate(Duration::from_millis(500) * index, 134.0);
+        }
+        assert_eq!(config.target(&tempo_trend), Duration::from_millis(12));
+    }
+
+    #[test]
+    fn test_steady_offset() {
+        let config = config();
+        let start = Instant::now();
+        let mut reference = ClockScheduler::new(&ClockLookaheadConfig::default(), start);
+        let mut scheduler = ClockScheduler::new(&config, start);
+        let reference = run(&mut reference, start, [Duration::ZERO; 24 * 8]);
+        let ticks = run(&mut scheduler, start, [config.lookahead(); 24 * 8]);
+
+        // reached within the slew, the ticks then run the lookahead ahead of the grid
+        assert_eq!(scheduler.lookahead(), config.lookahead());
+        for (tick, reference) in ticks.iter().zip(&reference).skip(24 * 4) {
+            assert_eq!(*reference - *tick, config.lookahead());
+        }
+    }
+
+    #[test]
+    fn test_slew() {
+        let config = config();
+        let step = config.lookahead() / 96;
+        let start = Instant::now();
+        let mut scheduler = ClockScheduler::new(&config, start);
+        // steady, changing, steady again
+        let targets = [config.lookahead(); 24 * 6]
+            .into_iter()
+            .chain([Duration::ZERO; 24 * 6])
+            .chain([config.lookahead(); 24 * 6]);
+        let ticks = run(&mut scheduler, start, targets);
+
+        // as many ticks as on the grid, none of them closer or further apart than a step
+        assert_eq!(ticks.len(), 24 * 18);
+        let mut previous = start;
+        for tick in &ticks {
+            let interval = *tick - previous;
+            assert!(interval >= INTERVAL.saturating_sub(step) && interval <= INTERVAL + step, "{interval:?}");
+            previous = *tick;
+        }
+        // the grid is kept
+        assert_eq!(*ticks.last().unwrap() + config.lookahead(), start + INTERVAL * 24 * 18);
+    }
+
+    #[test]
+    fn test_stall() {
+        let config = config();
+        let start = Instant::now();
+        let mut scheduler = ClockScheduler::new(&config, start);
+        run(&mut scheduler, start, [Duration::ZERO; 24]);
+        // the clock thread didn't run for a second
+        let late = start + INTERVAL * 24 + Duration::from_secs(1);
+        let tick = scheduler.next_tick(late, INTERVAL, Duration::ZERO);
+        assert_eq!(tick, late);
+        assert_eq!(scheduler.next_tick(tick, INTERVAL, Duration::ZERO), late + INTERVAL);
+    }
+}
@@ -0,0 +1,228 @@
+//! Link to a script running next to the DAW, over a TCP connection on localhost. The messages are framed as described
+//! in `daw_link_protocol`, `crates/daw-bridge` is a listener to start a script from.
+
+use crate::daw_link_protocol::DawMessage;
+use errors::{error, info, LogErrorWithExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+/// Where the standalone application sends to, besides MIDI
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DawLinkConfig {
+    // port the script listens to on localhost, none to not connect
+    pub port: Option<u16>,
+    // play, stop and continue are sent along with the tempo, whether they come from the interface or from the SysEx
+    // received
+    pub forward_transport: bool,
+}
+
+impl Default for DawLinkConfig {
+    fn default() -> Self {
+        Self { port: None, forward_transport: true }
+    }
+}
+
+fn connect_to_daw(daw_port: u16) -> io::Result<TcpStream> {
+    TcpStream::connect_timeout(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), daw_port), Duration::from_millis(10))
+}
+
+/// Connects to the DAW on a dedicated thread, so a slow connection attempt never holds up note processing. The thread
+/// is only started when a connection is first requested.
+pub struct DawConnector {
+    connect: fn(u16) -> io::Result<TcpStream>,
+    port_sender: Option<Sender<u16>>,
+    connection_sender: Sender<TcpStream>,
+    connection_receiver: Receiver<TcpStream>,
+}
+
+impl Default for DawConnector {
+    fn default() -> Self {
+        Self::new(connect_to_daw)
+    }
+}
+
+impl DawConnector {
+    #[must_use]
+    pub fn new(connect: fn(u16) -> io::Result<TcpStream>) -> Self {
+        let (connection_sender, connection_receiver) = channel();
+        Self { connect, port_sender: None, connection_sender, connection_receiver }
+    }
+
+    /// Never blocks, the connection is obtained later on with `take_connection`
+    pub fn connect(&mut self, daw_port: u16) {
+        if self.port_sender.as_ref().is_some_and(|port_sender| port_sender.send(daw_port).is_ok()) {
+            return;
+        }
+
+        let (port_sender, port_receiver) = channel::<u16>();
+        let connection_sender = self.connection_sender.clone();
+        let connect = self.connect;
+        let spawned = thread::Builder::new().name("DAW connector".to_string()).spawn(move || {
+            while let Ok(mut daw_port) = port_receiver.recv() {
+                // only the most recent port matters
+                daw_port = port_receiver.try_iter().last().unwrap_or(daw_port);
+                let Ok(connection) = connect(daw_port).log_error_msg("could not connect to daw, ignoring") else {
+                    continue;
+                };
+                if connection_sender.send(connection).is_err() {
+                    return;
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            error!("could not start daw connector thread: {e:?}");
+            return;
+        }
+        port_sender.send(daw_port).log_error_msg("daw connector thread is gone").ok();
+        self.port_sender = Some(port_sender);
+    }
+
+    /// Most recent connection established since the last call
+    #[must_use]
+    pub fn take_connection(&self) -> Option<TcpStream> {
+        self.connection_receiver.try_iter().last()
+    }
+}
+
+/// Connection to the DAW, established in the background by a `DawConnector`. A connection failing to send is closed,
+/// the next one is used once established
+#[derive(Default)]
+pub struct DawLink {
+    connector: DawConnector,
+    connection: Option<TcpStream>,
+}
+
+impl DawLink {
+    #[must_use]
+    pub fn new(connector: DawConnector) -> Self {
+        Self { connector, connection: None }
+    }
+
+    /// Link over a connection established beforehand
+    #[must_use]
+    pub fn connected(connection: TcpStream) -> Self {
+        Self { connector: DawConnector::default(), connection: Some(connection) }
+    }
+
+    /// Never blocks, see `DawConnector::connect`
+    pub fn connect(&mut self, daw_port: u16) {
+        self.connector.connect(daw_port);
+    }
+
+    /// Returns whether the whole message was sent, nothing is while there is no connection
+    pub fn send(&mut self, message: DawMessage) -> bool {
+        if let Some(connection) = self.connector.take_connection() {
+            self.connection = Some(connection);
+        }
+        let Some(connection) = &mut self.connection else {
+            return false;
+        };
+        let frame = message.encode();
+        match connection.write(&frame) {
+            Ok(sent) if sent == frame.len() => {
+                info!("sent {message:?}");
+                return true;
+            }
+            Ok(sent) => error!("only {sent} bytes could be sent, closing daw connection"),
+            Err(err) => error!("error while sending to daw {err:?}, closing"),
+        }
+        self.connection = None;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daw_link_protocol::{CONTINUE, PLAY, STOP};
+    use std::{
+        io::Read,
+        net::TcpListener,
+        time::{Duration, Instant},
+    };
+
+    fn slow_connect(daw_port: u16) -> io::Result<TcpStream> {
+        thread::sleep(Duration::from_millis(500));
+        connect_to_daw(daw_port)
+    }
+
+    fn wait_for_connection(daw_connector: &DawConnector) -> Option<TcpStream> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(connection) = daw_connector.take_connection() {
+                return Some(connection);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    fn test_connect_does_not_block() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let daw_port = listener.local_addr().unwrap().port();
+        let mut daw_connector = DawConnector::new(slow_connect);
+
+        let start = Instant::now();
+        daw_connector.connect(daw_port);
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert!(daw_connector.take_connection().is_none());
+
+        let connection = wait_for_connection(&daw_connector).expect("connection was not handed over");
+        assert_eq!(connection.peer_addr().unwrap().port(), daw_port);
+    }
+
+    #[test]
+    fn test_reconnect_and_failure() {
+        let mut daw_connector = DawConnector::default();
+
+        // nothing listens there
+        let daw_port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        daw_connector.connect(daw_port);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let daw_port = listener.local_addr().unwrap().port();
+        daw_connector.connect(daw_port);
+
+        let connection = wait_for_connection(&daw_connector).expect("connection was not handed over");
+        assert_eq!(connection.peer_addr().unwrap().port(), daw_port);
+    }
+
+    #[test]
+    fn test_link() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut daw_link = DawLink::default();
+        // not connected yet
+        assert!(!daw_link.send(DawMessage::Play));
+        daw_link.connect(listener.local_addr().unwrap().port());
+        let (mut daw, _) = listener.accept().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !daw_link.send(DawMessage::Tempo(100.5)) {
+            assert!(Instant::now() < deadline, "connection was not handed over");
+            thread::sleep(Duration::from_millis(10));
+        }
+        for message in [DawMessage::Play, DawMessage::Stop, DawMessage::Continue] {
+            assert!(daw_link.send(message));
+        }
+
+        let mut received = [0u8; 23];
+        daw.read_exact(&mut received).unwrap();
+        assert_eq!(
+            received,
+            [
+                0, 0, 0, 4, 0x42, 0xC9, 0, 0, // 100.5
+                0, 0, 0, 1, PLAY, //
+                0, 0, 0, 1, STOP, //
+                0, 0, 0, 1, CONTINUE,
+            ]
+        );
+    }
+}
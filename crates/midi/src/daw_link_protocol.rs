@@ -0,0 +1,162 @@
+//! Frames exchanged with a script running next to the DAW, see `daw_link` for the sending side and
+//! `crates/daw-bridge` for a listener. Each message is a frame made of the length of its payload, as a big-endian u32,
+//! followed by the payload.
+//!
+//! A 4 bytes payload is a tempo, as a big-endian f32, which is all older versions send. Any other payload starts with
+//! a type byte, the transport commands have nothing after it. Scripts only reading tempos skip the frames whose length
+//! isn't 4, and newer types are skipped the same way by `DawMessage::decode`.
+
+use std::io::{self, ErrorKind, Read};
+
+const LENGTH_SIZE: usize = 4;
+const TEMPO_SIZE: usize = 4;
+// no message comes close, a longer frame means the stream is not made of frames
+const MAX_PAYLOAD_SIZE: usize = 1024;
+pub const PLAY: u8 = 1;
+pub const STOP: u8 = 2;
+pub const CONTINUE: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DawMessage {
+    Tempo(f32),
+    Play,
+    Stop,
+    // resumes from where the transport was stopped
+    Continue,
+}
+
+impl DawMessage {
+    #[must_use]
+    pub fn encode(self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(LENGTH_SIZE + TEMPO_SIZE);
+        let message_type = match self {
+            Self::Tempo(bpm) => {
+                frame.extend_from_slice(&(TEMPO_SIZE as u32).to_be_bytes());
+                frame.extend_from_slice(&bpm.to_be_bytes());
+                return frame;
+            }
+            Self::Play => PLAY,
+            Self::Stop => STOP,
+            Self::Continue => CONTINUE,
+        };
+        frame.extend_from_slice(&1u32.to_be_bytes());
+        frame.push(message_type);
+        frame
+    }
+
+    /// Reads the frame at the start of `buffer`, returns the message and the size of the frame, or None while the frame
+    /// is incomplete. The message is None for types this version doesn't know
+    #[must_use]
+    pub fn decode(buffer: &[u8]) -> Option<(Option<Self>, usize)> {
+        let frame_size = payload_size(buffer)?.checked_add(LENGTH_SIZE)?;
+        let message = match *buffer.get(LENGTH_SIZE..frame_size)? {
+            [a, b, c, d] => Some(Self::Tempo(f32::from_be_bytes([a, b, c, d]))),
+            [PLAY] => Some(Self::Play),
+            [STOP] => Some(Self::Stop),
+            [CONTINUE] => Some(Self::Continue),
+            _ => None,
+        };
+        Some((message, frame_size))
+    }
+}
+
+fn payload_size(buffer: &[u8]) -> Option<usize> {
+    usize::try_from(u32::from_be_bytes(buffer.get(..LENGTH_SIZE)?.try_into().ok()?)).ok()
+}
+
+/// Messages read from a stream such as a TCP connection, whatever the way the frames are split when received. The
+/// messages of unknown types are None, it ends when the stream is closed and an incomplete frame is then dropped. The
+/// stream is not usable anymore after an error
+pub struct DawMessageReader<R> {
+    source: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> DawMessageReader<R> {
+    pub fn new(source: R) -> Self {
+        Self { source, buffer: Vec::new() }
+    }
+}
+
+impl<R: Read> Iterator for DawMessageReader<R> {
+    type Item = io::Result<Option<DawMessage>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = [0; 256];
+        loop {
+            if let Some((message, frame_size)) = DawMessage::decode(&self.buffer) {
+                self.buffer.drain(..frame_size);
+                return Some(Ok(message));
+            }
+            if payload_size(&self.buffer).is_some_and(|payload_size| payload_size > MAX_PAYLOAD_SIZE) {
+                return Some(Err(io::Error::new(ErrorKind::InvalidData, "frame too long, the stream is not framed")));
+            }
+            match self.source.read(&mut chunk) {
+                Ok(0) => return None,
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hands over one byte at a time, as a frame may be split when received
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let cases = [
+            (DawMessage::Tempo(120.0), vec![0, 0, 0, 4, 0x42, 0xF0, 0, 0]),
+            (DawMessage::Play, vec![0, 0, 0, 1, PLAY]),
+            (DawMessage::Stop, vec![0, 0, 0, 1, STOP]),
+            (DawMessage::Continue, vec![0, 0, 0, 1, CONTINUE]),
+        ];
+        for (message, frame) in cases {
+            assert_eq!(message.encode(), frame);
+            assert_eq!(DawMessage::decode(&frame), Some((Some(message), frame.len())));
+            // incomplete
+            assert_eq!(DawMessage::decode(&frame[..frame.len() - 1]), None);
+        }
+        assert_eq!(DawMessage::decode(&[0, 0]), None);
+
+        // an unknown type is skipped, the next frame is read after it
+        let buffer = [0, 0, 0, 3, 42, 1, 2, 0, 0, 0, 1, STOP];
+        assert_eq!(DawMessage::decode(&buffer), Some((None, 7)));
+        assert_eq!(DawMessage::decode(&buffer[7..]), Some((Some(DawMessage::Stop), 5)));
+    }
+
+    #[test]
+    fn test_reader() {
+        let mut stream =
+            [DawMessage::Tempo(93.5), DawMessage::Play].into_iter().flat_map(DawMessage::encode).collect::<Vec<_>>();
+        stream.extend_from_slice(&[0, 0, 0, 2, 42, 0]);
+        stream.extend(DawMessage::Stop.encode());
+        // incomplete
+        stream.extend_from_slice(&[0, 0, 0, 4, 0x42]);
+
+        let expected = vec![Some(DawMessage::Tempo(93.5)), Some(DawMessage::Play), None, Some(DawMessage::Stop)];
+        let messages = DawMessageReader::new(stream.as_slice()).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(messages, expected);
+        let messages = DawMessageReader::new(ByteByByte(&stream)).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(messages, expected);
+
+        let mut reader = DawMessageReader::new(b"GET / HTTP/1.1\r\n".as_slice());
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
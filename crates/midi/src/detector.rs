@@ -0,0 +1,282 @@
+//! The detection for applications embedding it, without the MIDI backends, the frontends or their configuration
+//!
+//! ```
+//! use midi::Detector;
+//! use std::time::{Duration, Instant};
+//!
+//! let mut detector = Detector::builder().bpm_center(120.0).bpm_range(60).beats_lookback(8).build();
+//! let start = Instant::now();
+//! for beat in 0..16 {
+//!     detector.push_note(start + Duration::from_millis(500) * beat, 60, 100, 0);
+//! }
+//! let estimate = detector.estimate().unwrap();
+//! assert!((estimate.bpm - 120.0).abs() < 0.5, "{}", estimate.bpm);
+//! ```
+
+use crate::{
+    midi_messages::MidiNoteOn, pre_detection::PreDetection, BPMDetection, BpmEstimate, ChannelFilter,
+    DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedMidiNoteOn,
+};
+use chrono::Duration;
+use instant::Instant;
+
+/// Parameters of a [`Detector`], the defaults for the ones not set
+#[derive(Clone, Debug, Default)]
+pub struct DetectorBuilder {
+    static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+}
+
+impl DetectorBuilder {
+    /// Middle of the tempo range
+    #[must_use]
+    pub fn bpm_center(mut self, bpm_center: f32) -> Self {
+        self.static_bpm_detection_parameters.bpm_center = bpm_center;
+        self
+    }
+
+    /// Width of the tempo range, half of it on each side of the center
+    #[must_use]
+    pub fn bpm_range(mut self, bpm_range: u16) -> Self {
+        self.static_bpm_detection_parameters.bpm_range = bpm_range;
+        self
+    }
+
+    /// Histogram bins per second of beat duration
+    #[must_use]
+    pub fn histogram_resolution(mut self, histogram_resolution: u16) -> Self {
+        self.static_bpm_detection_parameters.histogram_resolution = histogram_resolution;
+        self
+    }
+
+    /// Channels the notes are taken from, all of them by default
+    #[must_use]
+    pub fn channels(mut self, channels: ChannelFilter) -> Self {
+        self.static_bpm_detection_parameters.channels = channels;
+        self
+    }
+
+    /// Notes older than that many beats of the estimate are dropped
+    #[must_use]
+    pub fn beats_lookback(mut self, beats_lookback: u8) -> Self {
+        self.dynamic_bpm_detection_parameters.beats_lookback = beats_lookback;
+        self
+    }
+
+    /// Replaces the parameters of the histogram, including the ones set before
+    #[must_use]
+    pub fn static_parameters(mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) -> Self {
+        self.static_bpm_detection_parameters = static_bpm_detection_parameters;
+        self
+    }
+
+    /// Replaces the weights of the evaluation, including the lookback set before
+    #[must_use]
+    pub fn dynamic_parameters(mut self, dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters) -> Self {
+        self.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Detector {
+        Detector {
+            pre_detection: PreDetection::new(
+                &self.static_bpm_detection_parameters,
+                &self.dynamic_bpm_detection_parameters,
+            ),
+            bpm_detection: BPMDetection::new(self.static_bpm_detection_parameters),
+            dynamic_bpm_detection_parameters: self.dynamic_bpm_detection_parameters,
+            origin: None,
+        }
+    }
+}
+
+/// Tempo of the notes pushed, through the same stages as the notes received from a device
+pub struct Detector {
+    pre_detection: PreDetection,
+    bpm_detection: BPMDetection,
+    dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    // the notes are timestamped from the first one
+    origin: Option<Instant>,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl Detector {
+    #[must_use]
+    pub fn builder() -> DetectorBuilder {
+        DetectorBuilder::default()
+    }
+
+    /// A note played at `instant`, in the order they were played. A velocity of 0 is a note-off, as in MIDI
+    pub fn push_note(&mut self, instant: Instant, note: u8, velocity: u8, channel: u8) {
+        let bpm_detection = &mut self.bpm_detection;
+        self.pre_detection.flush(&self.dynamic_bpm_detection_parameters, instant, |onset| {
+            bpm_detection.receive_midi_message(onset);
+        });
+        if velocity == 0 {
+            self.pre_detection.note_off(channel, note);
+            return;
+        }
+        let origin = *self.origin.get_or_insert(instant);
+        let timestamp = Duration::from_std(instant.saturating_duration_since(origin)).unwrap_or(Duration::MAX);
+        self.pre_detection.note_on(
+            &self.dynamic_bpm_detection_parameters,
+            TimedMidiNoteOn { timestamp, midi_message: MidiNoteOn { channel, note, velocity } },
+            instant,
+            |onset| bpm_detection.receive_midi_message(onset),
+        );
+    }
+
+    /// Evaluates the notes pushed so far, none until two of them are an interval within the tempo range apart
+    pub fn estimate(&mut self) -> Option<BpmEstimate> {
+        let bpm_detection = &mut self.bpm_detection;
+        self.pre_detection.flush(&self.dynamic_bpm_detection_parameters, Instant::now(), |onset| {
+            bpm_detection.receive_midi_message(onset);
+        });
+        let newest_note = self.bpm_detection.newest_note_timestamp().unwrap_or_else(Duration::zero);
+        let effective_parameters =
+            self.pre_detection.effective_parameters(&self.dynamic_bpm_detection_parameters, newest_note);
+        let (histogram_data_points, estimate) = self.bpm_detection.compute_bpm(effective_parameters)?;
+        // an empty histogram still has a highest bin
+        let found = histogram_data_points.iter().any(|value| *value > 0.0);
+        self.pre_detection.zoom(&mut self.bpm_detection, &self.dynamic_bpm_detection_parameters, estimate.bpm);
+        found.then_some(estimate)
+    }
+
+    /// Histogram of the last estimate, see [`StaticBPMDetectionParameters::index_to_bpm`]
+    #[must_use]
+    pub fn histogram(&self) -> &[f32] {
+        self.bpm_detection.histogram_data_points()
+    }
+
+    /// Forgets the notes pushed, the next ones start a new timeline
+    pub fn clear(&mut self) {
+        self.bpm_detection.clear_notes();
+        self.pre_detection.clear(&mut self.bpm_detection);
+        self.origin = None;
+    }
+
+    #[must_use]
+    pub fn static_parameters(&self) -> &StaticBPMDetectionParameters {
+        self.pre_detection.auto_zoom().configured()
+    }
+
+    #[must_use]
+    pub fn dynamic_parameters(&self) -> &DynamicBPMDetectionParameters {
+        &self.dynamic_bpm_detection_parameters
+    }
+
+    /// The notes pushed so far are kept
+    pub fn set_static_parameters(&mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) {
+        self.pre_detection.set_configured(&static_bpm_detection_parameters);
+        self.bpm_detection.update_static_parameters(static_bpm_detection_parameters);
+    }
+
+    pub fn set_dynamic_parameters(&mut self, dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters) {
+        self.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration as StdDuration;
+
+    fn drums() -> ChannelFilter {
+        let mut channels = ChannelFilter::ALL;
+        channels.select(9, true);
+        channels
+    }
+
+    fn push_beats(detector: &mut Detector, start: Instant, bpm: f64, beats: u32) {
+        for beat in 0..beats {
+            detector.push_note(start + StdDuration::from_secs_f64(60.0 / bpm) * beat, 60, 100, 0);
+        }
+    }
+
+    #[test]
+    fn test_same_estimate_as_the_detection() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 60,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut detector = Detector::builder().static_parameters(static_bpm_detection_parameters.clone()).build();
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters);
+        let start = Instant::now();
+        push_beats(&mut detector, start, 97.3, 12);
+        for beat in 0..12 {
+            bpm_detection.receive_midi_message(TimedMidiNoteOn {
+                timestamp: Duration::from_std(StdDuration::from_secs_f64(60.0 / 97.3) * beat).unwrap(),
+                midi_message: MidiNoteOn { channel: 0, note: 60, velocity: 100 },
+            });
+        }
+        let estimate = detector.estimate().unwrap();
+        let (histogram_data_points, expected) =
+            bpm_detection.compute_bpm(&DynamicBPMDetectionParameters::default()).unwrap();
+        assert_eq!(estimate, expected);
+        assert_eq!(detector.histogram(), histogram_data_points);
+    }
+
+    #[test]
+    fn test_builder() {
+        let detector = Detector::builder()
+            .bpm_range(20)
+            .bpm_center(140.0)
+            .histogram_resolution(300)
+            .channels(drums())
+            .beats_lookback(4)
+            .build();
+        let static_bpm_detection_parameters = detector.static_parameters();
+        assert_eq!(static_bpm_detection_parameters.bpm_range, 20);
+        assert_eq!(static_bpm_detection_parameters.histogram_resolution, 300);
+        assert_eq!(static_bpm_detection_parameters.channels, drums());
+        assert_eq!(detector.dynamic_parameters().beats_lookback, 4);
+        // replaced along with the rest of the weights
+        let detector =
+            Detector::builder().beats_lookback(4).dynamic_parameters(DynamicBPMDetectionParameters::default()).build();
+        assert_eq!(detector.dynamic_parameters(), &DynamicBPMDetectionParameters::default());
+    }
+
+    #[test]
+    fn test_chords() {
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { max_simultaneous_onsets: 1, ..DynamicBPMDetectionParameters::default() };
+        let mut detector = Detector::builder().dynamic_parameters(dynamic_bpm_detection_parameters.clone()).build();
+        let mut single_notes = Detector::builder().dynamic_parameters(dynamic_bpm_detection_parameters).build();
+        let start = Instant::now();
+        push_beats(&mut single_notes, start, 120.0, 12);
+        for beat in 0..12 {
+            for tone in 0..3 {
+                let instant = start + StdDuration::from_millis(500 * beat + 2 * tone);
+                detector.push_note(instant, 60 + tone as u8 * 4, 100, 0);
+            }
+        }
+        // the last chord is complete once no other note can join it
+        detector.push_note(start + StdDuration::from_secs(6), 60, 0, 0);
+        single_notes.push_note(start + StdDuration::from_secs(6), 60, 0, 0);
+        assert_eq!(detector.estimate(), single_notes.estimate());
+    }
+
+    #[test]
+    fn test_skipped_notes() {
+        let mut detector = Detector::builder().channels(drums()).build();
+        let start = Instant::now();
+        detector.push_note(start, 36, 100, 0);
+        detector.push_note(start + StdDuration::from_millis(600), 36, 0, 9);
+        assert_eq!(detector.estimate(), None);
+        detector.push_note(start + StdDuration::from_millis(1200), 36, 100, 9);
+        detector.push_note(start + StdDuration::from_millis(1800), 36, 100, 9);
+        assert!(detector.estimate().is_some());
+
+        // a new timeline, the notes before are gone
+        detector.clear();
+        detector.push_note(start + StdDuration::from_secs(60), 36, 100, 9);
+        assert_eq!(detector.estimate(), None);
+    }
+}
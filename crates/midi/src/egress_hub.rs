@@ -0,0 +1,316 @@
+//! Network egress on a thread of its own. A DNS lookup or a socket write can stall for a long time, the detection
+//! threads only publish what is to be sent and never touch a socket. Each kind of message is a topic holding the latest
+//! one published, older ones that were not sent yet are dropped. The thread sending them owns the sinks, built anew
+//! when it is respawned after a panic.
+
+use crate::{
+    daw_link::DawLink,
+    daw_link_protocol::{DawMessage, CONTINUE, PLAY, STOP},
+    osc_output::OscOutput,
+};
+use errors::error;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use sync::Mutex;
+
+// slows down a sink panicking on every message
+const RESPAWN_DELAY: Duration = Duration::from_millis(100);
+// marks a slot holding a message, the payload is in the lower 32 bits
+const PUBLISHED: u64 = 1 << 32;
+
+/// What goes out of the application. The topics are the port of the DAW link, the tempo and the transport commands
+/// sent over it, and the tempo sent over OSC
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Egress {
+    // the DAW link connects to this port on localhost
+    DawPort(u16),
+    Daw(DawMessage),
+    OscBpm(f32),
+}
+
+const TOPICS: usize = 4;
+
+impl Egress {
+    // topic and payload
+    fn encode(self) -> (usize, u32) {
+        match self {
+            Self::DawPort(daw_port) => (0, u32::from(daw_port)),
+            Self::Daw(DawMessage::Tempo(bpm)) => (1, bpm.to_bits()),
+            Self::Daw(DawMessage::Play) => (2, u32::from(PLAY)),
+            Self::Daw(DawMessage::Stop) => (2, u32::from(STOP)),
+            Self::Daw(DawMessage::Continue) => (2, u32::from(CONTINUE)),
+            Self::OscBpm(bpm) => (3, bpm.to_bits()),
+        }
+    }
+
+    fn decode(topic: usize, payload: u32) -> Option<Self> {
+        match (topic, payload) {
+            (0, daw_port) => u16::try_from(daw_port).ok().map(Self::DawPort),
+            (1, bits) => Some(Self::Daw(DawMessage::Tempo(f32::from_bits(bits)))),
+            (2, payload) => match u8::try_from(payload).ok()? {
+                PLAY => Some(Self::Daw(DawMessage::Play)),
+                STOP => Some(Self::Daw(DawMessage::Stop)),
+                CONTINUE => Some(Self::Daw(DawMessage::Continue)),
+                _ => None,
+            },
+            (3, bits) => Some(Self::OscBpm(f32::from_bits(bits))),
+            _ => None,
+        }
+    }
+}
+
+/// Destination of the messages, called from the egress thread only, where it may block
+pub trait EgressSink: Send {
+    /// Returns whether the message went through, messages of other topics are ignored
+    fn send(&mut self, egress: Egress) -> bool;
+}
+
+impl EgressSink for DawLink {
+    fn send(&mut self, egress: Egress) -> bool {
+        match egress {
+            Egress::DawPort(daw_port) => {
+                self.connect(daw_port);
+                true
+            }
+            Egress::Daw(message) => DawLink::send(self, message),
+            Egress::OscBpm(_) => false,
+        }
+    }
+}
+
+impl EgressSink for OscOutput {
+    fn send(&mut self, egress: Egress) -> bool {
+        match egress {
+            Egress::OscBpm(bpm) => self.send_bpm(bpm),
+            Egress::DawPort(_) | Egress::Daw(_) => false,
+        }
+    }
+}
+
+pub type EgressSinks = Vec<Box<dyn EgressSink>>;
+
+// shared by the producers and the egress thread
+#[derive(Default)]
+struct Topics {
+    slots: [AtomicU64; TOPICS],
+    // whether the DAW link took the last tempo
+    tempo_delivered: AtomicBool,
+}
+
+/// Publishing side, cheap to clone. Publishing never blocks nor allocates, the thread stops once every clone is dropped
+#[derive(Clone)]
+pub struct EgressHub {
+    topics: Arc<Topics>,
+    // holds at most one wakeup, the thread sends everything published up to it
+    wake: SyncSender<()>,
+}
+
+impl EgressHub {
+    /// Starts the thread, `sinks` is called from it to build the sinks, again each time it is respawned. Nothing is
+    /// sent when the thread could not be started
+    #[must_use]
+    pub fn new(sinks: impl FnMut() -> EgressSinks + Send + 'static) -> Self {
+        let topics = Arc::new(Topics::default());
+        let (wake, wakeups) = sync_channel(1);
+        let wakeups = Mutex::new(wakeups);
+        let spawned = {
+            let topics = topics.clone();
+            thread::Builder::new().name("egress hub".to_string()).spawn(move || supervise(&topics, &wakeups, sinks))
+        };
+        if let Err(e) = spawned {
+            error!("could not start the egress thread, nothing is sent over the network: {e:?}");
+        }
+        Self { topics, wake }
+    }
+
+    /// Replaces the message of the same topic if it was not sent yet
+    pub fn publish(&self, egress: Egress) {
+        let (topic, payload) = egress.encode();
+        self.topics.slots[topic].store(PUBLISHED | u64::from(payload), Ordering::Release);
+        // full when a wakeup is already pending, which sends this message as well
+        self.wake.try_send(()).ok();
+    }
+
+    /// Whether the last tempo the thread sent to the DAW went through, the ones published since may still be waiting
+    #[must_use]
+    pub fn tempo_delivered(&self) -> bool {
+        self.topics.tempo_delivered.load(Ordering::Relaxed)
+    }
+}
+
+// respawns the egress thread until the hub is dropped
+fn supervise(topics: &Topics, wakeups: &Mutex<Receiver<()>>, mut sinks: impl FnMut() -> EgressSinks) {
+    loop {
+        let mut current_sinks = sinks();
+        let joined = thread::scope(|scope| {
+            thread::Builder::new()
+                .name("egress".to_string())
+                .spawn_scoped(scope, || send_published(topics, wakeups, &mut current_sinks))
+                .map(thread::ScopedJoinHandle::join)
+        });
+        match joined {
+            Ok(Ok(())) => return,
+            Ok(Err(_)) => error!("the egress thread panicked, respawning it"),
+            Err(e) => {
+                error!("could not respawn the egress thread, nothing is sent over the network anymore: {e:?}");
+                return;
+            }
+        }
+        thread::sleep(RESPAWN_DELAY);
+    }
+}
+
+// only one thread at a time, the lock is released when it panics
+fn send_published(topics: &Topics, wakeups: &Mutex<Receiver<()>>, sinks: &mut EgressSinks) {
+    let wakeups = wakeups.lock();
+    while wakeups.recv().is_ok() {
+        for (topic, slot) in topics.slots.iter().enumerate() {
+            let published = slot.swap(0, Ordering::Acquire);
+            if published & PUBLISHED == 0 {
+                continue;
+            }
+            let Some(egress) = Egress::decode(topic, published as u32) else {
+                continue;
+            };
+            // every sink gets the message, whether an earlier one took it or not
+            let mut delivered = false;
+            for sink in sinks.iter_mut() {
+                delivered |= sink.send(egress);
+            }
+            if matches!(egress, Egress::Daw(DawMessage::Tempo(_))) {
+                topics.tempo_delivered.store(delivered, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::mpsc::{channel, Sender},
+        time::Instant,
+    };
+
+    // hands the messages over to the test, blocking on the first one until released
+    struct BlockingSink {
+        sent: Sender<Egress>,
+        release: Option<Receiver<()>>,
+    }
+
+    impl EgressSink for BlockingSink {
+        fn send(&mut self, egress: Egress) -> bool {
+            self.sent.send(egress).ok();
+            if let Some(release) = self.release.take() {
+                release.recv().ok();
+            }
+            true
+        }
+    }
+
+    fn blocking_hub() -> (EgressHub, Receiver<Egress>, Sender<()>) {
+        let (sent, received) = channel();
+        let (release, released) = channel();
+        let mut released = Some(released);
+        let egress_hub = EgressHub::new(move || {
+            vec![Box::new(BlockingSink { sent: sent.clone(), release: released.take() }) as Box<dyn EgressSink>]
+        });
+        (egress_hub, received, release)
+    }
+
+    #[test]
+    fn test_encoding() {
+        for egress in [
+            Egress::DawPort(7000),
+            Egress::Daw(DawMessage::Tempo(100.5)),
+            Egress::Daw(DawMessage::Play),
+            Egress::Daw(DawMessage::Stop),
+            Egress::Daw(DawMessage::Continue),
+            Egress::OscBpm(100.5),
+        ] {
+            let (topic, payload) = egress.encode();
+            assert_eq!(Egress::decode(topic, payload), Some(egress));
+        }
+    }
+
+    #[test]
+    fn test_blocking_sink_does_not_delay_publish() {
+        let (egress_hub, received, release) = blocking_hub();
+        egress_hub.publish(Egress::Daw(DawMessage::Tempo(100.0)));
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok(Egress::Daw(DawMessage::Tempo(100.0))));
+
+        // the sink is now stuck
+        let start = Instant::now();
+        for index in 0..10_000 {
+            egress_hub.publish(Egress::Daw(DawMessage::Tempo(100.0 + index as f32 / 100.0)));
+        }
+        assert!(start.elapsed() < Duration::from_millis(100), "{:?}", start.elapsed());
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_latest_wins() {
+        let (egress_hub, received, release) = blocking_hub();
+        egress_hub.publish(Egress::Daw(DawMessage::Tempo(100.0)));
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok(Egress::Daw(DawMessage::Tempo(100.0))));
+
+        for bpm in [110.0, 120.0, 130.0] {
+            egress_hub.publish(Egress::Daw(DawMessage::Tempo(bpm)));
+        }
+        egress_hub.publish(Egress::Daw(DawMessage::Play));
+        egress_hub.publish(Egress::Daw(DawMessage::Stop));
+        release.send(()).unwrap();
+
+        let mut sent = vec![];
+        while let Ok(egress) = received.recv_timeout(Duration::from_millis(200)) {
+            sent.push(egress);
+        }
+        // the topics are not ordered between them, the sweep blocked on the first tempo goes on with the transport
+        assert_eq!(sent.len(), 2, "{sent:?}");
+        assert!(sent.contains(&Egress::Daw(DawMessage::Tempo(130.0))), "{sent:?}");
+        assert!(sent.contains(&Egress::Daw(DawMessage::Stop)), "{sent:?}");
+        assert!(egress_hub.tempo_delivered());
+    }
+
+    // panics on its first message
+    struct PanickingSink {
+        sent: Sender<Egress>,
+        panics: bool,
+    }
+
+    impl EgressSink for PanickingSink {
+        fn send(&mut self, egress: Egress) -> bool {
+            assert!(!self.panics, "sink failure");
+            self.sent.send(egress).ok();
+            true
+        }
+    }
+
+    #[test]
+    fn test_respawn() {
+        let (sent, received) = channel();
+        let mut spawned = 0;
+        let egress_hub = EgressHub::new(move || {
+            spawned += 1;
+            vec![Box::new(PanickingSink { sent: sent.clone(), panics: spawned == 1 }) as Box<dyn EgressSink>]
+        });
+        egress_hub.publish(Egress::Daw(DawMessage::Tempo(100.0)));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            egress_hub.publish(Egress::Daw(DawMessage::Tempo(120.0)));
+            if let Ok(egress) = received.recv_timeout(Duration::from_millis(50)) {
+                assert_eq!(egress, Egress::Daw(DawMessage::Tempo(120.0)));
+                break;
+            }
+            assert!(Instant::now() < deadline, "the egress thread was not respawned");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use midir::{ConnectError, ConnectErrorKind};
+use thiserror::Error;
+
+use crate::MidiBackend;
+
+/// Failures of the MIDI service that callers may want to handle differently
+#[derive(Debug, Error)]
+pub enum CoreError {
+    // the system MIDI layer could not be initialized. midir does not tell a missing backend apart from a lack of
+    // permissions, both end up here
+    #[error("MIDI backend unavailable: {0}")]
+    BackendUnavailable(String),
+    #[error("MIDI backend {requested} is not available: {hint}")]
+    BackendNotCompiled { requested: MidiBackend, hint: String },
+    #[error("MIDI port '{0}' does not exist anymore")]
+    PortNotFound(String),
+    #[error("could not connect to MIDI port '{port}': {reason}")]
+    ConnectionFailed { port: String, reason: String },
+    // the thread owning the MIDI connections is gone, nothing can be done with this service anymore
+    #[error("MIDI service is closed")]
+    ServiceClosed,
+    #[error("BPM detection worker is stopped")]
+    WorkerStopped,
+    #[error("could not start the MIDI service: {0}")]
+    ServiceStart(#[from] std::io::Error),
+}
+
+impl CoreError {
+    pub(crate) fn from_connect_error<T>(port: &str, error: &ConnectError<T>) -> Self {
+        match error.kind() {
+            ConnectErrorKind::InvalidPort => Self::PortNotFound(port.to_string()),
+            ConnectErrorKind::Other(reason) => {
+                Self::ConnectionFailed { port: port.to_string(), reason: reason.to_string() }
+            }
+        }
+    }
+}
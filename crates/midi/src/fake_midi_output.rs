@@ -1,16 +1,16 @@
 // This module only exists to allow building to a wasm target, which does not support Virtual midi output
 #![cfg(not(unix))]
 use log::info;
-use wmidi::{Channel, ControlFunction, MidiMessage, U7};
+use wmidi::{Channel, ControlFunction, MidiMessage, Note, U7};
 
-use crate::midi_output_trait::MidiOutput;
+use crate::{midi_backend::MidiBackend, midi_output_trait::MidiOutput};
 use errors::Result;
 
 pub struct VirtualMidiOutput {}
 
 impl VirtualMidiOutput {
     #[allow(clippy::unnecessary_wraps)]
-    pub fn new(_device_name: &str) -> Result<Self> {
+    pub fn new(_device_name: &str, _backend: MidiBackend) -> Result<Self> {
         Ok(Self {})
     }
 }
@@ -36,6 +36,16 @@ impl MidiOutput for VirtualMidiOutput {
         MidiMessage::ControlChange(channel, cc, value).copy_to_slice(&mut message).unwrap();
     }
 
+    fn program_change(&mut self, channel: Channel, program: U7) {
+        info!("Sending channel {} program change {}", channel.index(), u8::from(program));
+        let mut message = [0; 2];
+        MidiMessage::ProgramChange(channel, program).copy_to_slice(&mut message).unwrap();
+    }
+
+    fn note(&mut self, channel: Channel, note: Note, velocity: U7) {
+        info!("Sending channel {} note {} velocity {}", channel.index(), u8::from(note), u8::from(velocity));
+    }
+
     fn sysex(&mut self, value: &str) {
         info!("Sending as sysex: {value}");
     }
@@ -0,0 +1,216 @@
+//! Ambiguity between a straight and a triplet feel. Grooves built on quarter triplets make the tempo and 1.5 times it
+//! nearly as strong, which the weights against multiples and subdivisions, made for the octave errors, don't settle.
+//! The histogram is checked for a peak at 3:2 of the estimate, both candidates are then reported and the one the user
+//! picks is favoured the way a tapped tempo is
+
+use crate::StaticBPMDetectionParameters;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+// as a share of the tempo, how far from 3:2 of the estimate the other peak can be
+const TOLERANCE: f32 = 0.03;
+const TRIPLET_RATIO: f32 = 1.5;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
+pub struct FeelAmbiguityConfig {
+    // strength of the peak at 3:2 of the estimate relative to its own from which both tempos are reported, above 1 it
+    // never is
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub min_ratio_strength: f32,
+}
+
+impl Default for FeelAmbiguityConfig {
+    fn default() -> Self {
+        Self { min_ratio_strength: 0.6 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Feel {
+    Straight,
+    Triplet,
+}
+
+impl Feel {
+    pub const ALL: [Self; 2] = [Self::Straight, Self::Triplet];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Straight => "Straight",
+            Self::Triplet => "Triplet",
+        }
+    }
+}
+
+impl Display for Feel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Feel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|feel| feel.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown feel '{s}'"))
+    }
+}
+
+/// Tempos 3:2 apart the histogram supports nearly as much, the triplet one being the faster
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeelAmbiguity {
+    pub straight_bpm: f32,
+    pub triplet_bpm: f32,
+    // strength of the other peak relative to the one of the estimate
+    pub ratio_strength: f32,
+}
+
+impl FeelAmbiguity {
+    /// Looks for a peak at 3:2 of `bpm`, above or below it, in the histogram `bpm` was estimated from. None when there
+    /// is none, or when it is weaker than `min_ratio_strength` times the peak of the estimate
+    #[must_use]
+    pub fn detect(
+        histogram_data_points: &[f32],
+        histogram_parameters: &StaticBPMDetectionParameters,
+        bpm: f32,
+        min_ratio_strength: f32,
+    ) -> Option<Self> {
+        let (_, strength) = strongest_near(histogram_data_points, histogram_parameters, bpm)?;
+        if strength <= 0.0 {
+            return None;
+        }
+        [bpm * TRIPLET_RATIO, bpm / TRIPLET_RATIO]
+            .into_iter()
+            .filter_map(|target| {
+                let (index, other_strength) = strongest_near(histogram_data_points, histogram_parameters, target)?;
+                is_peak(histogram_data_points, index)
+                    .then(|| (histogram_parameters.index_to_bpm(index), other_strength))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(other_bpm, other_strength)| Self {
+                straight_bpm: bpm.min(other_bpm),
+                triplet_bpm: bpm.max(other_bpm),
+                ratio_strength: other_strength / strength,
+            })
+            .filter(|feel_ambiguity| feel_ambiguity.ratio_strength >= min_ratio_strength)
+    }
+
+    #[must_use]
+    pub fn bpm(&self, feel: Feel) -> f32 {
+        match feel {
+            Feel::Straight => self.straight_bpm,
+            Feel::Triplet => self.triplet_bpm,
+        }
+    }
+}
+
+impl Display for FeelAmbiguity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} straight or {:.1} triplet ({:.0}%)",
+            self.straight_bpm,
+            self.triplet_bpm,
+            self.ratio_strength * 100.0
+        )
+    }
+}
+
+// highest bin within the tolerance around `bpm`, with its value. None when the histogram doesn't reach that tempo
+fn strongest_near(
+    histogram_data_points: &[f32],
+    histogram_parameters: &StaticBPMDetectionParameters,
+    bpm: f32,
+) -> Option<(usize, f32)> {
+    let (low, high) = (bpm * (1.0 - TOLERANCE), bpm * (1.0 + TOLERANCE));
+    histogram_data_points
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| (low..=high).contains(&histogram_parameters.index_to_bpm(*index)))
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, value)| (index, *value))
+}
+
+// the highest bin of the window on a slope is at its edge, below the next bin out of it
+fn is_peak(histogram_data_points: &[f32], index: usize) -> bool {
+    let value = histogram_data_points[index];
+    value > 0.0
+        && index.checked_sub(1).is_none_or(|previous| histogram_data_points[previous] <= value)
+        && histogram_data_points.get(index + 1).is_none_or(|next| *next <= value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters() -> StaticBPMDetectionParameters {
+        StaticBPMDetectionParameters { bpm_center: 120.0, bpm_range: 120, ..StaticBPMDetectionParameters::default() }
+    }
+
+    // bumps of 1 BPM of deviation at the given tempos and strengths
+    fn histogram(peaks: &[(f32, f32)]) -> Vec<f32> {
+        let parameters = parameters();
+        (0..parameters.buffer_size())
+            .map(|index| {
+                let bin_bpm = parameters.index_to_bpm(index);
+                peaks.iter().map(|&(bpm, strength)| strength * (-0.5 * (bin_bpm - bpm).powi(2)).exp()).sum()
+            })
+            .collect()
+    }
+
+    fn detect(peaks: &[(f32, f32)], bpm: f32) -> Option<FeelAmbiguity> {
+        FeelAmbiguity::detect(&histogram(peaks), &parameters(), bpm, FeelAmbiguityConfig::default().min_ratio_strength)
+    }
+
+    #[test]
+    fn test_triplet_above() {
+        let feel_ambiguity = detect(&[(100.0, 1.0), (150.0, 0.8)], 100.0).unwrap();
+        assert!((feel_ambiguity.straight_bpm - 100.0).abs() < f32::EPSILON);
+        assert!((feel_ambiguity.triplet_bpm - 150.0).abs() < 1.0, "{feel_ambiguity:?}");
+        assert!((feel_ambiguity.ratio_strength - 0.8).abs() < 0.05, "{feel_ambiguity:?}");
+        assert!((feel_ambiguity.bpm(Feel::Triplet) - feel_ambiguity.triplet_bpm).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_straight_below() {
+        let feel_ambiguity = detect(&[(100.0, 0.9), (150.0, 1.0)], 150.0).unwrap();
+        assert!((feel_ambiguity.straight_bpm - 100.0).abs() < 1.0, "{feel_ambiguity:?}");
+        assert!((feel_ambiguity.triplet_bpm - 150.0).abs() < f32::EPSILON);
+        assert!((feel_ambiguity.ratio_strength - 0.9).abs() < 0.05, "{feel_ambiguity:?}");
+        assert!((feel_ambiguity.bpm(Feel::Straight) - feel_ambiguity.straight_bpm).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_no_false_positive() {
+        // too weak
+        assert_eq!(detect(&[(100.0, 1.0), (150.0, 0.3)], 100.0), None);
+        // octave, 4:3 and 5:4 peaks, however strong
+        assert_eq!(detect(&[(80.0, 1.0), (160.0, 0.95)], 80.0), None);
+        assert_eq!(detect(&[(100.0, 1.0), (133.3, 0.95)], 100.0), None);
+        assert_eq!(detect(&[(100.0, 1.0), (125.0, 0.95)], 100.0), None);
+        // only the slope of a peak beyond the tolerance
+        assert_eq!(detect(&[(100.0, 1.0), (156.0, 0.95)], 100.0), None);
+        // 3:2 out of the range of the histogram
+        assert_eq!(detect(&[(170.0, 1.0)], 170.0), None);
+        assert_eq!(detect(&[], 100.0), None);
+    }
+
+    #[test]
+    fn test_feel_names() {
+        for feel in Feel::ALL {
+            assert_eq!(feel.to_string().parse::<Feel>(), Ok(feel));
+        }
+        assert_eq!("triplet".parse::<Feel>(), Ok(Feel::Triplet));
+        assert!("swing".parse::<Feel>().is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{BPMDetection, DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+
+/// Notes frozen as a reference input, evaluated next to the live detection so that parameter changes can be judged
+/// against the same material. The new notes don't reach it, it is only evaluated again when the parameters change
+pub struct FrozenReference {
+    bpm_detection: BPMDetection,
+    // the parameters changed since the last evaluation
+    outdated: bool,
+}
+
+impl FrozenReference {
+    /// Freezes the notes `bpm_detection` received so far, evaluated with the configured parameters rather than the
+    /// ones the live detection may have narrowed to
+    #[must_use]
+    pub fn new(bpm_detection: &BPMDetection, configured: StaticBPMDetectionParameters) -> Self {
+        let mut bpm_detection = bpm_detection.freeze();
+        bpm_detection.update_static_parameters(configured);
+        Self { bpm_detection, outdated: true }
+    }
+
+    pub fn update_static_parameters(&mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) {
+        self.bpm_detection.update_static_parameters(static_bpm_detection_parameters);
+        self.outdated = true;
+    }
+
+    pub fn dynamic_parameters_changed(&mut self) {
+        self.outdated = true;
+    }
+
+    /// Histogram and tempo of the frozen notes, none if they were already evaluated with the current parameters
+    pub fn evaluate(
+        &mut self,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+    ) -> Option<(&[f32], f32)> {
+        if !self.outdated {
+            return None;
+        }
+        self.outdated = false;
+        self.bpm_detection
+            .compute_bpm(dynamic_bpm_detection_parameters)
+            .map(|(histogram_data_points, estimate)| (histogram_data_points, estimate.bpm))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bpm::bpm_to_beat_duration, midi_messages::MidiNoteOn, TimedMidiNoteOn};
+
+    fn notes_at(bpm: f32, count: i32) -> impl Iterator<Item = TimedMidiNoteOn> {
+        (0..count).map(move |beat| TimedMidiNoteOn {
+            timestamp: bpm_to_beat_duration(bpm) * beat,
+            midi_message: MidiNoteOn { channel: 0, note: 60, velocity: 100 },
+        })
+    }
+
+    #[test]
+    fn test_only_changes_with_parameters() {
+        let static_bpm_detection_parameters = StaticBPMDetectionParameters {
+            bpm_center: 100.0,
+            bpm_range: 80,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let mut dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters.clone());
+        for note in notes_at(120.0, 12) {
+            bpm_detection.receive_midi_message(note);
+        }
+
+        let mut frozen_reference = FrozenReference::new(&bpm_detection, static_bpm_detection_parameters.clone());
+        let (histogram_data_points, bpm) = frozen_reference.evaluate(&dynamic_bpm_detection_parameters).unwrap();
+        let histogram_data_points = histogram_data_points.to_vec();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+        assert!(frozen_reference.evaluate(&dynamic_bpm_detection_parameters).is_none());
+
+        // the live detection moves on to another tempo
+        for note in notes_at(80.0, 24) {
+            bpm_detection.receive_midi_message(TimedMidiNoteOn {
+                timestamp: note.timestamp + bpm_to_beat_duration(120.0) * 12,
+                ..note
+            });
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters);
+        }
+        assert!(frozen_reference.evaluate(&dynamic_bpm_detection_parameters).is_none());
+
+        // same notes, same parameters, same result
+        frozen_reference.dynamic_parameters_changed();
+        let (same_histogram_data_points, same_bpm) =
+            frozen_reference.evaluate(&dynamic_bpm_detection_parameters).unwrap();
+        assert_eq!(same_histogram_data_points, histogram_data_points);
+        assert!((same_bpm - bpm).abs() < f32::EPSILON);
+
+        *dynamic_bpm_detection_parameters.high_tempo_bias.value_mut() = 2.0;
+        frozen_reference.dynamic_parameters_changed();
+        let (changed_histogram_data_points, _) = frozen_reference.evaluate(&dynamic_bpm_detection_parameters).unwrap();
+        assert_ne!(changed_histogram_data_points, histogram_data_points);
+
+        frozen_reference.update_static_parameters(StaticBPMDetectionParameters {
+            bpm_range: 60,
+            ..static_bpm_detection_parameters
+        });
+        let (narrowed_histogram_data_points, bpm) =
+            frozen_reference.evaluate(&dynamic_bpm_detection_parameters).unwrap();
+        assert_ne!(narrowed_histogram_data_points.len(), histogram_data_points.len());
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+        assert!(frozen_reference.evaluate(&dynamic_bpm_detection_parameters).is_none());
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+#[cfg(target_os = "macos")]
+use std::sync::OnceLock;
+
+use sync::Mutex;
+
+type Callback = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Default)]
+struct Observers {
+    next_id: u64,
+    callbacks: Vec<(u64, Callback)>,
+    installed: bool,
+}
+
+/// Device change observer shared by every consumer of the process. The observer is installed along with the first
+/// callback, and uninstalled when the handle of the last one is dropped
+pub struct HotplugRegistry {
+    install: Box<dyn Fn() -> Result<(), String> + Send + Sync>,
+    uninstall: Box<dyn Fn() + Send + Sync>,
+    observers: Mutex<Observers>,
+}
+
+impl HotplugRegistry {
+    #[must_use]
+    pub fn new(
+        install: impl Fn() -> Result<(), String> + Send + Sync + 'static,
+        uninstall: impl Fn() + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self { install: Box::new(install), uninstall: Box::new(uninstall), observers: Mutex::default() })
+    }
+
+    /// `callback` is called on every device change until the returned handle is dropped
+    pub fn register(self: &Arc<Self>, callback: impl Fn() + Send + Sync + 'static) -> Result<HotplugHandle, String> {
+        let mut observers = self.observers.lock();
+        if !observers.installed {
+            (self.install)()?;
+            observers.installed = true;
+        }
+        let id = observers.next_id;
+        observers.next_id += 1;
+        observers.callbacks.push((id, Arc::new(callback)));
+        Ok(HotplugHandle { registration: Some((self.clone(), id)) })
+    }
+
+    /// Called by the observer, fans out the notification to all the registered callbacks
+    pub fn notify(&self) {
+        // callbacks run outside of the lock, they may drop their handle
+        let callbacks =
+            self.observers.lock().callbacks.iter().map(|(_, callback)| callback.clone()).collect::<Vec<_>>();
+        for callback in callbacks {
+            callback();
+        }
+    }
+
+    fn unregister(&self, id: u64) {
+        let mut observers = self.observers.lock();
+        observers.callbacks.retain(|(callback_id, _)| *callback_id != id);
+        if observers.callbacks.is_empty() && observers.installed {
+            (self.uninstall)();
+            observers.installed = false;
+        }
+    }
+}
+
+/// Keeps a callback registered to device changes, see `receive_device_updates`
+#[must_use]
+pub struct HotplugHandle {
+    registration: Option<(Arc<HotplugRegistry>, u64)>,
+}
+
+impl HotplugHandle {
+    // for platforms without device change notifications
+    #[cfg(not(target_os = "macos"))]
+    fn noop() -> Self {
+        Self { registration: None }
+    }
+}
+
+impl Drop for HotplugHandle {
+    fn drop(&mut self) {
+        if let Some((registry, id)) = self.registration.take() {
+            registry.unregister(id);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn registry() -> &'static Arc<HotplugRegistry> {
+    static REGISTRY: OnceLock<Arc<HotplugRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        HotplugRegistry::new(
+            || {
+                // coremidi_hotplug_notification can't remove its observer, so it is only ever installed once. Once
+                // the last consumer is gone, notifications reach nobody until a new one registers
+                static OBSERVER: OnceLock<Result<(), String>> = OnceLock::new();
+                OBSERVER
+                    .get_or_init(|| {
+                        coremidi_hotplug_notification::receive_device_updates(|| registry().notify())
+                            .map_err(|err| err.to_string())
+                    })
+                    .clone()
+            },
+            || {},
+        )
+    })
+}
+
+/// Registers `callback` to the device changes of the process, through a single CoreMIDI observer however many
+/// plugin instances or services are running
+#[cfg(target_os = "macos")]
+pub fn receive_device_updates(callback: impl Fn() + Send + Sync + 'static) -> Result<HotplugHandle, String> {
+    registry().register(callback)
+}
+
+#[cfg(not(target_os = "macos"))]
+#[allow(clippy::unnecessary_wraps)]
+pub fn receive_device_updates(_callback: impl Fn() + Send + Sync + 'static) -> Result<HotplugHandle, String> {
+    Ok(HotplugHandle::noop())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FakeObserver {
+        installs: Arc<AtomicUsize>,
+        uninstalls: Arc<AtomicUsize>,
+        registry: Arc<HotplugRegistry>,
+    }
+
+    impl FakeObserver {
+        fn new() -> Self {
+            let installs = Arc::new(AtomicUsize::new(0));
+            let uninstalls = Arc::new(AtomicUsize::new(0));
+            let registry = HotplugRegistry::new(
+                {
+                    let installs = installs.clone();
+                    move || {
+                        installs.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    }
+                },
+                {
+                    let uninstalls = uninstalls.clone();
+                    move || {
+                        uninstalls.fetch_add(1, Ordering::SeqCst);
+                    }
+                },
+            );
+            Self { installs, uninstalls, registry }
+        }
+
+        fn counts(&self) -> (usize, usize) {
+            (self.installs.load(Ordering::SeqCst), self.uninstalls.load(Ordering::SeqCst))
+        }
+    }
+
+    fn counter() -> (Arc<AtomicUsize>, impl Fn() + Send + Sync + 'static) {
+        let count = Arc::new(AtomicUsize::new(0));
+        (count.clone(), move || {
+            count.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let fake_observer = FakeObserver::new();
+        let (first_count, first) = counter();
+        let (second_count, second) = counter();
+        let first_handle = fake_observer.registry.register(first).unwrap();
+        let second_handle = fake_observer.registry.register(second).unwrap();
+        assert_eq!(fake_observer.counts(), (1, 0));
+
+        fake_observer.registry.notify();
+        fake_observer.registry.notify();
+        assert_eq!(first_count.load(Ordering::SeqCst), 2);
+        assert_eq!(second_count.load(Ordering::SeqCst), 2);
+
+        drop(first_handle);
+        fake_observer.registry.notify();
+        assert_eq!(first_count.load(Ordering::SeqCst), 2);
+        assert_eq!(second_count.load(Ordering::SeqCst), 3);
+        drop(second_handle);
+    }
+
+    #[test]
+    fn test_handle_drop() {
+        let fake_observer = FakeObserver::new();
+        let handles = (0..3).map(|_| fake_observer.registry.register(|| {}).unwrap()).collect::<Vec<_>>();
+        assert_eq!(fake_observer.counts(), (1, 0));
+
+        // the last consumer uninstalls the observer, once
+        let mut handles = handles.into_iter();
+        drop(handles.next());
+        drop(handles.next());
+        assert_eq!(fake_observer.counts(), (1, 0));
+        drop(handles.next());
+        assert_eq!(fake_observer.counts(), (1, 1));
+        fake_observer.registry.notify();
+
+        // a new consumer installs it again
+        let handle = fake_observer.registry.register(|| {}).unwrap();
+        assert_eq!(fake_observer.counts(), (2, 1));
+        drop(handle);
+        assert_eq!(fake_observer.counts(), (2, 2));
+    }
+
+    #[test]
+    fn test_failed_install() {
+        let registry = HotplugRegistry::new(|| Err("no CoreMIDI".to_string()), || panic!("nothing to uninstall"));
+        assert_eq!(registry.register(|| {}).err(), Some("no CoreMIDI".to_string()));
+        assert!(registry.observers.lock().callbacks.is_empty());
+    }
+
+    #[test]
+    fn test_callback_dropping_its_handle() {
+        let fake_observer = FakeObserver::new();
+        let handle = Arc::new(Mutex::new(None));
+        *handle.lock() = Some(
+            fake_observer
+                .registry
+                .register({
+                    let handle = handle.clone();
+                    move || drop(handle.lock().take())
+                })
+                .unwrap(),
+        );
+        fake_observer.registry.notify();
+        assert_eq!(fake_observer.counts(), (1, 1));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_noop_handle() {
+        drop(receive_device_updates(|| {}).unwrap());
+    }
+}
@@ -0,0 +1,175 @@
+//! Precision of the note timestamps as they are received. Backends report them in microseconds, but drivers quantize
+//! them or deliver them with a jitter of a millisecond or so: histogram bins finer than that only spread the same notes
+//! over more bins, at the cost of computing them. The precision is measured on the notes themselves, and
+//! `BPMDetection` caps its histogram resolution to it, see `StaticBPMDetectionParameters::resolution_floor_factor`
+
+use chrono::Duration;
+use std::time::Duration as StdDuration;
+
+// intervals the estimates are made over, each window replaces the estimate of the previous one
+const WINDOW: usize = 32;
+// in microseconds. Intervals sharing a larger divisor are the rhythm itself, such as sequenced 16th notes, rather than a
+// quantization of the timestamps
+const MAX_STEP: u64 = 4000;
+// two successive intervals closer than that share of the longest one are taken as the same note value played twice
+const SIMILAR_INTERVALS: f64 = 0.05;
+// the difference of two successive intervals involves three timestamps, its deviation is √6 times the one of a
+// timestamp. The median of its absolute value is 0.6745 times its deviation
+const MEDIAN_TO_JITTER: f64 = 0.6745 * 2.449_489_742_783_178;
+
+/// Running estimate of the precision of the note timestamps: the step they are quantized to, or the jitter of the
+/// repeated intervals when it is larger
+#[derive(Clone, Debug, Default)]
+pub struct InputPrecision {
+    // newest timestamp and the interval before it, in microseconds
+    previous: Option<(i64, Option<i64>)>,
+    window_step: u64,
+    window_intervals: usize,
+    deviations: [u64; WINDOW],
+    window_deviations: usize,
+    // estimates of the last complete windows, in microseconds
+    step: Option<u64>,
+    jitter: Option<u64>,
+}
+
+/// Measured precision of the note timestamps along with the histogram resolution it leaves, see `InputPrecision`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolutionFloor {
+    pub precision: StdDuration,
+    // bins per second the histogram is computed with, below the configured resolution when it was capped
+    pub effective_resolution: u16,
+}
+
+impl InputPrecision {
+    /// Notes of a chord, or older than the previous one, don't tell anything about the precision and are skipped
+    pub fn note(&mut self, timestamp: Duration) {
+        let Some(timestamp) = timestamp.num_microseconds() else {
+            return;
+        };
+        let Some((previous, previous_interval)) = self.previous else {
+            self.previous = Some((timestamp, None));
+            return;
+        };
+        let interval = timestamp - previous;
+        if interval <= 0 {
+            return;
+        }
+        self.previous = Some((timestamp, Some(interval)));
+
+        self.window_step = gcd(self.window_step, interval.unsigned_abs());
+        self.window_intervals += 1;
+        if self.window_intervals == WINDOW {
+            self.step = Some(self.window_step).filter(|step| *step <= MAX_STEP);
+            self.window_step = 0;
+            self.window_intervals = 0;
+        }
+
+        let Some(previous_interval) = previous_interval else {
+            return;
+        };
+        let deviation = (interval - previous_interval).unsigned_abs();
+        if deviation as f64 > SIMILAR_INTERVALS * interval.max(previous_interval) as f64 {
+            return;
+        }
+        self.deviations[self.window_deviations] = deviation;
+        self.window_deviations += 1;
+        if self.window_deviations == WINDOW {
+            // the median leaves out the intervals that were meant to differ, such as a swung note
+            self.deviations.sort_unstable();
+            self.jitter = Some((self.deviations[WINDOW / 2] as f64 / MEDIAN_TO_JITTER) as u64);
+            self.window_deviations = 0;
+        }
+    }
+
+    /// None until a first window of notes was received
+    #[must_use]
+    pub fn precision(&self) -> Option<StdDuration> {
+        let precision = match (self.step, self.jitter) {
+            (None, None) => return None,
+            (step, jitter) => step.unwrap_or(0).max(jitter.unwrap_or(0)),
+        };
+        Some(StdDuration::from_micros(precision))
+    }
+}
+
+/// `resolution` lowered so that the bins of the histogram are no narrower than `resolution_floor_factor` times
+/// `precision`. A factor of 0 leaves it as it is
+#[must_use]
+pub fn capped_resolution(resolution: u16, precision: StdDuration, resolution_floor_factor: f32) -> u16 {
+    let floor = precision.as_secs_f64() * f64::from(resolution_floor_factor);
+    if !(floor > 0.0 && floor.is_finite()) {
+        return resolution;
+    }
+    let highest_resolution = (1.0 / floor).floor().clamp(1.0, f64::from(u16::MAX)) as u16;
+    resolution.min(highest_resolution)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(timestamps: impl IntoIterator<Item = i64>) -> Option<StdDuration> {
+        let mut input_precision = InputPrecision::default();
+        for timestamp in timestamps {
+            input_precision.note(Duration::microseconds(timestamp));
+        }
+        input_precision.precision()
+    }
+
+    #[test]
+    fn test_quantized_timestamps() {
+        // a steady tempo of 119.91 BPM, as a driver rounding to the millisecond delivers it
+        let timestamps = (0..80).map(|beat| beat * 500_370 / 1000 * 1000);
+        assert_eq!(measured(timestamps), Some(StdDuration::from_millis(1)));
+        // the chord notes are skipped
+        let timestamps = (0..80).flat_map(|beat| [beat * 500_370 / 1000 * 1000; 3]);
+        assert_eq!(measured(timestamps), Some(StdDuration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_jittered_timestamps() {
+        let mut state = 12345u64;
+        let mut random = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as f64 / (1u64 << 31) as f64
+        };
+        // exact microseconds, off by up to 2 ms, a deviation of 1.15 ms
+        let timestamps =
+            (0..80).map(|beat| beat * 500_000 + ((random() * 2.0 - 1.0) * 2000.0) as i64).collect::<Vec<_>>();
+        let precision = measured(timestamps).unwrap();
+        assert!(precision > StdDuration::from_millis(1) && precision < StdDuration::from_millis(2), "{precision:?}");
+    }
+
+    #[test]
+    fn test_sequenced_notes() {
+        // 16th notes at 120 BPM with a rest on every fourth, the intervals are all multiples of 125 ms
+        let timestamps = (0..160).filter(|sixteenth| sixteenth % 4 != 3).map(|sixteenth| sixteenth * 125_000);
+        assert_eq!(measured(timestamps), Some(StdDuration::ZERO));
+    }
+
+    #[test]
+    fn test_not_enough_notes() {
+        assert_eq!(measured((0..WINDOW as i64).map(|beat| beat * 500_000)), None);
+        assert_eq!(measured([0; 100]), None);
+    }
+
+    #[test]
+    fn test_capped_resolution() {
+        let precision = StdDuration::from_millis(1);
+        assert_eq!(capped_resolution(2000, precision, 1.0), 1000);
+        assert_eq!(capped_resolution(2000, precision, 2.0), 500);
+        assert_eq!(capped_resolution(2000, precision, 0.5), 2000);
+        // bins already wider than the precision
+        assert_eq!(capped_resolution(450, precision, 1.0), 450);
+        assert_eq!(capped_resolution(2000, precision, 0.0), 2000);
+        assert_eq!(capped_resolution(2000, StdDuration::ZERO, 1.0), 2000);
+        assert_eq!(capped_resolution(2000, StdDuration::from_secs(10), 1.0), 1);
+    }
+}
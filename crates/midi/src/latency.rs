@@ -0,0 +1,219 @@
+use arraydeque::{ArrayDeque, Wrapping};
+use chrono::Duration;
+use derivative::Derivative;
+use instant::Instant;
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+    time::Duration as StdDuration,
+};
+use sync::Mutex;
+
+// latencies kept for the statistics, a few minutes of evaluations at a steady pace
+const WINDOW: usize = 256;
+
+/// Maps the timeline of the notes onto the wall clock, from one note timestamp known to have happened at `instant`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockAnchor {
+    pub timestamp: Duration,
+    pub instant: Instant,
+}
+
+impl ClockAnchor {
+    #[must_use]
+    pub fn new(timestamp: Duration, instant: Instant) -> Self {
+        Self { timestamp, instant }
+    }
+
+    /// When a note with that timestamp happened according to the wall clock
+    #[must_use]
+    pub fn instant_of(&self, timestamp: Duration) -> Instant {
+        let offset = timestamp - self.timestamp;
+        match offset.to_std() {
+            Ok(offset) => self.instant + offset,
+            Err(_) => self.instant.checked_sub((-offset).to_std().unwrap_or_default()).unwrap_or(self.instant),
+        }
+    }
+
+    /// Time elapsed between the note with that timestamp and `now`, zero if the note seems to be in the future
+    #[must_use]
+    pub fn elapsed_since(&self, timestamp: Duration, now: Instant) -> StdDuration {
+        now.saturating_duration_since(self.instant_of(timestamp))
+    }
+
+    /// Whether the timeline and the wall clock disagree by more than `tolerance` on when `timestamp` happened, as after
+    /// the timeline stopped while the wall clock went on. The anchor has to be taken again from `timestamp` then
+    #[must_use]
+    pub fn drifted(&self, timestamp: Duration, now: Instant, tolerance: StdDuration) -> bool {
+        let instant = self.instant_of(timestamp);
+        now.saturating_duration_since(instant).max(instant.saturating_duration_since(now)) > tolerance
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub mean: StdDuration,
+    pub p95: StdDuration,
+    pub samples: usize,
+}
+
+impl Display for LatencySummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} ms mean, {:.1} ms p95", self.mean.as_secs_f64() * 1000.0, self.p95.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Rolling statistics over the most recent latencies
+#[derive(Clone, Debug)]
+pub struct LatencyStats {
+    // in microseconds
+    latencies: ArrayDeque<u64, WINDOW, Wrapping>,
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self { latencies: ArrayDeque::new() }
+    }
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: StdDuration) {
+        self.latencies.push_back(u64::try_from(latency.as_micros()).unwrap_or(u64::MAX));
+    }
+
+    pub fn clear(&mut self) {
+        self.latencies.clear();
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> Option<LatencySummary> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut sorted = self.latencies.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let total = sorted.iter().map(|latency| u128::from(*latency)).sum::<u128>();
+        let mean = u64::try_from(total / sorted.len() as u128).unwrap_or(u64::MAX);
+        // nearest rank
+        let p95 = sorted[(sorted.len() * 95).div_ceil(100) - 1];
+        Some(LatencySummary {
+            mean: StdDuration::from_micros(mean),
+            p95: StdDuration::from_micros(p95),
+            samples: sorted.len(),
+        })
+    }
+}
+
+/// Delay between the newest note included in an evaluation and the delivery of the resulting tempo. Shared by all
+/// clones, recorded by whatever sends the tempo and read for display. Only tempos that went through are counted, none
+/// is recorded while the connection to the DAW is down
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct TempoLatency(#[derivative(Debug = "ignore")] Arc<Mutex<LatencyStats>>);
+
+impl Default for TempoLatency {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(LatencyStats::default())))
+    }
+}
+
+impl TempoLatency {
+    pub fn record(&self, latency: StdDuration) {
+        self.0.lock().record(latency);
+    }
+
+    /// Records the latency of a tempo sent `now`, computed from the newest note of its evaluation
+    pub fn record_delivery(&self, clock_anchor: &ClockAnchor, newest_note: Duration, now: Instant) {
+        self.record(clock_anchor.elapsed_since(newest_note, now));
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> Option<LatencySummary> {
+        self.0.lock().summary()
+    }
+
+    /// Summary without waiting for a latency being recorded, for the crash report
+    #[must_use]
+    pub fn try_summary(&self) -> Option<LatencySummary> {
+        self.0.try_lock()?.summary()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(millis: u64) -> StdDuration {
+        StdDuration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut latency_stats = LatencyStats::default();
+        assert_eq!(latency_stats.summary(), None);
+
+        for latency in 1..=100 {
+            latency_stats.record(millis(latency));
+        }
+        let summary = latency_stats.summary().unwrap();
+        assert_eq!(summary.samples, 100);
+        assert_eq!(summary.mean, StdDuration::from_micros(50_500));
+        assert_eq!(summary.p95, millis(95));
+        assert_eq!(summary.to_string(), "50.5 ms mean, 95.0 ms p95");
+
+        latency_stats.clear();
+        latency_stats.record(millis(7));
+        assert_eq!(latency_stats.summary(), Some(LatencySummary { mean: millis(7), p95: millis(7), samples: 1 }));
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut latency_stats = LatencyStats::default();
+        for _ in 0..WINDOW {
+            latency_stats.record(millis(1000));
+        }
+        for _ in 0..WINDOW {
+            latency_stats.record(millis(10));
+        }
+        let summary = latency_stats.summary().unwrap();
+        assert_eq!(summary.samples, WINDOW);
+        assert_eq!(summary.mean, millis(10));
+        assert_eq!(summary.p95, millis(10));
+    }
+
+    #[test]
+    fn test_p95_outliers() {
+        let mut latency_stats = LatencyStats::default();
+        for latency in (0..100).map(|n| if n % 20 == 0 { 200 } else { 10 }) {
+            latency_stats.record(millis(latency));
+        }
+        // 5 outliers out of 100 are at the 95th percentile, not above
+        assert_eq!(latency_stats.summary().unwrap().p95, millis(10));
+        latency_stats.record(millis(200));
+        assert_eq!(latency_stats.summary().unwrap().p95, millis(200));
+    }
+
+    #[test]
+    fn test_clock_anchor() {
+        let start = Instant::now();
+        let clock_anchor = ClockAnchor::new(Duration::seconds(10), start + millis(500));
+
+        assert_eq!(clock_anchor.instant_of(Duration::seconds(10)), start + millis(500));
+        assert_eq!(clock_anchor.instant_of(Duration::milliseconds(10_250)), start + millis(750));
+        assert_eq!(clock_anchor.instant_of(Duration::milliseconds(9_800)), start + millis(300));
+
+        assert_eq!(clock_anchor.elapsed_since(Duration::milliseconds(10_100), start + millis(620)), millis(20));
+        // the evaluation can't be older than its notes, clocks disagreeing slightly give zero
+        assert_eq!(clock_anchor.elapsed_since(Duration::seconds(11), start + millis(600)), StdDuration::ZERO);
+
+        assert!(!clock_anchor.drifted(Duration::milliseconds(10_250), start + millis(760), millis(20)));
+        // the timeline stopped for a second
+        assert!(clock_anchor.drifted(Duration::milliseconds(10_250), start + millis(1750), millis(20)));
+        // or ran ahead of the wall clock, as when rendering offline
+        assert!(clock_anchor.drifted(Duration::seconds(12), start + millis(600), millis(20)));
+
+        let tempo_latency = TempoLatency::default();
+        tempo_latency.clone().record_delivery(&clock_anchor, Duration::milliseconds(10_100), start + millis(640));
+        assert_eq!(tempo_latency.summary().map(|summary| summary.mean), Some(millis(40)));
+    }
+}
@@ -7,6 +7,7 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_precision_loss)]
 
+use build::PROJECT_NAME;
 #[cfg(any(target_os = "macos", target_os = "ios"))]
 pub use coremidi::restart;
 use derivative::Derivative;
@@ -21,12 +22,49 @@ pub type MidiError = wmidi::Error;
 
 pub use crate::midi_messages::{TimedMidiNoteOn, TimedTypedMidiMessage};
 
+pub mod accumulator;
+pub mod age_anchor;
+pub mod auto_zoom;
+pub mod beat_counter;
 pub mod bpm;
 pub mod bpm_detection_receiver;
+pub mod channel_filter;
+pub mod chord_filter;
+pub mod clock_input;
+pub mod clock_lookahead;
+pub mod daw_link;
+pub mod daw_link_protocol;
+pub mod detector;
+pub mod egress_hub;
+mod error;
+pub mod feel_ambiguity;
+pub mod frozen_reference;
+pub mod hotplug;
+pub mod input_precision;
+pub mod latency;
+pub mod memory;
+pub mod midi_backend;
+mod midi_file;
 pub mod midi_in;
 pub mod midi_messages;
 mod midi_output;
+pub mod multi_resolution;
 mod normal_distribution;
+pub mod osc_output;
+pub mod parameter_ramp;
+pub mod parameters;
+pub mod patterns;
+pub mod pre_detection;
+mod rate_limiter;
+pub mod remote_control;
+pub mod session_stats;
+pub mod tap_tempo;
+pub mod tempo_bands;
+pub mod tempo_change;
+mod tempo_map;
+pub mod tempo_source;
+pub mod velocity_gate;
+pub mod weight_response;
 mod worker;
 
 mod bpm_detection;
@@ -39,21 +77,125 @@ mod worker_event;
 
 pub use num_traits_chrono::DurationOps;
 
-pub use bpm_detection::BPMDetection;
+pub use accumulator::Accumulator;
+pub use age_anchor::AgeAnchor;
+pub use auto_zoom::AutoZoom;
+pub use beat_counter::{BarPosition, BeatCounter, BeatCounterConfig, BeatPhase, TimeSignature};
+pub use bpm_detection::{BPMDetection, BpmEstimate, PhaseSplit};
+pub use channel_filter::ChannelFilter;
+pub use chord_filter::ChordFilter;
+pub use clock_input::{ClockInputConfig, ClockTempo, ClockTick};
+pub use clock_lookahead::{ClockLookaheadConfig, ClockScheduler, TempoTrend, MAX_LOOKAHEAD};
+pub use daw_link::{DawConnector, DawLink, DawLinkConfig};
+pub use daw_link_protocol::{DawMessage, DawMessageReader};
+pub use detector::{Detector, DetectorBuilder};
+pub use egress_hub::{Egress, EgressHub, EgressSink, EgressSinks};
+pub use error::CoreError;
+pub use feel_ambiguity::{Feel, FeelAmbiguity, FeelAmbiguityConfig};
+pub use frozen_reference::FrozenReference;
+pub use input_precision::{InputPrecision, ResolutionFloor};
+pub use latency::{ClockAnchor, LatencyStats, LatencySummary, TempoLatency};
+pub use midi_backend::MidiBackend;
+pub use midi_file::{MidiFile, MidiFileError};
+pub use multi_resolution::{MultiResolutionConfig, MultiResolutionHistogram};
+pub use osc_output::{OscOutput, OscOutputConfig};
+pub use parameter_ramp::ParameterRamp;
+pub use parameters::ParameterDescriptor;
+pub use patterns::{DemoPatternConfig, PatternGenerator, PatternKind};
+pub use pre_detection::PreDetection;
+pub use rate_limiter::{RateLimitedNotes, RateLimiter, RateLimiterConfig};
+pub use remote_control::{RemoteControlClient, RemoteControlServer, RemoteControlServerConfig, RemoteMessage};
+pub use session_stats::{SessionStats, SessionStatsConfig, SessionSummary};
 pub use sysex::SysExCommand;
+pub use tap_tempo::{TapTempo, TapTrigger};
+pub use tempo_bands::{TempoBand, TempoBandAction, TempoBands};
+pub use tempo_change::TempoChangeDetector;
+pub use tempo_map::{write_smf, TempoCurve, TempoMapConfig};
+pub use tempo_source::{SharedTempoSource, TempoSource};
+pub use velocity_gate::VelocityGate;
+pub use weight_response::WeightResponse;
 
 pub use crate::{
-    bpm::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters},
-    midi_input_port::MidiInputPort,
+    bpm::{validate_interaction, ConfigWarning, DynamicBPMDetectionParameters, StaticBPMDetectionParameters},
+    midi_input_port::{MidiInputPort, PortName},
 };
 use parameter::{MutGetters, Parameter};
 use sync::ArcAtomicBool;
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
 pub struct MidiServiceConfig {
     pub device_name: String,
     pub send_tempo: ArcAtomicBool,
     pub enable_midi_clock: ArcAtomicBool,
+    // system MIDI layer, among the ones this build has
+    pub backend: MidiBackend,
+    // tempo used for the clock and the SysEx, see `TempoSource`
+    pub tempo_source: SharedTempoSource,
+    pub rate_limit: RateLimiterConfig,
+    pub demo_pattern: DemoPatternConfig,
+    pub beat_counter: BeatCounterConfig,
+    // note tapping the tempo instead of being detected, none when tapping only comes from the interface
+    pub tap_trigger: Option<TapTrigger>,
+    // TCP connection to a script running next to the DAW, see `daw_link`
+    pub daw_link: DawLinkConfig,
+    // `/bpm` OSC messages over UDP, see `osc_output`
+    pub osc_output: OscOutputConfig,
+    // how the steadiness of the detected tempo is scored, see `SessionStats`
+    pub session_stats: SessionStatsConfig,
+    // coarse histogram over the whole range and a fine one around the estimate, see `MultiResolutionConfig`
+    pub multi_resolution: MultiResolutionConfig,
+    // the MIDI clock runs ahead of the beat while the tempo is steady, see `ClockLookaheadConfig`
+    pub clock_lookahead: ClockLookaheadConfig,
+    // when the tempo and 1.5 times it are both reported, see `FeelAmbiguity`
+    pub feel_ambiguity: FeelAmbiguityConfig,
+    // tempo of the MIDI clock received, shown next to the detected one, see `clock_input`
+    pub clock_input: ClockInputConfig,
+    // MIDI sent when the tempo moves from one band to another, see `TempoBands`
+    pub tempo_bands: Vec<TempoBand>,
+    // in BPM, how far out of the active band the tempo goes before another one is entered
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub tempo_band_hysteresis: f32,
+    // diagnostic, shared by all clones of the configuration
+    #[serde(skip)]
+    #[derivative(PartialEq = "ignore")]
+    pub tempo_latency: TempoLatency,
+    // notes dropped by the limiter of `rate_limit`, shared by all clones of the configuration
+    #[serde(skip)]
+    #[derivative(PartialEq = "ignore")]
+    pub rate_limited_notes: RateLimitedNotes,
+    // set by the application, see `memory::LOW_MEMORY_BUDGET`
+    #[serde(skip)]
+    pub low_memory: bool,
+}
+
+impl Default for MidiServiceConfig {
+    fn default() -> Self {
+        Self {
+            device_name: PROJECT_NAME.to_string(),
+            send_tempo: ArcAtomicBool::default(),
+            enable_midi_clock: ArcAtomicBool::default(),
+            backend: MidiBackend::default(),
+            tempo_source: SharedTempoSource::default(),
+            rate_limit: RateLimiterConfig::default(),
+            demo_pattern: DemoPatternConfig::default(),
+            beat_counter: BeatCounterConfig::default(),
+            tap_trigger: None,
+            daw_link: DawLinkConfig::default(),
+            osc_output: OscOutputConfig::default(),
+            session_stats: SessionStatsConfig::default(),
+            multi_resolution: MultiResolutionConfig::default(),
+            clock_lookahead: ClockLookaheadConfig::default(),
+            feel_ambiguity: FeelAmbiguityConfig::default(),
+            clock_input: ClockInputConfig::default(),
+            tempo_bands: Vec::new(),
+            tempo_band_hysteresis: 2.0,
+            tempo_latency: TempoLatency::default(),
+            rate_limited_notes: RateLimitedNotes::default(),
+            low_memory: false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Derivative, MutGetters)]
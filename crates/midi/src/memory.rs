@@ -0,0 +1,197 @@
+use crate::{StaticBPMDetectionParameters, TempoMapConfig};
+
+// low-memory mode, for devices such as a Raspberry Pi Zero. Its buffers take about 100 KiB at most, against about
+// 2 MiB otherwise: mostly the notes and the events waiting for the detection, then a histogram sized for the largest
+// parameters. The GUI only allocates its copies of the histogram at the size received
+/// Notes kept by the detection, a few bars of dense playing
+pub const LOW_MEMORY_NOTE_CAPACITY: usize = 1000;
+/// Events waiting for the detection the buffer goes back to after a burst
+pub const LOW_MEMORY_EVENT_CAPACITY: usize = 128;
+/// Highest histogram resolution, in bins per second. About 1 BPM per bin at 120 BPM
+pub const LOW_MEMORY_HISTOGRAM_RESOLUTION: u16 = 250;
+/// Points of the tempo map
+pub const LOW_MEMORY_TEMPO_POINTS: usize = 1000;
+/// Bytes the buffers followed by `MemoryStats` stay under
+pub const LOW_MEMORY_BUDGET: usize = 128 * 1024;
+
+/// Brings the parameters within what the low-memory mode allows
+pub fn limit_static_parameters(static_bpm_detection_parameters: &mut StaticBPMDetectionParameters) {
+    static_bpm_detection_parameters.histogram_resolution =
+        static_bpm_detection_parameters.histogram_resolution.min(LOW_MEMORY_HISTOGRAM_RESOLUTION);
+}
+
+/// Brings the tempo map within what the low-memory mode allows
+pub fn limit_tempo_map(tempo_map_config: &mut TempoMapConfig) {
+    tempo_map_config.max_points = tempo_map_config.max_points.min(LOW_MEMORY_TEMPO_POINTS);
+}
+
+/// Gives back what a burst or a larger configuration left allocated : when `vec` can hold more than twice what is
+/// needed, its capacity is brought back to `needed`. Returns whether memory was released.
+pub fn shrink_excess<T>(vec: &mut Vec<T>, needed: usize) -> bool {
+    if vec.capacity() > needed.saturating_mul(2) {
+        vec.shrink_to(needed.max(vec.len()));
+        true
+    } else {
+        false
+    }
+}
+
+/// Capacities of the long-lived buffers of the detection pipeline, to confirm they stay bounded over long sessions
+#[cfg(feature = "memory-stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub buffered_events: usize,
+    pub notes: usize,
+    pub note_capacity: usize,
+    pub histogram_data_points: usize,
+}
+
+#[cfg(feature = "memory-stats")]
+impl MemoryStats {
+    /// Bytes allocated for the buffers
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.buffered_events * std::mem::size_of::<crate::worker_event::WorkerEvent>()
+            + self.note_capacity * std::mem::size_of::<crate::TimedMidiNoteOn>()
+            + self.histogram_data_points * std::mem::size_of::<f32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrink_excess() {
+        let mut vec = Vec::<u8>::with_capacity(100);
+        assert!(!shrink_excess(&mut vec, 50));
+        assert_eq!(vec.capacity(), 100);
+
+        vec.extend([0; 10]);
+        assert!(shrink_excess(&mut vec, 20));
+        assert!(vec.capacity() >= 20 && vec.capacity() < 100);
+        assert_eq!(vec.len(), 10);
+
+        // never drops content
+        let mut vec = vec![0u8; 30];
+        vec.reserve(1000);
+        assert!(shrink_excess(&mut vec, 5));
+        assert_eq!(vec.len(), 30);
+    }
+
+    #[cfg(feature = "memory-stats")]
+    #[test]
+    fn test_soak() {
+        use crate::{
+            bpm::max_histogram_data_buffer_size, bpm_detection::NOTE_CAPACITY, midi_messages::MidiNoteOn, BPMDetection,
+            DynamicBPMDetectionParameters, NormalDistributionConfig, StaticBPMDetectionParameters, TimedMidiNoteOn,
+        };
+        use chrono::Duration;
+
+        let static_bpm_detection_parameters = |n: u32| StaticBPMDetectionParameters {
+            bpm_center: 60.0 + (n % 90) as f32,
+            bpm_range: 1 + (n % 100) as u16,
+            histogram_resolution: 1 + (n * 7 % 2000) as u16,
+            // keeps each evaluation cheap, the point is the amount of notes and changes
+            normal_distribution: NormalDistributionConfig { imprecision: 1.0, ..NormalDistributionConfig::default() },
+            ..StaticBPMDetectionParameters::default()
+        };
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 2, ..DynamicBPMDetectionParameters::default() };
+
+        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters(0));
+        let mut buffered_events = Vec::with_capacity(NOTE_CAPACITY);
+        let mut stats = Vec::new();
+
+        // simulated time: 2 million notes, every 50ms, arriving in bursts of up to 50000 and a config change every ten
+        // bursts. The estimate after a change keeps the notes, changing after each burst would never prune them
+        let mut note = 0u32;
+        for burst in 0..2000u32 {
+            let burst_size = if burst % 100 == 0 { 50_000 } else { 500 };
+            buffered_events.extend((0..burst_size).map(|_| {
+                note += 1;
+                TimedMidiNoteOn {
+                    timestamp: Duration::milliseconds(i64::from(note) * 50),
+                    midi_message: MidiNoteOn { note: (note % 128) as u8, velocity: 100, channel: 0 },
+                }
+            }));
+            for timed_midi_note_on in buffered_events.drain(..) {
+                bpm_detection.receive_midi_message(timed_midi_note_on);
+            }
+            shrink_excess(&mut buffered_events, NOTE_CAPACITY);
+
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters);
+            if burst % 10 == 5 {
+                bpm_detection.update_static_parameters(static_bpm_detection_parameters(burst));
+            }
+
+            if burst >= 1000 {
+                stats.push(bpm_detection.memory_stats(buffered_events.capacity()));
+            }
+        }
+
+        // capacities don't move during the second half of the session
+        let capacities = |stats: &MemoryStats| (stats.buffered_events, stats.histogram_data_points);
+        assert!(stats.windows(2).all(|window| capacities(&window[0]) == capacities(&window[1])), "{stats:?}");
+        for stats in stats {
+            assert!(stats.buffered_events <= 2 * NOTE_CAPACITY, "{stats:?}");
+            assert!(stats.notes <= NOTE_CAPACITY, "{stats:?}");
+            assert!(stats.histogram_data_points <= max_histogram_data_buffer_size(), "{stats:?}");
+        }
+    }
+
+    #[cfg(feature = "memory-stats")]
+    #[test]
+    fn test_low_memory_budget() {
+        use crate::{
+            midi_messages::MidiNoteOn, worker_event::WorkerEvent, BPMDetection, DynamicBPMDetectionParameters,
+            NormalDistributionConfig, TimedMidiNoteOn,
+        };
+        use chrono::Duration;
+
+        // the widest range and the finest resolution, before the limits apply
+        let static_bpm_detection_parameters = |n: u32| {
+            let mut static_bpm_detection_parameters = StaticBPMDetectionParameters {
+                bpm_center: if n.is_multiple_of(2) { 1.0 } else { 150.0 },
+                bpm_range: 100,
+                histogram_resolution: 2000,
+                normal_distribution: NormalDistributionConfig {
+                    imprecision: 1.0,
+                    ..NormalDistributionConfig::default()
+                },
+                ..StaticBPMDetectionParameters::default()
+            };
+            limit_static_parameters(&mut static_bpm_detection_parameters);
+            static_bpm_detection_parameters
+        };
+        let dynamic_bpm_detection_parameters =
+            DynamicBPMDetectionParameters { beats_lookback: 255, ..DynamicBPMDetectionParameters::default() };
+
+        let mut bpm_detection = BPMDetection::with_low_memory(static_bpm_detection_parameters(0), true);
+        let mut buffered_events = Vec::with_capacity(LOW_MEMORY_EVENT_CAPACITY);
+
+        let mut note = 0u32;
+        for burst in 0..20u32 {
+            // enough notes to fill the detection, all kept by the long lookback
+            buffered_events.extend((0..5000).map(|_| {
+                note += 1;
+                WorkerEvent::TimedMidiNoteOn(TimedMidiNoteOn {
+                    timestamp: Duration::milliseconds(i64::from(note) * 300),
+                    midi_message: MidiNoteOn { note: (note % 128) as u8, velocity: 100, channel: 0 },
+                })
+            }));
+            for worker_event in buffered_events.drain(..) {
+                if let WorkerEvent::TimedMidiNoteOn(timed_midi_note_on) = worker_event {
+                    bpm_detection.receive_midi_message(timed_midi_note_on);
+                }
+            }
+            shrink_excess(&mut buffered_events, LOW_MEMORY_EVENT_CAPACITY);
+            bpm_detection.update_static_parameters(static_bpm_detection_parameters(burst));
+            bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters);
+
+            let stats = bpm_detection.memory_stats(buffered_events.capacity());
+            assert_eq!(stats.notes, LOW_MEMORY_NOTE_CAPACITY, "{stats:?}");
+            assert!(stats.bytes() < LOW_MEMORY_BUDGET, "{} bytes, {stats:?}", stats.bytes());
+        }
+    }
+}
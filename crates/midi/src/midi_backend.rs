@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+use crate::{midi_input_port::ordinals, CoreError};
+
+/// System MIDI layer the service talks to. midir picks it when building: a build only has the native backend of its
+/// platform, or JACK instead with the `jack` feature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MidiBackend {
+    // whichever this build has
+    #[default]
+    Auto,
+    Alsa,
+    Jack,
+    CoreMidi,
+    WinMM,
+}
+
+impl Display for MidiBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "Auto",
+            Self::Alsa => "ALSA",
+            Self::Jack => "JACK",
+            Self::CoreMidi => "CoreMIDI",
+            Self::WinMM => "WinMM",
+        })
+    }
+}
+
+impl MidiBackend {
+    /// Native backend of the platform, none where midir doesn't have one
+    #[must_use]
+    pub const fn native() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            Some(Self::CoreMidi)
+        } else if cfg!(target_os = "linux") {
+            Some(Self::Alsa)
+        } else if cfg!(target_os = "windows") {
+            Some(Self::WinMM)
+        } else {
+            None
+        }
+    }
+
+    /// Backend of this build
+    #[must_use]
+    pub const fn compiled() -> Option<Self> {
+        if cfg!(all(feature = "jack", not(target_os = "windows"))) {
+            Some(Self::Jack)
+        } else {
+            Self::native()
+        }
+    }
+
+    /// The backend to use when `self` is configured, an error telling how to get it if this build doesn't have it
+    pub fn resolve(self) -> Result<Self, CoreError> {
+        select(self, Self::native(), Self::compiled() == Some(Self::Jack))
+    }
+}
+
+fn select(requested: MidiBackend, native: Option<MidiBackend>, jack: bool) -> Result<MidiBackend, CoreError> {
+    let compiled = if jack { Some(MidiBackend::Jack) } else { native };
+    let hint = match (requested, compiled) {
+        (_, None) => "there is no MIDI backend on this platform".to_string(),
+        (MidiBackend::Auto, Some(compiled)) => return Ok(compiled),
+        (requested, Some(compiled)) if requested == compiled => return Ok(compiled),
+        (MidiBackend::Jack, Some(compiled)) => {
+            format!("JACK support is not compiled in, rebuild with the `jack` feature. This build uses {compiled}")
+        }
+        (requested, Some(MidiBackend::Jack)) if Some(requested) == native => {
+            "this build uses JACK instead, rebuild without the `jack` feature".to_string()
+        }
+        (_, Some(compiled)) => format!("it does not exist on this platform, this build uses {compiled}"),
+    };
+    Err(CoreError::BackendNotCompiled { requested, hint })
+}
+
+/// Port names as shown to the user. Ports sharing a name are told apart with the backend and a number
+#[must_use]
+pub fn disambiguate(names: &[String], backend: MidiBackend) -> Vec<String> {
+    let ordinals = ordinals(names);
+    names
+        .iter()
+        .zip(ordinals)
+        .map(|(name, ordinal)| {
+            if names.iter().filter(|other| *other == name).count() == 1 {
+                return name.clone();
+            }
+            format!("{name} ({backend} {ordinal})")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(result: Result<MidiBackend, CoreError>) -> String {
+        match result {
+            Err(CoreError::BackendNotCompiled { hint, .. }) => hint,
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        use MidiBackend::{Alsa, Auto, CoreMidi, Jack, WinMM};
+        // native builds
+        for native in [Alsa, CoreMidi, WinMM] {
+            assert_eq!(select(Auto, Some(native), false).unwrap(), native);
+            assert_eq!(select(native, Some(native), false).unwrap(), native);
+        }
+        assert!(hint(select(Jack, Some(Alsa), false)).contains("rebuild with the `jack` feature"));
+        assert!(hint(select(CoreMidi, Some(Alsa), false)).contains("this build uses ALSA"));
+
+        // JACK builds
+        assert_eq!(select(Auto, Some(Alsa), true).unwrap(), Jack);
+        assert_eq!(select(Jack, Some(CoreMidi), true).unwrap(), Jack);
+        assert!(hint(select(Alsa, Some(Alsa), true)).contains("rebuild without the `jack` feature"));
+        assert!(hint(select(WinMM, Some(Alsa), true)).contains("this build uses JACK"));
+
+        // no backend at all
+        assert!(hint(select(Auto, None, false)).contains("no MIDI backend"));
+    }
+
+    #[test]
+    fn test_compiled() {
+        // passes with and without the `jack` feature
+        if cfg!(all(feature = "jack", not(target_os = "windows"))) {
+            assert_eq!(MidiBackend::compiled(), Some(MidiBackend::Jack));
+            assert_eq!(MidiBackend::Auto.resolve().unwrap(), MidiBackend::Jack);
+        } else {
+            assert_eq!(MidiBackend::compiled(), MidiBackend::native());
+            assert!(MidiBackend::Jack.resolve().is_err());
+        }
+    }
+
+    #[test]
+    fn test_disambiguate() {
+        let names = ["Midi Through", "Drums", "Midi Through", "Keys", "Midi Through"].map(String::from).to_vec();
+        assert_eq!(
+            disambiguate(&names, MidiBackend::Alsa),
+            ["Midi Through (ALSA 1)", "Drums", "Midi Through (ALSA 2)", "Keys", "Midi Through (ALSA 3)"]
+        );
+        assert_eq!(disambiguate(&["Drums".to_string()], MidiBackend::Jack), ["Drums"]);
+    }
+}
@@ -0,0 +1,374 @@
+use chrono::Duration;
+use thiserror::Error;
+
+use crate::midi_messages::{MidiNoteOn, TimedMidiNoteOn};
+
+// tempo until the first set-tempo meta event, 120 BPM
+const DEFAULT_MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+/// Why a standard MIDI file could not be read
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MidiFileError {
+    #[error("not a standard MIDI file, it doesn't start with an MThd chunk")]
+    NotAMidiFile,
+    #[error("the file ends in the middle of {0}")]
+    Truncated(&'static str),
+    #[error("format {0} files hold independent sequences, only formats 0 and 1 are supported")]
+    UnsupportedFormat(u16),
+    #[error("the division of the header is 0 ticks")]
+    InvalidDivision,
+    #[error("data byte {byte:#04x} without a running status at byte {position} of a track")]
+    MissingStatus { byte: u8, position: usize },
+}
+
+/// Note-ons of a standard MIDI file, timed with its tempo map from the start of the file, and the start of each of its
+/// measures up to the last note
+#[derive(Clone, Debug, Default)]
+pub struct MidiFile {
+    pub notes: Vec<TimedMidiNoteOn>,
+    pub measures: Vec<Duration>,
+}
+
+// tick and value of the events the notes are timed with
+struct Track {
+    notes: Vec<(u64, MidiNoteOn)>,
+    tempo_changes: Vec<(u64, u32)>,
+    // numerator and denominator
+    time_signatures: Vec<(u64, (u8, u8))>,
+}
+
+#[derive(Clone, Copy)]
+enum Division {
+    TicksPerQuarter(u16),
+    // SMPTE timing, ticks don't depend on the tempo
+    TicksPerSecond(f64),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &'static str) -> Result<&'a [u8], MidiFileError> {
+        let taken = self.bytes.get(self.position..self.position + len).ok_or(MidiFileError::Truncated(what))?;
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn byte(&mut self, what: &'static str) -> Result<u8, MidiFileError> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &'static str) -> Result<u16, MidiFileError> {
+        let bytes = self.take(2, what)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self, what: &'static str) -> Result<u32, MidiFileError> {
+        let bytes = self.take(4, what)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // see `write_variable_length_quantity`, longer quantities are read as far as they go
+    fn variable_length_quantity(&mut self, what: &'static str) -> Result<u64, MidiFileError> {
+        let mut value = 0u64;
+        loop {
+            let byte = self.byte(what)?;
+            value = (value << 7) | u64::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+}
+
+impl MidiFile {
+    /// Reads the note-ons of a format 0 or 1 file, the tracks of a format 1 file share the tempo map whichever track
+    /// holds it. A note-on of velocity 0 is a note-off and is left out. Measures follow the time signatures, 4/4 until
+    /// the first one
+    pub fn read(bytes: &[u8]) -> Result<Self, MidiFileError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4, "the header").map_err(|_| MidiFileError::NotAMidiFile)? != b"MThd" {
+            return Err(MidiFileError::NotAMidiFile);
+        }
+        let header_length = reader.u32("the header")? as usize;
+        let mut header = Reader { bytes: reader.take(header_length, "the header")?, position: 0 };
+        let format = header.u16("the header")?;
+        if format > 1 {
+            return Err(MidiFileError::UnsupportedFormat(format));
+        }
+        let track_count = header.u16("the header")?;
+        let division = match header.u16("the header")? {
+            0 => return Err(MidiFileError::InvalidDivision),
+            division if division & 0x8000 == 0 => Division::TicksPerQuarter(division),
+            division => {
+                // negative frames per second, 29 stands for 29.97 drop frame
+                let frames_per_second = match -i32::from((division >> 8) as u8 as i8) {
+                    29 => 29.97,
+                    frames_per_second => f64::from(frames_per_second),
+                };
+                let ticks_per_frame = division & 0xFF;
+                if ticks_per_frame == 0 || frames_per_second <= 0.0 {
+                    return Err(MidiFileError::InvalidDivision);
+                }
+                Division::TicksPerSecond(frames_per_second * f64::from(ticks_per_frame))
+            }
+        };
+
+        let mut tracks = Vec::with_capacity(usize::from(track_count));
+        while tracks.len() < usize::from(track_count) && !reader.is_empty() {
+            let chunk_type = reader.take(4, "a chunk header")?;
+            let length = reader.u32("a chunk header")? as usize;
+            let chunk = reader.take(length, "a track")?;
+            // chunks of unknown types are to be skipped
+            if chunk_type == b"MTrk" {
+                tracks.push(read_track(chunk)?);
+            }
+        }
+
+        let mut notes = Vec::new();
+        let mut tempo_changes = Vec::new();
+        let mut time_signatures = Vec::new();
+        for track in tracks {
+            notes.extend(track.notes);
+            tempo_changes.extend(track.tempo_changes);
+            time_signatures.extend(track.time_signatures);
+        }
+        // stable, the notes of a tick stay in the order of their tracks
+        notes.sort_by_key(|(tick, _)| *tick);
+        tempo_changes.sort_by_key(|(tick, _)| *tick);
+        time_signatures.sort_by_key(|(tick, _)| *tick);
+
+        let tempo_map = TempoMap::new(division, &tempo_changes);
+        let last_tick = notes.last().map_or(0, |(tick, _)| *tick);
+        let measures = match division {
+            Division::TicksPerQuarter(ticks_per_quarter) => {
+                measure_ticks(u64::from(ticks_per_quarter), &time_signatures, last_tick)
+                    .map(|tick| tempo_map.duration(tick))
+                    .collect()
+            }
+            // no tempo to lay measures on
+            Division::TicksPerSecond(_) => Vec::new(),
+        };
+        let notes = notes
+            .into_iter()
+            .map(|(tick, midi_message)| TimedMidiNoteOn { timestamp: tempo_map.duration(tick), midi_message })
+            .collect();
+        Ok(Self { notes, measures })
+    }
+}
+
+fn read_track(bytes: &[u8]) -> Result<Track, MidiFileError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let mut track = Track { notes: Vec::new(), tempo_changes: Vec::new(), time_signatures: Vec::new() };
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while !reader.is_empty() {
+        tick += reader.variable_length_quantity("a delta time")?;
+        let position = reader.position;
+        let status = match reader.byte("an event")? {
+            status if status & 0x80 != 0 => status,
+            byte => {
+                // the data byte is read again as the first one of the event
+                reader.position = position;
+                running_status.ok_or(MidiFileError::MissingStatus { byte, position })?
+            }
+        };
+        match status {
+            0xFF => {
+                running_status = None;
+                let meta_type = reader.byte("a meta event")?;
+                let length = reader.variable_length_quantity("a meta event")? as usize;
+                let data = reader.take(length, "a meta event")?;
+                match (meta_type, data) {
+                    (0x51, [a, b, c]) => track.tempo_changes.push((tick, u32::from_be_bytes([0, *a, *b, *c]))),
+                    (0x58, [numerator, denominator, ..]) => {
+                        track.time_signatures.push((tick, (*numerator, *denominator)));
+                    }
+                    // end of track
+                    (0x2F, _) => break,
+                    _ => (),
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.variable_length_quantity("a system exclusive event")? as usize;
+                reader.take(length, "a system exclusive event")?;
+            }
+            _ => {
+                running_status = Some(status);
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    0x90 => {
+                        let data = reader.take(2, "a note-on")?;
+                        if data[1] > 0 {
+                            track.notes.push((tick, MidiNoteOn { channel, note: data[0], velocity: data[1] }));
+                        }
+                    }
+                    // program change and channel pressure
+                    0xC0 | 0xD0 => {
+                        reader.take(1, "a channel message")?;
+                    }
+                    _ => {
+                        reader.take(2, "a channel message")?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(track)
+}
+
+// ticks where each measure starts, the last one holding `last_tick`. A time signature starts a measure
+fn measure_ticks(
+    ticks_per_quarter: u64,
+    time_signatures: &[(u64, (u8, u8))],
+    last_tick: u64,
+) -> impl Iterator<Item = u64> + '_ {
+    let measure_length = move |(numerator, denominator): (u8, u8)| {
+        let denominator = 1u64 << denominator.min(6);
+        (u64::from(numerator.max(1)) * ticks_per_quarter * 4 / denominator).max(1)
+    };
+    let mut time_signatures = time_signatures.iter().peekable();
+    let mut time_signature = (4, 2);
+    let mut tick = 0;
+    std::iter::from_fn(move || {
+        if tick > last_tick {
+            return None;
+        }
+        let measure = tick;
+        while let Some((_, next_time_signature)) = time_signatures.next_if(|(at, _)| *at <= measure) {
+            time_signature = *next_time_signature;
+        }
+        tick += measure_length(time_signature);
+        if let Some((at, _)) = time_signatures.peek() {
+            tick = tick.min((*at).max(measure + 1));
+        }
+        Some(measure)
+    })
+}
+
+// time of the ticks, from the tempo changes in effect
+struct TempoMap {
+    division: Division,
+    // tick, microseconds from the start and microseconds per quarter from that tick on
+    segments: Vec<(u64, f64, u32)>,
+}
+
+impl TempoMap {
+    fn new(division: Division, tempo_changes: &[(u64, u32)]) -> Self {
+        let Division::TicksPerQuarter(ticks_per_quarter) = division else {
+            return Self { division, segments: Vec::new() };
+        };
+        let mut segments = vec![(0, 0.0, DEFAULT_MICROSECONDS_PER_QUARTER)];
+        for &(tick, microseconds_per_quarter) in tempo_changes {
+            let &(previous_tick, previous_microseconds, previous_microseconds_per_quarter) =
+                segments.last().expect("there is always the initial segment");
+            let microseconds = previous_microseconds
+                + (tick - previous_tick) as f64 * f64::from(previous_microseconds_per_quarter)
+                    / f64::from(ticks_per_quarter);
+            if tick == previous_tick {
+                segments.pop();
+            }
+            segments.push((tick, microseconds, microseconds_per_quarter));
+        }
+        Self { division, segments }
+    }
+
+    fn duration(&self, tick: u64) -> Duration {
+        let microseconds = match self.division {
+            Division::TicksPerSecond(ticks_per_second) => tick as f64 * 1_000_000.0 / ticks_per_second,
+            Division::TicksPerQuarter(ticks_per_quarter) => {
+                let index = self.segments.partition_point(|(segment_tick, ..)| *segment_tick <= tick) - 1;
+                let (segment_tick, microseconds, microseconds_per_quarter) = self.segments[index];
+                microseconds
+                    + (tick - segment_tick) as f64 * f64::from(microseconds_per_quarter) / f64::from(ticks_per_quarter)
+            }
+        };
+        Duration::nanoseconds((microseconds * 1000.0).round() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smf(format: u16, division: u16, tracks: &[Vec<u8>]) -> Vec<u8> {
+        let mut smf = b"MThd".to_vec();
+        smf.extend(6u32.to_be_bytes());
+        smf.extend(format.to_be_bytes());
+        smf.extend((tracks.len() as u16).to_be_bytes());
+        smf.extend(division.to_be_bytes());
+        for track in tracks {
+            smf.extend(b"MTrk");
+            smf.extend((track.len() as u32).to_be_bytes());
+            smf.extend(track);
+        }
+        smf
+    }
+
+    fn timestamps(midi_file: &MidiFile) -> Vec<i64> {
+        midi_file.notes.iter().map(|note| note.timestamp.num_milliseconds()).collect()
+    }
+
+    #[test]
+    fn test_tempo_map() {
+        // 120 BPM, then 60 BPM from the second quarter on. Running status, and a note-on of velocity 0 as note-off.
+        // the tempo meta event cancels the running status
+        let track = vec![
+            0x00, 0x90, 36, 100, //
+            0x60, 36, 0, //
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, //
+            0x00, 0x90, 38, 90, //
+            0x60, 0x80, 38, 0, //
+            0x00, 0x90, 36, 80, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let midi_file = MidiFile::read(&smf(0, 96, &[track])).unwrap();
+        assert_eq!(timestamps(&midi_file), vec![0, 500, 1500]);
+        assert_eq!(midi_file.notes[1].midi_message, MidiNoteOn { channel: 0, note: 38, velocity: 90 });
+        assert_eq!(midi_file.measures, vec![Duration::zero()]);
+    }
+
+    #[test]
+    fn test_format_1() {
+        // the tempo map and the time signature are in the first track, 3/4 at 100 BPM
+        let tempo_track = vec![
+            0x00, 0xFF, 0x58, 0x04, 3, 2, 24, 8, //
+            0x00, 0xFF, 0x51, 0x03, 0x09, 0x27, 0xC0, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut notes_track = vec![];
+        for _ in 0..7 {
+            notes_track.extend([0x00, 0x99, 42, 100, 0x83, 0x60, 0x89, 42, 0]);
+        }
+        notes_track.extend([0x00, 0xFF, 0x2F, 0x00]);
+        let midi_file = MidiFile::read(&smf(1, 480, &[tempo_track, notes_track])).unwrap();
+        assert_eq!(timestamps(&midi_file), (0..7).map(|beat| beat * 600).collect::<Vec<_>>());
+        assert!(midi_file.notes.iter().all(|note| note.midi_message.channel == 9));
+        // the seventh beat starts the third measure
+        assert_eq!(
+            midi_file.measures,
+            vec![Duration::zero(), Duration::milliseconds(1800), Duration::milliseconds(3600)]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(MidiFile::read(b"RIFF").unwrap_err(), MidiFileError::NotAMidiFile);
+        assert_eq!(MidiFile::read(&smf(2, 96, &[])).unwrap_err(), MidiFileError::UnsupportedFormat(2));
+        assert_eq!(MidiFile::read(&smf(0, 0, &[])).unwrap_err(), MidiFileError::InvalidDivision);
+        assert_eq!(
+            MidiFile::read(&smf(0, 96, &[vec![0x00, 36, 100]])).unwrap_err(),
+            MidiFileError::MissingStatus { byte: 36, position: 1 }
+        );
+        let mut truncated = smf(0, 96, &[vec![0x00, 0x90, 36, 100]]);
+        truncated.pop();
+        assert_eq!(MidiFile::read(&truncated).unwrap_err(), MidiFileError::Truncated("a track"));
+    }
+}
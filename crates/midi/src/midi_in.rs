@@ -1,27 +1,40 @@
 use chrono::Duration;
 use std::{
     sync::{
-        atomic::AtomicU64,
-        mpsc::{Receiver, SendError, Sender, SyncSender},
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, Sender, SyncSender},
         Arc,
     },
     thread,
 };
 
+use instant::Instant;
 use itertools::Itertools;
-use log::error;
+use log::{error, info};
+use sync::Mutex;
 
 #[cfg(unix)]
 use midir::os::unix::VirtualInput;
 use midir::{MidiInput, MidiInputConnection};
 
 use build::PROJECT_NAME;
-use errors::{error_backtrace, MakeReportExt, Report, Result};
+use errors::{error_backtrace, TypedResult};
 
 use crate::{
-    bpm_detection_receiver::BPMDetectionReceiver, midi_input_port::MidiInputPort, sysex::SysExCommand, worker,
-    worker_event::WorkerEvent, DynamicBPMDetectionParameters, MidiServiceConfig, StaticBPMDetectionParameters,
-    StaticMidiMessage, TimedTypedMidiMessage,
+    bpm_detection_receiver::BPMDetectionReceiver,
+    daw_link_protocol::DawMessage,
+    error::CoreError,
+    feel_ambiguity::Feel,
+    latency::ClockAnchor,
+    midi_backend::{self, MidiBackend},
+    midi_input_port::{ordinals, MidiInputPort, PortName},
+    patterns::{DemoPatternConfig, PatternGenerator, PatternKind},
+    sysex::SysExCommand,
+    tap_tempo::TapTrigger,
+    worker,
+    worker_event::WorkerEvent,
+    DynamicBPMDetectionParameters, MidiServiceConfig, RateLimitedNotes, RateLimiter, RateLimiterConfig,
+    StaticBPMDetectionParameters, StaticMidiMessage, TimedMidiNoteOn, TimedTypedMidiMessage,
 };
 
 #[cfg(unix)]
@@ -30,12 +43,29 @@ use crate::midi_output::VirtualMidiOutput;
 #[cfg(not(unix))]
 use crate::fake_midi_output::VirtualMidiOutput;
 
+#[cfg(target_os = "macos")]
+use crate::hotplug::{self, HotplugHandle};
+
 pub struct MidiIn<B: BPMDetectionReceiver> {
     midi_input: MidiInput,
+    backend: MidiBackend,
     start_timestamp: Arc<AtomicU64>,
     worker_sender: Sender<WorkerEvent>,
+    rate_limit: RateLimiterConfig,
+    rate_limited_notes: RateLimitedNotes,
+    tap_trigger: Option<TapTrigger>,
+    // the clock ticks are only sent to the worker when it tracks them
+    clock_input: bool,
+    // id of the demo pattern being played, 0 when none is. Real MIDI notes stop it
+    running_demo: Arc<AtomicU64>,
+    demo_count: AtomicU64,
+    // shared with the worker, which measures how late the tempo is sent
+    clock_anchor: Arc<Mutex<Option<ClockAnchor>>>,
     #[cfg(any(target_os = "macos", target_os = "ios"))]
     midi_config: MidiServiceConfig,
+    // device changes are notified until the service stops
+    #[cfg(target_os = "macos")]
+    _hotplug_handle: HotplugHandle,
     bpm_detection_receiver: B,
 }
 
@@ -48,51 +78,85 @@ where
         midi_service_config: MidiServiceConfig,
         bpm_detection_parameters: StaticBPMDetectionParameters,
         dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
-        #[cfg(target_os = "macos")] send_device_changes_notification: impl Fn() + Send + 'static,
+        #[cfg(target_os = "macos")] send_device_changes_notification: impl Fn() + Send + Sync + 'static,
         bpm_detection_receiver: B,
-    ) -> Result<Self> {
+    ) -> TypedResult<Self, CoreError> {
         #[cfg(target_os = "macos")]
-        coremidi_hotplug_notification::receive_device_updates(send_device_changes_notification).map_err(Report::msg)?;
+        let hotplug_handle =
+            hotplug::receive_device_updates(send_device_changes_notification).map_err(CoreError::BackendUnavailable)?;
+        let backend = midi_service_config.backend.resolve()?;
+        info!("using the {backend} MIDI backend");
         let (worker_sender, worker_receiver) = std::sync::mpsc::channel();
+        let clock_anchor = Arc::new(Mutex::new(None));
 
+        let virtual_midi_output = VirtualMidiOutput::new(midi_service_config.device_name.as_str(), backend)
+            .map_err(|err| CoreError::BackendUnavailable(format!("{backend}: {err}")))?;
         worker::spawn(
             &midi_service_config,
             bpm_detection_parameters,
             dynamic_bpm_detection_parameters,
             worker_receiver,
-            VirtualMidiOutput::new(midi_service_config.device_name.as_str())?,
+            clock_anchor.clone(),
+            virtual_midi_output,
             bpm_detection_receiver.clone(),
-        )?;
+        )
+        .map_err(|err| CoreError::BackendUnavailable(err.to_string()))?;
 
         Ok(Self {
+            rate_limit: midi_service_config.rate_limit.clone(),
+            rate_limited_notes: midi_service_config.rate_limited_notes.clone(),
+            tap_trigger: midi_service_config.tap_trigger,
+            clock_input: midi_service_config.clock_input.enabled,
             #[cfg(target_os = "macos")]
             midi_config: midi_service_config,
-            midi_input: MidiInput::new(PROJECT_NAME)?,
+            #[cfg(target_os = "macos")]
+            _hotplug_handle: hotplug_handle,
+            midi_input: MidiInput::new(PROJECT_NAME)
+                .map_err(|err| CoreError::BackendUnavailable(format!("{backend}: {err}")))?,
+            backend,
             start_timestamp: Arc::new(AtomicU64::from(0)),
+            running_demo: Arc::default(),
+            demo_count: AtomicU64::default(),
+            clock_anchor,
             worker_sender,
             bpm_detection_receiver,
         })
     }
 
-    pub fn get_ports(&self) -> Result<Vec<MidiInputPort>> {
+    /// Backend the ports are listed and opened with
+    #[must_use]
+    pub fn backend(&self) -> MidiBackend {
+        self.backend
+    }
+
+    pub fn get_ports(&self) -> TypedResult<Vec<MidiInputPort>, CoreError> {
         let mut devices = [
             MidiInputPort::None,
             #[cfg(any(target_os = "macos", target_os = "ios"))]
             MidiInputPort::Virtual(self.midi_config.device_name.clone()),
         ]
         .into_iter()
-        .chain(self.midi_input.ports().into_iter().enumerate().filter_map(|(n, port)| {
-            let port_name = match self.midi_input.port_name(&port) {
-                Ok(port_name) => port_name,
+        .collect_vec();
+        let (ports, port_names): (Vec<_>, Vec<_>) = self
+            .midi_input
+            .ports()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(n, port)| match self.midi_input.port_name(&port) {
+                Ok(port_name) => Some((port, port_name)),
                 Err(err) => {
                     error_backtrace!("Could not fetch name, skipping device {n} : {err:?}");
-                    return None;
+                    None
                 }
-            };
-            Some(MidiInputPort::Device(port, port_name))
-        }))
-        .collect_vec();
+            })
+            .unzip();
+        let ordinals = ordinals(&port_names);
+        let displays = midi_backend::disambiguate(&port_names, self.backend);
+        devices.extend(ports.into_iter().zip(port_names).zip(ordinals).zip(displays).map(
+            |(((port, name), ordinal), display)| MidiInputPort::Device(port, PortName { name, ordinal, display }),
+        ));
 
+        // the ports sharing a name keep the order of the backend
         devices.sort_unstable();
         Ok(devices)
     }
@@ -101,12 +165,18 @@ where
         &self,
         midi_input_port: &MidiInputPort,
         callback: T,
-    ) -> Result<Option<MidiInputConnection<()>>> {
+    ) -> TypedResult<Option<MidiInputConnection<()>>, CoreError> {
         let bpm_detection_receiver = self.bpm_detection_receiver.clone();
 
         let listener = move || {
             let start_timestamp = self.start_timestamp.clone();
             let worker_sender = self.worker_sender.clone();
+            let running_demo = self.running_demo.clone();
+            let clock_anchor = self.clock_anchor.clone();
+            let tap_trigger = self.tap_trigger;
+            let clock_input = self.clock_input;
+            // one bucket per connection
+            let rate_limiter = RateLimiter::new(&self.rate_limit, &self.rate_limited_notes);
             move |timestamp: u64, data: &[u8], (): &mut ()| {
                 let start_timestamp = match start_timestamp.load(std::sync::atomic::Ordering::Relaxed) {
                     0 => {
@@ -124,14 +194,61 @@ where
 
                 let midi_message = midi_message.to_owned();
 
-                if let Ok(SysExCommand::Tempo(bpm)) = SysExCommand::try_from(&midi_message) {
-                    bpm_detection_receiver.receive_daw_bpm(bpm);
+                // the trigger note only taps the tempo, it doesn't take part in the detection
+                if tap_trigger.is_some_and(|tap_trigger| tap_trigger.matches(&midi_message)) {
+                    // a note-on without velocity is a note-off
+                    if matches!(midi_message, StaticMidiMessage::NoteOn(_, _, velocity) if u8::from(velocity) > 0) {
+                        if let Err(e) = worker_sender.send(WorkerEvent::Tap(Instant::now())) {
+                            error!("Could not send tap to worker: {e:?}");
+                        }
+                    }
+                    return;
+                }
+
+                let transport = match SysExCommand::try_from(&midi_message) {
+                    Ok(SysExCommand::Tempo(bpm)) => {
+                        bpm_detection_receiver.receive_daw_bpm(bpm);
+                        if let Err(e) = worker_sender.send(WorkerEvent::DawBPM(bpm)) {
+                            error!("Could not send DAW tempo to worker: {e:?}");
+                        }
+                        None
+                    }
+                    Ok(SysExCommand::Play) => Some(DawMessage::Play),
+                    Ok(SysExCommand::Stop) => Some(DawMessage::Stop),
+                    Ok(SysExCommand::Continue) => Some(DawMessage::Continue),
+                    Err(()) => None,
+                };
+                if let Some(transport) = transport {
+                    if let Err(e) = worker_sender.send(WorkerEvent::Transport(transport)) {
+                        error!("Could not send transport to worker: {e:?}");
+                    }
                 }
 
                 let midi_message = TimedTypedMidiMessage { timestamp: timestamp - start_timestamp, midi_message };
 
-                if let Ok(midi_note_on) = WorkerEvent::try_from(midi_message.clone()) {
-                    if let Err(e) = worker_sender.send(midi_note_on) {
+                let worker_event = WorkerEvent::try_from(midi_message.clone())
+                    .ok()
+                    .filter(|worker_event| clock_input || !matches!(worker_event, WorkerEvent::TimingClock(_)));
+                if let Some(worker_event) = worker_event {
+                    if matches!(worker_event, WorkerEvent::TimedMidiNoteOn(_))
+                        && !rate_limiter.allow(midi_message.timestamp)
+                    {
+                        return;
+                    }
+                    // the demo notes have their own timeline, they must not be mixed with the real ones
+                    if matches!(worker_event, WorkerEvent::TimedMidiNoteOn(_)) {
+                        let demo_stopped = running_demo.swap(0, Ordering::Relaxed) != 0;
+                        if demo_stopped {
+                            if let Err(e) = worker_sender.send(WorkerEvent::ClearNotes) {
+                                error!("Could not send midi message to worker: {e:?}");
+                            }
+                        }
+                        let mut clock_anchor = clock_anchor.lock();
+                        if demo_stopped || clock_anchor.is_none() {
+                            *clock_anchor = Some(ClockAnchor::new(midi_message.timestamp, Instant::now()));
+                        }
+                    }
+                    if let Err(e) = worker_sender.send(worker_event) {
                         error!("Could not send midi message to worker: {e:?}");
                     }
                 }
@@ -144,40 +261,112 @@ where
             MidiInputPort::None => Ok(None),
             #[cfg(any(target_os = "macos", target_os = "ios"))]
             MidiInputPort::Virtual(name) => Ok(Some(
-                MidiInput::new(name.as_str())?
+                MidiInput::new(name.as_str())
+                    .map_err(|err| CoreError::BackendUnavailable(err.to_string()))?
                     .create_virtual(name.as_str(), listener(), ())
-                    .report_msg("Unable to create virtual input")?,
+                    .map_err(|err| CoreError::from_connect_error(name, &err))?,
             )),
             #[cfg(not(any(target_os = "macos", target_os = "ios")))]
             MidiInputPort::Virtual(_) => Ok(None),
-            MidiInputPort::Device(midi_input_port, name) => Ok(Some(
-                MidiInput::new(name.as_str())?
+            MidiInputPort::Device(midi_input_port, PortName { display: name, .. }) => Ok(Some(
+                MidiInput::new(name.as_str())
+                    .map_err(|err| CoreError::BackendUnavailable(err.to_string()))?
                     .connect(midi_input_port, name.as_str(), listener(), ())
-                    .report_msg("Unable to listen to input port")?,
+                    .map_err(|err| CoreError::from_connect_error(name, &err))?,
             )),
         }
     }
 
-    pub fn play(&self) -> Result<(), SendError<WorkerEvent>> {
-        self.worker_sender.send(WorkerEvent::Play)
+    fn send_to_worker(&self, worker_event: WorkerEvent) -> TypedResult<(), CoreError> {
+        self.worker_sender.send(worker_event).map_err(|_| CoreError::WorkerStopped)?;
+        Ok(())
+    }
+
+    pub fn play(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::Play)
+    }
+
+    pub fn stop(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::Stop)
+    }
+
+    /// Feeds the worker with a synthetic pattern, until `stop_demo` is called, another demo starts or a note is
+    /// received from the MIDI input
+    pub fn start_demo(
+        &self,
+        pattern_kind: PatternKind,
+        demo_pattern_config: &DemoPatternConfig,
+    ) -> TypedResult<(), CoreError> {
+        let demo_id = self.demo_count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.running_demo.store(demo_id, Ordering::Relaxed);
+        self.send_to_worker(WorkerEvent::ClearNotes)?;
+        // the first note of the pattern is sent right away, at timestamp zero
+        *self.clock_anchor.lock() = Some(ClockAnchor::new(Duration::zero(), Instant::now()));
+
+        let running_demo = self.running_demo.clone();
+        let worker_sender = self.worker_sender.clone();
+        let pattern_generator = PatternGenerator::from_config(pattern_kind, demo_pattern_config);
+        thread::Builder::new()
+            .name(format!("{pattern_kind} demo"))
+            .spawn(move || {
+                for (delay, timed_midi_note_on) in pattern_generator {
+                    thread::sleep(delay.to_std().unwrap_or_default());
+                    if running_demo.load(Ordering::Relaxed) != demo_id
+                        || worker_sender.send(WorkerEvent::TimedMidiNoteOn(timed_midi_note_on)).is_err()
+                    {
+                        break;
+                    }
+                }
+            })
+            .map_err(CoreError::ServiceStart)?;
+        Ok(())
+    }
+
+    /// Notes timestamped on the timeline of the MIDI input, delivered at once, such as the ones of a file
+    pub fn send_notes(&self, notes: Vec<TimedMidiNoteOn>) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::TimedBatch(notes))
+    }
+
+    pub fn stop_demo(&self) {
+        self.running_demo.store(0, Ordering::Relaxed);
+    }
+
+    /// Beats and bars are counted again from the next note
+    pub fn reset_beat_counter(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::ResetBeatCounter)
+    }
+
+    /// Taps the tempo, as the trigger note does
+    pub fn tap(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::Tap(Instant::now()))
+    }
+
+    /// Favours a candidate of the last ambiguity between a straight and a triplet feel, see `FeelAmbiguity`
+    pub fn choose_feel(&self, feel: Feel) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::ChooseFeel(feel))
+    }
+
+    /// The notes received so far are kept as a reference, see `FrozenReference`
+    pub fn freeze_notes(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::FreezeNotes)
     }
 
-    pub fn stop(&self) -> Result<(), SendError<WorkerEvent>> {
-        self.worker_sender.send(WorkerEvent::Stop)
+    pub fn clear_frozen_notes(&self) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::ClearFrozenNotes)
     }
 
     pub fn change_bpm_detection_parameters_live(
         &self,
         dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
-    ) -> Result<(), SendError<WorkerEvent>> {
-        self.worker_sender.send(WorkerEvent::DynamicBPMDetectionParameters(dynamic_bpm_detection_parameters))
+    ) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::DynamicBPMDetectionParameters(dynamic_bpm_detection_parameters))
     }
 
     pub fn change_bpm_detection_parameters(
         &self,
         bpm_detection_parameters: StaticBPMDetectionParameters,
-    ) -> Result<(), SendError<WorkerEvent>> {
-        self.worker_sender.send(WorkerEvent::StaticBPMDetectionParameters(bpm_detection_parameters))
+    ) -> TypedResult<(), CoreError> {
+        self.send_to_worker(WorkerEvent::StaticBPMDetectionParameters(bpm_detection_parameters))
     }
 }
 
@@ -194,46 +383,50 @@ where
         midi_service_config: MidiServiceConfig,
         bpm_detection_parameters: StaticBPMDetectionParameters,
         dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
-        #[cfg(target_os = "macos")] send_devices_change_notification: impl Fn() + Send + 'static,
+        #[cfg(target_os = "macos")] send_devices_change_notification: impl Fn() + Send + Sync + 'static,
         bpm_detection_receiver: B,
-    ) -> Result<
+    ) -> TypedResult<
         Receiver<
-            Result<
+            TypedResult<
                 SyncSender<Box<dyn FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) + Send + Sync + 'static>>,
-                Report,
+                CoreError,
             >,
         >,
+        CoreError,
     > {
         let (result_sender, result_receiver) = std::sync::mpsc::sync_channel(0);
 
-        thread::Builder::new().name("MIDI Service".to_string()).spawn(move || {
-            #[allow(forbidden_lint_groups)]
-            #[allow(clippy::no_effect_underscore_binding)]
-            let mut midi_input_connection = None; // just a value holder. Dropping it means we stop listening
-            let midi_in = match MidiIn::new(
-                midi_service_config,
-                bpm_detection_parameters,
-                dynamic_bpm_detection_parameters,
-                #[cfg(target_os = "macos")]
-                send_devices_change_notification,
-                bpm_detection_receiver,
-            ) {
-                Ok(result) => result,
-                Err(err) => {
-                    result_sender.send(Err(err)).unwrap();
-                    return;
+        thread::Builder::new()
+            .name("MIDI Service".to_string())
+            .spawn(move || {
+                #[allow(forbidden_lint_groups)]
+                #[allow(clippy::no_effect_underscore_binding)]
+                let mut midi_input_connection = None; // just a value holder. Dropping it means we stop listening
+                let midi_in = match MidiIn::new(
+                    midi_service_config,
+                    bpm_detection_parameters,
+                    dynamic_bpm_detection_parameters,
+                    #[cfg(target_os = "macos")]
+                    send_devices_change_notification,
+                    bpm_detection_receiver,
+                ) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        result_sender.send(Err(err)).unwrap();
+                        return;
+                    }
+                };
+                let (commands_sender, commands_receiver) = std::sync::mpsc::sync_channel::<
+                    Box<dyn FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) + Send + Sync + 'static>,
+                >(0);
+                if let Err(e) = result_sender.send(Ok(commands_sender)) {
+                    error!("error while reporting on thread start {e:?}");
                 }
-            };
-            let (commands_sender, commands_receiver) = std::sync::mpsc::sync_channel::<
-                Box<dyn FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) + Send + Sync + 'static>,
-            >(0);
-            if let Err(e) = result_sender.send(Ok(commands_sender)) {
-                error!("error while reporting on thread start {e:?}");
-            }
-            while let Ok(command) = commands_receiver.recv() {
-                command(&midi_in, &mut midi_input_connection);
-            }
-        })?;
+                while let Ok(command) = commands_receiver.recv() {
+                    command(&midi_in, &mut midi_input_connection);
+                }
+            })
+            .map_err(CoreError::from)?;
         Ok(result_receiver)
     }
 
@@ -241,9 +434,9 @@ where
         midi_service_config: MidiServiceConfig,
         bpm_detection_parameters: StaticBPMDetectionParameters,
         dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
-        #[cfg(target_os = "macos")] send_devices_change_notification: impl Fn() + Send + 'static,
+        #[cfg(target_os = "macos")] send_devices_change_notification: impl Fn() + Send + Sync + 'static,
         bpm_detection_receiver: B,
-    ) -> Result<Self> {
+    ) -> TypedResult<Self, CoreError> {
         Ok(Self {
             commands_sender: Self::start_service(
                 midi_service_config,
@@ -253,22 +446,65 @@ where
                 send_devices_change_notification,
                 bpm_detection_receiver,
             )?
-            .recv()??,
+            .recv()
+            .map_err(|_| CoreError::ServiceClosed)??,
         })
     }
 
-    pub fn execute<R, F>(&self, command: F) -> Result<R>
+    pub fn execute<R, F>(&self, command: F) -> TypedResult<R, CoreError>
     where
-        F: FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) -> Result<R> + Send + Sync + 'static,
+        F: FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) -> TypedResult<R, CoreError>
+            + Send
+            + Sync
+            + 'static,
         R: Send + Sync + 'static,
     {
         let (result_sender, result_receiver) = std::sync::mpsc::sync_channel(0);
 
-        self.commands_sender.send(Box::new(move |midi_in, midi_input_connection| {
-            if let Err(e) = result_sender.send(command(midi_in, midi_input_connection)) {
-                error_backtrace!("could not send back result : {e:?}");
-            };
-        }))?;
-        result_receiver.recv()?
+        self.commands_sender
+            .send(Box::new(move |midi_in, midi_input_connection| {
+                if let Err(e) = result_sender.send(command(midi_in, midi_input_connection)) {
+                    error_backtrace!("could not send back result : {e:?}");
+                };
+            }))
+            .map_err(|_| CoreError::ServiceClosed)?;
+        result_receiver.recv().map_err(|_| CoreError::ServiceClosed)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BpmEstimate;
+    use midir::{ConnectError, ConnectErrorKind};
+
+    #[derive(Clone)]
+    struct NoReceiver;
+
+    impl BPMDetectionReceiver for NoReceiver {
+        fn receive_bpm_histogram_data(&mut self, _: &[f32], _: BpmEstimate) {}
+
+        fn receive_daw_bpm(&self, _: f32) {}
+    }
+
+    #[test]
+    fn test_nonexistent_port() {
+        let error = CoreError::from_connect_error("gone", &ConnectError::new(ConnectErrorKind::InvalidPort, ()));
+        assert!(matches!(error, CoreError::PortNotFound(port) if port == "gone"));
+
+        let error = CoreError::from_connect_error("busy", &ConnectError::new(ConnectErrorKind::Other("busy"), ()));
+        assert!(matches!(error, CoreError::ConnectionFailed { .. }));
+    }
+
+    #[test]
+    fn test_closed_service() {
+        let (commands_sender, commands_receiver) = std::sync::mpsc::sync_channel(0);
+        drop(commands_receiver);
+        let midi_service = MidiService::<NoReceiver> { commands_sender };
+
+        let Err(error) = midi_service.execute(|midi_in, _| midi_in.get_ports()) else {
+            panic!("a closed service can't execute commands");
+        };
+        assert!(matches!(error.inner(), CoreError::ServiceClosed));
     }
 }
@@ -1,23 +1,107 @@
-#![allow(clippy::non_canonical_partial_ord_impl)]
-
-use derivative::Derivative;
-use std::fmt::Display;
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+};
 
 const NO_SELECTION: &str = "<none selected>";
 
-#[derive(Clone, PartialEq, Derivative)]
-#[allow(clippy::non_canonical_partial_ord_impl)]
-#[derivative(Eq, Debug, PartialOrd)]
-#[derivative(Ord = "feature_allow_slow_enum", PartialOrd = "feature_allow_slow_enum")]
+/// Name of a device port. Ports sharing a name, such as two identical controllers, are told apart by their ordinal
+#[derive(Clone, Debug)]
+pub struct PortName {
+    // as the backend reports it
+    pub name: String,
+    // from 1, among the ports sharing `name` in the order the backend lists them
+    pub ordinal: usize,
+    // shown to the user, see `midi_backend::disambiguate`
+    pub display: String,
+}
+
+impl PartialEq for PortName {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PortName {}
+
+impl PartialOrd for PortName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// the ports sharing a name stay in the order the backend lists them
+impl Ord for PortName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name).then(self.ordinal.cmp(&other.ordinal))
+    }
+}
+
+/// Ordinal of each port among the ones sharing its name, from 1 in the order of `names`
+#[must_use]
+pub fn ordinals(names: &[String]) -> Vec<usize> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| names[..=index].iter().filter(|other| *other == name).count())
+        .collect()
+}
+
+/// Position in `candidates` of the port listed again: the same backend port first, as long as it has the same name,
+/// then the port with the same name and ordinal, then the first port with the same name
+fn find_port<'a, H: PartialEq + 'a>(
+    port: &H,
+    name: &PortName,
+    mut candidates: impl Iterator<Item = (&'a H, &'a PortName)> + Clone,
+) -> Option<usize> {
+    candidates
+        .clone()
+        .position(|(candidate, candidate_name)| candidate == port && candidate_name.name == name.name)
+        .or_else(|| candidates.clone().position(|(_, candidate_name)| candidate_name == name))
+        .or_else(move || candidates.position(|(_, candidate_name)| candidate_name.name == name.name))
+}
+
+/// Ports are equal and sorted by name, the backend port is left out as it changes when a device is plugged again
+#[derive(Clone)]
 pub enum MidiInputPort {
     None,
     Virtual(String),
-    Device(
-        #[derivative(Ord = "ignore", Debug = "ignore")]
-        #[derivative(PartialOrd = "ignore", Debug = "ignore")]
-        midir::MidiInputPort,
-        String,
-    ),
+    Device(midir::MidiInputPort, PortName),
+}
+
+impl PartialEq for MidiInputPort {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MidiInputPort {}
+
+impl PartialOrd for MidiInputPort {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MidiInputPort {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MidiInputPort::None, MidiInputPort::None) => Ordering::Equal,
+            (MidiInputPort::Virtual(name), MidiInputPort::Virtual(other_name)) => name.cmp(other_name),
+            (MidiInputPort::Device(_, name), MidiInputPort::Device(_, other_name)) => name.cmp(other_name),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Debug for MidiInputPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiInputPort::None => f.write_str("None"),
+            MidiInputPort::Virtual(name) => f.debug_tuple("Virtual").field(name).finish(),
+            MidiInputPort::Device(_, name) => f.debug_tuple("Device").field(name).finish(),
+        }
+    }
 }
 
 impl Display for MidiInputPort {
@@ -31,7 +115,132 @@ impl MidiInputPort {
     pub fn as_str(&self) -> &str {
         match self {
             MidiInputPort::None => NO_SELECTION,
-            MidiInputPort::Virtual(name) | MidiInputPort::Device(_, name) => name.as_str(),
+            MidiInputPort::Virtual(name) => name.as_str(),
+            MidiInputPort::Device(_, name) => name.display.as_str(),
+        }
+    }
+
+    /// Position of this port in `ports`, listed again. A device port is looked up by its backend port, then by name
+    /// and ordinal, then by name alone, so the same one of two identical devices stays selected
+    #[must_use]
+    pub fn position_in(&self, ports: &[MidiInputPort]) -> Option<usize> {
+        let MidiInputPort::Device(port, name) = self else {
+            return ports.iter().position(|other| other == self);
+        };
+        let devices = ports.iter().enumerate().filter_map(|(index, other)| match other {
+            MidiInputPort::Device(other_port, other_name) => Some((index, (other_port, other_name))),
+            _ => None,
+        });
+        let position = find_port(port, name, devices.clone().map(|(_, device)| device))?;
+        devices.map(|(index, _)| index).nth(position)
+    }
+
+    /// Same port, down to the backend port
+    #[must_use]
+    pub fn is_same_port(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MidiInputPort::Device(port, name), MidiInputPort::Device(other_port, other_name)) => {
+                port == other_port && name == other_name
+            }
+            _ => self == other,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            MidiInputPort::None => 0,
+            MidiInputPort::Virtual(_) => 1,
+            MidiInputPort::Device(..) => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // synthetic backend ports, in the order the backend lists them
+    fn port_names(names: &[&str]) -> Vec<PortName> {
+        let names = names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        names
+            .iter()
+            .zip(ordinals(&names))
+            .map(|(name, ordinal)| PortName { name: name.clone(), ordinal, display: format!("{name} ({ordinal})") })
+            .collect()
+    }
+
+    fn find(port: (u32, &PortName), candidates: &[(u32, PortName)]) -> Option<usize> {
+        find_port(&port.0, port.1, candidates.iter().map(|(handle, name)| (handle, name)))
+    }
+
+    #[test]
+    fn test_ordering() {
+        let names = ["Launchpad Mini"; 12].into_iter().chain(["Drums", "Keys", "Drums"]).collect::<Vec<_>>();
+        let mut sorted = port_names(&names);
+        sorted.reverse();
+        sorted.sort();
+        let ordinals = sorted.iter().map(|name| (name.name.as_str(), name.ordinal)).collect::<Vec<_>>();
+        // the 10th comes after the 2nd
+        assert_eq!(
+            ordinals,
+            [("Drums", 1), ("Drums", 2), ("Keys", 1)]
+                .into_iter()
+                .chain((1..=12).map(|ordinal| ("Launchpad Mini", ordinal)))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            [MidiInputPort::Virtual("b".to_string()), MidiInputPort::None, MidiInputPort::Virtual("a".to_string())]
+                .into_iter()
+                .max(),
+            Some(MidiInputPort::Virtual("b".to_string()))
+        );
+        assert!(MidiInputPort::None < MidiInputPort::Virtual(String::new()));
+    }
+
+    #[test]
+    fn test_find_port() {
+        let names = port_names(&["Launchpad Mini", "Drums", "Launchpad Mini"]);
+        let ports = [(10, names[0].clone()), (11, names[1].clone()), (12, names[2].clone())];
+        for (index, (handle, name)) in ports.iter().enumerate() {
+            assert_eq!(find((*handle, name), &ports), Some(index));
         }
+
+        // the first launchpad was unplugged, the second one keeps its backend port but is the first of its name now
+        let replugged = port_names(&["Drums", "Launchpad Mini"]);
+        let without_first = [(11, replugged[0].clone()), (12, replugged[1].clone())];
+        assert_eq!(find((12, &names[2]), &without_first), Some(1));
+        // the first one is gone, the one now first of its name takes its place
+        assert_eq!(find((10, &names[0]), &without_first), Some(1));
+        // found by name alone
+        assert_eq!(find((30, &names[2]), &without_first), Some(1));
+
+        // listed in another order, the backend port tells them apart
+        let reordered = [(12, names[0].clone()), (11, names[1].clone()), (10, names[2].clone())];
+        assert_eq!(find((12, &names[2]), &reordered), Some(0));
+        assert_eq!(find((10, &names[0]), &reordered), Some(2));
+
+        // both plugged again with new backend ports, told apart by their ordinal
+        let reconnected = [(20, names[0].clone()), (21, names[1].clone()), (22, names[2].clone())];
+        assert_eq!(find((12, &names[2]), &reconnected), Some(2));
+        assert_eq!(find((10, &names[0]), &reconnected), Some(0));
+
+        // a backend port reused by another device doesn't count
+        let reused = [(12, port_names(&["Keys"])[0].clone())];
+        assert_eq!(find((12, &names[2]), &reused), None);
+    }
+
+    #[test]
+    fn test_position_in() {
+        let ports = [MidiInputPort::None, MidiInputPort::Virtual("a".to_string())];
+        assert_eq!(MidiInputPort::Virtual("a".to_string()).position_in(&ports), Some(1));
+        assert_eq!(MidiInputPort::Virtual("b".to_string()).position_in(&ports), None);
+        assert_eq!(MidiInputPort::None.position_in(&ports), Some(0));
+    }
+
+    #[test]
+    fn test_ordinals() {
+        let names = ["a", "b", "a", "a", "b"].map(String::from);
+        assert_eq!(ordinals(&names), [1, 1, 2, 3, 2]);
     }
 }
@@ -13,6 +13,7 @@ pub struct TimedTypedMidiMessage<T> {
 pub type TimedMidiMessage = TimedTypedMidiMessage<StaticMidiMessage>;
 pub type TimedMidiNoteOn = TimedTypedMidiMessage<MidiNoteOn>;
 
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub struct MidiNoteOn {
     pub channel: u8,
     pub note: u8,
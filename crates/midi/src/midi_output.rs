@@ -2,9 +2,12 @@
 use errors::MakeReportExt;
 use log::{error, info};
 use midir::{os::unix::VirtualOutput, MidiOutputConnection};
-use wmidi::{Channel, ControlFunction, MidiMessage, U7};
+use wmidi::{Channel, ControlFunction, MidiMessage, Note, U7};
 
-use crate::midi_output_trait::{MidiOutput, MIDI_CLOCK_MESSAGE, MIDI_PLAY_MESSAGE, MIDI_STOP_MESSAGE};
+use crate::{
+    midi_backend::MidiBackend,
+    midi_output_trait::{MidiOutput, MIDI_CLOCK_MESSAGE, MIDI_PLAY_MESSAGE, MIDI_STOP_MESSAGE},
+};
 use errors::{LogErrorWithExt, Result};
 
 pub struct VirtualMidiOutput {
@@ -12,9 +15,12 @@ pub struct VirtualMidiOutput {
 }
 
 impl VirtualMidiOutput {
-    pub fn new(device_name: &str) -> Result<Self> {
+    // `backend` is the one resolved for the input, midir builds both sides on the same one
+    pub fn new(device_name: &str, backend: MidiBackend) -> Result<Self> {
         let midi_output = midir::MidiOutput::new(device_name)?;
-        let virtual_output = midi_output.create_virtual(device_name).report_msg("unable to create virtual output")?;
+        let virtual_output = midi_output
+            .create_virtual(device_name)
+            .report_msg(&format!("unable to create virtual output with {backend}"))?;
 
         Ok(Self { virtual_output })
     }
@@ -48,6 +54,27 @@ impl MidiOutput for VirtualMidiOutput {
         }
     }
 
+    fn program_change(&mut self, channel: Channel, program: U7) {
+        info!("Sending channel {} program change {}", channel.index(), u8::from(program));
+        let mut message = [0; 2];
+        MidiMessage::ProgramChange(channel, program).copy_to_slice(&mut message).unwrap();
+        if let Err(err) = self.virtual_output.send(&message) {
+            error!("unable to send program change to virtual output: {err:?}");
+        }
+    }
+
+    fn note(&mut self, channel: Channel, note: Note, velocity: U7) {
+        info!("Sending channel {} note {} velocity {}", channel.index(), u8::from(note), u8::from(velocity));
+        for midi_message in [MidiMessage::NoteOn(channel, note, velocity), MidiMessage::NoteOff(channel, note, U7::MIN)]
+        {
+            let mut message = [0; 3];
+            midi_message.copy_to_slice(&mut message).unwrap();
+            if let Err(err) = self.virtual_output.send(&message) {
+                error!("unable to send note to virtual output: {err:?}");
+            }
+        }
+    }
+
     fn sysex(&mut self, value: &str) {
         info!("Sending as sysex: {value}");
         if let Err(err) = self
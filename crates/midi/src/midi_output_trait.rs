@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use wmidi::{Channel, ControlFunction, U7};
+use wmidi::{Channel, ControlFunction, Note, U7};
 
 pub const MIDI_CLOCK_MESSAGE: [u8; 1] = [0xF8];
 pub const MIDI_CONTINUE_MESSAGE: [u8; 1] = [0xFB];
@@ -12,5 +12,8 @@ pub trait MidiOutput {
     fn play(&mut self);
     fn stop(&mut self);
     fn cc(&mut self, channel: Channel, cc: ControlFunction, value: U7);
+    fn program_change(&mut self, channel: Channel, program: U7);
+    // a note-on immediately followed by its note-off
+    fn note(&mut self, channel: Channel, note: Note, velocity: U7);
     fn sysex(&mut self, value: &str);
 }
@@ -0,0 +1,138 @@
+use crate::{accumulator::WideBins, StaticBPMDetectionParameters};
+use serde::{Deserialize, Serialize};
+
+/// Detection on two histograms instead of one: a coarse one over the whole BPM range, and a fine one at the configured
+/// resolution within `fine_window` of the last estimate. The estimate is read from the fine histogram when the coarse
+/// peak falls in its window, from the coarse one otherwise, the next evaluation then zooms in around it. The cost of
+/// each interval is then the points of the coarse histogram it spreads over, plus the fine ones only when it is close
+/// to the estimate, instead of the points of a histogram at the configured resolution over the whole range
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultiResolutionConfig {
+    pub enabled: bool,
+    // bins per second of the histogram over the whole range, the configured resolution if it is lower
+    pub coarse_resolution: u16,
+    // in BPM, on each side of the estimate
+    pub fine_window: u16,
+}
+
+impl Default for MultiResolutionConfig {
+    fn default() -> Self {
+        Self { enabled: false, coarse_resolution: 100, fine_window: 5 }
+    }
+}
+
+impl MultiResolutionConfig {
+    /// Parameters of the histogram over the whole range of `configured`
+    #[must_use]
+    pub fn coarse_parameters(&self, configured: &StaticBPMDetectionParameters) -> StaticBPMDetectionParameters {
+        StaticBPMDetectionParameters {
+            histogram_resolution: self.coarse_resolution.clamp(1, configured.histogram_resolution),
+            ..configured.clone()
+        }
+    }
+
+    /// Parameters of the histogram within `fine_window` of `bpm`, at the resolution of `configured`
+    #[must_use]
+    pub fn fine_parameters(&self, configured: &StaticBPMDetectionParameters, bpm: f32) -> StaticBPMDetectionParameters {
+        StaticBPMDetectionParameters {
+            bpm_center: bpm,
+            bpm_range: self.fine_window.saturating_mul(2).max(2),
+            ..configured.clone()
+        }
+    }
+}
+
+/// Histograms of the last evaluation in multi-resolution mode, see `MultiResolutionConfig`
+#[derive(Clone, Copy, Debug)]
+pub struct MultiResolutionHistogram<'a> {
+    // the histogram sent with the estimate is computed with them, instead of the configured ones
+    pub coarse_parameters: &'a StaticBPMDetectionParameters,
+    // none until a first estimate
+    pub fine: Option<(&'a StaticBPMDetectionParameters, &'a [f32])>,
+}
+
+/// Histogram around the estimate, kept by `BPMDetection` next to the coarse one
+#[derive(Debug)]
+pub(crate) struct FineHistogram {
+    pub(crate) config: MultiResolutionConfig,
+    // where the next evaluation zooms in
+    center: Option<f32>,
+    // of the data points below, none until a first estimate
+    pub(crate) parameters: Option<StaticBPMDetectionParameters>,
+    pub(crate) histogram_data_points: Vec<f32>,
+    // as they are summed with `Accumulator::F64`
+    pub(crate) wide_data_points: WideBins,
+}
+
+impl FineHistogram {
+    pub(crate) fn new(config: MultiResolutionConfig) -> Self {
+        Self {
+            config,
+            center: None,
+            parameters: None,
+            histogram_data_points: Vec::new(),
+            wide_data_points: WideBins::default(),
+        }
+    }
+
+    /// Zooms the next evaluation in around `bpm`
+    pub(crate) fn recenter(&mut self, bpm: f32) {
+        self.center = Some(bpm);
+    }
+
+    /// Lays the histogram around the last estimate with the `configured` parameters, and clears it
+    pub(crate) fn prepare(&mut self, configured: &StaticBPMDetectionParameters) {
+        self.parameters = self.center.map(|center| self.config.fine_parameters(configured, center));
+        let buffer_size = self.parameters.as_ref().map_or(0, StaticBPMDetectionParameters::buffer_size);
+        self.histogram_data_points.clear();
+        self.histogram_data_points.resize(buffer_size, 0.0);
+        self.wide_data_points.clear(configured.accumulator, buffer_size);
+    }
+
+    /// Tempo of the highest bin, none before a first estimate or when nothing reached the window
+    pub(crate) fn peak(&self) -> Option<f32> {
+        let parameters = self.parameters.as_ref()?;
+        self.histogram_data_points
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value > 0.0)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(index, _)| parameters.index_to_bpm(index))
+    }
+
+    /// Whether `bpm` falls in the window of the last evaluation
+    pub(crate) fn contains(&self, bpm: f32) -> bool {
+        self.parameters
+            .as_ref()
+            .is_some_and(|parameters| bpm >= parameters.lowest_bpm() && bpm <= parameters.highest_bpm())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters() {
+        let configured = StaticBPMDetectionParameters {
+            bpm_center: 120.0,
+            bpm_range: 200,
+            ..StaticBPMDetectionParameters::default()
+        };
+        let config = MultiResolutionConfig { enabled: true, ..MultiResolutionConfig::default() };
+
+        let coarse = config.coarse_parameters(&configured);
+        assert!((coarse.lowest_bpm() - configured.lowest_bpm()).abs() < f32::EPSILON);
+        assert!((coarse.highest_bpm() - configured.highest_bpm()).abs() < f32::EPSILON);
+        assert!(coarse.buffer_size() < configured.buffer_size() / 4);
+        // never finer than configured
+        let low_resolution = StaticBPMDetectionParameters { histogram_resolution: 50, ..configured.clone() };
+        assert_eq!(config.coarse_parameters(&low_resolution).histogram_resolution, 50);
+
+        let fine = config.fine_parameters(&configured, 97.5);
+        assert!((fine.lowest_bpm() - 92.5).abs() < f32::EPSILON);
+        assert!((fine.highest_bpm() - 102.5).abs() < f32::EPSILON);
+        assert_eq!(fine.histogram_resolution, configured.histogram_resolution);
+    }
+}
@@ -2,16 +2,83 @@ use std::time::Duration;
 
 pub trait DurationOps {
     fn div(duration: Duration, divisor: Self) -> Duration;
+
+    /// None when the divisor is zero, negative or not a number, or when the quotient is too large for a duration
+    fn checked_div(duration: Duration, divisor: Self) -> Option<Duration>;
+
+    /// Saturates at `Duration::MAX` when the quotient is too large, as it is for a zero divisor. A negative divisor
+    /// or one that is not a number gives zero
+    fn saturating_div(duration: Duration, divisor: Self) -> Duration;
+}
+
+macro_rules! impl_duration_ops_for_float {
+    ($float_type:ty, $div:ident, $as_secs:ident, $try_from_secs:ident) => {
+        impl DurationOps for $float_type {
+            fn div(duration: Duration, divisor: Self) -> Duration {
+                duration.$div(divisor)
+            }
+
+            fn checked_div(duration: Duration, divisor: Self) -> Option<Duration> {
+                if divisor.is_nan() || divisor <= 0.0 {
+                    return None;
+                }
+                Duration::$try_from_secs(duration.$as_secs() / divisor).ok()
+            }
+
+            fn saturating_div(duration: Duration, divisor: Self) -> Duration {
+                <Self as DurationOps>::checked_div(duration, divisor).unwrap_or(if divisor >= 0.0 {
+                    Duration::MAX
+                } else {
+                    Duration::ZERO
+                })
+            }
+        }
+    };
 }
 
-impl DurationOps for f32 {
+impl_duration_ops_for_float!(f32, div_f32, as_secs_f32, try_from_secs_f32);
+impl_duration_ops_for_float!(f64, div_f64, as_secs_f64, try_from_secs_f64);
+
+impl DurationOps for u16 {
     fn div(duration: Duration, divisor: Self) -> Duration {
-        duration.div_f32(divisor)
+        duration / u32::from(divisor)
+    }
+
+    fn checked_div(duration: Duration, divisor: Self) -> Option<Duration> {
+        duration.checked_div(u32::from(divisor))
+    }
+
+    fn saturating_div(duration: Duration, divisor: Self) -> Duration {
+        <Self as DurationOps>::checked_div(duration, divisor).unwrap_or(Duration::MAX)
     }
 }
 
-impl DurationOps for f64 {
-    fn div(duration: Duration, divisor: Self) -> Duration {
-        duration.div_f64(divisor)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_div() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(<f32 as DurationOps>::checked_div(minute, 120.0), Some(Duration::from_millis(500)));
+        assert_eq!(<f64 as DurationOps>::checked_div(minute, 0.5), Some(Duration::from_secs(120)));
+        assert_eq!(<u16 as DurationOps>::checked_div(minute, 24), Some(Duration::from_millis(2500)));
+        for divisor in [0.0, -0.0, -1.0, f32::NAN, f32::NEG_INFINITY] {
+            assert_eq!(<f32 as DurationOps>::checked_div(minute, divisor), None, "{divisor}");
+        }
+        assert_eq!(<f64 as DurationOps>::checked_div(minute, f64::MIN_POSITIVE), None);
+        assert_eq!(<f64 as DurationOps>::checked_div(minute, f64::INFINITY), Some(Duration::ZERO));
+        assert_eq!(<u16 as DurationOps>::checked_div(minute, 0), None);
+    }
+
+    #[test]
+    fn test_saturating_div() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(<f32 as DurationOps>::saturating_div(minute, 0.0), Duration::MAX);
+        assert_eq!(<f32 as DurationOps>::saturating_div(minute, f32::MIN_POSITIVE), Duration::MAX);
+        assert_eq!(<f32 as DurationOps>::saturating_div(minute, -1.0), Duration::ZERO);
+        assert_eq!(<f64 as DurationOps>::saturating_div(minute, f64::NAN), Duration::ZERO);
+        assert_eq!(<u16 as DurationOps>::saturating_div(minute, 0), Duration::MAX);
+        assert_eq!(<u16 as DurationOps>::saturating_div(minute, u16::MAX), minute / u32::from(u16::MAX));
     }
 }
@@ -0,0 +1,139 @@
+//! Sends the tempo as an OSC message over UDP, for software such as lighting rigs or sound servers. The message is
+//! `/bpm` with a single float argument, sent each time an estimate is computed while it is enabled
+
+use errors::{info, LogErrorWithExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::atomic::Ordering,
+};
+use sync::ArcAtomicBool;
+
+pub const BPM_ADDRESS: &str = "/bpm";
+
+/// Where the standalone application sends the OSC messages to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscOutputConfig {
+    // shared with the worker, toggled from the interface
+    pub enabled: ArcAtomicBool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for OscOutputConfig {
+    fn default() -> Self {
+        Self { enabled: ArcAtomicBool::default(), host: Ipv4Addr::LOCALHOST.to_string(), port: 9001 }
+    }
+}
+
+/// OSC message of a single float argument, the address and the type tags are padded to a multiple of 4 bytes with
+/// nulls, the argument is big-endian
+#[must_use]
+pub fn encode_float(address: &str, value: f32) -> Vec<u8> {
+    let mut message = Vec::with_capacity(address.len() + 12);
+    push_padded(&mut message, address);
+    push_padded(&mut message, ",f");
+    message.extend_from_slice(&value.to_be_bytes());
+    message
+}
+
+// at least one null
+fn push_padded(message: &mut Vec<u8>, string: &str) {
+    message.extend_from_slice(string.as_bytes());
+    message.resize(message.len() + 4 - string.len() % 4, 0);
+}
+
+/// UDP socket towards the configured host, bound and resolved once. The socket doesn't block, a message that can't be
+/// sent is dropped and the failure logged, once until a message goes through again
+pub struct OscOutput {
+    enabled: ArcAtomicBool,
+    // none when the host could not be resolved or the socket bound
+    target: Option<(UdpSocket, SocketAddr)>,
+    failing: bool,
+}
+
+impl OscOutput {
+    #[must_use]
+    pub fn new(osc_output_config: &OscOutputConfig) -> Self {
+        let target = (osc_output_config.host.as_str(), osc_output_config.port)
+            .to_socket_addrs()
+            .log_error_msg("could not resolve the OSC host, not sending OSC")
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .and_then(|address| {
+                let unspecified: SocketAddr = if address.is_ipv4() {
+                    (Ipv4Addr::UNSPECIFIED, 0).into()
+                } else {
+                    (Ipv6Addr::UNSPECIFIED, 0).into()
+                };
+                let socket = UdpSocket::bind(unspecified)
+                    .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+                    .log_error_msg("could not bind the OSC socket, not sending OSC")
+                    .ok()?;
+                Some((socket, address))
+            });
+        Self { enabled: osc_output_config.enabled.clone(), target, failing: false }
+    }
+
+    /// Returns whether the message was sent, nothing is while it is disabled
+    pub fn send_bpm(&mut self, bpm: f32) -> bool {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let Some((socket, address)) = &self.target else {
+            return false;
+        };
+        let message = encode_float(BPM_ADDRESS, bpm);
+        let sent = socket.send_to(&message, address);
+        if sent.is_ok() {
+            if self.failing {
+                info!("sending OSC to {address} again");
+            }
+            self.failing = false;
+            return true;
+        }
+        if !self.failing {
+            self.failing = true;
+            sent.log_error_msg("could not send OSC, dropping the messages until it recovers").ok();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_encode_float() {
+        assert_eq!(
+            encode_float(BPM_ADDRESS, 100.5),
+            [
+                b'/', b'b', b'p', b'm', 0, 0, 0, 0, //
+                b',', b'f', 0, 0, //
+                0x42, 0xC9, 0, 0, // 100.5
+            ]
+        );
+        assert_eq!(encode_float("/tempo", 1.0).len(), 8 + 4 + 4);
+    }
+
+    #[test]
+    fn test_send_bpm() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let osc_output_config =
+            OscOutputConfig { port: receiver.local_addr().unwrap().port(), ..OscOutputConfig::default() };
+        let mut osc_output = OscOutput::new(&osc_output_config);
+
+        // disabled by default
+        assert!(!osc_output.send_bpm(90.0));
+        osc_output_config.enabled.store(true, Ordering::Relaxed);
+        assert!(osc_output.send_bpm(100.5));
+
+        let mut datagram = [0u8; 32];
+        let size = receiver.recv(&mut datagram).unwrap();
+        assert_eq!(&datagram[..size], encode_float(BPM_ADDRESS, 100.5).as_slice());
+    }
+}
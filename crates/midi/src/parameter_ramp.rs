@@ -0,0 +1,179 @@
+use chrono::Duration;
+use parameter::OnOff;
+
+use crate::DynamicBPMDetectionParameters;
+
+// weight changes up to that much apply right away, the ones from dragging a slider for instance
+const RAMP_THRESHOLD: f32 = 0.05;
+
+type WeightMut = fn(&mut DynamicBPMDetectionParameters) -> &mut OnOff<f32>;
+
+// weights used when combining notes, the other parameters apply right away
+const WEIGHTS: [WeightMut; 10] = [
+    DynamicBPMDetectionParameters::velocity_current_note_weight_mut,
+    DynamicBPMDetectionParameters::velocity_note_from_weight_mut,
+    DynamicBPMDetectionParameters::age_weight_mut,
+    DynamicBPMDetectionParameters::octave_distance_weight_mut,
+    DynamicBPMDetectionParameters::pitch_distance_weight_mut,
+    DynamicBPMDetectionParameters::multiplier_weight_mut,
+    DynamicBPMDetectionParameters::subdivision_weight_mut,
+    DynamicBPMDetectionParameters::in_beat_range_weight_mut,
+    DynamicBPMDetectionParameters::normal_distribution_weight_mut,
+    DynamicBPMDetectionParameters::high_tempo_bias_mut,
+];
+
+#[derive(Clone, Copy, Debug)]
+struct Ramp {
+    from: f32,
+    // set by the first evaluation following the change
+    start: Option<Duration>,
+}
+
+/// Parameters the detection evaluates with. When a weight jumps, the effective weight goes from the previous one to
+/// the new one over `parameter_ramp_ms`, measured on the evaluation timestamps, so the histogram doesn't snap. A weight
+/// switched on or off ramps from or to zero
+#[derive(Clone, Debug)]
+pub struct ParameterRamp {
+    // last configured parameters
+    target: DynamicBPMDetectionParameters,
+    effective: DynamicBPMDetectionParameters,
+    ramps: [Option<Ramp>; WEIGHTS.len()],
+}
+
+impl ParameterRamp {
+    #[must_use]
+    pub fn new(dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters) -> Self {
+        Self {
+            target: dynamic_bpm_detection_parameters.clone(),
+            effective: dynamic_bpm_detection_parameters.clone(),
+            ramps: [None; WEIGHTS.len()],
+        }
+    }
+
+    /// Parameters to evaluate with at `timestamp`, following `target`, the parameters currently configured
+    pub fn apply(
+        &mut self,
+        target: &DynamicBPMDetectionParameters,
+        timestamp: Duration,
+    ) -> &DynamicBPMDetectionParameters {
+        if target != &self.target {
+            self.retarget(target);
+        }
+        let ramp_duration = Duration::milliseconds(i64::from(self.target.parameter_ramp_ms));
+        for (weight_mut, ramp) in WEIGHTS.iter().zip(&mut self.ramps) {
+            let Some(ramp_state) = ramp.as_mut() else {
+                continue;
+            };
+            // timestamps going back start over with a new device
+            let start = match ramp_state.start {
+                Some(start) if start <= timestamp => start,
+                _ => timestamp,
+            };
+            ramp_state.start = Some(start);
+            let from = ramp_state.from;
+            let elapsed = timestamp - start;
+            let target_weight = *weight_mut(&mut self.target);
+            if elapsed >= ramp_duration || ramp_duration <= Duration::zero() {
+                *weight_mut(&mut self.effective) = target_weight;
+                *ramp = None;
+            } else {
+                let progress = elapsed.num_microseconds().unwrap_or(i64::MAX) as f32
+                    / ramp_duration.num_microseconds().unwrap_or(i64::MAX) as f32;
+                *weight_mut(&mut self.effective) = OnOff::On(from + (target_weight.weight() - from) * progress);
+            }
+        }
+        &self.effective
+    }
+
+    fn retarget(&mut self, target: &DynamicBPMDetectionParameters) {
+        let mut effective = target.clone();
+        for (weight_mut, ramp) in WEIGHTS.iter().zip(&mut self.ramps) {
+            let current = *weight_mut(&mut self.effective);
+            let target_weight = *weight_mut(&mut effective);
+            if (target_weight.weight() - current.weight()).abs() > RAMP_THRESHOLD {
+                *ramp = Some(Ramp { from: current.weight(), start: None });
+                *weight_mut(&mut effective) = current;
+            } else {
+                *ramp = None;
+            }
+        }
+        self.target = target.clone();
+        self.effective = effective;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a ramp of 200ms keeps the expected weights exact
+    fn params(subdivision_weight: OnOff<f32>) -> DynamicBPMDetectionParameters {
+        DynamicBPMDetectionParameters {
+            subdivision_weight,
+            parameter_ramp_ms: 200,
+            ..DynamicBPMDetectionParameters::default()
+        }
+    }
+
+    // effective subdivision weight at each evaluation, `target` being configured before the first one
+    fn trajectory(
+        parameter_ramp: &mut ParameterRamp,
+        target: &DynamicBPMDetectionParameters,
+        evaluations_ms: impl IntoIterator<Item = i64>,
+    ) -> Vec<OnOff<f32>> {
+        evaluations_ms
+            .into_iter()
+            .map(|ms| parameter_ramp.apply(target, Duration::milliseconds(ms)).subdivision_weight)
+            .collect()
+    }
+
+    #[test]
+    fn test_ramp() {
+        let mut parameter_ramp = ParameterRamp::new(&params(OnOff::On(1.0)));
+        let target = params(OnOff::On(3.0));
+        let weights = trajectory(&mut parameter_ramp, &target, [1000, 1050, 1100, 1150, 1200, 1300]);
+        assert_eq!(
+            weights,
+            [OnOff::On(1.0), OnOff::On(1.5), OnOff::On(2.0), OnOff::On(2.5), OnOff::On(3.0), OnOff::On(3.0)]
+        );
+        // the other weights didn't move, and the final state is the target exactly
+        assert_eq!(parameter_ramp.apply(&target, Duration::seconds(2)), &target);
+    }
+
+    #[test]
+    fn test_on_off() {
+        let mut parameter_ramp = ParameterRamp::new(&params(OnOff::On(2.0)));
+        let target = params(OnOff::Off(2.0));
+        let weights = trajectory(&mut parameter_ramp, &target, [0, 100, 200]);
+        assert_eq!(weights, [OnOff::On(2.0), OnOff::On(1.0), OnOff::Off(2.0)]);
+
+        let target = params(OnOff::On(2.0));
+        let weights = trajectory(&mut parameter_ramp, &target, [300, 400, 500]);
+        assert_eq!(weights, [OnOff::On(0.0), OnOff::On(1.0), OnOff::On(2.0)]);
+    }
+
+    #[test]
+    fn test_retarget_while_ramping() {
+        let mut parameter_ramp = ParameterRamp::new(&params(OnOff::On(0.0)));
+        let weights = trajectory(&mut parameter_ramp, &params(OnOff::On(2.0)), [0, 100]);
+        assert_eq!(weights, [OnOff::On(0.0), OnOff::On(1.0)]);
+        // going back from where it is
+        let weights = trajectory(&mut parameter_ramp, &params(OnOff::On(0.0)), [200, 300, 400]);
+        assert_eq!(weights, [OnOff::On(1.0), OnOff::On(0.5), OnOff::On(0.0)]);
+    }
+
+    #[test]
+    fn test_immediate() {
+        // small changes
+        let mut parameter_ramp = ParameterRamp::new(&params(OnOff::On(1.0)));
+        assert_eq!(trajectory(&mut parameter_ramp, &params(OnOff::On(1.04)), [0]), [OnOff::On(1.04)]);
+
+        // ramping disabled
+        let target = DynamicBPMDetectionParameters { parameter_ramp_ms: 0, ..params(OnOff::On(3.0)) };
+        assert_eq!(trajectory(&mut parameter_ramp, &target, [10]), [OnOff::On(3.0)]);
+
+        // other parameters
+        let target = DynamicBPMDetectionParameters { beats_lookback: 16, ..target };
+        assert_eq!(parameter_ramp.apply(&target, Duration::milliseconds(20)).beats_lookback, 16);
+    }
+}
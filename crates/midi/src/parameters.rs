@@ -0,0 +1,182 @@
+//! Description of the parameters, for the tools that map them to controllers. Each entry is built from the
+//! `Parameter` const the GUI and the plugin use, so the description can't drift from the actual range and default.
+
+use parameter::{OnOff, Parameter};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::{DynamicBPMDetectionParameters, NormalDistributionConfig, StaticBPMDetectionParameters};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ParameterDescriptor {
+    // dotted path of the value in the configuration file
+    pub id: &'static str,
+    // id of the plugin parameter, for the parameters the plugin exposes
+    pub plugin_id: Option<&'static str>,
+    pub label: &'static str,
+    pub unit: Option<&'static str>,
+    pub min: f64,
+    pub max: f64,
+    // 0 when the value is continuous
+    pub step: f64,
+    pub logarithmic: bool,
+    pub default: f64,
+    // the value can be switched off, the detection then ignores it
+    pub on_off: bool,
+    pub default_enabled: bool,
+}
+
+impl ParameterDescriptor {
+    #[must_use]
+    pub fn new<T, V: DescribedValue>(
+        id: &'static str,
+        plugin_id: Option<&'static str>,
+        parameter: &Parameter<T, V>,
+    ) -> Self {
+        let (default, default_enabled) = parameter.default.describe();
+        Self {
+            id,
+            plugin_id,
+            label: parameter.label,
+            unit: parameter.unit,
+            min: *parameter.range.start(),
+            max: *parameter.range.end(),
+            step: parameter.step,
+            logarithmic: parameter.logarithmic,
+            default,
+            on_off: default_enabled.is_some(),
+            default_enabled: default_enabled.unwrap_or(true),
+        }
+    }
+}
+
+/// Value of a parameter as it is described: the number, and whether it is enabled for the values that can be switched
+/// off
+pub trait DescribedValue {
+    fn describe(&self) -> (f64, Option<bool>);
+}
+
+macro_rules! impl_described_value {
+    ($($value_type:ty),*) => {
+        $(impl DescribedValue for $value_type {
+            fn describe(&self) -> (f64, Option<bool>) {
+                (parameter::Asf64::get(self), None)
+            }
+        })*
+    };
+}
+
+impl_described_value!(u8, u16, f32, f64, Duration);
+
+impl<T: DescribedValue> DescribedValue for OnOff<T> {
+    fn describe(&self) -> (f64, Option<bool>) {
+        match self {
+            OnOff::On(value) => (value.describe().0, Some(true)),
+            OnOff::Off(value) => (value.describe().0, Some(false)),
+        }
+    }
+}
+
+/// Descriptors of the parameters listed as `"id" => PARAMETER`, or `"id" ("plugin_id") => PARAMETER` for the ones the
+/// plugin exposes
+#[macro_export]
+macro_rules! parameter_descriptors {
+    ($($id:literal $(($plugin_id:literal))? => $parameter:expr),* $(,)?) => {
+        vec![$(
+            $crate::parameters::ParameterDescriptor::new(
+                $id,
+                $crate::parameter_descriptors!(@plugin_id $($plugin_id)?),
+                &$parameter,
+            )
+        ),*]
+    };
+    (@plugin_id) => { None };
+    (@plugin_id $plugin_id:literal) => { Some($plugin_id) };
+}
+
+/// Every parameter of the detection. A new `Parameter` const is to be registered here, the plugin tests check that
+/// the parameters it exposes are
+#[must_use]
+pub fn catalog() -> Vec<ParameterDescriptor> {
+    type Static = StaticBPMDetectionParameters;
+    type Dynamic = DynamicBPMDetectionParameters;
+    type Normal = NormalDistributionConfig;
+
+    parameter_descriptors![
+        "static_bpm_detection_parameters.bpm_center" ("lower_bound") => Static::BPM_CENTER,
+        "static_bpm_detection_parameters.bpm_range" ("upper_bound") => Static::BPM_RANGE,
+        "static_bpm_detection_parameters.histogram_resolution" ("sample_rate") => Static::HISTOGRAM_RESOLUTION,
+        "static_bpm_detection_parameters.note_low" ("note_low") => Static::NOTE_LOW,
+        "static_bpm_detection_parameters.note_high" ("note_high") => Static::NOTE_HIGH,
+        "static_bpm_detection_parameters.histogram_smoothing" => Static::HISTOGRAM_SMOOTHING,
+        "static_bpm_detection_parameters.normal_distribution.std_dev" ("std_dev") => Normal::STD_DEV,
+        "static_bpm_detection_parameters.normal_distribution.factor" ("factor") => Normal::FACTOR,
+        "static_bpm_detection_parameters.normal_distribution.imprecision" ("imprecision") => Normal::IMPRECISION,
+        "static_bpm_detection_parameters.normal_distribution.resolution" ("resolution") => Normal::RESOLUTION,
+        "dynamic_bpm_detection_parameters.beats_lookback" ("beats_lookback") => Dynamic::BEATS_LOOKBACK,
+        "dynamic_bpm_detection_parameters.velocity_current_note_weight" ("velocity_current_note_weight")
+            => Dynamic::CURRENT_VELOCITY,
+        "dynamic_bpm_detection_parameters.velocity_note_from_weight" ("velocity_note_from_weight")
+            => Dynamic::VELOCITY_FROM,
+        "dynamic_bpm_detection_parameters.age_weight" ("age_weight") => Dynamic::TIME_DISTANCE,
+        "dynamic_bpm_detection_parameters.octave_distance_weight" ("octave_distance_weight")
+            => Dynamic::OCTAVE_DISTANCE,
+        "dynamic_bpm_detection_parameters.pitch_distance_weight" ("pitch_distance_weight") => Dynamic::PITCH_DISTANCE,
+        "dynamic_bpm_detection_parameters.multiplier_weight" ("multiplier_weight") => Dynamic::MULTIPLIER_FACTOR,
+        "dynamic_bpm_detection_parameters.subdivision_weight" ("subdivision_weight") => Dynamic::SUBDIVISION_FACTOR,
+        "dynamic_bpm_detection_parameters.in_beat_range_weight" ("in_beat_range_weight") => Dynamic::IN_RANGE,
+        "dynamic_bpm_detection_parameters.normal_distribution_weight" ("normal_distribution_weight")
+            => Dynamic::NORMAL_DISTRIBUTION,
+        "dynamic_bpm_detection_parameters.high_tempo_bias" ("high_tempo_bias") => Dynamic::HIGH_TEMPO_BIAS,
+        "dynamic_bpm_detection_parameters.max_simultaneous_onsets" => Dynamic::MAX_SIMULTANEOUS_ONSETS,
+        "dynamic_bpm_detection_parameters.auto_velocity_gate" => Dynamic::AUTO_VELOCITY_GATE,
+        "dynamic_bpm_detection_parameters.minimum_velocity" ("minimum_velocity") => Dynamic::MINIMUM_VELOCITY,
+        "dynamic_bpm_detection_parameters.parameter_ramp_ms" => Dynamic::PARAMETER_RAMP,
+        "dynamic_bpm_detection_parameters.auto_zoom" => Dynamic::AUTO_ZOOM,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalog() {
+        let catalog = catalog();
+        let ids = catalog.iter().map(|descriptor| descriptor.id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), catalog.len());
+
+        // the ids are the paths of the values in the configuration
+        let config = serde_json::json!({
+            "static_bpm_detection_parameters": StaticBPMDetectionParameters::default(),
+            "dynamic_bpm_detection_parameters": DynamicBPMDetectionParameters::default(),
+        });
+        for descriptor in &catalog {
+            let pointer = format!("/{}", descriptor.id.replace('.', "/"));
+            assert!(config.pointer(&pointer).is_some(), "{} is not in the configuration", descriptor.id);
+            assert!(descriptor.min <= descriptor.default && descriptor.default <= descriptor.max, "{descriptor:?}");
+        }
+
+        let age = catalog.iter().find(|descriptor| descriptor.plugin_id == Some("age_weight")).unwrap();
+        assert!(age.on_off && age.default_enabled && age.logarithmic);
+        assert!((age.default - 0.7).abs() < 1e-6);
+        let auto_zoom = catalog.iter().find(|descriptor| descriptor.id.ends_with("auto_zoom")).unwrap();
+        assert!(auto_zoom.on_off && !auto_zoom.default_enabled);
+        let bpm_range = catalog.iter().find(|descriptor| descriptor.id.ends_with("bpm_range")).unwrap();
+        assert!(!bpm_range.on_off && bpm_range.default_enabled);
+        assert!((bpm_range.default - 40.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_json() {
+        let json = serde_json::to_value(catalog()).unwrap();
+        let bpm_center = &json[0];
+        assert_eq!(bpm_center["id"], "static_bpm_detection_parameters.bpm_center");
+        assert_eq!(bpm_center["plugin_id"], "lower_bound");
+        assert_eq!(bpm_center["unit"], serde_json::Value::Null);
+        assert_eq!(bpm_center["min"], 1.0);
+        assert_eq!(bpm_center["max"], 150.0);
+        assert_eq!(bpm_center["on_off"], false);
+    }
+}
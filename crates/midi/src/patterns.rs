@@ -0,0 +1,326 @@
+use chrono::Duration;
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{midi_messages::MidiNoteOn, TimedMidiNoteOn};
+
+// at full humanization, notes are that far off the grid at most
+const MAX_TIMING_JITTER_MS: f64 = 20.0;
+const MAX_VELOCITY_JITTER: f64 = 24.0;
+// general MIDI percussion
+const DRUMS_CHANNEL: u8 = 9;
+const KICK: u8 = 36;
+const SNARE: u8 = 38;
+const CLOSED_HI_HAT: u8 = 42;
+const CLAVES: u8 = 75;
+
+/// A note of a pattern, `beat` beats after the start of the bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Onset {
+    pub beat: f64,
+    pub note: u8,
+    pub velocity: u8,
+    // chance for the note to be played in a given bar
+    pub probability: f64,
+}
+
+const fn onset(beat: f64, note: u8, velocity: u8) -> Onset {
+    Onset { beat, note, velocity, probability: 1.0 }
+}
+
+const QUARTERS: [Onset; 1] = [onset(0.0, KICK, 100)];
+const SWING_EIGHTHS: [Onset; 2] = [onset(0.0, CLOSED_HI_HAT, 100), onset(2.0 / 3.0, CLOSED_HI_HAT, 70)];
+// son clave 3-2, over two bars of 4/4
+const CLAVE: [Onset; 5] = [
+    onset(0.0, CLAVES, 100),
+    onset(1.5, CLAVES, 100),
+    onset(3.0, CLAVES, 100),
+    onset(5.0, CLAVES, 100),
+    onset(6.0, CLAVES, 100),
+];
+const DRUMS: [Onset; 14] = [
+    onset(0.0, KICK, 110),
+    onset(0.0, CLOSED_HI_HAT, 90),
+    onset(0.5, CLOSED_HI_HAT, 70),
+    onset(1.0, SNARE, 100),
+    onset(1.0, CLOSED_HI_HAT, 90),
+    onset(1.5, CLOSED_HI_HAT, 70),
+    onset(2.0, KICK, 110),
+    onset(2.0, CLOSED_HI_HAT, 90),
+    Onset { probability: 0.5, ..onset(2.5, KICK, 90) },
+    onset(2.5, CLOSED_HI_HAT, 70),
+    onset(3.0, SNARE, 100),
+    onset(3.0, CLOSED_HI_HAT, 90),
+    Onset { probability: 0.3, ..onset(3.5, SNARE, 40) },
+    onset(3.5, CLOSED_HI_HAT, 70),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PatternKind {
+    Quarters,
+    SwingEighths,
+    Clave,
+    Drums,
+}
+
+impl PatternKind {
+    pub const ALL: [Self; 4] = [Self::Quarters, Self::SwingEighths, Self::Clave, Self::Drums];
+
+    /// Length of a bar in beats, and its notes sorted by beat
+    #[must_use]
+    pub fn bar(self) -> (f64, &'static [Onset]) {
+        match self {
+            Self::Quarters => (1.0, &QUARTERS),
+            Self::SwingEighths => (1.0, &SWING_EIGHTHS),
+            Self::Clave => (8.0, &CLAVE),
+            Self::Drums => (4.0, &DRUMS),
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Quarters => "Quarters",
+            Self::SwingEighths => "SwingEighths",
+            Self::Clave => "Clave",
+            Self::Drums => "Drums",
+        }
+    }
+}
+
+impl Display for PatternKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PatternKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown pattern '{s}'"))
+    }
+}
+
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
+pub struct DemoPatternConfig {
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub bpm: f32,
+    // 0 plays exactly on the grid, 1 is a loose drummer
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub humanization: f32,
+    pub seed: u64,
+}
+
+impl Default for DemoPatternConfig {
+    fn default() -> Self {
+        Self { bpm: 100.0, humanization: 0.3, seed: 0 }
+    }
+}
+
+/// `SplitMix64`, good enough to humanize a pattern and reproducible from its seed
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // in [-1, 1)
+    fn signed_unit(&mut self) -> f64 {
+        self.unit() * 2.0 - 1.0
+    }
+}
+
+/// Endless notes of a pattern, as `(delay since the previous note, note)`. Timestamps start at zero. The same seed
+/// always gives the same notes
+#[derive(Clone, Debug)]
+pub struct PatternGenerator {
+    kind: PatternKind,
+    // in nanoseconds
+    beat_duration: f64,
+    humanization: f64,
+    rng: Rng,
+    bar: u32,
+    index: usize,
+    last_timestamp: Duration,
+}
+
+impl PatternGenerator {
+    #[must_use]
+    pub fn new(kind: PatternKind, bpm: f32, humanization: f32, seed: u64) -> Self {
+        Self {
+            kind,
+            beat_duration: 60_000_000_000.0 / f64::from(bpm.max(1.0)),
+            humanization: f64::from(humanization.clamp(0.0, 1.0)),
+            rng: Rng(seed),
+            bar: 0,
+            index: 0,
+            last_timestamp: Duration::zero(),
+        }
+    }
+
+    #[must_use]
+    pub fn from_config(kind: PatternKind, demo_pattern_config: &DemoPatternConfig) -> Self {
+        Self::new(kind, demo_pattern_config.bpm, demo_pattern_config.humanization, demo_pattern_config.seed)
+    }
+}
+
+impl Iterator for PatternGenerator {
+    type Item = (Duration, TimedMidiNoteOn);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (bar_length, onsets) = self.kind.bar();
+        loop {
+            let onset = onsets[self.index];
+            let bar = self.bar;
+            self.index += 1;
+            if self.index == onsets.len() {
+                self.index = 0;
+                self.bar += 1;
+            }
+            if onset.probability < 1.0 && self.rng.unit() >= onset.probability {
+                continue;
+            }
+
+            let jitter = self.rng.signed_unit() * self.humanization * MAX_TIMING_JITTER_MS * 1_000_000.0;
+            let velocity =
+                f64::from(onset.velocity) + (self.rng.signed_unit() * self.humanization * MAX_VELOCITY_JITTER).round();
+            let position = (f64::from(bar) * bar_length + onset.beat) * self.beat_duration;
+            // humanization must not reorder notes
+            let timestamp = Duration::nanoseconds((position + jitter) as i64).max(self.last_timestamp);
+            let delay = timestamp - self.last_timestamp;
+            self.last_timestamp = timestamp;
+
+            return Some((
+                delay,
+                TimedMidiNoteOn {
+                    timestamp,
+                    midi_message: MidiNoteOn {
+                        channel: DRUMS_CHANNEL,
+                        note: onset.note,
+                        velocity: velocity.clamp(1.0, 127.0) as u8,
+                    },
+                },
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bpm::bpm_to_beat_duration, BPMDetection, DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+
+    const BPM: f32 = 100.0;
+
+    fn assert_close(actual: Duration, expected_beats: f64) {
+        let expected = bpm_to_beat_duration(BPM).num_nanoseconds().unwrap() as f64 * expected_beats;
+        let actual = actual.num_nanoseconds().unwrap() as f64;
+        assert!((actual - expected).abs() < 1000.0, "{actual} != {expected}");
+    }
+
+    fn delays(kind: PatternKind, count: usize) -> Vec<Duration> {
+        PatternGenerator::new(kind, BPM, 0.0, 0).take(count).map(|(delay, _)| delay).collect()
+    }
+
+    #[test]
+    fn test_inter_onset_intervals() {
+        for delay in delays(PatternKind::Quarters, 10).into_iter().skip(1) {
+            assert_close(delay, 1.0);
+        }
+        for (delay, expected) in
+            delays(PatternKind::SwingEighths, 10).into_iter().skip(1).zip([2.0 / 3.0, 1.0 / 3.0].iter().cycle())
+        {
+            assert_close(delay, *expected);
+        }
+        for (delay, expected) in
+            delays(PatternKind::Clave, 16).into_iter().skip(1).zip([1.5, 1.5, 2.0, 1.0, 2.0].iter().cycle())
+        {
+            assert_close(delay, *expected);
+        }
+
+        // drums have optional notes, but the hi-hat keeps playing eighths
+        let hi_hats = PatternGenerator::new(PatternKind::Drums, BPM, 0.0, 0)
+            .take(100)
+            .filter(|(_, note)| note.midi_message.note == CLOSED_HI_HAT)
+            .map(|(_, note)| note.timestamp)
+            .collect::<Vec<_>>();
+        for pair in hi_hats.windows(2) {
+            assert_close(pair[1] - pair[0], 0.5);
+        }
+    }
+
+    #[test]
+    fn test_first_note_is_at_zero() {
+        for kind in PatternKind::ALL {
+            let (delay, note) = PatternGenerator::new(kind, BPM, 0.0, 0).next().unwrap();
+            assert_eq!(delay, Duration::zero());
+            assert_eq!(note.timestamp, Duration::zero());
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let notes = |seed| {
+            PatternGenerator::new(PatternKind::Drums, BPM, 1.0, seed)
+                .take(200)
+                .map(|(delay, note)| (delay, note.timestamp, note.midi_message.note, note.midi_message.velocity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(notes(42), notes(42));
+        assert_ne!(notes(42), notes(43));
+
+        // humanized notes stay in order and close to the grid
+        let mut previous = Duration::zero();
+        for (delay, timestamp, _, velocity) in notes(42) {
+            assert!(delay >= Duration::zero());
+            assert!(timestamp >= previous);
+            assert!(velocity >= 1);
+            previous = timestamp;
+        }
+    }
+
+    #[test]
+    fn test_pattern_names() {
+        for kind in PatternKind::ALL {
+            assert_eq!(kind.to_string().parse::<PatternKind>(), Ok(kind));
+        }
+        assert_eq!("swingeighths".parse::<PatternKind>(), Ok(PatternKind::SwingEighths));
+        assert!("waltz".parse::<PatternKind>().is_err());
+    }
+
+    #[test]
+    fn test_detection_converges() {
+        let dynamic_bpm_detection_parameters = DynamicBPMDetectionParameters::default();
+        for kind in PatternKind::ALL {
+            let mut bpm_detection = BPMDetection::new(StaticBPMDetectionParameters::default());
+            let mut bpm = None;
+            for (_, note) in PatternGenerator::new(kind, BPM, 0.5, 42).take(48) {
+                bpm_detection.receive_midi_message(note);
+                bpm = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters).map(|(_, estimate)| estimate.bpm);
+            }
+            let bpm = bpm.unwrap();
+            assert!((bpm - BPM).abs() < 1.5, "{kind} detected at {bpm}");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::Duration;
+use instant::Instant;
+use std::time::Duration as StdDuration;
+
+use crate::{
+    auto_zoom::AutoZoom, chord_filter::ChordFilter, parameter_ramp::ParameterRamp, velocity_gate::VelocityGate,
+    BPMDetection, BpmEstimate, DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedMidiNoteOn,
+};
+
+/// Stages around `BPMDetection`, the same whether the notes come from a device or from a file: chords collapse into a
+/// single onset and ghost notes are gated before the detection, the weights ramp and the range narrows around a locked
+/// tempo when evaluating. Notes read from a file are driven by their timestamps, taken as the time they are received
+pub struct PreDetection {
+    chord_filter: ChordFilter,
+    velocity_gate: VelocityGate,
+    parameter_ramp: ParameterRamp,
+    auto_zoom: AutoZoom,
+}
+
+impl PreDetection {
+    #[must_use]
+    pub fn new(
+        static_bpm_detection_parameters: &StaticBPMDetectionParameters,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+    ) -> Self {
+        Self {
+            chord_filter: ChordFilter::default(),
+            velocity_gate: VelocityGate::default(),
+            parameter_ramp: ParameterRamp::new(dynamic_bpm_detection_parameters),
+            auto_zoom: AutoZoom::new(static_bpm_detection_parameters),
+        }
+    }
+
+    /// `emit` receives the onsets of the chords the note completes, the ones the velocity gate lets through
+    pub fn note_on(
+        &mut self,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+        note: TimedMidiNoteOn,
+        now: Instant,
+        emit: impl FnMut(TimedMidiNoteOn),
+    ) {
+        let gated = gate(&mut self.velocity_gate, dynamic_bpm_detection_parameters, emit);
+        self.chord_filter.note_on(dynamic_bpm_detection_parameters.max_simultaneous_onsets, note, now, gated);
+    }
+
+    pub fn note_off(&mut self, channel: u8, note: u8) {
+        self.chord_filter.note_off(channel, note);
+    }
+
+    /// Emits the pending chord once no more note can join it, as `note_on` does
+    pub fn flush(
+        &mut self,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+        now: Instant,
+        emit: impl FnMut(TimedMidiNoteOn),
+    ) {
+        let gated = gate(&mut self.velocity_gate, dynamic_bpm_detection_parameters, emit);
+        self.chord_filter.flush(dynamic_bpm_detection_parameters.max_simultaneous_onsets, now, gated);
+    }
+
+    /// Emits the pending chord right away, once there are no more notes to read
+    pub fn finish(
+        &mut self,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+        emit: impl FnMut(TimedMidiNoteOn),
+    ) {
+        let gated = gate(&mut self.velocity_gate, dynamic_bpm_detection_parameters, emit);
+        self.chord_filter.finish(dynamic_bpm_detection_parameters.max_simultaneous_onsets, gated);
+    }
+
+    /// See `ChordFilter::flush_in`
+    #[must_use]
+    pub fn flush_in(&self, now: Instant) -> Option<StdDuration> {
+        self.chord_filter.flush_in(now)
+    }
+
+    /// Parameters to evaluate with at `timestamp`, see `ParameterRamp`
+    pub fn effective_parameters(
+        &mut self,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+        timestamp: Duration,
+    ) -> &DynamicBPMDetectionParameters {
+        self.parameter_ramp.apply(dynamic_bpm_detection_parameters, timestamp)
+    }
+
+    /// Follows an estimate of `bpm_detection`, which runs with the parameters the auto zoom issues from then on
+    pub fn zoom(
+        &mut self,
+        bpm_detection: &mut BPMDetection,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+        bpm: f32,
+    ) {
+        let Some(newest_note) = bpm_detection.newest_note_timestamp() else {
+            return;
+        };
+        let note_density = bpm_detection.note_density(bpm, dynamic_bpm_detection_parameters.beats_lookback);
+        if let Some(effective) =
+            self.auto_zoom.update(dynamic_bpm_detection_parameters.auto_zoom, bpm, note_density, newest_note)
+        {
+            bpm_detection.update_static_parameters(effective);
+        }
+    }
+
+    /// Estimate of the notes received by `bpm_detection`, for the callers with nothing to report before the zoom
+    pub fn evaluate(
+        &mut self,
+        bpm_detection: &mut BPMDetection,
+        dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters,
+    ) -> Option<BpmEstimate> {
+        let newest_note = bpm_detection.newest_note_timestamp().unwrap_or_else(Duration::zero);
+        let effective_parameters = self.parameter_ramp.apply(dynamic_bpm_detection_parameters, newest_note);
+        let (_, estimate) = bpm_detection.compute_bpm(effective_parameters)?;
+        self.zoom(bpm_detection, dynamic_bpm_detection_parameters, estimate.bpm);
+        Some(estimate)
+    }
+
+    /// The user changed the static parameters, see `AutoZoom::set_configured`
+    pub fn set_configured(&mut self, static_bpm_detection_parameters: &StaticBPMDetectionParameters) {
+        self.auto_zoom.set_configured(static_bpm_detection_parameters);
+    }
+
+    /// Forgets the pending chord and the lock, for notes that don't follow the previous ones
+    pub fn clear(&mut self, bpm_detection: &mut BPMDetection) {
+        self.chord_filter = ChordFilter::default();
+        if let Some(configured) = self.auto_zoom.reset() {
+            bpm_detection.update_static_parameters(configured);
+        }
+    }
+
+    #[must_use]
+    pub fn auto_zoom(&self) -> &AutoZoom {
+        &self.auto_zoom
+    }
+
+    #[must_use]
+    pub fn velocity_gate(&self) -> &VelocityGate {
+        &self.velocity_gate
+    }
+}
+
+fn gate<'a>(
+    velocity_gate: &'a mut VelocityGate,
+    dynamic_bpm_detection_parameters: &'a DynamicBPMDetectionParameters,
+    mut emit: impl FnMut(TimedMidiNoteOn) + 'a,
+) -> impl FnMut(TimedMidiNoteOn) + 'a {
+    move |onset| {
+        if velocity_gate.admit(onset.midi_message.velocity, dynamic_bpm_detection_parameters.auto_velocity_gate) {
+            emit(onset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi_messages::MidiNoteOn;
+
+    fn note(millis: i64, note: u8, velocity: u8) -> TimedMidiNoteOn {
+        TimedMidiNoteOn {
+            timestamp: Duration::milliseconds(millis),
+            midi_message: MidiNoteOn { channel: 0, note, velocity },
+        }
+    }
+
+    // block chords of three tones at 120 BPM, with a ghost note after each
+    fn notes() -> Vec<TimedMidiNoteOn> {
+        (0..16)
+            .flat_map(|beat| {
+                let start = beat * 500;
+                [note(start, 60, 100), note(start + 2, 64, 90), note(start + 4, 67, 95), note(start + 250, 48, 20)]
+            })
+            .collect()
+    }
+
+    // the notes as read from a file, the onsets each one lets through
+    fn onsets(dynamic_bpm_detection_parameters: &DynamicBPMDetectionParameters) -> Vec<(i64, u8)> {
+        let mut pre_detection =
+            PreDetection::new(&StaticBPMDetectionParameters::default(), dynamic_bpm_detection_parameters);
+        let origin = Instant::now();
+        let mut onsets = Vec::new();
+        let mut push =
+            |onset: TimedMidiNoteOn| onsets.push((onset.timestamp.num_milliseconds(), onset.midi_message.note));
+        for note in notes() {
+            let now = origin + note.timestamp.to_std().unwrap();
+            pre_detection.flush(dynamic_bpm_detection_parameters, now, &mut push);
+            pre_detection.note_on(dynamic_bpm_detection_parameters, note, now, &mut push);
+        }
+        pre_detection.finish(dynamic_bpm_detection_parameters, &mut push);
+        onsets
+    }
+
+    #[test]
+    fn test_stages() {
+        assert_eq!(onsets(&DynamicBPMDetectionParameters::default()).len(), 64);
+        let filtered = onsets(&DynamicBPMDetectionParameters {
+            max_simultaneous_onsets: 1,
+            auto_velocity_gate: parameter::OnOff::On(0.5),
+            ..DynamicBPMDetectionParameters::default()
+        });
+        // the loudest tone of each chord, the ghost notes are gated once the velocities are learned
+        assert_eq!(filtered[..2], [(0, 60), (250, 48)]);
+        assert!(filtered[filtered.len() - 4..].iter().all(|(timestamp, note)| timestamp % 500 == 0 && *note == 60));
+    }
+}
@@ -0,0 +1,176 @@
+use chrono::Duration;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+// tokens are counted in millionths so refilling is exact with microsecond timestamps : each elapsed microsecond adds
+// `max_notes_per_second` micro-tokens
+const TOKEN: u64 = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimiterConfig {
+    // 0 disables rate limiting
+    pub max_notes_per_second: u32,
+    pub burst: u32,
+}
+
+impl Default for RateLimiterConfig {
+    fn default() -> Self {
+        Self { max_notes_per_second: 500, burst: 200 }
+    }
+}
+
+/// Diagnostic count of the notes dropped by the rate limiters it was given to, handed explicitly to what displays it.
+/// It is kept out of `RateLimiterConfig`: a configuration deserialized again would count in a copy nothing increments
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitedNotes(Arc<AtomicU64>);
+
+impl RateLimitedNotes {
+    #[must_use]
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Token bucket protecting the pipeline from note-on floods. There is one bucket per source; time is given by the
+/// timestamp of the incoming notes, so no clock is read on the hot path.
+pub struct RateLimiter {
+    refill_per_microsecond: u64,
+    capacity: u64,
+    tokens: AtomicU64,
+    last_refill: AtomicU64,
+    engaged: AtomicBool,
+    rate_limited_notes: RateLimitedNotes,
+}
+
+impl RateLimiter {
+    /// Limiter with the rate of `config`, counting the notes it drops in `rate_limited_notes`
+    #[must_use]
+    pub fn new(config: &RateLimiterConfig, rate_limited_notes: &RateLimitedNotes) -> Self {
+        let capacity = u64::from(config.burst.max(1)) * TOKEN;
+        Self {
+            refill_per_microsecond: u64::from(config.max_notes_per_second),
+            capacity,
+            tokens: AtomicU64::new(capacity),
+            last_refill: AtomicU64::new(0),
+            engaged: AtomicBool::new(false),
+            rate_limited_notes: rate_limited_notes.clone(),
+        }
+    }
+
+    /// Returns whether the note received at `timestamp` may go through. Timestamps are expected to be monotonic,
+    /// one that goes backwards is treated as no time having elapsed.
+    pub fn allow(&self, timestamp: Duration) -> bool {
+        if self.refill_per_microsecond == 0 {
+            return true;
+        }
+
+        let now = timestamp.num_microseconds().unwrap_or(i64::MAX).max(0) as u64;
+        let last_refill = self.last_refill.load(Ordering::Relaxed);
+        let elapsed = now.saturating_sub(last_refill);
+        if elapsed > 0 {
+            self.last_refill.store(now, Ordering::Relaxed);
+        }
+
+        let tokens = self
+            .tokens
+            .load(Ordering::Relaxed)
+            .saturating_add(elapsed.saturating_mul(self.refill_per_microsecond))
+            .min(self.capacity);
+
+        if tokens >= TOKEN {
+            self.tokens.store(tokens - TOKEN, Ordering::Relaxed);
+            self.engaged.store(false, Ordering::Relaxed);
+            true
+        } else {
+            self.tokens.store(tokens, Ordering::Relaxed);
+            self.rate_limited_notes.0.fetch_add(1, Ordering::Relaxed);
+            if !self.engaged.swap(true, Ordering::Relaxed) {
+                let notes_per_second = self.refill_per_microsecond * 1_000_000 / TOKEN;
+                warn!("more than {notes_per_second} notes per second received, dropping notes");
+            }
+            false
+        }
+    }
+
+    #[must_use]
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn rate_limiter(max_notes_per_second: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimiterConfig { max_notes_per_second, burst }, &RateLimitedNotes::default())
+    }
+
+    #[test]
+    fn test_burst_then_refill() {
+        let rate_limiter = rate_limiter(500, 200);
+        let at = Duration::seconds(1);
+
+        assert!((0..200).all(|_| rate_limiter.allow(at)));
+        assert!(!rate_limiter.allow(at));
+        assert!(rate_limiter.is_engaged());
+        assert_eq!(rate_limiter.rate_limited_notes.get(), 1);
+
+        // one note every 2ms at 500 notes per second
+        assert!(!rate_limiter.allow(at + Duration::microseconds(1999)));
+        assert!(rate_limiter.allow(at + Duration::microseconds(2000) + Duration::microseconds(1)));
+        assert!(!rate_limiter.is_engaged());
+
+        // refill never exceeds the burst
+        let later = at + Duration::hours(1);
+        assert!((0..200).all(|_| rate_limiter.allow(later)));
+        assert!(!rate_limiter.allow(later));
+        assert_eq!(rate_limiter.rate_limited_notes.get(), 3);
+    }
+
+    #[test]
+    fn test_timestamp_going_backwards() {
+        let rate_limiter = rate_limiter(10, 1);
+        assert!(rate_limiter.allow(Duration::seconds(10)));
+        assert!(!rate_limiter.allow(Duration::seconds(5)));
+        assert!(!rate_limiter.allow(Duration::seconds(10)));
+        assert!(rate_limiter.allow(Duration::milliseconds(10_100)));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let rate_limiter = rate_limiter(0, 1);
+        assert!((0..10_000).all(|_| rate_limiter.allow(Duration::zero())));
+    }
+
+    #[test]
+    fn test_flood_is_bounded() {
+        let config = RateLimiterConfig::default();
+        let rate_limited_notes = RateLimitedNotes::default();
+        let rate_limiter = RateLimiter::new(&config, &rate_limited_notes);
+        let (sender, receiver) = channel();
+
+        // 20000 notes per second during 10 seconds
+        let seconds = 10;
+        let flood = 200_000;
+        for n in 0..flood {
+            let timestamp = Duration::microseconds(n * seconds * 1_000_000 / flood);
+            if rate_limiter.allow(timestamp) {
+                sender.send(timestamp).unwrap();
+            }
+        }
+        drop(sender);
+
+        let delivered = receiver.iter().count() as u64;
+        let bound = u64::from(config.burst) + u64::from(config.max_notes_per_second) * seconds as u64;
+        assert!(delivered <= bound, "{delivered} > {bound}");
+        assert!(delivered >= bound - 1, "{delivered}");
+        assert_eq!(rate_limited_notes.get(), flood as u64 - delivered);
+    }
+}
@@ -0,0 +1,354 @@
+//! Control of a running plugin instance from the TUI, over a TCP connection. The plugin runs a
+//! `RemoteControlServer`, publishing its estimates and its parameters, the TUI connects with a `RemoteControlClient`
+//! and sends the parameters changed on its side.
+//!
+//! The messages are framed as in `daw_link_protocol`: the length of the payload, as a big-endian u32, followed by the
+//! payload, which is a JSON `RemoteMessage`. Payloads that don't decode, such as the types of newer versions, are
+//! skipped.
+
+use crate::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+use errors::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use sync::Mutex;
+
+const LENGTH_SIZE: usize = 4;
+// a histogram of the widest range at the finest resolution stays well under it
+const MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+// a client that doesn't read for that long is dropped, rather than holding up the plugin
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lets the TUI control the plugin instance, off by default. Only the first instance binding the port gets it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteControlServerConfig {
+    pub enabled: bool,
+    // listened to on localhost
+    pub port: u16,
+}
+
+impl Default for RemoteControlServerConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 7878 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum RemoteMessage {
+    // sent by the server after each evaluation
+    Estimate {
+        bpm: f32,
+        // absent from the messages of older versions, see `BpmEstimate`
+        #[serde(default)]
+        confidence: f32,
+        histogram_data_points: Vec<f32>,
+    },
+    // parameters the server runs with, sent when a client connects and when they change. Sent by a client, they are
+    // applied by the server
+    StaticParameters(StaticBPMDetectionParameters),
+    DynamicParameters(DynamicBPMDetectionParameters),
+}
+
+impl RemoteMessage {
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let payload = match serde_json::to_vec(self) {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!("could not encode {self:?}: {err:?}");
+                return Vec::new();
+            }
+        };
+        let mut frame = Vec::with_capacity(LENGTH_SIZE + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    /// Reads the next frame of `source`, blocking until it is complete. The message is None when its payload doesn't
+    /// decode
+    pub fn read(source: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut length = [0; LENGTH_SIZE];
+        source.read_exact(&mut length)?;
+        let payload_size = u32::from_be_bytes(length) as usize;
+        if payload_size > MAX_PAYLOAD_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("frame of {payload_size} bytes")));
+        }
+        let mut payload = vec![0; payload_size];
+        source.read_exact(&mut payload)?;
+        Ok(serde_json::from_slice(&payload).ok())
+    }
+}
+
+/// Listens to clients on a dedicated thread, each of them read on its own thread. Publishing writes to all of them, a
+/// client failing to receive is dropped
+pub struct RemoteControlServer {
+    local_addr: SocketAddr,
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+    // frames of the last parameters published, for the clients connecting afterwards
+    parameters: Arc<Mutex<[Option<Vec<u8>>; 2]>>,
+    requests: Receiver<RemoteMessage>,
+    closed: Arc<AtomicBool>,
+}
+
+impl RemoteControlServer {
+    /// Listens on localhost, on any free port when `port` is 0
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port))?;
+        let local_addr = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(Vec::<TcpStream>::new()));
+        let parameters = Arc::new(Mutex::new([None::<Vec<u8>>, None]));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (request_sender, requests) = channel();
+
+        thread::Builder::new().name("remote control server".to_string()).spawn({
+            let clients = clients.clone();
+            let parameters = parameters.clone();
+            let closed = closed.clone();
+            move || {
+                for stream in listener.incoming() {
+                    if closed.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let Ok(mut stream) = stream else {
+                        continue;
+                    };
+                    let Ok(mut reader) = stream.try_clone() else {
+                        continue;
+                    };
+                    stream.set_nodelay(true).ok();
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok();
+                    info!("remote control client connected from {:?}", stream.peer_addr());
+
+                    // held while the parameters are sent, so nothing published in between comes before them
+                    let mut clients = clients.lock();
+                    let sent = parameters.lock().iter().flatten().all(|frame| stream.write_all(frame).is_ok());
+                    if !sent {
+                        continue;
+                    }
+                    clients.push(stream);
+                    drop(clients);
+
+                    let request_sender = request_sender.clone();
+                    let spawned = thread::Builder::new().name("remote control client".to_string()).spawn(move || {
+                        while let Ok(message) = RemoteMessage::read(&mut reader) {
+                            if let Some(message) = message {
+                                if request_sender.send(message).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                    if let Err(err) = spawned {
+                        error!("could not start remote control client thread: {err:?}");
+                    }
+                }
+            }
+        })?;
+
+        info!("remote control server listening on {local_addr}");
+        Ok(Self { local_addr, clients, parameters, requests, closed })
+    }
+
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Sends `message` to every client. Parameters are kept for the clients connecting later on
+    pub fn publish(&self, message: &RemoteMessage) {
+        let frame = message.encode();
+        let mut clients = self.clients.lock();
+        match message {
+            RemoteMessage::StaticParameters(_) => self.parameters.lock()[0] = Some(frame.clone()),
+            RemoteMessage::DynamicParameters(_) => self.parameters.lock()[1] = Some(frame.clone()),
+            RemoteMessage::Estimate { .. } => {}
+        }
+        clients.retain_mut(|client| match client.write_all(&frame) {
+            Ok(()) => true,
+            Err(err) => {
+                info!("remote control client dropped: {err:?}");
+                client.shutdown(Shutdown::Both).ok();
+                false
+            }
+        });
+    }
+
+    /// Messages received from the clients since the last call
+    pub fn requests(&self) -> impl Iterator<Item = RemoteMessage> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+impl Drop for RemoteControlServer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // wakes the listening thread up, so it sees it is closed
+        TcpStream::connect_timeout(&self.local_addr, CONNECT_TIMEOUT).ok();
+        for client in self.clients.lock().drain(..) {
+            client.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
+
+/// Connection to a `RemoteControlServer`, its messages are handed to a callback on a dedicated thread, which ends with
+/// the connection
+pub struct RemoteControlClient {
+    stream: TcpStream,
+}
+
+impl RemoteControlClient {
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        mut on_message: impl FnMut(RemoteMessage) + Send + 'static,
+    ) -> io::Result<Self> {
+        let mut last_err = io::Error::new(ErrorKind::InvalidInput, "no address to connect to");
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_nodelay(true).ok();
+                    let mut reader = stream.try_clone()?;
+                    thread::Builder::new().name("remote control client".to_string()).spawn(move || loop {
+                        match RemoteMessage::read(&mut reader) {
+                            Ok(Some(message)) => on_message(message),
+                            Ok(None) => {}
+                            Err(err) => {
+                                info!("remote control connection closed: {err:?}");
+                                return;
+                            }
+                        }
+                    })?;
+                    info!("connected to the remote control server at {address}");
+                    return Ok(Self { stream });
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    pub fn send(&mut self, message: &RemoteMessage) -> io::Result<()> {
+        self.stream.write_all(&message.encode())
+    }
+}
+
+impl Drop for RemoteControlClient {
+    fn drop(&mut self) {
+        self.stream.shutdown(Shutdown::Both).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::mpsc::{Receiver, RecvTimeoutError},
+        time::Instant,
+    };
+
+    fn connect(server: &RemoteControlServer) -> (RemoteControlClient, Receiver<RemoteMessage>) {
+        let (sender, receiver) = channel();
+        let client = RemoteControlClient::connect(server.local_addr(), move |message| {
+            sender.send(message).ok();
+        })
+        .unwrap();
+        (client, receiver)
+    }
+
+    fn receive(receiver: &Receiver<RemoteMessage>) -> RemoteMessage {
+        match receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => panic!("nothing received"),
+        }
+    }
+
+    fn wait_for_request(server: &RemoteControlServer) -> RemoteMessage {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(request) = server.requests().next() {
+                return request;
+            }
+            assert!(Instant::now() < deadline, "nothing requested");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        let message =
+            RemoteMessage::Estimate { bpm: 120.5, confidence: 0.75, histogram_data_points: vec![0.0, 1.0, 0.5] };
+        let mut frames = message.encode();
+        // skipped, as a message of a newer version
+        let unknown = br#"{"type":"Unknown"}"#;
+        frames.extend_from_slice(&(unknown.len() as u32).to_be_bytes());
+        frames.extend_from_slice(unknown);
+        frames.extend_from_slice(&RemoteMessage::DynamicParameters(DynamicBPMDetectionParameters::default()).encode());
+
+        let mut source = frames.as_slice();
+        assert_eq!(RemoteMessage::read(&mut source).unwrap(), Some(message));
+        assert_eq!(RemoteMessage::read(&mut source).unwrap(), None);
+        assert_eq!(
+            RemoteMessage::read(&mut source).unwrap(),
+            Some(RemoteMessage::DynamicParameters(DynamicBPMDetectionParameters::default()))
+        );
+        assert_eq!(RemoteMessage::read(&mut source).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        // an estimate of an older version, without confidence
+        let older = br#"{"type":"Estimate","bpm":120.5,"histogram_data_points":[]}"#;
+        let mut frames = (older.len() as u32).to_be_bytes().to_vec();
+        frames.extend_from_slice(older);
+        assert_eq!(
+            RemoteMessage::read(&mut frames.as_slice()).unwrap(),
+            Some(RemoteMessage::Estimate { bpm: 120.5, confidence: 0.0, histogram_data_points: vec![] })
+        );
+
+        let mut too_long = &u32::MAX.to_be_bytes()[..];
+        assert_eq!(RemoteMessage::read(&mut too_long).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_loopback() {
+        let server = RemoteControlServer::bind(0).unwrap();
+        let static_parameters = StaticBPMDetectionParameters { bpm_center: 100.0, ..Default::default() };
+        server.publish(&RemoteMessage::StaticParameters(static_parameters.clone()));
+
+        // the parameters are sent on connection, the estimates as they come
+        let (mut client, received) = connect(&server);
+        assert_eq!(receive(&received), RemoteMessage::StaticParameters(static_parameters));
+        let estimate = RemoteMessage::Estimate { bpm: 101.5, confidence: 0.5, histogram_data_points: vec![0.25; 1000] };
+        server.publish(&estimate);
+        assert_eq!(receive(&received), estimate);
+
+        // round-trip of a parameter change, applied and published back by the server
+        let changed = StaticBPMDetectionParameters { bpm_center: 140.0, ..Default::default() };
+        client.send(&RemoteMessage::StaticParameters(changed.clone())).unwrap();
+        let request = wait_for_request(&server);
+        assert_eq!(request, RemoteMessage::StaticParameters(changed));
+        server.publish(&request);
+        assert_eq!(receive(&received), request);
+
+        // a client gone is dropped, the others keep receiving
+        let (_other_client, other_received) = connect(&server);
+        assert_eq!(receive(&other_received), request);
+        drop(client);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.clients.lock().len() > 1 {
+            assert!(Instant::now() < deadline, "client was not dropped");
+            server.publish(&estimate);
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(receive(&other_received), estimate);
+    }
+}
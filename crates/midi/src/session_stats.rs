@@ -0,0 +1,287 @@
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
+pub struct SessionStatsConfig {
+    // estimates of the first seconds of the session, and of the first seconds after a gap, are left out while the
+    // detection settles
+    pub warmup: Duration,
+    // longer than that without an estimate is a gap, left out of the session duration
+    pub max_gap: Duration,
+    // points taken off the score per percent of standard deviation of the estimate
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub scale: f32,
+    // a deliberate tempo change starts a new segment, the deviation is then measured within each segment
+    pub segment_on_tempo_change: bool,
+    // in percent, a change smaller than that is a wobble
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub tempo_change: f32,
+    // a change is deliberate once the tempo stays around its new value that long
+    pub settle: Duration,
+}
+
+impl Default for SessionStatsConfig {
+    fn default() -> Self {
+        Self {
+            warmup: Duration::from_secs(8),
+            max_gap: Duration::from_secs(4),
+            scale: 10.0,
+            segment_on_tempo_change: true,
+            tempo_change: 8.0,
+            settle: Duration::from_secs(4),
+        }
+    }
+}
+
+/// Count, mean and sum of squared deviations of values, merged without keeping the values
+#[derive(Clone, Copy, Debug, Default)]
+struct Moments {
+    count: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn push(&mut self, value: f64) {
+        self.merge(Moments { count: 1, mean: value, m2: 0.0 });
+    }
+
+    fn merge(&mut self, other: Moments) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * (self.count * other.count) as f64 / count as f64;
+        self.count = count;
+    }
+}
+
+/// What a session looked like so far, see `SessionStats`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SessionSummary {
+    // none until two estimates were counted
+    pub stability: Option<f32>,
+    // time played, gaps left out
+    pub duration: Duration,
+    pub notes: usize,
+}
+
+impl Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.duration.as_secs();
+        write!(f, "session: {}:{:02}, {} notes, stability: ", seconds / 60, seconds % 60, self.notes)?;
+        match self.stability {
+            Some(stability) => write!(f, "{stability:.0}"),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+/// Stability of the detected tempo over a practice session, updated on every evaluation.
+///
+/// The score is `100 - scale * deviation`, bounded to 0..=100, where the deviation is the standard deviation of the
+/// estimates in percent of the tempo, measured on the natural logarithm of the estimates so it doesn't depend on the
+/// tempo. The estimates of the first `warmup` of the session and after each gap are left out. When segmenting on tempo
+/// changes, an estimate further than `tempo_change` from the current segment starts a candidate segment, which
+/// replaces the current one once the estimates stay within `tempo_change` of it for `settle`. The deviation is then
+/// pooled over the segments, each measured from its own mean, so a deliberate change costs no more than the estimates
+/// of its transition. A candidate that doesn't settle was a wobble and counts in the current segment
+#[derive(Clone, Debug)]
+pub struct SessionStats {
+    config: SessionStatsConfig,
+    notes: usize,
+    duration: Duration,
+    stretch_start: Duration,
+    last_estimate: Option<Duration>,
+    closed_count: usize,
+    closed_m2: f64,
+    segment: Moments,
+    candidate: Moments,
+    candidate_since: Duration,
+}
+
+impl SessionStats {
+    #[must_use]
+    pub fn new(config: &SessionStatsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            notes: 0,
+            duration: Duration::ZERO,
+            stretch_start: Duration::ZERO,
+            last_estimate: None,
+            closed_count: 0,
+            closed_m2: 0.0,
+            segment: Moments::default(),
+            candidate: Moments::default(),
+            candidate_since: Duration::ZERO,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(&self.config);
+    }
+
+    pub fn note(&mut self) {
+        self.notes += 1;
+    }
+
+    /// Counts the tempo estimated at `at`, the time of the newest note it was estimated from. An estimate at the time
+    /// of the previous one evaluates the same notes again after a change of parameters, and is left out
+    pub fn estimate(&mut self, bpm: f32, at: Duration) {
+        if !bpm.is_finite() || bpm <= 0.0 || self.last_estimate == Some(at) {
+            return;
+        }
+        // the timestamps start over with another device, as after a gap
+        match self.last_estimate.and_then(|last_estimate| at.checked_sub(last_estimate)) {
+            Some(elapsed) if elapsed <= self.config.max_gap => self.duration += elapsed,
+            _ => self.stretch_start = at,
+        }
+        self.last_estimate = Some(at);
+        if at.saturating_sub(self.stretch_start) < self.config.warmup {
+            return;
+        }
+
+        let value = 100.0 * f64::from(bpm).ln();
+        if !self.config.segment_on_tempo_change || self.segment.count == 0 {
+            self.segment.push(value);
+            return;
+        }
+        let threshold = 100.0 * (1.0 + f64::from(self.config.tempo_change) / 100.0).ln();
+        if self.candidate.count > 0 {
+            if (value - self.candidate.mean).abs() <= threshold {
+                self.candidate.push(value);
+                if at.saturating_sub(self.candidate_since) >= self.config.settle {
+                    self.closed_count += self.segment.count;
+                    self.closed_m2 += self.segment.m2;
+                    self.segment = std::mem::take(&mut self.candidate);
+                }
+                return;
+            }
+            self.segment.merge(std::mem::take(&mut self.candidate));
+        }
+        if (value - self.segment.mean).abs() > threshold {
+            self.candidate.push(value);
+            self.candidate_since = at;
+        } else {
+            self.segment.push(value);
+        }
+    }
+
+    /// Score from 0 to 100, none until two estimates were counted
+    #[must_use]
+    pub fn stability(&self) -> Option<f32> {
+        let mut segment = self.segment;
+        segment.merge(self.candidate);
+        let count = self.closed_count + segment.count;
+        if count < 2 {
+            return None;
+        }
+        let deviation = ((self.closed_m2 + segment.m2) / count as f64).sqrt();
+        Some((100.0 - f64::from(self.config.scale) * deviation).clamp(0.0, 100.0) as f32)
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary { stability: self.stability(), duration: self.duration, notes: self.notes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    // one estimate every `INTERVAL` from the start of the session
+    fn score(config: &SessionStatsConfig, trace: impl IntoIterator<Item = f32>) -> SessionStats {
+        let mut session_stats = SessionStats::new(config);
+        for (index, bpm) in trace.into_iter().enumerate() {
+            session_stats.estimate(bpm, INTERVAL * index as u32);
+        }
+        session_stats
+    }
+
+    fn wobble(amplitude: f32) -> impl Iterator<Item = f32> {
+        (0..400).map(move |index| 120.0 * (1.0 + amplitude * (index as f32 * 2.3).sin()))
+    }
+
+    #[test]
+    fn test_constant() {
+        let stability = score(&SessionStatsConfig::default(), [120.0; 100]).stability().unwrap();
+        assert!((stability - 100.0).abs() < 1e-3, "{stability}");
+    }
+
+    #[test]
+    fn test_wobble() {
+        let config = SessionStatsConfig::default();
+        let slight = score(&config, wobble(0.01)).stability().unwrap();
+        let heavy = score(&config, wobble(0.1)).stability().unwrap();
+        assert!(slight > 85.0, "{slight}");
+        assert!(heavy < 50.0, "{heavy}");
+        // the wobble doesn't pass for tempo changes
+        let unsegmented = score(&SessionStatsConfig { segment_on_tempo_change: false, ..config }, wobble(0.1));
+        assert!((unsegmented.stability().unwrap() - heavy).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        let trace = || [100.0; 200].into_iter().chain([120.0; 200]);
+        let segmented = score(&SessionStatsConfig::default(), trace()).stability().unwrap();
+        assert!(segmented > 95.0, "{segmented}");
+        let unsegmented =
+            score(&SessionStatsConfig { segment_on_tempo_change: false, ..SessionStatsConfig::default() }, trace());
+        assert!(unsegmented.stability().unwrap() < 20.0, "{:?}", unsegmented.stability());
+
+        // a short excursion is a wobble
+        let excursion = [100.0; 100].into_iter().chain([120.0; 4]).chain([100.0; 100]);
+        let excursion = score(&SessionStatsConfig::default(), excursion).stability().unwrap();
+        assert!(excursion < segmented, "{excursion}");
+    }
+
+    #[test]
+    fn test_warmup_and_gaps() {
+        let config = SessionStatsConfig::default();
+        let mut session_stats = SessionStats::new(&config);
+        // the detection settling
+        session_stats.estimate(60.0, Duration::ZERO);
+        session_stats.estimate(90.0, Duration::from_secs(4));
+        assert_eq!(session_stats.stability(), None);
+        for second in 8..=20 {
+            session_stats.estimate(120.0, Duration::from_secs(second));
+            session_stats.note();
+        }
+        // a re-evaluation of the same notes
+        session_stats.estimate(60.0, Duration::from_secs(20));
+        // after a pause the detection settles again
+        session_stats.estimate(60.0, Duration::from_secs(50));
+        session_stats.estimate(90.0, Duration::from_secs(54));
+        session_stats.estimate(120.0, Duration::from_secs(58));
+        let summary = session_stats.summary();
+        assert!((summary.stability.unwrap() - 100.0).abs() < 1e-3, "{summary:?}");
+        assert_eq!(summary.duration, Duration::from_secs(28));
+        assert_eq!(summary.notes, 13);
+        assert_eq!(summary.to_string(), "session: 0:28, 13 notes, stability: 100");
+
+        // timestamps starting over
+        session_stats.estimate(60.0, Duration::from_secs(1));
+        session_stats.estimate(90.0, Duration::from_secs(5));
+        session_stats.estimate(120.0, Duration::from_secs(9));
+        assert_eq!(session_stats.summary().duration, Duration::from_secs(36));
+        assert!((session_stats.stability().unwrap() - 100.0).abs() < 1e-3);
+
+        session_stats.reset();
+        assert_eq!(session_stats.summary(), SessionSummary::default());
+    }
+}
@@ -6,6 +6,7 @@ pub enum SysExCommand {
     Tempo(f32),
     Play,
     Stop,
+    Continue,
 }
 
 impl TryFrom<&StaticMidiMessage> for SysExCommand {
@@ -19,6 +20,7 @@ impl TryFrom<&StaticMidiMessage> for SysExCommand {
             return Ok(match (parts.next(), parts.next()) {
                 (Some("PLAY"), None) => Self::Play, // TODO - search for PLAY STOP , use this instead. also lookup again
                 (Some("STOP"), None) => Self::Stop,
+                (Some("CONTINUE"), None) => Self::Continue,
                 (Some("TEMPO"), Some(rpm)) => {
                     let rpm = f32::from_str(rpm).or(Err(()))?;
                     Self::Tempo(rpm)
@@ -0,0 +1,129 @@
+use std::{collections::VecDeque, time::Duration as StdDuration};
+
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::StaticMidiMessage;
+
+// a longer pause starts a new sequence of taps
+const TAP_TIMEOUT: StdDuration = StdDuration::from_secs(3);
+// intervals the tapped tempo is computed from, the older ones are forgotten
+const MAX_INTERVALS: usize = 8;
+
+/// Note the user taps the tempo with. It only counts as a tap, it is not fed to the detection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TapTrigger {
+    // channel index, 0 for the first channel
+    pub channel: u8,
+    pub note: u8,
+}
+
+impl TapTrigger {
+    /// Whether the message is a note-on or a note-off of the trigger
+    #[must_use]
+    pub fn matches(&self, midi_message: &StaticMidiMessage) -> bool {
+        match midi_message {
+            StaticMidiMessage::NoteOn(channel, note, _) | StaticMidiMessage::NoteOff(channel, note, _) => {
+                channel.index() == self.channel && *note as u8 == self.note
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Tempo tapped by the user, from the median of the last intervals between taps
+#[derive(Clone, Debug, Default)]
+pub struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    /// Records a tap, returns the tapped tempo from the second tap of a sequence on
+    pub fn tap(&mut self, at: Instant) -> Option<f32> {
+        if self.taps.back().is_some_and(|last| at.saturating_duration_since(*last) > TAP_TIMEOUT) {
+            self.taps.clear();
+        }
+        self.taps.push_back(at);
+        if self.taps.len() > MAX_INTERVALS + 1 {
+            self.taps.pop_front();
+        }
+        self.bpm()
+    }
+
+    /// Whether the last tap started a new sequence, on the downbeat
+    #[must_use]
+    pub fn is_first_tap(&self) -> bool {
+        self.taps.len() == 1
+    }
+
+    fn bpm(&self) -> Option<f32> {
+        let mut intervals = self
+            .taps
+            .iter()
+            .zip(self.taps.iter().skip(1))
+            .map(|(previous, next)| next.saturating_duration_since(*previous).as_secs_f32())
+            .collect::<Vec<_>>();
+        if intervals.is_empty() {
+            return None;
+        }
+        intervals.sort_unstable_by(f32::total_cmp);
+        let middle = intervals.len() / 2;
+        let median = if intervals.len().is_multiple_of(2) {
+            f32::midpoint(intervals[middle - 1], intervals[middle])
+        } else {
+            intervals[middle]
+        };
+        (median > 0.0).then(|| 60.0 / median)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tempo tapped after each tap, at the given milliseconds
+    fn tapped(tap_tempo: &mut TapTempo, start: Instant, taps_ms: &[u64]) -> Vec<Option<f32>> {
+        taps_ms
+            .iter()
+            .map(|tap_ms| tap_tempo.tap(start + StdDuration::from_millis(*tap_ms)))
+            .map(|bpm| bpm.map(|bpm| (bpm * 10.0).round() / 10.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_two_taps() {
+        let mut tap_tempo = TapTempo::default();
+        assert_eq!(tapped(&mut tap_tempo, Instant::now(), &[0, 500]), [None, Some(120.0)]);
+    }
+
+    #[test]
+    fn test_irregular_taps() {
+        let mut tap_tempo = TapTempo::default();
+        let start = Instant::now();
+        // 600ms apart, one late and one early tap don't move the median
+        let bpms = tapped(&mut tap_tempo, start, &[0, 600, 1250, 1800, 2400, 3000]);
+        assert_eq!(bpms[5], Some(100.0));
+        // intervals of 600 and 650ms
+        assert_eq!(bpms[2], Some(96.0));
+        assert!(!tap_tempo.is_first_tap());
+
+        // a pause starts over
+        assert_eq!(tapped(&mut tap_tempo, start, &[6100, 6600]), [None, Some(120.0)]);
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let mut tap_tempo = TapTempo::default();
+        let start = Instant::now();
+        // 9 taps at 100 BPM, then the taps speed up to 120 BPM
+        let taps = (0..9).map(|tap| tap * 600).chain((1..=10).map(|tap| 4800 + tap * 500)).collect::<Vec<_>>();
+        let bpms = tapped(&mut tap_tempo, start, &taps);
+        assert_eq!(bpms[8], Some(100.0));
+        // half the intervals are the new ones
+        assert_eq!(bpms[12], Some(109.1));
+        // only the last 8 intervals count
+        assert_eq!(bpms[16], Some(120.0));
+        assert_eq!(tap_tempo.taps.len(), MAX_INTERVALS + 1);
+    }
+}
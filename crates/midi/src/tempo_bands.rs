@@ -0,0 +1,210 @@
+//! MIDI sent when the tempo moves from one configured range to another, such as a program change switching the preset
+//! of a delay pedal. The tempo has to go past a boundary of the active band by a margin before it counts as left, so
+//! an estimate wavering around a boundary doesn't send the actions of both bands in turn
+
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// What is sent on the MIDI output when the tempo enters a band. The channel starts at 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TempoBandAction {
+    ProgramChange { channel: u8, program: u8 },
+    // a note-on immediately followed by its note-off
+    Note { channel: u8, note: u8, velocity: u8 },
+}
+
+/// Range of tempos, from `min_bpm` included to `max_bpm` excluded, and the action sent when the tempo enters it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq, Eq)]
+pub struct TempoBand {
+    #[serde(default)]
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub min_bpm: f32,
+    // no upper bound when left out
+    #[serde(default = "TempoBand::unbounded")]
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub max_bpm: f32,
+    pub action: TempoBandAction,
+}
+
+impl TempoBand {
+    fn unbounded() -> f32 {
+        f32::INFINITY
+    }
+
+    #[must_use]
+    pub fn contains(&self, bpm: f32) -> bool {
+        bpm >= self.min_bpm && bpm < self.max_bpm
+    }
+
+    // whether `bpm` is far enough out of the band to leave it
+    fn left(&self, bpm: f32, hysteresis: f32) -> bool {
+        bpm < self.min_bpm - hysteresis || bpm >= self.max_bpm + hysteresis
+    }
+}
+
+impl Display for TempoBand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.min_bpm > 0.0, self.max_bpm.is_finite()) {
+            (false, false) => f.write_str("any tempo")?,
+            (false, true) => write!(f, "below {:.0} BPM", self.max_bpm)?,
+            (true, false) => write!(f, "from {:.0} BPM", self.min_bpm)?,
+            (true, true) => write!(f, "{:.0}-{:.0} BPM", self.min_bpm, self.max_bpm)?,
+        }
+        match self.action {
+            TempoBandAction::ProgramChange { channel, program } => {
+                write!(f, " · program {program} on channel {}", u16::from(channel) + 1)
+            }
+            TempoBandAction::Note { channel, note, .. } => {
+                write!(f, " · note {note} on channel {}", u16::from(channel) + 1)
+            }
+        }
+    }
+}
+
+/// Band the tempo is in, following the estimates
+#[derive(Clone, Debug, Default)]
+pub struct TempoBands {
+    bands: Vec<TempoBand>,
+    // in BPM, how far out of the active band the tempo has to go to leave it
+    hysteresis: f32,
+    active: Option<usize>,
+}
+
+impl TempoBands {
+    #[must_use]
+    pub fn new(bands: &[TempoBand], hysteresis: f32) -> Self {
+        Self { bands: bands.to_vec(), hysteresis: hysteresis.max(0.0), active: None }
+    }
+
+    /// The band entered at `bpm`, none while the tempo stays within the active band and its margin, or is in no band.
+    /// When the bands overlap the first one listed is entered
+    pub fn update(&mut self, bpm: f32) -> Option<TempoBand> {
+        if !bpm.is_finite() {
+            return None;
+        }
+        if let Some(active) = self.active {
+            if !self.bands[active].left(bpm, self.hysteresis) {
+                return None;
+            }
+        }
+        self.active = self.bands.iter().position(|band| band.contains(bpm));
+        self.active.map(|active| self.bands[active])
+    }
+
+    #[must_use]
+    pub fn active(&self) -> Option<TempoBand> {
+        self.active.map(|active| self.bands[active])
+    }
+
+    /// Forgets the active band, the next estimate enters its band again
+    pub fn reset(&mut self) {
+        self.active = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program_change(program: u8) -> TempoBandAction {
+        TempoBandAction::ProgramChange { channel: 0, program }
+    }
+
+    // below 90, 90 to 120 and above 120 BPM
+    fn tempo_bands(hysteresis: f32) -> TempoBands {
+        TempoBands::new(
+            &[
+                TempoBand { min_bpm: 0.0, max_bpm: 90.0, action: program_change(1) },
+                TempoBand { min_bpm: 90.0, max_bpm: 120.0, action: program_change(2) },
+                TempoBand { min_bpm: 120.0, max_bpm: f32::INFINITY, action: program_change(3) },
+            ],
+            hysteresis,
+        )
+    }
+
+    // the actions sent over a trace of estimates
+    fn sent(tempo_bands: &mut TempoBands, trace: impl IntoIterator<Item = f32>) -> Vec<TempoBandAction> {
+        trace.into_iter().filter_map(|bpm| tempo_bands.update(bpm)).map(|band| band.action).collect()
+    }
+
+    #[test]
+    fn test_slow_crossing() {
+        let mut tempo_bands = tempo_bands(2.0);
+        // from 80 to 130 BPM and back, by 0.5 BPM per estimate
+        let up = (0..=100).map(|step| 80.0 + step as f32 * 0.5);
+        assert_eq!(sent(&mut tempo_bands, up), [program_change(1), program_change(2), program_change(3)]);
+        assert_eq!(tempo_bands.active().map(|band| band.action), Some(program_change(3)));
+        let down = (0..=100).map(|step| 130.0 - step as f32 * 0.5);
+        assert_eq!(sent(&mut tempo_bands, down), [program_change(2), program_change(1)]);
+    }
+
+    #[test]
+    fn test_crossing_after_the_margin() {
+        let mut tempo_bands = tempo_bands(2.0);
+        assert_eq!(sent(&mut tempo_bands, [100.0, 120.0, 121.9]), [program_change(2)]);
+        assert_eq!(sent(&mut tempo_bands, [122.0]), [program_change(3)]);
+        assert!(sent(&mut tempo_bands, [118.1]).is_empty());
+        assert_eq!(sent(&mut tempo_bands, [117.9]), [program_change(2)]);
+    }
+
+    #[test]
+    fn test_flapping_at_a_boundary() {
+        let mut tempo_bands = tempo_bands(2.0);
+        assert_eq!(sent(&mut tempo_bands, [100.0]), [program_change(2)]);
+        // an estimate wavering by 1.5 BPM around 120 stays in the band it started in
+        let wavering = (0..200).map(|step| if step % 2 == 0 { 118.5 } else { 121.5 });
+        assert!(sent(&mut tempo_bands, wavering).is_empty());
+
+        // without a margin every crossing sends
+        let mut tempo_bands = self::tempo_bands(0.0);
+        assert_eq!(sent(&mut tempo_bands, [100.0]), [program_change(2)]);
+        let wavering = (0..10).map(|step| if step % 2 == 0 { 121.5 } else { 118.5 });
+        assert_eq!(sent(&mut tempo_bands, wavering).len(), 10);
+    }
+
+    #[test]
+    fn test_rapid_crossing() {
+        let mut tempo_bands = tempo_bands(2.0);
+        // a jump over the middle band only sends the action of the one the tempo lands in
+        assert_eq!(
+            sent(&mut tempo_bands, [80.0, 140.0, 85.0]),
+            [program_change(1), program_change(3), program_change(1)]
+        );
+        assert!(sent(&mut tempo_bands, [f32::NAN, 85.0]).is_empty());
+    }
+
+    #[test]
+    fn test_gap_between_bands() {
+        let note = TempoBandAction::Note { channel: 9, note: 36, velocity: 100 };
+        let mut tempo_bands = TempoBands::new(
+            &[
+                TempoBand { min_bpm: 60.0, max_bpm: 80.0, action: program_change(1) },
+                TempoBand { min_bpm: 100.0, max_bpm: 140.0, action: note },
+            ],
+            2.0,
+        );
+        assert_eq!(sent(&mut tempo_bands, [50.0, 70.0, 81.0]), [program_change(1)]);
+        assert!(sent(&mut tempo_bands, [90.0]).is_empty());
+        assert_eq!(tempo_bands.active(), None);
+        // entering a band is immediate, only leaving it has a margin
+        assert_eq!(sent(&mut tempo_bands, [100.0]), [note]);
+        tempo_bands.reset();
+        assert_eq!(sent(&mut tempo_bands, [101.0]), [note]);
+    }
+
+    #[test]
+    fn test_display() {
+        let tempo_bands = tempo_bands(2.0);
+        let names = tempo_bands.bands.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "below 90 BPM · program 1 on channel 1",
+                "90-120 BPM · program 2 on channel 1",
+                "from 120 BPM · program 3 on channel 1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Tempo changes found in the series of estimates, from a threshold on the slope of the estimated tempo. The notes
+//! played before the last change keep weighing on the histogram until they leave the lookback window, the share of
+//! the ones played since tells how far the estimate is from fully reflecting the recent playing
+
+use chrono::Duration;
+
+// in BPM per second of notes, a steeper estimate is a tempo change
+const SLOPE_THRESHOLD: f32 = 2.0;
+// in BPM, smaller steps are the estimate wavering between neighbouring bins
+const MIN_STEP: f32 = 2.0;
+
+/// Last tempo change of the estimates, timed as the notes are
+#[derive(Clone, Debug, Default)]
+pub struct TempoChangeDetector {
+    // time of the newest note and tempo of the previous estimate
+    previous: Option<(Duration, f32)>,
+    change_point: Option<Duration>,
+}
+
+impl TempoChangeDetector {
+    /// Estimate of the notes up to `at`. Returns whether it departs from the previous one steeply enough to be a tempo
+    /// change, which then happened after the notes of the previous estimate
+    pub fn estimate(&mut self, at: Duration, bpm: f32) -> bool {
+        let Some((previous_at, previous_bpm)) = self.previous.replace((at, bpm)) else {
+            return false;
+        };
+        let step = (bpm - previous_bpm).abs();
+        // a change of parameters evaluates the same notes again, it doesn't tell anything about the playing
+        let Some(elapsed) = (at - previous_at).to_std().ok().filter(|elapsed| !elapsed.is_zero()) else {
+            return false;
+        };
+        if step < MIN_STEP || step / elapsed.as_secs_f32() < SLOPE_THRESHOLD {
+            return false;
+        }
+        self.change_point = Some(previous_at);
+        true
+    }
+
+    /// Notes played after it reflect the current tempo, none when no change was found since the notes were cleared
+    #[must_use]
+    pub fn change_point(&self) -> Option<Duration> {
+        self.change_point
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one estimate per beat at the given tempos, with the time of its beat
+    fn trace(tempos: &[f32]) -> impl Iterator<Item = (Duration, f32)> + '_ {
+        tempos.iter().scan(Duration::zero(), |at, &bpm| {
+            *at += Duration::microseconds((60_000_000.0 / bpm) as i64);
+            Some((*at, bpm))
+        })
+    }
+
+    fn change_points(tempo_change_detector: &mut TempoChangeDetector, tempos: &[f32]) -> Vec<Duration> {
+        let mut change_points = Vec::new();
+        for (at, bpm) in trace(tempos) {
+            if tempo_change_detector.estimate(at, bpm) {
+                change_points.extend(tempo_change_detector.change_point());
+            }
+        }
+        change_points
+    }
+
+    #[test]
+    fn test_steady_tempo() {
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        // wavering between neighbouring bins
+        let tempos = [120.0, 120.5, 119.8, 120.2, 121.0, 119.5, 120.0, 120.3];
+        assert!(change_points(&mut tempo_change_detector, &tempos).is_empty());
+        assert_eq!(tempo_change_detector.change_point(), None);
+    }
+
+    #[test]
+    fn test_tempo_step() {
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        let tempos = [100.0, 100.0, 100.5, 100.0, 130.0, 130.0, 129.5, 130.0];
+        let estimates = trace(&tempos).collect::<Vec<_>>();
+        // after the notes of the last estimate at 100 BPM
+        assert_eq!(change_points(&mut tempo_change_detector, &tempos), vec![estimates[3].0]);
+        assert_eq!(tempo_change_detector.change_point(), Some(estimates[3].0));
+
+        tempo_change_detector.clear();
+        assert_eq!(tempo_change_detector.change_point(), None);
+    }
+
+    #[test]
+    fn test_slow_drift() {
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        // a bit more than half a BPM per second, a tempo the estimate follows as it goes
+        let tempos = (0..40).map(|beat| 100.0 + beat as f32 * 0.4).collect::<Vec<_>>();
+        assert!(change_points(&mut tempo_change_detector, &tempos).is_empty());
+
+        // nor is a step spread over a long stretch without estimates
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        assert!(!tempo_change_detector.estimate(Duration::zero(), 100.0));
+        assert!(!tempo_change_detector.estimate(Duration::seconds(10), 104.0));
+
+        // but the same change at once is one
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        assert_eq!(change_points(&mut tempo_change_detector, &[100.0, 100.0, 116.0, 116.0]).len(), 1);
+    }
+
+    #[test]
+    fn test_same_notes_evaluated_again() {
+        let mut tempo_change_detector = TempoChangeDetector::default();
+        let at = Duration::seconds(10);
+        assert!(!tempo_change_detector.estimate(at, 100.0));
+        // other parameters, same notes
+        assert!(!tempo_change_detector.estimate(at, 140.0));
+        assert_eq!(tempo_change_detector.change_point(), None);
+        // from the tempo of the last estimate
+        assert!(!tempo_change_detector.estimate(at + Duration::milliseconds(430), 140.0));
+    }
+}
@@ -0,0 +1,262 @@
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Duration};
+
+// largest value a set-tempo meta event can hold
+const MAX_MICROSECONDS_PER_QUARTER: u32 = 0x00FF_FFFF;
+// hard cap whatever the configuration says, a point is recorded at most once per evaluation
+const MAX_TEMPO_POINTS: usize = 100_000;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(PartialEq, Eq)]
+#[serde(default)]
+pub struct TempoMapConfig {
+    // oldest estimates are dropped beyond that
+    pub max_points: usize,
+    // tempo changes smaller than that, in BPM, are not recorded
+    #[derivative(PartialEq(compare_with = "f32::eq"))]
+    pub hysteresis: f32,
+    pub ppqn: u16,
+}
+
+impl Default for TempoMapConfig {
+    fn default() -> Self {
+        Self { max_points: 10_000, hysteresis: 0.5, ppqn: 480 }
+    }
+}
+
+/// Tempo estimates of a session, relative to its start
+#[derive(Clone, Debug)]
+pub struct TempoCurve {
+    points: VecDeque<(Duration, f32)>,
+    max_points: usize,
+    hysteresis: f32,
+}
+
+impl TempoCurve {
+    #[must_use]
+    pub fn new(config: &TempoMapConfig) -> Self {
+        Self {
+            points: VecDeque::new(),
+            max_points: config.max_points.clamp(1, MAX_TEMPO_POINTS),
+            hysteresis: config.hysteresis,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: Duration, bpm: f32) {
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return;
+        }
+        if let Some((last_timestamp, last_bpm)) = self.points.back() {
+            if timestamp < *last_timestamp || (bpm - last_bpm).abs() < self.hysteresis {
+                return;
+            }
+        }
+        if self.points.len() == self.max_points {
+            self.points.pop_front();
+        }
+        self.points.push_back((timestamp, bpm));
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = &(Duration, f32)> {
+        self.points.iter()
+    }
+
+    #[must_use]
+    pub fn to_smf(&self, ppqn: u16) -> Vec<u8> {
+        write_smf(self.points.iter().copied(), ppqn)
+    }
+}
+
+#[must_use]
+pub fn bpm_to_microseconds_per_quarter(bpm: f32) -> u32 {
+    (60_000_000.0 / f64::from(bpm)).round().clamp(1.0, f64::from(MAX_MICROSECONDS_PER_QUARTER)) as u32
+}
+
+fn write_variable_length_quantity(bytes: &mut Vec<u8>, value: u32) {
+    // groups of 7 bits, most significant first, all but the last one flagged with the high bit. Limited to 28 bits
+    let value = value.min(0x0FFF_FFFF);
+    let mut shift = 21;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        bytes.push(((value >> shift) & 0x7F) as u8 | 0x80);
+        shift -= 7;
+    }
+    bytes.push((value & 0x7F) as u8);
+}
+
+/// Format 0 standard MIDI file with one set-tempo meta event per point, the first point being at tick 0.
+/// Delta times are computed with the tempo in effect since the previous point, and rounded on the cumulated time so
+/// rounding errors don't drift.
+#[must_use]
+pub fn write_smf(points: impl IntoIterator<Item = (Duration, f32)>, ppqn: u16) -> Vec<u8> {
+    let ppqn = ppqn.clamp(1, 0x7FFF);
+
+    let mut track = Vec::new();
+    let mut previous: Option<(Duration, u32)> = None;
+    let mut exact_ticks = 0.0;
+    let mut written_ticks = 0u64;
+
+    for (timestamp, bpm) in points {
+        let microseconds_per_quarter = bpm_to_microseconds_per_quarter(bpm);
+        let delta = match previous {
+            None => 0,
+            Some((previous_timestamp, previous_microseconds_per_quarter)) => {
+                let elapsed = timestamp.saturating_sub(previous_timestamp).as_secs_f64() * 1_000_000.0;
+                exact_ticks += elapsed * f64::from(ppqn) / f64::from(previous_microseconds_per_quarter);
+                let ticks = exact_ticks.round() as u64;
+                let delta = ticks - written_ticks;
+                written_ticks = ticks;
+                delta
+            }
+        };
+        previous = Some((timestamp, microseconds_per_quarter));
+
+        write_variable_length_quantity(&mut track, u32::try_from(delta).unwrap_or(u32::MAX));
+        track.extend([0xFF, 0x51, 0x03]);
+        track.extend(&microseconds_per_quarter.to_be_bytes()[1..]);
+    }
+
+    // end of track
+    track.extend([0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut smf = Vec::with_capacity(22 + track.len());
+    smf.extend(b"MThd");
+    smf.extend(6u32.to_be_bytes());
+    // format 0, one track
+    smf.extend(0u16.to_be_bytes());
+    smf.extend(1u16.to_be_bytes());
+    smf.extend(ppqn.to_be_bytes());
+    smf.extend(b"MTrk");
+    smf.extend((track.len() as u32).to_be_bytes());
+    smf.extend(track);
+    smf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // returns ppqn and (absolute tick, microseconds per quarter) of each tempo event
+    fn parse(smf: &[u8]) -> (u16, Vec<(u64, u32)>) {
+        assert_eq!(&smf[0..4], b"MThd");
+        assert_eq!(u32::from_be_bytes(smf[4..8].try_into().unwrap()), 6);
+        assert_eq!(u16::from_be_bytes(smf[8..10].try_into().unwrap()), 0);
+        assert_eq!(u16::from_be_bytes(smf[10..12].try_into().unwrap()), 1);
+        let ppqn = u16::from_be_bytes(smf[12..14].try_into().unwrap());
+        assert_eq!(&smf[14..18], b"MTrk");
+        let length = u32::from_be_bytes(smf[18..22].try_into().unwrap()) as usize;
+        let track = &smf[22..];
+        assert_eq!(track.len(), length);
+
+        let mut events = vec![];
+        let mut position = 0;
+        let mut tick = 0;
+        loop {
+            let mut delta = 0u64;
+            loop {
+                let byte = track[position];
+                position += 1;
+                delta = (delta << 7) | u64::from(byte & 0x7F);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            tick += delta;
+            match &track[position..position + 3] {
+                [0xFF, 0x51, 0x03] => {
+                    let data = &track[position + 3..position + 6];
+                    events.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]])));
+                    position += 6;
+                }
+                [0xFF, 0x2F, 0x00] => {
+                    assert_eq!(position + 3, track.len());
+                    return (ppqn, events);
+                }
+                unexpected => panic!("unexpected event {unexpected:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_variable_length_quantity() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x2000, vec![0xC0, 0x00]),
+            (0x3FFF, vec![0xFF, 0x7F]),
+            (0x0FFF_FFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut bytes = vec![];
+            write_variable_length_quantity(&mut bytes, value);
+            assert_eq!(bytes, expected, "{value:#x}");
+        }
+    }
+
+    #[test]
+    fn test_smf() {
+        let points = [
+            (Duration::from_secs(10), 120.0),
+            // 2 seconds at 120 BPM = 4 quarters
+            (Duration::from_secs(12), 90.0),
+            // 1 second at 90 BPM = 1.5 quarters
+            (Duration::from_secs(13), 140.5),
+        ];
+        let (ppqn, events) = parse(&write_smf(points, 480));
+        assert_eq!(ppqn, 480);
+        assert_eq!(events, vec![(0, 500_000), (4 * 480, 666_667), (4 * 480 + 720, 427_046)]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let (ppqn, events) = parse(&write_smf([], 96));
+        assert_eq!(ppqn, 96);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_no_drift() {
+        // 1000 changes every 1/3 second alternating around 100 BPM
+        let points =
+            (0..1000u32).map(|n| (Duration::from_secs(u64::from(n)) / 3, if n % 2 == 0 { 100.0 } else { 101.0 }));
+        let (_, events) = parse(&write_smf(points, 960));
+        let expected_ticks = |n: u64| {
+            // pairs of 1/3s at 100 then 101 BPM
+            let seconds_per_quarter = |bpm: f64| 60.0 / bpm;
+            let pairs = n / 2;
+            let mut quarters =
+                pairs as f64 * (1.0 / 3.0 / seconds_per_quarter(100.0) + 1.0 / 3.0 / seconds_per_quarter(101.0));
+            if n % 2 == 1 {
+                quarters += 1.0 / 3.0 / seconds_per_quarter(100.0);
+            }
+            quarters * 960.0
+        };
+        let (last_tick, _) = events[999];
+        assert!((last_tick as f64 - expected_ticks(999)).abs() <= 1.0, "{last_tick} {}", expected_ticks(999));
+    }
+
+    #[test]
+    fn test_curve_hysteresis_and_capacity() {
+        let mut tempo_curve = TempoCurve::new(&TempoMapConfig { max_points: 3, hysteresis: 1.0, ppqn: 480 });
+        tempo_curve.push(Duration::from_secs(0), 120.0);
+        tempo_curve.push(Duration::from_secs(1), 120.5);
+        tempo_curve.push(Duration::from_secs(2), f32::NAN);
+        tempo_curve.push(Duration::from_secs(3), 125.0);
+        tempo_curve.push(Duration::from_secs(4), 130.0);
+        tempo_curve.push(Duration::from_secs(5), 100.0);
+        assert_eq!(
+            tempo_curve.points().copied().collect::<Vec<_>>(),
+            vec![(Duration::from_secs(3), 125.0), (Duration::from_secs(4), 130.0), (Duration::from_secs(5), 100.0)]
+        );
+        let (_, events) = parse(&tempo_curve.to_smf(480));
+        assert_eq!(events.len(), 3);
+    }
+}
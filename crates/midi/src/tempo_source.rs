@@ -0,0 +1,179 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+/// Which tempo is sent as MIDI clock, `SysEx` or to the DAW
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TempoSource {
+    #[default]
+    Detected,
+    Daw,
+    // detected tempo weighted by its confidence, the DAW tempo makes up for the rest
+    Blend,
+}
+
+impl TempoSource {
+    pub const ALL: [Self; 3] = [Self::Detected, Self::Daw, Self::Blend];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Detected => "Detected",
+            Self::Daw => "DAW",
+            Self::Blend => "Blend",
+        }
+    }
+
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Detected => Self::Daw,
+            Self::Daw => Self::Blend,
+            Self::Blend => Self::Detected,
+        }
+    }
+}
+
+impl Display for TempoSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Confidence proxy of an estimate: notes per beat over its lookback window, 1 as soon as there is a note per beat.
+/// Sparse playing gives a low confidence
+#[must_use]
+pub fn note_density_confidence(notes_in_lookback: usize, beats_lookback: u8) -> f32 {
+    if beats_lookback == 0 {
+        return 0.0;
+    }
+    (notes_in_lookback as f32 / f32::from(beats_lookback)).clamp(0.0, 1.0)
+}
+
+/// The tempo to send. Without a DAW tempo, every source falls back to the detected one
+#[must_use]
+pub fn output_tempo(tempo_source: TempoSource, detected_bpm: f32, daw_bpm: Option<f32>, confidence: f32) -> f32 {
+    match (tempo_source, daw_bpm) {
+        (TempoSource::Detected, _) | (_, None) => detected_bpm,
+        (TempoSource::Daw, Some(daw_bpm)) => daw_bpm,
+        (TempoSource::Blend, Some(daw_bpm)) => {
+            let weight = confidence.clamp(0.0, 1.0);
+            weight * detected_bpm + (1.0 - weight) * daw_bpm
+        }
+    }
+}
+
+/// Tempo source shared by all clones of a configuration, so that the GUI or the TUI can change it while tempo is
+/// being sent
+#[derive(Clone, Default)]
+pub struct SharedTempoSource(Arc<AtomicU8>);
+
+impl SharedTempoSource {
+    #[must_use]
+    pub fn new(tempo_source: TempoSource) -> Self {
+        Self(Arc::new(AtomicU8::new(tempo_source as u8)))
+    }
+
+    #[must_use]
+    pub fn load(&self, order: Ordering) -> TempoSource {
+        TempoSource::ALL.get(usize::from(self.0.load(order))).copied().unwrap_or_default()
+    }
+
+    pub fn store(&self, tempo_source: TempoSource, order: Ordering) {
+        self.0.store(tempo_source as u8, order);
+    }
+}
+
+impl Debug for SharedTempoSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+impl PartialEq for SharedTempoSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.load(Ordering::SeqCst) == other.load(Ordering::SeqCst)
+    }
+}
+
+impl Eq for SharedTempoSource {}
+
+impl Serialize for SharedTempoSource {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.load(Ordering::SeqCst).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedTempoSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        TempoSource::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_blend_confidence_sweep() {
+        let (detected_bpm, daw_bpm) = (128.0, 120.0);
+        for step in 0..=10 {
+            let confidence = step as f32 / 10.0;
+            let bpm = output_tempo(TempoSource::Blend, detected_bpm, Some(daw_bpm), confidence);
+            assert_close(bpm, daw_bpm + 8.0 * confidence);
+        }
+        // out of range confidences are clamped
+        assert_close(output_tempo(TempoSource::Blend, detected_bpm, Some(daw_bpm), 2.0), detected_bpm);
+        assert_close(output_tempo(TempoSource::Blend, detected_bpm, Some(daw_bpm), -1.0), daw_bpm);
+    }
+
+    #[test]
+    fn test_sources() {
+        for confidence in [0.0, 0.5, 1.0] {
+            assert_close(output_tempo(TempoSource::Detected, 128.0, Some(120.0), confidence), 128.0);
+            assert_close(output_tempo(TempoSource::Daw, 128.0, Some(120.0), confidence), 120.0);
+            // no DAW tempo, the detected one is used
+            for tempo_source in TempoSource::ALL {
+                assert_close(output_tempo(tempo_source, 128.0, None, confidence), 128.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_note_density_confidence() {
+        assert_close(note_density_confidence(0, 8), 0.0);
+        assert_close(note_density_confidence(2, 8), 0.25);
+        assert_close(note_density_confidence(8, 8), 1.0);
+        // chords and subdivisions don't make it more than certain
+        assert_close(note_density_confidence(32, 8), 1.0);
+        assert_close(note_density_confidence(4, 0), 0.0);
+    }
+
+    #[test]
+    fn test_shared_tempo_source() {
+        let tempo_source = SharedTempoSource::default();
+        assert_eq!(tempo_source.load(Ordering::Relaxed), TempoSource::Detected);
+        tempo_source.clone().store(TempoSource::Blend, Ordering::Relaxed);
+        assert_eq!(tempo_source.load(Ordering::Relaxed), TempoSource::Blend);
+        assert_eq!(tempo_source.load(Ordering::Relaxed).next(), TempoSource::Detected);
+
+        let serialized = serde_json::to_string(&tempo_source).unwrap();
+        assert_eq!(serialized, r#""Blend""#);
+        assert_eq!(serde_json::from_str::<SharedTempoSource>(&serialized).unwrap(), tempo_source);
+    }
+}
@@ -0,0 +1,164 @@
+use parameter::OnOff;
+
+// each note weighs that much less on every following note, about the last 50 notes count
+const FORGETTING: f32 = 0.98;
+// nothing is gated until that many notes were weighed
+const MIN_WEIGHT: f32 = 16.0;
+// share of the velocity variance the split into two groups must explain, from the lowest to the highest sensitivity.
+// A single group of velocities can't go above 0.75
+const SEPARABILITY: (f32, f32) = (0.92, 0.8);
+
+/// Velocity under which notes are ghost notes, learned from the velocities of the recent notes. When they form two
+/// groups, the threshold separating them is found the way Otsu's method does, otherwise nothing is gated
+#[derive(Clone, Debug)]
+pub struct VelocityGate {
+    histogram: [f32; 128],
+    weight: f32,
+    threshold: Option<u8>,
+}
+
+impl Default for VelocityGate {
+    fn default() -> Self {
+        Self { histogram: [0.0; 128], weight: 0.0, threshold: None }
+    }
+}
+
+impl VelocityGate {
+    /// Learns `velocity`, then tells whether the note passes the gate. The velocities are learned even when the gate is
+    /// off, it applies right away once switched on
+    pub fn admit(&mut self, velocity: u8, auto_velocity_gate: OnOff<f32>) -> bool {
+        self.learn(velocity);
+        self.threshold = match auto_velocity_gate {
+            OnOff::On(sensitivity) => self.estimate(sensitivity),
+            OnOff::Off(_) => None,
+        };
+        self.threshold.is_none_or(|threshold| velocity >= threshold)
+    }
+
+    /// Notes under that velocity were gated by the last `admit`, none if nothing was
+    #[must_use]
+    pub fn threshold(&self) -> Option<u8> {
+        self.threshold
+    }
+
+    fn learn(&mut self, velocity: u8) {
+        for weight in &mut self.histogram {
+            *weight *= FORGETTING;
+        }
+        self.histogram[usize::from(velocity.min(127))] += 1.0;
+        self.weight = self.weight * FORGETTING + 1.0;
+    }
+
+    /// Threshold separating the velocities in two groups, if they form two distinct enough groups. `sensitivity` goes
+    /// from 0, only well separated groups, to 1
+    #[must_use]
+    pub fn estimate(&self, sensitivity: f32) -> Option<u8> {
+        if self.weight < MIN_WEIGHT {
+            return None;
+        }
+        let mean = (0..128).map(|velocity| self.histogram[velocity] * velocity as f32).sum::<f32>() / self.weight;
+        let variance =
+            (0..128).map(|velocity| self.histogram[velocity] * (velocity as f32 - mean).powi(2)).sum::<f32>()
+                / self.weight;
+        if variance <= f32::EPSILON {
+            return None;
+        }
+
+        // notes under `threshold` are in the lower group
+        let mut best_variance = 0.0;
+        let mut best_thresholds = (0, 0);
+        let mut lower_weight = 0.0;
+        let mut lower_sum = 0.0;
+        for threshold in 1..128 {
+            lower_weight += self.histogram[threshold - 1];
+            lower_sum += self.histogram[threshold - 1] * (threshold - 1) as f32;
+            let upper_weight = self.weight - lower_weight;
+            if lower_weight <= f32::EPSILON || upper_weight <= f32::EPSILON {
+                continue;
+            }
+            let lower_mean = lower_sum / lower_weight;
+            let upper_mean = (mean * self.weight - lower_sum) / upper_weight;
+            let between_variance =
+                lower_weight * upper_weight * (upper_mean - lower_mean).powi(2) / (self.weight * self.weight);
+            if between_variance > best_variance * (1.0 + 1e-4) {
+                best_variance = between_variance;
+                best_thresholds = (threshold, threshold);
+            } else if between_variance >= best_variance * (1.0 - 1e-4) {
+                // no velocity in between, the threshold is put halfway
+                best_thresholds.1 = threshold;
+            }
+        }
+
+        let (strictest, loosest) = SEPARABILITY;
+        let separability = strictest + (loosest - strictest) * sensitivity.clamp(0.0, 1.0);
+        (best_variance / variance >= separability).then(|| usize::midpoint(best_thresholds.0, best_thresholds.1) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // velocities spread around `center` in a triangle of `spread` on both sides, the same for every call
+    fn velocities(center: i32, spread: i32, count: usize) -> impl Iterator<Item = u8> {
+        let width = spread + 1;
+        (0..count).map(move |index| {
+            let index = index as i32;
+            let offset = (index * 37) % width - spread / 2 + (index * 53) % width - spread / 2;
+            (center + offset).clamp(1, 127) as u8
+        })
+    }
+
+    fn learn(velocity_gate: &mut VelocityGate, velocities: impl IntoIterator<Item = u8>) -> usize {
+        velocities.into_iter().filter(|velocity| velocity_gate.admit(*velocity, OnOff::On(0.5))).count()
+    }
+
+    #[test]
+    fn test_bimodal() {
+        let mut velocity_gate = VelocityGate::default();
+        // ghost notes between the main hits
+        let notes = velocities(25, 10, 200).zip(velocities(100, 16, 200)).flat_map(|(ghost, hit)| [ghost, hit, hit]);
+        learn(&mut velocity_gate, notes);
+        let threshold = velocity_gate.threshold().unwrap();
+        assert!((40..=85).contains(&threshold), "{threshold}");
+
+        assert!(!velocity_gate.admit(30, OnOff::On(0.5)));
+        assert!(velocity_gate.admit(95, OnOff::On(0.5)));
+        // switched off, nothing is gated but the velocities are still learned
+        assert!(velocity_gate.admit(30, OnOff::Off(0.5)));
+        assert_eq!(velocity_gate.threshold(), None);
+        assert!(velocity_gate.estimate(0.5).is_some());
+    }
+
+    #[test]
+    fn test_unimodal() {
+        for (center, spread) in [(90, 20), (64, 60), (40, 10)] {
+            let mut velocity_gate = VelocityGate::default();
+            assert_eq!(learn(&mut velocity_gate, velocities(center, spread, 300)), 300);
+            assert_eq!(velocity_gate.estimate(1.0), None, "{center} {spread}");
+        }
+        // all the same velocity
+        let mut velocity_gate = VelocityGate::default();
+        assert_eq!(learn(&mut velocity_gate, [100; 50]), 50);
+        assert_eq!(velocity_gate.threshold(), None);
+    }
+
+    #[test]
+    fn test_forgetting() {
+        let mut velocity_gate = VelocityGate::default();
+        let notes = velocities(20, 8, 100).zip(velocities(110, 8, 100)).flat_map(|(ghost, hit)| [ghost, hit]);
+        learn(&mut velocity_gate, notes);
+        assert!(velocity_gate.threshold().is_some());
+
+        // the ghost notes stopped, the gate goes away
+        learn(&mut velocity_gate, velocities(90, 20, 300));
+        assert_eq!(velocity_gate.threshold(), None);
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let mut velocity_gate = VelocityGate::default();
+        let notes = [20, 110].into_iter().cycle().take(10);
+        assert_eq!(learn(&mut velocity_gate, notes), 10);
+    }
+}
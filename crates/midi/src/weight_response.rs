@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// How each criterion of a pair of notes, between 0 and 1, is turned into its share of the pair's intensity before its
+/// weight multiplies it. The shares are summed and the intensity is 10 to the power of the sum, so the response decides
+/// how much a weight change is felt:
+/// - `Log10` compresses the criteria, a poor note still counts and doubling a weight less than doubles its influence.
+///   A larger scale compresses more
+/// - `Linear` keeps the criteria as they are, a weight changes the histogram in proportion to it
+/// - `Power` lets the best criteria through and pushes the poor ones towards 0 as the exponent grows, weights then
+///   sharpen the estimate rather than smooth it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum WeightResponse {
+    Log10 { scale: f32 },
+    Linear,
+    Power { exp: f32 },
+}
+
+impl Default for WeightResponse {
+    fn default() -> Self {
+        Self::Log10 { scale: 9.0 }
+    }
+}
+
+impl WeightResponse {
+    pub const ALL: [Self; 3] = [Self::Log10 { scale: 9.0 }, Self::Linear, Self::Power { exp: 2.0 }];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Log10 { .. } => "Log10",
+            Self::Linear => "Linear",
+            Self::Power { .. } => "Power",
+        }
+    }
+
+    /// Share of the intensity of `criterion`, 0 for a criterion of 0 and 1 for a criterion of 1. Not a number when the
+    /// criterion is not
+    #[must_use]
+    #[inline]
+    pub fn apply(self, criterion: f32) -> f32 {
+        match self {
+            // the logarithm of 1 + scale is 1 for the default scale, the division is exact
+            Self::Log10 { scale } => (criterion * scale + 1.0).log10() / (scale + 1.0).log10(),
+            Self::Linear => criterion,
+            Self::Power { exp } => criterion.powf(exp),
+        }
+    }
+
+    /// Same kind as `other`, regardless of its setting
+    #[must_use]
+    pub fn same_kind(self, other: Self) -> bool {
+        self.label() == other.label()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_unchanged() {
+        // the transfer function the detection was tuned with
+        let previous = |criterion: f32| (criterion * 9.0 + 1.0).log10();
+        for criterion in (0..=1000).map(|step| step as f32 / 1000.0).chain([2.0]) {
+            assert_eq!(
+                WeightResponse::default().apply(criterion).to_bits(),
+                previous(criterion).to_bits(),
+                "{criterion}"
+            );
+        }
+        assert!(WeightResponse::default().apply(f32::NAN).is_nan());
+        assert!(WeightResponse::default().apply(-1.0).is_nan());
+    }
+
+    #[test]
+    fn test_responses() {
+        for response in
+            [WeightResponse::Log10 { scale: 99.0 }, WeightResponse::Linear, WeightResponse::Power { exp: 3.0 }]
+        {
+            assert!(response.apply(0.0).abs() < f32::EPSILON, "{response:?}");
+            assert!((response.apply(1.0) - 1.0).abs() < 1e-6, "{response:?}");
+            assert!(response.apply(f32::NAN).is_nan(), "{response:?}");
+        }
+
+        assert!((WeightResponse::Linear.apply(0.25) - 0.25).abs() < f32::EPSILON);
+        assert!((WeightResponse::Power { exp: 2.0 }.apply(0.5) - 0.25).abs() < f32::EPSILON);
+        assert!((WeightResponse::Power { exp: 0.5 }.apply(0.25) - 0.5).abs() < f32::EPSILON);
+        // a larger scale lifts the poor criteria further
+        assert!((WeightResponse::Log10 { scale: 99.0 }.apply(1.0 / 11.0) - 0.5).abs() < 1e-6);
+        assert!(WeightResponse::Log10 { scale: 99.0 }.apply(0.1) > WeightResponse::default().apply(0.1));
+
+        // from the most to the least forgiving with a poor criterion
+        let poor = 0.2;
+        let shares = [WeightResponse::default(), WeightResponse::Linear, WeightResponse::Power { exp: 2.0 }]
+            .map(|response| response.apply(poor));
+        assert!(shares[0] > shares[1] && shares[1] > shares[2], "{shares:?}");
+    }
+
+    #[test]
+    fn test_serde() {
+        let response: WeightResponse = serde_json::from_str(r#"{"kind": "Power", "exp": 1.5}"#).unwrap();
+        assert_eq!(response, WeightResponse::Power { exp: 1.5 });
+        assert_eq!(serde_json::to_string(&WeightResponse::Linear).unwrap(), r#"{"kind":"Linear"}"#);
+        assert_eq!(serde_json::to_string(&WeightResponse::default()).unwrap(), r#"{"kind":"Log10","scale":9.0}"#);
+        assert!(WeightResponse::Log10 { scale: 2.0 }.same_kind(WeightResponse::default()));
+        assert!(!WeightResponse::Linear.same_kind(WeightResponse::default()));
+    }
+}
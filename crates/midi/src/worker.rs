@@ -10,17 +10,35 @@ use std::{
     time::Duration as StdDuration,
 };
 use sync::Mutex;
+use wmidi::{Channel, Note, U7};
 
 use errors::Result;
 use sync::ArcAtomicBool;
 
 use crate::{
-    bpm::bpm_to_midi_clock_interval,
-    bpm_detection::{BPMDetection, NOTE_CAPACITY},
+    beat_counter::BeatCounter,
+    bpm::{bpm_to_midi_clock_interval, validate_interaction},
+    bpm_detection::{BPMDetection, PhaseSplit, NOTE_CAPACITY},
     bpm_detection_receiver::BPMDetectionReceiver,
+    clock_input::{ClockTempo, ClockTick},
+    clock_lookahead::{ClockLookaheadConfig, ClockScheduler, TempoTrend},
+    daw_link::DawLink,
+    daw_link_protocol::DawMessage,
+    egress_hub::{Egress, EgressHub, EgressSinks},
+    feel_ambiguity::{FeelAmbiguity, FeelAmbiguityConfig},
+    frozen_reference::FrozenReference,
+    latency::{ClockAnchor, TempoLatency},
+    memory::{shrink_excess, LOW_MEMORY_EVENT_CAPACITY},
     midi_output_trait::MidiOutput,
+    osc_output::OscOutput,
+    pre_detection::PreDetection,
+    session_stats::SessionStats,
+    tap_tempo::TapTempo,
+    tempo_bands::{TempoBandAction, TempoBands},
+    tempo_change::TempoChangeDetector,
+    tempo_source::{output_tempo, SharedTempoSource},
     worker_event::WorkerEvent,
-    DynamicBPMDetectionParameters, MidiServiceConfig, StaticBPMDetectionParameters,
+    DynamicBPMDetectionParameters, MidiServiceConfig, MultiResolutionConfig, StaticBPMDetectionParameters,
 };
 
 pub struct Worker<B, C>
@@ -36,7 +54,32 @@ where
     playback_sender: Sender<Playback>,
     dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
     clock_interval_microseconds: Arc<AtomicU64>,
+    clock_lookahead_microseconds: Arc<AtomicU64>,
+    clock_lookahead: ClockLookaheadConfig,
+    tempo_trend: TempoTrend,
     send_tempo: ArcAtomicBool,
+    enable_midi_clock: ArcAtomicBool,
+    tempo_source: SharedTempoSource,
+    daw_bpm: Option<f32>,
+    // maps note timestamps to the wall clock, unknown until a note is received
+    clock_anchor: Arc<Mutex<Option<ClockAnchor>>>,
+    tempo_latency: TempoLatency,
+    // smaller buffers, and no latency statistics
+    low_memory: bool,
+    multi_resolution: MultiResolutionConfig,
+    beat_counter: BeatCounter,
+    pre_detection: PreDetection,
+    tap_tempo: TapTempo,
+    // none when the MIDI clock received is not tracked
+    clock_tempo: Option<ClockTempo>,
+    session_stats: SessionStats,
+    tempo_change: TempoChangeDetector,
+    feel_ambiguity_config: FeelAmbiguityConfig,
+    // of the last evaluation, the candidate the user picks is taken from it
+    feel_ambiguity: Option<FeelAmbiguity>,
+    egress_hub: EgressHub,
+    forward_transport: bool,
+    tempo_bands: TempoBands,
 }
 
 enum Playback {
@@ -53,14 +96,28 @@ where
     #[allow(clippy::needless_pass_by_value)]
     #[allow(clippy::too_many_lines)]
     fn worker_loop(&mut self, static_bpm_detection_parameters: StaticBPMDetectionParameters) {
-        let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters);
+        self.report_config_warnings(&static_bpm_detection_parameters);
+        let mut bpm_detection = BPMDetection::with_low_memory(static_bpm_detection_parameters, self.low_memory);
+        bpm_detection.set_multi_resolution(&self.multi_resolution);
+        let mut frozen_reference: Option<FrozenReference> = None;
         let mut scheduled_bpm_detection_parameters_change: Option<StaticBPMDetectionParameters> = None;
         let mut schedule_evaluate_bpm: Option<Instant> = None;
-        let mut buffered_events = Vec::with_capacity(NOTE_CAPACITY);
+        let event_capacity = if self.low_memory { LOW_MEMORY_EVENT_CAPACITY } else { NOTE_CAPACITY };
+        let mut buffered_events = Vec::with_capacity(event_capacity);
+        #[cfg(feature = "memory-stats")]
+        let mut memory_stats_logged_at = Instant::now();
 
         loop {
-            let worker_event = if let Some(schedule_evaluate_bpm) = &schedule_evaluate_bpm {
-                let wait_for = StdDuration::from_millis(50).saturating_sub(schedule_evaluate_bpm.elapsed());
+            // a pending chord is complete once no other note can join it
+            let wait_for = [
+                schedule_evaluate_bpm
+                    .map(|scheduled_at| StdDuration::from_millis(50).saturating_sub(scheduled_at.elapsed())),
+                self.pre_detection.flush_in(Instant::now()),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            let worker_event = if let Some(wait_for) = wait_for {
                 match self.worker_events_receiver.recv_timeout(wait_for) {
                     Ok(worker_event) => Some(worker_event),
                     Err(RecvTimeoutError::Timeout) => None,
@@ -79,6 +136,10 @@ where
                 schedule_evaluate_bpm = None;
                 evaluate_bpm = true;
                 if let Some(scheduled_bpm_detection_parameters) = scheduled_bpm_detection_parameters_change.take() {
+                    self.pre_detection.set_configured(&scheduled_bpm_detection_parameters);
+                    if let Some(frozen_reference) = &mut frozen_reference {
+                        frozen_reference.update_static_parameters(scheduled_bpm_detection_parameters.clone());
+                    }
                     bpm_detection.update_static_parameters(scheduled_bpm_detection_parameters);
                 }
             }
@@ -91,32 +152,138 @@ where
                 for worker_event in buffered_events.drain(..) {
                     match worker_event {
                         WorkerEvent::TimedMidiNoteOn(midi_message) => {
-                            evaluate_bpm = true;
-                            bpm_detection.receive_midi_message(midi_message);
+                            self.bpm_detection_receiver.receive_note(&midi_message);
+                            self.pre_detection.note_on(
+                                &self.dynamic_bpm_detection_parameters,
+                                midi_message,
+                                Instant::now(),
+                                |onset| {
+                                    evaluate_bpm = true;
+                                    self.beat_counter.note(onset.timestamp);
+                                    self.session_stats.note();
+                                    bpm_detection.receive_midi_message(onset);
+                                },
+                            );
+                        }
+                        WorkerEvent::TimedBatch(midi_messages) => {
+                            let mut onsets = Vec::with_capacity(midi_messages.len());
+                            for midi_message in midi_messages {
+                                self.bpm_detection_receiver.receive_note(&midi_message);
+                                self.pre_detection.note_on(
+                                    &self.dynamic_bpm_detection_parameters,
+                                    midi_message,
+                                    Instant::now(),
+                                    |onset| onsets.push(onset),
+                                );
+                            }
+                            for onset in &onsets {
+                                self.beat_counter.note(onset.timestamp);
+                                self.session_stats.note();
+                            }
+                            evaluate_bpm |= bpm_detection.receive_batch(&onsets) > 0;
+                        }
+                        WorkerEvent::NoteOff { channel, note } => {
+                            self.pre_detection.note_off(channel, note);
+                            continue;
                         }
-                        WorkerEvent::TimingClock => {
+                        WorkerEvent::TimingClock(at) => {
+                            match self.clock_tempo.as_mut().map(|clock_tempo| clock_tempo.tick(at)) {
+                                Some(ClockTick::Tempo(bpm)) => self.bpm_detection_receiver.receive_clock_bpm(Some(bpm)),
+                                Some(ClockTick::Restarted) => self.bpm_detection_receiver.receive_clock_bpm(None),
+                                Some(ClockTick::Counting) | None => (),
+                            }
                             continue;
                         }
                         WorkerEvent::Play => {
                             if let Err(err) = self.playback_sender.send(Playback::Play) {
                                 error!("could not send play to clock thread : {err:?}");
                             };
+                            self.forward_transport(DawMessage::Play);
+                            continue;
+                        }
+                        WorkerEvent::DawBPM(bpm) => {
+                            self.daw_bpm = Some(bpm);
+                            continue;
+                        }
+                        WorkerEvent::ClearNotes => {
+                            self.tempo_trend.clear();
+                            bpm_detection.clear_notes();
+                            self.pre_detection.clear(&mut bpm_detection);
+                            self.reset_beat_counter();
+                            self.session_stats.reset();
+                            self.bpm_detection_receiver.receive_session_summary(self.session_stats.summary());
+                            self.tempo_change.clear();
+                            self.bpm_detection_receiver.receive_note_freshness(None);
+                            self.feel_ambiguity = None;
+                            self.bpm_detection_receiver.receive_feel_ambiguity(None);
+                            continue;
+                        }
+                        WorkerEvent::ResetBeatCounter => {
+                            self.reset_beat_counter();
+                            continue;
+                        }
+                        WorkerEvent::Tap(at) => {
+                            let tapped_bpm = self.tap_tempo.tap(at);
+                            // the first tap is on the downbeat, the bars are counted from there
+                            if self.tap_tempo.is_first_tap() {
+                                self.reset_beat_counter();
+                            }
+                            if let Some(tapped_bpm) = tapped_bpm {
+                                bpm_detection.seed(tapped_bpm);
+                                self.bpm_detection_receiver.receive_tapped_tempo(Some(tapped_bpm));
+                                evaluate_bpm = true;
+                            }
+                            continue;
+                        }
+                        WorkerEvent::ChooseFeel(feel) => {
+                            if let Some(feel_ambiguity) = &self.feel_ambiguity {
+                                bpm_detection.choose_feel(feel_ambiguity, feel);
+                                self.bpm_detection_receiver.receive_tapped_tempo(bpm_detection.seeded_bpm());
+                                evaluate_bpm = true;
+                            }
+                            continue;
+                        }
+                        WorkerEvent::FreezeNotes => {
+                            frozen_reference = Some(FrozenReference::new(
+                                &bpm_detection,
+                                self.pre_detection.auto_zoom().configured().clone(),
+                            ));
+                            evaluate_bpm = true;
+                            continue;
+                        }
+                        WorkerEvent::ClearFrozenNotes => {
+                            frozen_reference = None;
+                            self.bpm_detection_receiver.receive_frozen_bpm_histogram_data(None);
                             continue;
                         }
                         WorkerEvent::Stop => {
                             if let Err(err) = self.playback_sender.send(Playback::Stop) {
                                 error!("could not send stop to clock thread : {err:?}");
                             };
+                            self.forward_transport(DawMessage::Stop);
+                            continue;
+                        }
+                        WorkerEvent::Transport(transport) => {
+                            self.forward_transport(transport);
                             continue;
                         }
                         WorkerEvent::DynamicBPMDetectionParameters(dynamic_bpm_detection_parameters) => {
                             self.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters;
+                            if let Some(frozen_reference) = &mut frozen_reference {
+                                frozen_reference.dynamic_parameters_changed();
+                            }
+                            self.report_config_warnings(
+                                scheduled_bpm_detection_parameters_change
+                                    .as_ref()
+                                    .unwrap_or(self.pre_detection.auto_zoom().configured()),
+                            );
                             if schedule_evaluate_bpm.is_none() {
                                 schedule_evaluate_bpm = Some(Instant::now());
                             }
                             continue;
                         }
                         WorkerEvent::StaticBPMDetectionParameters(bpm_detection_parameters) => {
+                            self.report_config_warnings(&bpm_detection_parameters);
                             scheduled_bpm_detection_parameters_change = Some(bpm_detection_parameters);
                             if schedule_evaluate_bpm.is_none() {
                                 schedule_evaluate_bpm = Some(Instant::now());
@@ -125,25 +292,159 @@ where
                         }
                     };
                 }
+                // a burst may have grown it well beyond what is usually needed
+                shrink_excess(&mut buffered_events, event_capacity);
+            }
+
+            self.pre_detection.flush(&self.dynamic_bpm_detection_parameters, Instant::now(), |onset| {
+                evaluate_bpm = true;
+                self.beat_counter.note(onset.timestamp);
+                self.session_stats.note();
+                bpm_detection.receive_midi_message(onset);
+            });
+
+            #[cfg(feature = "memory-stats")]
+            if memory_stats_logged_at.elapsed() > StdDuration::from_secs(60) {
+                memory_stats_logged_at = Instant::now();
+                log::debug!("{:?}", bpm_detection.memory_stats(buffered_events.capacity()));
             }
 
             if evaluate_bpm {
-                let Some((histogram_data_points, bpm)) =
-                    bpm_detection.compute_bpm(&self.dynamic_bpm_detection_parameters)
-                else {
+                // only evaluated on a change of parameters, its notes stay the same
+                if let Some(frozen) = frozen_reference
+                    .as_mut()
+                    .and_then(|frozen_reference| frozen_reference.evaluate(&self.dynamic_bpm_detection_parameters))
+                {
+                    self.bpm_detection_receiver.receive_frozen_bpm_histogram_data(Some(frozen));
+                }
+
+                let newest_note = bpm_detection.newest_note_timestamp();
+                let effective_parameters = self.pre_detection.effective_parameters(
+                    &self.dynamic_bpm_detection_parameters,
+                    newest_note.unwrap_or_else(chrono::Duration::zero),
+                );
+                let phase_split_window = self.bpm_detection_receiver.phase_split_window();
+                bpm_detection.split_by_phase(
+                    phase_split_window
+                        .zip(self.beat_counter.phase())
+                        .map(|(window, beat_phase)| PhaseSplit { beat_phase, window }),
+                );
+                let Some((_, estimate)) = bpm_detection.compute_bpm(effective_parameters) else {
                     continue;
                 };
+                let bpm = estimate.bpm;
 
-                self.clock_interval_microseconds
-                    .store(bpm_to_midi_clock_interval(bpm).num_microseconds().unwrap() as u64, Ordering::Relaxed);
-                if self.send_tempo.load(Ordering::Relaxed) {
-                    self.midi_output.lock().sysex(&format!("TEMPO|{bpm}"));
+                let output_bpm =
+                    output_tempo(self.tempo_source.load(Ordering::Relaxed), bpm, self.daw_bpm, estimate.confidence);
+
+                self.clock_interval_microseconds.store(
+                    bpm_to_midi_clock_interval(output_bpm).num_microseconds().unwrap() as u64,
+                    Ordering::Relaxed,
+                );
+                if let Some(newest_note) = newest_note {
+                    self.tempo_trend.estimate(newest_note.to_std().unwrap_or_default(), output_bpm);
+                }
+                self.clock_lookahead_microseconds
+                    .store(self.clock_lookahead.target(&self.tempo_trend).as_micros() as u64, Ordering::Relaxed);
+                // each estimate while enabled, whether the tempo is sent or not
+                self.egress_hub.publish(Egress::OscBpm(output_bpm));
+                if let Some(tempo_band) = self.tempo_bands.update(output_bpm) {
+                    self.send_tempo_band_action(tempo_band.action);
+                }
+                self.bpm_detection_receiver.receive_tempo_band(self.tempo_bands.active());
+                let send_tempo = self.send_tempo.load(Ordering::Relaxed);
+                if send_tempo {
+                    self.midi_output.lock().sysex(&format!("TEMPO|{output_bpm}"));
+                    self.egress_hub.publish(Egress::Daw(DawMessage::Tempo(output_bpm)));
+                }
+                if !self.low_memory && (send_tempo || self.enable_midi_clock.load(Ordering::Relaxed)) {
+                    if let (Some(clock_anchor), Some(newest_note)) = (*self.clock_anchor.lock(), newest_note) {
+                        self.tempo_latency.record_delivery(&clock_anchor, newest_note, Instant::now());
+                    }
                 }
 
-                self.bpm_detection_receiver.receive_bpm_histogram_data(histogram_data_points, bpm);
+                if let Some(newest_note) = newest_note {
+                    self.beat_counter.tempo(bpm, newest_note);
+                    self.session_stats.estimate(bpm, newest_note.to_std().unwrap_or_default());
+                    self.tempo_change.estimate(newest_note, bpm);
+                }
+                self.bpm_detection_receiver.receive_bar_position(self.beat_counter.position());
+                self.bpm_detection_receiver.receive_beat_phase(self.beat_counter.phase());
+                self.bpm_detection_receiver.receive_velocity_gate(self.pre_detection.velocity_gate().threshold());
+                self.bpm_detection_receiver.receive_auto_zoom(self.pre_detection.auto_zoom().zoomed());
+                self.bpm_detection_receiver.receive_tapped_tempo(bpm_detection.seeded_bpm());
+                self.bpm_detection_receiver
+                    .receive_multi_resolution_histogram(bpm_detection.multi_resolution_histogram());
+                self.bpm_detection_receiver.receive_phase_split_histogram(bpm_detection.phase_split_histogram());
+                self.bpm_detection_receiver.receive_resolution_floor(bpm_detection.resolution_floor());
+                self.bpm_detection_receiver.receive_bpm_histogram_data(bpm_detection.histogram_data_points(), estimate);
+                self.bpm_detection_receiver.receive_session_summary(self.session_stats.summary());
+                let beats_lookback = self.dynamic_bpm_detection_parameters.beats_lookback;
+                self.bpm_detection_receiver.receive_note_freshness(
+                    self.tempo_change
+                        .change_point()
+                        .and_then(|change_point| bpm_detection.recent_share(change_point, bpm, beats_lookback)),
+                );
+                self.feel_ambiguity = bpm_detection.feel_ambiguity(bpm, &self.feel_ambiguity_config);
+                self.bpm_detection_receiver.receive_feel_ambiguity(self.feel_ambiguity);
+                self.pre_detection.zoom(&mut bpm_detection, &self.dynamic_bpm_detection_parameters, bpm);
             }
         }
     }
+
+    fn send_tempo_band_action(&self, action: TempoBandAction) {
+        let channel = match action {
+            TempoBandAction::ProgramChange { channel, .. } | TempoBandAction::Note { channel, .. } => channel,
+        };
+        let Ok(channel) = Channel::from_index(channel) else {
+            error!("tempo band channel {channel} is out of range, it starts at 0 and ends at 15");
+            return;
+        };
+        match action {
+            TempoBandAction::ProgramChange { program, .. } => match U7::try_from(program) {
+                Ok(program) => self.midi_output.lock().program_change(channel, program),
+                Err(_) => error!("tempo band program {program} is out of range, it ends at 127"),
+            },
+            TempoBandAction::Note { note, velocity, .. } => match (Note::try_from(note), U7::try_from(velocity)) {
+                (Ok(note), Ok(velocity)) => self.midi_output.lock().note(channel, note, velocity),
+                _ => error!("tempo band note {note} or velocity {velocity} is out of range, they end at 127"),
+            },
+        }
+    }
+
+    fn forward_transport(&mut self, transport: DawMessage) {
+        if self.forward_transport {
+            self.egress_hub.publish(Egress::Daw(transport));
+        }
+    }
+
+    fn reset_beat_counter(&mut self) {
+        self.beat_counter.reset();
+        self.bpm_detection_receiver.receive_bar_position(None);
+        self.bpm_detection_receiver.receive_beat_phase(None);
+    }
+
+    fn report_config_warnings(&self, static_bpm_detection_parameters: &StaticBPMDetectionParameters) {
+        self.bpm_detection_receiver.receive_config_warnings(&validate_interaction(
+            static_bpm_detection_parameters,
+            &self.dynamic_bpm_detection_parameters,
+        ));
+    }
+}
+
+// the sockets are opened, and the OSC host resolved, on the egress thread
+fn egress_hub(midi_service_config: &MidiServiceConfig) -> EgressHub {
+    let daw_port = midi_service_config.daw_link.port;
+    let osc_output_config = midi_service_config.osc_output.clone();
+    EgressHub::new(move || {
+        let mut sinks: EgressSinks = vec![Box::new(OscOutput::new(&osc_output_config))];
+        if let Some(daw_port) = daw_port {
+            let mut daw_link = DawLink::default();
+            daw_link.connect(daw_port);
+            sinks.push(Box::new(daw_link));
+        }
+        sinks
+    })
 }
 
 pub fn spawn(
@@ -151,14 +452,18 @@ pub fn spawn(
     static_bpm_detection_parameters: StaticBPMDetectionParameters,
     dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
     worker_receiver: Receiver<WorkerEvent>,
+    clock_anchor: Arc<Mutex<Option<ClockAnchor>>>,
     midi_output: impl MidiOutput + Send + 'static,
     bpm_detection_receiver: impl BPMDetectionReceiver,
 ) -> Result<()> {
     let midi_output = Arc::new(Mutex::new(midi_output));
     let clock_interval_microseconds = Arc::<AtomicU64>::default();
+    let clock_lookahead_microseconds = Arc::<AtomicU64>::default();
     let playback_sender = spawn_playback_controller(
         midi_service_config.enable_midi_clock.clone(),
         clock_interval_microseconds.clone(),
+        clock_lookahead_microseconds.clone(),
+        midi_service_config.clock_lookahead.clone(),
         midi_output.clone(),
     )?;
 
@@ -167,9 +472,30 @@ pub fn spawn(
         bpm_detection_receiver,
         worker_events_receiver: worker_receiver,
         playback_sender,
+        pre_detection: PreDetection::new(&static_bpm_detection_parameters, &dynamic_bpm_detection_parameters),
         dynamic_bpm_detection_parameters,
         clock_interval_microseconds,
+        clock_lookahead_microseconds,
+        clock_lookahead: midi_service_config.clock_lookahead.clone(),
+        tempo_trend: TempoTrend::new(&midi_service_config.clock_lookahead),
         send_tempo: midi_service_config.send_tempo.clone(),
+        enable_midi_clock: midi_service_config.enable_midi_clock.clone(),
+        tempo_source: midi_service_config.tempo_source.clone(),
+        daw_bpm: None,
+        clock_anchor,
+        tempo_latency: midi_service_config.tempo_latency.clone(),
+        low_memory: midi_service_config.low_memory,
+        multi_resolution: midi_service_config.multi_resolution.clone(),
+        beat_counter: BeatCounter::new(&midi_service_config.beat_counter),
+        tap_tempo: TapTempo::default(),
+        clock_tempo: midi_service_config.clock_input.enabled.then(|| ClockTempo::new(&midi_service_config.clock_input)),
+        session_stats: SessionStats::new(&midi_service_config.session_stats),
+        tempo_change: TempoChangeDetector::default(),
+        feel_ambiguity_config: midi_service_config.feel_ambiguity.clone(),
+        feel_ambiguity: None,
+        egress_hub: egress_hub(midi_service_config),
+        forward_transport: midi_service_config.daw_link.forward_transport,
+        tempo_bands: TempoBands::new(&midi_service_config.tempo_bands, midi_service_config.tempo_band_hysteresis),
     };
 
     thread::Builder::new()
@@ -181,6 +507,8 @@ pub fn spawn(
 fn spawn_playback_controller<C>(
     enable_midi_clock: ArcAtomicBool,
     clock_interval_microseconds: Arc<AtomicU64>,
+    clock_lookahead_microseconds: Arc<AtomicU64>,
+    clock_lookahead: ClockLookaheadConfig,
     midi_output: Arc<Mutex<C>>,
 ) -> Result<Sender<Playback>>
 where
@@ -197,6 +525,8 @@ where
                 &playback_receiver,
                 &enable_midi_clock.clone(),
                 &clock_interval_microseconds,
+                &clock_lookahead_microseconds,
+                &clock_lookahead,
             )
             .is_err()
             {
@@ -222,11 +552,13 @@ fn clock_emitter_loop<C>(
     playback: &Receiver<Playback>,
     enable_midi_clock: &ArcAtomicBool,
     clock_interval_microseconds: &Arc<AtomicU64>,
+    clock_lookahead_microseconds: &Arc<AtomicU64>,
+    clock_lookahead: &ClockLookaheadConfig,
 ) -> Result<(), ()>
 where
     C: MidiOutput + Send + 'static,
 {
-    let mut next_tick = Instant::now();
+    let mut clock_scheduler = ClockScheduler::new(clock_lookahead, Instant::now());
 
     while enable_midi_clock.load(Ordering::Relaxed) {
         match playback.try_recv() {
@@ -237,9 +569,12 @@ where
         };
 
         let interval_micros = clock_interval_microseconds.load(Ordering::Relaxed).min(1_000_000);
+        let target_lookahead = StdDuration::from_micros(clock_lookahead_microseconds.load(Ordering::Relaxed));
 
         // Calculate when the next tick should happen
-        next_tick += StdDuration::from_micros(interval_micros);
+        let next_tick =
+            clock_scheduler.next_tick(Instant::now(), StdDuration::from_micros(interval_micros), target_lookahead);
+        clock_lookahead.applied.store(clock_scheduler.lookahead().as_micros() as u64, Ordering::Relaxed);
 
         // Sleep for the most part of the interval, leaving a small amount of time for busy-waiting
         while Instant::now() < next_tick.checked_sub(StdDuration::from_millis(1)).unwrap() {
@@ -251,7 +586,7 @@ where
 
         // It's time to send the MIDI Timing Clock event
         clock_emitter.lock().tick(); // Replace with actual call to send MIDI event
-        next_tick = Instant::now() + StdDuration::from_micros(interval_micros);
     }
+    clock_lookahead.applied.store(0, Ordering::Relaxed);
     Ok(())
 }
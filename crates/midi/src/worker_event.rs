@@ -1,24 +1,50 @@
 use crate::{
-    DynamicBPMDetectionParameters, StaticBPMDetectionParameters, StaticMidiMessage, TimedMidiNoteOn,
-    TimedTypedMidiMessage,
+    daw_link_protocol::DawMessage, feel_ambiguity::Feel, DynamicBPMDetectionParameters, StaticBPMDetectionParameters,
+    StaticMidiMessage, TimedMidiNoteOn, TimedTypedMidiMessage,
 };
+use chrono::Duration;
+use instant::Instant;
 use wmidi::MidiMessage;
 
 pub enum WorkerEvent {
     TimedMidiNoteOn(TimedMidiNoteOn),
-    TimingClock,
+    // notes timestamped beforehand, delivered at once by a host or a file, see `BPMDetection::receive_batch`
+    TimedBatch(Vec<TimedMidiNoteOn>),
+    // releases a note held in a chord being formed
+    NoteOff { channel: u8, note: u8 },
+    // tick of the MIDI clock received, at the time of the message
+    TimingClock(Duration),
     Play,
     Stop,
     DynamicBPMDetectionParameters(DynamicBPMDetectionParameters),
     StaticBPMDetectionParameters(StaticBPMDetectionParameters),
+    // tempo sent by the DAW, blended with the detected one depending on the tempo source
+    DawBPM(f32),
+    // the next notes come from another timeline, such as a demo pattern
+    ClearNotes,
+    // the beats and bars are counted again from the next note
+    ResetBeatCounter,
+    // the user tapped the tempo, from the trigger note or the interface
+    Tap(Instant),
+    // the user picked a candidate of the last ambiguity between a straight and a triplet feel
+    ChooseFeel(Feel),
+    // the notes received so far become a reference the parameter changes are also evaluated on, see `FrozenReference`
+    FreezeNotes,
+    ClearFrozenNotes,
+    // play, stop or continue received as SysEx, only forwarded to the DAW link
+    Transport(DawMessage),
 }
 
 impl TryFrom<TimedTypedMidiMessage<StaticMidiMessage>> for WorkerEvent {
     type Error = ();
 
     fn try_from(value: TimedTypedMidiMessage<StaticMidiMessage>) -> errors::Result<Self, Self::Error> {
-        if let MidiMessage::TimingClock = value.midi_message {
-            return Ok(Self::TimingClock);
+        match value.midi_message {
+            MidiMessage::TimingClock => return Ok(Self::TimingClock(value.timestamp)),
+            MidiMessage::NoteOff(channel, note, _) => {
+                return Ok(Self::NoteOff { channel: channel.index(), note: note as u8 });
+            }
+            _ => (),
         }
         Ok(Self::TimedMidiNoteOn(TimedMidiNoteOn::try_from(value)?))
     }
@@ -170,7 +170,10 @@ where
                             }
                             value = Some(map.next_value()?);
                         }
-                        _ => return Err(de::Error::unknown_field(&key, &["enabled", "value"])),
+                        // left out like in the configuration structs, the caller may report it
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
                     }
                 }
                 let enabled = enabled.unwrap_or(true); // Default to true if not present
@@ -1,5 +1,6 @@
-use build::create_build_info;
+use build::{create_base_config, create_build_info};
 
 fn main() {
     create_build_info();
+    create_base_config("config/overlay.toml");
 }
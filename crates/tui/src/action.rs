@@ -1,12 +1,14 @@
+use std::fmt::{self, Display};
+
 use crate::mode::Mode;
 
-use midi::{DynamicBPMDetectionParameters, MidiInputPort, StaticBPMDetectionParameters};
+use midi::{DynamicBPMDetectionParameters, Feel, MidiInputPort, PatternKind, StaticBPMDetectionParameters};
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use strum::{Display, IntoStaticStr, VariantNames};
+use strum::{IntoEnumIterator, IntoStaticStr, VariantNames};
 
-#[derive(Debug, Clone, PartialEq, Eq, Display, VariantNames, IntoStaticStr)]
+#[derive(Debug, Clone, PartialEq, Eq, VariantNames, IntoStaticStr)]
 pub enum Action {
     Tick,
     Render,
@@ -30,6 +32,58 @@ pub enum Action {
     StaticBPMDetectionConfig(StaticBPMDetectionParameters),
     Save,
     ToggleSendTempo,
+    // sends each estimate as an OSC message, see `midi::osc_output`
+    ToggleOscOutput,
+    // switches between the detected tempo, the DAW tempo and a blend of both
+    CycleTempoSource,
+    ExportSnapshot,
+    ExportTempoMap,
+    // writes the configuration in use and the origin of its values to the data directory
+    ExportEffectiveConfig,
+    ToggleAlwaysOnTop,
+    RebindKey,
+    UnbindKey,
+    RestoreDefaultKeys,
+    // disables a default key binding
+    Unbound,
+    // while capturing, keys are not mapped to actions
+    CaptureKeys(bool),
+    // feeds the detection with a synthetic pattern until a MIDI note is received
+    StartDemoPattern(PatternKind),
+    StopDemoPattern,
+    // the beats and bars are counted again from the next note
+    ResetBeatCounter,
+    // taps the tempo, as the trigger note does
+    TapTempo,
+    // favours a candidate of the ambiguity between a straight and a triplet feel, when there is one
+    ChooseFeel(Feel),
+    // keeps the notes received so far as a reference the parameter changes are also evaluated on
+    FreezeNotes,
+    ClearFrozenNotes,
+    // the workspace crates log at debug level for a while, as they do after an error
+    ElevateLogging,
+    // writes the detection parameters in use as the named preset, see `Config::save_preset`
+    SavePreset(String),
+    // applies the detection parameters of the named preset
+    LoadPreset(String),
+    // the presets screen reads the name of a new preset
+    NamePreset,
+    // the highlighted entry of the current screen
+    Select,
+}
+
+// as written in the key bindings, with the argument of the actions that can be bound along with one
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::StartDemoPattern(pattern_kind) => write!(f, "StartDemoPattern({pattern_kind})"),
+            Action::Switch(mode) => write!(f, "Switch({mode})"),
+            Action::ChooseFeel(feel) => write!(f, "ChooseFeel({feel})"),
+            Action::SavePreset(name) => write!(f, "SavePreset({name})"),
+            Action::LoadPreset(name) => write!(f, "LoadPreset({name})"),
+            _ => f.write_str(self.into()),
+        }
+    }
 }
 
 impl Serialize for Action {
@@ -37,7 +91,7 @@ impl Serialize for Action {
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.into())
+        serializer.collect_str(self)
     }
 }
 
@@ -59,10 +113,38 @@ impl<'a> TryFrom<&'a str> for Action {
             "TogglePlayback" => Action::TogglePlayback,
             "ToggleMidiClock" => Action::ToggleMidiClock,
             "ToggleSendTempo" => Action::ToggleSendTempo,
+            "ToggleOscOutput" => Action::ToggleOscOutput,
+            "CycleTempoSource" => Action::CycleTempoSource,
             "MIDIRestart" => Action::MIDIRestart,
             "ShowGUI" => Action::ShowGUI,
             "Save" => Action::Save,
-            _ => return Err(value),
+            "ExportSnapshot" => Action::ExportSnapshot,
+            "ExportTempoMap" => Action::ExportTempoMap,
+            "ExportEffectiveConfig" => Action::ExportEffectiveConfig,
+            "ToggleAlwaysOnTop" => Action::ToggleAlwaysOnTop,
+            "RebindKey" => Action::RebindKey,
+            "UnbindKey" => Action::UnbindKey,
+            "RestoreDefaultKeys" => Action::RestoreDefaultKeys,
+            "Unbound" => Action::Unbound,
+            "StopDemoPattern" => Action::StopDemoPattern,
+            "ResetBeatCounter" => Action::ResetBeatCounter,
+            "TapTempo" => Action::TapTempo,
+            "FreezeNotes" => Action::FreezeNotes,
+            "ClearFrozenNotes" => Action::ClearFrozenNotes,
+            "ElevateLogging" => Action::ElevateLogging,
+            "NamePreset" => Action::NamePreset,
+            "Select" => Action::Select,
+            _ => {
+                let (name, argument) = value.strip_suffix(')').and_then(|value| value.split_once('(')).ok_or(value)?;
+                match name {
+                    "StartDemoPattern" => Action::StartDemoPattern(argument.parse().map_err(|_| value)?),
+                    "Switch" => Action::Switch(Mode::iter().find(|mode| mode.to_string() == argument).ok_or(value)?),
+                    "ChooseFeel" => Action::ChooseFeel(argument.parse().map_err(|_| value)?),
+                    "SavePreset" if !argument.is_empty() => Action::SavePreset(argument.to_string()),
+                    "LoadPreset" if !argument.is_empty() => Action::LoadPreset(argument.to_string()),
+                    _ => return Err(value),
+                }
+            }
         })
     }
 }
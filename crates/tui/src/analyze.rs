@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::Duration;
+use errors::{Report, Result};
+use instant::Instant;
+use midi::{BPMDetection, BpmEstimate, MidiFile, PreDetection, SessionStats, TimedMidiNoteOn};
+
+use crate::config::Config;
+
+// between two printed estimates of a file without measures, as SMPTE timed ones
+const DEFAULT_INTERVAL_MILLISECONDS: i64 = 2000;
+
+/// Analyzing a MIDI file instead of listening to a device, see `run`
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalyzeOptions {
+    pub file: PathBuf,
+    // configuration file loaded instead of the usual one
+    pub config: Option<PathBuf>,
+    // in seconds, between two printed estimates. One per measure otherwise
+    pub interval: Option<f64>,
+}
+
+/// Streams the note-ons of a standard MIDI file through the detection, with the same stages and evaluations as when
+/// listening to a device, the notes being received at their timestamps. Prints the estimate at the end of each measure
+/// or interval on stdout, followed by a summary
+pub fn run(config: Config, analyze_options: &AnalyzeOptions) -> Result<()> {
+    let config = match &analyze_options.config {
+        Some(config_path) if !config_path.is_file() => {
+            return Err(Report::msg(format!("configuration file {} not found", config_path.display())));
+        }
+        Some(config_path) => Config::from_path(config_path)?,
+        None => config,
+    };
+    let interval = match analyze_options.interval {
+        Some(interval) if !(interval > 0.0 && interval.is_finite()) => {
+            return Err(Report::msg(format!("the interval must be a positive number of seconds, not {interval}")));
+        }
+        Some(interval) => Some(Duration::from_std(std::time::Duration::try_from_secs_f64(interval)?)?),
+        None => None,
+    };
+    let bytes = fs::read(&analyze_options.file)
+        .map_err(|e| Report::msg(format!("could not read {}: {e}", analyze_options.file.display())))?;
+    let midi_file = MidiFile::read(&bytes)
+        .map_err(|e| Report::msg(format!("could not read {}: {e}", analyze_options.file.display())))?;
+    analyze(&config, &midi_file, interval, &mut io::stdout().lock())
+}
+
+fn analyze(config: &Config, midi_file: &MidiFile, interval: Option<Duration>, output: &mut impl Write) -> Result<()> {
+    let Some(last_note) = midi_file.notes.last() else {
+        writeln!(output, "no note in the file")?;
+        return Ok(());
+    };
+
+    // label and start of each line, the last one holds the last note
+    let periods = match interval {
+        None if !midi_file.measures.is_empty() => midi_file
+            .measures
+            .iter()
+            .enumerate()
+            .map(|(index, start)| (format!("bar {:<4} {}", index + 1, format_timestamp(*start)), *start))
+            .collect::<Vec<_>>(),
+        _ => {
+            let interval = interval.unwrap_or_else(|| Duration::milliseconds(DEFAULT_INTERVAL_MILLISECONDS));
+            let mut starts = vec![Duration::zero()];
+            while let Some(next) =
+                starts.last().map(|start| *start + interval).filter(|next| *next <= last_note.timestamp)
+            {
+                starts.push(next);
+            }
+            starts.into_iter().map(|start| (format_timestamp(start), start)).collect()
+        }
+    };
+
+    let mut bpm_detection =
+        BPMDetection::with_low_memory(config.static_bpm_detection_parameters.clone(), config.low_memory);
+    bpm_detection.set_multi_resolution(&config.midi.multi_resolution);
+    let dynamic_bpm_detection_parameters = &config.dynamic_bpm_detection_parameters;
+    let mut pre_detection =
+        PreDetection::new(&config.static_bpm_detection_parameters, dynamic_bpm_detection_parameters);
+    let mut session_stats = SessionStats::new(&config.midi.session_stats);
+    let mut estimate = None;
+    let mut period = 0;
+    let origin = Instant::now();
+    let mut onsets = Vec::new();
+    for note in &midi_file.notes {
+        let now = origin + note.timestamp.to_std().unwrap_or_default();
+        // chords completed by the time this note is received belong to the previous periods
+        pre_detection.flush(dynamic_bpm_detection_parameters, now, |onset| onsets.push(onset));
+        evaluate(&mut pre_detection, &mut bpm_detection, config, &mut onsets, &mut session_stats, &mut estimate);
+        while periods.get(period + 1).is_some_and(|(_, start)| note.timestamp >= *start) {
+            write_estimate(output, &periods[period].0, estimate)?;
+            period += 1;
+        }
+        pre_detection.note_on(dynamic_bpm_detection_parameters, note.clone(), now, |onset| onsets.push(onset));
+        evaluate(&mut pre_detection, &mut bpm_detection, config, &mut onsets, &mut session_stats, &mut estimate);
+    }
+    pre_detection.finish(dynamic_bpm_detection_parameters, |onset| onsets.push(onset));
+    evaluate(&mut pre_detection, &mut bpm_detection, config, &mut onsets, &mut session_stats, &mut estimate);
+    for (label, _) in &periods[period..] {
+        write_estimate(output, label, estimate)?;
+    }
+
+    match estimate {
+        Some(BpmEstimate { bpm, confidence }) => {
+            writeln!(output, "final estimate: {bpm:.2} BPM, confidence {confidence:.2}")?;
+        }
+        None => writeln!(output, "final estimate: -")?,
+    }
+    writeln!(output, "{}", session_stats.summary())?;
+    Ok(())
+}
+
+// passes the onsets let through to the detection, and evaluates it when there are some
+fn evaluate(
+    pre_detection: &mut PreDetection,
+    bpm_detection: &mut BPMDetection,
+    config: &Config,
+    onsets: &mut Vec<TimedMidiNoteOn>,
+    session_stats: &mut SessionStats,
+    estimate: &mut Option<BpmEstimate>,
+) {
+    let Some(newest_onset) = onsets.last().map(|onset| onset.timestamp) else {
+        return;
+    };
+    for onset in onsets.drain(..) {
+        session_stats.note();
+        bpm_detection.receive_midi_message(onset);
+    }
+    if let Some(bpm_estimate) = pre_detection.evaluate(bpm_detection, &config.dynamic_bpm_detection_parameters) {
+        session_stats.estimate(bpm_estimate.bpm, newest_onset.to_std().unwrap_or_default());
+        *estimate = Some(bpm_estimate);
+    }
+}
+
+// the estimate of the notes received so far, which may be an earlier one when no note was received since
+fn write_estimate(output: &mut impl Write, label: &str, estimate: Option<BpmEstimate>) -> io::Result<()> {
+    match estimate {
+        Some(BpmEstimate { bpm, confidence }) => {
+            writeln!(output, "{label}  {bpm:>7.2} BPM  confidence {confidence:.2}")
+        }
+        None => writeln!(output, "{label}        -"),
+    }
+}
+
+fn format_timestamp(timestamp: Duration) -> String {
+    let milliseconds = timestamp.num_milliseconds();
+    format!("{:02}:{:02}.{:03}", milliseconds / 60_000, milliseconds / 1000 % 60, milliseconds % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi::midi_messages::{MidiNoteOn, TimedMidiNoteOn};
+
+    // a note per beat at 100 BPM over 8 measures of 4/4
+    fn midi_file() -> MidiFile {
+        MidiFile {
+            notes: (0..32)
+                .map(|beat| TimedMidiNoteOn {
+                    timestamp: Duration::milliseconds(600) * beat,
+                    midi_message: MidiNoteOn { channel: 9, note: 36, velocity: 100 },
+                })
+                .collect(),
+            measures: (0..8).map(|measure| Duration::milliseconds(2400) * measure).collect(),
+        }
+    }
+
+    fn analyzed(interval: Option<Duration>) -> Vec<String> {
+        let config = Config::base_config().unwrap();
+        let mut output = Vec::new();
+        analyze(&config, &midi_file(), interval, &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(ToString::to_string).collect()
+    }
+
+    // the tempo printed before "BPM"
+    fn bpm(line: &str) -> f32 {
+        line.split(" BPM").next().and_then(|start| start.split_whitespace().last()).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_per_measure() {
+        let lines = analyzed(None);
+        // one line per measure, the final estimate and the session summary
+        assert_eq!(lines.len(), 10, "{lines:#?}");
+        assert!(lines[0].starts_with("bar 1    00:00.000"), "{lines:#?}");
+        assert!(lines[7].starts_with("bar 8    00:16.800"), "{lines:#?}");
+        assert!((bpm(&lines[7]) - 100.0).abs() < 1.0, "{lines:#?}");
+        assert!(lines[8].starts_with("final estimate:"), "{lines:#?}");
+        assert!((bpm(&lines[8]) - 100.0).abs() < 1.0, "{lines:#?}");
+        assert!(lines[9].contains("32 notes"), "{lines:#?}");
+    }
+
+    #[test]
+    fn test_interval() {
+        let lines = analyzed(Some(Duration::seconds(5)));
+        // 0, 5, 10 and 15 seconds, the last note is at 18.6
+        assert_eq!(lines.len(), 6, "{lines:#?}");
+        assert!(lines[3].starts_with("00:15.000"), "{lines:#?}");
+    }
+
+    #[test]
+    fn test_chords() {
+        let mut config = Config::base_config().unwrap();
+        config.dynamic_bpm_detection_parameters.max_simultaneous_onsets = 1;
+        // each beat a chord of three tones
+        let mut chords = midi_file();
+        chords.notes = chords
+            .notes
+            .iter()
+            .flat_map(|note| {
+                (0..3u8).map(|tone| TimedMidiNoteOn {
+                    timestamp: note.timestamp + Duration::milliseconds(i64::from(tone) * 2),
+                    midi_message: MidiNoteOn { note: 60 + tone * 4, ..note.midi_message },
+                })
+            })
+            .collect();
+        let mut output = Vec::new();
+        analyze(&config, &chords, None, &mut output).unwrap();
+        let lines = String::from_utf8(output).unwrap().lines().map(ToString::to_string).collect::<Vec<_>>();
+        assert!((bpm(&lines[8]) - 100.0).abs() < 1.0, "{lines:#?}");
+        // an onset per chord
+        assert!(lines[9].contains("32 notes"), "{lines:#?}");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(Duration::milliseconds(754_321)), "12:34.321");
+    }
+}
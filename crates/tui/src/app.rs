@@ -1,10 +1,17 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
 use log::{debug, info};
-use std::sync::mpsc::SyncSender;
+use std::{
+    sync::{mpsc::SyncSender, Arc},
+    time::Instant,
+};
 
-use errors::{error_backtrace, Result};
-use gui::GuiRemote;
+use errors::{elevate_logging, error_backtrace, error_debug_window, register_crash_section, Result};
+use gui::{
+    effective_config::{register_parameters_crash_section, DetectionParameters},
+    GuiRemote,
+};
 use ratatui::prelude::Rect;
+use sync::{ArcRwLockExt, Mutex};
 
 use errors::LogErrorWithExt;
 use tokio::sync::{
@@ -13,8 +20,15 @@ use tokio::sync::{
 };
 
 use crate::{
-    components::{midi_display::MidiDisplay, select_device::SelectDevice, ComponentNewBox},
-    services::{midi::MidiService, screens::Screens},
+    components::{
+        histogram_view::HistogramView, keybindings_editor::KeyBindingsEditor, midi_display::MidiDisplay,
+        preset_select::PresetSelect, select_device::SelectDevice, ComponentNewBox,
+    },
+    config_saver::ConfigSaver,
+    config_warnings::ConfigWarningsForwarder,
+    key_sequence::{self, KeySequenceMatcher},
+    services::{config_watcher::ConfigWatcher, midi::MidiService, remote::RemoteService, screens::Screens},
+    tempo_recorder::TempoRecorder,
     tui::Event,
 };
 
@@ -33,9 +47,10 @@ pub async fn run_tui(
     start_gui: SyncSender<()>,
     action_tx: UnboundedSender<Action>,
     mut action_rx: UnboundedReceiver<Action>,
-    config: Config,
+    mut config: Config,
     mut gui_exit_receiver: UnboundedReceiver<()>,
     gui_remote: GuiRemote,
+    config_saver: ConfigSaver,
 ) -> Result<()> {
     let (event_tx, mut event_rx) = mpsc::unbounded_channel();
 
@@ -95,66 +110,120 @@ pub async fn run_tui(
         })
     });
 
-    let mut components = [SelectDevice::box_new(), MidiDisplay::box_new()];
+    let mut components = [
+        SelectDevice::box_new(),
+        MidiDisplay::box_new(),
+        KeyBindingsEditor::box_new(),
+        PresetSelect::box_new(),
+        HistogramView::box_new(),
+    ];
     for component in &mut components {
         component.register_config_handler(config.clone())?;
     }
 
-    let mut services = [
-        MidiService::box_new(
-            &config.midi,
-            config.static_bpm_detection_parameters.clone(),
-            config.dynamic_bpm_detection_parameters.clone(),
-            event_tx.clone(),
-            gui_remote.clone(),
-        )
-        .await?,
-        Box::<Screens>::default(),
+    let tempo_recorder = TempoRecorder::new(gui_remote.clone(), &config.tempo_map);
+
+    let detection_parameters = Arc::new(Mutex::new(DetectionParameters {
+        static_bpm_detection_parameters: config.static_bpm_detection_parameters.clone(),
+        dynamic_bpm_detection_parameters: config.dynamic_bpm_detection_parameters.clone(),
+    }));
+    let _crash_sections = [
+        register_parameters_crash_section("detection parameters", detection_parameters.clone()),
+        register_crash_section("diagnostics", {
+            let rate_limited_notes = config.midi.rate_limited_notes.clone();
+            let tempo_latency = config.midi.tempo_latency.clone();
+            let session_summary = tempo_recorder.session_summary_source();
+            move || {
+                format!(
+                    "rate limited notes: {}\ntempo latency: {}\n{}",
+                    rate_limited_notes.get(),
+                    tempo_latency.try_summary().map_or_else(|| "-".to_string(), |summary| summary.to_string()),
+                    session_summary().map_or_else(|| "session: -".to_string(), |summary| summary.to_string())
+                )
+            }
+        }),
     ];
+
+    let bpm_detection_receiver = ConfigWarningsForwarder::new(tempo_recorder.clone(), event_tx.clone());
+    let detection_service = match &config.connect {
+        Some(address) => RemoteService::box_new(address, action_tx.clone(), bpm_detection_receiver).await?,
+        None => {
+            MidiService::box_new(
+                &config.midi,
+                config.static_bpm_detection_parameters.clone(),
+                config.dynamic_bpm_detection_parameters.clone(),
+                event_tx.clone(),
+                bpm_detection_receiver,
+            )
+            .await?
+        }
+    };
+    let config_watcher = ConfigWatcher::box_new(&config, config_saver.last_written(), action_tx.clone());
+    let mut services = [detection_service, Box::<Screens>::default(), config_watcher];
     let mut should_quit = false;
     let mut should_suspend = false;
     let mut mode = Mode::DeviceView;
     action_tx.send(Action::Switch(mode))?;
 
-    let mut last_tick_key_events = Vec::new();
+    let mut key_sequence = KeySequenceMatcher::new(config.key_sequence_timeout);
+    // the GUI can only be started once, afterwards showing it brings its window to front
+    let mut gui_started = false;
+    // the key bindings editor is capturing a new sequence
+    let mut capturing_keys = false;
 
     let mut tui = tui::Tui::new(event_tx.clone())?.tick_rate(config.tick_rate).frame_rate(config.frame_rate);
     tui.enter()?;
 
     loop {
-        if let Some(e) = event_rx.recv().await {
+        // pending keys expire at their own deadline, whatever the tick rate
+        let event = match key_sequence.deadline() {
+            Some(deadline) => {
+                if let Ok(event) = tokio::time::timeout_at(deadline.into(), event_rx.recv()).await {
+                    event
+                } else {
+                    let action =
+                        config.keybindings.get(|keybindings| key_sequence.expire(keybindings, mode, Instant::now()));
+                    if let Some(action) = action {
+                        info!("Got action: {action:?}");
+                        action_tx.send(action)?;
+                    }
+                    None
+                }
+            }
+            None => event_rx.recv().await,
+        };
+        if let Some(e) = event {
             match e {
                 Event::Tick => action_tx.send(Action::Tick)?,
                 Event::Render => action_tx.send(Action::Render)?,
                 Event::Resize(x, y) => action_tx.send(Action::Resize(x, y))?,
-                Event::FocusGained => gui_remote.always_on_top(),
-                Event::FocusLost => gui_remote.always_on_top_cancel(),
-                Event::Key(key) => {
-                    for mapping in [config.keybindings.get(&None), config.keybindings.get(&Some(mode))].iter().flatten()
-                    {
-                        if let Some(action) = mapping.get(&vec![key]) {
-                            info!("Got action: {action:?}");
-                            action_tx.send(action.clone())?;
-                        } else {
-                            // If the key was not handled as a single key action,
-                            // then consider it for multi-key combinations.
-                            last_tick_key_events.push(key);
-
-                            // Check for multi-key combinations
-                            if let Some(action) = mapping.get(&last_tick_key_events) {
-                                info!("Got action: {action:?}");
-                                action_tx.send(action.clone())?;
-                            }
-                        };
+                Event::FocusGained => gui_remote.set_tui_focused(true),
+                Event::FocusLost => gui_remote.set_tui_focused(false),
+                Event::Key(key) if !capturing_keys => {
+                    let actions =
+                        config.keybindings.get(|keybindings| key_sequence.key(key, keybindings, mode, Instant::now()));
+                    for action in actions {
+                        info!("Got action: {action:?}");
+                        action_tx.send(action)?;
                     }
                 }
                 Event::Init
+                | Event::Key(_)
                 | Event::Error
                 | Event::Paste(_)
                 | Event::Mouse(_)
                 | Event::DeviceChangeDetected
                 | Event::DeviceList(_)
-                | Event::Midi(_) => (),
+                | Event::Midi(_)
+                | Event::ConfigWarnings(_)
+                | Event::BarPosition(_)
+                | Event::TappedTempo(_)
+                | Event::NoteFreshness(_)
+                | Event::Estimate(_)
+                | Event::FeelAmbiguity(_)
+                | Event::TempoBand(_)
+                | Event::Histogram(..)
+                | Event::DawBpm(_) => (),
             }
 
             // duplicate because despite having both Service and Component implementing the same EventHandler trait,
@@ -181,9 +250,6 @@ pub async fn run_tui(
                 debug!("{action:?}");
             }
             match action {
-                Action::Tick => {
-                    last_tick_key_events.drain(..);
-                }
                 Action::Quit => should_quit = true,
                 Action::Suspend => should_suspend = true,
 
@@ -198,6 +264,7 @@ pub async fn run_tui(
                                 }
                             }
                         }
+                        key_sequence::draw_pending(f, f.size(), key_sequence.pending());
                     })?;
                 }
                 Action::Render => {
@@ -210,11 +277,56 @@ pub async fn run_tui(
                                 }
                             }
                         }
+                        key_sequence::draw_pending(f, f.size(), key_sequence.pending());
                     })?;
                 }
                 Action::Switch(new_mode) => mode = new_mode,
-                Action::ShowGUI => start_gui.send(()).log_error_msg("unable to start GUI")?,
-                Action::Save => gui_remote.save_config(),
+                Action::ShowGUI if gui_started => gui_remote.focus_window(),
+                Action::ShowGUI => {
+                    start_gui.send(()).log_error_msg("unable to start GUI")?;
+                    gui_started = true;
+                }
+                Action::ToggleAlwaysOnTop => gui_remote.cycle_always_on_top(),
+                // the GUI picks up the parameters changed outside of it on its next frame
+                Action::Refresh => gui_remote.request_repaint(),
+                Action::Save if gui_started => gui_remote.save_config(),
+                // the GUI saves the configuration along with its parameters, until it is started the TUI saves it
+                Action::Save => {
+                    config_saver.save(&config).log_error_msg("Could not save configuration").ok();
+                }
+                Action::SavePreset(ref name) => {
+                    config.save_preset(name).log_error_msg("could not save preset").ok();
+                }
+                // applied as the GUI applies its parameters, so the detection picks them up live
+                Action::LoadPreset(ref name) => {
+                    if let Ok(preset) = config.load_preset(name).log_error_msg("could not load preset") {
+                        action_tx.send(Action::StaticBPMDetectionConfig(preset.static_bpm_detection_parameters))?;
+                        action_tx.send(Action::DynamicBPMDetectionConfig(preset.dynamic_bpm_detection_parameters))?;
+                    }
+                }
+                Action::CaptureKeys(capturing) => capturing_keys = capturing,
+                Action::ExportSnapshot => gui_remote.export_snapshot(),
+                Action::ExportTempoMap => {
+                    tempo_recorder.export().log_error_msg("could not export tempo map").ok();
+                }
+                Action::ExportEffectiveConfig => {
+                    config.export_effective_config().log_error_msg("could not export effective config").ok();
+                }
+                Action::ElevateLogging => {
+                    if elevate_logging(error_debug_window()) {
+                        info!("debug logs for the next {:?}", error_debug_window());
+                    }
+                }
+                Action::StaticBPMDetectionConfig(ref static_bpm_detection_parameters) => {
+                    config.static_bpm_detection_parameters = static_bpm_detection_parameters.clone();
+                    detection_parameters.lock().static_bpm_detection_parameters =
+                        static_bpm_detection_parameters.clone();
+                }
+                Action::DynamicBPMDetectionConfig(ref dynamic_bpm_detection_parameters) => {
+                    config.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters.clone();
+                    detection_parameters.lock().dynamic_bpm_detection_parameters =
+                        dynamic_bpm_detection_parameters.clone();
+                }
                 _ => {}
             }
 
@@ -248,6 +360,7 @@ pub async fn run_tui(
         }
     }
     tui.exit().await?;
+    println!("{}", tempo_recorder.session_summary());
 
     signal_task.abort();
     gui_close_task.abort();
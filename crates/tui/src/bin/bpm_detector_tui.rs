@@ -10,7 +10,17 @@ use gui::{create_gui, start_gui, GuiRemote};
 
 use errors::initialize_panic_handler;
 use tui::{
-    action::Action, app::run_tui, cli::update_config, config::Config, live_parameters::LiveParameters,
+    action::Action,
+    analyze,
+    app::run_tui,
+    cli::update_config,
+    config::Config,
+    config_broadcaster::ConfigBroadcaster,
+    config_saver::ConfigSaver,
+    gui_only::{self, SharedDeviceChoice},
+    headless,
+    live_parameters::LiveParameters,
+    safe_mode,
     services::crossterm::reset_crossterm,
 };
 
@@ -20,6 +30,9 @@ async fn tokio_main(
     action_rx: UnboundedReceiver<Action>,
     config: Config,
     gui_remote: GuiRemote,
+    // the GUI runs without the TUI, see `gui_only::run`
+    device_choice: Option<SharedDeviceChoice>,
+    config_saver: ConfigSaver,
 ) -> Result<()> {
     let (gui_exit_sender, gui_exit_receiver) = mpsc::unbounded_channel();
     let (tokio_has_exited_sender, tokio_has_exited_receiver) = sync_channel(0);
@@ -28,7 +41,15 @@ async fn tokio_main(
         info!("waiting for clean exit");
         tokio_has_exited_receiver.recv().ok(); // this blocks until tokio has exited
     });
-    run_tui(start_gui, action_tx, action_rx, config, gui_exit_receiver, gui_remote.clone()).await?;
+    if let Some(device_choice) = device_choice {
+        gui_only::run(start_gui, action_tx, action_rx, config, gui_exit_receiver, gui_remote.clone(), device_choice)
+            .await?;
+    } else {
+        run_tui(start_gui, action_tx, action_rx, config, gui_exit_receiver, gui_remote.clone(), config_saver.clone())
+            .await?;
+    }
+    // a save requested right before leaving
+    config_saver.flush().await;
     tokio_has_exited_sender.try_send(()).ok();
     gui_remote.close();
     // Nothing should be added here : due to macOS application lifecycle, once the GUI exits, which happens when
@@ -39,24 +60,53 @@ async fn tokio_main(
 fn main() -> Result<()> {
     initialize_logging()?;
     initialize_panic_handler(reset_crossterm)?;
-    let config = Config::new()?;
+    let config = if safe_mode::requested() { Config::safe_mode()? } else { Config::new()? };
     let config = match update_config(config) {
-        Ok(args) => args,
+        Ok(Some(args)) => args,
+        Ok(None) => return Ok(()),
         Err(e) => {
             e.print()?;
             return Ok(());
         }
     };
 
+    if let Some(analyze_options) = config.analyze.clone() {
+        return analyze::run(config, &analyze_options);
+    }
+
+    if let Some(headless_options) = config.headless.clone() {
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        return runtime.block_on(headless::run(config, headless_options));
+    }
+
     let (action_tx, action_rx) = mpsc::unbounded_channel();
 
-    let (gui_remote, app_builder) = create_gui(LiveParameters { action_tx: action_tx.clone(), config: config.clone() });
+    let device_choice = config.gui_only.then(SharedDeviceChoice::default);
+    let (config_saver, config_saver_task) = ConfigSaver::new(action_tx.clone());
+    let (config_broadcaster, config_broadcaster_task) = ConfigBroadcaster::new(action_tx.clone());
+    let (gui_remote, app_builder) = create_gui(LiveParameters {
+        action_tx: action_tx.clone(),
+        config: config.clone(),
+        config_saver: config_saver.clone(),
+        config_broadcaster,
+        device_choice: device_choice.clone(),
+    });
 
     // "runtime" must not be dropped, so it cannot be inlined with `spawn` here. Otherwise, the executor will
     // immediately exit
     let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
     let (should_start_gui_sender, should_start_gui_receiver) = sync_channel(0);
-    runtime.spawn(tokio_main(should_start_gui_sender, action_tx.clone(), action_rx, config.clone(), gui_remote));
+    runtime.spawn(config_saver_task.run());
+    runtime.spawn(config_broadcaster_task.run());
+    runtime.spawn(tokio_main(
+        should_start_gui_sender,
+        action_tx.clone(),
+        action_rx,
+        config.clone(),
+        gui_remote,
+        device_choice,
+        config_saver,
+    ));
 
     if should_start_gui_receiver.recv().is_ok() {
         start_gui(app_builder)?;
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::Duration;
+use instant::Instant;
+use midi::{
+    bpm::{beat_duration_to_bpm, bpm_to_beat_duration},
+    chord_filter::CHORD_WINDOW,
+};
+
+// intervals the median is taken from
+const INTERVALS: usize = 8;
+// notes are counted over that period, channels without a note for that long are dropped
+pub const ACTIVITY_WINDOW: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Default)]
+struct ChannelOnsets {
+    last_onset: Option<Duration>,
+    intervals: VecDeque<Duration>,
+    received: VecDeque<Instant>,
+}
+
+/// Rough tempo of one channel, see `ChannelTempoTracker`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelEstimate {
+    pub channel: u8,
+    // none until two onsets were received
+    pub bpm: Option<f32>,
+    pub notes: usize,
+}
+
+/// Approximate tempo of each MIDI channel, from the median of its last inter-onset intervals. Independent of the
+/// detection, for a quick look at what each instrument is playing
+#[derive(Default)]
+pub struct ChannelTempoTracker {
+    channels: BTreeMap<u8, ChannelOnsets>,
+}
+
+impl ChannelTempoTracker {
+    /// `timestamp` is the one of the MIDI message, `now` when it was received
+    pub fn note_on(&mut self, channel: u8, timestamp: Duration, now: Instant) {
+        let channel_onsets = self.channels.entry(channel).or_default();
+        channel_onsets.received.push_back(now);
+        let chord_window = Duration::from_std(CHORD_WINDOW).unwrap_or_default();
+        match channel_onsets.last_onset.map(|last_onset| timestamp - last_onset) {
+            // notes of a chord are a single onset
+            Some(interval) if interval >= Duration::zero() && interval <= chord_window => return,
+            Some(interval) if interval > chord_window => {
+                channel_onsets.intervals.push_back(interval);
+                let exceed = channel_onsets.intervals.len().saturating_sub(INTERVALS);
+                channel_onsets.intervals.drain(..exceed);
+            }
+            // first note, or the timestamps started over
+            _ => (),
+        }
+        channel_onsets.last_onset = Some(timestamp);
+    }
+
+    /// Drops the notes older than `ACTIVITY_WINDOW`, and the channels left without any
+    pub fn evict(&mut self, now: Instant) {
+        self.channels.retain(|_, channel_onsets| {
+            while channel_onsets
+                .received
+                .front()
+                .is_some_and(|received| now.saturating_duration_since(*received) > ACTIVITY_WINDOW)
+            {
+                channel_onsets.received.pop_front();
+            }
+            !channel_onsets.received.is_empty()
+        });
+    }
+
+    #[must_use]
+    pub fn estimates(&self, lowest_bpm: f32, highest_bpm: f32) -> Vec<ChannelEstimate> {
+        self.channels
+            .iter()
+            .map(|(channel, channel_onsets)| ChannelEstimate {
+                channel: *channel,
+                bpm: median(&channel_onsets.intervals)
+                    .map(|interval| fold_into_range(interval, lowest_bpm, highest_bpm)),
+                notes: channel_onsets.received.len(),
+            })
+            .collect()
+    }
+}
+
+fn median(intervals: &VecDeque<Duration>) -> Option<Duration> {
+    let mut intervals = intervals.iter().copied().collect::<Vec<_>>();
+    intervals.sort_unstable();
+    let middle = intervals.len() / 2;
+    match intervals.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((intervals[middle - 1] + intervals[middle]) / 2),
+        _ => Some(intervals[middle]),
+    }
+}
+
+/// Tempo of a beat lasting `interval`, doubled or halved until it is within the range. When the range is narrower
+/// than an octave, a tempo below it may stay there
+fn fold_into_range(interval: Duration, lowest_bpm: f32, highest_bpm: f32) -> f32 {
+    let shortest_beat = bpm_to_beat_duration(highest_bpm);
+    let longest_beat = bpm_to_beat_duration(lowest_bpm);
+    let mut interval = interval;
+    while interval < shortest_beat {
+        interval = interval * 2;
+    }
+    while interval > longest_beat && interval / 2 >= shortest_beat {
+        interval = interval / 2;
+    }
+    beat_duration_to_bpm(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 0.01, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_median() {
+        let intervals = |millis: &[i64]| millis.iter().copied().map(Duration::milliseconds).collect::<VecDeque<_>>();
+        assert_eq!(median(&intervals(&[])), None);
+        assert_eq!(median(&intervals(&[500, 100, 300])), Some(Duration::milliseconds(300)));
+        assert_eq!(median(&intervals(&[500, 100, 300, 400])), Some(Duration::milliseconds(350)));
+    }
+
+    #[test]
+    fn test_fold_into_range() {
+        let bpm = |millis| Some(fold_into_range(Duration::milliseconds(millis), 90.0, 180.0));
+        assert_close(bpm(500), 120.0);
+        // eighth notes and half notes
+        assert_close(bpm(250), 120.0);
+        assert_close(bpm(1000), 120.0);
+        assert_close(bpm(125), 120.0);
+        assert_close(bpm(4000), 120.0);
+        // narrower than an octave, tempos that don't fit stay below the range
+        assert_close(Some(fold_into_range(Duration::milliseconds(300), 110.0, 130.0)), 100.0);
+        assert_close(Some(fold_into_range(Duration::milliseconds(800), 110.0, 130.0)), 75.0);
+    }
+
+    #[test]
+    fn test_channels() {
+        let start = Instant::now();
+        let mut tracker = ChannelTempoTracker::default();
+        for beat in 0..16 {
+            let timestamp = Duration::milliseconds(beat * 500);
+            let now = start + timestamp.to_std().unwrap();
+            // a chord every beat on channel 0
+            tracker.note_on(0, timestamp, now);
+            tracker.note_on(0, timestamp + Duration::milliseconds(3), now);
+            // eighth notes on channel 9, one of them a bit late
+            tracker.note_on(9, timestamp, now);
+            tracker.note_on(9, timestamp + Duration::milliseconds(if beat == 3 { 280 } else { 250 }), now);
+        }
+        let estimates = tracker.estimates(90.0, 180.0);
+        assert_eq!(
+            estimates.iter().map(|estimate| (estimate.channel, estimate.notes)).collect::<Vec<_>>(),
+            vec![(0, 32), (9, 32)]
+        );
+        assert_close(estimates[0].bpm, 120.0);
+        assert_close(estimates[1].bpm, 120.0);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let start = Instant::now();
+        let mut tracker = ChannelTempoTracker::default();
+        tracker.note_on(0, Duration::zero(), start);
+        tracker.note_on(1, Duration::zero(), start);
+        let later = start + ACTIVITY_WINDOW / 2;
+        tracker.note_on(1, Duration::milliseconds(500), later);
+        assert_eq!(tracker.estimates(90.0, 180.0)[0].bpm, None);
+
+        tracker.evict(start + ACTIVITY_WINDOW);
+        assert_eq!(tracker.estimates(90.0, 180.0).len(), 2);
+
+        // channel 0 is gone, channel 1 only counts its last note
+        tracker.evict(start + ACTIVITY_WINDOW + std::time::Duration::from_millis(1));
+        let estimates = tracker.estimates(90.0, 180.0);
+        assert_eq!(estimates.len(), 1);
+        assert_eq!(estimates[0].channel, 1);
+        assert_eq!(estimates[0].notes, 1);
+        assert_close(estimates[0].bpm, 120.0);
+
+        tracker.evict(later + ACTIVITY_WINDOW * 2);
+        assert!(tracker.estimates(90.0, 180.0).is_empty());
+    }
+}
@@ -1,13 +1,21 @@
-use crate::config::Config;
+use crate::{analyze::AnalyzeOptions, config::Config, headless::HeadlessOptions, safe_mode::SAFE_MODE_FLAG};
 
-use crate::utils::version;
+use crate::utils::{version, version_json};
 use clap::{
     builder::{_AutoValueParser, via_prelude::_ValueParserViaParse},
-    Arg, Command, Error,
+    error::ErrorKind,
+    Arg, ArgAction, Command, Error,
+};
+use gui::parameter_catalog;
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::PathBuf,
 };
-use std::env;
 
-pub fn update_config(config: Config) -> Result<Config, Error> {
+/// Returns `None` when the command line only asked for information, which is already printed, and the program should
+/// exit
+pub fn update_config(mut config: Config) -> Result<Option<Config>, Error> {
     let matches = Command::new(clap::crate_name!())
         .author(clap::crate_authors!())
         .version(version())
@@ -30,10 +38,125 @@ pub fn update_config(config: Config) -> Result<Config, Error> {
                 .help("Frame rate, i.e. number of frames per second")
                 .default_value(config.frame_rate.to_string()),
         )
+        .arg(
+            Arg::new("safe_mode")
+                .long(SAFE_MODE_FLAG.trim_start_matches('-'))
+                .action(ArgAction::SetTrue)
+                .help("Start with the built-in configuration, the configuration file is renamed aside"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Listen to a MIDI device without the terminal UI and the GUI, until interrupted"),
+        )
+        .arg(Arg::new("gui_only").long("gui-only").action(ArgAction::SetTrue).conflicts_with("headless").help(
+            "Start the GUI without the terminal UI, the MIDI input is picked in its settings panel. The default when \
+             not started from a terminal",
+        ))
+        .arg(
+            Arg::new("connect")
+                .long("connect")
+                .value_name("HOST:PORT")
+                .conflicts_with_all(["headless", "gui_only"])
+                .help(
+                    "Control a running plugin instance instead of listening to MIDI, its remote control server must \
+                     be enabled",
+                ),
+        )
+        .arg(
+            Arg::new("json_status")
+                .long("json-status")
+                .action(ArgAction::SetTrue)
+                .requires("headless")
+                .help("Print each tempo change as a JSON line on stdout, and a summary line when stopping"),
+        )
+        .arg(
+            Arg::new("device")
+                .long("device")
+                .value_name("NAME")
+                .requires("headless")
+                .help("Part of the name of the device to listen to when headless, the first device otherwise"),
+        )
+        .arg(
+            Arg::new("version_json")
+                .long("version-json")
+                .action(ArgAction::SetTrue)
+                .help("Print version, build and configuration paths as JSON"),
+        )
+        .arg(
+            Arg::new("dump_parameters")
+                .long("dump-parameters")
+                .action(ArgAction::SetTrue)
+                .help("Print every parameter with its range, step and default as JSON, for controller mapping tools"),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Print the tempo detected in a MIDI file for each measure, and a summary")
+                .arg(
+                    Arg::new("file")
+                        .value_parser(_AutoValueParser::<PathBuf>::new().value_parser())
+                        .value_name("FILE.mid")
+                        .required(true)
+                        .help("Standard MIDI file to analyze, its tempo map times the notes"),
+                )
+                .arg(
+                    Arg::new("config")
+                        .value_parser(_AutoValueParser::<PathBuf>::new().value_parser())
+                        .long("config")
+                        .value_name("PATH")
+                        .help("Configuration file to read the detection parameters from, instead of the usual one"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .value_parser(_AutoValueParser::<f64>::new().value_parser())
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .help("Print the tempo at this interval instead of once per measure"),
+                ),
+        )
         .try_get_matches()?;
 
+    if matches.get_flag("version_json") {
+        match version_json() {
+            Ok(version_json) => println!("{version_json}"),
+            Err(e) => return Err(Error::raw(ErrorKind::Io, format!("{e:?}"))),
+        }
+        return Ok(None);
+    }
+
+    if matches.get_flag("dump_parameters") {
+        match serde_json::to_string_pretty(&parameter_catalog()) {
+            Ok(parameters_json) => println!("{parameters_json}"),
+            Err(e) => return Err(Error::raw(ErrorKind::Io, format!("{e:?}"))),
+        }
+        return Ok(None);
+    }
+
     let _tick_rate = *matches.get_one::<f64>("tick_rate").unwrap();
     let _frame_rate = *matches.get_one::<f64>("frame_rate").unwrap();
 
-    Ok(config)
+    if matches.get_flag("headless") {
+        config.headless = Some(HeadlessOptions {
+            json_status: matches.get_flag("json_status"),
+            device: matches.get_one::<String>("device").cloned(),
+        });
+    }
+
+    config.connect = matches.get_one::<String>("connect").cloned();
+
+    if let Some(analyze_matches) = matches.subcommand_matches("analyze") {
+        config.analyze = Some(AnalyzeOptions {
+            file: analyze_matches.get_one::<PathBuf>("file").cloned().unwrap_or_default(),
+            config: analyze_matches.get_one::<PathBuf>("config").cloned(),
+            interval: analyze_matches.get_one::<f64>("interval").copied(),
+        });
+    }
+
+    // started from a file manager or an application bundle, there is no terminal to draw the TUI in. The modes that
+    // don't draw it, or that the GUI alone can't run, are left as they are
+    let terminal_mode = config.headless.is_some() || config.connect.is_some() || config.analyze.is_some();
+    config.gui_only = matches.get_flag("gui_only") || (!terminal_mode && !io::stdin().is_terminal());
+
+    Ok(Some(config))
 }
@@ -0,0 +1,161 @@
+use errors::Result;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Sparkline},
+};
+
+use crate::{
+    action::Action,
+    components::Component,
+    config::Config,
+    mode::Mode,
+    tui::{Event, Frame},
+    utils::dispatch::{ActionHandler, EventHandler},
+};
+
+// the sparkline scales the columns to the highest one, the values only need to keep their proportions
+const SPARKLINE_SCALE: f32 = 1000.0;
+
+/// Histogram of the detection on its own screen, as the GUI plots it
+#[derive(Debug, Default)]
+pub struct HistogramView {
+    active: bool,
+    config: Option<Config>,
+    // bins from the highest tempo to the lowest, as the detection sends them
+    histogram: Vec<f32>,
+    bpm: Option<f32>,
+    daw_bpm: Option<f32>,
+}
+
+impl Component for HistogramView {
+    fn draw(&mut self, f: &mut Frame<'_>, rect: Rect) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        let mut title = "Histogram".to_string();
+        if let Some(bpm) = self.bpm {
+            title.push_str(&format!(" · estimate: {bpm:.1} BPM"));
+        }
+        if let Some(daw_bpm) = self.daw_bpm {
+            title.push_str(&format!(" · DAW: {daw_bpm:.1} BPM"));
+        }
+        let style = self.config.as_ref().map_or(Style::default(), |config| config.styles[&Mode::Histogram]["default"]);
+        let block = Block::default().title(title).borders(Borders::ALL).style(style);
+        let inner = block.inner(rect);
+        f.render_widget(block, rect);
+
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner);
+        let data = sparkline_data(&downsample(&self.histogram, usize::from(areas[0].width)));
+        f.render_widget(Sparkline::default().data(&data).style(style), areas[0]);
+
+        // the configured range, the detection may be zoomed within it
+        if let Some(config) = &self.config {
+            let static_bpm_detection_parameters = &config.static_bpm_detection_parameters;
+            let lowest = Paragraph::new(format!("{:.0}", static_bpm_detection_parameters.lowest_bpm())).style(style);
+            let highest = Paragraph::new(format!("{:.0}", static_bpm_detection_parameters.highest_bpm()))
+                .style(style)
+                .alignment(Alignment::Right);
+            f.render_widget(lowest, areas[1]);
+            f.render_widget(highest, areas[1]);
+        }
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = Some(config);
+        Ok(())
+    }
+}
+
+/// Highest bin of each of `columns` equal slices of `histogram`, reversed so the tempo goes up from left to right.
+/// With more columns than bins, the bins are repeated
+fn downsample(histogram: &[f32], columns: usize) -> Vec<f32> {
+    if histogram.is_empty() {
+        return Vec::new();
+    }
+    let mut downsampled = (0..columns)
+        .map(|column| {
+            let start = column * histogram.len() / columns;
+            let end = ((column + 1) * histogram.len() / columns).max(start + 1);
+            histogram[start..end].iter().copied().fold(0.0, f32::max)
+        })
+        .collect::<Vec<_>>();
+    downsampled.reverse();
+    downsampled
+}
+
+// from 0 to `SPARKLINE_SCALE`, which the casts can't truncate
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn sparkline_data(columns: &[f32]) -> Vec<u64> {
+    let highest = columns.iter().copied().fold(0.0, f32::max);
+    columns.iter().map(|value| if highest > 0.0 { (value / highest * SPARKLINE_SCALE) as u64 } else { 0 }).collect()
+}
+
+impl EventHandler for HistogramView {
+    fn handle_event(&mut self, event: &Event) -> Result<Option<Action>> {
+        match event {
+            Event::Histogram(histogram, bpm) => {
+                self.histogram.clone_from(histogram);
+                self.bpm = Some(*bpm).filter(|bpm| bpm.is_finite());
+            }
+            Event::DawBpm(daw_bpm) => self.daw_bpm = Some(*daw_bpm),
+            _ => (),
+        }
+        Ok(None)
+    }
+}
+
+impl ActionHandler for HistogramView {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        match action {
+            Action::Switch(mode) => self.active = mode == &Mode::Histogram,
+            Action::StaticBPMDetectionConfig(static_bpm_detection_parameters) => {
+                if let Some(config) = &mut self.config {
+                    config.static_bpm_detection_parameters = static_bpm_detection_parameters.clone();
+                }
+            }
+            _ => (),
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downsample() {
+        // a peak near the highest tempo ends up on the right
+        let mut histogram = vec![0.0; 1000];
+        histogram[10] = 5.0;
+        histogram[500] = 2.0;
+        let columns = downsample(&histogram, 80);
+        assert_eq!(columns.len(), 80);
+        assert_eq!(columns.iter().position(|value| value.to_bits() == 5.0f32.to_bits()), Some(79));
+        assert_eq!(columns.iter().position(|value| value.to_bits() == 2.0f32.to_bits()), Some(39));
+        assert_eq!(columns.iter().filter(|value| **value > 0.0).count(), 2);
+
+        // wider than the histogram
+        let bits = |values: Vec<f32>| values.into_iter().map(f32::to_bits).collect::<Vec<_>>();
+        assert_eq!(bits(downsample(&[1.0, 2.0], 4)), bits(vec![2.0, 2.0, 1.0, 1.0]));
+        assert!(downsample(&[], 80).is_empty());
+        assert!(downsample(&histogram, 0).is_empty());
+    }
+
+    #[test]
+    fn test_events() {
+        let mut histogram_view = HistogramView::default();
+        histogram_view.handle_event(&Event::Histogram(vec![0.0, 1.0], 120.0)).unwrap();
+        histogram_view.handle_event(&Event::DawBpm(119.5)).unwrap();
+        assert_eq!(histogram_view.histogram.len(), 2);
+        assert_eq!(histogram_view.bpm.map(f32::to_bits), Some(120.0f32.to_bits()));
+        assert_eq!(histogram_view.daw_bpm.map(f32::to_bits), Some(119.5f32.to_bits()));
+        // no estimate yet
+        histogram_view.handle_event(&Event::Histogram(vec![0.0, 0.0], f32::NAN)).unwrap();
+        assert_eq!(histogram_view.bpm, None);
+    }
+}
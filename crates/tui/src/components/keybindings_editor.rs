@@ -0,0 +1,335 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use derivative::Derivative;
+use errors::Result;
+use itertools::Itertools;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListDirection, ListState, Paragraph, Wrap},
+};
+use strum::IntoEnumIterator;
+use sync::ArcRwLockExt;
+
+use crate::{
+    action::Action,
+    components::Component,
+    config::{key_sequence_to_string, parse_key_sequence, Config, KeyBindings},
+    mode::Mode,
+    tui::Frame,
+    utils::dispatch::{ActionHandler, EventHandler},
+};
+
+// editor actions listed in the hint, with their label
+const EDITOR_ACTIONS: [(Action, &str); 4] = [
+    (Action::RebindKey, "rebind"),
+    (Action::UnbindKey, "unbind"),
+    (Action::RestoreDefaultKeys, "restore defaults"),
+    (Action::Save, "save"),
+];
+
+#[derive(Debug)]
+enum Status {
+    Info(String),
+    Warning(String),
+}
+
+/// Lists the key bindings of every mode, and edits them in the configuration shared with the rest of the application
+#[derive(Derivative)]
+#[derivative(Debug, Default)]
+pub struct KeyBindingsEditor {
+    active: bool,
+    config: Option<Config>,
+    defaults: KeyBindings,
+    widget_state: ListState,
+    // keys received since the capture started, `None` when not capturing
+    captured: Option<Vec<KeyEvent>>,
+    status: Option<Status>,
+}
+
+fn scope_name(scope: Option<Mode>) -> String {
+    scope.map_or_else(|| "Global".to_string(), |mode| mode.to_string())
+}
+
+/// Every action bound in a scope either currently or by default, grouped by scope
+fn rows(keybindings: &KeyBindings, defaults: &KeyBindings) -> Vec<(Option<Mode>, Action)> {
+    std::iter::once(None)
+        .chain(Mode::iter().map(Some))
+        .flat_map(|scope| {
+            [keybindings, defaults]
+                .into_iter()
+                .filter_map(move |bindings| bindings.get(&scope))
+                .flat_map(|bindings| bindings.values())
+                .filter(|action| **action != Action::Unbound)
+                .sorted_by_cached_key(ToString::to_string)
+                .dedup()
+                .map(move |action| (scope, action.clone()))
+                .collect_vec()
+        })
+        .collect()
+}
+
+/// The captured keys as written in the configuration, provided they read back as the same sequence
+fn captured_sequence(keys: &[KeyEvent]) -> Result<Vec<KeyEvent>, String> {
+    let sequence = keys.iter().map(|key| KeyEvent::new(key.code, key.modifiers)).collect_vec();
+    let written = key_sequence_to_string(&sequence);
+    match parse_key_sequence(&written) {
+        Ok(parsed) if parsed == sequence => Ok(sequence),
+        _ => Err(format!("{written} cannot be written in the configuration")),
+    }
+}
+
+impl KeyBindingsEditor {
+    fn selected(&self) -> Option<(Option<Mode>, Action)> {
+        let config = self.config.as_ref()?;
+        let rows = config.keybindings.get(|keybindings| rows(keybindings, &self.defaults));
+        rows.into_iter().nth(self.widget_state.selected()?)
+    }
+
+    fn hint(&self) -> String {
+        let Some(config) = self.config.as_ref() else {
+            return String::new();
+        };
+        config.keybindings.get(|keybindings| {
+            EDITOR_ACTIONS
+                .iter()
+                .filter_map(|(action, label)| {
+                    let sequence = [Some(Mode::Keybindings), None]
+                        .into_iter()
+                        .find_map(|scope| keybindings.sequences(scope, action).into_iter().next())?;
+                    Some(format!("{} {label}", key_sequence_to_string(&sequence)))
+                })
+                .join(" · ")
+        })
+    }
+
+    fn finish_capture(&mut self, keys: &[KeyEvent]) {
+        let (Some(config), Some((scope, action))) = (self.config.as_ref(), self.selected()) else {
+            return;
+        };
+        let sequence = match captured_sequence(keys) {
+            Ok(sequence) => sequence,
+            Err(message) => {
+                self.status = Some(Status::Warning(message));
+                return;
+            }
+        };
+        let written = key_sequence_to_string(&sequence);
+        let result =
+            config.keybindings.get_mut(|keybindings| keybindings.rebind(&self.defaults, scope, &action, sequence));
+        self.status = Some(match result {
+            Ok(()) => Status::Info(format!("{action} bound to {written} in {}", scope_name(scope))),
+            Err(conflicts) => Status::Warning(format!(
+                "{written} is already bound to {}",
+                conflicts.iter().map(|(scope, action)| format!("{action} in {}", scope_name(*scope))).join(", ")
+            )),
+        });
+    }
+
+    fn unbind(&mut self) {
+        let (Some(config), Some((scope, action))) = (self.config.as_ref(), self.selected()) else {
+            return;
+        };
+        config.keybindings.get_mut(|keybindings| keybindings.unbind(&self.defaults, scope, &action));
+        self.status = Some(Status::Info(format!("{action} unbound in {}", scope_name(scope))));
+    }
+
+    fn restore_defaults(&mut self) {
+        let (Some(config), Some((scope, action))) = (self.config.as_ref(), self.selected()) else {
+            return;
+        };
+        let skipped =
+            config.keybindings.get_mut(|keybindings| keybindings.restore_defaults(&self.defaults, scope, &action));
+        self.status = Some(if skipped.is_empty() {
+            Status::Info(format!("{action} restored to its default keys in {}", scope_name(scope)))
+        } else {
+            Status::Warning(format!(
+                "{action} restored, except {}",
+                skipped
+                    .iter()
+                    .map(|(sequence, other_scope, other_action)| {
+                        format!(
+                            "{} already bound to {other_action} in {}",
+                            key_sequence_to_string(sequence),
+                            scope_name(*other_scope)
+                        )
+                    })
+                    .join(", ")
+            ))
+        });
+    }
+}
+
+impl Component for KeyBindingsEditor {
+    fn draw(&mut self, f: &mut Frame<'_>, rect: Rect) -> Result<()> {
+        let (true, Some(config)) = (self.active, self.config.as_ref()) else {
+            return Ok(());
+        };
+        let default = config.styles[&Mode::Keybindings]["default"];
+
+        let items = config.keybindings.get(|keybindings| {
+            rows(keybindings, &self.defaults)
+                .into_iter()
+                .map(|(scope, action)| {
+                    let sequences = keybindings
+                        .sequences(scope, &action)
+                        .iter()
+                        .map(|sequence| key_sequence_to_string(sequence))
+                        .join(" ");
+                    let action = action.to_string();
+                    format!("{:<12}{action:<24}{sequences}", scope_name(scope))
+                })
+                .collect_vec()
+        });
+        let title = match (&self.captured, self.selected()) {
+            (Some(_), Some((scope, action))) => {
+                format!("Press the new keys for {action} in {}, <esc> cancels", scope_name(scope))
+            }
+            _ => "Key bindings".to_string(),
+        };
+        let list = List::new(items)
+            .block(Block::default().style(default).title(title).borders(Borders::ALL))
+            .style(default)
+            .highlight_style(default.add_modifier(Modifier::REVERSED))
+            .direction(ListDirection::TopToBottom);
+
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(3)])
+            .split(rect);
+        f.render_stateful_widget(list, areas[0], &mut self.widget_state);
+
+        let status = match &self.status {
+            Some(Status::Info(message)) => Paragraph::new(message.as_str()).style(default),
+            Some(Status::Warning(message)) => Paragraph::new(message.as_str()).style(default.fg(Color::Yellow)),
+            None => Paragraph::new(""),
+        };
+        f.render_widget(
+            status.wrap(Wrap { trim: true }).block(Block::default().title(self.hint()).borders(Borders::ALL)),
+            areas[1],
+        );
+
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.defaults = Config::base_config()?.keybindings.get(Clone::clone);
+        self.config = Some(config);
+        self.widget_state.select(Some(0));
+        Ok(())
+    }
+}
+
+impl ActionHandler for KeyBindingsEditor {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        if let Action::Switch(mode) = action {
+            self.active = mode == &Mode::Keybindings;
+            if !self.active && self.captured.take().is_some() {
+                return Ok(Some(Action::CaptureKeys(false)));
+            }
+            return Ok(None);
+        }
+
+        if !self.active {
+            return Ok(None);
+        }
+
+        if let Some(captured) = &self.captured {
+            // keys received within the same tick form a sequence, as when they are mapped to actions
+            if action == &Action::Tick && !captured.is_empty() {
+                let captured = self.captured.take().unwrap_or_default();
+                self.finish_capture(&captured);
+                return Ok(Some(Action::CaptureKeys(false)));
+            }
+            return Ok(None);
+        }
+
+        let row_count = self
+            .config
+            .as_ref()
+            .map_or(0, |config| config.keybindings.get(|keybindings| rows(keybindings, &self.defaults).len()));
+        match action {
+            Action::Up if row_count > 0 => {
+                let selection = match self.widget_state.selected() {
+                    Some(0) | None => row_count - 1,
+                    Some(selection) => selection - 1,
+                };
+                self.widget_state.select(Some(selection));
+            }
+            Action::Down if row_count > 0 => {
+                self.widget_state.select(Some((self.widget_state.selected().unwrap_or_default() + 1) % row_count));
+            }
+            Action::RebindKey => {
+                self.captured = Some(Vec::new());
+                self.status = None;
+                return Ok(Some(Action::CaptureKeys(true)));
+            }
+            Action::UnbindKey => self.unbind(),
+            Action::RestoreDefaultKeys => self.restore_defaults(),
+            Action::Save => self.status = Some(Status::Info("key bindings saved".to_string())),
+            _ => (),
+        }
+        Ok(None)
+    }
+}
+
+impl EventHandler for KeyBindingsEditor {
+    fn handle_key_events(&mut self, key: &KeyEvent) -> Result<Option<Action>> {
+        let Some(captured) = &mut self.captured else {
+            return Ok(None);
+        };
+        if captured.is_empty() && key.code == KeyCode::Esc && key.modifiers == KeyModifiers::NONE {
+            self.captured = None;
+            self.status = Some(Status::Info("rebinding cancelled".to_string()));
+            return Ok(Some(Action::CaptureKeys(false)));
+        }
+        captured.push(*key);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_capture_round_trip() {
+        let captures = [
+            vec![key(KeyCode::Char('g'), KeyModifiers::NONE), key(KeyCode::Char('g'), KeyModifiers::NONE)],
+            vec![key(KeyCode::Char('a'), KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT)],
+            vec![
+                key(KeyCode::Char('x'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('S'), KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+                key(KeyCode::Enter, KeyModifiers::ALT),
+            ],
+            vec![
+                key(KeyCode::F(12), KeyModifiers::SHIFT | KeyModifiers::CONTROL),
+                key(KeyCode::PageDown, KeyModifiers::NONE),
+            ],
+            vec![key(KeyCode::Char('-'), KeyModifiers::CONTROL), key(KeyCode::Char(' '), KeyModifiers::ALT)],
+        ];
+        for keys in captures {
+            let sequence = captured_sequence(&keys).unwrap();
+            let written = key_sequence_to_string(&sequence);
+            assert_eq!(parse_key_sequence(&written).unwrap(), keys, "{written}");
+
+            // written in the configuration file and loaded again
+            let mut keybindings = KeyBindings::default();
+            keybindings.entry(Some(Mode::Keybindings)).or_default().insert(sequence.clone(), Action::Save);
+            let serialized = toml::to_string(&keybindings).unwrap();
+            let deserialized = KeyBindings::deserialize(toml::de::Deserializer::new(&serialized)).unwrap();
+            assert_eq!(deserialized.sequences(Some(Mode::Keybindings), &Action::Save), vec![sequence]);
+        }
+    }
+
+    #[test]
+    fn test_capture_not_writable() {
+        assert!(captured_sequence(&[key(KeyCode::Char('<'), KeyModifiers::NONE)]).is_err());
+        assert!(captured_sequence(&[key(KeyCode::CapsLock, KeyModifiers::NONE)]).is_err());
+    }
+}
@@ -1,15 +1,17 @@
 use errors::Result;
-use std::collections::VecDeque;
+use std::{collections::VecDeque, sync::atomic::Ordering};
 
 use derivative::Derivative;
 
 use ratatui::prelude::*;
 
 use errors::MakeReportExt;
-use midi::StaticMidiMessage;
-use ratatui::widgets::{Block, Borders, List};
+use instant::Instant;
+use midi::{BarPosition, FeelAmbiguity, StaticMidiMessage, TempoBand, TempoSource};
+use ratatui::widgets::{Block, Borders, List, Paragraph, Row, Table, Wrap};
 
 use crate::{
+    channel_tempo::{ChannelTempoTracker, ACTIVITY_WINDOW},
     components::Component,
     layout::{rect_x, rect_y, Position},
 };
@@ -31,6 +33,17 @@ pub struct MidiDisplay {
     config: Option<Config>,
     received: VecDeque<String>,
     start_timestamp: u64,
+    config_warnings: Vec<String>,
+    bar_position: Option<BarPosition>,
+    tapped_bpm: Option<f32>,
+    note_freshness: Option<f32>,
+    // of the last estimate, see `BpmEstimate`
+    confidence: Option<f32>,
+    feel_ambiguity: Option<FeelAmbiguity>,
+    // band of the configured tempo bands the tempo is in
+    tempo_band: Option<TempoBand>,
+    #[derivative(Debug = "ignore")]
+    channel_tempo: ChannelTempoTracker,
 }
 
 impl Component for MidiDisplay {
@@ -38,10 +51,97 @@ impl Component for MidiDisplay {
         if !self.active {
             return Ok(());
         }
-        let zone = rect_y(rect_x(rect, 50, Position::End), 100, Position::Start);
+        let mut zone = rect_y(rect_x(rect, 50, Position::End), 100, Position::Start);
+        if let Some(safe_mode_notice) = self.config.as_ref().and_then(|config| config.safe_mode_notice.as_deref()) {
+            let notice = Paragraph::new(safe_mode_notice)
+                .style(Style::default().fg(Color::Yellow))
+                .wrap(Wrap { trim: true })
+                .block(Block::default().title("Safe mode").borders(Borders::ALL));
+            f.render_widget(notice, rect_y(zone, 20, Position::Start));
+            zone = rect_y(zone, 80, Position::End);
+        }
+        if !self.config_warnings.is_empty() {
+            let warnings = Paragraph::new(self.config_warnings.join("\n"))
+                .style(Style::default().fg(Color::Yellow))
+                .wrap(Wrap { trim: true })
+                .block(Block::default().title("Configuration warnings").borders(Borders::ALL));
+            f.render_widget(warnings, rect_y(zone, 30, Position::End));
+            zone = rect_y(zone, 70, Position::Start);
+        }
+        let mut title = match self.config.as_ref().map_or(0, |config| config.midi.rate_limited_notes.get()) {
+            0 => "Notes".to_string(),
+            rate_limited_notes => format!("Notes ({rate_limited_notes} rate limited)"),
+        };
+        if let Some(tempo_source) = self
+            .config
+            .as_ref()
+            .map(|config| config.midi.tempo_source.load(Ordering::Relaxed))
+            .filter(|tempo_source| *tempo_source != TempoSource::Detected)
+        {
+            title.push_str(&format!(" · sending {tempo_source} tempo"));
+        }
+        if let Some(tempo_latency) = self.config.as_ref().and_then(|config| config.midi.tempo_latency.summary()) {
+            title.push_str(&format!(" · tempo latency {tempo_latency}"));
+        }
+        if let Some(clock_lookahead) = self.config.as_ref().map(|config| &config.midi).and_then(|midi| {
+            (!midi.clock_lookahead.lookahead().is_zero() && midi.enable_midi_clock.load(Ordering::Relaxed))
+                .then(|| midi.clock_lookahead.applied())
+        }) {
+            title.push_str(&format!(" · clock ahead {:.1} ms", clock_lookahead.as_secs_f64() * 1000.0));
+        }
+        if let Some(bar_position) = self.bar_position {
+            title.push_str(&format!(" · {bar_position}"));
+        }
+        if let Some(tapped_bpm) = self.tapped_bpm {
+            title.push_str(&format!(" · tapped: {tapped_bpm:.1}"));
+        }
+        if let Some(note_freshness) = self.note_freshness {
+            title.push_str(&format!(" · history: {:.0}% recent", note_freshness * 100.0));
+        }
+        if let Some(confidence) = self.confidence {
+            title.push_str(&format!(" · confidence: {:.0}%", confidence * 100.0));
+        }
+        if let Some(feel_ambiguity) = self.feel_ambiguity {
+            title.push_str(&format!(" · feel: {feel_ambiguity}"));
+        }
+        if let Some(tempo_band) = self.tempo_band {
+            title.push_str(&format!(" · band: {tempo_band}"));
+        }
+
+        self.channel_tempo.evict(Instant::now());
+        let channel_estimates = self.config.as_ref().map_or_else(Vec::new, |config| {
+            let static_bpm_detection_parameters = &config.static_bpm_detection_parameters;
+            self.channel_tempo
+                .estimates(static_bpm_detection_parameters.lowest_bpm(), static_bpm_detection_parameters.highest_bpm())
+        });
+        // only worth a breakdown when several instruments play
+        if channel_estimates.len() > 1 {
+            let height = (channel_estimates.len() as u16 + 3).min(zone.height / 2);
+            let areas = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(height)])
+                .split(zone);
+            let rows = channel_estimates.iter().map(|channel_estimate| {
+                Row::new([
+                    (channel_estimate.channel + 1).to_string(),
+                    channel_estimate.bpm.map_or_else(|| "-".to_string(), |bpm| format!("~{bpm:.0}")),
+                    channel_estimate.notes.to_string(),
+                ])
+            });
+            let table = Table::new(rows, [Constraint::Length(8), Constraint::Length(8), Constraint::Min(0)])
+                .header(Row::new([
+                    "Channel".to_string(),
+                    "BPM".to_string(),
+                    format!("Notes ({}s)", ACTIVITY_WINDOW.as_secs()),
+                ]))
+                .block(Block::default().title("Per channel tempo (approximate)").borders(Borders::ALL));
+            f.render_widget(table, areas[1]);
+            zone = areas[0];
+        }
+
         let list = List::new(self.received.iter().rev().take(zone.height as usize).rev().map(String::as_str))
             .style(self.config.as_ref().map_or(Style::default(), |config| config.styles[&Mode::DeviceView]["default"]))
-            .block(Block::default().title("Notes").borders(Borders::ALL));
+            .block(Block::default().title(title).borders(Borders::ALL));
         f.render_widget(list, zone);
 
         Ok(())
@@ -55,12 +155,46 @@ impl Component for MidiDisplay {
 
 impl EventHandler for MidiDisplay {
     fn handle_event(&mut self, event: &Event) -> Result<Option<Action>> {
+        if let Event::ConfigWarnings(config_warnings) = event {
+            self.config_warnings = config_warnings.iter().map(ToString::to_string).collect();
+            return Ok(None);
+        }
+        if let Event::BarPosition(bar_position) = event {
+            self.bar_position = *bar_position;
+            return Ok(None);
+        }
+        if let Event::TappedTempo(tapped_bpm) = event {
+            self.tapped_bpm = *tapped_bpm;
+            return Ok(None);
+        }
+        if let Event::NoteFreshness(note_freshness) = event {
+            self.note_freshness = *note_freshness;
+            return Ok(None);
+        }
+        if let Event::FeelAmbiguity(feel_ambiguity) = event {
+            self.feel_ambiguity = *feel_ambiguity;
+            return Ok(None);
+        }
+        if let Event::TempoBand(tempo_band) = event {
+            self.tempo_band = *tempo_band;
+            return Ok(None);
+        }
+        if let Event::Estimate(estimate) = event {
+            self.confidence = Some(estimate.confidence);
+            return Ok(None);
+        }
         if let Event::Midi(midi_message) = event {
             if midi_message.midi_message == StaticMidiMessage::ActiveSensing
                 || midi_message.midi_message == StaticMidiMessage::TimingClock
             {
                 return Ok(None);
             }
+            if let StaticMidiMessage::NoteOn(channel, _, velocity) = midi_message.midi_message {
+                // a note-on without velocity is a note-off
+                if u8::from(velocity) > 0 {
+                    self.channel_tempo.note_on(channel.index(), midi_message.timestamp, Instant::now());
+                }
+            }
 
             let text = if let StaticMidiMessage::OwnedSysEx(value) = &midi_message.midi_message {
                 let bytes = value.iter().map(|u7| u8::from(*u7)).collect();
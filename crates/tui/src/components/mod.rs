@@ -1,4 +1,7 @@
+pub mod histogram_view;
+pub mod keybindings_editor;
 pub mod midi_display;
+pub mod preset_select;
 pub mod select_device;
 
 use crate::tui::Frame;
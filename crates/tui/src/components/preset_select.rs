@@ -0,0 +1,145 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use errors::Result;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListDirection, ListState},
+};
+
+use crate::{
+    action::Action,
+    components::Component,
+    config::Config,
+    layout::{centered_rect, Position},
+    mode::Mode,
+    tui::Frame,
+    utils::dispatch::{ActionHandler, EventHandler},
+};
+
+/// Lists the saved presets, the highlighted one is loaded with `Action::Select`. `Action::NamePreset` reads the name
+/// of a new preset from the keys typed next
+#[derive(Debug, Default)]
+pub struct PresetSelect {
+    active: bool,
+    config: Option<Config>,
+    presets: Vec<String>,
+    widget_state: ListState,
+    // name typed so far, `None` when not naming a preset
+    name: Option<String>,
+    // saved once the keys are mapped to actions again
+    confirmed_name: Option<String>,
+}
+
+impl PresetSelect {
+    fn refresh_presets(&mut self, selected: Option<&str>) {
+        self.presets = Config::list_presets();
+        let selection = match selected {
+            Some(selected) => self.presets.iter().position(|preset| preset == selected),
+            None => self.widget_state.selected().filter(|selection| *selection < self.presets.len()),
+        };
+        self.widget_state.select(selection.or((!self.presets.is_empty()).then_some(0)));
+    }
+}
+
+impl Component for PresetSelect {
+    fn draw(&mut self, f: &mut Frame<'_>, rect: Rect) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+
+        let default = self.config.as_ref().map_or(Style::default(), |config| config.styles[&Mode::Presets]["default"]);
+        let title = match &self.name {
+            Some(name) => format!("Preset name: {name}_ · <enter> saves, <esc> cancels"),
+            None if self.presets.is_empty() => "Presets · none saved yet".to_string(),
+            None => "Presets".to_string(),
+        };
+
+        let list = List::new(self.presets.iter().map(String::as_str))
+            .block(Block::default().style(default).title(title).borders(Borders::ALL))
+            .style(default)
+            .highlight_style(default.add_modifier(Modifier::REVERSED))
+            .direction(ListDirection::TopToBottom);
+
+        let popup_area = centered_rect(rect, 50, Position::Start, 50, Position::Start);
+        f.render_stateful_widget(list, popup_area, &mut self.widget_state);
+
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = Some(config);
+        Ok(())
+    }
+}
+
+impl ActionHandler for PresetSelect {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        if let Action::Switch(mode) = action {
+            self.active = mode == &Mode::Presets;
+            if self.active {
+                self.refresh_presets(None);
+            } else if self.name.take().is_some() {
+                return Ok(Some(Action::CaptureKeys(false)));
+            }
+            return Ok(None);
+        }
+
+        match action {
+            Action::CaptureKeys(false) => {
+                if let Some(name) = self.confirmed_name.take() {
+                    return Ok(Some(Action::SavePreset(name)));
+                }
+            }
+            // saved and loaded by the application, which may have been asked by a key binding of another screen
+            Action::SavePreset(name) => self.refresh_presets(Some(name)),
+            Action::LoadPreset(name) => self.refresh_presets(Some(name)),
+            _ if !self.active || self.name.is_some() => (),
+            Action::Up if !self.presets.is_empty() => {
+                let selection = match self.widget_state.selected() {
+                    Some(0) | None => self.presets.len() - 1,
+                    Some(selection) => selection - 1,
+                };
+                self.widget_state.select(Some(selection));
+            }
+            Action::Down if !self.presets.is_empty() => {
+                self.widget_state
+                    .select(Some((self.widget_state.selected().unwrap_or_default() + 1) % self.presets.len()));
+            }
+            Action::Select => {
+                if let Some(preset) = self.widget_state.selected().and_then(|selection| self.presets.get(selection)) {
+                    return Ok(Some(Action::LoadPreset(preset.clone())));
+                }
+            }
+            Action::NamePreset => {
+                self.name = Some(String::new());
+                return Ok(Some(Action::CaptureKeys(true)));
+            }
+            _ => (),
+        }
+        Ok(None)
+    }
+}
+
+impl EventHandler for PresetSelect {
+    fn handle_key_events(&mut self, key: &KeyEvent) -> Result<Option<Action>> {
+        let Some(name) = &mut self.name else {
+            return Ok(None);
+        };
+        match key.code {
+            KeyCode::Esc => self.name = None,
+            KeyCode::Enter if !name.is_empty() => self.confirmed_name = self.name.take(),
+            KeyCode::Backspace => {
+                name.pop();
+                return Ok(None);
+            }
+            // a preset is named after its file
+            KeyCode::Char(c)
+                if !matches!(c, '/' | '\\') && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                name.push(c);
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(Action::CaptureKeys(false)))
+    }
+}
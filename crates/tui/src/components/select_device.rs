@@ -9,7 +9,7 @@ use ratatui::{
     widgets::{Block, Borders, List, ListDirection, ListState},
 };
 
-use midi::MidiInputPort;
+use midi::{MidiBackend, MidiInputPort};
 
 use crate::{
     components::Component,
@@ -50,40 +50,29 @@ impl SelectDevice {
 
     #[minitrace::trace]
     fn refresh_devices(&mut self, devices: &[MidiInputPort]) {
-        let mut updated_selection = None;
-        for diff in devices
-            .iter()
-            .enumerate()
-            .merge_join_by(self.devices.iter().enumerate(), |(_, new_device), (_, old_device)| {
-                new_device.cmp(old_device)
-            })
+        for diff in
+            devices.iter().merge_join_by(self.devices.iter(), |new_device, old_device| new_device.cmp(old_device))
         {
             match diff {
-                EitherOrBoth::Both((new_index, new), (old_index, _)) => {
-                    if new == &self.selection && new_index != old_index {
-                        info!("updating from {old_index} to {new_index} because current selection moved in order");
-                        updated_selection = Some(new_index);
-                    }
-                }
-                EitherOrBoth::Left((i, device)) => {
-                    if i > 0 && device == &self.selection {
-                        info!("updating to {i} because device reappeared");
-                        updated_selection = Some(i);
-                    }
-                    info!("Device added: {device}");
-                }
-                EitherOrBoth::Right((_old_index, device)) => {
-                    if device == &self.selection {
-                        info!("updating to 0 because device was removed");
-                        updated_selection = Some(0);
-                    }
-                    info!("Device removed: {device}");
-                }
+                EitherOrBoth::Both(..) => (),
+                EitherOrBoth::Left(device) => info!("Device added: {device}"),
+                EitherOrBoth::Right(device) => info!("Device removed: {device}"),
+            }
+        }
+        // among identical devices, the selected one is followed, see `MidiInputPort::position_in`
+        let updated_selection = match self.selection.position_in(devices) {
+            Some(index) => {
+                self.selection = devices[index].clone();
+                index
             }
-            if let Some(updated_selection) = updated_selection {
-                info!("updated selection : {updated_selection}");
-                self.widget_state.select(Some(updated_selection));
+            None => {
+                info!("updating to 0 because device was removed");
+                0
             }
+        };
+        if self.widget_state.selected() != Some(updated_selection) {
+            info!("updated selection : {updated_selection}");
+            self.widget_state.select(Some(updated_selection));
         }
         self.devices = Vec::from(devices);
     }
@@ -98,9 +87,12 @@ impl Component for SelectDevice {
         let default =
             self.config.as_ref().map_or(Style::default(), |config| config.styles[&Mode::DeviceView]["default"]);
         let devices = self.devices.iter().map(MidiInputPort::as_str);
+        // the configured backend when this build doesn't have it, the service reports why
+        let configured_backend = self.config.as_ref().map_or(MidiBackend::Auto, |config| config.midi.backend);
+        let title = format!("Devices · {}", configured_backend.resolve().unwrap_or(configured_backend));
 
         let list = List::new(devices)
-            .block(Block::default().style(default).title("List").borders(Borders::ALL))
+            .block(Block::default().style(default).title(title).borders(Borders::ALL))
             .style(default)
             .highlight_style(default.add_modifier(Modifier::REVERSED))
             .repeat_highlight_symbol(true)
@@ -1,11 +1,18 @@
 use bitflags::Flags;
-use std::{collections::HashMap, fmt::Debug, fs::write, path::PathBuf};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use config::ConfigError;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use derive_deref::{Deref, DerefMut};
 use itertools::Itertools;
-use log::{error, info};
+use log::{error, info, warn};
 use ratatui::style::Style;
 use serde::{
     de,
@@ -18,12 +25,20 @@ use strum::Display;
 
 use build::{get_config_dir, get_data_dir};
 use errors::{Report, Result, TypedResult};
-use gui::GUIConfig;
-use midi::{DynamicBPMDetectionParameters, MidiServiceConfig, StaticBPMDetectionParameters};
+use gui::{
+    effective_config::{Provenance, SharedProvenance},
+    unknown_keys, GUIConfig,
+};
+use midi::{memory, DynamicBPMDetectionParameters, MidiServiceConfig, StaticBPMDetectionParameters, TempoMapConfig};
+use sync::{ArcRwLock, ArcRwLockExt, RwLock};
 
-use crate::{action::Action, mode::Mode};
+use crate::{
+    action::Action, analyze::AnalyzeOptions, headless::HeadlessOptions, mode::Mode, safe_mode,
+    services::config_watcher::SharedReloadedParameters,
+};
 
-const CONFIG: &str = include_str!("../config/base_config.toml");
+// config/base_config.toml at the root of the workspace merged with config/overlay.toml, see `build::create_base_config`
+const CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/base_config.toml"));
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -33,54 +48,113 @@ pub struct AppConfig {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
-    #[serde(default, flatten)]
+    // set once loaded. Flattening it would swallow the unknown keys, see `unknown_keys`
     #[allow(forbidden_lint_groups)]
     #[allow(clippy::struct_field_names)]
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub app_config: AppConfig,
-    #[serde(default)]
-    pub keybindings: KeyBindings,
-    #[serde(default)]
+    pub keybindings: SharedKeyBindings,
     pub styles: HashMap<Mode, HashMap<String, Style>>,
     pub frame_rate: f64,
     pub tick_rate: f64,
+    // a key that is both a binding and the start of longer ones waits this long for the next key
+    pub key_sequence_timeout: Duration,
+    // smaller buffers and coarser limits for devices with little memory, see `midi::memory`
+    pub low_memory: bool,
     #[serde(rename = "GUI")]
     pub gui: GUIConfig,
     #[serde(rename = "MIDI")]
     pub midi: MidiServiceConfig,
-    #[serde(default)]
     pub static_bpm_detection_parameters: StaticBPMDetectionParameters,
-    #[serde(default)]
     pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    pub tempo_map: TempoMapConfig,
+    // origin of the values, shared by all clones of the configuration
+    #[serde(skip)]
+    pub provenance: SharedProvenance,
+    // detection parameters reloaded from the file while running, for the GUI, see `services::config_watcher`
+    #[serde(skip)]
+    pub reloaded_parameters: SharedReloadedParameters,
+    // set when started in safe mode, see `Config::safe_mode`
+    #[serde(skip)]
+    pub safe_mode_notice: Option<String>,
+    // set by `--headless`, see `headless::run`
+    #[serde(skip)]
+    pub headless: Option<HeadlessOptions>,
+    // set by the `analyze` subcommand, see `analyze::run`
+    #[serde(skip)]
+    pub analyze: Option<AnalyzeOptions>,
+    // set by `--gui-only`, see `gui_only::run`
+    #[serde(skip)]
+    pub gui_only: bool,
+    // set by `--connect`, the TUI then controls a plugin instance instead of listening to MIDI, see
+    // `services::remote`
+    #[serde(skip)]
+    pub connect: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            app_config: AppConfig::default(),
+            keybindings: SharedKeyBindings::default(),
+            styles: HashMap::new(),
+            frame_rate: 20.0,
+            tick_rate: 2.0,
+            key_sequence_timeout: Duration::from_millis(800),
+            low_memory: false,
+            gui: GUIConfig::default(),
+            midi: MidiServiceConfig::default(),
+            static_bpm_detection_parameters: StaticBPMDetectionParameters::default(),
+            dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters::default(),
+            tempo_map: TempoMapConfig::default(),
+            provenance: SharedProvenance::default(),
+            reloaded_parameters: SharedReloadedParameters::default(),
+            safe_mode_notice: None,
+            headless: None,
+            analyze: None,
+            gui_only: false,
+            connect: None,
+        }
+    }
 }
 
 impl Config {
+    #[must_use]
+    pub fn config_path() -> PathBuf {
+        get_config_dir().join("config.toml")
+    }
+
     pub fn base_config() -> Result<Self, ConfigError> {
-        Config::deserialize(toml::de::Deserializer::new(CONFIG)).map_err(de::Error::custom)
+        unknown_keys::deserialize_warning(toml::de::Deserializer::new(CONFIG), "the built-in configuration")
+            .map_err(de::Error::custom)
     }
 
     pub fn new() -> TypedResult<Self, ConfigError> {
-        let data_dir = get_data_dir();
-        let config_dir = get_config_dir();
-        let mut builder = config::Config::builder()
-            .set_default("_data_dir", data_dir.to_str().unwrap())?
-            .set_default("_config_dir", config_dir.to_str().unwrap())?;
-
-        builder = builder.add_source(
-            config::File::from(config_dir.join("config.toml")).format(config::FileFormat::Toml).required(false),
-        );
+        Self::from_path(&Self::config_path())
+    }
+
+    /// Built-in configuration overridden by the file at `config_path` when it exists, as `new` loads the usual one
+    pub fn from_path(config_path: &Path) -> TypedResult<Self, ConfigError> {
+        // sections and values missing from the file keep their built-in value
+        let builder = config::Config::builder()
+            .add_source(config::File::from_str(CONFIG, config::FileFormat::Toml))
+            .add_source(config::File::from(config_path.to_path_buf()).format(config::FileFormat::Toml).required(false));
 
         let base_config = Self::base_config()?;
 
-        let mut cfg: Self = builder.build()?.try_deserialize()?;
+        let mut cfg: Self = unknown_keys::deserialize_warning(builder.build()?, &config_path.display().to_string())?;
+        cfg.app_config = AppConfig { data_dir: get_data_dir(), config_dir: get_config_dir() };
 
-        for (mode, default_bindings) in &*base_config.keybindings {
-            let user_bindings = cfg.keybindings.entry(*mode).or_default();
-            for (key, cmd) in default_bindings {
-                user_bindings.entry(key.clone()).or_insert_with(|| cmd.clone());
-            }
+        let file = fs::read_to_string(config_path).ok().and_then(|file| file.parse::<toml::Table>().ok());
+
+        // read from the file alone, the layered sources hold both spellings of a sequence written differently
+        let mut keybindings = base_config.keybindings.get(Clone::clone);
+        if let Some(user_keybindings) = file.as_ref().and_then(|file| file.get("keybindings")) {
+            keybindings.merge(KeyBindings::deserialize(user_keybindings.clone()).map_err(ConfigError::custom)?);
         }
+        cfg.keybindings = SharedKeyBindings(Arc::new(RwLock::new(keybindings)));
         for (mode, default_styles) in &base_config.styles {
             let user_styles = cfg.styles.entry(*mode).or_default();
             for (style_key, style) in default_styles {
@@ -88,27 +162,270 @@ impl Config {
             }
         }
 
+        let mut provenance = Provenance::new(&base_config);
+        if let Some(file) = &file {
+            provenance.loaded_from_file(file, &cfg);
+        }
+        cfg.provenance = SharedProvenance::new(provenance);
+        cfg.apply_low_memory();
+
         Ok(cfg)
     }
 
-    pub fn save(&self) -> Result<()> {
-        let serialized = match toml::to_string_pretty(self) {
-            Ok(serialized) => serialized,
+    /// Built-in configuration only, for when the configuration file prevents the application from starting. The file
+    /// is renamed so it is not loaded on the next start either, it can still be restored by hand
+    pub fn safe_mode() -> TypedResult<Self, ConfigError> {
+        let mut cfg = Self::base_config()?;
+        cfg.app_config = AppConfig { data_dir: get_data_dir(), config_dir: get_config_dir() };
+        cfg.provenance = SharedProvenance::new(Provenance::new(&cfg));
+        cfg.apply_low_memory();
+        let config_path = Self::config_path();
+        let notice = match safe_mode::set_aside(&config_path, chrono::Local::now()) {
+            Ok(Some(disabled_path)) => {
+                format!("Safe mode: built-in configuration, yours was moved to {}", disabled_path.display())
+            }
+            Ok(None) => "Safe mode: built-in configuration".to_string(),
             Err(e) => {
-                error!("Serialization error: {:?}", e);
-                return Err(Report::new(e));
+                error!("could not move {} aside: {e}", config_path.display());
+                format!("Safe mode: built-in configuration, {} could not be moved aside", config_path.display())
             }
         };
+        info!("{notice}");
+        cfg.safe_mode_notice = Some(notice);
+        Ok(cfg)
+    }
+
+    /// Passes `low_memory` on to the MIDI service and the GUI, and brings the parameters within its limits
+    pub fn apply_low_memory(&mut self) {
+        self.midi.low_memory = self.low_memory;
+        self.gui.low_memory = self.low_memory;
+        if self.low_memory {
+            memory::limit_static_parameters(&mut self.static_bpm_detection_parameters);
+            memory::limit_tempo_map(&mut self.tempo_map);
+        }
+    }
+
+    /// Writes the configuration in use and where its values come from to the data directory
+    pub fn export_effective_config(&self) -> Result<PathBuf> {
+        let directory = get_data_dir();
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("effective_config_{}.toml", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        fs::write(&path, self.provenance.render(self))?;
+        info!("effective configuration exported to {}", path.display());
+        Ok(path)
+    }
+
+    /// Directory of the presets, see `save_preset`
+    #[must_use]
+    pub fn presets_dir() -> PathBuf {
+        get_config_dir().join("presets")
+    }
+
+    /// Writes the detection parameters in use as the preset `name`, replacing a preset of the same name
+    pub fn save_preset(&self, name: &str) -> Result<PathBuf> {
+        self.save_preset_in(&Self::presets_dir(), name)
+    }
+
+    /// Detection parameters of the preset `name`, brought within the limits of `low_memory`. They are applied by
+    /// sending them as `Action::StaticBPMDetectionConfig` and `Action::DynamicBPMDetectionConfig`
+    pub fn load_preset(&self, name: &str) -> Result<Preset> {
+        self.load_preset_from(&Self::presets_dir(), name)
+    }
+
+    /// Names of the saved presets, in alphabetical order
+    #[must_use]
+    pub fn list_presets() -> Vec<String> {
+        list_presets_in(&Self::presets_dir())
+    }
+
+    fn save_preset_in(&self, directory: &Path, name: &str) -> Result<PathBuf> {
+        let path = preset_path(directory, name)?;
+        let preset = Preset {
+            static_bpm_detection_parameters: self.static_bpm_detection_parameters.clone(),
+            dynamic_bpm_detection_parameters: self.dynamic_bpm_detection_parameters.clone(),
+        };
+        fs::create_dir_all(directory)?;
+        fs::write(&path, toml::to_string_pretty(&preset)?)?;
+        info!("preset {name} saved to {}", path.display());
+        Ok(path)
+    }
+
+    fn load_preset_from(&self, directory: &Path, name: &str) -> Result<Preset> {
+        let path = preset_path(directory, name)?;
+        let mut preset: Preset = unknown_keys::deserialize_warning(
+            toml::de::Deserializer::new(&fs::read_to_string(&path)?),
+            &path.display().to_string(),
+        )?;
+        if self.low_memory {
+            memory::limit_static_parameters(&mut preset.static_bpm_detection_parameters);
+        }
+        Ok(preset)
+    }
 
-        let config_path = get_config_dir().join("config.toml");
-        info!("configuration saved");
-        Ok(write(config_path, serialized)?)
+    /// As saved to the configuration file, see `ConfigSaver`
+    pub fn serialized(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| {
+            error!("Serialization error: {:?}", e);
+            Report::new(e)
+        })
     }
 }
 
-#[derive(Clone, Debug, Default, Deref, DerefMut)]
+/// Detection parameters saved under a name, to switch between the settings of different instruments, see
+/// `Config::save_preset`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    pub static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+}
+
+// a preset is a file of the presets directory, named after it
+fn preset_path(directory: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(Report::msg(format!("invalid preset name: {name:?}")));
+    }
+    Ok(directory.join(format!("{name}.toml")))
+}
+
+fn list_presets_in(directory: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            path.file_stem()?.to_str().map(ToString::to_string)
+        })
+        .filter(|name| preset_path(directory, name).is_ok())
+        .sorted()
+        .collect()
+}
+
+/// Actions by key sequence, globally under `None` and per mode. A sequence resolves to the binding of the current mode,
+/// then to the global one, see `key_sequence`. The configuration file overrides the built-in bindings sequence by
+/// sequence, see `merge`, so a built-in binding of a mode still wins over a global one of the file
+#[derive(Clone, Debug, Default, Deref, DerefMut, PartialEq, Eq)]
 pub struct KeyBindings(pub HashMap<Option<Mode>, HashMap<Vec<KeyEvent>, Action>>);
 
+impl KeyBindings {
+    /// Bindings of `overrides` replace the ones of the same sequence in the same scope, the others are kept
+    pub fn merge(&mut self, overrides: KeyBindings) {
+        for (scope, bindings) in overrides.0 {
+            self.entry(scope).or_default().extend(bindings);
+        }
+    }
+
+    /// Other actions bound to `sequence` in the scopes active along with `scope`: global bindings apply in every mode
+    #[must_use]
+    pub fn conflicts(
+        &self,
+        scope: Option<Mode>,
+        sequence: &[KeyEvent],
+        action: &Action,
+    ) -> Vec<(Option<Mode>, Action)> {
+        self.iter()
+            .filter(|(other_scope, _)| scope.is_none() || other_scope.is_none() || **other_scope == scope)
+            .filter_map(|(other_scope, bindings)| {
+                bindings
+                    .get(sequence)
+                    .filter(|bound| *bound != action && **bound != Action::Unbound)
+                    .map(|bound| (*other_scope, bound.clone()))
+            })
+            .collect()
+    }
+
+    /// Sequences bound to `action` in `scope`, sorted by their written form
+    #[must_use]
+    pub fn sequences(&self, scope: Option<Mode>, action: &Action) -> Vec<Vec<KeyEvent>> {
+        self.get(&scope)
+            .into_iter()
+            .flatten()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(sequence, _)| sequence.clone())
+            .sorted_by_cached_key(|sequence| key_sequence_to_string(sequence))
+            .collect()
+    }
+
+    /// Replaces the bindings of `action` in `scope` by `sequence`. Nothing changes if `sequence` is bound to another
+    /// action, those are returned instead
+    pub fn rebind(
+        &mut self,
+        defaults: &KeyBindings,
+        scope: Option<Mode>,
+        action: &Action,
+        sequence: Vec<KeyEvent>,
+    ) -> Result<(), Vec<(Option<Mode>, Action)>> {
+        let conflicts = self.conflicts(scope, &sequence, action);
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        self.unbind(defaults, scope, action);
+        self.entry(scope).or_default().insert(sequence, action.clone());
+        Ok(())
+    }
+
+    /// Removes the bindings of `action` in `scope`. Default sequences are kept as `Action::Unbound`, otherwise they
+    /// would be bound again when the configuration is loaded
+    pub fn unbind(&mut self, defaults: &KeyBindings, scope: Option<Mode>, action: &Action) {
+        let Some(bindings) = self.get_mut(&scope) else {
+            return;
+        };
+        let is_default = |sequence: &Vec<KeyEvent>| {
+            defaults.get(&scope).is_some_and(|default_bindings| default_bindings.contains_key(sequence))
+        };
+        bindings.retain(|sequence, bound| bound != action || is_default(sequence));
+        for bound in bindings.values_mut().filter(|bound| *bound == action) {
+            *bound = Action::Unbound;
+        }
+    }
+
+    /// Binds `action` in `scope` to its default sequences again. Default sequences now bound to other actions are left
+    /// as they are, and returned along with their current action
+    pub fn restore_defaults(
+        &mut self,
+        defaults: &KeyBindings,
+        scope: Option<Mode>,
+        action: &Action,
+    ) -> Vec<(Vec<KeyEvent>, Option<Mode>, Action)> {
+        self.unbind(defaults, scope, action);
+        let mut skipped = Vec::new();
+        for sequence in defaults.sequences(scope, action) {
+            if let Some((other_scope, other_action)) = self.conflicts(scope, &sequence, action).into_iter().next() {
+                skipped.push((sequence, other_scope, other_action));
+            } else {
+                self.entry(scope).or_default().insert(sequence, action.clone());
+            }
+        }
+        skipped
+    }
+}
+
+/// Key bindings shared by all the clones of the configuration, so the bindings edited in the TUI are the ones saved
+#[derive(Clone, Debug, Default, Deref)]
+pub struct SharedKeyBindings(pub ArcRwLock<KeyBindings>);
+
+impl Serialize for SharedKeyBindings {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.get(|keybindings| keybindings.serialize(serializer))
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedKeyBindings {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        KeyBindings::deserialize(deserializer).map(|keybindings| Self(Arc::new(RwLock::new(keybindings))))
+    }
+}
+
 impl Serialize for KeyBindings {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -119,18 +436,14 @@ impl Serialize for KeyBindings {
         for (mode, bindings) in &self.0 {
             // Log information about each key-value pair
             if let Some(mode) = mode.as_ref() {
-                let value: HashMap<_, _> = bindings
-                    .iter()
-                    .map(|(key_events, action)| {
-                        (format!("<{}>", key_events.iter().map(key_event_to_string).join("-")), action)
-                    })
-                    .collect();
+                let value: HashMap<_, _> =
+                    bindings.iter().map(|(key_events, action)| (key_sequence_to_string(key_events), action)).collect();
                 main_map.serialize_entry(&mode.to_string(), &value).map_err(|e| {
                     ser::Error::custom(Report::msg(format!("can't serialize mode entry {mode} {bindings:?} : {e:?}")))
                 })?;
             } else {
                 for (key_events, action) in bindings {
-                    let key = format!("<{}>", key_events.iter().map(key_event_to_string).join("-"));
+                    let key = key_sequence_to_string(key_events);
                     main_map.serialize_entry(&key, action).map_err(|e| {
                         ser::Error::custom(Report::msg(format!("can't serialize main entry {key}:{action:?} ({e:?})")))
                     })?;
@@ -163,40 +476,42 @@ impl<'de> Deserialize<'de> for KeyBindings {
     {
         let parsed_map = HashMap::<KeyOrMode, KeyMappingsOrAction>::deserialize(deserializer)?;
 
-        let keybindings = parsed_map
-            .into_iter()
-            .try_fold(
-                HashMap::<Option<Mode>, HashMap<Vec<KeyEvent>, Action>>::default(),
-                |mut keybindings, (key_or_mode, keymapping_or_action)| {
-                    match (key_or_mode, keymapping_or_action) {
-                        (KeyOrMode::Mode(mode), KeyMappingsOrAction::Keymapping(mapping)) => {
-                            keybindings.insert(
-                                Some(mode),
-                                mapping
-                                    .into_iter()
-                                    .map(|(key_str, cmd)| Ok((parse_key_sequence(&key_str)?, cmd)))
-                                    .collect::<Result<_, String>>()?,
-                            );
-                        }
-                        (KeyOrMode::Key(key), KeyMappingsOrAction::Action(action)) => {
-                            let mappings = keybindings.entry(None).or_default();
-                            let sequence = parse_key_sequence(key.as_str())?;
-                            mappings.insert(sequence, action);
-                        }
-                        (KeyOrMode::Key(mode), KeyMappingsOrAction::Keymapping(_)) => {
-                            return Err(format!("{mode} is not a valid mode"));
-                        }
-                        (KeyOrMode::Mode(mode), KeyMappingsOrAction::Action(action)) => {
-                            return Err(format!("{mode} is a mode and cannot be assigned to an action ( {action} )"));
-                        }
-                    };
-
-                    Ok(keybindings)
-                },
-            )
-            .map_err(Error::custom)?;
-
-        Ok(KeyBindings(keybindings))
+        let mut keybindings = KeyBindings::default();
+        let mut written = Vec::<(Option<Mode>, String, Action)>::new();
+        for (key_or_mode, keymapping_or_action) in parsed_map {
+            match (key_or_mode, keymapping_or_action) {
+                (KeyOrMode::Mode(mode), KeyMappingsOrAction::Keymapping(mapping)) => {
+                    keybindings.entry(Some(mode)).or_default();
+                    written.extend(mapping.into_iter().map(|(key, action)| (Some(mode), key, action)));
+                }
+                (KeyOrMode::Key(key), KeyMappingsOrAction::Action(action)) => written.push((None, key, action)),
+                (KeyOrMode::Key(mode), KeyMappingsOrAction::Keymapping(_)) => {
+                    return Err(Error::custom(format!("{mode} is not a valid mode")));
+                }
+                (KeyOrMode::Mode(mode), KeyMappingsOrAction::Action(action)) => {
+                    return Err(Error::custom(format!(
+                        "{mode} is a mode and cannot be assigned to an action ( {action} )"
+                    )));
+                }
+            };
+        }
+
+        // a sequence written twice in a scope, such as <ctrl-a> and <Ctrl-A>, is bound by its first spelling in
+        // alphabetical order, whatever the order the table was read in
+        written.sort_by(|(_, key, _), (_, other_key, _)| key.cmp(other_key));
+        for (scope, key, action) in written {
+            let sequence = parse_key_sequence(&key).map_err(Error::custom)?;
+            match keybindings.entry(scope).or_default().entry(sequence) {
+                Entry::Occupied(bound) => {
+                    warn!("{key} is bound twice, to {} and {action}, the latter is ignored", bound.get());
+                }
+                Entry::Vacant(vacant) => {
+                    vacant.insert(action);
+                }
+            }
+        }
+
+        Ok(keybindings)
     }
 }
 
@@ -298,7 +613,7 @@ pub fn key_event_to_string(key_event: &KeyEvent) -> String {
         KeyCode::Delete => "delete",
         KeyCode::Insert => "insert",
         KeyCode::F(c) => {
-            char = format!("f({c})");
+            char = format!("f{c}");
             &char
         }
         KeyCode::Char(' ') => "space",
@@ -343,6 +658,12 @@ pub fn key_event_to_string(key_event: &KeyEvent) -> String {
     key
 }
 
+/// Written form of `key_events` in the configuration, read back by `parse_key_sequence`
+#[must_use]
+pub fn key_sequence_to_string(key_events: &[KeyEvent]) -> String {
+    key_events.iter().map(|key_event| format!("<{}>", key_event_to_string(key_event))).collect()
+}
+
 pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
     if raw.chars().filter(|c| *c == '>').count() != raw.chars().filter(|c| *c == '<').count() {
         return Err(format!("Unable to parse `{raw}`"));
@@ -372,21 +693,117 @@ pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
 
 #[cfg(test)]
 mod tests {
+    use build::PROJECT_NAME;
+    use midi::{Feel, PatternKind};
     use pretty_assertions::assert_eq;
+    use std::time::Instant;
 
     use super::*;
+    use crate::key_sequence::KeySequenceMatcher;
 
     #[test]
     fn test_config() -> Result<()> {
         let c = Config::new()?;
 
         assert_eq!(
-            c.keybindings.get(&None).unwrap().get(&parse_key_sequence("<q>").unwrap_or_default()).unwrap(),
-            &Action::Quit
+            c.keybindings.get(|keybindings| keybindings[&None][&parse_key_sequence("<q>").unwrap_or_default()].clone()),
+            Action::Quit
         );
         Ok(())
     }
 
+    #[test]
+    fn test_partial_config() {
+        let cases: [(&str, &[&str], f64, &str); 3] = [
+            ("", &[], 20.0, PROJECT_NAME),
+            ("frame_rate = 30.0\n[MIDI]\ndevice_name = \"Drums\"", &[], 30.0, "Drums"),
+            (
+                "frame_rat = 30.0\n[GUI]\ndrift_tolerence = 2.0\n[MIDI.beat_counter]\nreset_after_bar = 4",
+                &["frame_rat", "GUI.drift_tolerence", "MIDI.beat_counter.reset_after_bar"],
+                20.0,
+                PROJECT_NAME,
+            ),
+        ];
+        for (source, expected_unknown_keys, frame_rate, device_name) in cases {
+            let (config, unknown_keys): (Config, _) =
+                unknown_keys::deserialize_collecting(toml::de::Deserializer::new(source)).unwrap();
+            assert_eq!(unknown_keys, expected_unknown_keys, "{source}");
+            assert!((config.frame_rate - frame_rate).abs() < f64::EPSILON, "{source}");
+            assert!((config.tick_rate - 2.0).abs() < f64::EPSILON, "{source}");
+            assert_eq!(config.midi.device_name, device_name, "{source}");
+            assert_eq!(config.midi.beat_counter.reset_after_bars, 2, "{source}");
+        }
+
+        // the built-in configuration only has known keys
+        let (_, unknown_keys): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(CONFIG)).unwrap();
+        assert!(unknown_keys.is_empty(), "{unknown_keys:?}");
+    }
+
+    #[test]
+    fn test_low_memory() {
+        let source = "low_memory = true\n[static_bpm_detection_parameters]\nhistogram_resolution = 1000";
+        let (mut config, _): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(source)).unwrap();
+        config.apply_low_memory();
+        assert!(config.midi.low_memory && config.gui.low_memory);
+        assert_eq!(
+            config.static_bpm_detection_parameters.histogram_resolution,
+            memory::LOW_MEMORY_HISTOGRAM_RESOLUTION
+        );
+        assert_eq!(config.tempo_map.max_points, memory::LOW_MEMORY_TEMPO_POINTS);
+
+        // a lower resolution is kept
+        let source = "low_memory = true\n[static_bpm_detection_parameters]\nhistogram_resolution = 100";
+        let (mut config, _): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(source)).unwrap();
+        config.apply_low_memory();
+        assert_eq!(config.static_bpm_detection_parameters.histogram_resolution, 100);
+
+        let mut config = Config::default();
+        config.apply_low_memory();
+        assert!(!config.midi.low_memory);
+        assert_eq!(config.tempo_map.max_points, TempoMapConfig::default().max_points);
+    }
+
+    #[test]
+    fn test_presets() {
+        let directory = std::env::temp_dir().join(format!("presets_test_{}", std::process::id()));
+        assert!(list_presets_in(&directory).is_empty());
+
+        let mut config = Config::default();
+        config.static_bpm_detection_parameters.histogram_resolution = 1000;
+        config.dynamic_bpm_detection_parameters.beats_lookback = 4;
+        config.save_preset_in(&directory, "drums").unwrap();
+        config.dynamic_bpm_detection_parameters.beats_lookback = 16;
+        config.save_preset_in(&directory, "keys").unwrap();
+        // not a preset
+        fs::write(directory.join("notes.txt"), "").unwrap();
+        assert_eq!(list_presets_in(&directory), ["drums", "keys"]);
+
+        let preset = config.load_preset_from(&directory, "drums").unwrap();
+        assert_eq!(preset.static_bpm_detection_parameters.histogram_resolution, 1000);
+        assert_eq!(preset.dynamic_bpm_detection_parameters.beats_lookback, 4);
+        assert_eq!(
+            config.load_preset_from(&directory, "keys").unwrap().dynamic_bpm_detection_parameters.beats_lookback,
+            16
+        );
+
+        config.low_memory = true;
+        assert_eq!(
+            config.load_preset_from(&directory, "drums").unwrap().static_bpm_detection_parameters.histogram_resolution,
+            memory::LOW_MEMORY_HISTOGRAM_RESOLUTION
+        );
+
+        assert!(config.load_preset_from(&directory, "missing").is_err());
+        for invalid in ["", "../config", ".hidden", "a\\b"] {
+            assert!(config.save_preset_in(&directory, invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(list_presets_in(&directory), ["drums", "keys"]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
     #[test]
     fn test_simple_keys() {
         assert_eq!(parse_key_event("a").unwrap(), KeyEvent::new(KeyCode::Char('a'), KeyModifiers::empty()));
@@ -438,4 +855,183 @@ mod tests {
 
         assert_eq!(parse_key_event("AlT-eNtEr").unwrap(), KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT));
     }
+
+    fn keybindings(bindings: &[(Option<Mode>, &str, Action)]) -> KeyBindings {
+        let mut keybindings = KeyBindings::default();
+        for (scope, sequence, action) in bindings {
+            keybindings.entry(*scope).or_default().insert(parse_key_sequence(sequence).unwrap(), action.clone());
+        }
+        keybindings
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let keybindings = keybindings(&[
+            (None, "<q>", Action::Quit),
+            (Some(Mode::DeviceView), "<r>", Action::MIDIRestart),
+            (Some(Mode::Keybindings), "<d>", Action::UnbindKey),
+            (Some(Mode::Keybindings), "<x>", Action::Unbound),
+        ]);
+        let sequence = |raw| parse_key_sequence(raw).unwrap();
+
+        assert_eq!(keybindings.conflicts(None, &sequence("<q>"), &Action::Save), vec![(None, Action::Quit)]);
+        // global bindings apply in every mode
+        assert_eq!(
+            keybindings.conflicts(Some(Mode::Home), &sequence("<q>"), &Action::Save),
+            vec![(None, Action::Quit)]
+        );
+        assert_eq!(
+            keybindings.conflicts(None, &sequence("<r>"), &Action::Save),
+            vec![(Some(Mode::DeviceView), Action::MIDIRestart)]
+        );
+        // modes don't overlap
+        assert!(keybindings.conflicts(Some(Mode::Keybindings), &sequence("<r>"), &Action::RebindKey).is_empty());
+        // binding an action to its own sequence again is not a conflict, neither is a disabled default
+        assert!(keybindings.conflicts(None, &sequence("<q>"), &Action::Quit).is_empty());
+        assert!(keybindings.conflicts(Some(Mode::Keybindings), &sequence("<x>"), &Action::Save).is_empty());
+        // a sequence starting with a bound key is a different sequence
+        assert!(keybindings.conflicts(None, &sequence("<q><q>"), &Action::Save).is_empty());
+    }
+
+    #[test]
+    fn test_rebind_unbind_restore() {
+        let defaults =
+            keybindings(&[(None, "<q>", Action::Quit), (None, "<esc>", Action::Quit), (None, "<s>", Action::Save)]);
+        let mut keybindings = defaults.clone();
+        let sequence = |raw| parse_key_sequence(raw).unwrap();
+
+        assert_eq!(
+            keybindings.rebind(&defaults, None, &Action::Quit, sequence("<s>")),
+            Err(vec![(None, Action::Save)])
+        );
+        assert_eq!(keybindings, defaults);
+
+        keybindings.rebind(&defaults, None, &Action::Quit, sequence("<ctrl-q><q>")).unwrap();
+        assert_eq!(keybindings.sequences(None, &Action::Quit), vec![sequence("<ctrl-q><q>")]);
+        // the default sequences stay disabled once saved and loaded again
+        assert_eq!(keybindings[&None][&sequence("<q>")], Action::Unbound);
+        assert_eq!(keybindings[&None][&sequence("<esc>")], Action::Unbound);
+
+        keybindings.unbind(&defaults, None, &Action::Quit);
+        assert!(keybindings.sequences(None, &Action::Quit).is_empty());
+        assert!(!keybindings[&None].contains_key(&sequence("<ctrl-q><q>")));
+
+        keybindings.rebind(&defaults, None, &Action::Save, sequence("<esc>")).unwrap();
+        let skipped = keybindings.restore_defaults(&defaults, None, &Action::Quit);
+        assert_eq!(skipped, vec![(sequence("<esc>"), None, Action::Save)]);
+        assert_eq!(keybindings.sequences(None, &Action::Quit), vec![sequence("<q>")]);
+    }
+
+    #[test]
+    fn test_key_sequence_round_trip() {
+        for raw in
+            ["<q>", "<g><g>", "<ctrl-alt-shift-a>", "<ctrl-x><ctrl-shift-s><alt-enter>", "<f5><shift-f12>", "<ctrl-->"]
+        {
+            let sequence = parse_key_sequence(raw).unwrap();
+            assert_eq!(parse_key_sequence(&key_sequence_to_string(&sequence)).unwrap(), sequence, "{raw}");
+        }
+        assert_eq!(
+            key_sequence_to_string(&[
+                KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
+                KeyEvent::new(KeyCode::Char('S'), KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+            ]),
+            "<ctrl-x><ctrl-shift-S>"
+        );
+    }
+
+    #[test]
+    fn test_serialize_keybindings() {
+        let keybindings = keybindings(&[
+            (None, "<ctrl-x><ctrl-s>", Action::Save),
+            (None, "<f1>", Action::Help),
+            (Some(Mode::Keybindings), "<alt-shift-r><d>", Action::RestoreDefaultKeys),
+            (Some(Mode::DeviceView), "<4>", Action::StartDemoPattern(PatternKind::Drums)),
+        ]);
+        let serialized = toml::to_string(&keybindings).unwrap();
+        let deserialized = KeyBindings::deserialize(toml::de::Deserializer::new(&serialized)).unwrap();
+        assert_eq!(deserialized, keybindings);
+    }
+
+    #[test]
+    fn test_action_arguments() {
+        let source = "\"<g><d>\" = \"Switch(DeviceView)\"\n[DeviceView]\n\"<1>\" = \"StartDemoPattern(Clave)\"";
+        let parsed = KeyBindings::deserialize(toml::de::Deserializer::new(source)).unwrap();
+        assert_eq!(
+            parsed,
+            keybindings(&[
+                (None, "<g><d>", Action::Switch(Mode::DeviceView)),
+                (Some(Mode::DeviceView), "<1>", Action::StartDemoPattern(PatternKind::Clave)),
+            ])
+        );
+        assert_eq!(Action::Switch(Mode::Keybindings).to_string(), "Switch(Keybindings)");
+        assert_eq!(Action::try_from("ChooseFeel(Triplet)"), Ok(Action::ChooseFeel(Feel::Triplet)));
+        assert_eq!(Action::ChooseFeel(Feel::Straight).to_string(), "ChooseFeel(Straight)");
+        assert_eq!(Action::try_from("LoadPreset(drum kit)"), Ok(Action::LoadPreset("drum kit".to_string())));
+        assert_eq!(Action::SavePreset("keys".to_string()).to_string(), "SavePreset(keys)");
+
+        for invalid in ["Switch(Nowhere)", "Switch", "Quit(Home)", "StartDemoPattern()", "Switch(Home", "LoadPreset()"]
+        {
+            assert_eq!(Action::try_from(invalid), Err(invalid));
+            let source = format!("\"<g>\" = \"{invalid}\"");
+            assert!(KeyBindings::deserialize(toml::de::Deserializer::new(&source)).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_merge_keybindings() {
+        let mut merged = keybindings(&[
+            (None, "<q>", Action::Quit),
+            (None, "<s>", Action::Save),
+            (Some(Mode::DeviceView), "<up>", Action::Up),
+            (Some(Mode::DeviceView), "<down>", Action::Down),
+            (Some(Mode::DeviceView), "<r>", Action::MIDIRestart),
+        ]);
+        let source = "\"<s>\" = \"Unbound\"\n\"<r>\" = \"Refresh\"\n[DeviceView]\n\"<up>\" = \"Help\"";
+        merged.merge(KeyBindings::deserialize(toml::de::Deserializer::new(source)).unwrap());
+
+        // only the sequences of the file are replaced, in their scope
+        assert_eq!(
+            merged,
+            keybindings(&[
+                (None, "<q>", Action::Quit),
+                (None, "<s>", Action::Unbound),
+                (None, "<r>", Action::Refresh),
+                (Some(Mode::DeviceView), "<up>", Action::Help),
+                (Some(Mode::DeviceView), "<down>", Action::Down),
+                (Some(Mode::DeviceView), "<r>", Action::MIDIRestart),
+            ])
+        );
+
+        // the binding of the mode wins over the global one, even when only the latter comes from the file
+        let mut matcher = KeySequenceMatcher::new(Duration::from_millis(800));
+        let now = Instant::now();
+        let r = parse_key_sequence("<r>").unwrap()[0];
+        assert_eq!(matcher.key(r, &merged, Mode::DeviceView, now), [Action::MIDIRestart]);
+        assert_eq!(matcher.key(r, &merged, Mode::Home, now), [Action::Refresh]);
+    }
+
+    #[test]
+    fn test_duplicate_sequences() {
+        // both spellings are the same sequence, the first one in alphabetical order is kept whatever the read order
+        let source = "\"<ctrl-a>\" = \"Quit\"\n\"<Ctrl-A>\" = \"Save\"\n\"<CTRL-a>\" = \"Help\"";
+        for _ in 0..10 {
+            let parsed = KeyBindings::deserialize(toml::de::Deserializer::new(source)).unwrap();
+            assert_eq!(parsed, keybindings(&[(None, "<ctrl-a>", Action::Help)]));
+        }
+        // an empty mode is kept
+        let parsed = KeyBindings::deserialize(toml::de::Deserializer::new("[Home]")).unwrap();
+        assert_eq!(parsed.get(&Some(Mode::Home)), Some(&HashMap::new()));
+    }
+
+    #[test]
+    fn test_shared_base_config() {
+        // what every frontend starts from, so the detection parameters are the same in all of them
+        let shared: Config = toml::from_str(include_str!("../../../config/base_config.toml")).unwrap();
+        let (config, unknown_keys): (Config, _) =
+            unknown_keys::deserialize_collecting(toml::de::Deserializer::new(CONFIG)).unwrap();
+        assert!(unknown_keys.is_empty(), "{unknown_keys:?}");
+        assert_eq!(config.static_bpm_detection_parameters, shared.static_bpm_detection_parameters);
+        assert_eq!(config.dynamic_bpm_detection_parameters, shared.dynamic_bpm_detection_parameters);
+        assert_eq!(config.gui.interpolation_curve.to_bits(), shared.gui.interpolation_curve.to_bits());
+    }
 }
@@ -0,0 +1,166 @@
+//! Sends the detection parameters changed in the GUI at most once per `COALESCE_WINDOW`. Dragging a slider changes
+//! them on every frame, and each `Action::StaticBPMDetectionConfig` or `Action::DynamicBPMDetectionConfig` is a round
+//! trip to the detection worker. Changes made within the window are sent once, with their latest value
+
+use std::{sync::Arc, time::Duration};
+
+use errors::{Report, Result};
+use midi::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+use sync::Mutex;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::action::Action;
+
+const COALESCE_WINDOW: Duration = Duration::from_millis(50);
+
+// latest values not sent yet
+#[derive(Default)]
+struct Pending {
+    static_bpm_detection_parameters: Option<StaticBPMDetectionParameters>,
+    dynamic_bpm_detection_parameters: Option<DynamicBPMDetectionParameters>,
+}
+
+/// Marks the parameters as changed, cheap to clone, from any thread
+#[derive(Clone)]
+pub struct ConfigBroadcaster {
+    pending: Arc<Mutex<Pending>>,
+    // wakes the task up, it then waits for the window to end
+    changed: UnboundedSender<()>,
+}
+
+/// Sends the changed parameters as actions once run
+pub struct ConfigBroadcasterTask {
+    pending: Arc<Mutex<Pending>>,
+    changed: UnboundedReceiver<()>,
+    action_tx: UnboundedSender<Action>,
+}
+
+impl ConfigBroadcaster {
+    #[must_use]
+    pub fn new(action_tx: UnboundedSender<Action>) -> (Self, ConfigBroadcasterTask) {
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let (changed_tx, changed) = mpsc::unbounded_channel();
+        (Self { pending: pending.clone(), changed: changed_tx }, ConfigBroadcasterTask { pending, changed, action_tx })
+    }
+
+    /// Fails only if the task is gone
+    pub fn static_changed(&self, static_bpm_detection_parameters: StaticBPMDetectionParameters) -> Result<()> {
+        self.pending.lock().static_bpm_detection_parameters = Some(static_bpm_detection_parameters);
+        self.wake()
+    }
+
+    /// Fails only if the task is gone
+    pub fn dynamic_changed(&self, dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters) -> Result<()> {
+        self.pending.lock().dynamic_bpm_detection_parameters = Some(dynamic_bpm_detection_parameters);
+        self.wake()
+    }
+
+    fn wake(&self) -> Result<()> {
+        self.changed
+            .send(())
+            .map_err(|_| Report::msg("the parameters can't be applied anymore, the task sending them stopped"))
+    }
+}
+
+impl ConfigBroadcasterTask {
+    /// Until every `ConfigBroadcaster` is dropped, or the actions aren't received anymore
+    pub async fn run(mut self) {
+        while self.changed.recv().await.is_some() {
+            tokio::time::sleep(COALESCE_WINDOW).await;
+            // the changes made meanwhile are all in `pending`
+            while self.changed.try_recv().is_ok() {}
+            if self.send_pending().is_err() {
+                return;
+            }
+        }
+        // the last changes made before the broadcasters were dropped
+        self.send_pending().ok();
+    }
+
+    fn send_pending(&self) -> Result<()> {
+        let Pending { static_bpm_detection_parameters, dynamic_bpm_detection_parameters } =
+            std::mem::take(&mut *self.pending.lock());
+        if let Some(static_bpm_detection_parameters) = static_bpm_detection_parameters {
+            self.action_tx.send(Action::StaticBPMDetectionConfig(static_bpm_detection_parameters))?;
+        }
+        if let Some(dynamic_bpm_detection_parameters) = dynamic_bpm_detection_parameters {
+            self.action_tx.send(Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(action_rx: &mut UnboundedReceiver<Action>) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Ok(action) = action_rx.try_recv() {
+            actions.push(action);
+        }
+        actions
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_drag() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (config_broadcaster, config_broadcaster_task) = ConfigBroadcaster::new(action_tx);
+        let task = tokio::spawn(config_broadcaster_task.run());
+
+        // a slider dragged over 60 frames, about one second
+        let mut static_bpm_detection_parameters = StaticBPMDetectionParameters::default();
+        for frame in 0..60u8 {
+            static_bpm_detection_parameters.bpm_center = 80.0 + f32::from(frame) / 2.0;
+            config_broadcaster.static_changed(static_bpm_detection_parameters.clone()).unwrap();
+            tokio::time::sleep(Duration::from_millis(16)).await;
+        }
+        tokio::time::sleep(COALESCE_WINDOW * 2).await;
+
+        let actions = received(&mut action_rx);
+        // one per window at most, with some slack for a slow test runner
+        assert!(!actions.is_empty() && actions.len() <= 30, "{}", actions.len());
+        let Some(Action::StaticBPMDetectionConfig(last)) = actions.last() else {
+            panic!("{actions:?}");
+        };
+        assert_eq!(*last, static_bpm_detection_parameters);
+        assert!(actions.iter().all(|action| matches!(action, Action::StaticBPMDetectionConfig(_))));
+
+        drop(config_broadcaster);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_both_configs_in_a_window() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (config_broadcaster, config_broadcaster_task) = ConfigBroadcaster::new(action_tx);
+        // all made before the task runs
+        for beats_lookback in 1..=60 {
+            config_broadcaster
+                .dynamic_changed(DynamicBPMDetectionParameters { beats_lookback, ..Default::default() })
+                .unwrap();
+        }
+        config_broadcaster.static_changed(StaticBPMDetectionParameters::default()).unwrap();
+        let task = tokio::spawn(config_broadcaster_task.run());
+        tokio::time::sleep(COALESCE_WINDOW * 2).await;
+
+        let actions = received(&mut action_rx);
+        assert_eq!(actions.len(), 2, "{actions:?}");
+        assert!(matches!(
+            &actions[1],
+            Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters)
+                if dynamic_bpm_detection_parameters.beats_lookback == 60
+        ));
+
+        drop(config_broadcaster);
+        task.await.unwrap();
+    }
+
+    #[test]
+    fn test_stopped_task() {
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let (config_broadcaster, config_broadcaster_task) = ConfigBroadcaster::new(action_tx);
+        drop(config_broadcaster_task);
+        assert!(config_broadcaster.dynamic_changed(DynamicBPMDetectionParameters::default()).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Saves the configuration without blocking the render loop nor the GUI frame. The caller serializes it, which is
+//! cheap, the file is written on a blocking thread. Saves requested while one is being written are coalesced, only the
+//! latest one is written next. The file is replaced at once, it is never read half written
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use errors::{Report, Result};
+use log::{error, info};
+use sync::Mutex;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+use crate::{action::Action, config::Config};
+
+enum Request {
+    Save { path: PathBuf, serialized: String },
+    // answered once the saves requested before are written
+    Flush(oneshot::Sender<()>),
+}
+
+/// Path and modification time of the file last written, for `ConfigWatcher` to skip the saves of the application
+#[derive(Clone, Debug, Default)]
+pub struct LastWritten(Arc<Mutex<Option<(PathBuf, SystemTime)>>>);
+
+impl LastWritten {
+    #[must_use]
+    pub fn is(&self, path: &Path, modified: SystemTime) -> bool {
+        self.0
+            .lock()
+            .as_ref()
+            .is_some_and(|(written, written_modified)| written == path && *written_modified == modified)
+    }
+
+    pub(crate) fn record(&self, path: PathBuf) {
+        *self.0.lock() =
+            fs::metadata(&path).and_then(|metadata| metadata.modified()).ok().map(|modified| (path, modified));
+    }
+}
+
+/// Requests the saves, cheap to clone, from any thread
+#[derive(Clone)]
+pub struct ConfigSaver {
+    requests: UnboundedSender<Request>,
+    last_written: LastWritten,
+}
+
+/// Writes the requested saves once run, failures are sent as `Action::Error`
+pub struct ConfigSaverTask {
+    requests: UnboundedReceiver<Request>,
+    action_tx: UnboundedSender<Action>,
+    last_written: LastWritten,
+}
+
+impl ConfigSaver {
+    #[must_use]
+    pub fn new(action_tx: UnboundedSender<Action>) -> (Self, ConfigSaverTask) {
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+        let last_written = LastWritten::default();
+        (
+            Self { requests: requests_tx, last_written: last_written.clone() },
+            ConfigSaverTask { requests, action_tx, last_written },
+        )
+    }
+
+    #[must_use]
+    pub fn last_written(&self) -> LastWritten {
+        self.last_written.clone()
+    }
+
+    /// Fails only if `config` can't be serialized or the task is gone, the outcome of the write is reported by the task
+    pub fn save(&self, config: &Config) -> Result<()> {
+        self.save_to(Config::config_path(), config.serialized()?)
+    }
+
+    fn save_to(&self, path: PathBuf, serialized: String) -> Result<()> {
+        self.requests
+            .send(Request::Save { path, serialized })
+            .map_err(|_| Report::msg("the configuration can't be saved anymore, the task saving it stopped"))
+    }
+
+    /// Waits for the saves requested so far, returns right away if the task is gone
+    pub async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.requests.send(Request::Flush(done)).is_ok() {
+            flushed.await.ok();
+        }
+    }
+}
+
+impl ConfigSaverTask {
+    /// Until every `ConfigSaver` is dropped
+    pub async fn run(mut self) {
+        while let Some(request) = self.requests.recv().await {
+            // along with the ones queued meanwhile
+            let mut requests = vec![request];
+            while let Ok(request) = self.requests.try_recv() {
+                requests.push(request);
+            }
+            let mut latest_save = None;
+            let mut flushes = Vec::new();
+            for request in requests {
+                match request {
+                    Request::Save { path, serialized } => latest_save = Some((path, serialized)),
+                    Request::Flush(done) => flushes.push(done),
+                }
+            }
+            if let Some((path, serialized)) = latest_save {
+                self.write(path, serialized).await;
+            }
+            for done in flushes {
+                done.send(()).ok();
+            }
+        }
+    }
+
+    async fn write(&self, path: PathBuf, serialized: String) {
+        let written = tokio::task::spawn_blocking({
+            let path = path.clone();
+            let last_written = self.last_written.clone();
+            move || replace(&path, &serialized).map(|()| last_written.record(path))
+        })
+        .await
+        .map_err(Report::new)
+        .and_then(|written| written.map_err(Report::new));
+        match written {
+            Ok(()) => info!("configuration saved to {}", path.display()),
+            Err(e) => {
+                error!("could not save the configuration to {}: {e:?}", path.display());
+                self.action_tx.send(Action::Error(format!("Could not save the configuration: {e}"))).ok();
+            }
+        }
+    }
+}
+
+// written next to it then renamed over it, the rename stays on the same file system
+fn replace(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().map_or_else(|| "config".into(), |file_name| file_name.to_string_lossy());
+    let temporary_path = path.with_file_name(format!(".{file_name}.tmp"));
+    fs::write(&temporary_path, contents)?;
+    fs::rename(&temporary_path, path).inspect_err(|_| {
+        fs::remove_file(&temporary_path).ok();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("config_saver_{name}_{}", std::process::id()));
+        fs::remove_dir_all(&directory).ok();
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_saves() {
+        let directory = directory("coalesced");
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (config_saver, config_saver_task) = ConfigSaver::new(action_tx);
+        // all queued before the task runs
+        for index in 0..3 {
+            config_saver.save_to(directory.join(format!("config_{index}.toml")), format!("index = {index}")).unwrap();
+        }
+        let task = tokio::spawn(config_saver_task.run());
+        config_saver.flush().await;
+
+        assert!(!directory.join("config_0.toml").exists());
+        assert!(!directory.join("config_1.toml").exists());
+        assert_eq!(fs::read_to_string(directory.join("config_2.toml")).unwrap(), "index = 2");
+        assert!(!directory.join(".config_2.toml.tmp").exists());
+        let modified = fs::metadata(directory.join("config_2.toml")).unwrap().modified().unwrap();
+        assert!(config_saver.last_written().is(&directory.join("config_2.toml"), modified));
+        assert!(action_rx.try_recv().is_err());
+
+        // saved again once the previous one is written
+        config_saver.save_to(directory.join("config_0.toml"), "index = 0".to_string()).unwrap();
+        config_saver.flush().await;
+        assert!(directory.join("config_0.toml").exists());
+
+        drop(config_saver);
+        task.await.unwrap();
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failed_save() {
+        let directory = directory("failed");
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let (config_saver, config_saver_task) = ConfigSaver::new(action_tx);
+        tokio::spawn(config_saver_task.run());
+
+        config_saver.save_to(directory.join("missing").join("config.toml"), String::new()).unwrap();
+        config_saver.flush().await;
+        assert!(matches!(action_rx.try_recv(), Ok(Action::Error(message)) if message.contains("Could not save")));
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn test_stopped_task() {
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let (config_saver, config_saver_task) = ConfigSaver::new(action_tx);
+        drop(config_saver_task);
+        assert!(config_saver.save(&Config::default()).is_err());
+    }
+}
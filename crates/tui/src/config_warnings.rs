@@ -0,0 +1,129 @@
+use log::error;
+use midi::{
+    bpm_detection_receiver::BPMDetectionReceiver, BarPosition, BeatPhase, BpmEstimate, ConfigWarning, FeelAmbiguity,
+    MultiResolutionHistogram, ResolutionFloor, SessionSummary, StaticBPMDetectionParameters, TempoBand,
+    TimedMidiNoteOn,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::tui::Event;
+
+/// Sends the warnings about the detection parameters, the estimates and their histogram, the DAW tempo, the bar
+/// position, the tapped tempo, the share of recent notes and the feel ambiguities to the TUI, while forwarding
+/// everything to the wrapped receiver
+#[derive(Clone)]
+pub struct ConfigWarningsForwarder<B: BPMDetectionReceiver> {
+    bpm_detection_receiver: B,
+    event_tx: UnboundedSender<Event>,
+}
+
+impl<B: BPMDetectionReceiver> ConfigWarningsForwarder<B> {
+    pub fn new(bpm_detection_receiver: B, event_tx: UnboundedSender<Event>) -> Self {
+        Self { bpm_detection_receiver, event_tx }
+    }
+}
+
+impl<B: BPMDetectionReceiver> BPMDetectionReceiver for ConfigWarningsForwarder<B> {
+    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], estimate: BpmEstimate) {
+        self.bpm_detection_receiver.receive_bpm_histogram_data(histogram_data_points, estimate);
+        if let Err(e) = self.event_tx.send(Event::Estimate(estimate)) {
+            error!("error while notifying the estimate {e:?}");
+        }
+        if let Err(e) = self.event_tx.send(Event::Histogram(histogram_data_points.to_vec(), estimate.bpm)) {
+            error!("error while notifying the histogram {e:?}");
+        }
+    }
+
+    fn receive_daw_bpm(&self, bpm: f32) {
+        self.bpm_detection_receiver.receive_daw_bpm(bpm);
+        if let Err(e) = self.event_tx.send(Event::DawBpm(bpm)) {
+            error!("error while notifying the DAW tempo {e:?}");
+        }
+    }
+
+    fn receive_clock_bpm(&self, bpm: Option<f32>) {
+        self.bpm_detection_receiver.receive_clock_bpm(bpm);
+    }
+
+    fn receive_note(&self, timed_midi_note_on: &TimedMidiNoteOn) {
+        self.bpm_detection_receiver.receive_note(timed_midi_note_on);
+    }
+
+    fn receive_config_warnings(&self, config_warnings: &[ConfigWarning]) {
+        self.bpm_detection_receiver.receive_config_warnings(config_warnings);
+        if let Err(e) = self.event_tx.send(Event::ConfigWarnings(config_warnings.to_vec())) {
+            error!("error while notifying configuration warnings {e:?}");
+        }
+    }
+
+    fn receive_velocity_gate(&self, threshold: Option<u8>) {
+        self.bpm_detection_receiver.receive_velocity_gate(threshold);
+    }
+
+    fn receive_auto_zoom(&self, zoomed: Option<&StaticBPMDetectionParameters>) {
+        self.bpm_detection_receiver.receive_auto_zoom(zoomed);
+    }
+
+    fn receive_tapped_tempo(&self, bpm: Option<f32>) {
+        self.bpm_detection_receiver.receive_tapped_tempo(bpm);
+        if let Err(e) = self.event_tx.send(Event::TappedTempo(bpm)) {
+            error!("error while notifying the tapped tempo {e:?}");
+        }
+    }
+
+    fn receive_frozen_bpm_histogram_data(&self, frozen: Option<(&[f32], f32)>) {
+        self.bpm_detection_receiver.receive_frozen_bpm_histogram_data(frozen);
+    }
+
+    fn receive_beat_phase(&self, beat_phase: Option<BeatPhase>) {
+        self.bpm_detection_receiver.receive_beat_phase(beat_phase);
+    }
+
+    fn receive_bar_position(&self, bar_position: Option<BarPosition>) {
+        self.bpm_detection_receiver.receive_bar_position(bar_position);
+        if let Err(e) = self.event_tx.send(Event::BarPosition(bar_position)) {
+            error!("error while notifying the bar position {e:?}");
+        }
+    }
+
+    fn receive_session_summary(&self, session_summary: SessionSummary) {
+        self.bpm_detection_receiver.receive_session_summary(session_summary);
+    }
+
+    fn receive_note_freshness(&self, freshness: Option<f32>) {
+        self.bpm_detection_receiver.receive_note_freshness(freshness);
+        if let Err(e) = self.event_tx.send(Event::NoteFreshness(freshness)) {
+            error!("error while notifying the share of recent notes {e:?}");
+        }
+    }
+
+    fn receive_feel_ambiguity(&self, feel_ambiguity: Option<FeelAmbiguity>) {
+        self.bpm_detection_receiver.receive_feel_ambiguity(feel_ambiguity);
+        if let Err(e) = self.event_tx.send(Event::FeelAmbiguity(feel_ambiguity)) {
+            error!("error while notifying the feel ambiguity {e:?}");
+        }
+    }
+
+    fn receive_tempo_band(&self, tempo_band: Option<TempoBand>) {
+        self.bpm_detection_receiver.receive_tempo_band(tempo_band);
+        if let Err(e) = self.event_tx.send(Event::TempoBand(tempo_band)) {
+            error!("error while notifying the tempo band {e:?}");
+        }
+    }
+
+    fn receive_multi_resolution_histogram(&self, multi_resolution_histogram: Option<MultiResolutionHistogram<'_>>) {
+        self.bpm_detection_receiver.receive_multi_resolution_histogram(multi_resolution_histogram);
+    }
+
+    fn phase_split_window(&self) -> Option<f32> {
+        self.bpm_detection_receiver.phase_split_window()
+    }
+
+    fn receive_phase_split_histogram(&self, phase_split: Option<(&[f32], &[f32])>) {
+        self.bpm_detection_receiver.receive_phase_split_histogram(phase_split);
+    }
+
+    fn receive_resolution_floor(&self, resolution_floor: Option<ResolutionFloor>) {
+        self.bpm_detection_receiver.receive_resolution_floor(resolution_floor);
+    }
+}
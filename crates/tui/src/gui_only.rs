@@ -0,0 +1,237 @@
+//! The GUI as a desktop app of its own, without the terminal UI. The MIDI input is picked in the settings panel of the
+//! GUI, the configuration is loaded from and saved to the same paths.
+
+use std::sync::mpsc::SyncSender;
+
+use errors::{elevate_logging, error_debug_window, LogErrorWithExt, Result};
+use gui::{GuiRemote, MidiInputs};
+use log::{debug, error, info};
+use midi::MidiInputPort;
+use sync::{ArcRwLock, ArcRwLockExt};
+use tokio::sync::{
+    mpsc,
+    mpsc::{UnboundedReceiver, UnboundedSender},
+};
+
+use crate::{
+    action::Action,
+    config::Config,
+    config_warnings::ConfigWarningsForwarder,
+    lifecycle::signals::spawn_signal_task,
+    services::midi::MidiService,
+    tempo_recorder::TempoRecorder,
+    tui::Event,
+    utils::dispatch::{try_dispatch_concurrently, ActionHandler, EventHandler},
+};
+
+/// MIDI inputs listed in the settings panel, refreshed when devices are plugged or unplugged
+#[derive(Clone, Debug)]
+pub struct DeviceChoice {
+    devices: Vec<MidiInputPort>,
+    selected: MidiInputPort,
+    // why the selected input can't be listened to
+    message: Option<String>,
+}
+
+pub type SharedDeviceChoice = ArcRwLock<DeviceChoice>;
+
+impl Default for DeviceChoice {
+    fn default() -> Self {
+        Self { devices: Vec::new(), selected: MidiInputPort::None, message: None }
+    }
+}
+
+impl DeviceChoice {
+    /// Devices listed again. The selected input stays selected while it is there, see `MidiInputPort::position_in`,
+    /// otherwise the first device is. Returns the input to listen to when the selection changed
+    pub fn set_devices(&mut self, devices: Vec<MidiInputPort>) -> Option<MidiInputPort> {
+        let selected = match self.selected.position_in(&devices) {
+            Some(position) if self.selected != MidiInputPort::None => devices[position].clone(),
+            _ => devices
+                .iter()
+                .find(|device| matches!(device, MidiInputPort::Device(..)))
+                .cloned()
+                .unwrap_or(MidiInputPort::None),
+        };
+        self.devices = devices;
+        // the same device plugged again is listened to again
+        if selected.is_same_port(&self.selected) {
+            return None;
+        }
+        info!("listening to {selected}");
+        self.selected = selected.clone();
+        self.message = None;
+        Some(selected)
+    }
+
+    /// Returns the input to listen to, none if there is no such input
+    pub fn select(&mut self, id: &str) -> Option<MidiInputPort> {
+        let device = self.devices.iter().find(|device| device.as_str() == id)?.clone();
+        self.selected = device.clone();
+        self.message = None;
+        Some(device)
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    #[must_use]
+    pub fn midi_inputs(&self) -> MidiInputs {
+        let message = self.message.clone().or_else(|| {
+            (!self.devices.iter().any(|device| matches!(device, MidiInputPort::Device(..))))
+                .then(|| "No MIDI device found, plug one in".to_string())
+        });
+        MidiInputs {
+            inputs: self.devices.iter().map(|device| (device.to_string(), device.to_string())).collect(),
+            selected: Some(self.selected.to_string()),
+            message,
+        }
+    }
+}
+
+/// Runs the MIDI service for the GUI, which is started right away, until its window is closed or a signal asks to
+/// stop
+pub async fn run(
+    start_gui: SyncSender<()>,
+    action_tx: UnboundedSender<Action>,
+    mut action_rx: UnboundedReceiver<Action>,
+    mut config: Config,
+    mut gui_exit_receiver: UnboundedReceiver<()>,
+    gui_remote: GuiRemote,
+    device_choice: SharedDeviceChoice,
+) -> Result<()> {
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+
+    let signal_task = spawn_signal_task({
+        let action_tx = action_tx.clone();
+        move || Ok(action_tx.send(Action::Quit)?)
+    })?;
+
+    let tempo_recorder = TempoRecorder::new(gui_remote.clone(), &config.tempo_map);
+
+    let mut services = [MidiService::box_new(
+        &config.midi,
+        config.static_bpm_detection_parameters.clone(),
+        config.dynamic_bpm_detection_parameters.clone(),
+        event_tx.clone(),
+        ConfigWarningsForwarder::new(tempo_recorder.clone(), event_tx.clone()),
+    )
+    .await?];
+
+    start_gui.send(()).log_error_msg("unable to start GUI")?;
+
+    loop {
+        tokio::select! {
+            Some(event) = event_rx.recv() => {
+                // the list is sent again by the MIDI service on each hotplug notification
+                if let Event::DeviceList(devices) = &event {
+                    let selected = device_choice.get_mut(|device_choice| device_choice.set_devices(devices.clone()));
+                    if let Some(selected) = selected {
+                        action_tx.send(Action::SelectDevice(selected))?;
+                    }
+                    gui_remote.request_repaint();
+                }
+                try_dispatch_concurrently(
+                    services.iter_mut().map(Box::as_mut),
+                    &event,
+                    &action_tx,
+                    EventHandler::handle_event,
+                )
+                .await?;
+            }
+            Some(action) = action_rx.recv() => {
+                debug!("{action:?}");
+                match action {
+                    Action::Quit => break,
+                    Action::Error(ref message) => {
+                        error!("{message}");
+                        device_choice.get_mut(|device_choice| device_choice.set_message(message.clone()));
+                        gui_remote.request_repaint();
+                    }
+                    Action::ShowGUI => gui_remote.focus_window(),
+                    Action::ToggleAlwaysOnTop => gui_remote.cycle_always_on_top(),
+                    Action::Save => gui_remote.save_config(),
+                    Action::ExportSnapshot => gui_remote.export_snapshot(),
+                    Action::ExportTempoMap => {
+                        tempo_recorder.export().log_error_msg("could not export tempo map").ok();
+                    }
+                    Action::ExportEffectiveConfig => {
+                        config.export_effective_config().log_error_msg("could not export effective config").ok();
+                    }
+                    Action::ElevateLogging => {
+                        if elevate_logging(error_debug_window()) {
+                            info!("debug logs for the next {:?}", error_debug_window());
+                        }
+                    }
+                    Action::StaticBPMDetectionConfig(ref static_bpm_detection_parameters) => {
+                        config.static_bpm_detection_parameters = static_bpm_detection_parameters.clone();
+                    }
+                    Action::DynamicBPMDetectionConfig(ref dynamic_bpm_detection_parameters) => {
+                        config.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters.clone();
+                    }
+                    _ => {}
+                }
+                try_dispatch_concurrently(
+                    services.iter_mut().map(Box::as_mut),
+                    &action,
+                    &action_tx,
+                    ActionHandler::handle_action,
+                )
+                .await?;
+            }
+            _ = gui_exit_receiver.recv() => break,
+        }
+    }
+
+    signal_task.abort();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn virtual_ports(names: &[&str]) -> Vec<MidiInputPort> {
+        [MidiInputPort::None]
+            .into_iter()
+            .chain(names.iter().map(|name| MidiInputPort::Virtual((*name).to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_device_choice() {
+        let mut device_choice = DeviceChoice::default();
+        // nothing to listen to yet
+        assert_eq!(device_choice.set_devices(virtual_ports(&["a", "b"])), None);
+        let midi_inputs = device_choice.midi_inputs();
+        assert_eq!(midi_inputs.inputs.len(), 3);
+        assert_eq!(midi_inputs.selected.as_deref(), Some(MidiInputPort::None.as_str()));
+        assert!(midi_inputs.message.is_some());
+
+        assert_eq!(device_choice.select("c"), None);
+        assert_eq!(device_choice.select("b"), Some(MidiInputPort::Virtual("b".to_string())));
+        assert_eq!(device_choice.midi_inputs().selected.as_deref(), Some("b"));
+
+        // another device is plugged, the selection stays
+        assert_eq!(device_choice.set_devices(virtual_ports(&["c", "a", "b"])), None);
+        assert_eq!(device_choice.midi_inputs().selected.as_deref(), Some("b"));
+
+        // the selected one is unplugged
+        assert_eq!(device_choice.set_devices(virtual_ports(&["c", "a"])), Some(MidiInputPort::None));
+        assert_eq!(device_choice.midi_inputs().selected.as_deref(), Some(MidiInputPort::None.as_str()));
+    }
+
+    #[test]
+    fn test_message() {
+        let mut device_choice = DeviceChoice::default();
+        device_choice.set_devices(virtual_ports(&["a"]));
+        device_choice.select("a");
+        device_choice.set_message("a is used by another application".to_string());
+        assert_eq!(device_choice.midi_inputs().message.as_deref(), Some("a is used by another application"));
+
+        // picking again clears it
+        device_choice.select("a");
+        assert_eq!(device_choice.midi_inputs().message.as_deref(), Some("No MIDI device found, plug one in"));
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{self, Write};
+
+use errors::{Report, Result};
+use log::{error, info};
+use midi::{MidiInputPort, MidiService};
+use tokio::sync::mpsc;
+
+use crate::{config::Config, json_status::JsonStatus, lifecycle::signals::spawn_signal_task};
+
+/// Running without the terminal UI and the GUI, see `run`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeadlessOptions {
+    // prints the estimates as JSON lines on stdout
+    pub json_status: bool,
+    // part of the name of the device to listen to, the first device otherwise
+    pub device: Option<String>,
+}
+
+/// Listens to a device until a signal asks to stop. The tempo is sent as configured, and with `json_status` each
+/// estimate is printed on stdout, followed by a summary line once stopped
+pub async fn run(config: Config, headless_options: HeadlessOptions) -> Result<()> {
+    let hysteresis = config.tempo_map.hysteresis;
+    let beats_lookback = config.dynamic_bpm_detection_parameters.beats_lookback;
+    if headless_options.json_status {
+        run_with(config, headless_options, JsonStatus::new(io::stdout(), hysteresis, beats_lookback)).await
+    } else {
+        run_with(config, headless_options, JsonStatus::new(io::sink(), hysteresis, beats_lookback)).await
+    }
+}
+
+async fn run_with<W: Write + Send + 'static>(
+    config: Config,
+    headless_options: HeadlessOptions,
+    json_status: JsonStatus<W>,
+) -> Result<()> {
+    let (quit_tx, mut quit_rx) = mpsc::unbounded_channel();
+    let signal_task = spawn_signal_task(move || Ok(quit_tx.send(())?))?;
+
+    let midi_service = tokio::task::spawn_blocking({
+        let json_status = json_status.clone();
+        move || {
+            MidiService::new(
+                config.midi,
+                config.static_bpm_detection_parameters,
+                config.dynamic_bpm_detection_parameters,
+                #[cfg(target_os = "macos")]
+                || info!("MIDI devices changed"),
+                json_status,
+            )
+        }
+    })
+    .await??;
+
+    let device = headless_options.device.clone();
+    let port = tokio::task::block_in_place(|| {
+        midi_service.execute(move |midi_in, midi_input_connection| {
+            let port = midi_in.get_ports()?.into_iter().find(|port| match (port, &device) {
+                (MidiInputPort::Device(_, name), Some(device)) => name.display.contains(device.as_str()),
+                (MidiInputPort::Device(..), None) => true,
+                _ => false,
+            });
+            if let Some(port) = &port {
+                *midi_input_connection = midi_in.listen(port, |_| ())?;
+            }
+            Ok(port)
+        })
+    })?;
+    let Some(port) = port else {
+        return Err(Report::msg(match headless_options.device {
+            Some(device) => format!("no MIDI device matches {device}"),
+            None => "no MIDI device found".to_string(),
+        }));
+    };
+    info!("listening to {port} without UI");
+    json_status.set_device(port.as_str());
+
+    quit_rx.recv().await;
+    signal_task.abort();
+
+    if let Err(e) = tokio::task::block_in_place(|| {
+        midi_service.execute(|_, midi_input_connection| {
+            midi_input_connection.take();
+            Ok(())
+        })
+    }) {
+        error!("error while closing {port}: {e:?}");
+    }
+    json_status.finish();
+    Ok(())
+}
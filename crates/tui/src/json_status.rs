@@ -0,0 +1,261 @@
+use std::{collections::VecDeque, io::Write, sync::Arc};
+
+use chrono::{Duration, Local, SecondsFormat};
+use instant::Instant;
+use log::error;
+use midi::{
+    bpm::bpm_to_beat_duration, bpm_detection_receiver::BPMDetectionReceiver, tempo_source::note_density_confidence,
+    BpmEstimate, TimedMidiNoteOn,
+};
+use serde::Serialize;
+use sync::Mutex;
+
+/// One line per estimate that moved away from the last printed one
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StatusLine {
+    pub ts: String,
+    pub bpm: f32,
+    pub confidence: f32,
+    pub daw_bpm: Option<f32>,
+    pub device: String,
+    pub notes_per_sec: f32,
+}
+
+/// Printed once when shutting down
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SummaryLine {
+    pub ts: String,
+    pub summary: Summary,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub device: String,
+    pub duration_secs: f32,
+    pub notes: u64,
+    pub estimates: u64,
+    pub lines: u64,
+    pub last_bpm: Option<f32>,
+}
+
+struct State<W: Write> {
+    output: W,
+    device: String,
+    daw_bpm: Option<f32>,
+    // timestamps of the recent notes, as many as the confidence and the note rate need
+    notes: VecDeque<Duration>,
+    note_count: u64,
+    estimates: u64,
+    lines: u64,
+    printed_bpm: Option<f32>,
+}
+
+/// Writes the tempo estimates as JSON lines, leaving out those within `hysteresis` of the last one written
+#[derive(Clone)]
+pub struct JsonStatus<W: Write + Send + 'static> {
+    state: Arc<Mutex<State<W>>>,
+    hysteresis: f32,
+    beats_lookback: u8,
+    start: Instant,
+}
+
+impl<W: Write + Send + 'static> JsonStatus<W> {
+    pub fn new(output: W, hysteresis: f32, beats_lookback: u8) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                output,
+                device: String::new(),
+                daw_bpm: None,
+                notes: VecDeque::new(),
+                note_count: 0,
+                estimates: 0,
+                lines: 0,
+                printed_bpm: None,
+            })),
+            hysteresis,
+            beats_lookback,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn set_device(&self, device: &str) {
+        device.clone_into(&mut self.state.lock().device);
+    }
+
+    /// Writes the summary line, to call once no estimate can come anymore
+    pub fn finish(&self) {
+        let mut state = self.state.lock();
+        let summary_line = SummaryLine {
+            ts: now(),
+            summary: Summary {
+                device: state.device.clone(),
+                duration_secs: self.start.elapsed().as_secs_f32(),
+                notes: state.note_count,
+                estimates: state.estimates,
+                lines: state.lines,
+                last_bpm: state.printed_bpm,
+            },
+        };
+        write_line(&mut state.output, &summary_line);
+    }
+}
+
+impl<W: Write + Send + 'static> BPMDetectionReceiver for JsonStatus<W> {
+    fn receive_bpm_histogram_data(&mut self, _histogram_data_points: &[f32], estimate: BpmEstimate) {
+        let detected_bpm = estimate.bpm;
+        let mut state = self.state.lock();
+        state.estimates += 1;
+        if state.printed_bpm.is_some_and(|printed_bpm| (detected_bpm - printed_bpm).abs() < self.hysteresis) {
+            return;
+        }
+
+        let lookback = bpm_to_beat_duration(detected_bpm) * i32::from(self.beats_lookback);
+        let newest_note = state.notes.back().copied().unwrap_or_else(Duration::zero);
+        let window = lookback.max(Duration::seconds(1));
+        while state.notes.front().is_some_and(|note| newest_note - *note > window) {
+            state.notes.pop_front();
+        }
+        let notes_in_lookback = state.notes.iter().filter(|note| newest_note - **note <= lookback).count();
+        let notes_in_last_second =
+            state.notes.iter().filter(|note| newest_note - **note < Duration::seconds(1)).count();
+
+        let status_line = StatusLine {
+            ts: now(),
+            bpm: detected_bpm,
+            confidence: note_density_confidence(notes_in_lookback, self.beats_lookback),
+            daw_bpm: state.daw_bpm,
+            device: state.device.clone(),
+            notes_per_sec: notes_in_last_second as f32,
+        };
+        write_line(&mut state.output, &status_line);
+        state.printed_bpm = Some(detected_bpm);
+        state.lines += 1;
+    }
+
+    fn receive_daw_bpm(&self, bpm: f32) {
+        self.state.lock().daw_bpm = Some(bpm);
+    }
+
+    fn receive_note(&self, timed_midi_note_on: &TimedMidiNoteOn) {
+        let mut state = self.state.lock();
+        // the timestamps start over with a new device
+        if state.notes.back().is_some_and(|note| *note > timed_midi_note_on.timestamp) {
+            state.notes.clear();
+        }
+        state.notes.push_back(timed_midi_note_on.timestamp);
+        state.note_count += 1;
+    }
+}
+
+fn now() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+fn write_line<W: Write>(output: &mut W, line: &impl Serialize) {
+    let result = serde_json::to_writer(&mut *output, line)
+        .map_err(std::io::Error::from)
+        .and_then(|()| writeln!(output))
+        .and_then(|()| output.flush());
+    if let Err(e) = result {
+        error!("could not write the status: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi::midi_messages::MidiNoteOn;
+    use serde_json::Value;
+
+    fn estimate(bpm: f32) -> BpmEstimate {
+        BpmEstimate { bpm, confidence: 1.0 }
+    }
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    fn note(json_status: &JsonStatus<Output>, milliseconds: i64) {
+        json_status.receive_note(&TimedMidiNoteOn {
+            timestamp: Duration::milliseconds(milliseconds),
+            midi_message: MidiNoteOn { channel: 0, note: 36, velocity: 100 },
+        });
+    }
+
+    #[test]
+    fn test_shape() {
+        let output = Output::default();
+        let mut json_status = JsonStatus::new(output.clone(), 0.5, 4);
+        json_status.set_device("Drums");
+        json_status.receive_daw_bpm(120.0);
+        // a note on every beat at 120 BPM
+        for beat in 0..8 {
+            note(&json_status, beat * 500);
+        }
+        json_status.receive_bpm_histogram_data(&[], estimate(120.0));
+        json_status.finish();
+
+        let lines = output.lines();
+        assert_eq!(lines.len(), 2);
+        let Value::Object(status) = &lines[0] else { panic!("{}", lines[0]) };
+        let mut fields = status.keys().collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, ["bpm", "confidence", "daw_bpm", "device", "notes_per_sec", "ts"]);
+        assert!(chrono::DateTime::parse_from_rfc3339(status["ts"].as_str().unwrap()).is_ok());
+        assert_eq!(status["bpm"], 120.0);
+        assert_eq!(status["confidence"], 1.0);
+        assert_eq!(status["daw_bpm"], 120.0);
+        assert_eq!(status["device"], "Drums");
+        assert_eq!(status["notes_per_sec"], 2.0);
+
+        let summary = &lines[1]["summary"];
+        assert_eq!(summary["device"], "Drums");
+        assert_eq!(summary["notes"], 8);
+        assert_eq!(summary["estimates"], 1);
+        assert_eq!(summary["lines"], 1);
+        assert_eq!(summary["last_bpm"], 120.0);
+
+        // no DAW tempo yet
+        let output = Output::default();
+        let mut json_status = JsonStatus::new(output.clone(), 0.5, 4);
+        json_status.receive_bpm_histogram_data(&[], estimate(90.0));
+        assert_eq!(output.lines()[0]["daw_bpm"], Value::Null);
+        assert_eq!(output.lines()[0]["confidence"], 0.0);
+    }
+
+    #[test]
+    fn test_change_filter() {
+        let output = Output::default();
+        let mut json_status = JsonStatus::new(output.clone(), 0.5, 4);
+        for bpm in [120.0, 120.2, 119.7, 120.6, 120.4, 118.0, 118.0] {
+            json_status.receive_bpm_histogram_data(&[], estimate(bpm));
+        }
+        let printed = output.lines().iter().map(|line| line["bpm"].as_f64().unwrap() as f32).collect::<Vec<_>>();
+        // each estimate is compared with the last printed one, drifting slowly doesn't go unnoticed
+        assert_eq!(printed, [120.0, 120.6, 118.0]);
+
+        json_status.finish();
+        let summary = &output.lines()[3]["summary"];
+        assert_eq!(summary["estimates"], 7);
+        assert_eq!(summary["lines"], 3);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    mem,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::Rect,
+    style::{Color, Style},
+    widgets::Paragraph,
+};
+
+use crate::{
+    action::Action,
+    config::{key_sequence_to_string, KeyBindings},
+    mode::Mode,
+    tui::Frame,
+};
+
+/// Bindings in scope by their keys, each node is a prefix of at least one binding
+#[derive(Debug, Default)]
+struct KeyTrie {
+    action: Option<Action>,
+    children: HashMap<KeyEvent, KeyTrie>,
+}
+
+impl KeyTrie {
+    // the bindings of the mode are looked up first, a global binding only applies to the sequences they leave
+    fn new(keybindings: &KeyBindings, mode: Mode) -> Self {
+        let mut trie = Self::default();
+        for bindings in [keybindings.get(&Some(mode)), keybindings.get(&None)].into_iter().flatten() {
+            for (sequence, action) in bindings.iter().filter(|(_, action)| **action != Action::Unbound) {
+                let node = sequence.iter().fold(&mut trie, |node, key| node.children.entry(*key).or_default());
+                node.action.get_or_insert_with(|| action.clone());
+            }
+        }
+        trie
+    }
+
+    fn get(&self, sequence: &[KeyEvent]) -> Option<&KeyTrie> {
+        sequence.iter().try_fold(self, |node, key| node.children.get(key))
+    }
+}
+
+/// Keys typed so far towards a binding. A binding fires as soon as no longer one starts with it, otherwise it waits
+/// for the next key or for `timeout`, whichever comes first
+#[derive(Debug)]
+pub struct KeySequenceMatcher {
+    timeout: Duration,
+    pending: Vec<KeyEvent>,
+    // when the pending keys expire, `None` without pending keys
+    deadline: Option<Instant>,
+}
+
+impl KeySequenceMatcher {
+    #[must_use]
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, pending: Vec::new(), deadline: None }
+    }
+
+    #[must_use]
+    pub fn pending(&self) -> &[KeyEvent] {
+        &self.pending
+    }
+
+    #[must_use]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Actions triggered by `key` pressed at `now` in `mode`
+    pub fn key(&mut self, key: KeyEvent, keybindings: &KeyBindings, mode: Mode, now: Instant) -> Vec<Action> {
+        let trie = KeyTrie::new(keybindings, mode);
+        let mut actions = Vec::new();
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            actions.extend(self.take_pending(&trie));
+        }
+        self.pending.push(key);
+        loop {
+            match trie.get(&self.pending) {
+                Some(node) if node.children.is_empty() => {
+                    actions.extend(node.action.clone());
+                    self.clear();
+                    return actions;
+                }
+                Some(_) => {
+                    self.deadline = Some(now + self.timeout);
+                    return actions;
+                }
+                // the key was typed on its own
+                None if self.pending.len() == 1 => {
+                    self.clear();
+                    return actions;
+                }
+                // the key doesn't continue the pending ones, they fire if they are a binding and the key starts over
+                None => {
+                    self.pending.pop();
+                    actions.extend(self.take_pending(&trie));
+                    self.pending.push(key);
+                }
+            }
+        }
+    }
+
+    /// Action of the pending keys once they went `timeout` without another key, they are dropped if they are only the
+    /// start of longer bindings
+    pub fn expire(&mut self, keybindings: &KeyBindings, mode: Mode, now: Instant) -> Option<Action> {
+        if !self.deadline.is_some_and(|deadline| now >= deadline) {
+            return None;
+        }
+        self.take_pending(&KeyTrie::new(keybindings, mode))
+    }
+
+    fn take_pending(&mut self, trie: &KeyTrie) -> Option<Action> {
+        let pending = mem::take(&mut self.pending);
+        self.deadline = None;
+        trie.get(&pending).and_then(|node| node.action.clone())
+    }
+
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.deadline = None;
+    }
+}
+
+/// Pending keys in the bottom right corner of `area`, until they form a binding or expire
+pub fn draw_pending(f: &mut Frame<'_>, area: Rect, pending: &[KeyEvent]) {
+    if pending.is_empty() || area.height == 0 {
+        return;
+    }
+    let text = format!(" {}… ", key_sequence_to_string(pending));
+    let width = u16::try_from(text.chars().count()).unwrap_or(u16::MAX).min(area.width.saturating_sub(2));
+    let status = Rect::new(area.right().saturating_sub(width + 1), area.bottom() - 1, width, 1);
+    f.render_widget(Paragraph::new(text).style(Style::default().fg(Color::Yellow)), status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_key_sequence;
+
+    const TIMEOUT: Duration = Duration::from_millis(800);
+
+    fn keybindings(global: &[(&str, Action)], device_view: &[(&str, Action)]) -> KeyBindings {
+        let scope = |bindings: &[(&str, Action)]| {
+            bindings.iter().map(|(sequence, action)| (parse_key_sequence(sequence).unwrap(), action.clone())).collect()
+        };
+        KeyBindings([(None, scope(global)), (Some(Mode::DeviceView), scope(device_view))].into_iter().collect())
+    }
+
+    fn key(raw: &str) -> KeyEvent {
+        parse_key_sequence(raw).unwrap()[0]
+    }
+
+    #[test]
+    fn test_exact_and_longer() {
+        let keybindings = keybindings(
+            &[("<g>", Action::Help), ("<g><d>", Action::Switch(Mode::DeviceView)), ("<q>", Action::Quit)],
+            &[],
+        );
+        let start = Instant::now();
+        let mut matcher = KeySequenceMatcher::new(TIMEOUT);
+
+        // without a longer binding, fires right away
+        assert_eq!(matcher.key(key("<q>"), &keybindings, Mode::Home, start), [Action::Quit]);
+        assert!(matcher.pending().is_empty() && matcher.deadline().is_none());
+
+        // <g> waits for a longer binding
+        assert!(matcher.key(key("<g>"), &keybindings, Mode::Home, start).is_empty());
+        assert_eq!(matcher.pending(), [key("<g>")]);
+        assert_eq!(matcher.deadline(), Some(start + TIMEOUT));
+        assert_eq!(
+            matcher.key(key("<d>"), &keybindings, Mode::Home, start + TIMEOUT / 2),
+            [Action::Switch(Mode::DeviceView)]
+        );
+
+        // a key that doesn't continue the sequence fires the pending binding, then its own
+        matcher.key(key("<g>"), &keybindings, Mode::Home, start);
+        assert_eq!(matcher.key(key("<q>"), &keybindings, Mode::Home, start), [Action::Help, Action::Quit]);
+        matcher.key(key("<g>"), &keybindings, Mode::Home, start);
+        assert_eq!(matcher.key(key("<z>"), &keybindings, Mode::Home, start), [Action::Help]);
+        assert!(matcher.pending().is_empty());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let keybindings = keybindings(
+            &[("<g>", Action::Help), ("<g><d>", Action::Switch(Mode::DeviceView)), ("<z><z>", Action::Suspend)],
+            &[],
+        );
+        let start = Instant::now();
+        let mut matcher = KeySequenceMatcher::new(TIMEOUT);
+
+        matcher.key(key("<g>"), &keybindings, Mode::Home, start);
+        assert_eq!(matcher.expire(&keybindings, Mode::Home, start + TIMEOUT / 2), None);
+        assert_eq!(matcher.pending(), [key("<g>")]);
+        assert_eq!(matcher.expire(&keybindings, Mode::Home, start + TIMEOUT), Some(Action::Help));
+        assert!(matcher.pending().is_empty());
+
+        // a prefix only is dropped
+        matcher.key(key("<z>"), &keybindings, Mode::Home, start);
+        assert_eq!(matcher.expire(&keybindings, Mode::Home, start + TIMEOUT), None);
+        assert!(matcher.pending().is_empty() && matcher.deadline().is_none());
+
+        // a key coming after the timeout without the expiry handled doesn't continue the sequence
+        matcher.key(key("<g>"), &keybindings, Mode::Home, start);
+        assert_eq!(matcher.key(key("<d>"), &keybindings, Mode::Home, start + TIMEOUT * 2), [Action::Help]);
+    }
+
+    #[test]
+    fn test_prefix_conflicts() {
+        let keybindings = keybindings(
+            &[("<g><d>", Action::Switch(Mode::DeviceView)), ("<r>", Action::Refresh)],
+            &[("<g>", Action::Up), ("<r>", Action::MIDIRestart), ("<q>", Action::Unbound)],
+        );
+        let start = Instant::now();
+        let mut matcher = KeySequenceMatcher::new(TIMEOUT);
+
+        // the bindings of the mode win over the global ones
+        assert_eq!(matcher.key(key("<r>"), &keybindings, Mode::DeviceView, start), [Action::MIDIRestart]);
+        assert_eq!(matcher.key(key("<r>"), &keybindings, Mode::Home, start), [Action::Refresh]);
+        assert!(matcher.key(key("<q>"), &keybindings, Mode::DeviceView, start).is_empty());
+
+        // a binding of the mode that starts a global one waits for it
+        assert!(matcher.key(key("<g>"), &keybindings, Mode::DeviceView, start).is_empty());
+        assert_eq!(matcher.expire(&keybindings, Mode::DeviceView, start + TIMEOUT), Some(Action::Up));
+        matcher.key(key("<g>"), &keybindings, Mode::DeviceView, start);
+        assert_eq!(matcher.key(key("<d>"), &keybindings, Mode::DeviceView, start), [Action::Switch(Mode::DeviceView)]);
+    }
+}
@@ -4,14 +4,25 @@
 #![allow(clippy::module_name_repetitions)]
 
 pub mod action;
+pub mod analyze;
 pub mod app;
+pub mod channel_tempo;
 pub mod cli;
 pub mod components;
 pub mod config;
+pub mod config_broadcaster;
+pub mod config_saver;
+pub mod config_warnings;
+pub mod gui_only;
+pub mod headless;
+pub mod json_status;
+pub mod key_sequence;
 pub mod layout;
 pub mod lifecycle;
 pub mod live_parameters;
 pub mod mode;
+pub mod safe_mode;
 pub mod services;
+pub mod tempo_recorder;
 pub mod tui;
 pub mod utils;
@@ -1,13 +1,25 @@
-use crate::{action::Action, config::Config};
+use crate::{
+    action::Action,
+    config::Config,
+    config_broadcaster::ConfigBroadcaster,
+    config_saver::ConfigSaver,
+    gui_only::{DeviceChoice, SharedDeviceChoice},
+};
 use errors::{LogErrorWithExt, Report, Result};
-use gui::{BPMDetectionParameters, GUIConfig};
-use midi::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
-use std::sync::atomic::Ordering;
+use gui::{BPMDetectionParameters, ConfigPaths, GUIConfig, MidiInputs, UiState};
+use midi::{memory, DynamicBPMDetectionParameters, Feel, LatencySummary, StaticBPMDetectionParameters, TempoSource};
+use std::{sync::atomic::Ordering, time::Duration};
+use sync::ArcRwLockExt;
 use tokio::sync::mpsc::UnboundedSender;
 
 pub struct LiveParameters {
     pub action_tx: UnboundedSender<Action>,
     pub config: Config,
+    pub config_saver: ConfigSaver,
+    // the parameters changed by the GUI are sent through it, coalesced while a slider is dragged
+    pub config_broadcaster: ConfigBroadcaster,
+    // set when the GUI runs without the TUI, the MIDI input is then picked in the GUI
+    pub device_choice: Option<SharedDeviceChoice>,
 }
 
 impl BPMDetectionParameters for LiveParameters {
@@ -45,19 +57,119 @@ impl BPMDetectionParameters for LiveParameters {
         self.config.midi.send_tempo.store(enabled, Ordering::Relaxed);
     }
 
+    fn get_tempo_source(&self) -> Option<TempoSource> {
+        Some(self.config.midi.tempo_source.load(Ordering::Relaxed))
+    }
+
+    fn set_tempo_source(&mut self, tempo_source: TempoSource) {
+        self.config.midi.tempo_source.store(tempo_source, Ordering::Relaxed);
+    }
+
     fn apply_static(&mut self) -> Result<()> {
-        Ok(self
-            .action_tx
-            .send(Action::StaticBPMDetectionConfig(self.config.static_bpm_detection_parameters.clone()))?)
+        if self.config.low_memory {
+            memory::limit_static_parameters(&mut self.config.static_bpm_detection_parameters);
+        }
+        self.config_broadcaster.static_changed(self.config.static_bpm_detection_parameters.clone())
     }
 
     fn apply_dynamic(&mut self) -> Result<()> {
-        Ok(self
-            .action_tx
-            .send(Action::DynamicBPMDetectionConfig(self.config.dynamic_bpm_detection_parameters.clone()))?)
+        self.config_broadcaster.dynamic_changed(self.config.dynamic_bpm_detection_parameters.clone())
+    }
+
+    fn get_rate_limited_notes(&self) -> u64 {
+        self.config.midi.rate_limited_notes.get()
+    }
+
+    fn get_tempo_latency(&self) -> Option<LatencySummary> {
+        self.config.midi.tempo_latency.summary()
+    }
+
+    fn get_clock_lookahead(&self) -> Option<Duration> {
+        let clock_lookahead = &self.config.midi.clock_lookahead;
+        (!clock_lookahead.lookahead().is_zero() && self.config.midi.enable_midi_clock.load(Ordering::Relaxed))
+            .then(|| clock_lookahead.applied())
+    }
+
+    fn can_tap_tempo(&self) -> bool {
+        true
+    }
+
+    fn tap_tempo(&mut self) {
+        self.action_tx.send(Action::TapTempo).log_error_msg("could not tap the tempo").ok();
+    }
+
+    fn can_choose_feel(&self) -> bool {
+        true
+    }
+
+    fn choose_feel(&mut self, feel: Feel) {
+        self.action_tx.send(Action::ChooseFeel(feel)).log_error_msg("could not choose the feel").ok();
+    }
+
+    fn can_freeze_notes(&self) -> bool {
+        true
+    }
+
+    fn set_notes_frozen(&mut self, frozen: bool) {
+        let action = if frozen { Action::FreezeNotes } else { Action::ClearFrozenNotes };
+        self.action_tx.send(action).log_error_msg("could not freeze the notes").ok();
+    }
+
+    fn get_midi_inputs(&self) -> Option<MidiInputs> {
+        self.device_choice.as_ref().map(|device_choice| device_choice.get(DeviceChoice::midi_inputs))
+    }
+
+    fn select_midi_input(&mut self, id: &str) {
+        let Some(device) = self
+            .device_choice
+            .as_ref()
+            .and_then(|device_choice| device_choice.get_mut(|device_choice| device_choice.select(id)))
+        else {
+            return;
+        };
+        self.action_tx.send(Action::SelectDevice(device)).log_error_msg("could not select the MIDI input").ok();
+    }
+
+    fn midi_input_step(&self) -> &'static str {
+        if self.device_choice.is_some() {
+            "Pick a MIDI input in the settings"
+        } else {
+            "Select a MIDI input in the device screen of the TUI"
+        }
+    }
+
+    // reloaded from the file, already applied to the detection by the TUI
+    fn pull_changes(&mut self) {
+        let reloaded = self.config.reloaded_parameters.take();
+        if let Some(static_bpm_detection_parameters) = reloaded.static_bpm_detection_parameters {
+            self.config.static_bpm_detection_parameters = static_bpm_detection_parameters;
+        }
+        if let Some(dynamic_bpm_detection_parameters) = reloaded.dynamic_bpm_detection_parameters {
+            self.config.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters;
+        }
+    }
+
+    fn get_safe_mode_notice(&self) -> Option<String> {
+        self.config.safe_mode_notice.clone()
+    }
+
+    fn effective_config(&self) -> Option<String> {
+        Some(self.config.provenance.render(&self.config))
     }
 
     fn save(&mut self) {
-        self.config.save().log_error_msg("Could not save configuration").ok();
+        self.config_saver.save(&self.config).log_error_msg("Could not save configuration").ok();
+    }
+
+    fn load_ui_state(&self) -> Option<UiState> {
+        UiState::load_file()
+    }
+
+    fn save_ui_state(&mut self, ui_state: &UiState) {
+        ui_state.save_file().log_error_msg("Could not save the UI state").ok();
+    }
+
+    fn config_paths(&self) -> Option<ConfigPaths> {
+        Some(ConfigPaths::new(vec![Config::config_path()]))
     }
 }
@@ -6,4 +6,7 @@ pub enum Mode {
     #[default]
     Home,
     DeviceView,
+    Keybindings,
+    Presets,
+    Histogram,
 }
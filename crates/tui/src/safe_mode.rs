@@ -0,0 +1,95 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    terminal,
+};
+
+pub const SAFE_MODE_FLAG: &str = "--safe-mode";
+// holding that key while starting also starts in safe mode
+const SAFE_MODE_KEY: char = 's';
+// the keys typed while starting are read for that long
+const KEY_WINDOW: Duration = Duration::from_millis(250);
+
+/// Whether the configuration file should be left out, because of `--safe-mode` or the safe mode key being held
+#[must_use]
+pub fn requested() -> bool {
+    env::args().any(|arg| arg == SAFE_MODE_FLAG) || key_held()
+}
+
+fn key_held() -> bool {
+    if terminal::enable_raw_mode().is_err() {
+        return false;
+    }
+    let deadline = Instant::now() + KEY_WINDOW;
+    let mut held = false;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match event::poll(remaining) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read() {
+                    held = key.code == KeyCode::Char(SAFE_MODE_KEY);
+                    if held {
+                        break;
+                    }
+                }
+            }
+            _ => break,
+        }
+    }
+    terminal::disable_raw_mode().ok();
+    held
+}
+
+/// Renames `config_path` to `<name>.disabled-<timestamp>` next to it, so it is not loaded anymore but can be restored.
+/// Returns where it was moved, none if there is no such file
+pub fn set_aside(config_path: &Path, now: DateTime<Local>) -> io::Result<Option<PathBuf>> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let file_name = config_path.file_name().map_or_else(|| "config".into(), |file_name| file_name.to_string_lossy());
+    let disabled_name = format!("{file_name}.disabled-{}", now.format("%Y%m%d-%H%M%S"));
+    let mut disabled_path = config_path.with_file_name(&disabled_name);
+    let mut attempt = 1;
+    while disabled_path.exists() {
+        disabled_path = config_path.with_file_name(format!("{disabled_name}-{attempt}"));
+        attempt += 1;
+    }
+    fs::rename(config_path, &disabled_path)?;
+    Ok(Some(disabled_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_set_aside() {
+        let directory = env::temp_dir().join(format!("safe_mode_test_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let config_path = directory.join("config.toml");
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 12, 30, 5).unwrap();
+
+        assert_eq!(set_aside(&config_path, now).unwrap(), None);
+
+        fs::write(&config_path, "frame_rate = 0.0").unwrap();
+        let disabled_path = set_aside(&config_path, now).unwrap().unwrap();
+        assert_eq!(disabled_path, directory.join("config.toml.disabled-20240301-123005"));
+        assert!(!config_path.exists());
+        assert_eq!(fs::read_to_string(&disabled_path).unwrap(), "frame_rate = 0.0");
+
+        // set aside again within the same second, the first copy is kept
+        fs::write(&config_path, "tick_rate = 0.0").unwrap();
+        let disabled_path = set_aside(&config_path, now).unwrap().unwrap();
+        assert_eq!(disabled_path, directory.join("config.toml.disabled-20240301-123005-1"));
+        assert_eq!(fs::read_to_string(&disabled_path).unwrap(), "tick_rate = 0.0");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
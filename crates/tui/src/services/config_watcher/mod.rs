@@ -0,0 +1,324 @@
+//! Reloads the configuration file when it is modified while the application runs, so that it can be edited without a
+//! restart. The file is polled on every tick. Only the detection parameters are applied, as if they were changed in the
+//! TUI, the key bindings and the styles are left as they were loaded. A file that can't be read keeps the configuration
+//! in use, the error is shown. The saves of the application itself are skipped
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use errors::Result;
+use log::{error, info};
+use midi::{DynamicBPMDetectionParameters, StaticBPMDetectionParameters};
+use sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    action::Action,
+    config::Config,
+    config_saver::LastWritten,
+    services::Service,
+    utils::dispatch::{ActionHandler, EventHandler},
+};
+
+/// Parameters reloaded from the file that the GUI didn't pick up yet, see `BPMDetectionParameters::pull_changes`
+#[derive(Debug, Default)]
+pub struct ReloadedParameters {
+    pub static_bpm_detection_parameters: Option<StaticBPMDetectionParameters>,
+    pub dynamic_bpm_detection_parameters: Option<DynamicBPMDetectionParameters>,
+}
+
+/// Shared by all clones of the configuration, the GUI holds one
+#[derive(Clone, Debug, Default)]
+pub struct SharedReloadedParameters(Arc<Mutex<ReloadedParameters>>);
+
+impl SharedReloadedParameters {
+    /// Replaces the parameters not picked up yet
+    pub fn publish(&self, reloaded: ReloadedParameters) {
+        let mut pending = self.0.lock();
+        if reloaded.static_bpm_detection_parameters.is_some() {
+            pending.static_bpm_detection_parameters = reloaded.static_bpm_detection_parameters;
+        }
+        if reloaded.dynamic_bpm_detection_parameters.is_some() {
+            pending.dynamic_bpm_detection_parameters = reloaded.dynamic_bpm_detection_parameters;
+        }
+    }
+
+    #[must_use]
+    pub fn take(&self) -> ReloadedParameters {
+        std::mem::take(&mut *self.0.lock())
+    }
+}
+
+pub struct ConfigWatcher {
+    path: PathBuf,
+    // of the file as last read, none while it doesn't exist
+    modified: Option<SystemTime>,
+    last_written: LastWritten,
+    // in use, the reloaded ones are only applied when they differ
+    static_bpm_detection_parameters: StaticBPMDetectionParameters,
+    dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    reloaded_parameters: SharedReloadedParameters,
+    action_tx: UnboundedSender<Action>,
+}
+
+impl ConfigWatcher {
+    #[must_use]
+    pub fn box_new(config: &Config, last_written: LastWritten, action_tx: UnboundedSender<Action>) -> Box<dyn Service> {
+        Box::new(Self::new(Config::config_path(), config, last_written, action_tx))
+    }
+
+    fn new(path: PathBuf, config: &Config, last_written: LastWritten, action_tx: UnboundedSender<Action>) -> Self {
+        Self {
+            modified: modified(&path),
+            last_written,
+            path,
+            static_bpm_detection_parameters: config.static_bpm_detection_parameters.clone(),
+            dynamic_bpm_detection_parameters: config.dynamic_bpm_detection_parameters.clone(),
+            reloaded_parameters: config.reloaded_parameters.clone(),
+            action_tx,
+        }
+    }
+
+    /// Actions applying the parameters that changed in the file since it was last read, none if it wasn't modified
+    fn reload_if_modified(&mut self) -> Vec<Action> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return Vec::new();
+        }
+        self.modified = modified;
+        // removed, or moved aside by the safe mode, what was loaded stays in use
+        let Some(modified) = modified else {
+            return Vec::new();
+        };
+        if self.last_written.is(&self.path, modified) {
+            return Vec::new();
+        }
+        let config = match Config::from_path(&self.path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("could not reload {}: {e}", self.path.display());
+                return vec![Action::Error(format!(
+                    "Could not reload the configuration, keeping the previous one: {e}"
+                ))];
+            }
+        };
+
+        let mut reloaded = ReloadedParameters::default();
+        if config.static_bpm_detection_parameters != self.static_bpm_detection_parameters {
+            reloaded.static_bpm_detection_parameters = Some(config.static_bpm_detection_parameters);
+        }
+        if config.dynamic_bpm_detection_parameters != self.dynamic_bpm_detection_parameters {
+            reloaded.dynamic_bpm_detection_parameters = Some(config.dynamic_bpm_detection_parameters);
+        }
+        let mut actions = Vec::new();
+        if let Some(static_bpm_detection_parameters) = &reloaded.static_bpm_detection_parameters {
+            self.static_bpm_detection_parameters = static_bpm_detection_parameters.clone();
+            actions.push(Action::StaticBPMDetectionConfig(static_bpm_detection_parameters.clone()));
+        }
+        if let Some(dynamic_bpm_detection_parameters) = &reloaded.dynamic_bpm_detection_parameters {
+            self.dynamic_bpm_detection_parameters = dynamic_bpm_detection_parameters.clone();
+            actions.push(Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters.clone()));
+        }
+        if actions.is_empty() {
+            return actions;
+        }
+        info!("detection parameters reloaded from {}", self.path.display());
+        self.reloaded_parameters.publish(reloaded);
+        // for the GUI to pick them up
+        actions.push(Action::Refresh);
+        actions
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl ActionHandler for ConfigWatcher {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        match action {
+            Action::Tick => {
+                for action in self.reload_if_modified() {
+                    self.action_tx.send(action)?;
+                }
+            }
+            // changed from the GUI, the TUI or a preset, the file is compared against them
+            Action::StaticBPMDetectionConfig(static_bpm_detection_parameters) => {
+                self.static_bpm_detection_parameters.clone_from(static_bpm_detection_parameters);
+            }
+            Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters) => {
+                self.dynamic_bpm_detection_parameters.clone_from(dynamic_bpm_detection_parameters);
+            }
+            Action::Render
+            | Action::Resize(_, _)
+            | Action::Suspend
+            | Action::Quit
+            | Action::Refresh
+            | Action::Error(_)
+            | Action::Switch(_)
+            | Action::NextScreen
+            | Action::PrevScreen
+            | Action::Help
+            | Action::Down
+            | Action::Up
+            | Action::MIDIRestart
+            | Action::SelectDevice(_)
+            | Action::TogglePlayback
+            | Action::ToggleMidiClock
+            | Action::ShowGUI
+            | Action::Save
+            | Action::ToggleSendTempo
+            | Action::ToggleOscOutput
+            | Action::CycleTempoSource
+            | Action::ExportSnapshot
+            | Action::ExportTempoMap
+            | Action::ExportEffectiveConfig
+            | Action::ToggleAlwaysOnTop
+            | Action::RebindKey
+            | Action::UnbindKey
+            | Action::RestoreDefaultKeys
+            | Action::Unbound
+            | Action::CaptureKeys(_)
+            | Action::StartDemoPattern(_)
+            | Action::StopDemoPattern
+            | Action::ResetBeatCounter
+            | Action::TapTempo
+            | Action::ChooseFeel(_)
+            | Action::FreezeNotes
+            | Action::ClearFrozenNotes
+            | Action::ElevateLogging
+            | Action::SavePreset(_)
+            | Action::LoadPreset(_)
+            | Action::NamePreset
+            | Action::Select => (),
+        }
+        Ok(None)
+    }
+}
+
+impl EventHandler for ConfigWatcher {}
+
+impl Service for ConfigWatcher {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, time::Duration};
+    use tokio::sync::mpsc;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("config_watcher_{name}_{}", std::process::id()));
+        fs::remove_dir_all(&directory).ok();
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // with a distinct modification time, the file system may not tell apart writes made in a row
+    fn write(path: &Path, contents: &str, seconds: u64) {
+        fs::write(path, contents).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reload() {
+        let directory = directory("reload");
+        let path = directory.join("config.toml");
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let config = Config::base_config().unwrap();
+        let mut config_watcher = ConfigWatcher::new(path.clone(), &config, LastWritten::default(), action_tx);
+        assert!(config_watcher.reload_if_modified().is_empty());
+
+        write(&path, "[static_bpm_detection_parameters]\nbpm_center = 93.0\n", 1);
+        let actions = config_watcher.reload_if_modified();
+        let [Action::StaticBPMDetectionConfig(static_bpm_detection_parameters), Action::Refresh] = actions.as_slice()
+        else {
+            panic!("{actions:?}");
+        };
+        assert_eq!(static_bpm_detection_parameters.bpm_center.to_bits(), 93.0f32.to_bits());
+        let reloaded = config.reloaded_parameters.take();
+        assert_eq!(reloaded.static_bpm_detection_parameters.as_ref(), Some(static_bpm_detection_parameters));
+        assert_eq!(reloaded.dynamic_bpm_detection_parameters, None);
+        // not modified since
+        assert!(config_watcher.reload_if_modified().is_empty());
+
+        // saved by the application with the parameters in use
+        write(&path, "[static_bpm_detection_parameters]\nbpm_center = 93.0\n", 2);
+        assert!(config_watcher.reload_if_modified().is_empty());
+        assert!(config.reloaded_parameters.take().static_bpm_detection_parameters.is_none());
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn test_own_save() {
+        let directory = directory("own_save");
+        let path = directory.join("config.toml");
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let config = Config::base_config().unwrap();
+        let last_written = LastWritten::default();
+        let mut config_watcher = ConfigWatcher::new(path.clone(), &config, last_written.clone(), action_tx);
+
+        // saved by the application before the parameters it holds reach the watcher
+        write(&path, "[static_bpm_detection_parameters]\nbpm_center = 93.0\n", 1);
+        last_written.record(path.clone());
+        assert!(config_watcher.reload_if_modified().is_empty());
+
+        // edited afterwards
+        write(&path, "[static_bpm_detection_parameters]\nbpm_center = 94.0\n", 2);
+        assert_eq!(config_watcher.reload_if_modified().len(), 2);
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn test_malformed_file() {
+        let directory = directory("malformed");
+        let path = directory.join("config.toml");
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let config = Config::base_config().unwrap();
+        let mut config_watcher = ConfigWatcher::new(path.clone(), &config, LastWritten::default(), action_tx);
+
+        write(&path, "[static_bpm_detection_parameters\nbpm_center = 93.0\n", 1);
+        let actions = config_watcher.reload_if_modified();
+        assert!(matches!(actions.as_slice(), [Action::Error(message)] if message.contains("keeping the previous")));
+        assert_eq!(config_watcher.static_bpm_detection_parameters, config.static_bpm_detection_parameters);
+
+        // once fixed
+        write(&path, "[dynamic_bpm_detection_parameters]\nbeats_lookback = 5\n", 2);
+        let actions = config_watcher.reload_if_modified();
+        let [Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters), Action::Refresh] = actions.as_slice()
+        else {
+            panic!("{actions:?}");
+        };
+        assert_eq!(dynamic_bpm_detection_parameters.beats_lookback, 5);
+        fs::remove_dir_all(directory).ok();
+    }
+
+    #[test]
+    fn test_changed_while_running() {
+        let directory = directory("changed");
+        let path = directory.join("config.toml");
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let config = Config::base_config().unwrap();
+        let mut config_watcher = ConfigWatcher::new(path.clone(), &config, LastWritten::default(), action_tx);
+
+        // set from the GUI before the file is edited to the same value
+        let static_bpm_detection_parameters =
+            StaticBPMDetectionParameters { bpm_center: 93.0, ..config.static_bpm_detection_parameters.clone() };
+        config_watcher.handle_action(&Action::StaticBPMDetectionConfig(static_bpm_detection_parameters)).unwrap();
+        write(&path, "[static_bpm_detection_parameters]\nbpm_center = 93.0\n", 1);
+        assert!(config_watcher.reload_if_modified().is_empty());
+
+        // removed, the configuration in use is kept
+        fs::remove_file(&path).unwrap();
+        assert!(config_watcher.reload_if_modified().is_empty());
+        fs::remove_dir_all(directory).ok();
+    }
+}
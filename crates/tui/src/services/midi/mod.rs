@@ -7,9 +7,9 @@ use crate::{
     tui::Event,
     utils::dispatch::{ActionHandler, EventHandler},
 };
-use errors::{Report, Result};
+use errors::{Report, Result, TypedResult};
 use midi::{
-    midi_in::MidiIn, restart, DynamicBPMDetectionParameters, MidiInputConnection, MidiServiceConfig,
+    midi_in::MidiIn, restart, CoreError, DynamicBPMDetectionParameters, MidiInputConnection, MidiServiceConfig,
     StaticBPMDetectionParameters, SysExCommand, TimedMidiMessage,
 };
 
@@ -37,9 +37,12 @@ impl<B> MidiService<B>
 where
     B: BPMDetectionReceiver,
 {
-    fn execute<R, F>(&mut self, command: F) -> Result<R>
+    fn execute<R, F>(&mut self, command: F) -> TypedResult<R, CoreError>
     where
-        F: FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) -> Result<R> + Send + Sync + 'static,
+        F: FnOnce(&MidiIn<B>, &mut Option<MidiInputConnection<()>>) -> TypedResult<R, CoreError>
+            + Send
+            + Sync
+            + 'static,
         R: Send + Sync + 'static,
     {
         let midi_service = self.midi_service.clone();
@@ -96,19 +99,24 @@ where
 {
     fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
         match action {
-            Action::DynamicBPMDetectionConfig(bpm_detection_parameters_live) => {
+            // the worker already has the parameters it was last sent, a round trip to it is skipped
+            Action::DynamicBPMDetectionConfig(bpm_detection_parameters_live)
+                if *bpm_detection_parameters_live != self.dynamic_bpm_detection_parameters =>
+            {
                 let bpm_detection_parameters_live = bpm_detection_parameters_live.clone();
                 self.dynamic_bpm_detection_parameters = bpm_detection_parameters_live.clone();
                 self.midi_service.read().execute(move |midi_in, _| {
-                    Ok(midi_in.change_bpm_detection_parameters_live(bpm_detection_parameters_live)?)
+                    midi_in.change_bpm_detection_parameters_live(bpm_detection_parameters_live)
                 })?;
             }
-            Action::StaticBPMDetectionConfig(bpm_detection_parameters) => {
+            Action::StaticBPMDetectionConfig(bpm_detection_parameters)
+                if *bpm_detection_parameters != self.bpm_detection_parameters =>
+            {
                 let bpm_detection_parameters = bpm_detection_parameters.clone();
                 self.bpm_detection_parameters = bpm_detection_parameters.clone();
-                self.midi_service.read().execute(move |midi_in, _| {
-                    Ok(midi_in.change_bpm_detection_parameters(bpm_detection_parameters)?)
-                })?;
+                self.midi_service
+                    .read()
+                    .execute(move |midi_in, _| midi_in.change_bpm_detection_parameters(bpm_detection_parameters))?;
             }
             Action::MIDIRestart => {
                 if let Err(e) = restart() {
@@ -120,29 +128,27 @@ where
                 let event_tx = self.event_tx.clone();
                 let midi_input_port = midi_input_port.clone();
 
-                self.execute(move |midi_in, midi_input_connection| {
-                    match midi_in.listen(&midi_input_port, move |midi_message| {
+                let result = self.execute(move |midi_in, midi_input_connection| {
+                    *midi_input_connection = midi_in.listen(&midi_input_port, move |midi_message| {
                         if let Err(send_error) = event_tx.send(Event::Midi(midi_message)) {
                             error!("error while dispatching midi notes: {:?}", send_error);
                         }
-                    }) {
-                        Ok(input_connection) => {
-                            *midi_input_connection = input_connection;
-                        }
-                        Err(err) => {
-                            error!("error while selecting device : {err:?}");
-                        }
-                    };
+                    })?;
                     Ok(())
-                })?;
+                });
+                if let Err(err) = result {
+                    if matches!(err.inner(), CoreError::ServiceClosed) {
+                        return Err(err.into());
+                    }
+                    error!("error while selecting device : {err:?}");
+                    return Ok(Some(Action::Error(user_message(err.inner()))));
+                }
             }
             Action::TogglePlayback => {
                 self.playing = !self.playing;
                 let playing = self.playing;
 
-                self.execute(move |midi_in, _| {
-                    (if playing { midi_in.play() } else { midi_in.stop() }).map_err(Report::new)
-                })?;
+                self.execute(move |midi_in, _| if playing { midi_in.play() } else { midi_in.stop() })?;
             }
             Action::ToggleMidiClock => {
                 self.midi_service_config.enable_midi_clock.fetch_xor(true, Ordering::Relaxed);
@@ -150,6 +156,45 @@ where
             Action::ToggleSendTempo => {
                 self.midi_service_config.send_tempo.fetch_xor(true, Ordering::Relaxed);
             }
+            Action::ToggleOscOutput => {
+                let osc_output = &self.midi_service_config.osc_output;
+                let enabled = !osc_output.enabled.fetch_xor(true, Ordering::Relaxed);
+                info!("OSC output to {}:{} {}", osc_output.host, osc_output.port, if enabled { "on" } else { "off" });
+            }
+            Action::CycleTempoSource => {
+                let tempo_source = self.midi_service_config.tempo_source.load(Ordering::Relaxed).next();
+                info!("sending the {tempo_source} tempo");
+                self.midi_service_config.tempo_source.store(tempo_source, Ordering::Relaxed);
+            }
+            Action::StartDemoPattern(pattern_kind) => {
+                info!("playing the {pattern_kind} demo pattern");
+                let pattern_kind = *pattern_kind;
+                let demo_pattern_config = self.midi_service_config.demo_pattern.clone();
+                self.execute(move |midi_in, _| midi_in.start_demo(pattern_kind, &demo_pattern_config))?;
+            }
+            Action::StopDemoPattern => {
+                self.execute(|midi_in, _| {
+                    midi_in.stop_demo();
+                    Ok(())
+                })?;
+            }
+            Action::ResetBeatCounter => {
+                self.execute(|midi_in, _| midi_in.reset_beat_counter())?;
+            }
+            Action::TapTempo => {
+                self.execute(|midi_in, _| midi_in.tap())?;
+            }
+            Action::ChooseFeel(feel) => {
+                let feel = *feel;
+                self.execute(move |midi_in, _| midi_in.choose_feel(feel))?;
+            }
+            Action::FreezeNotes => {
+                info!("freezing the notes as a reference");
+                self.execute(|midi_in, _| midi_in.freeze_notes())?;
+            }
+            Action::ClearFrozenNotes => {
+                self.execute(|midi_in, _| midi_in.clear_frozen_notes())?;
+            }
             Action::Tick
             | Action::Render
             | Action::Resize(_, _)
@@ -164,6 +209,22 @@ where
             | Action::PrevScreen
             | Action::NextScreen
             | Action::Save
+            | Action::ExportSnapshot
+            | Action::ExportTempoMap
+            | Action::ExportEffectiveConfig
+            | Action::ElevateLogging
+            | Action::ToggleAlwaysOnTop
+            | Action::RebindKey
+            | Action::UnbindKey
+            | Action::RestoreDefaultKeys
+            | Action::Unbound
+            | Action::CaptureKeys(_)
+            | Action::SavePreset(_)
+            | Action::LoadPreset(_)
+            | Action::NamePreset
+            | Action::Select
+            | Action::DynamicBPMDetectionConfig(_)
+            | Action::StaticBPMDetectionConfig(_)
             | Action::Switch(_) => (),
         }
         Ok(None)
@@ -178,19 +239,39 @@ where
         if let Event::Midi(TimedMidiMessage { midi_message, .. }) = &event {
             match SysExCommand::try_from(midi_message) {
                 Ok(SysExCommand::Stop) => self.playing = false,
-                Ok(SysExCommand::Play) => self.playing = true,
+                Ok(SysExCommand::Play | SysExCommand::Continue) => self.playing = true,
                 _ => (),
             };
             return Ok(None);
         }
         if event == &Event::DeviceChangeDetected {
             let event_tx = self.event_tx.clone();
-            self.execute(move |midi_in, _| {
-                event_tx.send(Event::DeviceList(midi_in.get_ports()?)).map_err(Report::new)
-            })?;
+            let device_list = self.execute(move |midi_in, _| midi_in.get_ports())?;
+            event_tx.send(Event::DeviceList(device_list)).map_err(Report::new)?;
         }
         self.default_handle_event(event)
     }
 }
 
 impl<B> Service for MidiService<B> where B: BPMDetectionReceiver {}
+
+/// What went wrong with the MIDI service, and what the user can do about it
+fn user_message(error: &CoreError) -> String {
+    match error {
+        CoreError::PortNotFound(port) => {
+            format!("{port} is not available anymore, reconnect it or pick another device")
+        }
+        CoreError::ConnectionFailed { port, .. } => {
+            format!("could not listen to {port}, it may be used exclusively by another application")
+        }
+        CoreError::BackendNotCompiled { requested, hint } => {
+            format!("the {requested} MIDI backend is not available, {hint}")
+        }
+        CoreError::BackendUnavailable(_) | CoreError::ServiceStart(_) => {
+            "the MIDI system is not available, try restarting MIDI or the application".to_string()
+        }
+        CoreError::WorkerStopped | CoreError::ServiceClosed => {
+            "the MIDI service stopped, please restart the application".to_string()
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use crate::utils::dispatch::{ActionHandler, EventHandler};
 
+pub mod config_watcher;
 pub mod crossterm;
 pub mod midi;
+pub mod remote;
 pub mod screens;
 
 pub trait Service: ActionHandler + EventHandler + Send + Sync {}
@@ -0,0 +1,145 @@
+use crate::{
+    action::Action,
+    services::Service,
+    utils::dispatch::{ActionHandler, EventHandler},
+};
+use errors::Result;
+use log::{error, info};
+use midi::{
+    bpm_detection_receiver::BPMDetectionReceiver, BpmEstimate, DynamicBPMDetectionParameters, RemoteControlClient,
+    RemoteMessage, StaticBPMDetectionParameters,
+};
+use std::sync::Arc;
+use sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+// parameters as last received from the plugin
+#[derive(Default)]
+struct ReceivedParameters {
+    static_bpm_detection_parameters: Option<StaticBPMDetectionParameters>,
+    dynamic_bpm_detection_parameters: Option<DynamicBPMDetectionParameters>,
+}
+
+/// Stands in for the MIDI service with `--connect`: the estimates of a plugin instance go to the receiver, as the
+/// ones of the local detection would, and the parameters changed in the TUI are sent to the plugin. The parameters of
+/// the plugin are dispatched as if they were changed in the TUI, without being sent back
+pub struct RemoteService {
+    client: RemoteControlClient,
+    received: Arc<Mutex<ReceivedParameters>>,
+}
+
+impl RemoteService {
+    pub async fn box_new<B>(
+        address: &str,
+        action_tx: UnboundedSender<Action>,
+        mut bpm_detection_receiver: B,
+    ) -> Result<Box<dyn Service>>
+    where
+        B: BPMDetectionReceiver,
+    {
+        let received = Arc::new(Mutex::new(ReceivedParameters::default()));
+        let on_message = {
+            let received = received.clone();
+            move |message: RemoteMessage| match message {
+                RemoteMessage::Estimate { bpm, confidence, histogram_data_points } => {
+                    bpm_detection_receiver
+                        .receive_bpm_histogram_data(&histogram_data_points, BpmEstimate { bpm, confidence });
+                }
+                RemoteMessage::StaticParameters(static_bpm_detection_parameters) => {
+                    received.lock().static_bpm_detection_parameters = Some(static_bpm_detection_parameters.clone());
+                    action_tx.send(Action::StaticBPMDetectionConfig(static_bpm_detection_parameters)).ok();
+                }
+                RemoteMessage::DynamicParameters(dynamic_bpm_detection_parameters) => {
+                    received.lock().dynamic_bpm_detection_parameters = Some(dynamic_bpm_detection_parameters.clone());
+                    action_tx.send(Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters)).ok();
+                }
+            }
+        };
+        let address = address.to_string();
+        let client = tokio::task::spawn_blocking(move || RemoteControlClient::connect(address, on_message)).await??;
+        Ok(Box::new(Self { client, received }))
+    }
+
+    fn send(&mut self, message: &RemoteMessage) -> Option<Action> {
+        if let Err(e) = self.client.send(message) {
+            error!("error while sending to the plugin {e:?}");
+            return Some(Action::Error(
+                "the connection to the plugin is lost, please restart the application".to_string(),
+            ));
+        }
+        None
+    }
+}
+
+impl ActionHandler for RemoteService {
+    fn handle_action(&mut self, action: &Action) -> Result<Option<Action>> {
+        match action {
+            Action::StaticBPMDetectionConfig(static_bpm_detection_parameters) => {
+                let echoed = self.received.lock().static_bpm_detection_parameters.as_ref()
+                    == Some(static_bpm_detection_parameters);
+                if echoed {
+                    return Ok(None);
+                }
+                info!("sending the static parameters to the plugin");
+                Ok(self.send(&RemoteMessage::StaticParameters(static_bpm_detection_parameters.clone())))
+            }
+            Action::DynamicBPMDetectionConfig(dynamic_bpm_detection_parameters) => {
+                let echoed = self.received.lock().dynamic_bpm_detection_parameters.as_ref()
+                    == Some(dynamic_bpm_detection_parameters);
+                if echoed {
+                    return Ok(None);
+                }
+                info!("sending the dynamic parameters to the plugin");
+                Ok(self.send(&RemoteMessage::DynamicParameters(dynamic_bpm_detection_parameters.clone())))
+            }
+            // the plugin plays, listens and sends on its own
+            Action::MIDIRestart
+            | Action::SelectDevice(_)
+            | Action::TogglePlayback
+            | Action::ToggleMidiClock
+            | Action::ToggleSendTempo
+            | Action::ToggleOscOutput
+            | Action::CycleTempoSource
+            | Action::StartDemoPattern(_)
+            | Action::StopDemoPattern
+            | Action::ResetBeatCounter
+            | Action::TapTempo
+            | Action::ChooseFeel(_)
+            | Action::FreezeNotes
+            | Action::ClearFrozenNotes
+            | Action::Tick
+            | Action::Render
+            | Action::Resize(_, _)
+            | Action::Suspend
+            | Action::Quit
+            | Action::Refresh
+            | Action::Error(_)
+            | Action::Down
+            | Action::Up
+            | Action::Help
+            | Action::ShowGUI
+            | Action::PrevScreen
+            | Action::NextScreen
+            | Action::Save
+            | Action::ExportSnapshot
+            | Action::ExportTempoMap
+            | Action::ExportEffectiveConfig
+            | Action::ElevateLogging
+            | Action::ToggleAlwaysOnTop
+            | Action::RebindKey
+            | Action::UnbindKey
+            | Action::RestoreDefaultKeys
+            | Action::Unbound
+            | Action::CaptureKeys(_)
+            | Action::SavePreset(_)
+            | Action::LoadPreset(_)
+            | Action::NamePreset
+            | Action::Select
+            | Action::Switch(_) => Ok(None),
+        }
+    }
+}
+
+impl EventHandler for RemoteService {}
+
+impl Service for RemoteService {}
@@ -35,8 +35,31 @@ impl ActionHandler for Screens {
             | Action::TogglePlayback
             | Action::ToggleMidiClock
             | Action::ToggleSendTempo
+            | Action::ToggleOscOutput
+            | Action::CycleTempoSource
             | Action::ShowGUI
             | Action::Save
+            | Action::ExportSnapshot
+            | Action::ExportTempoMap
+            | Action::ExportEffectiveConfig
+            | Action::ElevateLogging
+            | Action::ToggleAlwaysOnTop
+            | Action::RebindKey
+            | Action::UnbindKey
+            | Action::RestoreDefaultKeys
+            | Action::Unbound
+            | Action::CaptureKeys(_)
+            | Action::StartDemoPattern(_)
+            | Action::StopDemoPattern
+            | Action::ResetBeatCounter
+            | Action::TapTempo
+            | Action::ChooseFeel(_)
+            | Action::FreezeNotes
+            | Action::ClearFrozenNotes
+            | Action::SavePreset(_)
+            | Action::LoadPreset(_)
+            | Action::NamePreset
+            | Action::Select
             | Action::DynamicBPMDetectionConfig(_)
             | Action::StaticBPMDetectionConfig(_)
             | Action::SelectDevice(_) => Ok(None),
@@ -49,13 +72,27 @@ impl ActionHandler for Screens {
                 Ok(Some(Action::Switch(Mode::iter().nth(n).unwrap())))
             }
             Action::PrevScreen => {
-                if self.current_mode == 0 {
-                    Mode::COUNT - 1
-                } else {
-                    (self.current_mode - 1) % Mode::COUNT
-                };
-                Ok(Some(Action::Switch(Mode::iter().nth(self.current_mode).unwrap())))
+                let n = (self.current_mode + Mode::COUNT - 1) % Mode::COUNT;
+                Ok(Some(Action::Switch(Mode::iter().nth(n).unwrap())))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prev_and_next_screen() {
+        let mut screens = Screens::default();
+        let modes = Mode::iter().collect::<Vec<_>>();
+        for (index, mode) in modes.iter().enumerate() {
+            screens.handle_action(&Action::Switch(*mode)).unwrap();
+            let prev = modes[(index + modes.len() - 1) % modes.len()];
+            let next = modes[(index + 1) % modes.len()];
+            assert_eq!(screens.handle_action(&Action::PrevScreen).unwrap(), Some(Action::Switch(prev)), "{mode}");
+            assert_eq!(screens.handle_action(&Action::NextScreen).unwrap(), Some(Action::Switch(next)), "{mode}");
+        }
+    }
+}
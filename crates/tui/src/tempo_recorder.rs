@@ -0,0 +1,145 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use build::get_data_dir;
+use errors::{Report, Result};
+use instant::Instant;
+use log::info;
+use midi::{
+    bpm_detection_receiver::BPMDetectionReceiver, BarPosition, BeatPhase, BpmEstimate, ConfigWarning, FeelAmbiguity,
+    MultiResolutionHistogram, ResolutionFloor, SessionSummary, StaticBPMDetectionParameters, TempoBand, TempoCurve,
+    TempoMapConfig, TimedMidiNoteOn,
+};
+use sync::Mutex;
+
+/// Records the estimated tempo of the session while forwarding estimates to the GUI, so it can be exported as a
+/// tempo map
+#[derive(Clone)]
+pub struct TempoRecorder<B: BPMDetectionReceiver> {
+    bpm_detection_receiver: B,
+    start: Instant,
+    ppqn: u16,
+    tempo_curve: Arc<Mutex<TempoCurve>>,
+    // printed when leaving
+    session_summary: Arc<Mutex<SessionSummary>>,
+}
+
+impl<B: BPMDetectionReceiver> TempoRecorder<B> {
+    pub fn new(bpm_detection_receiver: B, tempo_map_config: &TempoMapConfig) -> Self {
+        Self {
+            bpm_detection_receiver,
+            start: Instant::now(),
+            ppqn: tempo_map_config.ppqn,
+            tempo_curve: Arc::new(Mutex::new(TempoCurve::new(tempo_map_config))),
+            session_summary: Arc::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn session_summary(&self) -> SessionSummary {
+        *self.session_summary.lock()
+    }
+
+    /// Reads the summary without waiting for an estimate being recorded, for the crash report
+    pub fn session_summary_source(&self) -> impl Fn() -> Option<SessionSummary> + Send + Sync + 'static {
+        let session_summary = self.session_summary.clone();
+        move || session_summary.try_lock().map(|session_summary| *session_summary)
+    }
+
+    /// Writes a standard MIDI file in the data directory and returns its path
+    pub fn export(&self) -> Result<PathBuf> {
+        let smf = {
+            let tempo_curve = self.tempo_curve.lock();
+            if tempo_curve.is_empty() {
+                return Err(Report::msg("no tempo was detected yet"));
+            }
+            tempo_curve.to_smf(self.ppqn)
+        };
+
+        let directory = get_data_dir().join("tempo_maps");
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("tempo_map_{}.mid", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        fs::write(&path, smf)?;
+        info!("tempo map exported to {}", path.display());
+        Ok(path)
+    }
+}
+
+impl<B: BPMDetectionReceiver> BPMDetectionReceiver for TempoRecorder<B> {
+    fn receive_bpm_histogram_data(&mut self, histogram_data_points: &[f32], estimate: BpmEstimate) {
+        self.tempo_curve.lock().push(self.start.elapsed(), estimate.bpm);
+        self.bpm_detection_receiver.receive_bpm_histogram_data(histogram_data_points, estimate);
+    }
+
+    fn receive_daw_bpm(&self, bpm: f32) {
+        self.bpm_detection_receiver.receive_daw_bpm(bpm);
+    }
+
+    fn receive_clock_bpm(&self, bpm: Option<f32>) {
+        self.bpm_detection_receiver.receive_clock_bpm(bpm);
+    }
+
+    fn receive_note(&self, timed_midi_note_on: &TimedMidiNoteOn) {
+        self.bpm_detection_receiver.receive_note(timed_midi_note_on);
+    }
+
+    fn receive_config_warnings(&self, config_warnings: &[ConfigWarning]) {
+        self.bpm_detection_receiver.receive_config_warnings(config_warnings);
+    }
+
+    fn receive_velocity_gate(&self, threshold: Option<u8>) {
+        self.bpm_detection_receiver.receive_velocity_gate(threshold);
+    }
+
+    fn receive_auto_zoom(&self, zoomed: Option<&StaticBPMDetectionParameters>) {
+        self.bpm_detection_receiver.receive_auto_zoom(zoomed);
+    }
+
+    fn receive_tapped_tempo(&self, bpm: Option<f32>) {
+        self.bpm_detection_receiver.receive_tapped_tempo(bpm);
+    }
+
+    fn receive_frozen_bpm_histogram_data(&self, frozen: Option<(&[f32], f32)>) {
+        self.bpm_detection_receiver.receive_frozen_bpm_histogram_data(frozen);
+    }
+
+    fn receive_beat_phase(&self, beat_phase: Option<BeatPhase>) {
+        self.bpm_detection_receiver.receive_beat_phase(beat_phase);
+    }
+
+    fn receive_bar_position(&self, bar_position: Option<BarPosition>) {
+        self.bpm_detection_receiver.receive_bar_position(bar_position);
+    }
+
+    fn receive_session_summary(&self, session_summary: SessionSummary) {
+        *self.session_summary.lock() = session_summary;
+        self.bpm_detection_receiver.receive_session_summary(session_summary);
+    }
+
+    fn receive_note_freshness(&self, freshness: Option<f32>) {
+        self.bpm_detection_receiver.receive_note_freshness(freshness);
+    }
+
+    fn receive_feel_ambiguity(&self, feel_ambiguity: Option<FeelAmbiguity>) {
+        self.bpm_detection_receiver.receive_feel_ambiguity(feel_ambiguity);
+    }
+
+    fn receive_tempo_band(&self, tempo_band: Option<TempoBand>) {
+        self.bpm_detection_receiver.receive_tempo_band(tempo_band);
+    }
+
+    fn receive_multi_resolution_histogram(&self, multi_resolution_histogram: Option<MultiResolutionHistogram<'_>>) {
+        self.bpm_detection_receiver.receive_multi_resolution_histogram(multi_resolution_histogram);
+    }
+
+    fn phase_split_window(&self) -> Option<f32> {
+        self.bpm_detection_receiver.phase_split_window()
+    }
+
+    fn receive_phase_split_histogram(&self, phase_split: Option<(&[f32], &[f32])>) {
+        self.bpm_detection_receiver.receive_phase_split_histogram(phase_split);
+    }
+
+    fn receive_resolution_floor(&self, resolution_floor: Option<ResolutionFloor>) {
+        self.bpm_detection_receiver.receive_resolution_floor(resolution_floor);
+    }
+}
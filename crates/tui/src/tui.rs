@@ -22,7 +22,7 @@ use ratatui::backend::CrosstermBackend as Backend;
 use midi::midi_messages::TimedMidiMessage;
 
 use instant::Instant;
-use midi::MidiInputPort;
+use midi::{BarPosition, BpmEstimate, ConfigWarning, FeelAmbiguity, MidiInputPort, TempoBand};
 use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
 
@@ -48,6 +48,16 @@ pub enum Event {
     DeviceChangeDetected,
     DeviceList(Vec<MidiInputPort>),
     Midi(TimedMidiMessage),
+    ConfigWarnings(Vec<ConfigWarning>),
+    BarPosition(Option<BarPosition>),
+    TappedTempo(Option<f32>),
+    NoteFreshness(Option<f32>),
+    Estimate(BpmEstimate),
+    FeelAmbiguity(Option<FeelAmbiguity>),
+    TempoBand(Option<TempoBand>),
+    // bins of the histogram and the estimated tempo
+    Histogram(Vec<f32>, f32),
+    DawBpm(f32),
 }
 
 pub struct Tui {
@@ -1,4 +1,10 @@
 use build::{get_config_dir, get_data_dir};
+use errors::Result;
+use gui::{about_info, AboutInfo, ConfigPaths};
+use midi::MidiBackend;
+use serde::Serialize;
+
+use crate::config::Config;
 
 pub mod dispatch;
 
@@ -13,14 +19,34 @@ pub fn version() -> String {
     // let current_exe_path = PathBuf::from(clap::crate_name!()).display().to_string();
     let config_dir_path = get_config_dir().display().to_string();
     let data_dir_path = get_data_dir().display().to_string();
+    let midi_backend = MidiBackend::compiled().map_or_else(|| "none".to_string(), |backend| backend.to_string());
 
     format!(
         "\
 {commit_hash}
 
 Authors: {author}
+MIDI backend: {midi_backend}
 
 Config directory: {config_dir_path}
 Data directory: {data_dir_path}"
     )
 }
+
+#[derive(Serialize)]
+struct VersionInfo {
+    authors: &'static str,
+    // the one this build talks to, none on platforms without MIDI
+    midi_backend: Option<MidiBackend>,
+    #[serde(flatten)]
+    about: AboutInfo,
+}
+
+pub fn version_json() -> Result<String> {
+    let version_info = VersionInfo {
+        authors: clap::crate_authors!(),
+        midi_backend: MidiBackend::compiled(),
+        about: about_info(Some(ConfigPaths::new(vec![Config::config_path()]))),
+    };
+    Ok(serde_json::to_string_pretty(&version_info)?)
+}
@@ -0,0 +1,5 @@
+use build::create_base_config;
+
+fn main() {
+    create_base_config("config/overlay.toml");
+}
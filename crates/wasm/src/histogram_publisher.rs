@@ -0,0 +1,110 @@
+use crate::wasm::sleep;
+use errors::debug;
+use instant::Instant;
+use std::{mem, time::Duration as StdDuration};
+
+// below that, copying the histogram at once takes less than a frame even on slow devices
+const CHUNKED_THRESHOLD: usize = 8192;
+const CHUNK_SIZE: usize = 4096;
+
+/// Copies the histogram out of the detection into a staging buffer, in slices between which the browser gets to
+/// render when the histogram is large, then hands the complete buffer over to the GUI. The GUI keeps consuming whole
+/// snapshots, it never sees a partially copied histogram.
+///
+/// For large histograms, the time spent on the main thread is logged at debug level, visible in the browser console.
+/// Only the copy is sliced, `BPMDetection::compute_bpm` still runs on the main thread as a single task.
+#[derive(Default)]
+pub(crate) struct HistogramPublisher {
+    staging: Vec<f32>,
+}
+
+impl HistogramPublisher {
+    /// `hand_over` receives the complete copy and returns the previously published buffer, reused for the next copy
+    pub(crate) async fn publish(
+        &mut self,
+        histogram_data_points: &[f32],
+        hand_over: impl FnOnce(Vec<f32>) -> Vec<f32>,
+    ) {
+        let chunked = histogram_data_points.len() > CHUNKED_THRESHOLD;
+        let mut main_thread_time = StdDuration::ZERO;
+
+        let mut started_at = Instant::now();
+        self.staging.clear();
+        self.staging.reserve(histogram_data_points.len());
+        for chunk in histogram_data_points.chunks(CHUNK_SIZE) {
+            self.staging.extend_from_slice(chunk);
+            if chunked && self.staging.len() < histogram_data_points.len() {
+                main_thread_time += started_at.elapsed();
+                // a timeout rather than a microtask, so rendering can happen in between
+                sleep(StdDuration::ZERO).await;
+                started_at = Instant::now();
+            }
+        }
+        self.staging = hand_over(mem::take(&mut self.staging));
+        main_thread_time += started_at.elapsed();
+
+        if chunked {
+            debug!("published {} histogram bins in {main_thread_time:?}", histogram_data_points.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join;
+    use std::{cell::RefCell, rc::Rc};
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    async fn test_snapshots_are_never_torn() {
+        const GENERATIONS: u8 = 5;
+        let published = Rc::new(RefCell::new(Vec::<f32>::new()));
+        let done = Rc::new(RefCell::new(false));
+
+        let publish = {
+            let published = published.clone();
+            let done = done.clone();
+            async move {
+                let mut histogram_publisher = HistogramPublisher::default();
+                for generation in 1..=GENERATIONS {
+                    let histogram_data_points = vec![f32::from(generation); CHUNKED_THRESHOLD * 3 + 1];
+                    histogram_publisher.publish(&histogram_data_points, |snapshot| published.replace(snapshot)).await;
+                }
+                *done.borrow_mut() = true;
+            }
+        };
+
+        let read = async {
+            let mut reads = 0;
+            while !*done.borrow() {
+                let snapshot = published.borrow();
+                if let Some(first) = snapshot.first() {
+                    assert_eq!(snapshot.len(), CHUNKED_THRESHOLD * 3 + 1);
+                    assert!(snapshot.iter().all(|value| value.total_cmp(first).is_eq()), "torn snapshot");
+                    reads += 1;
+                }
+                drop(snapshot);
+                sleep(StdDuration::ZERO).await;
+            }
+            reads
+        };
+
+        let ((), reads) = join(publish, read).await;
+        assert!(reads > 0);
+        assert_eq!(published.borrow().first(), Some(&f32::from(GENERATIONS)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_buffers_are_reused() {
+        let mut histogram_publisher = HistogramPublisher::default();
+        let mut published = Vec::new();
+        histogram_publisher.publish(&[1.0; 100], |snapshot| mem::replace(&mut published, snapshot)).await;
+        histogram_publisher.publish(&[2.0; 100], |snapshot| mem::replace(&mut published, snapshot)).await;
+        assert_eq!(published, vec![2.0; 100]);
+        // the first snapshot came back as staging buffer
+        assert!(histogram_publisher.staging.capacity() >= 100);
+    }
+}
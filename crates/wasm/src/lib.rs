@@ -2,32 +2,41 @@
 use derivative::Derivative;
 use errors::{error_backtrace, LogErrorWithExt, Report};
 use futures::channel::mpsc::Sender;
-use gui::{BPMDetectionParameters, GUIConfig};
+use gui::{unknown_keys, BPMDetectionParameters, GUIConfig, MidiInputs, UiState};
 use midi::{
     midi_messages::MidiNoteOn, DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedTypedMidiMessage,
 };
 use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use web_midi::WebMidi;
 
+mod histogram_publisher;
 pub mod wasm;
+mod web_midi;
 
-const CONFIG: &str = include_str!("../config/base_config.toml");
+// config/base_config.toml at the root of the workspace merged with config/overlay.toml, see `build::create_base_config`
+const CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/base_config.toml"));
 
+// every field defaults on its own, the default of the whole configuration is the built-in one, which is deserialized
 #[derive(Clone, Derivative, Serialize, Deserialize)]
 pub struct Config {
-    #[serde(rename = "GUI")]
+    #[serde(rename = "GUI", default)]
     pub gui_config: GUIConfig,
+    #[serde(default)]
     pub dynamic_bpm_detection_parameters: DynamicBPMDetectionParameters,
+    #[serde(default)]
     pub static_bpm_detection_parameters: StaticBPMDetectionParameters,
 }
 
 pub struct LiveConfig {
     config: Config,
     sender: Sender<QueueItem>,
+    web_midi: Rc<WebMidi>,
 }
 
 impl LiveConfig {
-    fn new(sender: Sender<QueueItem>) -> Self {
-        Self { config: Config::default(), sender }
+    fn new(sender: Sender<QueueItem>, web_midi: Rc<WebMidi>) -> Self {
+        Self { config: Config::default(), sender, web_midi }
     }
 }
 
@@ -35,6 +44,8 @@ enum QueueItem {
     StaticParameters(StaticBPMDetectionParameters),
     DynamicParameters(DynamicBPMDetectionParameters),
     Note(TimedTypedMidiMessage<MidiNoteOn>),
+    // the next notes come from another timeline, such as a demo pattern
+    ClearNotes,
     DelayedDynamicUpdate,
     DelayedStaticUpdate,
 }
@@ -72,6 +83,18 @@ impl BPMDetectionParameters for LiveConfig {
 
     fn set_send_tempo(&mut self, _: bool) {}
 
+    fn get_midi_inputs(&self) -> Option<MidiInputs> {
+        self.web_midi.midi_inputs()
+    }
+
+    fn select_midi_input(&mut self, id: &str) {
+        self.web_midi.select(id);
+    }
+
+    fn midi_input_step(&self) -> &'static str {
+        "Allow MIDI access when the browser asks, then pick an input in the settings"
+    }
+
     fn apply_static(&mut self) -> Result<(), Self::Error> {
         self.sender
             .try_send(QueueItem::StaticParameters(self.config.static_bpm_detection_parameters.clone()))
@@ -87,11 +110,22 @@ impl BPMDetectionParameters for LiveConfig {
             .ok();
         Ok(())
     }
+
+    fn load_ui_state(&self) -> Option<UiState> {
+        UiState::load_local_storage()
+    }
+
+    fn save_ui_state(&mut self, ui_state: &UiState) {
+        ui_state.save_local_storage();
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        match Config::deserialize(toml::de::Deserializer::new(CONFIG)) {
+        match unknown_keys::deserialize_warning::<Self, _>(
+            toml::de::Deserializer::new(CONFIG),
+            "the built-in configuration",
+        ) {
             Ok(config) => config,
             Err(err) => {
                 error_backtrace!("{err}");
@@ -136,4 +170,22 @@ value = 1";
         let config = Config::default();
         assert_eq!(config.test, OnOff::Off(1.0));
     }
+
+    #[test]
+    fn test_shared_base_config() {
+        let shared: crate::Config = toml::from_str(include_str!("../../../config/base_config.toml")).unwrap();
+        let (config, unknown_keys): (crate::Config, _) =
+            gui::unknown_keys::deserialize_collecting(toml::de::Deserializer::new(crate::CONFIG)).unwrap();
+        assert!(unknown_keys.is_empty(), "{unknown_keys:?}");
+        assert_eq!(config.gui_config.interpolation_curve.to_bits(), shared.gui_config.interpolation_curve.to_bits());
+        // the web page keeps the detection parameters it had before the configuration was shared
+        let static_parameters = &config.static_bpm_detection_parameters;
+        assert_eq!(static_parameters.bpm_center.to_bits(), shared.static_bpm_detection_parameters.bpm_center.to_bits());
+        assert_eq!(static_parameters.normal_distribution.imprecision.to_bits(), 2000.0f32.to_bits());
+        assert_eq!(static_parameters.normal_distribution.std_dev.to_bits(), 24.0f64.to_bits());
+        let dynamic_parameters = &config.dynamic_bpm_detection_parameters;
+        assert_eq!(dynamic_parameters.beats_lookback, shared.dynamic_bpm_detection_parameters.beats_lookback);
+        assert_eq!(dynamic_parameters.velocity_current_note_weight, OnOff::Off(0.7));
+        assert_eq!(dynamic_parameters.high_tempo_bias, OnOff::On(0.95));
+    }
 }
@@ -5,18 +5,21 @@
 #![allow(clippy::module_name_repetitions)]
 #![allow(clippy::cast_possible_truncation)]
 
-use crate::{LiveConfig, QueueItem};
+use crate::{histogram_publisher::HistogramPublisher, web_midi::WebMidi, LiveConfig, QueueItem};
 use atomic_refcell::AtomicRefCell;
 use chrono::Duration;
 use errors::{LogErrorWithExt, Result};
 use futures::{channel::mpsc::Sender, StreamExt};
-use gui::{create_gui, start_gui, GuiRemote};
+use gui::{create_gui, parameter_catalog, start_gui, GuiRemote};
 use instant::Instant;
 use midi::{
-    bpm_detection_receiver::BPMDetectionReceiver, midi_messages::MidiNoteOn, BPMDetection,
-    DynamicBPMDetectionParameters, StaticBPMDetectionParameters, TimedTypedMidiMessage,
+    bpm_detection_receiver::BPMDetectionReceiver, midi_messages::MidiNoteOn, validate_interaction, AutoZoom,
+    BPMDetection, ChordFilter, DemoPatternConfig, DynamicBPMDetectionParameters, ParameterRamp, PatternGenerator,
+    PatternKind, StaticBPMDetectionParameters, TimedTypedMidiMessage, VelocityGate,
 };
 use std::{
+    cell::Cell,
+    rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -26,7 +29,7 @@ use std::{
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen_futures::{js_sys::Promise, JsFuture};
 
-async fn sleep(duration: StdDuration) {
+pub(crate) async fn sleep(duration: StdDuration) {
     let promise = Promise::new(&mut |yes, _| {
         web_sys::window()
             .unwrap()
@@ -41,6 +44,10 @@ pub struct GuiRemoteWrapper {
     #[allow(dead_code)]
     gui_remote: GuiRemote, // javascript will hold this value, or the GUI will be dropped
     redraw_sender: Sender<QueueItem>,
+    // id of the demo pattern being played, 0 when none is. Notes from javascript stop it
+    running_demo: Rc<Cell<u64>>,
+    demo_count: u64,
+    web_midi: Rc<WebMidi>,
 }
 
 #[wasm_bindgen]
@@ -50,23 +57,85 @@ impl GuiRemoteWrapper {
             timestamp: Duration::milliseconds(timestamp as i64),
             midi_message: MidiNoteOn { channel, note, velocity },
         };
+        note_in(&mut self.redraw_sender, &self.running_demo, note);
+    }
 
-        self.redraw_sender.try_send(QueueItem::Note(note)).log_error_msg("channel full").ok();
+    /// Asks for the permission to use the MIDI devices, then listens to the input picked in the settings panel. Pages
+    /// calling it don't need to forward the MIDI messages with `event_in`
+    pub fn request_web_midi(&self) {
+        wasm_bindgen_futures::spawn_local(self.web_midi.clone().request());
+    }
+
+    /// Plays one of the synthetic patterns, `Quarters`, `SwingEighths`, `Clave` or `Drums`, until `stop_demo` is
+    /// called or a note is received
+    pub fn start_demo(&mut self, pattern: &str, bpm: f32) {
+        let Ok(pattern_kind) = pattern.parse::<PatternKind>().log_error_msg("cannot start demo") else {
+            return;
+        };
+        self.demo_count += 1;
+        let demo_id = self.demo_count;
+        self.running_demo.set(demo_id);
+        self.redraw_sender.try_send(QueueItem::ClearNotes).log_error_msg("channel full").ok();
+
+        let pattern_generator =
+            PatternGenerator::from_config(pattern_kind, &DemoPatternConfig { bpm, ..DemoPatternConfig::default() });
+        wasm_bindgen_futures::spawn_local({
+            let running_demo = self.running_demo.clone();
+            let mut redraw_sender = self.redraw_sender.clone();
+            async move {
+                for (delay, note) in pattern_generator {
+                    sleep(delay.to_std().unwrap_or_default()).await;
+                    if running_demo.get() != demo_id {
+                        break;
+                    }
+                    redraw_sender.try_send(QueueItem::Note(note)).log_error_msg("channel full").ok();
+                }
+            }
+        });
+    }
+
+    pub fn stop_demo(&mut self) {
+        self.running_demo.set(0);
     }
 }
 
+// notes from javascript and from Web MIDI
+pub(crate) fn note_in(
+    redraw_sender: &mut Sender<QueueItem>,
+    running_demo: &Cell<u64>,
+    note: TimedTypedMidiMessage<MidiNoteOn>,
+) {
+    // the demo notes have their own timeline, they must not be mixed with the real ones
+    if running_demo.replace(0) != 0 {
+        redraw_sender.try_send(QueueItem::ClearNotes).log_error_msg("channel full").ok();
+    }
+    redraw_sender.try_send(QueueItem::Note(note)).log_error_msg("channel full").ok();
+}
+
 const REDRAW_THRESHOLD_MILLIS: u64 = 200;
 
+/// Every parameter with its id, range, step and default, as a JSON array, for controller mapping tools
+#[wasm_bindgen]
+#[must_use]
+pub fn parameters_json() -> String {
+    serde_json::to_string(&parameter_catalog()).unwrap()
+}
+
 pub fn run() -> Result<GuiRemoteWrapper> {
     let (redraw_sender, mut redraw_receiver) = futures::channel::mpsc::channel(100);
 
-    let live_config = LiveConfig::new(redraw_sender.clone());
+    let running_demo = Rc::<Cell<u64>>::default();
+    let web_midi = Rc::new(WebMidi::new(redraw_sender.clone(), running_demo.clone()));
+    let live_config = LiveConfig::new(redraw_sender.clone(), web_midi.clone());
     let static_bpm_detection_parameters = live_config.config.static_bpm_detection_parameters.clone();
     let mut dynamic_bpm_detection_parameters = live_config.config.dynamic_bpm_detection_parameters.clone();
+    // the GUI always starts on the page, its buffers are allocated before the detection sends anything
     let (gui_remote, gui_builder) = create_gui(live_config);
+    let gui_builder = gui_builder.eager();
+    web_midi.set_gui_remote(gui_remote.clone());
 
     wasm_bindgen_futures::spawn_local({
-        let mut gui_remote = gui_remote.clone();
+        let gui_remote = gui_remote.clone();
         let update_static: Arc<AtomicRefCell<Option<StaticBPMDetectionParameters>>> =
             Arc::new(AtomicRefCell::default());
         let update_dynamic: Arc<AtomicRefCell<Option<DynamicBPMDetectionParameters>>> =
@@ -75,7 +144,16 @@ pub fn run() -> Result<GuiRemoteWrapper> {
         let redraw_sender = redraw_sender.clone();
 
         async move {
+            let mut auto_zoom = AutoZoom::new(&static_bpm_detection_parameters);
             let mut bpm_detection = BPMDetection::new(static_bpm_detection_parameters);
+            let mut chord_filter = ChordFilter::default();
+            let mut velocity_gate = VelocityGate::default();
+            let mut parameter_ramp = ParameterRamp::new(&dynamic_bpm_detection_parameters);
+            let mut histogram_publisher = HistogramPublisher::default();
+            gui_remote.receive_config_warnings(&validate_interaction(
+                bpm_detection.static_parameters(),
+                &dynamic_bpm_detection_parameters,
+            ));
             'main: while let Some(mut redraw_reason) = redraw_receiver.next().await {
                 let now = Instant::now();
                 loop {
@@ -111,7 +189,17 @@ pub fn run() -> Result<GuiRemoteWrapper> {
                             continue 'main;
                         }
                         QueueItem::Note(note) => {
-                            bpm_detection.receive_midi_message(note);
+                            chord_filter.note_on(
+                                dynamic_bpm_detection_parameters.max_simultaneous_onsets,
+                                note,
+                                Instant::now(),
+                                |onset| {
+                                    let auto_velocity_gate = dynamic_bpm_detection_parameters.auto_velocity_gate;
+                                    if velocity_gate.admit(onset.midi_message.velocity, auto_velocity_gate) {
+                                        bpm_detection.receive_midi_message(onset);
+                                    }
+                                },
+                            );
 
                             if !update_notes.fetch_or(true, Ordering::Relaxed) {
                                 wasm_bindgen_futures::spawn_local({
@@ -124,16 +212,33 @@ pub fn run() -> Result<GuiRemoteWrapper> {
                             }
                             continue 'main;
                         }
+                        QueueItem::ClearNotes => {
+                            chord_filter = ChordFilter::default();
+                            bpm_detection.clear_notes();
+                            if let Some(configured) = auto_zoom.reset() {
+                                bpm_detection.update_static_parameters(configured);
+                            }
+                            continue 'main;
+                        }
 
                         QueueItem::DelayedStaticUpdate => {
                             if let Some(new_static_bpm_detection_parameters) = update_static.borrow_mut().take() {
+                                auto_zoom.set_configured(&new_static_bpm_detection_parameters);
                                 bpm_detection.update_static_parameters(new_static_bpm_detection_parameters);
+                                gui_remote.receive_config_warnings(&validate_interaction(
+                                    auto_zoom.configured(),
+                                    &dynamic_bpm_detection_parameters,
+                                ));
                             }
                         }
                         QueueItem::DelayedDynamicUpdate => {
                             update_notes.store(false, Ordering::Relaxed);
                             if let Some(new_dynamic_bpm_detection_parameters) = update_dynamic.borrow_mut().take() {
                                 dynamic_bpm_detection_parameters = new_dynamic_bpm_detection_parameters;
+                                gui_remote.receive_config_warnings(&validate_interaction(
+                                    auto_zoom.configured(),
+                                    &dynamic_bpm_detection_parameters,
+                                ));
                             }
                         }
                     }
@@ -147,16 +252,44 @@ pub fn run() -> Result<GuiRemoteWrapper> {
                     redraw_reason = next_redraw_reason;
                 }
 
-                let Some((histogram_data, bpm)) = bpm_detection.compute_bpm(&dynamic_bpm_detection_parameters) else {
+                // evaluations are delayed by more than the chord window
+                chord_filter.flush(dynamic_bpm_detection_parameters.max_simultaneous_onsets, Instant::now(), |onset| {
+                    if velocity_gate
+                        .admit(onset.midi_message.velocity, dynamic_bpm_detection_parameters.auto_velocity_gate)
+                    {
+                        bpm_detection.receive_midi_message(onset);
+                    }
+                });
+                gui_remote.receive_velocity_gate(velocity_gate.threshold());
+                let effective_parameters = parameter_ramp.apply(
+                    &dynamic_bpm_detection_parameters,
+                    bpm_detection.newest_note_timestamp().unwrap_or_else(Duration::zero),
+                );
+                let Some((histogram_data, estimate)) = bpm_detection.compute_bpm(effective_parameters) else {
                     continue;
                 };
+                let bpm = estimate.bpm;
 
-                gui_remote.receive_bpm_histogram_data(histogram_data, bpm);
+                histogram_publisher
+                    .publish(histogram_data, |histogram_data| {
+                        gui_remote.receive_auto_zoom(auto_zoom.zoomed());
+                        gui_remote.publish_histogram(histogram_data, estimate)
+                    })
+                    .await;
+
+                if let Some(newest_note) = bpm_detection.newest_note_timestamp() {
+                    let note_density = bpm_detection.note_density(bpm, dynamic_bpm_detection_parameters.beats_lookback);
+                    if let Some(effective) =
+                        auto_zoom.update(dynamic_bpm_detection_parameters.auto_zoom, bpm, note_density, newest_note)
+                    {
+                        bpm_detection.update_static_parameters(effective);
+                    }
+                }
             }
         }
     });
 
     start_gui(gui_builder).unwrap();
 
-    Ok(GuiRemoteWrapper { gui_remote, redraw_sender })
+    Ok(GuiRemoteWrapper { gui_remote, redraw_sender, running_demo, demo_count: 0, web_midi })
 }
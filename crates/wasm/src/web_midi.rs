@@ -0,0 +1,327 @@
+//! Built-in Web MIDI flow: asks for the permission, lists the inputs in the settings panel and feeds the notes of the
+//! selected one to the detection, so that a page doesn't need any javascript of its own to receive MIDI.
+#![allow(forbidden_lint_groups)]
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::{wasm::note_in, QueueItem};
+use chrono::Duration;
+use errors::{error, info};
+use futures::channel::mpsc::Sender;
+use gui::{GuiRemote, MidiInputs};
+use midi::{midi_messages::MidiNoteOn, TimedTypedMidiMessage};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::Rc,
+};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{MidiAccess, MidiInput, MidiMessageEvent};
+
+const NOTE_ON: u8 = 0x90;
+
+/// The note-on of raw MIDI data received at `timestamp`, in milliseconds. A note-on with a zero velocity is a note-off
+pub(crate) fn note_on(data: &[u8], timestamp: f64) -> Option<TimedTypedMidiMessage<MidiNoteOn>> {
+    let [status, note, velocity, ..] = *data else {
+        return None;
+    };
+    if status & 0xF0 != NOTE_ON || velocity == 0 {
+        return None;
+    }
+    Some(TimedTypedMidiMessage {
+        timestamp: Duration::milliseconds(timestamp as i64),
+        midi_message: MidiNoteOn { channel: status & 0x0F, note, velocity },
+    })
+}
+
+/// Where the flow stands, as shown in the settings panel
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum WebMidiState {
+    // the page didn't ask for Web MIDI, the settings panel shows nothing
+    #[default]
+    NotRequested,
+    Requesting,
+    // not supported by the browser, or the permission was denied
+    Unavailable(String),
+    Ready {
+        // id and name of each input
+        inputs: Vec<(String, String)>,
+        selected: Option<String>,
+    },
+}
+
+impl WebMidiState {
+    /// Inputs listed again, once the access is granted or when a device is plugged or unplugged. The selected input
+    /// stays selected while it is there, otherwise the first one is
+    pub(crate) fn set_inputs(&mut self, inputs: Vec<(String, String)>) {
+        let selected = match self {
+            Self::Ready { selected, .. } => {
+                selected.take().filter(|selected| inputs.iter().any(|(id, _)| id == selected))
+            }
+            _ => None,
+        };
+        let selected = selected.or_else(|| inputs.first().map(|(id, _)| id.clone()));
+        *self = Self::Ready { inputs, selected };
+    }
+
+    /// Returns false if there is no such input
+    pub(crate) fn select(&mut self, id: &str) -> bool {
+        let Self::Ready { inputs, selected } = self else {
+            return false;
+        };
+        if !inputs.iter().any(|(input_id, _)| input_id == id) {
+            return false;
+        }
+        *selected = Some(id.to_string());
+        true
+    }
+
+    #[must_use]
+    pub(crate) fn selected(&self) -> Option<&str> {
+        match self {
+            Self::Ready { selected, .. } => selected.as_deref(),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn midi_inputs(&self) -> Option<MidiInputs> {
+        let message = |message: &str| Some(MidiInputs { message: Some(message.to_string()), ..MidiInputs::default() });
+        match self {
+            Self::NotRequested => None,
+            Self::Requesting => message("Waiting for the permission to use MIDI devices"),
+            Self::Unavailable(reason) => message(reason),
+            Self::Ready { inputs, .. } if inputs.is_empty() => message("No MIDI input found, plug a device in"),
+            Self::Ready { inputs, selected } => {
+                Some(MidiInputs { inputs: inputs.clone(), selected: selected.clone(), message: None })
+            }
+        }
+    }
+}
+
+/// Javascript side of the flow. The callbacks given to the browser live as long as they are subscribed
+pub(crate) struct WebMidi {
+    state: RefCell<WebMidiState>,
+    access: RefCell<Option<MidiAccess>>,
+    inputs: RefCell<Vec<MidiInput>>,
+    listening: RefCell<Option<(MidiInput, Closure<dyn FnMut(MidiMessageEvent)>)>>,
+    on_state_change: RefCell<Option<Closure<dyn FnMut()>>>,
+    redraw_sender: Sender<QueueItem>,
+    running_demo: Rc<Cell<u64>>,
+    // set once the GUI is created, the state changes outside of its frames
+    gui_remote: OnceCell<GuiRemote>,
+}
+
+impl WebMidi {
+    pub(crate) fn new(redraw_sender: Sender<QueueItem>, running_demo: Rc<Cell<u64>>) -> Self {
+        Self {
+            state: RefCell::default(),
+            access: RefCell::default(),
+            inputs: RefCell::default(),
+            listening: RefCell::default(),
+            on_state_change: RefCell::default(),
+            redraw_sender,
+            running_demo,
+            gui_remote: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn set_gui_remote(&self, gui_remote: GuiRemote) {
+        self.gui_remote.set(gui_remote).ok();
+    }
+
+    pub(crate) fn midi_inputs(&self) -> Option<MidiInputs> {
+        self.state.borrow().midi_inputs()
+    }
+
+    pub(crate) fn select(&self, id: &str) {
+        if self.state.borrow_mut().select(id) {
+            self.listen();
+        }
+    }
+
+    /// Asks for the permission, then lists the inputs and listens to the first one
+    pub(crate) async fn request(self: Rc<Self>) {
+        self.set_state(WebMidiState::Requesting);
+        let Some(navigator) = web_sys::window().map(|window| window.navigator()) else {
+            return;
+        };
+        if !js_sys::Reflect::has(&navigator, &JsValue::from_str("requestMIDIAccess")).unwrap_or(false) {
+            self.set_state(WebMidiState::Unavailable("Web MIDI is not supported by this browser".to_string()));
+            return;
+        }
+        let access = match navigator.request_midi_access() {
+            Ok(promise) => JsFuture::from(promise).await,
+            Err(err) => Err(err),
+        };
+        let access = match access {
+            Ok(access) => access.unchecked_into::<MidiAccess>(),
+            Err(err) => {
+                info!("Access to MIDI devices not granted: {err:?}");
+                self.set_state(WebMidiState::Unavailable("Access to the MIDI devices was denied".to_string()));
+                return;
+            }
+        };
+
+        let on_state_change = Closure::<dyn FnMut()>::new({
+            let web_midi = Rc::downgrade(&self);
+            move || {
+                if let Some(web_midi) = web_midi.upgrade() {
+                    web_midi.list_inputs();
+                }
+            }
+        });
+        access.set_onstatechange(Some(on_state_change.as_ref().unchecked_ref()));
+        self.on_state_change.replace(Some(on_state_change));
+        self.access.replace(Some(access));
+        self.list_inputs();
+    }
+
+    fn list_inputs(&self) {
+        let Some(access) = self.access.borrow().clone() else {
+            return;
+        };
+        let inputs = match js_sys::try_iter(&access.inputs()) {
+            Ok(Some(entries)) => entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| entry.unchecked_into::<js_sys::Array>().get(1).dyn_into::<MidiInput>().ok())
+                .collect::<Vec<_>>(),
+            Ok(None) | Err(_) => {
+                error!("could not list the MIDI inputs");
+                Vec::new()
+            }
+        };
+        self.state
+            .borrow_mut()
+            .set_inputs(inputs.iter().map(|input| (input.id(), input.name().unwrap_or_else(|| input.id()))).collect());
+        self.inputs.replace(inputs);
+        self.listen();
+    }
+
+    // subscribes to the selected input, if it isn't already
+    fn listen(&self) {
+        let selected = self.state.borrow().selected().map(ToString::to_string);
+        if self.listening.borrow().as_ref().map(|(input, _)| input.id()) == selected {
+            self.request_repaint();
+            return;
+        }
+        if let Some((input, _)) = self.listening.take() {
+            input.set_onmidimessage(None);
+        }
+        let Some(input) = self.inputs.borrow().iter().find(|input| Some(input.id()) == selected).cloned() else {
+            self.request_repaint();
+            return;
+        };
+
+        let on_midi_message = Closure::<dyn FnMut(MidiMessageEvent)>::new({
+            let mut redraw_sender = self.redraw_sender.clone();
+            let running_demo = self.running_demo.clone();
+            move |event: MidiMessageEvent| {
+                let Ok(data) = event.data() else {
+                    return;
+                };
+                // same clock as performance.now(), some browsers leave it at zero
+                let timestamp = match event.time_stamp() {
+                    timestamp if timestamp > 0.0 => timestamp,
+                    _ => web_sys::window()
+                        .and_then(|window| window.performance())
+                        .map_or(0.0, |performance| performance.now()),
+                };
+                if let Some(note) = note_on(&data, timestamp) {
+                    note_in(&mut redraw_sender, &running_demo, note);
+                }
+            }
+        });
+        input.set_onmidimessage(Some(on_midi_message.as_ref().unchecked_ref()));
+        self.listening.replace(Some((input, on_midi_message)));
+        self.request_repaint();
+    }
+
+    fn set_state(&self, state: WebMidiState) {
+        self.state.replace(state);
+        self.request_repaint();
+    }
+
+    fn request_repaint(&self) {
+        if let Some(gui_remote) = self.gui_remote.get() {
+            gui_remote.request_repaint();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn inputs(ids: &[&str]) -> Vec<(String, String)> {
+        ids.iter().map(|id| ((*id).to_string(), format!("{id} name"))).collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_note_on() {
+        let note = note_on(&[0x93, 60, 100], 1500.7).unwrap();
+        let MidiNoteOn { channel, note: pitch, velocity } = note.midi_message;
+        assert_eq!((channel, pitch, velocity), (3, 60, 100));
+        assert_eq!(note.timestamp, Duration::milliseconds(1500));
+
+        // note-off, either way
+        assert!(note_on(&[0x90, 60, 0], 0.0).is_none());
+        assert!(note_on(&[0x80, 60, 100], 0.0).is_none());
+        // control change, clock and truncated data
+        assert!(note_on(&[0xB0, 7, 100], 0.0).is_none());
+        assert!(note_on(&[0xF8], 0.0).is_none());
+        assert!(note_on(&[0x90, 60], 0.0).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_permission_flow() {
+        let mut state = WebMidiState::default();
+        assert_eq!(state.midi_inputs(), None);
+
+        state = WebMidiState::Requesting;
+        assert!(state.midi_inputs().unwrap().message.is_some());
+        assert!(!state.select("a"));
+
+        state = WebMidiState::Unavailable("denied".to_string());
+        assert_eq!(state.midi_inputs().unwrap().message.as_deref(), Some("denied"));
+        assert_eq!(state.selected(), None);
+
+        // granted, without any device
+        state.set_inputs(Vec::new());
+        let midi_inputs = state.midi_inputs().unwrap();
+        assert!(midi_inputs.inputs.is_empty());
+        assert!(midi_inputs.message.is_some());
+        assert_eq!(state.selected(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_device_list() {
+        let mut state = WebMidiState::default();
+        state.set_inputs(inputs(&["a", "b"]));
+        assert_eq!(state.selected(), Some("a"));
+        assert_eq!(
+            state.midi_inputs(),
+            Some(MidiInputs { inputs: inputs(&["a", "b"]), selected: Some("a".to_string()), message: None })
+        );
+
+        assert!(!state.select("c"));
+        assert!(state.select("b"));
+        assert_eq!(state.selected(), Some("b"));
+
+        // a device is plugged, the selection stays
+        state.set_inputs(inputs(&["c", "a", "b"]));
+        assert_eq!(state.selected(), Some("b"));
+
+        // the selected device is unplugged
+        state.set_inputs(inputs(&["c", "a"]));
+        assert_eq!(state.selected(), Some("c"));
+
+        // all are, then one comes back
+        state.set_inputs(Vec::new());
+        assert_eq!(state.selected(), None);
+        state.set_inputs(inputs(&["a"]));
+        assert_eq!(state.selected(), Some("a"));
+    }
+}